@@ -0,0 +1,100 @@
+//! Inserting messages into a 10k-message timeline, in delivery order and out of it.
+
+use chrono::Local;
+use criterion::{criterion_group, criterion_main, BatchSize, Criterion};
+use mls_client_core::bidi::TextDirection;
+use mls_client_core::history::SeenIds;
+use mls_client_core::{Group, GroupMode, Message, Protection, WirePolicy};
+use std::collections::{BTreeMap, HashMap};
+
+const MESSAGES: u64 = 10_000;
+
+fn group() -> Group {
+    Group {
+        id: "bench".to_string(),
+        name: "bench".to_string(),
+        members: vec!["alice".to_string(), "bob".to_string()],
+        messages: Vec::new(),
+        is_active: true,
+        admins: vec!["alice".to_string()],
+        mode: GroupMode::Chat,
+        unread: 0,
+        unread_mentions: 0,
+        rotation_policy: None,
+        epoch_age: None,
+        onboarding: None,
+        onboarding_pinned: false,
+        archived_pages: 0,
+        seen_ids: SeenIds::default(),
+        nicknames: HashMap::new(),
+        tag: None,
+        joined_at: HashMap::new(),
+        wire_policy: WirePolicy::default(),
+        removed: false,
+        deleted: None,
+        last_active: HashMap::new(),
+        quarantine: Vec::new(),
+        redactions: Vec::new(),
+        membership_log: Vec::new(),
+        audit_anchor: None,
+        text_direction: TextDirection::default(),
+    }
+}
+
+fn message(seq: u64) -> Message {
+    Message {
+        id: format!("message-{}", seq),
+        sender: ["alice", "bob"][(seq % 2) as usize].to_string(),
+        content: format!("message number {} in the benchmark timeline", seq),
+        timestamp: Local::now(),
+        group_id: "bench".to_string(),
+        server_seq: Some(seq),
+        received_at: Some(Local::now()),
+        clock_skewed: false,
+        pinned: false,
+        reactions: BTreeMap::new(),
+        shared_by: None,
+        redacted_by: None,
+        translation: None,
+        pending: false,
+        protection: Protection::Sent,
+        attachment: false,
+        unsupported: None,
+    }
+}
+
+fn insert(c: &mut Criterion) {
+    let mut group = c.benchmark_group("history");
+    group.sample_size(20);
+    group.bench_function("insert 10k in order", |b| {
+        b.iter_batched(
+            || (self::group(), (1..=MESSAGES).map(message).collect::<Vec<_>>()),
+            |(mut group, messages)| {
+                for message in messages {
+                    group.insert_message(message);
+                }
+                group
+            },
+            BatchSize::LargeInput,
+        )
+    });
+    // A message sequenced before everything on the timeline, e.g. re-fetched after a reconnect
+    let mut full = self::group();
+    for seq in 1..=MESSAGES {
+        full.insert_message(message(seq * 2));
+    }
+    group.bench_function("insert late into 10k", |b| {
+        b.iter_batched(
+            || (full.clone(), message(1)),
+            |(mut group, message)| {
+                group.insert_message(message);
+                group
+            },
+            BatchSize::LargeInput,
+        )
+    });
+    group.finish();
+}
+
+criterion_group!(benches, insert);
+criterion_main!(benches);
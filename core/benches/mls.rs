@@ -0,0 +1,49 @@
+//! Encrypting an application message for a two-member group, and the other member
+//! decrypting it.
+
+use criterion::{criterion_group, criterion_main, BatchSize, Criterion, Throughput};
+use mls_client_core::crypto::CryptoProvider;
+use mls_client_core::extensions::GroupState;
+use mls_client_core::mls_client::{IncomingMls, MlsClient};
+use mls_client_core::{GroupMode, WirePolicy};
+
+const GROUP_ID: &str = "bench";
+const MESSAGE_BYTES: usize = 256;
+
+/// Alice and Bob in a group Alice created and added Bob to.
+fn members() -> (MlsClient, MlsClient) {
+    let runtime = tokio::runtime::Runtime::new().expect("runtime");
+    let crypto = CryptoProvider::new();
+    let mut alice = runtime.block_on(MlsClient::new("alice", &crypto)).expect("alice");
+    let mut bob = runtime.block_on(MlsClient::new("bob", &crypto)).expect("bob");
+    let state = GroupState::new("alice", GroupMode::Chat);
+    let group = alice.create_group(WirePolicy::Ciphertext, &state).expect("create group");
+    alice.add_group(GROUP_ID, group);
+    let (_commit, welcome) = alice.add_member(GROUP_ID, bob.get_key_package()).expect("add bob");
+    alice.merge_pending_commit(GROUP_ID).expect("merge add");
+    bob.join_from_welcome(GROUP_ID, &welcome, WirePolicy::Ciphertext).expect("bob joins");
+    (alice, bob)
+}
+
+fn pipeline(c: &mut Criterion) {
+    let (mut alice, mut bob) = members();
+    let plaintext = vec![b'x'; MESSAGE_BYTES];
+    let mut group = c.benchmark_group("mls");
+    group.throughput(Throughput::Bytes(MESSAGE_BYTES as u64));
+    group.bench_function("encrypt", |b| b.iter(|| alice.encrypt_message(GROUP_ID, &plaintext).expect("encrypt")));
+    // Each ciphertext decrypts once, so every iteration gets a fresh one
+    group.bench_function("decrypt", |b| {
+        b.iter_batched(
+            || alice.encrypt_message(GROUP_ID, &plaintext).expect("encrypt"),
+            |ciphertext| match bob.process_incoming(GROUP_ID, &ciphertext).expect("decrypt") {
+                IncomingMls::Application { content, .. } => content,
+                _ => panic!("not an application message"),
+            },
+            BatchSize::SmallInput,
+        )
+    });
+    group.finish();
+}
+
+criterion_group!(benches, pipeline);
+criterion_main!(benches);
@@ -0,0 +1,48 @@
+//! Measuring the wrapped heights of a 10k-message timeline: from scratch, from the cache,
+//! and after a resize.
+
+use criterion::{criterion_group, criterion_main, BatchSize, Criterion};
+use mls_client_core::timeline::WrapCache;
+
+const MESSAGES: usize = 10_000;
+
+/// Message IDs and rendered lines of varied length, some long enough to wrap several times.
+fn timeline() -> Vec<(String, String)> {
+    (0..MESSAGES)
+        .map(|index| {
+            let text = format!("[12:{:02}] bob 🔒: message {} {}", index % 60, index, "lorem ipsum dolor sit amet ".repeat(index % 12));
+            (format!("message-{}", index), text)
+        })
+        .collect()
+}
+
+fn layout(c: &mut Criterion) {
+    let owned = timeline();
+    let messages: Vec<(&str, String)> = owned.iter().map(|(id, text)| (id.as_str(), text.clone())).collect();
+    let mut group = c.benchmark_group("timeline");
+    group.bench_function("layout 10k uncached", |b| {
+        b.iter_batched(
+            WrapCache::default,
+            |mut cache| {
+                cache.layout("bench", 80, &messages, 0);
+                cache
+            },
+            BatchSize::SmallInput,
+        )
+    });
+    let mut cache = WrapCache::default();
+    cache.layout("bench", 80, &messages, 0);
+    group.bench_function("layout 10k cached", |b| b.iter(|| cache.layout("bench", 80, &messages, 0)));
+    // Every resize measures everything again and keeps the same message at the top
+    let mut width = 80;
+    group.bench_function("layout 10k after resize", |b| {
+        b.iter(|| {
+            width = if width == 80 { 100 } else { 80 };
+            cache.layout("bench", width, &messages, 5_000)
+        })
+    });
+    group.finish();
+}
+
+criterion_group!(benches, layout);
+criterion_main!(benches);
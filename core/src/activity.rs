@@ -0,0 +1,136 @@
+//! The activity log: every status line the client showed, timestamped and with a severity, so
+//! a result is still there to read after the next command runs.
+
+use chrono::{DateTime, Local};
+use std::collections::VecDeque;
+
+/// Entries kept; older ones are dropped from the front.
+const MAX_ENTRIES: usize = 2000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default)]
+pub enum Severity {
+    #[default]
+    Info,
+    /// Something the user should look at, like a command typed wrong or a refused action
+    Warning,
+    /// An operation failed
+    Error,
+}
+
+impl Severity {
+    pub const ALL: [Severity; 3] = [Severity::Info, Severity::Warning, Severity::Error];
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "info" | "all" => Some(Severity::Info),
+            "warning" | "warnings" => Some(Severity::Warning),
+            "error" | "errors" => Some(Severity::Error),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Severity::Info => "info",
+            Severity::Warning => "warning",
+            Severity::Error => "error",
+        }
+    }
+
+    /// Prefix for plain-text output, where there is no color.
+    pub fn marker(self) -> &'static str {
+        match self {
+            Severity::Info => "*",
+            Severity::Warning => "!",
+            Severity::Error => "!!",
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ActivityEntry {
+    /// When the entry was last repeated
+    pub timestamp: DateTime<Local>,
+    pub severity: Severity,
+    pub text: String,
+    /// How many times in a row the same line was reported
+    pub repeats: u32,
+}
+
+impl ActivityEntry {
+    pub fn describe(&self) -> String {
+        if self.repeats > 1 {
+            format!("{} (×{})", self.text, self.repeats)
+        } else {
+            self.text.clone()
+        }
+    }
+}
+
+/// Append-only within its size limit. The same line reported again in a row bumps the last
+/// entry's count instead of adding another, so a status set on every tick cannot flood it.
+#[derive(Debug, Default)]
+pub struct ActivityLog {
+    entries: VecDeque<ActivityEntry>,
+    /// Entries ever added, including those dropped since
+    appended: u64,
+}
+
+impl ActivityLog {
+    pub fn push(&mut self, severity: Severity, text: impl Into<String>) {
+        let text = text.into();
+        if let Some(last) = self.entries.back_mut().filter(|last| last.severity == severity && last.text == text) {
+            last.repeats += 1;
+            last.timestamp = Local::now();
+            return;
+        }
+        self.entries.push_back(ActivityEntry { timestamp: Local::now(), severity, text, repeats: 1 });
+        self.appended += 1;
+        if self.entries.len() > MAX_ENTRIES {
+            self.entries.pop_front();
+        }
+    }
+
+    pub fn latest(&self) -> Option<&ActivityEntry> {
+        self.entries.back()
+    }
+
+    /// The text of the newest entry, or nothing before the first.
+    pub fn latest_text(&self) -> &str {
+        self.latest().map(|entry| entry.text.as_str()).unwrap_or_default()
+    }
+
+    /// Entries ever added, to be passed to `since` later.
+    pub fn appended(&self) -> u64 {
+        self.appended
+    }
+
+    /// Entries added after `appended` entries had been, oldest first.
+    pub fn since(&self, appended: u64) -> impl Iterator<Item = &ActivityEntry> {
+        let new = usize::try_from(self.appended.saturating_sub(appended)).unwrap_or(usize::MAX);
+        self.entries.iter().skip(self.entries.len().saturating_sub(new))
+    }
+
+    /// The newest `count` entries, newest first.
+    pub fn recent(&self, count: usize) -> impl Iterator<Item = &ActivityEntry> {
+        self.entries.iter().rev().take(count)
+    }
+
+    /// Entries at `severity` or above whose text contains `filter` (case-insensitively),
+    /// newest first.
+    pub fn matching<'a>(&'a self, severity: Severity, filter: &str) -> impl Iterator<Item = &'a ActivityEntry> {
+        let filter = filter.to_lowercase();
+        self.entries
+            .iter()
+            .rev()
+            .filter(move |entry| entry.severity >= severity && (filter.is_empty() || entry.text.to_lowercase().contains(&filter)))
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+}
@@ -0,0 +1,91 @@
+//! Older timeline pages moved out of memory under the memory budget.
+//!
+//! A group's evicted messages are written, oldest first, to page files of up to `PAGE_SIZE`
+//! messages under `ARCHIVE_DIR`, one directory per group; `Group::archived_pages` counts
+//! them. They leave the history file at the same time, and the newest page is read back
+//! (and its file removed) once the timeline is scrolled past the oldest loaded message.
+
+use std::path::{Path, PathBuf};
+use tokio::fs;
+use crate::crypto::to_hex;
+use crate::errors::StorageError;
+use crate::secrets::secure_delete_dir;
+use crate::vault;
+use crate::Message;
+
+pub const ARCHIVE_DIR: &str = "archive";
+
+/// Messages moved to or from disk at a time; groups keep at least this many in memory.
+pub const PAGE_SIZE: usize = 200;
+
+/// Directory holding `group_id`'s pages, named by the ID's bytes in hex so any ID is a
+/// valid file name.
+fn group_dir(archive: &Path, group_id: &str) -> PathBuf {
+    archive.join(to_hex(group_id.as_bytes()))
+}
+
+fn page_path(archive: &Path, group_id: &str, page: usize) -> PathBuf {
+    group_dir(archive, group_id).join(format!("{}.json", page))
+}
+
+pub async fn write_page(archive: &Path, group_id: &str, page: usize, messages: &[Message]) -> Result<(), StorageError> {
+    let dir = vault::locate(group_dir(archive, group_id));
+    fs::create_dir_all(&dir).await.map_err(StorageError::io(dir.display().to_string()))?;
+    let path = page_path(archive, group_id, page);
+    let name = path.display().to_string();
+    let content = serde_json::to_string(messages).map_err(StorageError::invalid(name.clone()))?;
+    vault::write(&path, content).await.map_err(StorageError::io(name))
+}
+
+pub fn page_exists(archive: &Path, group_id: &str, page: usize) -> bool {
+    vault::locate(page_path(archive, group_id, page)).exists()
+}
+
+pub async fn read_page(archive: &Path, group_id: &str, page: usize) -> Result<Vec<Message>, StorageError> {
+    let path = page_path(archive, group_id, page);
+    let name = path.display().to_string();
+    let content = vault::read_to_string(&path).await.map_err(StorageError::io(name.clone()))?;
+    serde_json::from_str(&content).map_err(StorageError::invalid(name))
+}
+
+/// Read page `page` back and remove its file; its messages belong to the history file again.
+pub async fn take_page(archive: &Path, group_id: &str, page: usize) -> Result<Vec<Message>, StorageError> {
+    let messages = read_page(archive, group_id, page).await?;
+    let path = vault::locate(page_path(archive, group_id, page));
+    fs::remove_file(&path).await.map_err(StorageError::io(path.display().to_string()))?;
+    Ok(messages)
+}
+
+/// Bytes taken on disk by `group_id`'s pages.
+pub async fn group_size(archive: &Path, group_id: &str) -> u64 {
+    let Ok(mut entries) = fs::read_dir(vault::locate(group_dir(archive, group_id))).await else {
+        return 0;
+    };
+    let mut size = 0;
+    while let Ok(Some(entry)) = entries.next_entry().await {
+        size += entry.metadata().await.map_or(0, |metadata| metadata.len());
+    }
+    size
+}
+
+/// Securely delete every page of a forgotten group.
+pub async fn remove_group(archive: &Path, group_id: &str) -> Result<(), StorageError> {
+    secure_delete_dir(&vault::locate(group_dir(archive, group_id)).display().to_string()).await
+}
+
+/// Rough heap and inline size of a message, for the memory budget.
+pub fn footprint(message: &Message) -> usize {
+    let optional = |text: &Option<String>| text.as_ref().map_or(0, String::len);
+    std::mem::size_of::<Message>()
+        + message.id.len()
+        + message.sender.len()
+        + message.content.len()
+        + message.group_id.len()
+        + optional(&message.shared_by)
+        + optional(&message.redacted_by)
+        + optional(&message.translation)
+        + message.reactions
+            .iter()
+            .map(|(emoji, members)| emoji.len() + members.iter().map(String::len).sum::<usize>())
+            .sum::<usize>()
+}
@@ -0,0 +1,143 @@
+//! Large payloads kept outside the delivery service.
+//!
+//! With `attachment_storage` set to anything but `ds`, a payload over the chunk threshold is
+//! encrypted under a fresh key and put in the profile's store instead of being sent in
+//! chunks. The group gets one `attachment` message, itself MLS-encrypted, carrying only
+//! where the blob is, its key and its digest. Members fetch it from their own configured
+//! store and only when it is the same one, so a message cannot make a client contact a
+//! server of the sender's choosing.
+
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine as _};
+use serde::{Deserialize, Serialize};
+use tokio::fs;
+use crate::crypto::{to_hex, CryptoProvider};
+use crate::errors::{AttachmentError, StorageError};
+use crate::remote::{Remote, S3Bucket};
+
+/// Bytes of random blob name, so names reveal nothing and cannot be guessed.
+const NAME_BYTES: usize = 16;
+const KEY_BYTES: usize = 32;
+const NONCE_BYTES: usize = 12;
+/// Poly1305 tag appended to the sealed payload.
+const TAG_BYTES: usize = 16;
+
+/// Where a profile puts payloads too large for one message.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AttachmentStorage {
+    /// In chunks through the delivery service
+    #[default]
+    Ds,
+    /// `sftp://[user@]host[:port]/path`
+    Sftp(String),
+    /// A collection URL
+    WebDav(String),
+    S3(S3Bucket),
+}
+
+impl AttachmentStorage {
+    /// The external store, `None` for the delivery service.
+    pub fn remote(&self) -> Option<Remote> {
+        match self {
+            AttachmentStorage::Ds => None,
+            AttachmentStorage::Sftp(target) | AttachmentStorage::WebDav(target) => Remote::parse(target),
+            AttachmentStorage::S3(bucket) => Some(Remote::S3(bucket.clone())),
+        }
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        match (self, self.remote()) {
+            (AttachmentStorage::Ds, _) => Ok(()),
+            (AttachmentStorage::Sftp(_), Some(Remote::Sftp { host, .. })) if !host.is_empty() => Ok(()),
+            (AttachmentStorage::Sftp(_), _) => Err("sftp storage must be an sftp://[user@]host[:port]/path URL".to_string()),
+            (AttachmentStorage::WebDav(_), Some(Remote::WebDav(_))) => Ok(()),
+            (AttachmentStorage::WebDav(_), _) => Err("webdav storage must be an http:// or https:// URL".to_string()),
+            (AttachmentStorage::S3(bucket), _) => {
+                if !(bucket.endpoint.starts_with("https://") || bucket.endpoint.starts_with("http://")) {
+                    return Err("s3 endpoint must be an http:// or https:// URL".to_string());
+                }
+                if bucket.bucket.trim().is_empty() || bucket.bucket.contains('/') {
+                    return Err("s3 bucket must be a bucket name".to_string());
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+/// Body of an `attachment` message.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Pointer {
+    /// `Remote::locator` of the store holding the blob
+    pub store: String,
+    /// Random hex name of the blob
+    pub name: String,
+    /// Content key and nonce, base64
+    pub key: String,
+    pub nonce: String,
+    /// SHA-256 of the payload, hex
+    pub digest: String,
+    /// Payload size in bytes
+    pub size: usize,
+}
+
+impl Pointer {
+    /// Whether the name is one `seal` could have made, and so safe to use as a file name.
+    pub fn is_well_formed(&self) -> bool {
+        self.name.len() == NAME_BYTES * 2 && self.name.bytes().all(|b| b.is_ascii_hexdigit())
+    }
+}
+
+/// Encrypt `payload` under a fresh key for `remote`, returning the pointer to send and the
+/// blob to upload.
+pub fn seal(crypto: &CryptoProvider, remote: &Remote, payload: &[u8]) -> Result<(Pointer, Vec<u8>), AttachmentError> {
+    let random = |len| crypto.random_bytes(len).map_err(|e| AttachmentError::Crypto(e.to_string()));
+    let name = to_hex(&random(NAME_BYTES)?);
+    let key = random(KEY_BYTES)?;
+    let nonce = random(NONCE_BYTES)?;
+    // The name is bound in, so a blob cannot be swapped for another sealed under the same key
+    let sealed = crypto.seal(&key, &nonce, name.as_bytes(), payload).map_err(|e| AttachmentError::Crypto(e.to_string()))?;
+    let digest = crypto.sha256(payload).map_err(|e| AttachmentError::Crypto(e.to_string()))?;
+    let pointer = Pointer {
+        store: remote.locator(),
+        name,
+        key: BASE64.encode(key),
+        nonce: BASE64.encode(nonce),
+        digest: to_hex(&digest),
+        size: payload.len(),
+    };
+    Ok((pointer, sealed))
+}
+
+/// Put a sealed blob in the store, staging it in the temporary directory.
+pub async fn upload(remote: &Remote, name: &str, sealed: &[u8]) -> Result<(), AttachmentError> {
+    let staged = std::env::temp_dir().join(format!("mls-{}", name));
+    fs::write(&staged, sealed).await.map_err(StorageError::io(staged.display().to_string()))?;
+    let uploaded = remote.put(&staged, name).await;
+    let _ = fs::remove_file(&staged).await;
+    Ok(uploaded?)
+}
+
+/// Fetch the sealed blob `pointer` names; pointers larger than `limit` are refused.
+pub async fn download(remote: &Remote, pointer: &Pointer, limit: usize) -> Result<Vec<u8>, AttachmentError> {
+    if pointer.size > limit {
+        return Err(AttachmentError::TooLarge { size: pointer.size, limit });
+    }
+    let sealed = remote.get(&pointer.name, pointer.size + TAG_BYTES).await?;
+    if sealed.len() != pointer.size + TAG_BYTES {
+        return Err(AttachmentError::Mismatch);
+    }
+    Ok(sealed)
+}
+
+/// Decrypt a fetched blob and check it is the payload the pointer describes.
+pub fn open(crypto: &CryptoProvider, pointer: &Pointer, sealed: &[u8]) -> Result<Vec<u8>, AttachmentError> {
+    let key = BASE64.decode(&pointer.key).map_err(|_| AttachmentError::Mismatch)?;
+    let nonce = BASE64.decode(&pointer.nonce).map_err(|_| AttachmentError::Mismatch)?;
+    let payload = crypto.open(&key, &nonce, pointer.name.as_bytes(), sealed).map_err(|_| AttachmentError::Mismatch)?;
+    let digest = crypto.sha256(&payload).map_err(|e| AttachmentError::Crypto(e.to_string()))?;
+    if to_hex(&digest) != pointer.digest {
+        return Err(AttachmentError::Mismatch);
+    }
+    Ok(payload)
+}
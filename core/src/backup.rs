@@ -0,0 +1,273 @@
+//! Scheduled encrypted backups of the data directory.
+//!
+//! A backup bundles every data file (config, history with its archived pages, transfers,
+//! schedule, previews and the additional accounts' files) into one JSON document, encrypts it
+//! to an age or GPG recipient like an `export`, and writes it to a directory or uploads it
+//! over SFTP or WebDAV (see `remote`). Only the newest `keep` archives this client
+//! wrote are kept. MLS state is not persisted yet, so there is no keystore to include.
+
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine as _};
+use chrono::{DateTime, Local};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::{Component, Path, PathBuf};
+use tokio::fs;
+use tokio::process::Command;
+use crate::archive::ARCHIVE_DIR;
+use crate::config::ACCOUNTS_DIR;
+use crate::errors::{BackupError, StorageError};
+use crate::export::{self, ExportEncryption};
+use crate::remote::{self, Remote};
+use crate::search::SEARCH_INDEX_PATH;
+use crate::secrets::local_data_paths;
+use crate::vault;
+
+/// Holds the `BackupState`.
+pub const BACKUP_STATE_PATH: &str = "backup-state.json";
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BackupConfig {
+    /// A directory, `sftp://[user@]host[:port]/path` (relative to the login directory) or
+    /// a WebDAV collection URL
+    pub target: String,
+    /// Who can decrypt the archives
+    pub encryption: ExportEncryption,
+    #[serde(default = "default_interval_hours")]
+    pub interval_hours: u64,
+    /// Archives kept on the target; older ones are deleted
+    #[serde(default = "default_keep")]
+    pub keep: usize,
+}
+
+fn default_interval_hours() -> u64 {
+    24
+}
+
+fn default_keep() -> usize {
+    7
+}
+
+impl BackupConfig {
+    /// Check the target and recipient are usable, for config validation.
+    pub fn validate(&self) -> Result<(), String> {
+        if self.target.trim().is_empty() {
+            return Err("target must not be empty".to_string());
+        }
+        if let Target::Remote(Remote::Sftp { host, .. }) = self.target() {
+            if host.is_empty() {
+                return Err("sftp target must name a host".to_string());
+            }
+        }
+        let recipient = match &self.encryption {
+            ExportEncryption::Age(recipient) | ExportEncryption::Gpg(recipient) => recipient,
+        };
+        if recipient.trim().is_empty() {
+            return Err("encryption recipient must not be empty".to_string());
+        }
+        if self.interval_hours == 0 {
+            return Err("interval_hours must be at least 1".to_string());
+        }
+        if self.keep == 0 {
+            return Err("keep must be at least 1".to_string());
+        }
+        Ok(())
+    }
+
+    fn target(&self) -> Target {
+        match Remote::parse(&self.target) {
+            Some(remote) => Target::Remote(remote),
+            None => Target::Dir(PathBuf::from(self.target.trim())),
+        }
+    }
+
+    fn extension(&self) -> &'static str {
+        match self.encryption {
+            ExportEncryption::Age(_) => "age",
+            ExportEncryption::Gpg(_) => "gpg",
+        }
+    }
+}
+
+enum Target {
+    Dir(PathBuf),
+    Remote(Remote),
+}
+
+/// When the last backup ran and which archives it has left on the target.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct BackupState {
+    /// When the last backup succeeded
+    pub last: Option<DateTime<Local>>,
+    /// Target the archives below are on; a new target starts a new list
+    pub target: String,
+    /// Archive names on `target`, oldest first
+    pub archives: Vec<String>,
+}
+
+impl BackupState {
+    pub fn due(&self, config: &BackupConfig) -> bool {
+        let interval = chrono::Duration::hours(i64::try_from(config.interval_hours).unwrap_or(i64::MAX / 3600));
+        self.last.is_none_or(|last| Local::now() - last >= interval)
+    }
+}
+
+pub async fn load_state(path: &Path) -> Result<BackupState, StorageError> {
+    if !vault::locate(path).exists() {
+        return Ok(BackupState::default());
+    }
+    let name = path.display().to_string();
+    let content = vault::read_to_string(path).await.map_err(StorageError::io(name.clone()))?;
+    serde_json::from_str(&content).map_err(StorageError::invalid(name))
+}
+
+pub async fn save_state(state: &BackupState, path: &Path) -> Result<(), StorageError> {
+    let name = path.display().to_string();
+    let content = serde_json::to_string(state).map_err(StorageError::invalid(name.clone()))?;
+    vault::write(path, content).await.map_err(StorageError::io(name))
+}
+
+/// The data files as they were when the backup started, by path relative to the data
+/// directory with `/` separators, base64 encoded.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Bundle {
+    pub created: DateTime<Local>,
+    pub files: BTreeMap<String, String>,
+}
+
+/// Read every data file into a bundle, leaving out the search indexes, which are rebuilt from
+/// the history on the first search after a restore. With a passphrase set the files are
+/// bundled as sealed, so restoring them needs the same key slots.
+pub async fn collect() -> Result<Bundle, StorageError> {
+    let mut pending: Vec<PathBuf> = local_data_paths()
+        .into_iter()
+        .filter(|path| *path != BACKUP_STATE_PATH && *path != SEARCH_INDEX_PATH)
+        .chain([ARCHIVE_DIR, ACCOUNTS_DIR])
+        .map(PathBuf::from)
+        .collect();
+    let mut files = BTreeMap::new();
+    while let Some(path) = pending.pop() {
+        let located = vault::locate(&path);
+        let name = located.display().to_string();
+        if located.is_dir() {
+            let mut entries = fs::read_dir(&located).await.map_err(StorageError::io(name.clone()))?;
+            while let Some(entry) = entries.next_entry().await.map_err(StorageError::io(name.clone()))? {
+                pending.push(path.join(entry.file_name()));
+            }
+        } else if located.is_file() && !path.ends_with(SEARCH_INDEX_PATH) {
+            let content = fs::read(&located).await.map_err(StorageError::io(name))?;
+            let key: Vec<String> = path.components().map(|part| part.as_os_str().to_string_lossy().into_owned()).collect();
+            files.insert(key.join("/"), BASE64.encode(content));
+        }
+    }
+    Ok(Bundle { created: Local::now(), files })
+}
+
+/// What a backup run left on the target.
+#[derive(Debug)]
+pub struct BackupOutcome {
+    pub name: String,
+    pub state: BackupState,
+    /// Old archives that could not be deleted, with why; they are no longer tracked
+    pub leftovers: Vec<String>,
+}
+
+/// Encrypt `bundle` into a new archive on the configured target, then delete the oldest
+/// archives beyond `keep`.
+pub async fn run(config: &BackupConfig, bundle: &Bundle, mut state: BackupState) -> Result<BackupOutcome, BackupError> {
+    let name = format!("mls-backup-{}.{}", bundle.created.format("%Y%m%d-%H%M%S"), config.extension());
+    let data = serde_json::to_vec(bundle).map_err(StorageError::invalid(name.clone()))?;
+    let target = config.target();
+    match &target {
+        Target::Dir(dir) => {
+            fs::create_dir_all(dir).await.map_err(StorageError::io(dir.display().to_string()))?;
+            export::encrypt_to(&dir.join(&name), &data, &config.encryption).await?;
+        }
+        Target::Remote(remote) => {
+            let staged = std::env::temp_dir().join(&name);
+            export::encrypt_to(&staged, &data, &config.encryption).await?;
+            let uploaded = remote.put(&staged, &name).await;
+            let _ = fs::remove_file(&staged).await;
+            uploaded?;
+        }
+    }
+
+    if state.target != config.target {
+        state.target = config.target.clone();
+        state.archives.clear();
+    }
+    state.archives.push(name.clone());
+    state.last = Some(bundle.created);
+    let mut leftovers = Vec::new();
+    while state.archives.len() > config.keep {
+        let old = state.archives.remove(0);
+        if let Err(e) = delete(&target, &old).await {
+            leftovers.push(format!("{} ({})", old, e));
+        }
+    }
+    Ok(BackupOutcome { name, state, leftovers })
+}
+
+async fn delete(target: &Target, name: &str) -> Result<(), BackupError> {
+    match target {
+        Target::Dir(dir) => {
+            let path = dir.join(name);
+            fs::remove_file(&path).await.map_err(StorageError::io(path.display().to_string()))?;
+        }
+        Target::Remote(remote) => remote.delete(name).await?,
+    }
+    Ok(())
+}
+
+/// Decrypt an archive by its extension: `.age` with `identity` (an age identity file),
+/// `.gpg` with the local keyring.
+pub async fn decrypt(path: &Path, identity: Option<&Path>) -> Result<Bundle, BackupError> {
+    let name = path.display().to_string();
+    let (tool, mut command) = match path.extension().and_then(|extension| extension.to_str()) {
+        Some("age") => {
+            let mut command = Command::new("age");
+            command.arg("--decrypt");
+            if let Some(identity) = identity {
+                command.arg("--identity").arg(identity);
+            }
+            ("age", command)
+        }
+        Some("gpg") => {
+            let mut command = Command::new("gpg");
+            command.arg("--decrypt");
+            ("gpg", command)
+        }
+        _ => return Err(BackupError::Invalid(name)),
+    };
+    command.arg(path);
+    let output = remote::output(&mut command, None).await.map_err(|source| BackupError::Spawn { tool, source })?;
+    if !output.status.success() {
+        return Err(BackupError::Failed { tool, stderr: String::from_utf8_lossy(&output.stderr).trim().to_string() });
+    }
+    serde_json::from_slice(&output.stdout).map_err(|_| BackupError::Invalid(name))
+}
+
+/// Write the bundle's files into the data directory, which must not hold any of them yet.
+/// Returns how many were written.
+pub async fn restore(bundle: &Bundle) -> Result<usize, BackupError> {
+    let mut files = Vec::new();
+    for (name, content) in &bundle.files {
+        let path = PathBuf::from(name);
+        if !path.components().all(|part| matches!(part, Component::Normal(_))) {
+            return Err(BackupError::UnsafePath(name.clone()));
+        }
+        if vault::locate(&path).exists() {
+            return Err(BackupError::Exists(name.clone()));
+        }
+        let content = BASE64.decode(content).map_err(|_| BackupError::Invalid(name.clone()))?;
+        files.push((path, content));
+    }
+    for (path, content) in &files {
+        let path = vault::locate(path);
+        let name = path.display().to_string();
+        if let Some(parent) = path.parent().filter(|parent| !parent.as_os_str().is_empty()) {
+            fs::create_dir_all(parent).await.map_err(StorageError::io(parent.display().to_string()))?;
+        }
+        fs::write(&path, content).await.map_err(StorageError::io(name))?;
+    }
+    Ok(files.len())
+}
@@ -0,0 +1,260 @@
+//! Right-to-left text for terminals that draw every cell left to right.
+//!
+//! A reduced form of the Unicode bidirectional algorithm (UAX #9) for one line without
+//! explicit embeddings: characters are strong left-to-right, strong right-to-left (Hebrew,
+//! Arabic, Syriac, Thaana, N'Ko and their presentation forms), numbers or neutral; numbers in
+//! right-to-left text keep their digit order, neutrals between two runs of one direction take
+//! it, and brackets in right-to-left runs are mirrored. Combining marks stay with their base
+//! character.
+
+use serde::{Deserialize, Serialize};
+
+/// Base direction of a group's messages and composer.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TextDirection {
+    /// Each message by its first letter
+    #[default]
+    Auto,
+    Ltr,
+    Rtl,
+}
+
+impl TextDirection {
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "auto" => Some(TextDirection::Auto),
+            "ltr" => Some(TextDirection::Ltr),
+            "rtl" => Some(TextDirection::Rtl),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            TextDirection::Auto => "auto",
+            TextDirection::Ltr => "ltr",
+            TextDirection::Rtl => "rtl",
+        }
+    }
+
+    /// Whether `text` is laid out right to left under this setting.
+    pub fn is_rtl(self, text: &str) -> bool {
+        match self {
+            TextDirection::Auto => first_strong_rtl(text).unwrap_or(false),
+            TextDirection::Ltr => false,
+            TextDirection::Rtl => true,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Class {
+    Left,
+    Right,
+    Number,
+    Neutral,
+}
+
+/// A base character and the combining marks after it.
+struct Cluster<'a> {
+    text: &'a str,
+    class: Class,
+    level: u8,
+}
+
+impl Cluster<'_> {
+    /// Odd levels run right to left.
+    fn runs_rtl(&self) -> bool {
+        self.level % 2 == 1
+    }
+}
+
+/// `text` in the order its characters appear on screen.
+pub fn reorder(text: &str, rtl: bool) -> String {
+    visual(text, rtl).into_iter().map(mirrored).collect()
+}
+
+/// Screen column of the insertion point after the last character of `text`, counting one
+/// column per character from the left edge of the reordered text; -1 is just left of it,
+/// where right-to-left text grows.
+pub fn end_cursor(text: &str, rtl: bool) -> isize {
+    let clusters = resolve(text, rtl);
+    let Some(last) = clusters.len().checked_sub(1) else {
+        return if rtl { -1 } else { 0 };
+    };
+    let order = visual_order(&levels(&clusters));
+    let column = order.iter().position(|index| *index == last).unwrap_or(last) as isize;
+    if clusters[last].runs_rtl() {
+        column - 1
+    } else {
+        column + 1
+    }
+}
+
+/// Characters drawn on screen for `text`, combining marks included with their base.
+pub fn width(text: &str) -> usize {
+    clusters(text).count()
+}
+
+fn visual(text: &str, rtl: bool) -> Vec<Cluster<'_>> {
+    let clusters = resolve(text, rtl);
+    let order = visual_order(&levels(&clusters));
+    let mut clusters: Vec<Option<Cluster>> = clusters.into_iter().map(Some).collect();
+    order.into_iter().filter_map(|index| clusters[index].take()).collect()
+}
+
+fn levels(clusters: &[Cluster<'_>]) -> Vec<u8> {
+    clusters.iter().map(|cluster| cluster.level).collect()
+}
+
+/// Indexes of clusters at `levels` from left to right on screen (rule L2): from the highest
+/// level down to the lowest odd one, every run at that level or above is reversed.
+fn visual_order(levels: &[u8]) -> Vec<usize> {
+    let mut order: Vec<usize> = (0..levels.len()).collect();
+    let highest = levels.iter().copied().max().unwrap_or(0);
+    let lowest_odd = levels.iter().copied().filter(|level| level % 2 == 1).min().unwrap_or(highest + 1);
+    for level in (lowest_odd..=highest).rev() {
+        let mut start = 0;
+        while start < order.len() {
+            if levels[order[start]] < level {
+                start += 1;
+                continue;
+            }
+            let end = (start..order.len()).find(|i| levels[order[*i]] < level).unwrap_or(order.len());
+            order[start..end].reverse();
+            start = end;
+        }
+    }
+    order
+}
+
+/// Clusters of `text` with their embedding level: 0 or 2 left to right, 1 right to left.
+fn resolve(text: &str, rtl: bool) -> Vec<Cluster<'_>> {
+    let base = if rtl { Class::Right } else { Class::Left };
+    let mut clusters: Vec<Cluster> = clusters(text).collect();
+
+    // Numbers after left-to-right text (or at the start of a left-to-right line) are part of it
+    let mut last_strong = base;
+    for cluster in &mut clusters {
+        match cluster.class {
+            Class::Left | Class::Right => last_strong = cluster.class,
+            Class::Number if last_strong == Class::Left => cluster.class = Class::Left,
+            _ => {}
+        }
+    }
+
+    // Neutrals between two runs of the same direction take it, others (trailing whitespace
+    // among them) the base direction; numbers count as right to left here
+    let direction = |class: Class| if class == Class::Left { Class::Left } else { Class::Right };
+    let mut index = 0;
+    while index < clusters.len() {
+        if clusters[index].class != Class::Neutral {
+            index += 1;
+            continue;
+        }
+        let end = (index..clusters.len()).find(|i| clusters[*i].class != Class::Neutral).unwrap_or(clusters.len());
+        let before = index.checked_sub(1).map_or(base, |i| direction(clusters[i].class));
+        let after = clusters.get(end).map_or(base, |cluster| direction(cluster.class));
+        let resolved = if before == after { before } else { base };
+        for cluster in &mut clusters[index..end] {
+            cluster.class = resolved;
+        }
+        index = end;
+    }
+
+    for cluster in &mut clusters {
+        cluster.level = match (cluster.class, rtl) {
+            (Class::Right, _) => 1,
+            (Class::Left, false) => 0,
+            (Class::Left, true) | (Class::Number, _) => 2,
+            (Class::Neutral, _) => u8::from(rtl),
+        };
+    }
+    clusters
+}
+
+fn clusters(text: &str) -> impl Iterator<Item = Cluster<'_>> {
+    let mut rest = text;
+    std::iter::from_fn(move || {
+        let mut chars = rest.char_indices();
+        let (_, first) = chars.next()?;
+        let end = chars.find(|(_, c)| !is_mark(*c)).map_or(rest.len(), |(end, _)| end);
+        let (text, remainder) = rest.split_at(end);
+        rest = remainder;
+        Some(Cluster { text, class: class(first), level: 0 })
+    })
+}
+
+fn first_strong_rtl(text: &str) -> Option<bool> {
+    text.chars().map(class).find_map(|class| match class {
+        Class::Left => Some(false),
+        Class::Right => Some(true),
+        _ => None,
+    })
+}
+
+fn class(c: char) -> Class {
+    if c.is_ascii_digit() || matches!(c, '\u{0660}'..='\u{0669}' | '\u{06F0}'..='\u{06F9}') {
+        Class::Number
+    } else if is_rtl(c) {
+        Class::Right
+    } else if c.is_alphabetic() {
+        Class::Left
+    } else {
+        Class::Neutral
+    }
+}
+
+fn is_rtl(c: char) -> bool {
+    matches!(c,
+        '\u{0590}'..='\u{08FF}'
+        | '\u{FB1D}'..='\u{FDFF}'
+        | '\u{FE70}'..='\u{FEFF}'
+        | '\u{10800}'..='\u{10FFF}'
+        | '\u{1E800}'..='\u{1EFFF}'
+    ) && !is_mark(c)
+}
+
+/// Combining marks drawn over the previous character, Hebrew points and Arabic harakat
+/// among them.
+fn is_mark(c: char) -> bool {
+    matches!(c,
+        '\u{0300}'..='\u{036F}'
+        | '\u{0591}'..='\u{05BD}'
+        | '\u{05BF}' | '\u{05C1}' | '\u{05C2}' | '\u{05C4}' | '\u{05C5}' | '\u{05C7}'
+        | '\u{0610}'..='\u{061A}'
+        | '\u{064B}'..='\u{065F}'
+        | '\u{0670}'
+        | '\u{06D6}'..='\u{06DC}'
+        | '\u{06DF}'..='\u{06E4}'
+        | '\u{06E7}' | '\u{06E8}'
+        | '\u{06EA}'..='\u{06ED}'
+        | '\u{200C}' | '\u{200D}'
+    )
+}
+
+/// A cluster's text, with brackets swapped when it runs right to left (rule L4).
+fn mirrored(cluster: Cluster<'_>) -> String {
+    if !cluster.runs_rtl() {
+        return cluster.text.to_string();
+    }
+    let mut chars = cluster.text.chars();
+    let Some(first) = chars.next() else {
+        return String::new();
+    };
+    let swapped = match first {
+        '(' => ')',
+        ')' => '(',
+        '[' => ']',
+        ']' => '[',
+        '{' => '}',
+        '}' => '{',
+        '<' => '>',
+        '>' => '<',
+        '«' => '»',
+        '»' => '«',
+        c => c,
+    };
+    std::iter::once(swapped).chain(chars).collect()
+}
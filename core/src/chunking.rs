@@ -0,0 +1,210 @@
+use base64::{Engine as _, engine::general_purpose::STANDARD as BASE64};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use chrono::{DateTime, Duration, Utc};
+use crate::crypto::{to_hex, CryptoProvider};
+use crate::errors::{ChunkError, CryptoError};
+
+/// Upper bound on slices per message, so a hostile `total` cannot make us track
+/// (or request) billions of missing chunks.
+const MAX_CHUNKS: u32 = 65_536;
+
+/// Partial messages older than this are abandoned.
+pub const REASSEMBLY_TIMEOUT: Duration = Duration::seconds(600);
+
+/// One slice of a large application payload, sent as its own MLS application message.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Chunk {
+    pub parent_id: String,
+    pub index: u32,
+    pub total: u32,
+    /// SHA-256 of the complete payload, checked after reassembly
+    pub payload_digest: String,
+    /// SHA-256 binding this slice to its parent, position and payload
+    pub digest: String,
+    pub data: String, // base64 encoded
+}
+
+impl Chunk {
+    fn binding(crypto: &CryptoProvider, parent_id: &str, index: u32, total: u32, payload_digest: &str, data: &[u8]) -> Result<String, CryptoError> {
+        let input = [
+            parent_id.as_bytes(),
+            &index.to_be_bytes(),
+            &total.to_be_bytes(),
+            payload_digest.as_bytes(),
+            data,
+        ]
+        .concat();
+        Ok(to_hex(&crypto.sha256(&input)?))
+    }
+
+    /// Check the per-chunk binding and return the decoded slice.
+    pub fn verify(&self, crypto: &CryptoProvider) -> Result<Vec<u8>, ChunkError> {
+        let failed = || ChunkError::Integrity { parent_id: self.parent_id.clone(), index: self.index };
+        let data = BASE64.decode(&self.data).map_err(|_| failed())?;
+        let expected = Self::binding(crypto, &self.parent_id, self.index, self.total, &self.payload_digest, &data)?;
+        if expected != self.digest || self.index >= self.total || self.total > MAX_CHUNKS {
+            return Err(failed());
+        }
+        Ok(data)
+    }
+}
+
+/// Split `payload` into chunks of at most `chunk_size` bytes bound to `parent_id`.
+pub fn split(crypto: &CryptoProvider, parent_id: &str, payload: &[u8], chunk_size: usize) -> Result<Vec<Chunk>, ChunkError> {
+    let payload_digest = to_hex(&crypto.sha256(payload)?);
+    let slices: Vec<&[u8]> = payload.chunks(chunk_size.max(1)).collect();
+    let total = u32::try_from(slices.len())
+        .ok()
+        .filter(|total| *total <= MAX_CHUNKS)
+        .ok_or(ChunkError::TooManyChunks { needed: slices.len(), allowed: MAX_CHUNKS })?;
+
+    slices
+        .into_iter()
+        .enumerate()
+        .map(|(index, data)| {
+            let index = index as u32;
+            Ok(Chunk {
+                parent_id: parent_id.to_string(),
+                index,
+                total,
+                payload_digest: payload_digest.clone(),
+                digest: Chunk::binding(crypto, parent_id, index, total, &payload_digest, data)?,
+                data: BASE64.encode(data),
+            })
+        })
+        .collect()
+}
+
+#[derive(Serialize, Deserialize)]
+struct Partial {
+    sender: String,
+    group_id: String,
+    total: u32,
+    payload_digest: String,
+    /// Verified slices, kept encoded so an interrupted download survives a restart
+    chunks: BTreeMap<u32, Chunk>,
+    started: DateTime<Utc>,
+}
+
+/// Collects chunks per parent message until the payload is complete.
+#[derive(Default, Serialize, Deserialize)]
+pub struct Reassembler {
+    partial: HashMap<String, Partial>,
+}
+
+impl Reassembler {
+    /// Add a verified chunk; returns the parent ID and payload once every slice has arrived.
+    pub fn add(&mut self, crypto: &CryptoProvider, group_id: &str, sender: &str, chunk: Chunk) -> Result<Option<(String, Vec<u8>)>, ChunkError> {
+        self.expire();
+
+        chunk.verify(crypto)?;
+        let parent_id = chunk.parent_id.clone();
+        let partial = self.partial.entry(parent_id.clone()).or_insert_with(|| Partial {
+            sender: sender.to_string(),
+            group_id: group_id.to_string(),
+            total: chunk.total,
+            payload_digest: chunk.payload_digest.clone(),
+            chunks: BTreeMap::new(),
+            started: Utc::now(),
+        });
+        // Every slice must agree on who sent it and what the whole payload is
+        if partial.sender != sender || partial.group_id != group_id
+            || partial.total != chunk.total || partial.payload_digest != chunk.payload_digest
+        {
+            return Err(ChunkError::Mismatch { parent_id, index: chunk.index });
+        }
+        partial.chunks.insert(chunk.index, chunk);
+        if partial.chunks.len() < partial.total as usize {
+            return Ok(None);
+        }
+
+        let Some(partial) = self.partial.remove(&parent_id) else {
+            return Ok(None);
+        };
+        // Slices may have been reloaded from disk, so check each again before assembling
+        let mut payload = Vec::new();
+        for chunk in partial.chunks.values() {
+            payload.extend(chunk.verify(crypto)?);
+        }
+        if to_hex(&crypto.sha256(&payload)?) != partial.payload_digest {
+            return Err(ChunkError::Reassembled(parent_id));
+        }
+        Ok(Some((parent_id, payload)))
+    }
+
+    /// Abandon partial messages whose remaining slices are unlikely to arrive.
+    pub fn expire(&mut self) {
+        let now = Utc::now();
+        self.partial.retain(|_, partial| now - partial.started < REASSEMBLY_TIMEOUT);
+    }
+
+    /// Incomplete downloads as (group ID, sender, parent ID, missing slice indexes).
+    pub fn missing(&self) -> Vec<(String, String, String, Vec<u32>)> {
+        self.partial
+            .iter()
+            .map(|(parent_id, partial)| {
+                let missing = (0..partial.total).filter(|index| !partial.chunks.contains_key(index)).collect();
+                (partial.group_id.clone(), partial.sender.clone(), parent_id.clone(), missing)
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn payload() -> Vec<u8> {
+        (0..1000u32).flat_map(|n| n.to_be_bytes()).collect()
+    }
+
+    #[test]
+    fn reassembles_out_of_order() {
+        let crypto = CryptoProvider::new();
+        let mut chunks = split(&crypto, "parent", &payload(), 300).unwrap();
+        assert_eq!(chunks.len(), 14);
+        chunks.reverse();
+        let mut reassembler = Reassembler::default();
+        let last = chunks.pop().unwrap();
+        for chunk in chunks {
+            assert!(reassembler.add(&crypto, "group", "alice", chunk).unwrap().is_none());
+        }
+        assert_eq!(reassembler.missing(), vec![("group".to_string(), "alice".to_string(), "parent".to_string(), vec![0])]);
+        let (parent_id, assembled) = reassembler.add(&crypto, "group", "alice", last).unwrap().unwrap();
+        assert_eq!(parent_id, "parent");
+        assert_eq!(assembled, payload());
+        assert!(reassembler.missing().is_empty());
+    }
+
+    #[test]
+    fn rejects_tampered_chunks() {
+        let crypto = CryptoProvider::new();
+        let chunks = split(&crypto, "parent", &payload(), 300).unwrap();
+        let mut moved = chunks[1].clone();
+        moved.index = 2;
+        assert!(matches!(moved.verify(&crypto), Err(ChunkError::Integrity { index: 2, .. })));
+        let mut rewritten = chunks[1].clone();
+        rewritten.data = BASE64.encode(b"something else");
+        assert!(rewritten.verify(&crypto).is_err());
+        let mut reparented = chunks[1].clone();
+        reparented.parent_id = "other".to_string();
+        assert!(reparented.verify(&crypto).is_err());
+    }
+
+    #[test]
+    fn rejects_chunks_from_another_sender_or_group() {
+        let crypto = CryptoProvider::new();
+        let chunks = split(&crypto, "parent", &payload(), 300).unwrap();
+        let mut reassembler = Reassembler::default();
+        reassembler.add(&crypto, "group", "alice", chunks[0].clone()).unwrap();
+        assert!(matches!(reassembler.add(&crypto, "group", "mallory", chunks[1].clone()), Err(ChunkError::Mismatch { .. })));
+        assert!(matches!(reassembler.add(&crypto, "other", "alice", chunks[1].clone()), Err(ChunkError::Mismatch { .. })));
+    }
+
+    #[test]
+    fn caps_the_number_of_chunks() {
+        let crypto = CryptoProvider::new();
+        assert!(matches!(split(&crypto, "parent", &vec![0; MAX_CHUNKS as usize + 1], 1), Err(ChunkError::TooManyChunks { .. })));
+    }
+}
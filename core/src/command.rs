@@ -0,0 +1,525 @@
+//! The command engine shared by the TUI command mode and the line-based interface: command
+//! lines tokenized, then parsed into a `Command` with its arguments checked. Running one is
+//! up to the client, so nothing here knows about the terminal.
+//!
+//! Words split on whitespace unless quoted. A quote opens at the start of a word or right
+//! after `=` (so `it's` needs no escaping but `--name="My Group"` works): single quotes keep
+//! everything literally, double quotes honour `\"` and `\\`. Outside quotes a backslash
+//! escapes whitespace, a quote or another backslash and is kept before anything else, so
+//! regex patterns like `\d+` come through unchanged.
+
+use chrono::{Local, NaiveDate};
+use std::collections::BTreeMap;
+use crate::errors::CommandError;
+use crate::group::GroupMode;
+use crate::history::HistoryShare;
+use crate::WirePolicy;
+
+/// Options given to a command by name, with their value if they had one.
+pub type Options = BTreeMap<String, Option<String>>;
+
+/// One word of a command line.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Token {
+    pub text: String,
+    /// It began with a quote or an escape, so it is never read as an option
+    pub quoted: bool,
+    /// Byte offset in the line where the word starts
+    pub start: usize,
+}
+
+#[derive(Debug, Clone)]
+pub struct CommandLine<'a> {
+    line: &'a str,
+    tokens: Vec<Token>,
+}
+
+impl<'a> CommandLine<'a> {
+    pub fn parse(line: &'a str) -> Result<Self, CommandError> {
+        Ok(Self { line, tokens: tokenize(line)? })
+    }
+
+    /// Every word with quotes and escapes resolved.
+    pub fn words(&self) -> Vec<&str> {
+        self.tokens.iter().map(|token| token.text.as_str()).collect()
+    }
+
+    /// The line from word `index` on exactly as typed, for free text such as a message body;
+    /// empty past the last word.
+    pub fn rest(&self, index: usize) -> &'a str {
+        self.tokens.get(index).map_or("", |token| self.line[token.start..].trim_end())
+    }
+
+    /// Positional words and the `--name=value` / `--name` options among them, for a command
+    /// taking the options in `allowed`. A bare `--` ends the options; quoted words are never
+    /// options.
+    pub fn options(&self, allowed: &[&str]) -> Result<(Vec<&str>, Options), CommandError> {
+        let mut words = Vec::new();
+        let mut options = Options::new();
+        let mut ended = false;
+        for token in &self.tokens {
+            let option = (!ended && !token.quoted).then(|| token.text.strip_prefix("--")).flatten();
+            match option {
+                Some("") => ended = true,
+                Some(option) => {
+                    let (name, value) = match option.split_once('=') {
+                        Some((name, value)) => (name, Some(value.to_string())),
+                        None => (option, None),
+                    };
+                    if !allowed.contains(&name) {
+                        return Err(CommandError::UnknownOption {
+                            command: self.tokens.first().map(|token| token.text.clone()).unwrap_or_default(),
+                            option: name.to_string(),
+                        });
+                    }
+                    options.insert(name.to_string(), value);
+                }
+                None => words.push(token.text.as_str()),
+            }
+        }
+        Ok((words, options))
+    }
+}
+
+/// Every command, for the unknown-command hint.
+pub const COMMANDS: &[&str] = &[
+    "create", "clone", "invite", "invite-file", "kick", "find", "search", "invite-token", "join", "join-token", "rejoin",
+    "leave", "forget", "send", "mode", "dashboard", "passphrase", "lock", "wipe", "metrics", "kp", "rotate-identity",
+    "update", "key-policy", "nick", "tag", "direction", "folder", "template", "filter", "account", "whoami", "flow",
+    "tree", "diff", "members", "inactive", "cleanup", "onboarding", "export", "translate", "preview", "speak", "remind",
+    "quarantine", "redact", "errors", "details", "retry", "dnd", "quiet-hours", "low-bandwidth", "debug", "commit",
+    "config", "groups", "list", "status", "netstat", "accept-insecure", "trust", "trust-sender", "settings", "undo",
+    "backup", "selftest", "stress", "activity", "help", "quit",
+];
+
+/// A command with its arguments checked, for the client to run. Groups named by the user are
+/// left as typed (`None` for the active group), and commands with their own sub-commands
+/// carry their remaining words.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Command<'a> {
+    /// `None` for the configured default policy
+    Create { name: &'a str, policy: Option<WirePolicy> },
+    Clone { group: &'a str, new_name: &'a str },
+    InviteFile { group: &'a str, path: &'a str },
+    /// `share` is `None` for the configured default, `Some(None)` to share nothing
+    Invite { identity: &'a str, share: Option<Option<HistoryShare>> },
+    Forget(Option<&'a str>),
+    Rejoin(Option<&'a str>),
+    Leave(Option<&'a str>),
+    InviteToken(Option<&'a str>),
+    Find(Option<&'a str>),
+    Search(Vec<&'a str>),
+    JoinToken(&'a str),
+    Onboarding(Vec<&'a str>),
+    Kick(&'a str),
+    /// The `n`-th most recent message, 1 the latest
+    Redact(usize),
+    Join(&'a str),
+    /// The message exactly as typed
+    Send(&'a str),
+    Mode(GroupMode),
+    Members,
+    Errors(Option<&'a str>),
+    Retry,
+    Details,
+    Whoami,
+    /// Frames to draw, `None` for the default
+    Flow(Option<usize>),
+    Tree,
+    Diff(Vec<&'a str>),
+    Quarantine { clear: bool },
+    Cleanup(Vec<&'a str>),
+    /// Days, `None` for `inactive_member_days`
+    Inactive(Option<u64>),
+    Update(Option<&'a str>),
+    KeyPolicy(Vec<&'a str>),
+    Export(Vec<&'a str>),
+    Remind(Vec<&'a str>),
+    Speak(Vec<&'a str>),
+    Preview(Vec<&'a str>),
+    Translate(Vec<&'a str>),
+    Template(TemplateAction<'a>),
+    /// Empty to clear it
+    Nick(String),
+    Folder(Vec<&'a str>),
+    Filter(Vec<&'a str>),
+    Account(Vec<&'a str>),
+    Tag { group: &'a str, color: &'a str, icon: Option<&'a str> },
+    Direction(Option<&'a str>),
+    Undo,
+    Backup(Vec<&'a str>),
+    Selftest(Vec<&'a str>),
+    Stress(Vec<&'a str>),
+    Activity(Vec<&'a str>),
+    Quit,
+    Help,
+    Dashboard,
+    /// The passphrases are asked for at a masked prompt, never typed after the command
+    Passphrase,
+    /// `passphrase duress off` removes it
+    DuressPassphrase { remove: bool },
+    Lock,
+    Wipe,
+    /// Ours, or the identity's
+    ShowKeyPackage(Option<&'a str>),
+    RotateIdentity,
+    /// The toggles below take `None` to flip the setting
+    Dnd(Option<bool>),
+    QuietHours(Vec<&'a str>),
+    LowBandwidth(Option<bool>),
+    Debug(Option<bool>),
+    EncryptionPreview(Option<bool>),
+    Commit(CommitAction),
+    Config(ConfigAction<'a>),
+    Metrics,
+    Settings,
+    Groups,
+    List,
+    Netstat,
+    AcceptInsecure(Vec<&'a str>),
+    Trust(Vec<&'a str>),
+    TrustSender(Vec<&'a str>),
+    Status,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum TemplateAction<'a> {
+    Save(&'a str),
+    Use(&'a str),
+    Delete(&'a str),
+    List,
+}
+
+/// `commit` on the active group's held commit.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CommitAction {
+    Preview,
+    Send,
+    Abort,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ConfigAction<'a> {
+    Check,
+    Repair,
+    Show,
+    Set { setting: &'a str, value: String },
+}
+
+impl<'a> Command<'a> {
+    /// Read the command on `line`, or say how it is used.
+    pub fn parse(line: &'a CommandLine) -> Result<Self, CommandError> {
+        let parts = line.words();
+        let rest = || parts.get(1..).unwrap_or_default().to_vec();
+        let usage = |text: &'static str| Err(CommandError::Usage(text));
+        let command = match parts.first().copied() {
+            Some("create") => {
+                let (words, options) = line.options(&["policy"])?;
+                let policy = match options.get("policy").map(|policy| policy.as_deref().unwrap_or_default()).or(words.get(2).copied()) {
+                    Some(policy) => WirePolicy::parse(policy).map(Some),
+                    None => Some(None),
+                };
+                match (words.get(1), policy) {
+                    (Some(name), Some(policy)) => Command::Create { name, policy },
+                    _ => return usage("create <group_name> [ciphertext|mixed|public-commits] (or --policy=<policy>)"),
+                }
+            }
+            Some("clone") => match parts.get(1..) {
+                Some([group, new_name]) => Command::Clone { group, new_name },
+                _ => return usage("clone <group> <new_name>; quote names with spaces"),
+            },
+            Some("invite-file") => match parts.get(1..) {
+                Some([group, path]) => Command::InviteFile { group, path },
+                _ => return usage("invite-file <group> <path>; quote names with spaces"),
+            },
+            Some("invite") => {
+                let (words, options) = line.options(&["history", "since"])?;
+                // `--history=5` is the same as `history 5`
+                let mut share_args: Vec<&str> = words.get(2..).unwrap_or_default().to_vec();
+                for (name, value) in &options {
+                    share_args.push(name);
+                    share_args.push(value.as_deref().unwrap_or_default());
+                }
+                let share = match share_args.as_slice() {
+                    [] => Ok(None),
+                    ["history", count] => count.parse().map(|count| Some((count > 0).then_some(HistoryShare::Last(count)))).map_err(|_| ()),
+                    ["since", date] => NaiveDate::parse_from_str(date, "%Y-%m-%d")
+                        .ok()
+                        .and_then(|date| date.and_hms_opt(0, 0, 0)?.and_local_timezone(Local).earliest())
+                        .map(|since| Some(Some(HistoryShare::Since(since))))
+                        .ok_or(()),
+                    _ => Err(()),
+                };
+                match (words.get(1), share) {
+                    (Some(identity), Ok(share)) => Command::Invite { identity, share },
+                    _ => return usage("invite <identity> [history <n> | since <YYYY-MM-DD>] (or --history=<n>, --since=<date>)"),
+                }
+            }
+            Some("forget") => Command::Forget(parts.get(1).copied()),
+            Some("rejoin") => Command::Rejoin(parts.get(1).copied()),
+            Some("leave") => Command::Leave(parts.get(1).copied()),
+            Some("invite-token") => Command::InviteToken(parts.get(1).copied()),
+            Some("find") => Command::Find(parts.get(1).copied()),
+            Some("search") => Command::Search(rest()),
+            Some("join-token") => match parts.get(1) {
+                Some(token) => Command::JoinToken(token),
+                None => return usage("join-token <token>"),
+            },
+            Some("onboarding") => Command::Onboarding(rest()),
+            Some("kick") => match parts.get(1) {
+                Some(identity) => Command::Kick(identity),
+                None => return usage("kick <identity> (removes them from the active group)"),
+            },
+            Some("redact") => match parts.get(1).and_then(|n| n.parse::<usize>().ok()).filter(|n| *n > 0) {
+                Some(n) => Command::Redact(n),
+                None => return usage("redact <n> (1 is the most recent message)"),
+            },
+            Some("join") => match parts.get(1) {
+                Some(group_id) => Command::Join(group_id),
+                None => return usage("join <group_id>"),
+            },
+            Some("send") => match line.rest(1) {
+                "" => return usage("send <message>"),
+                message => Command::Send(message),
+            },
+            Some("mode") => match parts.get(1).and_then(|mode| GroupMode::parse(mode)) {
+                Some(mode) => Command::Mode(mode),
+                None => return usage("mode announce|chat"),
+            },
+            Some("members") => Command::Members,
+            Some("errors") => Command::Errors(parts.get(1).copied()),
+            Some("retry") => Command::Retry,
+            Some("details") => Command::Details,
+            Some("whoami") => Command::Whoami,
+            Some("flow") => Command::Flow(parts.get(1).and_then(|n| n.parse().ok())),
+            Some("tree") => Command::Tree,
+            Some("diff") => Command::Diff(rest()),
+            Some("quarantine") => match parts.get(1) {
+                None => Command::Quarantine { clear: false },
+                Some(&"clear") => Command::Quarantine { clear: true },
+                Some(_) => return usage("quarantine [clear]"),
+            },
+            Some("cleanup") => Command::Cleanup(rest()),
+            Some("inactive") => match parts.get(1).map(|days| days.parse::<u64>()) {
+                None => Command::Inactive(None),
+                Some(Ok(days)) => Command::Inactive(Some(days)),
+                Some(Err(_)) => return usage("inactive [days]"),
+            },
+            Some("update") => Command::Update(parts.get(1).copied()),
+            Some("key-policy") => Command::KeyPolicy(rest()),
+            Some("export") => Command::Export(rest()),
+            Some("remind") => Command::Remind(rest()),
+            Some("speak") => Command::Speak(rest()),
+            Some("preview") => Command::Preview(rest()),
+            Some("translate") => Command::Translate(rest()),
+            Some("template") => match (parts.get(1), parts.get(2)) {
+                (Some(&"save"), Some(name)) => Command::Template(TemplateAction::Save(name)),
+                (Some(&"use"), Some(name)) => Command::Template(TemplateAction::Use(name)),
+                (Some(&"delete"), Some(name)) => Command::Template(TemplateAction::Delete(name)),
+                (Some(&"list"), None) => Command::Template(TemplateAction::List),
+                _ => return usage("template save|use|delete <name> | template list"),
+            },
+            Some("nick") => Command::Nick(parts.get(1..).map(|words| words.join(" ")).unwrap_or_default()),
+            Some("folder") => Command::Folder(rest()),
+            Some("filter") => Command::Filter(rest()),
+            Some("account") => Command::Account(rest()),
+            Some("tag") => match (parts.get(1), parts.get(2)) {
+                (Some(group), Some(color)) => Command::Tag { group, color, icon: parts.get(3).copied() },
+                _ => return usage("tag <group> <color> [emoji] | tag <group> clear"),
+            },
+            Some("direction") => Command::Direction(parts.get(1).copied()),
+            Some("undo") => Command::Undo,
+            Some("backup") => Command::Backup(rest()),
+            Some("selftest") => Command::Selftest(rest()),
+            Some("stress") => Command::Stress(rest()),
+            Some("activity") => Command::Activity(rest()),
+            Some("quit") => Command::Quit,
+            Some("help") => Command::Help,
+            Some("dashboard") => Command::Dashboard,
+            Some("passphrase") => match (parts.get(1), parts.get(2)) {
+                (None, _) => Command::Passphrase,
+                (Some(&"duress"), None) => Command::DuressPassphrase { remove: false },
+                (Some(&"duress"), Some(&"off")) => Command::DuressPassphrase { remove: true },
+                _ => return usage("passphrase | passphrase duress [off] (the passphrase is asked for at a prompt)"),
+            },
+            Some("lock") => Command::Lock,
+            Some("wipe") => Command::Wipe,
+            Some("kp") => match parts.get(1) {
+                Some(&"show") => Command::ShowKeyPackage(parts.get(2).copied()),
+                _ => return usage("kp show [identity]"),
+            },
+            Some("rotate-identity") => Command::RotateIdentity,
+            Some("dnd") => match toggle(parts.get(1)) {
+                Some(on) => Command::Dnd(on),
+                None => return usage("dnd [on|off]"),
+            },
+            Some("quiet-hours") => Command::QuietHours(rest()),
+            Some("low-bandwidth") => match toggle(parts.get(1)) {
+                Some(on) => Command::LowBandwidth(on),
+                None => return usage("low-bandwidth [on|off]"),
+            },
+            Some("debug") => match parts.get(1) {
+                Some(&"preview") => match toggle(parts.get(2)) {
+                    Some(on) => Command::EncryptionPreview(on),
+                    None => return usage("debug preview [on|off]"),
+                },
+                _ => match toggle(parts.get(1)) {
+                    Some(on) => Command::Debug(on),
+                    None => return usage("debug [on|off] | debug preview [on|off]"),
+                },
+            },
+            Some("commit") => match parts.get(1) {
+                None => Command::Commit(CommitAction::Preview),
+                Some(&"send") => Command::Commit(CommitAction::Send),
+                Some(&"abort") => Command::Commit(CommitAction::Abort),
+                Some(_) => return usage("commit [send|abort]"),
+            },
+            Some("config") => match parts.get(1) {
+                Some(&"check") => Command::Config(ConfigAction::Check),
+                Some(&"repair") => Command::Config(ConfigAction::Repair),
+                Some(&"show") => Command::Config(ConfigAction::Show),
+                Some(&"set") if parts.len() >= 4 => Command::Config(ConfigAction::Set { setting: parts[2], value: parts[3..].join(" ") }),
+                _ => return usage("config show|check|repair, config set <setting> <value>"),
+            },
+            Some("metrics") => Command::Metrics,
+            Some("settings") => Command::Settings,
+            Some("groups") => Command::Groups,
+            Some("list") => Command::List,
+            Some("netstat") => Command::Netstat,
+            Some("accept-insecure") => Command::AcceptInsecure(rest()),
+            Some("trust") => Command::Trust(rest()),
+            Some("trust-sender") => Command::TrustSender(rest()),
+            Some("status") => Command::Status,
+            Some(other) => return Err(CommandError::Unknown(other.to_string())),
+            None => return Err(CommandError::Unknown(String::new())),
+        };
+        Ok(command)
+    }
+}
+
+/// `on` or `off`, or `Some(None)` to flip when no word is given.
+fn toggle(word: Option<&&str>) -> Option<Option<bool>> {
+    match word {
+        None => Some(None),
+        Some(&"on") => Some(Some(true)),
+        Some(&"off") => Some(Some(false)),
+        Some(_) => None,
+    }
+}
+
+/// Command mode reference, shared by the help screen and simple mode.
+pub const HELP: &[&str] = &[
+    "  create <group_name> [ciphertext|mixed|public-commits] (or --policy=...): Create new group with a wire format policy; quote names with spaces",
+    "  clone <group> <new_name>: Create a group with another group's settings and invite its members",
+    "  invite <identity> [history <n> | since <YYYY-MM-DD>] (or --history=<n>, --since=<date>): Add someone to the active group, optionally sharing earlier messages",
+    "  invite-file <group> <path>: Invite everyone listed in a file (one identity per line, or the first CSV column) in batched commits (admins)",
+    "  find <username>: Look someone up in the delivery service's directory, then DM or invite them",
+    "  search <words> [from:<user>] [in:<group>] [before:|after:<YYYY-MM-DD>] [has:attachment|link|reaction|pin]: Search every group's history",
+    "  kick <identity>: Remove someone from the active group (admins)",
+    "  join <group_id>: Join existing group",
+    "  invite-token [group] / join-token <token>: Get a single-use invite token to share (admins), or join with one",
+    "  rejoin [group] / forget [group]: Rejoin a group you were removed from, or drop it from this device",
+    "  leave [group]: Ask the other members to remove you, then drop the group from this device",
+    "  send <message>: Send message",
+    "  undo: Cancel the last message still in its undo window (Ctrl+Z; see undo_send_secs)",
+    "  mode announce|chat: Restrict posting to admins",
+    "  list: Show available groups",
+    "  groups: List the groups on this device",
+    "  status: Check MLS service connection",
+    "  metrics: Toggle the metrics HUD",
+    "  netstat: Bytes and frames sent/received by type, and reconnects, this session",
+    "  accept-insecure [yes] | accept-insecure forget <endpoint>: Review and accept a plaintext delivery-service connection",
+    "  trust [reset <server>]: Show the delivery service's pinned identity key, or clear a pin after a legitimate key rotation",
+    "  trust-sender [<identity> [notify|speak|translate|preview|all]...] | trust-sender revoke|bridge|unbridge <identity>: Mark bridged senders and choose which automations they may set off",
+    "  kp show [identity]: Inspect a key package",
+    "  rotate-identity: Move all groups to a new signature key",
+    "  update [group]: Rotate a group's keys with a commit refreshing your leaf (u in normal mode)",
+    "  key-policy [days <n>] [messages <n>] [auto] | off: Set or show how often the active group's keys must rotate",
+    "  nick [name]: Set or clear your display name in the active group",
+    "  tag <group> <color> [emoji] | tag <group> clear: Mark a group in the list",
+    "  direction [auto|ltr|rtl]: Show or set whether the active group's messages and composer run left to right, right to left, or by their first letter",
+    "  folder create|delete <name>, folder move <group> <folder>|none: Organize the group list",
+    "  account add <name> <username> <host:port>, account remove|switch <name>, account list: Run more identities side by side",
+    "  whoami: Show your identity, fingerprints and storage paths",
+    "  flow [n]: Sequence diagram of the last n protocol frames of the active group",
+    "  tree: Show the active group's ratchet tree and epoch",
+    "  diff <group> <epoch_a> <epoch_b>: Who was added, removed or updated between two epochs, and by whom",
+    "  inactive [days]: List members silent that long and offer to remove them (admins)",
+    "  cleanup [days] / cleanup archive <group>: List groups quiet that long with their storage, to archive, leave or forget",
+    "  translate <n> | translate auto [on|off]: Translate a message (1 is the most recent), or every incoming one in the active group",
+    "  preview [on|off]: Show or switch link preview cards for links received in the active group",
+    "  remind [post] <10m|HH:MM|YYYY-MM-DDTHH:MM> <text>, remind list, remind cancel <id>: Local reminders, optionally posted to the active group",
+    "  speak [all|mentions|off]: Show or set which incoming messages of the active group are read aloud",
+    "  export <file> [from|to <YYYY-MM-DD>] [member <id>]... [age|gpg <recipient>]: Write the active group's transcript, optionally encrypted",
+    "  selftest [dir]: Check the MLS layer against the interop test vectors in dir (default test-vectors)",
+    "  stress <group> <count> [rate] | stress stop: Send synthetic messages through the real pipeline and report timings (debug mode)",
+    "  activity [info|warning|error] [text]: Open the activity log at that severity or above, listing entries containing the text",
+    "  backup [now]: Show where encrypted backups go and when the last one ran, or run one now",
+    "  quarantine [clear]: List or drop the active group's messages that failed to decrypt",
+    "  redact <n>: Remove the n-th most recent message for every member (admins)",
+    "  onboarding [set <text> | clear | dismiss]: Show the active group's onboarding message, change it (admins) or unpin it",
+    "  errors [code]: List error codes or explain one",
+    "  details: Explain the most recent error",
+    "  retry: Repeat the last failed create/join/send",
+    "  members: Open the roster of the active group (also 'r')",
+    "  template save|use|delete <name>, template list: Message templates",
+    "  filter add [all] <hide|dim|highlight> regex <pattern> | keywords <words>, filter remove <n>, filter list: Message filters",
+    "  dnd [on|off]: Toggle Do Not Disturb",
+    "  quiet-hours <start> <end>|off: Suppress notifications daily (HH:MM)",
+    "  low-bandwidth [on|off]: Batch acknowledgements and outgoing frames for slow or metered links",
+    "  debug [on|off]: Toggle commit summaries and review of your own commits",
+    "  debug preview [on|off]: In debug mode, hold each outgoing message after encryption and show its wire size, padding, epoch and generation",
+    "  config check|repair: List problems in config.json, or rewrite it keeping the valid settings",
+    "  config show | config set <setting> <value>: Show the effective settings, or change one (e.g. sounds.message off)",
+    "  commit [send|abort]: Show, deliver or discard the active group's pending commit",
+    "  passphrase / passphrase duress [off]: Set the unlock passphrase, or a decoy one, at a masked prompt",
+    "  lock: Lock the session now (Ctrl+L)",
+    "  wipe: Destroy all local data (guarded)",
+    "  dashboard: Open the activity dashboard",
+    "  settings: Open the settings screen",
+    "  help: Show the help screen",
+    "  quit: Exit application",
+];
+
+/// Split `line` into words; see the module documentation for the quoting rules.
+pub fn tokenize(line: &str) -> Result<Vec<Token>, CommandError> {
+    let mut tokens = Vec::new();
+    let mut chars = line.char_indices().peekable();
+    loop {
+        while chars.next_if(|(_, c)| c.is_whitespace()).is_some() {}
+        let Some(&(start, _)) = chars.peek() else {
+            break;
+        };
+        let mut token = Token { text: String::new(), quoted: false, start };
+        let mut previous: Option<char> = None;
+        while let Some((_, c)) = chars.next_if(|(_, c)| !c.is_whitespace()) {
+            match c {
+                '\'' | '"' if previous.is_none() || previous == Some('=') => {
+                    token.quoted |= previous.is_none();
+                    loop {
+                        match chars.next() {
+                            None => return Err(CommandError::UnterminatedQuote(c)),
+                            Some((_, close)) if close == c => break,
+                            Some((_, '\\')) if c == '"' => match chars.next_if(|(_, next)| matches!(next, '"' | '\\')) {
+                                Some((_, escaped)) => token.text.push(escaped),
+                                None => token.text.push('\\'),
+                            },
+                            Some((_, inner)) => token.text.push(inner),
+                        }
+                    }
+                }
+                '\\' => match chars.next_if(|(_, next)| next.is_whitespace() || matches!(next, '\'' | '"' | '\\')) {
+                    Some((_, escaped)) => {
+                        token.quoted |= previous.is_none();
+                        token.text.push(escaped);
+                    }
+                    None => token.text.push('\\'),
+                },
+                c => token.text.push(c),
+            }
+            previous = Some(c);
+        }
+        tokens.push(token);
+    }
+    Ok(tokens)
+}
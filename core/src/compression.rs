@@ -0,0 +1,57 @@
+use crate::errors::CompressionError;
+
+/// Feature listed in the leaf capabilities of clients that can decode zstd payloads.
+pub const ZSTD: &str = "zstd";
+
+/// Payloads shorter than this are never worth compressing.
+const MIN_COMPRESS_LEN: usize = 256;
+/// Compressed output must be at most this fraction of the input to be used.
+const MAX_COMPRESSED_RATIO: f64 = 0.9;
+/// Upper bound on decompressed size, guarding against decompression bombs.
+const MAX_DECOMPRESSED_LEN: usize = 16 * 1024 * 1024;
+const LEVEL: i32 = 3;
+
+/// Compress `payload` if it is large enough and actually shrinks; `None` means send it as-is.
+pub fn maybe_compress(payload: &[u8]) -> Option<Vec<u8>> {
+    if payload.len() < MIN_COMPRESS_LEN {
+        return None;
+    }
+    let compressed = zstd::bulk::compress(payload, LEVEL).ok()?;
+    if (compressed.len() as f64) <= payload.len() as f64 * MAX_COMPRESSED_RATIO {
+        Some(compressed)
+    } else {
+        None
+    }
+}
+
+pub fn decompress(data: &[u8]) -> Result<Vec<u8>, CompressionError> {
+    zstd::bulk::decompress(data, MAX_DECOMPRESSED_LEN).map_err(CompressionError::Decompress)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crypto::CryptoProvider;
+
+    #[test]
+    fn round_trips_compressible_payloads() {
+        let payload = "the same sentence again and again. ".repeat(100).into_bytes();
+        let compressed = maybe_compress(&payload).unwrap();
+        assert!(compressed.len() < payload.len());
+        assert_eq!(decompress(&compressed).unwrap(), payload);
+    }
+
+    #[test]
+    fn leaves_short_and_incompressible_payloads_alone() {
+        assert!(maybe_compress(&[b'a'; MIN_COMPRESS_LEN - 1]).is_none());
+        let noise = CryptoProvider::new().random_bytes(4096).unwrap();
+        assert!(maybe_compress(&noise).is_none());
+    }
+
+    #[test]
+    fn refuses_garbage_and_oversized_output() {
+        assert!(decompress(b"not zstd").is_err());
+        let bomb = zstd::bulk::compress(&vec![0; MAX_DECOMPRESSED_LEN + 1], LEVEL).unwrap();
+        assert!(decompress(&bomb).is_err());
+    }
+}
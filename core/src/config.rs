@@ -0,0 +1,649 @@
+use chrono::NaiveTime;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+use std::path::{Path, PathBuf};
+use crate::attachments::AttachmentStorage;
+use crate::backup::BackupConfig;
+use crate::errors::ConfigError;
+use crate::filters::MessageFilter;
+use crate::notify::SoundConfig;
+use crate::platform::{self, GlyphMode};
+use crate::preview::LinkPreviewConfig;
+use crate::senders::Automation;
+use crate::speech::SpeechConfig;
+use crate::translate::TranslationConfig;
+use crate::transport::{InsecureEndpoint, PinnedKey};
+use crate::validation::HandshakePolicy;
+use crate::vault;
+use crate::WirePolicy;
+
+pub const CONFIG_PATH: &str = "config.json";
+/// History and transfer state of the additional accounts, one subdirectory per account.
+pub const ACCOUNTS_DIR: &str = "accounts";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Config {
+    pub username: String,
+    pub delivery_service_address: String,
+    /// Further addresses of the same delivery service, tried in order when the others fail
+    #[serde(default)]
+    pub delivery_service_fallbacks: Vec<String>,
+    /// Identifies this installation among the user's devices; generated on first run
+    #[serde(default)]
+    pub device_id: String,
+    /// Lock the session after this many idle minutes (0 disables auto-lock)
+    #[serde(default)]
+    pub auto_lock_minutes: u64,
+    /// Warn when clocks (local, server, sender) disagree by more than this many seconds
+    #[serde(default = "default_clock_skew_threshold_secs")]
+    pub clock_skew_threshold_secs: u64,
+    /// Application payloads larger than this are split into chunks
+    #[serde(default = "default_chunk_threshold_bytes")]
+    pub chunk_threshold_bytes: usize,
+    /// Compress application payloads when every group member supports it
+    #[serde(default = "default_compress_payloads")]
+    pub compress_payloads: bool,
+    /// Longest text message the composer will send
+    #[serde(default = "default_max_message_bytes")]
+    pub max_message_bytes: usize,
+    /// Largest payload sent as chunks (after compression)
+    #[serde(default = "default_max_attachment_bytes")]
+    pub max_attachment_bytes: usize,
+    /// Suppress notifications until turned off again
+    #[serde(default)]
+    pub do_not_disturb: bool,
+    /// Daily window during which notifications are suppressed
+    #[serde(default)]
+    pub quiet_hours: Option<QuietHours>,
+    /// Still notify about messages that mention us while notifications are suppressed
+    #[serde(default = "default_dnd_allow_mentions")]
+    pub dnd_allow_mentions: bool,
+    /// Bell or audio file per event type, with per-group overrides
+    #[serde(default)]
+    pub sounds: SoundConfig,
+    /// Also raise notifications on the desktop (notify-send / osascript)
+    #[serde(default)]
+    pub desktop_notifications: bool,
+    /// Show the active group and unread/mention counts in the terminal (and tmux/screen) title
+    #[serde(default = "default_terminal_title")]
+    pub terminal_title: bool,
+    /// Ring the terminal bell on mentions even when the mention sound is a file or off,
+    /// so tmux/screen flag the window
+    #[serde(default)]
+    pub mention_bell: bool,
+    /// ntfy topic or UnifiedPush endpoint URL sent a content-free stub (group ID and a
+    /// counter) for each notification, so a phone can prompt opening the session
+    #[serde(default)]
+    pub push_relay: Option<String>,
+    /// Reusable message bodies; {date}, {group} and {user} are filled in on use
+    #[serde(default)]
+    pub templates: BTreeMap<String, String>,
+    /// Signature key fingerprints confirmed out-of-band, by identity
+    #[serde(default)]
+    pub verified_keys: BTreeMap<String, String>,
+    /// Delivery-service endpoints accepted for plaintext connections with `accept-insecure`
+    #[serde(default)]
+    pub insecure_endpoints: BTreeMap<String, InsecureEndpoint>,
+    /// Delivery-service identity keys pinned on first use, by endpoint; cleared with `trust reset`
+    #[serde(default)]
+    pub pinned_server_keys: BTreeMap<String, PinnedKey>,
+    /// Bridge and bot identities, exact or with `*` wildcards; see `senders`
+    #[serde(default)]
+    pub bridged_senders: Vec<String>,
+    /// Automations each bridged sender may set off, managed with `trust-sender`
+    #[serde(default)]
+    pub trusted_senders: BTreeMap<String, BTreeSet<Automation>>,
+    /// Named, collapsible sections of the group list, in display order
+    #[serde(default)]
+    pub folders: Vec<GroupFolder>,
+    /// Recent messages re-shared with members we invite (0 shares nothing)
+    #[serde(default)]
+    pub share_history_on_invite: usize,
+    /// Members `invite-file` adds per commit
+    #[serde(default = "default_invite_batch_size")]
+    pub invite_batch_size: usize,
+    /// Wire format policy for groups we create without naming one
+    #[serde(default)]
+    pub default_wire_policy: WirePolicy,
+    /// Show protocol internals: summaries of incoming commits, and our own commits held for
+    /// review before they are sent
+    #[serde(default)]
+    pub debug_mode: bool,
+    /// In debug mode, hold each outgoing message after encryption and show its wire size,
+    /// padding, epoch and ratchet generation until it is sent or discarded
+    #[serde(default)]
+    pub encryption_preview: bool,
+    /// For satellite or metered links: acknowledgements are batched and outgoing frames held
+    /// briefly so they share writes
+    #[serde(default)]
+    pub low_bandwidth: bool,
+    /// Seconds a sent message waits, cancellable with Ctrl+Z, before it is encrypted and
+    /// transmitted (0 to 10; 0 sends at once)
+    #[serde(default)]
+    pub undo_send_secs: u64,
+    /// Destructive actions that run without asking for confirmation first
+    #[serde(default)]
+    pub dont_ask: BTreeSet<Confirmable>,
+    /// Megabytes of timeline kept in memory before the least recently viewed groups' oldest
+    /// messages move to page files on disk (0 for no limit)
+    #[serde(default = "default_memory_budget_mb")]
+    pub memory_budget_mb: u64,
+    /// Members silent for this many days are suggested for removal by `inactive`
+    #[serde(default = "default_inactive_member_days")]
+    pub inactive_member_days: u64,
+    /// Days without activity after which `cleanup` lists a group
+    #[serde(default = "default_inactive_group_days")]
+    pub inactive_group_days: u64,
+    /// Rules that hide, dim or highlight matching messages, managed with `filter`
+    #[serde(default)]
+    pub filters: Vec<MessageFilter>,
+    /// Credential types, trusted roots and identity naming rules that Welcomes and commits
+    /// must meet; violations are quarantined with a security alert
+    #[serde(default)]
+    pub handshake_policy: HandshakePolicy,
+    /// External translator for `translate` and groups translated automatically
+    #[serde(default)]
+    pub translation: Option<TranslationConfig>,
+    /// Text-to-speech command and the groups whose incoming messages it reads aloud
+    #[serde(default)]
+    pub speech: Option<SpeechConfig>,
+    /// Proxy and opted-in groups for link preview cards
+    #[serde(default)]
+    pub link_previews: Option<LinkPreviewConfig>,
+    /// Target, recipient and schedule of automatic encrypted backups; off unless set
+    #[serde(default)]
+    pub backups: Option<BackupConfig>,
+    /// Where payloads over `chunk_threshold_bytes` go: chunked through the delivery service
+    /// (the default) or encrypted into an SFTP, WebDAV or S3 store
+    #[serde(default)]
+    pub attachment_storage: AttachmentStorage,
+    /// `auto` (Unicode unless the Windows console's code page lacks it), `unicode` or `ascii`
+    #[serde(default)]
+    pub glyphs: GlyphMode,
+    /// The terminal lays out right-to-left text itself (e.g. Konsole, mlterm), so the client
+    /// draws it in logical order
+    #[serde(default)]
+    pub terminal_bidi: bool,
+    /// Identities run alongside `username`, each with its own delivery service connection
+    #[serde(default)]
+    pub accounts: Vec<AccountConfig>,
+}
+
+/// Destructive actions that ask for confirmation before they run.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Confirmable {
+    Kick,
+    Leave,
+    Forget,
+    Wipe,
+    Redact,
+}
+
+impl Confirmable {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Confirmable::Kick => "kick",
+            Confirmable::Leave => "leave",
+            Confirmable::Forget => "forget",
+            Confirmable::Wipe => "wipe",
+            Confirmable::Redact => "redact",
+        }
+    }
+}
+
+/// A section of the group list holding groups by ID.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GroupFolder {
+    pub name: String,
+    #[serde(default)]
+    pub collapsed: bool,
+    #[serde(default)]
+    pub groups: Vec<String>,
+}
+
+/// An additional identity, shown as its own section of the group list.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AccountConfig {
+    /// Short label such as "work"; also names the account's data directory
+    pub name: String,
+    pub username: String,
+    pub delivery_service_address: String,
+    #[serde(default)]
+    pub delivery_service_fallbacks: Vec<String>,
+    #[serde(default)]
+    pub attachment_storage: AttachmentStorage,
+}
+
+impl AccountConfig {
+    /// `delivery_service_address` followed by the fallbacks, for `NetworkClient::new`.
+    pub fn delivery_service_addresses(&self) -> Vec<String> {
+        std::iter::once(self.delivery_service_address.clone()).chain(self.delivery_service_fallbacks.iter().cloned()).collect()
+    }
+
+    pub fn data_dir(&self) -> PathBuf {
+        Path::new(ACCOUNTS_DIR).join(&self.name)
+    }
+
+    /// Where this account keeps `file` (e.g. `HISTORY_PATH`) instead of the top-level copy.
+    pub fn data_path(&self, file: &str) -> PathBuf {
+        self.data_dir().join(file)
+    }
+}
+
+/// A local-time window, e.g. 22:00 to 07:00; `end` before `start` wraps past midnight.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct QuietHours {
+    pub start: NaiveTime,
+    pub end: NaiveTime,
+}
+
+impl QuietHours {
+    pub fn contains(&self, time: NaiveTime) -> bool {
+        if self.start <= self.end {
+            time >= self.start && time < self.end
+        } else {
+            time >= self.start || time < self.end
+        }
+    }
+}
+
+fn default_terminal_title() -> bool {
+    true
+}
+
+fn default_dnd_allow_mentions() -> bool {
+    true
+}
+
+fn default_compress_payloads() -> bool {
+    true
+}
+
+fn default_chunk_threshold_bytes() -> usize {
+    16 * 1024
+}
+
+fn default_max_message_bytes() -> usize {
+    64 * 1024
+}
+
+fn default_max_attachment_bytes() -> usize {
+    25 * 1024 * 1024
+}
+
+fn default_invite_batch_size() -> usize {
+    10
+}
+
+fn default_memory_budget_mb() -> u64 {
+    128
+}
+
+fn default_inactive_member_days() -> u64 {
+    30
+}
+
+fn default_inactive_group_days() -> u64 {
+    90
+}
+
+fn default_clock_skew_threshold_secs() -> u64 {
+    300
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            username: "user".to_string(),
+            delivery_service_address: "127.0.0.1:8080".to_string(),
+            delivery_service_fallbacks: Vec::new(),
+            device_id: uuid::Uuid::new_v4().to_string(),
+            auto_lock_minutes: 0,
+            clock_skew_threshold_secs: default_clock_skew_threshold_secs(),
+            chunk_threshold_bytes: default_chunk_threshold_bytes(),
+            compress_payloads: default_compress_payloads(),
+            max_message_bytes: default_max_message_bytes(),
+            max_attachment_bytes: default_max_attachment_bytes(),
+            do_not_disturb: false,
+            quiet_hours: None,
+            dnd_allow_mentions: default_dnd_allow_mentions(),
+            sounds: SoundConfig::default(),
+            desktop_notifications: false,
+            terminal_title: default_terminal_title(),
+            mention_bell: false,
+            push_relay: None,
+            templates: BTreeMap::new(),
+            verified_keys: BTreeMap::new(),
+            insecure_endpoints: BTreeMap::new(),
+            pinned_server_keys: BTreeMap::new(),
+            bridged_senders: Vec::new(),
+            trusted_senders: BTreeMap::new(),
+            folders: Vec::new(),
+            share_history_on_invite: 0,
+            invite_batch_size: default_invite_batch_size(),
+            default_wire_policy: WirePolicy::default(),
+            debug_mode: false,
+            encryption_preview: false,
+            low_bandwidth: false,
+            undo_send_secs: 0,
+            dont_ask: BTreeSet::new(),
+            memory_budget_mb: default_memory_budget_mb(),
+            inactive_member_days: default_inactive_member_days(),
+            inactive_group_days: default_inactive_group_days(),
+            filters: Vec::new(),
+            handshake_policy: HandshakePolicy::default(),
+            translation: None,
+            speech: None,
+            link_previews: None,
+            backups: None,
+            attachment_storage: AttachmentStorage::default(),
+            glyphs: GlyphMode::default(),
+            terminal_bidi: false,
+            accounts: Vec::new(),
+        }
+    }
+}
+
+/// Fields a config file must set; every other field has a default.
+const REQUIRED_FIELDS: &[&str] = &["username", "delivery_service_address"];
+/// Longest undo window `undo_send_secs` may set.
+pub const MAX_UNDO_SEND_SECS: u64 = 10;
+
+/// A problem found in the config file, by top-level field where there is one.
+#[derive(Debug, Clone)]
+pub struct ConfigIssue {
+    pub field: Option<String>,
+    pub message: String,
+}
+
+impl fmt::Display for ConfigIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.field {
+            Some(field) => write!(f, "{}: {}", field, self.message),
+            None => write!(f, "{}", self.message),
+        }
+    }
+}
+
+impl Config {
+    /// `delivery_service_address` followed by the fallbacks, for `NetworkClient::new`.
+    pub fn delivery_service_addresses(&self) -> Vec<String> {
+        std::iter::once(self.delivery_service_address.clone()).chain(self.delivery_service_fallbacks.iter().cloned()).collect()
+    }
+
+    pub async fn load_or_default() -> Result<Self, ConfigError> {
+        if vault::locate(CONFIG_PATH).exists() {
+            let content = vault::read_to_string(CONFIG_PATH).await?;
+            Ok(serde_json::from_str(&content)?)
+        } else {
+            let config = Config::default();
+            config.save().await?;
+            Ok(config)
+        }
+    }
+
+    /// Load the config, tolerating problems: fields that fail validation fall back to their
+    /// defaults and are returned as issues instead of stopping the client.
+    pub async fn load_checked() -> Result<(Self, Vec<ConfigIssue>), ConfigError> {
+        if !vault::locate(CONFIG_PATH).exists() {
+            return Ok((Self::load_or_default().await?, Vec::new()));
+        }
+        let content = vault::read_to_string(CONFIG_PATH).await?;
+        let issues = check(&content);
+        if issues.is_empty() {
+            return Ok((serde_json::from_str(&content)?, issues));
+        }
+        Ok((recover(&content), issues))
+    }
+
+    /// The config a new decoy profile starts with: this one's connection and display
+    /// settings, without the templates, contacts, rules and accounts that show who the real
+    /// profile talks to.
+    pub fn decoy(&self) -> Self {
+        Config {
+            push_relay: None,
+            templates: BTreeMap::new(),
+            verified_keys: BTreeMap::new(),
+            bridged_senders: Vec::new(),
+            trusted_senders: BTreeMap::new(),
+            folders: Vec::new(),
+            filters: Vec::new(),
+            translation: self.translation.clone().map(|mut translation| {
+                translation.auto_groups.clear();
+                translation
+            }),
+            speech: self.speech.clone().map(|mut speech| {
+                speech.groups.clear();
+                speech
+            }),
+            link_previews: self.link_previews.clone().map(|mut previews| {
+                previews.groups.clear();
+                previews
+            }),
+            accounts: Vec::new(),
+            backups: None,
+            ..self.clone()
+        }
+    }
+
+    /// A copy with the setting at dotted `path` (e.g. `sounds.message`) set to `raw`, read as
+    /// JSON when it parses and as a string otherwise. The result is validated like a loaded file.
+    pub fn with_setting(&self, path: &str, raw: &str) -> Result<Config, ConfigIssue> {
+        let issue = |message: String| ConfigIssue { field: Some(path.to_string()), message };
+        let Ok(Value::Object(current)) = serde_json::to_value(self) else {
+            return Err(issue("could not encode the current config".to_string()));
+        };
+        let (top, rest) = path.split_once('.').map_or((path, None), |(top, rest)| (top, Some(rest)));
+        let Some(mut field) = current.get(top).cloned() else {
+            return Err(issue(match closest_field(&current, top) {
+                Some(known) => format!("unknown field (did you mean `{}`?)", known),
+                None => "unknown field".to_string(),
+            }));
+        };
+        let value = serde_json::from_str(raw).unwrap_or_else(|_| Value::String(raw.to_string()));
+        match rest {
+            None => field = value.clone(),
+            Some(rest) => {
+                let keys: Vec<String> = rest.split('.').map(|key| key.replace('~', "~0").replace('/', "~1")).collect();
+                let pointer = format!("/{}", keys.join("/"));
+                if let Some(target) = field.pointer_mut(&pointer) {
+                    *target = value.clone();
+                } else {
+                    // A new entry in a map, e.g. a per-group sound
+                    let (parent, key) = pointer.rsplit_once('/').unwrap_or_default();
+                    match field.pointer_mut(parent) {
+                        Some(Value::Object(map)) => {
+                            map.insert(key.replace("~1", "/").replace("~0", "~"), value.clone());
+                        }
+                        _ => return Err(issue("no such setting".to_string())),
+                    }
+                }
+            }
+        }
+        check_field(&current, top, &field).map_err(issue)?;
+        let mut updated = current;
+        updated.insert(top.to_string(), field);
+        let config: Config = serde_json::from_value(Value::Object(updated)).map_err(|e| issue(e.to_string()))?;
+        // Misspelled keys inside a struct are dropped when decoding rather than rejected
+        let pointer = format!("/{}", path.replace('~', "~0").replace('/', "~1").replace('.', "/"));
+        if serde_json::to_value(&config).ok().and_then(|encoded| encoded.pointer(&pointer).cloned()).is_none() {
+            return Err(issue("no such setting".to_string()));
+        }
+        Ok(config)
+    }
+
+    pub async fn save(&self) -> Result<(), ConfigError> {
+        let content = serde_json::to_string_pretty(self)?;
+        vault::write(CONFIG_PATH, content).await?;
+        Ok(())
+    }
+}
+
+/// Validate a config file field by field.
+pub fn check(content: &str) -> Vec<ConfigIssue> {
+    let issue = |field: Option<&str>, message: String| ConfigIssue { field: field.map(str::to_string), message };
+    let fields = match serde_json::from_str::<Value>(content) {
+        Ok(Value::Object(fields)) => fields,
+        Ok(_) => return vec![issue(None, "must be a JSON object".to_string())],
+        Err(e) => return vec![issue(None, format!("not valid JSON: {}", e))],
+    };
+    let defaults = default_fields();
+    let mut issues: Vec<ConfigIssue> = REQUIRED_FIELDS
+        .iter()
+        .filter(|field| !fields.contains_key(**field))
+        .map(|field| issue(Some(field), "missing (required)".to_string()))
+        .collect();
+    for (name, value) in &fields {
+        if !defaults.contains_key(name) {
+            let message = match closest_field(&defaults, name) {
+                Some(known) => format!("unknown field (did you mean `{}`?)", known),
+                None => "unknown field".to_string(),
+            };
+            issues.push(issue(Some(name), message));
+        } else if let Err(message) = check_field(&defaults, name, value) {
+            issues.push(issue(Some(name), message));
+        }
+    }
+    issues
+}
+
+/// The config `content` describes with every invalid or unknown field dropped, so defaults
+/// take their place. Unreadable files yield the defaults.
+pub fn recover(content: &str) -> Config {
+    let mut recovered = default_fields();
+    if let Ok(Value::Object(fields)) = serde_json::from_str::<Value>(content) {
+        for (name, value) in fields {
+            if recovered.contains_key(&name) && check_field(&recovered, &name, &value).is_ok() {
+                recovered.insert(name, value);
+            }
+        }
+    }
+    serde_json::from_value(Value::Object(recovered)).unwrap_or_default()
+}
+
+/// Check a `host:port` delivery service address.
+pub fn check_address(address: &str) -> Result<(), String> {
+    let expected = "expected host:port, e.g. 127.0.0.1:8080";
+    let Some((host, port)) = address.rsplit_once(':') else {
+        return Err(format!("`{}` has no port; {}", address, expected));
+    };
+    if host.is_empty() {
+        return Err(format!("`{}` has no host; {}", address, expected));
+    }
+    match port.parse::<u16>() {
+        Ok(port) if port > 0 => Ok(()),
+        _ => Err(format!("`{}` is not a valid port; {}", port, expected)),
+    }
+}
+
+fn default_fields() -> Map<String, Value> {
+    match serde_json::to_value(Config::default()) {
+        Ok(Value::Object(fields)) => fields,
+        _ => Map::new(),
+    }
+}
+
+/// Deserialize the defaults with `name` replaced, so type errors are attributed to that field.
+fn check_field(defaults: &Map<String, Value>, name: &str, value: &Value) -> Result<(), String> {
+    let mut candidate = defaults.clone();
+    candidate.insert(name.to_string(), value.clone());
+    serde_json::from_value::<Config>(Value::Object(candidate)).map_err(|e| e.to_string())?;
+    match (name, value) {
+        ("username", Value::String(username)) if username.trim().is_empty() => Err("must not be empty".to_string()),
+        ("delivery_service_address", Value::String(address)) => check_address(address),
+        ("delivery_service_fallbacks", Value::Array(addresses)) => {
+            addresses.iter().filter_map(Value::as_str).try_for_each(check_address)
+        }
+        ("push_relay", Value::String(url)) if !url.starts_with("https://") && !url.starts_with("http://") => {
+            Err(format!("`{}` is not an http(s) URL", url))
+        }
+        ("undo_send_secs", Value::Number(secs)) if secs.as_u64().is_some_and(|secs| secs > MAX_UNDO_SEND_SECS) => {
+            Err(format!("must be 0 to {} seconds", MAX_UNDO_SEND_SECS))
+        }
+        ("invite_batch_size", Value::Number(size)) if size.as_u64() == Some(0) => Err("must be at least 1".to_string()),
+        ("accounts", accounts) => check_accounts(accounts),
+        ("handshake_policy", policy) => serde_json::from_value::<HandshakePolicy>(policy.clone())
+            .map_err(|e| e.to_string())?
+            .validate(),
+        ("translation", translation) => match serde_json::from_value::<Option<TranslationConfig>>(translation.clone()).map_err(|e| e.to_string())? {
+            Some(translation) => translation.validate(),
+            None => Ok(()),
+        },
+        ("speech", speech) => match serde_json::from_value::<Option<SpeechConfig>>(speech.clone()).map_err(|e| e.to_string())? {
+            Some(speech) => speech.validate(),
+            None => Ok(()),
+        },
+        ("backups", backups) => match serde_json::from_value::<Option<BackupConfig>>(backups.clone()).map_err(|e| e.to_string())? {
+            Some(backups) => backups.validate(),
+            None => Ok(()),
+        },
+        ("attachment_storage", storage) => serde_json::from_value::<AttachmentStorage>(storage.clone())
+            .map_err(|e| e.to_string())?
+            .validate(),
+        ("link_previews", previews) => match serde_json::from_value::<Option<LinkPreviewConfig>>(previews.clone()).map_err(|e| e.to_string())? {
+            Some(previews) => previews.validate(),
+            None => Ok(()),
+        },
+        _ => Ok(()),
+    }
+}
+
+/// Account names must be usable as directory names and distinct from each other.
+fn check_accounts(accounts: &Value) -> Result<(), String> {
+    let accounts: Vec<AccountConfig> = serde_json::from_value(accounts.clone()).map_err(|e| e.to_string())?;
+    for (index, account) in accounts.iter().enumerate() {
+        check_account_name(&account.name)?;
+        if accounts[..index].iter().any(|earlier| earlier.name == account.name) {
+            return Err(format!("account `{}` is listed twice", account.name));
+        }
+        if account.username.trim().is_empty() {
+            return Err(format!("account `{}` has an empty username", account.name));
+        }
+        account
+            .delivery_service_addresses()
+            .iter()
+            .try_for_each(|address| check_address(address))
+            .map_err(|e| format!("account `{}`: {}", account.name, e))?;
+        account.attachment_storage.validate().map_err(|e| format!("account `{}`: {}", account.name, e))?;
+    }
+    Ok(())
+}
+
+/// Check an account name: letters, digits, `-` and `_` only, and not a name Windows reserves.
+pub fn check_account_name(name: &str) -> Result<(), String> {
+    if name.is_empty() || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_') {
+        return Err(format!("`{}` is not a valid account name; use letters, digits, - and _", name));
+    }
+    if platform::reserved_file_name(name) {
+        return Err(format!("`{}` is a reserved device name on Windows; choose another account name", name));
+    }
+    Ok(())
+}
+
+/// The known field within two edits of `name`, for typo suggestions.
+fn closest_field<'a>(defaults: &'a Map<String, Value>, name: &str) -> Option<&'a str> {
+    defaults
+        .keys()
+        .map(|known| (edit_distance(known, name), known))
+        .filter(|(distance, _)| *distance <= 2)
+        .min_by_key(|(distance, _)| *distance)
+        .map(|(_, known)| known.as_str())
+}
+
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut diagonal = row[0];
+        row[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let above = row[j + 1];
+            row[j + 1] = (above + 1).min(row[j] + 1).min(diagonal + usize::from(ca != *cb));
+            diagonal = above;
+        }
+    }
+    row[b.len()]
+}
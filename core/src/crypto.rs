@@ -0,0 +1,91 @@
+use hmac::{Hmac, Mac};
+use openmls_rust_crypto::OpenMlsRustCrypto;
+use sha2::Sha256;
+use openmls_traits::{crypto::OpenMlsCrypto, random::OpenMlsRand, types::{AeadType, HashType, SignatureScheme}, OpenMlsProvider};
+
+use crate::errors::CryptoError;
+
+pub struct CryptoProvider {
+    provider: OpenMlsRustCrypto,
+}
+
+impl CryptoProvider {
+    pub fn new() -> Self {
+        Self {
+            provider: OpenMlsRustCrypto::default(),
+        }
+    }
+
+    pub fn provider(&self) -> &OpenMlsRustCrypto {
+        &self.provider
+    }
+
+    /// SHA-256 digest of `data`.
+    pub fn sha256(&self, data: &[u8]) -> Result<Vec<u8>, CryptoError> {
+        self.provider
+            .crypto()
+            .hash(HashType::Sha2_256, data)
+            .map_err(CryptoError::Hash)
+    }
+
+    /// SHA-256 of `data` as hex in groups of four, for reading aloud when verifying out-of-band.
+    pub fn fingerprint(&self, data: &[u8]) -> Result<String, CryptoError> {
+        let hex = to_hex(&self.sha256(data)?);
+        let groups: Vec<&str> = hex
+            .as_bytes()
+            .chunks(4)
+            .map(|group| std::str::from_utf8(group).unwrap_or_default())
+            .collect();
+        Ok(groups.join(" "))
+    }
+
+    /// ChaCha20-Poly1305 encryption under a 32-byte `key` and 12-byte `nonce`, for data kept
+    /// outside MLS (the tag is appended).
+    pub fn seal(&self, key: &[u8], nonce: &[u8], aad: &[u8], data: &[u8]) -> Result<Vec<u8>, CryptoError> {
+        self.provider
+            .crypto()
+            .aead_encrypt(AeadType::ChaCha20Poly1305, key, data, nonce, aad)
+            .map_err(CryptoError::Seal)
+    }
+
+    /// Reverse `seal`, failing if the data or `aad` was changed.
+    pub fn open(&self, key: &[u8], nonce: &[u8], aad: &[u8], sealed: &[u8]) -> Result<Vec<u8>, CryptoError> {
+        self.provider
+            .crypto()
+            .aead_decrypt(AeadType::ChaCha20Poly1305, key, sealed, nonce, aad)
+            .map_err(CryptoError::Open)
+    }
+
+    /// Check an Ed25519 `signature` over `data` by `public_key`.
+    pub fn verify_ed25519(&self, public_key: &[u8], data: &[u8], signature: &[u8]) -> Result<(), CryptoError> {
+        self.provider
+            .crypto()
+            .verify_signature(SignatureScheme::ED25519, data, public_key, signature)
+            .map_err(CryptoError::Signature)
+    }
+
+    pub fn random_bytes(&self, len: usize) -> Result<Vec<u8>, CryptoError> {
+        self.provider
+            .rand()
+            .random_vec(len)
+            .map_err(CryptoError::Random)
+    }
+}
+
+/// HMAC-SHA256 of `data` under `key`.
+pub fn hmac_sha256(key: &[u8], data: &[u8]) -> Option<Vec<u8>> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(key).ok()?;
+    mac.update(data);
+    Some(mac.finalize().into_bytes().to_vec())
+}
+
+/// Lowercase hex rendering for digests and fingerprints.
+pub fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+impl Default for CryptoProvider {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,498 @@
+use thiserror::Error;
+use crate::config::CONFIG_PATH;
+use crate::instance::LOCK_PATH;
+
+/// A user-facing failure: a code and one-line summary for the status bar, plus the
+/// explanation and remediation shown by `errors <code>` / `details`.
+#[derive(Debug)]
+pub struct ErrorCode {
+    pub code: &'static str,
+    pub summary: &'static str,
+    pub explanation: &'static [&'static str],
+}
+
+pub const NOT_CONNECTED: ErrorCode = ErrorCode {
+    code: "E001",
+    summary: "Not connected to MLS service",
+    explanation: &[
+        "The operation needs the delivery service, but the client is offline.",
+        "",
+        "Try:",
+        "  - Check the connection with 'status'",
+        "  - Verify the delivery service address in settings (s)",
+        "  - Make sure the delivery service is running and reachable",
+    ],
+};
+
+pub const GROUP_NOT_FOUND: ErrorCode = ErrorCode {
+    code: "E002",
+    summary: "Group not found or access denied",
+    explanation: &[
+        "The delivery service did not return a Welcome for this group. This could mean:",
+        "  1. The group doesn't exist on the MLS service",
+        "  2. You don't have permission to join",
+        "  3. The MLS service is not properly configured",
+        "",
+        "Try:",
+        "  - Create the group first with 'create <group_name>'",
+        "  - Ask a member to invite you",
+        "  - Check your MLS service configuration",
+    ],
+};
+
+pub const JOIN_FAILED: ErrorCode = ErrorCode {
+    code: "E003",
+    summary: "Join request failed",
+    explanation: &[
+        "The join request could not be delivered. This could be due to:",
+        "  1. Network connectivity issues",
+        "  2. MLS service not running",
+        "  3. Invalid group ID",
+        "",
+        "Try:",
+        "  - Check the connection with 'status'",
+        "  - Double-check the group ID",
+    ],
+};
+
+pub const INVALID_WELCOME: ErrorCode = ErrorCode {
+    code: "E004",
+    summary: "Invalid Welcome message",
+    explanation: &[
+        "The delivery service answered the join, but the Welcome could not be parsed.",
+        "The group may use a protocol version or ciphersuite this client does not support,",
+        "or the message was corrupted in transit.",
+        "",
+        "Try:",
+        "  - Join again",
+        "  - Ask the inviter which client and ciphersuite the group uses",
+    ],
+};
+
+pub const SEND_FAILED: ErrorCode = ErrorCode {
+    code: "E005",
+    summary: "Message could not be sent",
+    explanation: &[
+        "Encrypting or queueing the message failed; it was not added to the timeline.",
+        "",
+        "Try:",
+        "  - Check the connection with 'status'",
+        "  - Send the message again",
+    ],
+};
+
+pub const PUBLISH_FAILED: ErrorCode = ErrorCode {
+    code: "E006",
+    summary: "Group created locally but not published",
+    explanation: &[
+        "The group exists on this device, but the delivery service did not accept it,",
+        "so other members cannot join yet.",
+        "",
+        "Try:",
+        "  - Check the connection with 'status'",
+        "  - Recreate the group once connected",
+    ],
+};
+
+pub const MESSAGE_TOO_LARGE: ErrorCode = ErrorCode {
+    code: "E007",
+    summary: "Message too large",
+    explanation: &[
+        "The message is larger than the limit set by max_message_bytes in config.json,",
+        "or by the delivery service if it advertises a lower one. It was not sent and",
+        "is still in the composer.",
+        "",
+        "Try:",
+        "  - Shorten the message or split it into several",
+        "  - Raise max_message_bytes if the delivery service accepts larger frames",
+    ],
+};
+
+pub const WIRE_FORMAT_REJECTED: ErrorCode = ErrorCode {
+    code: "E008",
+    summary: "Message rejected by the group's wire format policy",
+    explanation: &[
+        "A member sent a handshake message as PublicMessage (or PrivateMessage) but this",
+        "group only accepts the other format. Members disagree on the policy, usually",
+        "because the group was joined without the policy from its invitation.",
+        "",
+        "Try:",
+        "  - Ask the group creator which policy the group uses ('create <name> <policy>')",
+        "  - Rejoin from the invitation so the announced policy is applied",
+        "  - Set default_wire_policy in config.json to match your delivery service",
+    ],
+};
+
+pub const INVITE_TOKEN_REJECTED: ErrorCode = ErrorCode {
+    code: "E009",
+    summary: "Invite token rejected",
+    explanation: &[
+        "The delivery service did not accept the invite token. Tokens admit one person,",
+        "once, and are only issued by servers that support them.",
+        "",
+        "Try:",
+        "  - Check that the token was copied completely",
+        "  - Ask the admin for a new token with 'invite-token'",
+        "  - Ask the admin to invite you directly with 'invite <identity>'",
+    ],
+};
+
+pub const ALL: &[&ErrorCode] = &[
+    &NOT_CONNECTED,
+    &GROUP_NOT_FOUND,
+    &JOIN_FAILED,
+    &INVALID_WELCOME,
+    &SEND_FAILED,
+    &PUBLISH_FAILED,
+    &MESSAGE_TOO_LARGE,
+    &WIRE_FORMAT_REJECTED,
+    &INVITE_TOKEN_REJECTED,
+];
+
+pub fn lookup(code: &str) -> Option<&'static ErrorCode> {
+    ALL.iter().copied().find(|error| error.code.eq_ignore_ascii_case(code))
+}
+
+/// Failures talking to the delivery service. Every variant means the connection is not
+/// usable, so callers can reconnect and retry.
+#[derive(Debug, Error)]
+pub enum NetworkError {
+    #[error("Not connected to delivery service")]
+    NotConnected,
+    #[error("Send queue closed")]
+    QueueClosed,
+    #[error("Delivery service connection failed: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Could not encode frame: {0}")]
+    Encode(#[from] serde_json::Error),
+}
+
+/// Failures in local MLS group state or protocol processing.
+#[derive(Debug, Error)]
+pub enum MlsError {
+    /// We hold no MLS state for the group (e.g. after a restart); rejoining restores it.
+    #[error("No MLS state for group {0}")]
+    UnknownGroup(String),
+    #[error("{identity} is not a member of group {group_id}")]
+    NotAMember { group_id: String, identity: String },
+    #[error("Expected a Welcome message")]
+    NotAWelcome,
+    #[error("No identity rotation in progress")]
+    NoRotation,
+    /// The group's wire format policy does not accept this message's format.
+    #[error("{wire_format} rejected by the {policy} wire format policy of group {group_id}")]
+    WireFormatRejected { group_id: String, wire_format: &'static str, policy: &'static str },
+    /// The message belongs to an epoch we are not in, yet or any more.
+    #[error("Message for group {group_id} is from another epoch")]
+    WrongEpoch { group_id: String },
+    /// The sender's leaf is not in our view of the group.
+    #[error("Message for group {group_id} is from an unknown sender")]
+    UnknownSender { group_id: String },
+    /// A Welcome or commit brings in a credential the handshake policy rejects; nothing was applied.
+    #[error("Handshake policy violation in group {group_id}: {reason}")]
+    PolicyViolation { group_id: String, reason: String },
+    /// An exported ratchet tree could not be decoded; names what was wrong.
+    #[error("Malformed ratchet tree: {0}")]
+    MalformedTree(&'static str),
+    /// An openmls operation failed; `operation` names it.
+    #[error("{operation} failed: {source}")]
+    Protocol {
+        operation: &'static str,
+        #[source]
+        source: Box<dyn std::error::Error + Send + Sync>,
+    },
+}
+
+/// Failures reading or writing persisted history and transfer state.
+#[derive(Debug, Error)]
+pub enum StorageError {
+    #[error("Failed to access {path}: {source}")]
+    Io {
+        path: String,
+        #[source]
+        source: std::io::Error,
+    },
+    #[error("Invalid data in {path}: {source}")]
+    Invalid {
+        path: String,
+        #[source]
+        source: serde_json::Error,
+    },
+}
+
+impl StorageError {
+    pub fn io(path: impl Into<String>) -> impl FnOnce(std::io::Error) -> Self {
+        let path = path.into();
+        move |source| StorageError::Io { path, source }
+    }
+
+    pub fn invalid(path: impl Into<String>) -> impl FnOnce(serde_json::Error) -> Self {
+        let path = path.into();
+        move |source| StorageError::Invalid { path, source }
+    }
+}
+
+/// Failures of the crypto provider on data kept outside MLS: digests, sealed local files,
+/// signatures and random bytes.
+#[derive(Debug, Error)]
+pub enum CryptoError {
+    #[error("Hashing failed: {0}")]
+    Hash(#[source] openmls_traits::types::CryptoError),
+    #[error("Encryption failed: {0}")]
+    Seal(#[source] openmls_traits::types::CryptoError),
+    /// Wrong key, or the data or its additional data was changed.
+    #[error("Decryption failed: {0}")]
+    Open(#[source] openmls_traits::types::CryptoError),
+    #[error("Signature verification failed: {0}")]
+    Signature(#[source] openmls_traits::types::CryptoError),
+    #[error("Random generation failed: {0}")]
+    Random(#[source] openmls_traits::types::CryptoError),
+    #[error("Key derivation failed: {0}")]
+    Derive(#[source] argon2::Error),
+}
+
+#[derive(Debug, Error)]
+pub enum CompressionError {
+    /// Not zstd, or it would decompress to more than the client accepts.
+    #[error("Could not decompress payload: {0}")]
+    Decompress(#[source] std::io::Error),
+}
+
+/// Failures splitting a payload into chunks or putting one back together.
+#[derive(Debug, Error)]
+pub enum ChunkError {
+    #[error("Payload needs {needed} chunks, more than the {allowed} allowed; raise chunk_threshold_bytes")]
+    TooManyChunks { needed: usize, allowed: u32 },
+    /// The slice does not match its binding to the parent message and position.
+    #[error("Chunk {index} of message {parent_id} failed integrity check")]
+    Integrity { parent_id: String, index: u32 },
+    /// The slice came from another sender or group, or describes another payload.
+    #[error("Chunk {index} does not match the rest of message {parent_id}")]
+    Mismatch { parent_id: String, index: u32 },
+    #[error("Reassembled message {0} failed integrity check")]
+    Reassembled(String),
+    #[error("Malformed chunk: {0}")]
+    Malformed(#[from] serde_json::Error),
+    #[error(transparent)]
+    Crypto(#[from] CryptoError),
+}
+
+/// Why a commit was not added to a group's membership log.
+#[derive(Debug, Error)]
+pub enum AuditError {
+    #[error("the log was altered at epoch {epoch}")]
+    Altered { epoch: u64 },
+    #[error("epoch {epoch} is older than the newest logged epoch {newest}")]
+    OutOfOrder { epoch: u64, newest: u64 },
+}
+
+/// Failures moving messages and commits between MLS and the delivery service.
+#[derive(Debug, Error)]
+pub enum PipelineError {
+    #[error(transparent)]
+    Mls(#[from] MlsError),
+    #[error(transparent)]
+    Network(#[from] NetworkError),
+    #[error("Could not encode payload: {0}")]
+    Encode(#[from] serde_json::Error),
+    #[error(transparent)]
+    Split(#[from] ChunkError),
+    /// A slice of an incoming chunked message was dropped; the frame is still taken as seen.
+    #[error("Dropped chunk from {sender}: {source}")]
+    Reassemble {
+        sender: String,
+        #[source]
+        source: ChunkError,
+    },
+    #[error("Payload of {size} bytes exceeds the {limit} byte attachment limit")]
+    TooLarge { size: usize, limit: usize },
+    #[error("not every member of the group reads typed payloads")]
+    Untyped,
+    /// The group's name; it is read-only from now on.
+    #[error("{0} no longer exists on the delivery service")]
+    Deleted(String),
+    /// The ID sealed in the payload is not the frame's, so the frame is not taken as seen.
+    #[error("its ID was changed in transit")]
+    IdChanged,
+    /// The group's rules do not let `sender` send this content; nothing of it was applied.
+    #[error("Rejected {kind} content from {sender}: only admins may send it")]
+    NotAllowed { kind: String, sender: String },
+    /// An identity rotation needs a group whose leaf moves to the new key.
+    #[error("not a member of any group")]
+    NoGroups,
+}
+
+/// Failures taking the data directory's instance lock.
+#[derive(Debug, Error)]
+pub enum InstanceError {
+    #[error("Another instance{} is already using this data directory. Close it first, or use --attach if it is a daemon.", .pid.map(|pid| format!(" (PID {})", pid)).unwrap_or_default())]
+    Running { pid: Option<u32> },
+    #[error("Failed to access {}: {0}", LOCK_PATH)]
+    Io(#[from] std::io::Error),
+}
+
+/// Failures upgrading the data directory to the current schema version.
+#[derive(Debug, Error)]
+pub enum MigrationError {
+    #[error("Stored data is version {found}, but this client only supports up to version {supported}; update the client")]
+    TooNew { found: u32, supported: u32 },
+    #[error("Could not back up or restore data files: {0}")]
+    Backup(#[source] StorageError),
+    #[error("Upgrade to version {version} ({description}) failed: {source}. Your files were restored from {backup}")]
+    Failed {
+        version: u32,
+        description: &'static str,
+        backup: String,
+        #[source]
+        source: StorageError,
+    },
+    #[error(transparent)]
+    Storage(#[from] StorageError),
+}
+
+/// Failures writing a transcript export.
+#[derive(Debug, Error)]
+pub enum ExportError {
+    #[error("{0} already exists; choose another file name")]
+    Exists(String),
+    #[error("Failed to write {path}: {source}")]
+    Io {
+        path: String,
+        #[source]
+        source: std::io::Error,
+    },
+    #[error("Could not run {tool} (is it installed?): {source}")]
+    Spawn {
+        tool: &'static str,
+        #[source]
+        source: std::io::Error,
+    },
+    #[error("{tool} failed: {stderr}")]
+    Encrypt { tool: &'static str, stderr: String },
+}
+
+/// Failures moving a file to or from an SFTP, WebDAV or S3 store.
+#[derive(Debug, Error)]
+pub enum RemoteError {
+    #[error("Could not run {tool} (is it installed?): {source}")]
+    Spawn {
+        tool: &'static str,
+        #[source]
+        source: std::io::Error,
+    },
+    #[error("{tool} failed: {stderr}")]
+    Failed { tool: &'static str, stderr: String },
+    #[error(transparent)]
+    Storage(#[from] StorageError),
+}
+
+/// Failures storing or fetching an attachment kept outside the delivery service.
+#[derive(Debug, Error)]
+pub enum AttachmentError {
+    #[error(transparent)]
+    Remote(#[from] RemoteError),
+    #[error(transparent)]
+    Storage(#[from] StorageError),
+    #[error("Could not encrypt or decrypt the attachment: {0}")]
+    Crypto(String),
+    #[error("The fetched attachment does not match its message")]
+    Mismatch,
+    #[error("Attachment of {size} bytes exceeds the {limit} byte attachment limit")]
+    TooLarge { size: usize, limit: usize },
+}
+
+/// Failures making, uploading or restoring an encrypted backup.
+#[derive(Debug, Error)]
+pub enum BackupError {
+    #[error("No backups configured; set `backups` in config.json")]
+    NotConfigured,
+    #[error(transparent)]
+    Storage(#[from] StorageError),
+    #[error(transparent)]
+    Encrypt(#[from] ExportError),
+    #[error("Could not run {tool} (is it installed?): {source}")]
+    Spawn {
+        tool: &'static str,
+        #[source]
+        source: std::io::Error,
+    },
+    #[error("{tool} failed: {stderr}")]
+    Failed { tool: &'static str, stderr: String },
+    #[error(transparent)]
+    Remote(#[from] RemoteError),
+    #[error("{0} is not a backup made by this client (or was not decrypted)")]
+    Invalid(String),
+    #[error("The backup holds {0}, which is outside the data directory")]
+    UnsafePath(String),
+    #[error("{0} already exists; restore into a fresh data directory, or move it away first")]
+    Exists(String),
+}
+
+/// Failures running the configured translator.
+#[derive(Debug, Error)]
+pub enum TranslateError {
+    #[error("No translator configured; set `translation` in config.json")]
+    NotConfigured,
+    #[error("Could not run {program} (is it installed?): {source}")]
+    Spawn {
+        program: String,
+        #[source]
+        source: std::io::Error,
+    },
+    #[error("{program} did not finish within {secs}s")]
+    Timeout { program: String, secs: u64 },
+    #[error("{program} failed: {stderr}")]
+    Failed { program: String, stderr: String },
+    #[error("{0} returned no translation")]
+    Empty(String),
+}
+
+#[derive(Debug, Error)]
+pub enum CommandError {
+    #[error("Unterminated {0} quote: close it, or escape it with a backslash")]
+    UnterminatedQuote(char),
+    #[error("Unknown option --{option} for {command}")]
+    UnknownOption { command: String, option: String },
+    #[error("Usage: {0}")]
+    Usage(&'static str),
+    #[error("Unknown command: {0}. Available commands: {commands}", commands = crate::command::COMMANDS.join(", "))]
+    Unknown(String),
+}
+
+#[derive(Debug, Error)]
+pub enum SearchError {
+    #[error("Nothing to search for: give words or filters such as from:alice, in:<group>, before:2024-01-01 or has:attachment")]
+    Empty,
+    #[error("{0}: needs a value")]
+    MissingValue(String),
+    #[error("{filter}:{value} is not a date; use YYYY-MM-DD")]
+    BadDate { filter: String, value: String },
+    #[error("has:{0} is not known; use has:attachment, has:link, has:reaction or has:pin")]
+    UnknownHas(String),
+    #[error("OR needs a search term on both sides")]
+    DanglingOr,
+    #[error("Search index failed: {0}")]
+    Index(#[from] rusqlite::Error),
+}
+
+#[derive(Debug, Error)]
+pub enum PreviewError {
+    #[error("Link previews need `link_previews` with a proxy in config.json")]
+    NotConfigured,
+    #[error("Could not run curl (is it installed?): {0}")]
+    Spawn(#[source] std::io::Error),
+    #[error("the page did not load within {0}s")]
+    Timeout(u64),
+    #[error("fetching failed: {0}")]
+    Failed(String),
+    #[error("the page has no title")]
+    NoMetadata,
+}
+
+#[derive(Debug, Error)]
+pub enum ConfigError {
+    #[error("Failed to access {}: {0}", CONFIG_PATH)]
+    Io(#[from] std::io::Error),
+    #[error("{} is not valid: {0}", CONFIG_PATH)]
+    Invalid(#[from] serde_json::Error),
+}
@@ -0,0 +1,136 @@
+use chrono::{Local, NaiveDate};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use std::process::Stdio;
+use tokio::io::AsyncWriteExt;
+use tokio::process::Command;
+use crate::errors::ExportError;
+use crate::group::{Group, Message};
+
+/// Which messages of a group a transcript covers; empty fields do not restrict it.
+#[derive(Debug, Clone, Default)]
+pub struct ExportRange {
+    /// First day included, by server receive time
+    pub from: Option<NaiveDate>,
+    /// Last day included
+    pub to: Option<NaiveDate>,
+    /// Senders to include, by identity
+    pub members: Vec<String>,
+}
+
+impl ExportRange {
+    fn includes(&self, message: &Message) -> bool {
+        let day = message.shown_time().date_naive();
+        self.from.iter().all(|from| day >= *from)
+            && self.to.iter().all(|to| day <= *to)
+            && (self.members.is_empty() || self.members.contains(&message.sender))
+    }
+
+    fn describe(&self) -> String {
+        let mut parts = Vec::new();
+        if let Some(from) = self.from {
+            parts.push(format!("from {}", from));
+        }
+        if let Some(to) = self.to {
+            parts.push(format!("to {}", to));
+        }
+        if !self.members.is_empty() {
+            parts.push(format!("members {}", self.members.join(", ")));
+        }
+        if parts.is_empty() {
+            "all messages".to_string()
+        } else {
+            parts.join(", ")
+        }
+    }
+}
+
+/// Public-key encryption applied before the transcript reaches the disk, written in the config
+/// as `{"age": "<recipient>"}` or `{"gpg": "<key>"}`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ExportEncryption {
+    /// An age recipient (`age1…` or an SSH public key)
+    Age(String),
+    /// A GPG key ID, fingerprint or email in the local keyring
+    Gpg(String),
+}
+
+impl ExportEncryption {
+    fn command(&self, path: &Path) -> (&'static str, Command) {
+        match self {
+            ExportEncryption::Age(recipient) => {
+                let mut command = Command::new("age");
+                command.arg("--recipient").arg(recipient).arg("--output").arg(path);
+                ("age", command)
+            }
+            ExportEncryption::Gpg(recipient) => {
+                let mut command = Command::new("gpg");
+                command.args(["--batch", "--encrypt", "--recipient"]).arg(recipient).arg("--output").arg(path);
+                ("gpg", command)
+            }
+        }
+    }
+}
+
+/// A plain-text transcript of the messages in `range`, with a header recording what was
+/// selected, and the number of messages it holds.
+pub fn transcript(group: &Group, range: &ExportRange, exported_by: &str) -> (String, usize) {
+    let messages: Vec<&Message> = group.messages.iter().filter(|message| range.includes(message)).collect();
+    let mut text = format!(
+        "# Transcript of {} ({})\n# Exported {} by {}: {} message(s), {}\n",
+        group.name,
+        group.id,
+        Local::now().format("%Y-%m-%d %H:%M:%S %z"),
+        exported_by,
+        messages.len(),
+        range.describe()
+    );
+    for message in &messages {
+        let shared = message.shared_by.as_ref().map(|by| format!(" (shared by {})", by)).unwrap_or_default();
+        let skewed = if message.clock_skewed {
+            format!(" (sender claimed {})", message.timestamp.format("%Y-%m-%d %H:%M:%S"))
+        } else {
+            String::new()
+        };
+        text.push_str(&format!(
+            "[{}]{}{} {}: {}\n",
+            message.shown_time().format("%Y-%m-%d %H:%M:%S"),
+            shared,
+            skewed,
+            message.sender,
+            message.content
+        ));
+    }
+    (text, messages.len())
+}
+
+/// Write `transcript` to a new file at `path`. When encrypting, the plain text only ever
+/// passes through the encryption tool's stdin; a failed run leaves no file behind.
+pub async fn write(path: &str, transcript: &str, encryption: Option<&ExportEncryption>) -> Result<(), ExportError> {
+    let target = Path::new(path);
+    if target.exists() {
+        return Err(ExportError::Exists(path.to_string()));
+    }
+    match encryption {
+        Some(encryption) => encrypt_to(target, transcript.as_bytes(), encryption).await,
+        None => tokio::fs::write(target, transcript).await.map_err(|source| ExportError::Io { path: path.to_string(), source }),
+    }
+}
+
+/// Encrypt `data` with `encryption`'s tool into `target`, which is removed again if it fails.
+pub async fn encrypt_to(target: &Path, data: &[u8], encryption: &ExportEncryption) -> Result<(), ExportError> {
+    let (tool, mut command) = encryption.command(target);
+    let spawned = command.stdin(Stdio::piped()).stdout(Stdio::null()).stderr(Stdio::piped()).spawn();
+    let mut child = spawned.map_err(|source| ExportError::Spawn { tool, source })?;
+    if let Some(mut stdin) = child.stdin.take() {
+        // A write error means the tool exited early; its status and stderr explain why
+        let _ = stdin.write_all(data).await;
+    }
+    let output = child.wait_with_output().await.map_err(|source| ExportError::Spawn { tool, source })?;
+    if output.status.success() {
+        return Ok(());
+    }
+    let _ = tokio::fs::remove_file(target).await;
+    Err(ExportError::Encrypt { tool, stderr: String::from_utf8_lossy(&output.stderr).trim().to_string() })
+}
@@ -0,0 +1,95 @@
+//! Client state carried in private-use MLS extensions, so every member reads it from the
+//! group state they agreed on instead of from frames the delivery service relays.
+//!
+//! The group's mode and admin list live in a group context extension. Only a commit changes
+//! them, and `MlsClient::process_incoming` refuses one whose committer was not an admin
+//! before it.
+//!
+//! Optional client features are extension types listed in each leaf's capabilities, which
+//! the member signs and every commit that sets the leaf authenticates: a feature is used in
+//! a group only once every leaf in its ratchet tree lists it.
+
+use openmls::prelude::{Capabilities, Extension, ExtensionType, Extensions, UnknownExtension};
+use serde::{Deserialize, Serialize};
+
+use crate::compression;
+use crate::group::GroupMode;
+use crate::payload;
+
+/// Group context extension holding the JSON-encoded `GroupState`.
+pub const GROUP_STATE: u16 = 0xff0a;
+
+/// Optional features and the extension type that stands for each in leaf capabilities.
+pub const FEATURES: [(&str, u16); 2] = [(compression::ZSTD, 0xff0b), (payload::PAYLOAD, 0xff0c)];
+
+/// The extension type standing for `feature`, if this client knows it.
+pub fn feature_extension(feature: &str) -> Option<u16> {
+    FEATURES.iter().find(|(name, _)| *name == feature).map(|(_, extension)| *extension)
+}
+
+/// Mode and admins of a group, as its MLS group context says.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct GroupState {
+    pub mode: GroupMode,
+    pub admins: Vec<String>,
+}
+
+impl GroupState {
+    /// A new group's state, with its creator the only admin.
+    pub fn new(creator: &str, mode: GroupMode) -> Self {
+        Self { mode, admins: vec![creator.to_string()] }
+    }
+
+    pub fn is_admin(&self, identity: &str) -> bool {
+        self.admins.iter().any(|admin| admin == identity)
+    }
+
+    pub fn extension(&self) -> serde_json::Result<Extension> {
+        Ok(Extension::Unknown(GROUP_STATE, UnknownExtension(serde_json::to_vec(self)?)))
+    }
+
+    /// The state in `extensions`; `None` if the group carries none or it cannot be read.
+    pub fn find(extensions: &Extensions) -> Option<Self> {
+        extensions.iter().find_map(|extension| match extension {
+            Extension::Unknown(GROUP_STATE, UnknownExtension(data)) => serde_json::from_slice(data).ok(),
+            _ => None,
+        })
+    }
+}
+
+/// Leaf capabilities of this client: the group state extension, which openmls requires every
+/// member to support, and every optional feature.
+pub fn leaf_capabilities() -> Capabilities {
+    let extensions: Vec<ExtensionType> = std::iter::once(GROUP_STATE)
+        .chain(FEATURES.iter().map(|(_, extension)| *extension))
+        .map(ExtensionType::Unknown)
+        .collect();
+    Capabilities::new(None, None, Some(&extensions), None, None)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn makes_the_creator_the_only_admin() {
+        let state = GroupState::new("alice", GroupMode::Announce);
+        assert!(state.is_admin("alice"));
+        assert!(!state.is_admin("bob"));
+    }
+
+    #[test]
+    fn finds_the_state_among_other_extensions() {
+        let state = GroupState { mode: GroupMode::Announce, admins: vec!["alice".to_string(), "carol".to_string()] };
+        let other = Extension::Unknown(0xff0d, UnknownExtension(b"{}".to_vec()));
+        let extensions = Extensions::from_vec(vec![other, state.extension().unwrap()]).unwrap();
+        assert_eq!(GroupState::find(&extensions), Some(state));
+    }
+
+    #[test]
+    fn finds_nothing_when_the_state_is_missing_or_unreadable() {
+        assert_eq!(GroupState::find(&Extensions::empty()), None);
+        let garbled = Extension::Unknown(GROUP_STATE, UnknownExtension(b"not json".to_vec()));
+        assert_eq!(GroupState::find(&Extensions::single(garbled)), None);
+    }
+}
@@ -0,0 +1,100 @@
+use regex::{Regex, RegexBuilder};
+use serde::{Deserialize, Serialize};
+
+/// What a matching filter does to a message in the timeline. Ordered by precedence: when
+/// several filters match, the strongest action wins.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FilterAction {
+    Highlight,
+    Dim,
+    Hide,
+}
+
+impl FilterAction {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "highlight" => Some(FilterAction::Highlight),
+            "dim" => Some(FilterAction::Dim),
+            "hide" => Some(FilterAction::Hide),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            FilterAction::Highlight => "highlight",
+            FilterAction::Dim => "dim",
+            FilterAction::Hide => "hide",
+        }
+    }
+}
+
+/// Text a filter looks for; both kinds match case-insensitively.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FilterPattern {
+    Regex(String),
+    /// Matches when any of the keywords appears
+    Keywords(Vec<String>),
+}
+
+/// A user-defined rule applied to incoming messages when the timeline is drawn.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MessageFilter {
+    pub action: FilterAction,
+    pub pattern: FilterPattern,
+    /// Group ID the filter is limited to; `None` applies it everywhere
+    #[serde(default)]
+    pub group: Option<String>,
+}
+
+impl MessageFilter {
+    fn matcher(&self) -> Result<Regex, regex::Error> {
+        let pattern = match &self.pattern {
+            FilterPattern::Regex(pattern) => pattern.clone(),
+            FilterPattern::Keywords(keywords) => keywords.iter().map(|keyword| regex::escape(keyword)).collect::<Vec<_>>().join("|"),
+        };
+        RegexBuilder::new(&pattern).case_insensitive(true).build()
+    }
+
+    /// Check that the pattern compiles, so a filter is only saved if it can be applied.
+    pub fn validate(&self) -> Result<(), regex::Error> {
+        self.matcher().map(|_| ())
+    }
+
+    pub fn describe(&self) -> String {
+        let pattern = match &self.pattern {
+            FilterPattern::Regex(pattern) => format!("regex /{}/", pattern),
+            FilterPattern::Keywords(keywords) => format!("keywords {}", keywords.join(", ")),
+        };
+        format!("{} {}", self.action.as_str(), pattern)
+    }
+}
+
+/// Filters compiled once, for matching on every redraw.
+#[derive(Default)]
+pub struct FilterSet {
+    compiled: Vec<(FilterAction, Option<String>, Regex)>,
+}
+
+impl FilterSet {
+    /// Compile `filters`; any whose pattern is invalid (e.g. edited by hand) is left out.
+    pub fn new(filters: &[MessageFilter]) -> Self {
+        let compiled = filters
+            .iter()
+            .filter_map(|filter| Some((filter.action, filter.group.clone(), filter.matcher().ok()?)))
+            .collect();
+        Self { compiled }
+    }
+
+    /// The strongest action of the filters for `group_id` that match `text`.
+    pub fn action_for(&self, group_id: &str, text: &str) -> Option<FilterAction> {
+        self.compiled
+            .iter()
+            .filter(|(_, group, _)| group.is_none() || group.as_deref() == Some(group_id))
+            .filter(|(_, _, regex)| regex.is_match(text))
+            .map(|(action, _, _)| *action)
+            .max()
+    }
+}
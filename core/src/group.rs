@@ -0,0 +1,889 @@
+use chrono::{DateTime, Local};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use crate::bidi::TextDirection;
+use crate::crypto::{hmac_sha256, to_hex};
+use crate::errors::AuditError;
+use crate::history::{HistoryBundle, SeenIds};
+use crate::network::NetworkMessage;
+use crate::payload::Content;
+
+/// Undecryptable messages kept per group; the oldest are dropped beyond this.
+const MAX_QUARANTINE: usize = 200;
+/// Commits kept in a group's membership log; the oldest are dropped beyond this.
+const MAX_MEMBERSHIP_LOG: usize = 1_000;
+/// Shown in place of a message an admin redacted.
+pub const REDACTED_PLACEHOLDER: &str = "[redacted by admin]";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Message {
+    pub id: String,
+    pub sender: String,
+    pub content: String,
+    pub timestamp: DateTime<Local>,
+    pub group_id: String,
+    /// Delivery sequence assigned by the DS; `None` for local echoes
+    #[serde(default)]
+    pub server_seq: Option<u64>,
+    /// When the DS received the message; `None` for local echoes and older servers
+    #[serde(default)]
+    pub received_at: Option<DateTime<Local>>,
+    /// Sender-claimed time deviates from the server receive time (or the server-corrected
+    /// clock) beyond the threshold
+    #[serde(default)]
+    pub clock_skewed: bool,
+    #[serde(default)]
+    pub pinned: bool,
+    /// Emoji to the members who reacted with it
+    #[serde(default)]
+    pub reactions: BTreeMap<String, Vec<String>>,
+    /// Member who re-shared this message from before we joined; `None` if received live
+    #[serde(default)]
+    pub shared_by: Option<String>,
+    /// Admin who redacted this message; its content is then the placeholder
+    #[serde(default)]
+    pub redacted_by: Option<String>,
+    /// Output of the configured translator, shown beneath the original
+    #[serde(default)]
+    pub translation: Option<String>,
+    /// Our own message, sent but not yet seen in the delivery service's copy
+    #[serde(default)]
+    pub pending: bool,
+    /// How the message reached the timeline
+    #[serde(default)]
+    pub protection: Protection,
+    /// Sent or received through the attachment store rather than inline
+    #[serde(default)]
+    pub attachment: bool,
+    /// Content type of a payload this client cannot show; the content is its fallback text
+    #[serde(default)]
+    pub unsupported: Option<String>,
+}
+
+/// What protects a message in the timeline, so end-to-end protected content can be told
+/// apart from anything else.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Protection {
+    /// Decrypted from an MLS message and authenticated as the sender's leaf
+    Authenticated(Authenticated),
+    /// Our own message, encrypted to the group when it was sent
+    Sent,
+    /// Re-shared by a member: their encryption covers it, the original sender's signature does not
+    Shared,
+    /// Written by this client, e.g. a note in place of an attachment it could not fetch
+    Local,
+    /// Stored before protection was recorded
+    #[default]
+    Unrecorded,
+}
+
+impl Protection {
+    /// Timeline marker: a lock for content MLS protected end to end.
+    pub fn marker(&self) -> &'static str {
+        match self {
+            Protection::Authenticated(_) | Protection::Sent | Protection::Shared => "🔒",
+            Protection::Local => "⚙",
+            Protection::Unrecorded => "·",
+        }
+    }
+
+    pub fn describe(&self) -> &'static str {
+        match self {
+            Protection::Authenticated(_) => "End-to-end encrypted and authenticated as the sender",
+            Protection::Sent => "Sent by you, end-to-end encrypted to the group",
+            Protection::Shared => "Re-shared by a member over MLS; the original sender is not authenticated",
+            Protection::Local => "Written by this client; it did not arrive over MLS",
+            Protection::Unrecorded => "Stored before this client recorded how messages were protected",
+        }
+    }
+}
+
+/// What the MLS layer checked on an incoming message.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Authenticated {
+    pub epoch: u64,
+    /// Leaf index of the sender in the ratchet tree
+    pub leaf: Option<u32>,
+    pub wire_format: String,
+    /// Additional authenticated data, hex
+    pub aad: String,
+}
+
+impl Message {
+    /// The server receive time when known; the sender-claimed time is only a fallback, as a
+    /// member can set it to anything.
+    pub fn shown_time(&self) -> DateTime<Local> {
+        self.received_at.unwrap_or(self.timestamp)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Group {
+    pub id: String,
+    pub name: String,
+    pub members: Vec<String>,
+    pub messages: Vec<Message>,
+    pub is_active: bool,
+    pub admins: Vec<String>,
+    pub mode: GroupMode,
+    #[serde(default)]
+    pub unread: usize,
+    /// Unread messages that mention us
+    #[serde(default)]
+    pub unread_mentions: usize,
+    #[serde(default)]
+    pub seen_ids: SeenIds,
+    /// Per-group display names announced by members via profile messages
+    #[serde(default)]
+    pub nicknames: HashMap<String, String>,
+    /// When we created or joined the group, under our identity; older histories also hold
+    /// when other members were first seen. See `joined`
+    #[serde(default)]
+    pub joined_at: HashMap<String, DateTime<Local>>,
+    /// Local color/icon marker; never sent to other members
+    #[serde(default)]
+    pub tag: Option<GroupTag>,
+    /// Local base direction for laying out messages and the composer
+    #[serde(default)]
+    pub text_direction: TextDirection,
+    #[serde(default)]
+    pub wire_policy: WirePolicy,
+    /// A commit removed us: the timeline stays readable but nothing more can be sent or received
+    #[serde(default)]
+    pub removed: bool,
+    /// The delivery service deleted the group or let it expire: the timeline stays readable
+    /// but nothing more can be sent
+    #[serde(default)]
+    pub deleted: Option<GroupDeletion>,
+    /// When each member last sent anything to the group
+    #[serde(default)]
+    pub last_active: HashMap<String, DateTime<Local>>,
+    /// Messages that failed to decrypt, retried after later commits are applied
+    #[serde(default)]
+    pub quarantine: Vec<Quarantined>,
+    /// Audit record of admin redactions, kept after the content is gone
+    #[serde(default)]
+    pub redactions: Vec<Redaction>,
+    /// Audit record of the membership changes of every commit processed, by epoch
+    #[serde(default)]
+    pub membership_log: Vec<EpochChange>,
+    /// Link of the newest entry dropped from `membership_log`, which the oldest kept one
+    /// follows
+    #[serde(default)]
+    pub audit_anchor: Option<String>,
+    /// Local limit on how long the group may stay in one epoch; never sent to other members
+    #[serde(default)]
+    pub rotation_policy: Option<RotationPolicy>,
+    /// Age of the current epoch, tracked for `rotation_policy`
+    #[serde(default)]
+    pub epoch_age: Option<EpochAge>,
+    /// Text admins have members receive as they are added
+    #[serde(default)]
+    pub onboarding: Option<Onboarding>,
+    /// The onboarding message came with our own addition and is shown above the timeline
+    /// until dismissed
+    #[serde(default)]
+    pub onboarding_pinned: bool,
+    /// Pages of older messages moved to disk under the memory budget; see `archive`
+    #[serde(default)]
+    pub archived_pages: usize,
+}
+
+/// How often a group's keys must be rotated by moving it to a new epoch; 0 lifts a limit.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RotationPolicy {
+    #[serde(default)]
+    pub max_days: u64,
+    #[serde(default)]
+    pub max_messages: u64,
+    /// Commit an update as soon as the policy is violated, in groups we administer
+    #[serde(default)]
+    pub auto_commit: bool,
+}
+
+impl RotationPolicy {
+    pub fn describe(&self) -> String {
+        let mut limits = Vec::new();
+        if self.max_days > 0 {
+            limits.push(format!("every {} day(s)", self.max_days));
+        }
+        if self.max_messages > 0 {
+            limits.push(format!("every {} message(s)", self.max_messages));
+        }
+        let auto = if self.auto_commit { ", automatic" } else { "" };
+        format!("rotate at least {}{}", limits.join(" or "), auto)
+    }
+}
+
+/// When the group entered its current epoch and how many messages it has carried since.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EpochAge {
+    pub epoch: u64,
+    pub since: DateTime<Local>,
+    pub messages: u64,
+}
+
+/// An admin's onboarding message, as sent in `onboarding` payloads.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Onboarding {
+    pub text: String,
+    pub set_by: String,
+    pub set_at: DateTime<Local>,
+}
+
+/// The delivery service's notice that it no longer holds a group, from a `group_deleted` frame:
+/// `{"expired": true, "reason": "..."}`, both optional.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GroupDeletion {
+    /// Dropped after a period of inactivity rather than deleted
+    #[serde(default)]
+    pub expired: bool,
+    /// The operator's explanation, if any
+    #[serde(default)]
+    pub reason: Option<String>,
+    /// When the notice arrived
+    #[serde(default = "Local::now")]
+    pub at: DateTime<Local>,
+}
+
+impl GroupDeletion {
+    /// Read a frame's content, stamped with the time it arrived; anything unreadable counts as
+    /// a plain deletion.
+    pub fn parse(content: &[u8]) -> Self {
+        let deletion = serde_json::from_slice(content).unwrap_or(Self { expired: false, reason: None, at: Local::now() });
+        Self { at: Local::now(), ..deletion }
+    }
+
+    /// E.g. "expired on the delivery service on 2024-05-01 (inactive for 90 days)".
+    pub fn describe(&self) -> String {
+        let what = if self.expired { "expired on the delivery service" } else { "was deleted by the delivery service" };
+        let reason = self.reason.as_ref().map(|reason| format!(" ({})", reason)).unwrap_or_default();
+        format!("{} on {}{}", what, self.at.format("%Y-%m-%d"), reason)
+    }
+}
+
+/// An admin's redaction of a message, by ID.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Redaction {
+    pub message_id: String,
+    pub redacted_by: String,
+    pub at: DateTime<Local>,
+}
+
+/// Who a commit added, removed and updated, and who committed it.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EpochChange {
+    /// Epoch the commit moved the group to
+    pub epoch: u64,
+    pub committer: String,
+    pub added: Vec<String>,
+    pub removed: Vec<String>,
+    /// Members whose leaf got fresh keys, the committer included when the commit had a path
+    pub updated: Vec<String>,
+    pub at: DateTime<Local>,
+    /// Hex HMAC-SHA256 over this entry and the previous entry's link, under the data
+    /// directory's audit key; set by `record_commit`, `None` in logs written before links
+    /// were keyed
+    #[serde(default)]
+    pub link: Option<String>,
+}
+
+/// Membership changes between two epochs, rebuilt from the membership log.
+#[derive(Debug, Clone, Default)]
+pub struct MembershipDiff {
+    /// Logged commits in the range, oldest first
+    pub changes: Vec<EpochChange>,
+    /// Epochs in the range with no logged commit (before we joined, or never seen)
+    pub missing: Vec<u64>,
+    /// Members at the end of the range who were not there at its start
+    pub joined: Vec<String>,
+    /// Members at the start of the range who were gone by its end
+    pub left: Vec<String>,
+}
+
+/// A frame that could not be processed, kept with why and how often it failed.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Quarantined {
+    pub message_id: String,
+    pub frame: NetworkMessage,
+    pub reason: QuarantineReason,
+    pub detail: String,
+    pub first_failed: DateTime<Local>,
+    pub attempts: u32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum QuarantineReason {
+    /// Sent in an epoch we have not reached yet or have already left
+    WrongEpoch,
+    /// From a leaf not in our view of the tree, e.g. added by a commit we have not seen
+    UnknownSender,
+    /// A commit the handshake policy rejected
+    PolicyViolation,
+    /// Any other processing failure
+    ProcessingFailed,
+}
+
+impl QuarantineReason {
+    pub fn code(&self) -> &'static str {
+        match self {
+            QuarantineReason::WrongEpoch => "wrong-epoch",
+            QuarantineReason::UnknownSender => "unknown-sender",
+            QuarantineReason::PolicyViolation => "policy-violation",
+            QuarantineReason::ProcessingFailed => "processing-failed",
+        }
+    }
+}
+
+/// Color and optional icon shown next to a group in the list and the message pane title.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GroupTag {
+    /// Color name (e.g. "red", "lightblue") or "#rrggbb"
+    pub color: String,
+    pub icon: Option<String>,
+}
+
+impl GroupTag {
+    /// Swatch plus icon, e.g. "■ 🚀 "
+    pub fn label(&self) -> String {
+        match &self.icon {
+            Some(icon) => format!("■ {} ", icon),
+            None => "■ ".to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum GroupMode {
+    Chat,
+    Announce,
+}
+
+impl GroupMode {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            GroupMode::Chat => "chat",
+            GroupMode::Announce => "announce",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "chat" => Some(GroupMode::Chat),
+            "announce" => Some(GroupMode::Announce),
+            _ => None,
+        }
+    }
+}
+
+/// Which MLS wire formats the group sends and accepts. Application messages are always
+/// encrypted; the choice is about handshake messages (proposals and commits).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum WirePolicy {
+    /// Send and accept only PrivateMessage
+    #[default]
+    Ciphertext,
+    /// Send PrivateMessage, accept PublicMessage handshakes too
+    Mixed,
+    /// Send handshakes as PublicMessage so the DS can validate them; accept both
+    PublicCommits,
+}
+
+impl WirePolicy {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            WirePolicy::Ciphertext => "ciphertext",
+            WirePolicy::Mixed => "mixed",
+            WirePolicy::PublicCommits => "public-commits",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "ciphertext" => Some(WirePolicy::Ciphertext),
+            "mixed" => Some(WirePolicy::Mixed),
+            "public-commits" => Some(WirePolicy::PublicCommits),
+            _ => None,
+        }
+    }
+}
+
+impl Group {
+    pub fn is_admin(&self, username: &str) -> bool {
+        self.admins.iter().any(|admin| admin == username)
+    }
+
+    /// Whether nothing more can be sent: a commit removed us, or the delivery service dropped
+    /// the group.
+    pub fn is_read_only(&self) -> bool {
+        self.removed || self.deleted.is_some()
+    }
+
+    /// Restart the epoch age when the group has moved to a new epoch since it was last tracked.
+    pub fn track_epoch(&mut self, epoch: u64) {
+        if self.epoch_age.as_ref().is_some_and(|age| age.epoch == epoch) {
+            return;
+        }
+        self.epoch_age = Some(EpochAge { epoch, since: Local::now(), messages: 0 });
+    }
+
+    /// Why the group violates its rotation policy, e.g. "epoch is 9 days old".
+    pub fn rotation_overdue(&self) -> Option<String> {
+        let (policy, age) = (self.rotation_policy?, self.epoch_age.as_ref()?);
+        let days = (Local::now() - age.since).num_days().max(0) as u64;
+        if policy.max_days > 0 && days >= policy.max_days {
+            return Some(format!("epoch is {} day(s) old", days));
+        }
+        if policy.max_messages > 0 && age.messages >= policy.max_messages {
+            return Some(format!("{} message(s) in this epoch", age.messages));
+        }
+        None
+    }
+
+    /// Whether `username` may post application messages in this group.
+    pub fn can_post(&self, username: &str) -> bool {
+        self.mode == GroupMode::Chat || self.is_admin(username)
+    }
+
+    /// Why `me` may not post in this group, if so: it is gone from the delivery service, we
+    /// were removed, or it is an announcement group and we are not an admin.
+    pub fn posting_denied(&self, me: &str) -> Option<String> {
+        if let Some(deletion) = &self.deleted {
+            Some(format!("{} {}: it is read-only. 'export' or 'forget' it", self.name, deletion.describe()))
+        } else if self.removed {
+            Some(format!("You were removed from {}: it is read-only. 'rejoin' or 'forget' it", self.name))
+        } else if self.can_post(me) {
+            None
+        } else {
+            Some(format!("{} is an announcement group: only admins can post", self.name))
+        }
+    }
+
+    /// Whether to apply `content` from `sender`, checked before any of it takes effect.
+    /// Redactions and the onboarding message come only from admins. Reactions and a member's
+    /// own nickname add nothing to the timeline, so announcement groups take them from
+    /// everyone; everything else, including content types this client does not know, needs
+    /// `can_post`.
+    pub fn accepts(&self, sender: &str, content: &Content) -> bool {
+        match content {
+            Content::Redaction { .. } | Content::Onboarding(_) => self.is_admin(sender),
+            Content::Reaction { .. } | Content::Profile { .. } => true,
+            Content::Chat(_) | Content::Unsupported { .. } => self.can_post(sender),
+        }
+    }
+
+    /// The name to show for `member` in this group, falling back to their identity.
+    pub fn display_name<'a>(&'a self, member: &'a str) -> &'a str {
+        self.nicknames.get(member).map(String::as_str).unwrap_or(member)
+    }
+
+    /// Whether `nick` would pass `member` off as someone else: it is another member's identity,
+    /// or another member already goes by it. Compared ignoring case.
+    pub fn nick_collides(&self, member: &str, nick: &str) -> bool {
+        self.members.iter().any(|other| other != member && other.eq_ignore_ascii_case(nick))
+            || self.nicknames.iter().any(|(other, taken)| other != member && taken.eq_ignore_ascii_case(nick))
+    }
+
+    /// Set or clear `member`'s nickname here, unless it would pass them off as another
+    /// member. Returns whether it was taken.
+    pub fn set_nick(&mut self, member: &str, nick: &str) -> bool {
+        if self.nick_collides(member, nick) {
+            return false;
+        }
+        if nick.is_empty() {
+            self.nicknames.remove(member);
+        } else {
+            self.nicknames.insert(member.to_string(), nick.to_string());
+        }
+        true
+    }
+
+    /// Add `sender`'s reaction to the message it names, if that message is in the timeline.
+    pub fn apply_reaction(&mut self, sender: &str, message_id: &str, emoji: String) {
+        let Some(target) = self.messages.iter_mut().find(|m| m.id == message_id) else {
+            return;
+        };
+        let reactors = target.reactions.entry(emoji).or_default();
+        if !reactors.iter().any(|reactor| reactor == sender) {
+            reactors.push(sender.to_string());
+        }
+    }
+
+    /// When `member` joined: when we processed the latest commit that added them, or for
+    /// ourselves when we created or joined the group. `None` for members already in the group
+    /// before we joined.
+    pub fn joined(&self, member: &str) -> Option<DateTime<Local>> {
+        self.membership_log
+            .iter()
+            .rev()
+            .find(|change| change.added.iter().any(|added| added == member))
+            .map(|change| change.at)
+            .or_else(|| self.joined_at.get(member).copied())
+    }
+
+    pub fn note_activity(&mut self, member: &str, at: DateTime<Local>) {
+        let last = self.last_active.entry(member.to_string()).or_insert(at);
+        *last = (*last).max(at);
+    }
+
+    /// Members other than `me` not heard from since `cutoff`, with when they were last seen (or
+    /// joined, if they never sent anything), longest silent first.
+    pub fn inactive_since(&self, me: &str, cutoff: DateTime<Local>) -> Vec<(String, DateTime<Local>)> {
+        let mut inactive: Vec<(String, DateTime<Local>)> = self.members
+            .iter()
+            .filter(|member| *member != me)
+            .filter_map(|member| {
+                let seen = self.last_active.get(member).copied().or_else(|| self.joined(member))?;
+                (seen < cutoff).then(|| (member.clone(), seen))
+            })
+            .collect();
+        inactive.sort_by_key(|(_, seen)| *seen);
+        inactive
+    }
+
+    /// When anything last happened in the group: a message, a member heard from, or a commit.
+    pub fn last_activity(&self) -> Option<DateTime<Local>> {
+        let message = self.messages.last().map(Message::shown_time);
+        let heard = self.last_active.values().max().copied();
+        let commit = self.membership_log.last().map(|change| change.at);
+        [message, heard, commit].into_iter().flatten().max()
+    }
+
+    /// Whether `text` addresses `username` by identity or by their nickname here.
+    pub fn mentions(&self, username: &str, text: &str) -> bool {
+        text.contains(&format!("@{}", username))
+            || self.nicknames.get(username).is_some_and(|nick| text.contains(&format!("@{}", nick)))
+    }
+
+    /// Keep a frame that failed to process; a repeat failure updates its entry.
+    pub fn quarantine(&mut self, message_id: String, frame: NetworkMessage, reason: QuarantineReason, detail: String) {
+        if let Some(entry) = self.quarantine.iter_mut().find(|entry| entry.message_id == message_id) {
+            entry.reason = reason;
+            entry.detail = detail;
+            entry.attempts += 1;
+            return;
+        }
+        self.quarantine.push(Quarantined { message_id, frame, reason, detail, first_failed: Local::now(), attempts: 1 });
+        let excess = self.quarantine.len().saturating_sub(MAX_QUARANTINE);
+        self.quarantine.drain(..excess);
+    }
+
+    /// Insert a message ordered by server sequence rather than sender-claimed time.
+    pub fn insert_message(&mut self, mut message: Message) {
+        self.apply_redactions(&mut message);
+        if let Some(age) = &mut self.epoch_age {
+            age.messages += 1;
+        }
+        let position = self.server_position(message.server_seq);
+        self.messages.insert(position, message);
+    }
+
+    /// Where a message with `server_seq` goes: before the first one the server sequenced
+    /// after it; unsequenced messages go last.
+    fn server_position(&self, server_seq: Option<u64>) -> usize {
+        match server_seq {
+            Some(seq) => self.messages
+                .iter()
+                .position(|m| m.server_seq.is_some_and(|s| s > seq))
+                .unwrap_or(self.messages.len()),
+            None => self.messages.len(),
+        }
+    }
+
+    /// Apply the delivery service's copy of a message `sender` (we) sent: the local echo is
+    /// no longer pending and takes the server's sequence and receive time, moving to its
+    /// place in server order. `false` if there is no such message of ours.
+    pub fn reconcile_echo(&mut self, message_id: &str, sender: &str, server_seq: Option<u64>, received_at: Option<DateTime<Local>>) -> bool {
+        let Some(index) = self.messages.iter().position(|m| m.id == message_id && m.sender == sender) else {
+            return false;
+        };
+        let mut message = self.messages.remove(index);
+        message.pending = false;
+        message.server_seq = server_seq.or(message.server_seq);
+        message.received_at = received_at.or(message.received_at);
+        let position = match message.server_seq {
+            Some(_) => self.server_position(message.server_seq),
+            None => index,
+        };
+        self.messages.insert(position, message);
+        true
+    }
+
+    /// Put a page of older messages read back from disk ahead of the timeline, applying the
+    /// redactions that arrived while it was there. Returns how many were added; any already
+    /// on the timeline are skipped.
+    pub fn restore_page(&mut self, page: Vec<Message>) -> usize {
+        let mut restored: Vec<Message> = page
+            .into_iter()
+            .filter(|message| !self.messages.iter().any(|m| m.id == message.id))
+            .collect();
+        for message in &mut restored {
+            self.apply_redactions(message);
+        }
+        let added = restored.len();
+        self.messages.splice(0..0, restored);
+        added
+    }
+
+    /// Place a re-shared message by its original time, ahead of anything newer.
+    pub fn insert_shared(&mut self, mut message: Message) {
+        self.apply_redactions(&mut message);
+        let position = self.messages
+            .iter()
+            .position(|m| m.timestamp > message.timestamp)
+            .unwrap_or(self.messages.len());
+        self.messages.insert(position, message);
+    }
+
+    /// Merge messages `sharer` re-shared from before we joined, marked with who shared them.
+    /// Returns how many were new here.
+    pub fn merge_shared(&mut self, bundle: HistoryBundle, sharer: &str) -> usize {
+        let mut added = 0;
+        for shared in bundle.messages {
+            if !self.seen_ids.insert(shared.id.clone()) {
+                continue;
+            }
+            self.insert_shared(Message {
+                id: shared.id,
+                sender: shared.sender,
+                content: shared.content,
+                timestamp: shared.timestamp,
+                group_id: self.id.clone(),
+                server_seq: None,
+                received_at: None,
+                clock_skewed: false,
+                pinned: false,
+                reactions: BTreeMap::new(),
+                shared_by: Some(sharer.to_string()),
+                redacted_by: None,
+                translation: None,
+                pending: false,
+                protection: Protection::Shared,
+                attachment: false,
+                unsupported: None,
+            });
+            added += 1;
+        }
+        added
+    }
+
+    /// Record that `by` redacted `message_id` and blank the message if we have it; a redaction
+    /// that arrives first is applied when the message does. Returns the message's sender.
+    pub fn redact(&mut self, message_id: &str, by: &str, at: DateTime<Local>) -> Option<String> {
+        if !self.redactions.iter().any(|redaction| redaction.message_id == message_id) {
+            self.redactions.push(Redaction { message_id: message_id.to_string(), redacted_by: by.to_string(), at });
+        }
+        let message = self.messages.iter_mut().find(|message| message.id == message_id)?;
+        blank(message, by);
+        Some(message.sender.clone())
+    }
+
+    /// Log what a processed commit changed, linked under `key` to the newest entry as stored;
+    /// a commit for the newest entry's epoch replaces it. Entries already logged are never
+    /// rewritten: if one no longer checks out, or the commit is older than the newest entry,
+    /// nothing is logged.
+    pub fn record_commit(&mut self, mut change: EpochChange, key: &[u8]) -> Result<(), AuditError> {
+        if let Some(epoch) = self.broken_audit_link(key) {
+            return Err(AuditError::Altered { epoch });
+        }
+        match self.membership_log.last() {
+            Some(newest) if change.epoch < newest.epoch => {
+                return Err(AuditError::OutOfOrder { epoch: change.epoch, newest: newest.epoch });
+            }
+            Some(newest) if change.epoch == newest.epoch => {
+                self.membership_log.pop();
+            }
+            _ => {}
+        }
+        let previous = match self.membership_log.last() {
+            Some(newest) => newest.link.clone(),
+            None => self.audit_anchor.clone(),
+        };
+        change.link = audit_link(key, previous.as_deref(), &change);
+        self.membership_log.push(change);
+        if self.membership_log.len() > MAX_MEMBERSHIP_LOG {
+            self.audit_anchor = self.membership_log.remove(0).link;
+        }
+        Ok(())
+    }
+
+    /// Epoch of the first membership log entry whose link under `key` does not follow from
+    /// the entry before it; `None` if every link checks out. Entries from before links were
+    /// keyed are skipped, but only ahead of the first linked one.
+    pub fn broken_audit_link(&self, key: &[u8]) -> Option<u64> {
+        let mut previous = self.audit_anchor.clone();
+        for change in self.membership_log.iter().skip_while(|change| change.link.is_none()) {
+            if change.link.is_none() || change.link != audit_link(key, previous.as_deref(), change) {
+                return Some(change.epoch);
+            }
+            previous = change.link.clone();
+        }
+        None
+    }
+
+    /// Membership changes from epoch `from` to epoch `to`: the commits that entered the epochs
+    /// after `from` up to and including `to`, and their net effect.
+    pub fn membership_diff(&self, from: u64, to: u64) -> MembershipDiff {
+        let changes: Vec<EpochChange> = self.membership_log
+            .iter()
+            .filter(|change| change.epoch > from && change.epoch <= to)
+            .cloned()
+            .collect();
+        let missing = (from + 1..=to)
+            .filter(|epoch| !changes.iter().any(|change| change.epoch == *epoch))
+            .collect();
+        let mut diff = MembershipDiff { changes, missing, ..MembershipDiff::default() };
+        for change in &diff.changes {
+            for identity in &change.added {
+                match diff.left.iter().position(|left| left == identity) {
+                    Some(position) => {
+                        diff.left.remove(position);
+                    }
+                    None if !diff.joined.contains(identity) => diff.joined.push(identity.clone()),
+                    None => {}
+                }
+            }
+            for identity in &change.removed {
+                match diff.joined.iter().position(|joined| joined == identity) {
+                    Some(position) => {
+                        diff.joined.remove(position);
+                    }
+                    None if !diff.left.contains(identity) => diff.left.push(identity.clone()),
+                    None => {}
+                }
+            }
+        }
+        diff
+    }
+
+    fn apply_redactions(&self, message: &mut Message) {
+        if let Some(redaction) = self.redactions.iter().find(|redaction| redaction.message_id == message.id) {
+            blank(message, &redaction.redacted_by);
+        }
+    }
+}
+
+/// HMAC under `key` linking `change` to the entry before it in the membership log. The time
+/// is covered as an instant, so a change of time zone does not break the link.
+fn audit_link(key: &[u8], previous: Option<&str>, change: &EpochChange) -> Option<String> {
+    let entry = (
+        previous,
+        change.epoch,
+        &change.committer,
+        &change.added,
+        &change.removed,
+        &change.updated,
+        change.at.timestamp(),
+        change.at.timestamp_subsec_nanos(),
+    );
+    let encoded = serde_json::to_vec(&entry).ok()?;
+    hmac_sha256(key, &encoded).map(|mac| to_hex(&mac))
+}
+
+fn blank(message: &mut Message, by: &str) {
+    message.content = REDACTED_PLACEHOLDER.to_string();
+    message.reactions.clear();
+    message.pinned = false;
+    message.translation = None;
+    message.redacted_by = Some(by.to_string());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    const KEY: &[u8] = b"audit key of this data directory";
+
+    fn group() -> Group {
+        serde_json::from_value(json!({
+            "id": "g", "name": "general", "members": [], "messages": [], "is_active": true, "admins": [], "mode": "chat"
+        }))
+        .unwrap()
+    }
+
+    fn change(epoch: u64, added: &str) -> EpochChange {
+        EpochChange {
+            epoch,
+            committer: "alice".to_string(),
+            added: vec![added.to_string()],
+            removed: Vec::new(),
+            updated: vec!["alice".to_string()],
+            at: Local::now(),
+            link: None,
+        }
+    }
+
+    #[test]
+    fn links_each_commit_under_the_key() {
+        let mut group = group();
+        for (epoch, added) in [(1, "bob"), (2, "carol"), (3, "dave")] {
+            group.record_commit(change(epoch, added), KEY).unwrap();
+        }
+        assert!(group.membership_log.iter().all(|change| change.link.is_some()));
+        assert_eq!(group.broken_audit_link(KEY), None);
+        assert_eq!(group.broken_audit_link(b"another key"), Some(1));
+    }
+
+    #[test]
+    fn reports_an_altered_entry_without_rewriting_it() {
+        let mut group = group();
+        for (epoch, added) in [(1, "bob"), (2, "carol")] {
+            group.record_commit(change(epoch, added), KEY).unwrap();
+        }
+        group.membership_log[0].added = vec!["mallory".to_string()];
+        let logged = group.membership_log.clone();
+        assert!(matches!(group.record_commit(change(3, "dave"), KEY), Err(AuditError::Altered { epoch: 1 })));
+        assert_eq!(group.membership_log.len(), logged.len());
+        assert!(group.membership_log.iter().zip(&logged).all(|(kept, before)| kept.link == before.link));
+        assert_eq!(group.broken_audit_link(KEY), Some(1));
+    }
+
+    #[test]
+    fn refuses_commits_older_than_the_newest_entry() {
+        let mut group = group();
+        group.record_commit(change(5, "bob"), KEY).unwrap();
+        assert!(matches!(group.record_commit(change(4, "carol"), KEY), Err(AuditError::OutOfOrder { epoch: 4, newest: 5 })));
+        group.record_commit(change(5, "carol"), KEY).unwrap();
+        assert_eq!(group.membership_log.len(), 1);
+        assert_eq!(group.membership_log[0].added, vec!["carol".to_string()]);
+        assert_eq!(group.broken_audit_link(KEY), None);
+    }
+
+    #[test]
+    fn checks_only_from_the_first_keyed_entry() {
+        let mut group = group();
+        group.membership_log.push(change(1, "bob"));
+        group.record_commit(change(2, "carol"), KEY).unwrap();
+        assert_eq!(group.broken_audit_link(KEY), None);
+        group.membership_log.push(change(3, "dave"));
+        assert_eq!(group.broken_audit_link(KEY), Some(3));
+    }
+
+    #[test]
+    fn only_admins_post_in_announcement_groups() {
+        let mut group = group();
+        group.admins = vec!["alice".to_string()];
+        assert_eq!(group.posting_denied("bob"), None);
+        group.mode = GroupMode::Announce;
+        assert_eq!(group.posting_denied("alice"), None);
+        assert!(group.posting_denied("bob").is_some_and(|denied| denied.contains("announcement group")));
+        group.removed = true;
+        assert!(group.posting_denied("alice").is_some_and(|denied| denied.contains("removed")));
+    }
+
+    #[test]
+    fn keeps_a_nickname_from_naming_another_member() {
+        let mut group = group();
+        group.members = vec!["alice".to_string(), "bob".to_string()];
+        assert!(group.set_nick("bob", "Bobby"));
+        assert!(!group.set_nick("alice", "BOB"));
+        assert!(!group.set_nick("alice", "bobby"));
+        assert!(group.set_nick("bob", ""));
+        assert!(!group.nicknames.contains_key("bob"));
+    }
+}
@@ -0,0 +1,139 @@
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::path::Path;
+use chrono::{DateTime, Local};
+use crate::errors::StorageError;
+use crate::integrity;
+use crate::vault;
+use crate::{Group, Message};
+
+pub const HISTORY_PATH: &str = "history.json";
+
+const MAX_SEEN_IDS: usize = 5000;
+
+/// Bounded set of message IDs already applied to a group's timeline.
+/// The oldest IDs are evicted first once the bound is reached.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(from = "VecDeque<String>", into = "VecDeque<String>")]
+pub struct SeenIds {
+    order: VecDeque<String>,
+    set: HashSet<String>,
+}
+
+impl SeenIds {
+    pub fn contains(&self, id: &str) -> bool {
+        self.set.contains(id)
+    }
+
+    /// Record `id`, returning false if it had already been seen.
+    pub fn insert(&mut self, id: String) -> bool {
+        if !self.set.insert(id.clone()) {
+            return false;
+        }
+        self.order.push_back(id);
+        while self.order.len() > MAX_SEEN_IDS {
+            if let Some(oldest) = self.order.pop_front() {
+                self.set.remove(&oldest);
+            }
+        }
+        true
+    }
+
+    /// Forget `id` so the message can be applied again.
+    pub fn remove(&mut self, id: &str) {
+        if self.set.remove(id) {
+            self.order.retain(|seen| seen != id);
+        }
+    }
+}
+
+impl From<VecDeque<String>> for SeenIds {
+    fn from(order: VecDeque<String>) -> Self {
+        let set = order.iter().cloned().collect();
+        Self { order, set }
+    }
+}
+
+impl From<SeenIds> for VecDeque<String> {
+    fn from(seen: SeenIds) -> Self {
+        seen.order
+    }
+}
+
+/// Which earlier messages an admin re-shares with a member they add.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum HistoryShare {
+    Last(usize),
+    Since(DateTime<Local>),
+}
+
+/// A message from before the recipient joined, as the sharing member saw it.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SharedMessage {
+    pub id: String,
+    pub sender: String,
+    pub content: String,
+    pub timestamp: DateTime<Local>,
+}
+
+/// Earlier messages re-encrypted for a new member in the epoch they joined.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct HistoryBundle {
+    pub messages: Vec<SharedMessage>,
+}
+
+impl HistoryBundle {
+    /// The messages `share` selects, dropping the oldest until the encoded bundle fits `max_bytes`.
+    /// Messages that were themselves re-shared to us are left out: we cannot vouch for them.
+    /// Redacted messages are left out as well.
+    pub fn select(messages: &[Message], share: HistoryShare, max_bytes: usize) -> Self {
+        let live = messages.iter().filter(|message| message.shared_by.is_none() && message.redacted_by.is_none());
+        let selected: Vec<&Message> = match share {
+            HistoryShare::Last(count) => {
+                let live: Vec<&Message> = live.collect();
+                let skip = live.len().saturating_sub(count);
+                live.into_iter().skip(skip).collect()
+            }
+            HistoryShare::Since(since) => live.filter(|message| message.shown_time() >= since).collect(),
+        };
+        let mut bundle = Self {
+            messages: selected
+                .into_iter()
+                .map(|message| SharedMessage {
+                    id: message.id.clone(),
+                    sender: message.sender.clone(),
+                    content: message.content.clone(),
+                    // What we saw, not what the original sender claimed
+                    timestamp: message.shown_time(),
+                })
+                .collect(),
+        };
+        while !bundle.messages.is_empty() && serde_json::to_vec(&bundle).map_or(true, |encoded| encoded.len() > max_bytes) {
+            bundle.messages.remove(0);
+        }
+        bundle
+    }
+}
+
+/// Load persisted groups and their timelines from `path` (normally `HISTORY_PATH`), or
+/// nothing on first run.
+pub async fn load(path: &Path) -> Result<HashMap<String, Group>, StorageError> {
+    if !vault::locate(path).exists() {
+        return Ok(HashMap::new());
+    }
+    let name = path.display().to_string();
+    let content = vault::read_to_string(path).await.map_err(StorageError::io(name.clone()))?;
+    serde_json::from_str(&content).map_err(StorageError::invalid(name))
+}
+
+/// Write `groups` to `path` with its checksum alongside, read by `integrity::load_checked`.
+pub async fn save(groups: &HashMap<String, Group>, path: &Path) -> Result<(), StorageError> {
+    let name = path.display().to_string();
+    let content = serde_json::to_string(groups).map_err(StorageError::invalid(name.clone()))?;
+    vault::write(path, &content).await.map_err(StorageError::io(name))?;
+    if let Some(checksum) = integrity::checksum(content.as_bytes()) {
+        let checksum_path = integrity::checksum_path(path);
+        vault::write(&checksum_path, checksum).await.map_err(StorageError::io(checksum_path.display().to_string()))?;
+    }
+    Ok(())
+}
@@ -0,0 +1,36 @@
+use std::fs::{File, OpenOptions};
+use std::io::{Read, Seek, Write};
+
+use fs2::FileExt;
+
+use crate::errors::InstanceError;
+
+/// Marks the data directory as in use; two clients sharing MLS state would corrupt it.
+pub const LOCK_PATH: &str = "mls-client.lock";
+
+/// Held for the lifetime of the process as an exclusive advisory lock on `LOCK_PATH`. The
+/// operating system releases it when the process exits, however it exits, so a lock file left
+/// behind never needs clearing; the PID inside only names the holder in the error.
+pub struct InstanceLock {
+    _file: File,
+}
+
+impl InstanceLock {
+    /// Take the lock, failing if another process holds it.
+    pub fn acquire() -> Result<Self, InstanceError> {
+        let mut file = OpenOptions::new().read(true).write(true).create(true).truncate(false).open(LOCK_PATH)?;
+        if let Err(e) = file.try_lock_exclusive() {
+            if e.raw_os_error() != fs2::lock_contended_error().raw_os_error() {
+                return Err(e.into());
+            }
+            // The holder may not have written its PID yet, and Windows keeps it from being read
+            let mut content = String::new();
+            let pid = file.read_to_string(&mut content).ok().and_then(|_| content.trim().parse().ok());
+            return Err(InstanceError::Running { pid });
+        }
+        file.set_len(0)?;
+        file.rewind()?;
+        writeln!(file, "{}", std::process::id())?;
+        Ok(Self { _file: file })
+    }
+}
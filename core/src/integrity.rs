@@ -0,0 +1,226 @@
+//! Startup check of the history file and the pages moved out of it.
+//!
+//! `history::save` writes a SHA-256 checksum next to the file. At start the file must match
+//! it and parse; each group's membership log must have strictly increasing epochs and every
+//! entry's link, an HMAC under the data directory's audit key, must follow from the entry
+//! before it; and every page the group counts must be in the archive. A file
+//! that passes is copied to `LAST_GOOD_DIR`, the first backup tried when a later start
+//! finds damage. MLS state is not persisted yet, so there is no keystore on disk to check.
+
+use serde_json::{Map, Value};
+use std::collections::{BTreeSet, HashMap};
+use std::fmt;
+use std::io;
+use std::path::{Path, PathBuf};
+use tokio::fs;
+use crate::archive;
+use crate::crypto::{to_hex, CryptoProvider};
+use crate::errors::StorageError;
+use crate::history::HISTORY_PATH;
+use crate::migrate::BACKUP_DIR;
+use crate::vault;
+use crate::Group;
+
+pub const HISTORY_CHECKSUM_PATH: &str = "history.json.sha256";
+/// Where a damaged history file is kept once the user picks a recovery option.
+pub const DAMAGED_HISTORY_PATH: &str = "history.json.damaged";
+/// Subdirectory of `BACKUP_DIR` with the history file of the last start that passed.
+pub const LAST_GOOD_DIR: &str = "last-good";
+/// Random key the membership log links are made under, one per data directory. Unlike the
+/// checksum it cannot be recomputed by whoever edits the history file, and once a passphrase
+/// is set it is sealed in the profile with the other data files.
+pub const AUDIT_KEY_PATH: &str = "audit.key";
+const AUDIT_KEY_LEN: usize = 32;
+
+/// Something the check found wrong.
+#[derive(Debug, Clone)]
+pub enum Problem {
+    /// The file cannot be read or is not valid JSON
+    Unreadable(String),
+    /// The content differs from the checksum saved with it
+    Checksum,
+    /// One group's entry does not parse; the others do
+    UnreadableGroup { group: String, error: String },
+    /// A membership log entry is not after the one before it
+    EpochOrder { group: String, epoch: u64 },
+    /// A membership log entry's link does not follow from the entry before it
+    AuditChain { group: String, epoch: u64 },
+    /// A page of older messages the group counts is not in the archive
+    MissingPage { group: String, page: usize },
+}
+
+impl fmt::Display for Problem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Problem::Unreadable(error) => write!(f, "the history file cannot be read: {}", error),
+            Problem::Checksum => write!(f, "the history file does not match the checksum saved with it"),
+            Problem::UnreadableGroup { group, error } => write!(f, "group {} cannot be read: {}", group, error),
+            Problem::EpochOrder { group, epoch } => write!(f, "{}: the audit log goes back to epoch {}", group, epoch),
+            Problem::AuditChain { group, epoch } => write!(f, "{}: the audit log was altered at epoch {}", group, epoch),
+            Problem::MissingPage { group, page } => write!(f, "{}: older messages (page {}) are missing from the archive", group, page + 1),
+        }
+    }
+}
+
+/// What the check found.
+#[derive(Debug, Default)]
+pub struct Report {
+    pub problems: Vec<Problem>,
+    /// IDs of the groups with a problem of their own, which can be rejoined
+    pub damaged: BTreeSet<String>,
+}
+
+impl Report {
+    pub fn is_clean(&self) -> bool {
+        self.problems.is_empty()
+    }
+}
+
+/// Hex SHA-256 of `content`, as saved next to the history file.
+pub fn checksum(content: &[u8]) -> Option<String> {
+    CryptoProvider::new().sha256(content).ok().map(|digest| to_hex(&digest))
+}
+
+/// The data directory's audit key, created on first use.
+pub async fn audit_key() -> Result<Vec<u8>, StorageError> {
+    if vault::locate(AUDIT_KEY_PATH).exists() {
+        return vault::read(AUDIT_KEY_PATH).await.map_err(StorageError::io(AUDIT_KEY_PATH));
+    }
+    let key = CryptoProvider::new().random_bytes(AUDIT_KEY_LEN).map_err(|e| StorageError::io(AUDIT_KEY_PATH)(io::Error::other(e)))?;
+    vault::write(AUDIT_KEY_PATH, &key).await.map_err(StorageError::io(AUDIT_KEY_PATH))?;
+    Ok(key)
+}
+
+pub fn checksum_path(path: &Path) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(".sha256");
+    PathBuf::from(name)
+}
+
+/// Load the history file at `path` like `history::load`, checking it against its checksum and
+/// its groups for consistency. Whatever can be read is returned, a damaged group included
+/// when only its logs are wrong; a file that does not parse as a whole is read group by group.
+pub async fn load_checked(path: &Path, archive: &Path, audit_key: &[u8]) -> (HashMap<String, Group>, Report) {
+    let mut report = Report::default();
+    if !vault::locate(path).exists() {
+        return (HashMap::new(), report);
+    }
+    let content = match vault::read(path).await {
+        Ok(content) => content,
+        Err(e) => {
+            report.problems.push(Problem::Unreadable(e.to_string()));
+            return (HashMap::new(), report);
+        }
+    };
+    // Files saved before checksums existed have none to compare against
+    if let Ok(expected) = vault::read_to_string(checksum_path(path)).await {
+        if checksum(&content).is_some_and(|actual| actual != expected.trim()) {
+            report.problems.push(Problem::Checksum);
+        }
+    }
+    let groups = match serde_json::from_slice::<HashMap<String, Group>>(&content) {
+        Ok(groups) => groups,
+        Err(e) => {
+            report.problems.push(Problem::Unreadable(e.to_string()));
+            salvage(&content, &mut report)
+        }
+    };
+    for group in groups.values() {
+        check_group(group, archive, audit_key, &mut report);
+    }
+    (groups, report)
+}
+
+/// The groups of a history file that does not parse as a whole, if it is still a JSON object.
+fn salvage(content: &[u8], report: &mut Report) -> HashMap<String, Group> {
+    let Ok(entries) = serde_json::from_slice::<Map<String, Value>>(content) else {
+        return HashMap::new();
+    };
+    entries
+        .into_iter()
+        .filter_map(|(group_id, value)| match serde_json::from_value::<Group>(value) {
+            Ok(group) => Some((group_id, group)),
+            Err(e) => {
+                report.problems.push(Problem::UnreadableGroup { group: group_id.clone(), error: e.to_string() });
+                report.damaged.insert(group_id);
+                None
+            }
+        })
+        .collect()
+}
+
+fn check_group(group: &Group, archive: &Path, audit_key: &[u8], report: &mut Report) {
+    let mut problems = Vec::new();
+    if let Some(pair) = group.membership_log.windows(2).find(|pair| pair[1].epoch <= pair[0].epoch) {
+        problems.push(Problem::EpochOrder { group: group.name.clone(), epoch: pair[1].epoch });
+    }
+    if let Some(epoch) = group.broken_audit_link(audit_key) {
+        problems.push(Problem::AuditChain { group: group.name.clone(), epoch });
+    }
+    if let Some(page) = (0..group.archived_pages).find(|page| !archive::page_exists(archive, &group.id, *page)) {
+        problems.push(Problem::MissingPage { group: group.name.clone(), page });
+    }
+    if !problems.is_empty() {
+        report.problems.append(&mut problems);
+        report.damaged.insert(group.id.clone());
+    }
+}
+
+/// Copy the history file, which just passed the check, and its checksum to `LAST_GOOD_DIR`.
+pub async fn keep_last_good() -> Result<(), StorageError> {
+    if !vault::locate(HISTORY_PATH).exists() {
+        return Ok(());
+    }
+    let dir = vault::locate(Path::new(BACKUP_DIR).join(LAST_GOOD_DIR));
+    fs::create_dir_all(&dir).await.map_err(StorageError::io(dir.display().to_string()))?;
+    for file in [HISTORY_PATH, HISTORY_CHECKSUM_PATH] {
+        let path = vault::locate(file);
+        if path.exists() {
+            fs::copy(&path, dir.join(file)).await.map_err(StorageError::io(file))?;
+        }
+    }
+    Ok(())
+}
+
+/// Keep the damaged history file as `DAMAGED_HISTORY_PATH` before it is replaced or rewritten.
+pub async fn set_aside() -> Result<(), StorageError> {
+    if !vault::locate(HISTORY_PATH).exists() {
+        return Ok(());
+    }
+    fs::copy(vault::locate(HISTORY_PATH), vault::locate(DAMAGED_HISTORY_PATH)).await.map_err(StorageError::io(HISTORY_PATH))?;
+    Ok(())
+}
+
+/// The newest backed-up history file that passes the check, with its groups: the copy from
+/// the last good start or one taken before an upgrade.
+pub async fn newest_good_backup(archive: &Path, audit_key: &[u8]) -> Option<(PathBuf, HashMap<String, Group>)> {
+    let mut candidates = Vec::new();
+    let mut dirs = fs::read_dir(vault::locate(BACKUP_DIR)).await.ok()?;
+    while let Ok(Some(dir)) = dirs.next_entry().await {
+        let path = Path::new(BACKUP_DIR).join(dir.file_name()).join(HISTORY_PATH);
+        if let Ok(modified) = fs::metadata(vault::locate(&path)).await.and_then(|metadata| metadata.modified()) {
+            candidates.push((modified, path));
+        }
+    }
+    candidates.sort_by_key(|(modified, _)| std::cmp::Reverse(*modified));
+    for (_, path) in candidates {
+        let (groups, report) = load_checked(&path, archive, audit_key).await;
+        if report.is_clean() {
+            return Some((path, groups));
+        }
+    }
+    None
+}
+
+/// Put the backup at `path`, relative to the data directory, in place of the history file.
+pub async fn restore_backup(path: &Path) -> Result<(), StorageError> {
+    fs::copy(vault::locate(path), vault::locate(HISTORY_PATH)).await.map_err(StorageError::io(path.display().to_string()))?;
+    let checksum = vault::locate(checksum_path(path));
+    let current = vault::locate(HISTORY_CHECKSUM_PATH);
+    if checksum.exists() {
+        fs::copy(&checksum, &current).await.map_err(StorageError::io(checksum.display().to_string()))?;
+    } else if current.exists() {
+        fs::remove_file(&current).await.map_err(StorageError::io(HISTORY_CHECKSUM_PATH))?;
+    }
+    Ok(())
+}
@@ -0,0 +1,50 @@
+//! Protocol, networking and storage for the MLS client, independent of any user interface.
+
+pub mod activity;
+pub mod archive;
+pub mod attachments;
+pub mod backup;
+pub mod bidi;
+pub mod chunking;
+pub mod command;
+pub mod compression;
+pub mod config;
+pub mod crypto;
+pub mod errors;
+pub mod export;
+pub mod extensions;
+pub mod filters;
+pub mod group;
+pub mod history;
+pub mod instance;
+pub mod integrity;
+pub mod interop;
+pub mod local_ds;
+pub mod migrate;
+pub mod mls_client;
+pub mod netstats;
+pub mod network;
+pub mod notify;
+pub mod payload;
+pub mod pipeline;
+pub mod platform;
+pub mod preview;
+pub mod ratchet_tree;
+pub mod remote;
+pub mod rotation;
+pub mod scheduler;
+pub mod search;
+pub mod secrets;
+pub mod send_queue;
+pub mod senders;
+pub mod speech;
+pub mod tasks;
+pub mod timeline;
+pub mod trace;
+pub mod transfers;
+pub mod translate;
+pub mod transport;
+pub mod validation;
+pub mod vault;
+
+pub use group::{Authenticated, Group, GroupDeletion, GroupMode, GroupTag, Message, Onboarding, Protection, QuarantineReason, RotationPolicy, WirePolicy, REDACTED_PLACEHOLDER};
@@ -0,0 +1,284 @@
+use serde::Deserialize;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::io::ErrorKind;
+use std::sync::{Arc, Mutex};
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::mpsc;
+use crate::errors::NetworkError;
+use crate::network::{DirectoryEntry, InviteToken, NetworkMessage};
+
+/// Unacknowledged frames kept per identity; older ones are dropped.
+const MAX_MAILBOX: usize = 1_000;
+/// Most identities one directory lookup returns.
+const MAX_DIRECTORY_RESULTS: usize = 20;
+
+/// Control frames sent by `NetworkClient`; everything else is a `NetworkMessage` to route.
+#[derive(Debug, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum Request {
+    ListKeyPackages { client_id: String },
+    CreateGroup { group_id: String, creator_id: String },
+    JoinGroup { group_id: String, client_id: String },
+    ListGroups,
+    PublishKeyPackage { client_id: String, key_package: String },
+    FetchKeyPackages { identity: String },
+    FindIdentities { query: String },
+    #[serde(rename = "request_invite_token")]
+    InviteToken { client_id: String, group_id: String },
+    RedeemInviteToken { client_id: String, token: String },
+    Ack { client_id: String, sequences: Vec<u64> },
+}
+
+struct Connection {
+    identity: Option<String>,
+    tx: mpsc::UnboundedSender<String>,
+}
+
+/// Routing state of the loopback delivery service.
+#[derive(Default)]
+struct State {
+    next_connection: u64,
+    next_sequence: u64,
+    connections: HashMap<u64, Connection>,
+    /// Identities that receive a group's frames: the creator and everyone sent a Welcome
+    groups: HashMap<String, HashSet<String>>,
+    /// Latest base64 key package published by each identity
+    key_packages: HashMap<String, String>,
+    /// Frames for each identity by sequence, kept until acknowledged and sent again on connect
+    mailboxes: HashMap<String, BTreeMap<u64, String>>,
+    /// Unredeemed invite tokens: the group each admits to and the member who requested it
+    invite_tokens: HashMap<String, (String, String)>,
+}
+
+impl State {
+    /// Assign the next delivery sequence and the receive time to a frame about to be routed.
+    fn stamp(&mut self, message: &mut NetworkMessage) {
+        self.next_sequence += 1;
+        message.sequence = Some(self.next_sequence);
+        message.received_at = Some(chrono::Utc::now().timestamp() as u64);
+    }
+
+    /// Hand a sequenced frame to every connection of `identity`, keeping it until acknowledged.
+    fn deliver(&mut self, identity: &str, message: &NetworkMessage) -> Result<(), NetworkError> {
+        let line = serde_json::to_string(message)?;
+        if let Some(sequence) = message.sequence {
+            let mailbox = self.mailboxes.entry(identity.to_string()).or_default();
+            mailbox.insert(sequence, line.clone());
+            while mailbox.len() > MAX_MAILBOX {
+                mailbox.pop_first();
+            }
+        }
+        for connection in self.connections.values() {
+            if connection.identity.as_deref() == Some(identity) {
+                let _ = connection.tx.send(line.clone());
+            }
+        }
+        Ok(())
+    }
+
+    /// Bind `connection` to `identity` and send it everything not yet acknowledged, in order.
+    fn identify(&mut self, connection: u64, identity: &str) {
+        if let Some(entry) = self.connections.get_mut(&connection) {
+            entry.identity = Some(identity.to_string());
+            for line in self.mailboxes.get(identity).into_iter().flat_map(BTreeMap::values) {
+                let _ = entry.tx.send(line.clone());
+            }
+        }
+    }
+
+    fn reply(&self, connection: u64, line: String) {
+        if let Some(entry) = self.connections.get(&connection) {
+            let _ = entry.tx.send(line);
+        }
+    }
+
+    /// Answer a request with a frame of `message_type` from the service itself.
+    fn reply_frame(&self, connection: u64, message_type: &str, content: Vec<u8>) -> Result<(), NetworkError> {
+        let reply = NetworkMessage {
+            message_type: message_type.to_string(),
+            sender: "delivery_service".to_string(),
+            recipient: None,
+            group_id: None,
+            content,
+            timestamp: chrono::Utc::now().timestamp() as u64,
+            sequence: None,
+            received_at: None,
+            message_id: None,
+        };
+        self.reply(connection, serde_json::to_string(&reply)?);
+        Ok(())
+    }
+
+    fn handle(&mut self, connection: u64, line: &str) -> Result<(), NetworkError> {
+        if let Ok(request) = serde_json::from_str::<Request>(line) {
+            match request {
+                Request::ListKeyPackages { client_id } => {
+                    let hello = serde_json::json!({ "server_time": chrono::Utc::now().timestamp() });
+                    self.reply(connection, hello.to_string());
+                    self.identify(connection, &client_id);
+                }
+                Request::CreateGroup { group_id, creator_id } => {
+                    self.groups.entry(group_id).or_default().insert(creator_id);
+                }
+                Request::JoinGroup { group_id, client_id } => {
+                    // Members decide; the joiner waits for a Welcome. Invitees already have one.
+                    let members: Vec<String> = self.groups.get(&group_id).into_iter().flatten().cloned().collect();
+                    if members.contains(&client_id) {
+                        return Ok(());
+                    }
+                    let mut request = NetworkMessage::for_group("join_request", &client_id, &group_id, Vec::new());
+                    self.stamp(&mut request);
+                    for member in members {
+                        self.deliver(&member, &request)?;
+                    }
+                }
+                Request::ListGroups => {}
+                Request::PublishKeyPackage { client_id, key_package } => {
+                    self.key_packages.insert(client_id, key_package);
+                }
+                Request::FetchKeyPackages { identity } => {
+                    let packages: Vec<&String> = self.key_packages.get(&identity).into_iter().collect();
+                    let reply = NetworkMessage {
+                        message_type: "key_packages".to_string(),
+                        sender: identity,
+                        recipient: None,
+                        group_id: None,
+                        content: serde_json::to_vec(&packages)?,
+                        timestamp: chrono::Utc::now().timestamp() as u64,
+                        sequence: None,
+                        received_at: None,
+                        message_id: None,
+                    };
+                    self.reply(connection, serde_json::to_string(&reply)?);
+                }
+                Request::FindIdentities { query } => {
+                    // The directory is everyone who has published a key package
+                    let query = query.to_lowercase();
+                    let mut entries: Vec<DirectoryEntry> = self.key_packages.iter()
+                        .filter(|(identity, _)| identity.to_lowercase().contains(&query))
+                        .map(|(identity, key_package)| DirectoryEntry {
+                            identity: identity.clone(),
+                            key_packages: vec![key_package.clone()],
+                        })
+                        .collect();
+                    entries.sort_by(|a, b| a.identity.cmp(&b.identity));
+                    entries.truncate(MAX_DIRECTORY_RESULTS);
+                    self.reply_frame(connection, "directory", serde_json::to_vec(&entries)?)?;
+                }
+                Request::InviteToken { client_id, group_id } => {
+                    // Only members may invite; anyone else gets a reply without a group
+                    let member = self.groups.get(&group_id).is_some_and(|members| members.contains(&client_id));
+                    let token = uuid::Uuid::new_v4().simple().to_string();
+                    if member {
+                        self.invite_tokens.insert(token.clone(), (group_id.clone(), client_id));
+                    }
+                    let issued = InviteToken { token, group_id: member.then_some(group_id) };
+                    self.reply_frame(connection, "invite_token", serde_json::to_vec(&issued)?)?;
+                }
+                Request::RedeemInviteToken { client_id, token } => {
+                    // Single use: the token is gone whether or not the admin follows through
+                    let redeemed = self.invite_tokens.remove(&token);
+                    if let Some((group_id, issuer)) = &redeemed {
+                        let mut request = NetworkMessage::for_group("token_join", &client_id, group_id, token.as_bytes().to_vec());
+                        request.recipient = Some(issuer.clone());
+                        self.stamp(&mut request);
+                        self.deliver(issuer, &request)?;
+                    }
+                    let reply = InviteToken { token, group_id: redeemed.map(|(group_id, _)| group_id) };
+                    self.reply_frame(connection, "token_redeemed", serde_json::to_vec(&reply)?)?;
+                }
+                Request::Ack { client_id, sequences } => {
+                    if let Some(mailbox) = self.mailboxes.get_mut(&client_id) {
+                        for sequence in sequences {
+                            mailbox.remove(&sequence);
+                        }
+                    }
+                }
+            }
+            return Ok(());
+        }
+
+        let Ok(mut message) = serde_json::from_str::<NetworkMessage>(line) else {
+            return Ok(());
+        };
+        // Only the service may announce that a group is gone; a member must not fake it
+        if message.message_type == "group_deleted" {
+            return Ok(());
+        }
+        self.stamp(&mut message);
+        match (&message.recipient, &message.group_id) {
+            (Some(recipient), group_id) => {
+                if let (Some(group_id), "welcome") = (group_id, message.message_type.as_str()) {
+                    self.groups.entry(group_id.clone()).or_default().insert(recipient.clone());
+                }
+                self.deliver(recipient, &message)?;
+            }
+            (None, Some(group_id)) => {
+                let members: Vec<String> = self.groups.get(group_id).into_iter().flatten()
+                    // Application frames also go back to their sender, whose copy confirms
+                    // the local echo and gives it its sequence
+                    .filter(|member| **member != message.sender || message.message_type == "application")
+                    .cloned()
+                    .collect();
+                for member in members {
+                    self.deliver(&member, &message)?;
+                }
+            }
+            (None, None) => {}
+        }
+        Ok(())
+    }
+}
+
+/// Serve the delivery service protocol in-process on `address` so clients on this machine can
+/// talk to each other without an external server. Returns `false` if the address is already
+/// taken, typically by another client started with `--local-ds`, which is then used instead.
+pub async fn spawn(address: &str) -> Result<bool, NetworkError> {
+    let listener = match TcpListener::bind(address).await {
+        Ok(listener) => listener,
+        Err(e) if e.kind() == ErrorKind::AddrInUse => return Ok(false),
+        Err(e) => return Err(e.into()),
+    };
+    let state = Arc::new(Mutex::new(State::default()));
+    tokio::spawn(async move {
+        while let Ok((stream, _)) = listener.accept().await {
+            tokio::spawn(serve(stream, state.clone()));
+        }
+    });
+    Ok(true)
+}
+
+async fn serve(stream: TcpStream, state: Arc<Mutex<State>>) {
+    let (reader, mut writer) = stream.into_split();
+    let (tx, mut rx) = mpsc::unbounded_channel::<String>();
+    let connection = match state.lock() {
+        Ok(mut state) => {
+            state.next_connection += 1;
+            let id = state.next_connection;
+            state.connections.insert(id, Connection { identity: None, tx });
+            id
+        }
+        Err(_) => return,
+    };
+
+    tokio::spawn(async move {
+        while let Some(line) = rx.recv().await {
+            if writer.write_all(line.as_bytes()).await.is_err() || writer.write_all(b"\n").await.is_err() {
+                break;
+            }
+        }
+    });
+
+    let mut lines = BufReader::new(reader).lines();
+    while let Ok(Some(line)) = lines.next_line().await {
+        let Ok(mut state) = state.lock() else {
+            break;
+        };
+        // A frame that cannot be re-encoded is dropped; the connection stays up
+        let _ = state.handle(connection, &line);
+    }
+    if let Ok(mut state) = state.lock() {
+        state.connections.remove(&connection);
+    }
+}
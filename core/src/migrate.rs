@@ -0,0 +1,170 @@
+use serde_json::Value;
+use std::path::{Path, PathBuf};
+use tokio::fs;
+use crate::config::CONFIG_PATH;
+use crate::errors::{MigrationError, StorageError};
+use crate::secrets::{local_data_paths, secure_delete_dir};
+use crate::vault;
+
+/// Schema version of the files in the data directory; absent before versioning was introduced.
+pub const VERSION_PATH: &str = "state-version";
+/// Copies of the data files taken before each upgrade, one subdirectory per run.
+pub const BACKUP_DIR: &str = "backups";
+
+/// The schema version this client reads and writes.
+pub const CURRENT_VERSION: u32 = 2;
+
+/// One upgrade step, taking the data directory from `version - 1` to `version`.
+struct Migration {
+    version: u32,
+    description: &'static str,
+    run: fn(&mut Files) -> Result<(), StorageError>,
+}
+
+/// In order; each step is applied once, when the stored version is below its own.
+const MIGRATIONS: &[Migration] = &[
+    Migration {
+        version: 1,
+        description: "assign a device ID to configs written before device IDs existed",
+        run: assign_device_id,
+    },
+    Migration {
+        version: 2,
+        description: "drop the passphrase hashes, replaced by key slots that seal the data files",
+        run: drop_passphrase_hashes,
+    },
+];
+
+/// What an upgrade did, for the status bar.
+#[derive(Debug)]
+pub struct Upgrade {
+    pub from: u32,
+    pub to: u32,
+    pub backup: String,
+}
+
+/// Bring the data directory up to `CURRENT_VERSION`, returning `None` if it already was.
+/// Every data file is backed up first; if a step fails the originals are put back and
+/// the version is left unchanged, so nothing is lost and the next start tries again.
+pub async fn run() -> Result<Option<Upgrade>, MigrationError> {
+    let existing: Vec<&str> = local_data_paths().into_iter().filter(|path| vault::locate(path).exists()).collect();
+    let found = stored_version().await?;
+    if found > CURRENT_VERSION {
+        return Err(MigrationError::TooNew { found, supported: CURRENT_VERSION });
+    }
+    if found == CURRENT_VERSION {
+        return Ok(None);
+    }
+    // First run: nothing to upgrade
+    if existing.is_empty() {
+        write_version(CURRENT_VERSION).await?;
+        return Ok(None);
+    }
+
+    let backup = backup(&existing, found).await.map_err(MigrationError::Backup)?;
+    let mut files = Files::default();
+    for migration in MIGRATIONS.iter().filter(|migration| migration.version > found) {
+        let applied = match (migration.run)(&mut files) {
+            Ok(()) => files.flush().await,
+            Err(e) => Err(e),
+        };
+        if let Err(source) = applied {
+            restore(&existing, &backup).await.map_err(MigrationError::Backup)?;
+            return Err(MigrationError::Failed {
+                version: migration.version,
+                description: migration.description,
+                backup: backup.display().to_string(),
+                source,
+            });
+        }
+    }
+    write_version(CURRENT_VERSION).await?;
+    Ok(Some(Upgrade { from: found, to: CURRENT_VERSION, backup: backup.display().to_string() }))
+}
+
+/// Securely delete every backup, for `wipe`.
+pub async fn wipe_backups() -> Result<(), StorageError> {
+    secure_delete_dir(BACKUP_DIR).await
+}
+
+async fn stored_version() -> Result<u32, StorageError> {
+    if !vault::locate(VERSION_PATH).exists() {
+        return Ok(0);
+    }
+    let content = vault::read_to_string(VERSION_PATH).await.map_err(StorageError::io(VERSION_PATH))?;
+    serde_json::from_str(content.trim()).map_err(StorageError::invalid(VERSION_PATH))
+}
+
+async fn write_version(version: u32) -> Result<(), StorageError> {
+    vault::write(VERSION_PATH, version.to_string()).await.map_err(StorageError::io(VERSION_PATH))
+}
+
+/// Copy `paths` into a fresh `backups/v<version>-<timestamp>` directory.
+async fn backup(paths: &[&str], version: u32) -> Result<PathBuf, StorageError> {
+    let dir = vault::locate(Path::new(BACKUP_DIR).join(format!("v{}-{}", version, chrono::Local::now().format("%Y%m%d-%H%M%S"))));
+    fs::create_dir_all(&dir).await.map_err(StorageError::io(dir.display().to_string()))?;
+    for path in paths {
+        fs::copy(vault::locate(path), dir.join(path)).await.map_err(StorageError::io(*path))?;
+    }
+    Ok(dir)
+}
+
+async fn restore(paths: &[&str], backup: &Path) -> Result<(), StorageError> {
+    for path in paths {
+        fs::copy(backup.join(path), vault::locate(path)).await.map_err(StorageError::io(*path))?;
+    }
+    Ok(())
+}
+
+/// Data files as untyped JSON, read on first use and written back after each step.
+#[derive(Default)]
+struct Files {
+    loaded: Vec<(&'static str, Value)>,
+}
+
+impl Files {
+    /// The parsed contents of `path`, or `None` if the file does not exist.
+    fn get(&mut self, path: &'static str) -> Result<Option<&mut Value>, StorageError> {
+        if !self.loaded.iter().any(|(loaded, _)| *loaded == path) {
+            if !vault::locate(path).exists() {
+                return Ok(None);
+            }
+            let content = vault::read_blocking(path).map_err(StorageError::io(path))?;
+            let value = serde_json::from_slice(&content).map_err(StorageError::invalid(path))?;
+            self.loaded.push((path, value));
+        }
+        Ok(self.loaded.iter_mut().find(|(loaded, _)| *loaded == path).map(|(_, value)| value))
+    }
+
+    async fn flush(&self) -> Result<(), StorageError> {
+        for (path, value) in &self.loaded {
+            // Keep the layout each file is normally saved with
+            let content = if *path == CONFIG_PATH {
+                serde_json::to_string_pretty(value)
+            } else {
+                serde_json::to_string(value)
+            };
+            let content = content.map_err(StorageError::invalid(*path))?;
+            vault::write(path, content).await.map_err(StorageError::io(*path))?;
+        }
+        Ok(())
+    }
+}
+
+fn assign_device_id(files: &mut Files) -> Result<(), StorageError> {
+    if let Some(Value::Object(config)) = files.get(CONFIG_PATH)? {
+        if !matches!(config.get("device_id"), Some(Value::String(id)) if !id.is_empty()) {
+            config.insert("device_id".to_string(), Value::String(uuid::Uuid::new_v4().to_string()));
+        }
+    }
+    Ok(())
+}
+
+/// A hash cannot be turned into the key slots; the passphrase has to be set again.
+fn drop_passphrase_hashes(files: &mut Files) -> Result<(), StorageError> {
+    if let Some(Value::Object(config)) = files.get(CONFIG_PATH)? {
+        config.remove("passphrase");
+        config.remove("duress_passphrase");
+    }
+    Ok(())
+}
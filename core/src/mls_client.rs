@@ -0,0 +1,849 @@
+use openmls::prelude::*;
+use openmls_rust_crypto::OpenMlsRustCrypto;
+use openmls_basic_credential::SignatureKeyPair;
+use openmls_memory_storage::MemoryStorage;
+use crate::crypto::CryptoProvider;
+use crate::errors::MlsError;
+use crate::extensions::{self, GroupState};
+use crate::group::{Authenticated, EpochChange};
+use crate::ratchet_tree::{self, TreeNode};
+use crate::rotation::{IdentityRotation, RotationStatus};
+use crate::validation::HandshakeValidator;
+use crate::WirePolicy;
+use openmls::prelude::tls_codec::{Deserialize, Serialize};
+use openmls_traits::OpenMlsProvider;
+use crate::crypto::to_hex;
+use chrono::{DateTime, Local};
+use std::collections::{HashMap, HashSet};
+
+/// Outcome of processing an inbound MLS message for a group.
+pub enum IncomingMls {
+    Application { sender: String, content: Vec<u8>, proof: Authenticated },
+    /// `superseded` is my own pending commit for the same epoch, discarded in favour of this one
+    Commit { summary: CommitSummary, superseded: Option<CommitSummary> },
+    /// A commit by `by` removed us; the group can no longer be read or written
+    Removed { by: String },
+    /// Stored until a member commits it, e.g. `sender` leaving
+    Proposal { sender: String },
+}
+
+/// Why a key package cannot be added to a group.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Mismatch {
+    Ciphersuite { group: Ciphersuite, offered: Ciphersuite },
+    Extension(ExtensionType),
+    Proposal(ProposalType),
+    Credential(CredentialType),
+}
+
+impl Mismatch {
+    /// Required capabilities can be dropped from the group; its ciphersuite is fixed for life.
+    pub fn relaxable(&self) -> bool {
+        !matches!(self, Mismatch::Ciphersuite { .. })
+    }
+
+    pub fn describe(&self) -> String {
+        match self {
+            Mismatch::Ciphersuite { group, offered } => {
+                format!("Unsupported ciphersuite: group uses {:?}, key package is for {:?}", group, offered)
+            }
+            Mismatch::Extension(extension) => format!("Missing required extension: {:?}", extension),
+            Mismatch::Proposal(proposal) => format!("Missing required proposal type: {:?}", proposal),
+            Mismatch::Credential(credential) => format!("Missing required credential type: {:?}", credential),
+        }
+    }
+}
+
+/// What a staged commit changes, read before it is merged.
+#[derive(Debug, Clone)]
+pub struct CommitSummary {
+    pub committer: String,
+    /// Epoch the group enters once the commit is merged
+    pub epoch: u64,
+    pub adds: Vec<String>,
+    pub removes: Vec<String>,
+    pub updates: Vec<String>,
+    pub psks: usize,
+    pub context_extensions: bool,
+    /// Whether the commit carries an update path (fresh keys for the committer's direct path)
+    pub path: bool,
+}
+
+impl CommitSummary {
+    fn new(group: &MlsGroup, committer: String, staged: &StagedCommit) -> Self {
+        let member_name = |index: LeafNodeIndex| {
+            group.members()
+                .find(|member| member.index == index)
+                .map(|member| credential_identity(&member.credential))
+                .unwrap_or_else(|| format!("leaf {}", index.u32()))
+        };
+        Self {
+            committer,
+            epoch: staged.group_context().epoch().as_u64(),
+            adds: staged.add_proposals()
+                .map(|add| credential_identity(add.add_proposal().key_package().leaf_node().credential()))
+                .collect(),
+            removes: staged.remove_proposals().map(|remove| member_name(remove.remove_proposal().removed())).collect(),
+            updates: staged.update_proposals()
+                .map(|update| credential_identity(update.update_proposal().leaf_node().credential()))
+                .collect(),
+            psks: staged.psk_proposals().count(),
+            context_extensions: staged.queued_proposals()
+                .any(|proposal| matches!(proposal.proposal(), Proposal::GroupContextExtensions(_))),
+            path: staged.update_path_leaf_node().is_some(),
+        }
+    }
+
+    /// The membership part of the commit, for the group's membership log.
+    pub fn membership_change(&self) -> EpochChange {
+        let mut updated = self.updates.clone();
+        if self.path && !updated.contains(&self.committer) {
+            updated.push(self.committer.clone());
+        }
+        EpochChange {
+            epoch: self.epoch,
+            committer: self.committer.clone(),
+            added: self.adds.clone(),
+            removed: self.removes.clone(),
+            updated,
+            at: Local::now(),
+            link: None,
+        }
+    }
+
+    /// One line, e.g. "alice → epoch 4: add bob; remove carol; path".
+    pub fn describe(&self) -> String {
+        let mut changes = Vec::new();
+        if !self.adds.is_empty() {
+            changes.push(format!("add {}", self.adds.join(", ")));
+        }
+        if !self.removes.is_empty() {
+            changes.push(format!("remove {}", self.removes.join(", ")));
+        }
+        if !self.updates.is_empty() {
+            changes.push(format!("update {}", self.updates.join(", ")));
+        }
+        if self.psks > 0 {
+            changes.push(format!("{} PSK(s)", self.psks));
+        }
+        if self.context_extensions {
+            changes.push("group context extensions".to_string());
+        }
+        changes.push(if self.path { "path" } else { "no path" }.to_string());
+        format!("{} → epoch {}: {}", self.committer, self.epoch, changes.join("; "))
+    }
+}
+
+pub struct MlsClient {
+    pub crypto: OpenMlsRustCrypto,
+    pub storage: MemoryStorage,
+    pub signer: SignatureKeyPair,
+    pub credential: BasicCredential,
+    pub signature_key: SignaturePublicKey,
+    pub key_package: KeyPackage,
+    pub groups: HashMap<String, MlsGroup>,
+    /// Applied to every Welcome and commit before it changes a group
+    pub handshake: HandshakeValidator,
+    /// Per group, the epoch of our last application message and the next generation of our
+    /// application ratchet in it. openmls does not expose the ratchet; counting is exact as
+    /// groups live only as long as this client and every message goes through here.
+    sent_generations: HashMap<String, (u64, u32)>,
+    /// A signature key being rolled out; groups whose Update to it has merged sign with it
+    pub rotation: Option<IdentityRotation>,
+    /// Groups whose pending commit is the rotation's Update
+    rotating: HashSet<String>,
+}
+
+/// What an encrypted application message put on the wire, for the debug encryption preview.
+#[derive(Debug, Clone)]
+pub struct EncryptionDetails {
+    pub epoch: u64,
+    /// Generation of our application secret ratchet that encrypted it
+    pub generation: u32,
+    pub plaintext_bytes: usize,
+    /// The serialized MLS message
+    pub ciphertext_bytes: usize,
+    /// Block size the encrypted content is padded to; 0 for no padding
+    pub padding_size: usize,
+}
+
+impl MlsClient {
+    pub async fn new(username: &str, _crypto_provider: &CryptoProvider) -> Result<Self, MlsError> {
+        let crypto = OpenMlsRustCrypto::default();
+        let storage = MemoryStorage::default();
+        
+        // Generate signature key pair
+        let signer = SignatureKeyPair::new(SignatureScheme::ED25519).map_err(failed("Generating signature key"))?;
+        
+        // Store the signature key into the key store
+        signer.store(&storage).map_err(failed("Storing signature key"))?;
+        
+        // Create basic credential with username
+        let credential = BasicCredential::new(username.as_bytes().to_vec());
+        let signature_key: SignaturePublicKey = signer.public().into();
+        
+        let key_package = build_key_package(&crypto, &signer, &credential, &signature_key)?;
+
+        Ok(Self {
+            crypto,
+            storage,
+            signer,
+            credential,
+            signature_key,
+            key_package,
+            groups: HashMap::new(),
+            handshake: HandshakeValidator::default(),
+            sent_generations: HashMap::new(),
+            rotation: None,
+            rotating: HashSet::new(),
+        })
+    }
+
+    /// Start an identity rotation with a fresh signature key, unless one is in progress.
+    pub fn start_rotation(&mut self) -> Result<(), MlsError> {
+        if self.rotation.is_none() {
+            let signer = SignatureKeyPair::new(SignatureScheme::ED25519).map_err(failed("Generating signature key"))?;
+            signer.store(&self.storage).map_err(failed("Storing signature key"))?;
+            self.rotation = Some(IdentityRotation::new(signer));
+        }
+        Ok(())
+    }
+
+    /// Stage an Update commit in the group that moves my leaf to the rotation's new key.
+    /// Returns the serialized commit; once `merge_pending_commit` merges it, the group signs
+    /// with the new key.
+    pub fn stage_signer_update(&mut self, group_id: &str) -> Result<Vec<u8>, MlsError> {
+        let rotation = self.rotation.as_ref().ok_or(MlsError::NoRotation)?;
+        let group = self.groups.get_mut(group_id)
+            .ok_or_else(|| MlsError::UnknownGroup(group_id.to_string()))?;
+        let credential_with_key = CredentialWithKey {
+            credential: self.credential.clone().into(),
+            signature_key: rotation.new_signer.public().into(),
+        };
+        let bundle = group.self_update_with_new_signer(
+            &self.crypto,
+            &self.signer,
+            NewSignerBundle {
+                signer: &rotation.new_signer,
+                credential_with_key,
+            },
+            LeafNodeParameters::default(),
+        ).map_err(failed("Staging signer update"))?;
+        self.rotating.insert(group_id.to_string());
+        bundle.commit().tls_serialize_detached().map_err(failed("Encoding commit"))
+    }
+
+    /// Stage a commit that refreshes my leaf and path secrets, moving the group to a new epoch.
+    pub fn stage_self_update(&mut self, group_id: &str) -> Result<Vec<u8>, MlsError> {
+        let group = self.groups.get_mut(group_id)
+            .ok_or_else(|| MlsError::UnknownGroup(group_id.to_string()))?;
+        let signer = group_signer(&self.signer, &self.rotation, group_id);
+        let bundle = group.self_update(&self.crypto, signer, LeafNodeParameters::default())
+            .map_err(failed("Staging self update"))?;
+        bundle.commit().tls_serialize_detached().map_err(failed("Encoding commit"))
+    }
+
+    pub fn epoch(&self, group_id: &str) -> Option<u64> {
+        self.groups.get(group_id).map(|group| group.epoch().as_u64())
+    }
+
+    /// Merge my own pending commit, returning the group's new epoch.
+    pub fn merge_pending_commit(&mut self, group_id: &str) -> Result<u64, MlsError> {
+        let group = self.groups.get_mut(group_id)
+            .ok_or_else(|| MlsError::UnknownGroup(group_id.to_string()))?;
+        group.merge_pending_commit(&self.crypto).map_err(failed("Merging pending commit"))?;
+        let epoch = group.epoch().as_u64();
+        if self.rotating.remove(group_id) {
+            if let Some(rotation) = self.rotation.as_mut() {
+                rotation.progress.insert(group_id.to_string(), RotationStatus::Committed { epoch });
+            }
+        }
+        Ok(epoch)
+    }
+
+    /// What my pending commit in the group would change, if there is one.
+    pub fn pending_commit(&self, group_id: &str) -> Option<CommitSummary> {
+        let group = self.groups.get(group_id)?;
+        let identity = String::from_utf8_lossy(self.credential.identity()).into_owned();
+        group.pending_commit().map(|staged| CommitSummary::new(group, identity, staged))
+    }
+
+    /// Drop my own pending commit after it failed to reach the delivery service or was aborted.
+    pub fn clear_pending_commit(&mut self, group_id: &str) -> Result<(), MlsError> {
+        let group = self.groups.get_mut(group_id)
+            .ok_or_else(|| MlsError::UnknownGroup(group_id.to_string()))?;
+        group.clear_pending_commit(self.crypto.storage()).map_err(failed("Clearing pending commit"))?;
+        self.rotating.remove(group_id);
+        Ok(())
+    }
+
+    /// Finish the rotation once every group carries the new key: delete the old key and
+    /// regenerate my key package.
+    pub fn retire_signer(&mut self) -> Result<(), MlsError> {
+        let new_signer = self.rotation.take().ok_or(MlsError::NoRotation)?.new_signer;
+        let new_signature_key: SignaturePublicKey = new_signer.public().into();
+        let key_package = build_key_package(&self.crypto, &new_signer, &self.credential, &new_signature_key)?;
+
+        SignatureKeyPair::delete(&self.storage, self.signer.public(), self.signer.signature_scheme())
+            .map_err(failed("Deleting old signature key"))?;
+        self.signer = new_signer;
+        self.signature_key = new_signature_key;
+        self.key_package = key_package;
+        Ok(())
+    }
+
+    pub fn get_identity(&self) -> &[u8] {
+        self.credential.identity()
+    }
+
+    pub fn get_key_package(&self) -> &KeyPackage {
+        &self.key_package
+    }
+
+    /// Our current key package as published to the delivery service.
+    pub fn key_package_bytes(&self) -> Result<Vec<u8>, MlsError> {
+        self.key_package.tls_serialize_detached().map_err(failed("Encoding key package"))
+    }
+
+    /// Create a group following `policy`, with `state` in its group context.
+    pub fn create_group(&self, policy: WirePolicy, state: &GroupState) -> Result<MlsGroup, MlsError> {
+        let credential_with_key = CredentialWithKey {
+            credential: self.credential.clone().into(),
+            signature_key: self.signature_key.clone(),
+        };
+        let state = state.extension().map_err(failed("Encoding group state"))?;
+        // Welcomes carry the ratchet tree so joiners need nothing else from the DS
+        let group_config = MlsGroupCreateConfig::builder()
+            .wire_format_policy(wire_format_policy(policy))
+            .use_ratchet_tree_extension(true)
+            .capabilities(extensions::leaf_capabilities())
+            .with_group_context_extensions(Extensions::single(state))
+            .map_err(failed("Creating group"))?
+            .build();
+
+        let group = MlsGroup::new(
+            &self.crypto,
+            &self.signer,
+            &group_config,
+            credential_with_key,
+        ).map_err(failed("Creating group"))?;
+
+        Ok(group)
+    }
+
+    /// The group's mode and admins, from its group context.
+    pub fn group_state(&self, group_id: &str) -> Option<GroupState> {
+        GroupState::find(self.groups.get(group_id)?.extensions())
+    }
+
+    /// Stage a group context extensions commit replacing the group's mode and admins with
+    /// `state`; returns the commit to deliver.
+    pub fn stage_group_state(&mut self, group_id: &str, state: &GroupState) -> Result<Vec<u8>, MlsError> {
+        let group = self.groups.get_mut(group_id)
+            .ok_or_else(|| MlsError::UnknownGroup(group_id.to_string()))?;
+        let mut extensions: Vec<Extension> = group.extensions()
+            .iter()
+            .filter(|extension| extension.extension_type() != ExtensionType::Unknown(extensions::GROUP_STATE))
+            .cloned()
+            .collect();
+        extensions.push(state.extension().map_err(failed("Encoding group state"))?);
+        let extensions = Extensions::from_vec(extensions).map_err(failed("Building extensions"))?;
+        let signer = group_signer(&self.signer, &self.rotation, group_id);
+        let (commit, _welcome, _group_info) = group.update_group_context_extensions(&self.crypto, extensions, signer)
+            .map_err(failed("Changing group state"))?;
+        commit.tls_serialize_detached().map_err(failed("Encoding commit"))
+    }
+
+    pub fn get_group(&self, group_id: &str) -> Option<&MlsGroup> {
+        self.groups.get(group_id)
+    }
+
+    pub fn get_group_mut(&mut self, group_id: &str) -> Option<&mut MlsGroup> {
+        self.groups.get_mut(group_id)
+    }
+
+    pub fn add_group(&mut self, group_id: &str, group: MlsGroup) {
+        self.sent_generations.remove(group_id);
+        self.groups.insert(group_id.to_string(), group);
+    }
+
+    /// Identities of the group's members, in leaf order.
+    pub fn member_identities(&self, group_id: &str) -> Vec<String> {
+        self.groups
+            .get(group_id)
+            .map(|group| group.members().map(|member| credential_identity(&member.credential)).collect())
+            .unwrap_or_default()
+    }
+
+    /// Whether every leaf of the group lists `feature` (one of `extensions::FEATURES`) in its
+    /// capabilities.
+    pub fn supports_all(&self, group_id: &str, feature: &str) -> bool {
+        let Some(extension) = extensions::feature_extension(feature) else {
+            return false;
+        };
+        let Ok((_, _, nodes)) = self.ratchet_tree(group_id) else {
+            return false;
+        };
+        nodes.iter().flatten().all(|node| match node {
+            TreeNode::Leaf { extensions, .. } => extensions.contains(&extension),
+            TreeNode::Parent { .. } => true,
+        })
+    }
+
+    /// The leaf whose credential carries `identity`, if they are in the group.
+    pub fn find_member(&self, group_id: &str, identity: &str) -> Option<Member> {
+        self.groups
+            .get(group_id)?
+            .members()
+            .find(|member| credential_identity(&member.credential) == identity)
+    }
+
+    /// The group's current epoch and ratchet tree, decoded from its exported form.
+    pub fn ratchet_tree(&self, group_id: &str) -> Result<(u64, u32, Vec<Option<TreeNode>>), MlsError> {
+        let group = self.groups.get(group_id)
+            .ok_or_else(|| MlsError::UnknownGroup(group_id.to_string()))?;
+        let exported = group.export_ratchet_tree().tls_serialize_detached().map_err(failed("Encoding ratchet tree"))?;
+        Ok((group.epoch().as_u64(), group.own_leaf_index().u32(), ratchet_tree::parse(&exported)?))
+    }
+
+    /// The group's GroupInfo, signed by our leaf and carrying the ratchet tree, for the
+    /// delivery service to publish.
+    pub fn export_group_info(&self, group_id: &str) -> Result<Vec<u8>, MlsError> {
+        let group = self.groups.get(group_id)
+            .ok_or_else(|| MlsError::UnknownGroup(group_id.to_string()))?;
+        let signer = group_signer(&self.signer, &self.rotation, group_id);
+        let group_info = group.export_group_info(self.crypto.crypto(), signer, true).map_err(failed("Exporting group info"))?;
+        group_info.tls_serialize_detached().map_err(failed("Encoding group info"))
+    }
+
+    /// Stage an Add commit for `key_package`; returns the commit and the Welcome to deliver.
+    pub fn add_member(&mut self, group_id: &str, key_package: &KeyPackage) -> Result<(Vec<u8>, Vec<u8>), MlsError> {
+        self.add_members(group_id, std::slice::from_ref(key_package))
+    }
+
+    /// Stage one commit adding every key package; the single Welcome it returns is for all of
+    /// them, each new member decrypting only their own secrets.
+    pub fn add_members(&mut self, group_id: &str, key_packages: &[KeyPackage]) -> Result<(Vec<u8>, Vec<u8>), MlsError> {
+        let group = self.groups.get_mut(group_id)
+            .ok_or_else(|| MlsError::UnknownGroup(group_id.to_string()))?;
+        let signer = group_signer(&self.signer, &self.rotation, group_id);
+        let (commit, welcome, _group_info) =
+            group.add_members(&self.crypto, signer, key_packages).map_err(failed("Adding members"))?;
+        Ok((
+            commit.tls_serialize_detached().map_err(failed("Encoding commit"))?,
+            welcome.tls_serialize_detached().map_err(failed("Encoding Welcome"))?,
+        ))
+    }
+
+    /// Join a group from a serialized Welcome (which must carry the ratchet tree) and store it as `group_id`.
+    pub fn join_from_welcome(&mut self, group_id: &str, data: &[u8], policy: WirePolicy) -> Result<(), MlsError> {
+        let message = MlsMessageIn::tls_deserialize(&mut &data[..]).map_err(failed("Decoding Welcome"))?;
+        let MlsMessageBodyIn::Welcome(welcome) = message.extract() else {
+            return Err(MlsError::NotAWelcome);
+        };
+        let join_config = MlsGroupJoinConfig::builder()
+            .wire_format_policy(wire_format_policy(policy))
+            .build();
+        let staged = StagedWelcome::new_from_welcome(&self.crypto, &join_config, welcome, None)
+            .map_err(failed("Staging Welcome"))?;
+        for member in staged.members() {
+            self.handshake.check(&member.credential)
+                .map_err(|reason| MlsError::PolicyViolation { group_id: group_id.to_string(), reason })?;
+        }
+        let group = staged.into_group(&self.crypto).map_err(failed("Joining group"))?;
+        self.sent_generations.remove(group_id);
+        self.groups.insert(group_id.to_string(), group);
+        Ok(())
+    }
+
+    /// Everything about `key_package` that keeps it out of the group: a different ciphersuite, or
+    /// capabilities the group's required capabilities extension demands but the leaf lacks.
+    pub fn check_compatibility(&self, group_id: &str, key_package: &KeyPackage) -> Result<Vec<Mismatch>, MlsError> {
+        let group = self.groups.get(group_id)
+            .ok_or_else(|| MlsError::UnknownGroup(group_id.to_string()))?;
+        let mut mismatches = Vec::new();
+        if key_package.ciphersuite() != group.ciphersuite() {
+            mismatches.push(Mismatch::Ciphersuite { group: group.ciphersuite(), offered: key_package.ciphersuite() });
+        }
+        if let Some(required) = group.extensions().required_capabilities() {
+            let capabilities = key_package.leaf_node().capabilities();
+            mismatches.extend(required.extension_types().iter()
+                .filter(|extension| !capabilities.extensions().contains(extension))
+                .map(|extension| Mismatch::Extension(*extension)));
+            mismatches.extend(required.proposal_types().iter()
+                .filter(|proposal| !capabilities.proposals().contains(proposal))
+                .map(|proposal| Mismatch::Proposal(*proposal)));
+            mismatches.extend(required.credential_types().iter()
+                .filter(|credential| !capabilities.credentials().contains(credential))
+                .map(|credential| Mismatch::Credential(*credential)));
+        }
+        Ok(mismatches)
+    }
+
+    /// Stage a group context extensions commit that drops the relaxable `mismatches` from the
+    /// required capabilities; returns the commit to deliver.
+    pub fn relax_requirements(&mut self, group_id: &str, mismatches: &[Mismatch]) -> Result<Vec<u8>, MlsError> {
+        let group = self.groups.get_mut(group_id)
+            .ok_or_else(|| MlsError::UnknownGroup(group_id.to_string()))?;
+        let mut extensions: Vec<Extension> = group.extensions()
+            .iter()
+            .filter(|extension| extension.extension_type() != ExtensionType::RequiredCapabilities)
+            .cloned()
+            .collect();
+        if let Some(required) = group.extensions().required_capabilities() {
+            let extension_types: Vec<ExtensionType> = required.extension_types().iter()
+                .filter(|extension| !mismatches.contains(&Mismatch::Extension(**extension)))
+                .copied()
+                .collect();
+            let proposal_types: Vec<ProposalType> = required.proposal_types().iter()
+                .filter(|proposal| !mismatches.contains(&Mismatch::Proposal(**proposal)))
+                .copied()
+                .collect();
+            let credential_types: Vec<CredentialType> = required.credential_types().iter()
+                .filter(|credential| !mismatches.contains(&Mismatch::Credential(**credential)))
+                .copied()
+                .collect();
+            extensions.push(Extension::RequiredCapabilities(RequiredCapabilitiesExtension::new(
+                &extension_types,
+                &proposal_types,
+                &credential_types,
+            )));
+        }
+        let extensions = Extensions::from_vec(extensions).map_err(failed("Building extensions"))?;
+        let signer = group_signer(&self.signer, &self.rotation, group_id);
+        let (commit, _welcome, _group_info) = group.update_group_context_extensions(&self.crypto, extensions, signer)
+            .map_err(failed("Relaxing required capabilities"))?;
+        commit.tls_serialize_detached().map_err(failed("Encoding commit"))
+    }
+
+    /// Propose removing ourselves from the group; returns the proposal to deliver. Another member
+    /// has to commit it, so the group itself is left to the caller to drop.
+    pub fn leave_group(&mut self, group_id: &str) -> Result<Vec<u8>, MlsError> {
+        let group = self.groups.get_mut(group_id)
+            .ok_or_else(|| MlsError::UnknownGroup(group_id.to_string()))?;
+        let signer = group_signer(&self.signer, &self.rotation, group_id);
+        let proposal = group.leave_group(&self.crypto, signer).map_err(failed("Proposing to leave"))?;
+        proposal.tls_serialize_detached().map_err(failed("Encoding proposal"))
+    }
+
+    /// Stage one commit removing every member in `identities`; returns the commit to deliver.
+    pub fn remove_members(&mut self, group_id: &str, identities: &[String]) -> Result<Vec<u8>, MlsError> {
+        let indexes = identities
+            .iter()
+            .map(|identity| {
+                self.find_member(group_id, identity)
+                    .map(|member| member.index)
+                    .ok_or_else(|| MlsError::NotAMember { group_id: group_id.to_string(), identity: identity.clone() })
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        let group = self.groups.get_mut(group_id)
+            .ok_or_else(|| MlsError::UnknownGroup(group_id.to_string()))?;
+        let signer = group_signer(&self.signer, &self.rotation, group_id);
+        let (commit, _welcome, _group_info) = group.remove_members(&self.crypto, signer, &indexes)
+            .map_err(failed("Removing members"))?;
+        commit.tls_serialize_detached().map_err(failed("Encoding commit"))
+    }
+
+    /// Encrypt an application payload for the group and return the serialized MLS message.
+    pub fn encrypt_message(&mut self, group_id: &str, plaintext: &[u8]) -> Result<Vec<u8>, MlsError> {
+        self.encrypt_message_detailed(group_id, plaintext).map(|(ciphertext, _)| ciphertext)
+    }
+
+    /// `encrypt_message`, also reporting the epoch, ratchet generation and sizes involved.
+    pub fn encrypt_message_detailed(&mut self, group_id: &str, plaintext: &[u8]) -> Result<(Vec<u8>, EncryptionDetails), MlsError> {
+        let group = self.groups.get_mut(group_id)
+            .ok_or_else(|| MlsError::UnknownGroup(group_id.to_string()))?;
+        let epoch = group.epoch().as_u64();
+        let signer = group_signer(&self.signer, &self.rotation, group_id);
+        let message = group.create_message(&self.crypto, signer, plaintext).map_err(failed("Encrypting message"))?;
+        let ciphertext = message.tls_serialize_detached().map_err(failed("Encoding message"))?;
+        // Each epoch starts a fresh ratchet at generation 0
+        let next = match self.sent_generations.get(group_id) {
+            Some((sent_epoch, next)) if *sent_epoch == epoch => *next,
+            _ => 0,
+        };
+        self.sent_generations.insert(group_id.to_string(), (epoch, next + 1));
+        let details = EncryptionDetails {
+            epoch,
+            generation: next,
+            plaintext_bytes: plaintext.len(),
+            ciphertext_bytes: ciphertext.len(),
+            padding_size: group.configuration().padding_size(),
+        };
+        Ok((ciphertext, details))
+    }
+
+    /// Decrypt/verify a serialized MLS message for the group, merging commits as they arrive.
+    pub fn process_incoming(&mut self, group_id: &str, data: &[u8]) -> Result<IncomingMls, MlsError> {
+        let group = self.groups.get_mut(group_id)
+            .ok_or_else(|| MlsError::UnknownGroup(group_id.to_string()))?;
+        let message = MlsMessageIn::tls_deserialize(&mut &data[..]).map_err(failed("Decoding message"))?;
+        let protocol_message = message.try_into_protocol_message().map_err(failed("Decoding message"))?;
+        let policy = group.configuration().wire_format_policy();
+        let wire_format = protocol_message.wire_format();
+        if !accepts(policy.incoming(), wire_format) {
+            return Err(MlsError::WireFormatRejected {
+                group_id: group_id.to_string(),
+                wire_format: wire_format_name(wire_format),
+                policy: incoming_policy_name(policy.incoming()),
+            });
+        }
+        let processed = group.process_message(&self.crypto, protocol_message).map_err(|e| match e {
+            ProcessMessageError::ValidationError(ValidationError::WrongEpoch) => {
+                MlsError::WrongEpoch { group_id: group_id.to_string() }
+            }
+            ProcessMessageError::ValidationError(ValidationError::UnknownMember) => {
+                MlsError::UnknownSender { group_id: group_id.to_string() }
+            }
+            e => failed("Processing message")(e),
+        })?;
+        let sender = credential_identity(processed.credential());
+        let committer = processed.credential().clone();
+        let proof = Authenticated {
+            epoch: processed.epoch().as_u64(),
+            leaf: match processed.sender() {
+                Sender::Member(leaf) => Some(leaf.u32()),
+                _ => None,
+            },
+            wire_format: wire_format_name(wire_format).to_string(),
+            aad: to_hex(processed.aad()),
+        };
+
+        match processed.into_content() {
+            ProcessedMessageContent::ApplicationMessage(app) => Ok(IncomingMls::Application {
+                sender,
+                content: app.into_bytes(),
+                proof,
+            }),
+            ProcessedMessageContent::StagedCommitMessage(staged) => {
+                // Rejected commits are never merged: the group stays in its epoch until the policy allows them
+                for credential in commit_credentials(committer, &staged) {
+                    self.handshake.check(&credential)
+                        .map_err(|reason| MlsError::PolicyViolation { group_id: group_id.to_string(), reason })?;
+                }
+                // Mode and admins change only by a commit from someone who was an admin before it
+                let state = GroupState::find(group.extensions());
+                if GroupState::find(staged.group_context().extensions()) != state
+                    && !state.is_some_and(|state| state.is_admin(&sender))
+                {
+                    return Err(MlsError::PolicyViolation {
+                        group_id: group_id.to_string(),
+                        reason: format!("{} changed the group's mode or admins without being an admin", sender),
+                    });
+                }
+                let summary = CommitSummary::new(group, sender.clone(), &staged);
+                let removed = staged.self_removed();
+                // Only one commit per epoch wins; theirs reached the DS first, so mine is void
+                let identity = String::from_utf8_lossy(self.credential.identity()).into_owned();
+                let superseded = group.pending_commit().map(|pending| CommitSummary::new(group, identity, pending));
+                if superseded.is_some() {
+                    group.clear_pending_commit(self.crypto.storage()).map_err(failed("Clearing pending commit"))?;
+                    self.rotating.remove(group_id);
+                }
+                group.merge_staged_commit(&self.crypto, *staged).map_err(failed("Merging commit"))?;
+                if removed {
+                    // Nothing further can be decrypted; keep no state that suggests otherwise
+                    self.groups.remove(group_id);
+                    return Ok(IncomingMls::Removed { by: sender });
+                }
+                Ok(IncomingMls::Commit { summary, superseded })
+            }
+            ProcessedMessageContent::ProposalMessage(proposal) => {
+                // The next commit from any member includes it
+                group.store_pending_proposal(self.crypto.storage(), *proposal).map_err(failed("Storing proposal"))?;
+                Ok(IncomingMls::Proposal { sender })
+            }
+            ProcessedMessageContent::ExternalJoinProposalMessage(_) => Ok(IncomingMls::Proposal { sender }),
+        }
+    }
+}
+
+/// The key our leaf in `group_id` carries: the rotation's new one once its Update has merged
+/// there, else the one we started with.
+fn group_signer<'a>(signer: &'a SignatureKeyPair, rotation: &'a Option<IdentityRotation>, group_id: &str) -> &'a SignatureKeyPair {
+    match rotation {
+        Some(rotation) if rotation.committed(group_id) => &rotation.new_signer,
+        _ => signer,
+    }
+}
+
+/// The committer's credential and every credential the commit adds or updates.
+fn commit_credentials(committer: Credential, staged: &StagedCommit) -> Vec<Credential> {
+    let mut credentials = vec![committer];
+    credentials.extend(staged.add_proposals().map(|add| add.add_proposal().key_package().leaf_node().credential().clone()));
+    credentials.extend(staged.update_proposals().map(|update| update.update_proposal().leaf_node().credential().clone()));
+    credentials.extend(staged.update_path_leaf_node().map(|leaf| leaf.credential().clone()));
+    credentials
+}
+
+fn build_key_package(
+    crypto: &OpenMlsRustCrypto,
+    signer: &SignatureKeyPair,
+    credential: &BasicCredential,
+    signature_key: &SignaturePublicKey,
+) -> Result<KeyPackage, MlsError> {
+    // Create credential with key
+    let credential_with_key = CredentialWithKey {
+        credential: credential.clone().into(),
+        signature_key: signature_key.clone(),
+    };
+
+    // Create key package bundle
+    let key_package_bundle = KeyPackage::builder()
+        .leaf_node_capabilities(extensions::leaf_capabilities())
+        .build(
+            Ciphersuite::MLS_128_DHKEMX25519_AES128GCM_SHA256_Ed25519,
+            crypto,
+            signer,
+            credential_with_key,
+        )
+        .map_err(failed("Building key package"))?;
+
+    Ok(key_package_bundle.key_package().clone())
+}
+
+/// The openmls policy behind a group's `WirePolicy`.
+pub fn wire_format_policy(policy: WirePolicy) -> WireFormatPolicy {
+    match policy {
+        WirePolicy::Ciphertext => PURE_CIPHERTEXT_WIRE_FORMAT_POLICY,
+        WirePolicy::Mixed => MIXED_CIPHERTEXT_WIRE_FORMAT_POLICY,
+        WirePolicy::PublicCommits => MIXED_PLAINTEXT_WIRE_FORMAT_POLICY,
+    }
+}
+
+/// Checked before processing so a mismatch is reported as such rather than as a generic failure.
+fn accepts(incoming: IncomingWireFormatPolicy, wire_format: WireFormat) -> bool {
+    match incoming {
+        IncomingWireFormatPolicy::Mixed => true,
+        IncomingWireFormatPolicy::AlwaysCiphertext => wire_format == WireFormat::PrivateMessage,
+        IncomingWireFormatPolicy::AlwaysPlaintext => wire_format == WireFormat::PublicMessage,
+    }
+}
+
+fn wire_format_name(wire_format: WireFormat) -> &'static str {
+    match wire_format {
+        WireFormat::PublicMessage => "PublicMessage",
+        WireFormat::PrivateMessage => "PrivateMessage",
+        WireFormat::Welcome => "Welcome",
+        WireFormat::GroupInfo => "GroupInfo",
+        WireFormat::KeyPackage => "KeyPackage",
+    }
+}
+
+fn incoming_policy_name(incoming: IncomingWireFormatPolicy) -> &'static str {
+    match incoming {
+        IncomingWireFormatPolicy::AlwaysCiphertext => "ciphertext-only",
+        IncomingWireFormatPolicy::AlwaysPlaintext => "plaintext-only",
+        IncomingWireFormatPolicy::Mixed => "mixed",
+    }
+}
+
+/// Wrap an openmls error with the operation that produced it.
+fn failed<E: std::error::Error + Send + Sync + 'static>(operation: &'static str) -> impl FnOnce(E) -> MlsError {
+    move |source| MlsError::Protocol { operation, source: Box::new(source) }
+}
+
+/// Parse and verify a serialized key package received from the delivery service.
+pub fn decode_key_package(crypto: &OpenMlsRustCrypto, data: &[u8]) -> Result<KeyPackage, MlsError> {
+    let key_package_in = KeyPackageIn::tls_deserialize(&mut &data[..]).map_err(failed("Decoding key package"))?;
+    key_package_in.validate(crypto.crypto(), ProtocolVersion::Mls10).map_err(failed("Validating key package"))
+}
+
+/// Human-readable summary of a key package for the `kp show` popup.
+pub fn describe_key_package(crypto: &OpenMlsRustCrypto, key_package: &KeyPackage) -> Vec<String> {
+    let leaf_node = key_package.leaf_node();
+    let capabilities = leaf_node.capabilities();
+    let lifetime = key_package.life_time();
+    let format_time = |secs: u64| {
+        DateTime::from_timestamp(secs as i64, 0)
+            .map(|t| t.with_timezone(&Local).format("%Y-%m-%d %H:%M:%S").to_string())
+            .unwrap_or_else(|| secs.to_string())
+    };
+    let hash_ref = key_package
+        .hash_ref(crypto.crypto())
+        .map(|hash| to_hex(hash.as_slice()))
+        .unwrap_or_else(|e| format!("<error: {}>", e));
+    let extensions: Vec<String> = key_package
+        .extensions()
+        .iter()
+        .map(|extension| format!("{:?}", extension.extension_type()))
+        .collect();
+
+    vec![
+        format!("Identity:        {}", credential_identity(leaf_node.credential())),
+        format!("Credential type: {:?}", leaf_node.credential().credential_type()),
+        format!("Ciphersuite:     {:?}", key_package.ciphersuite()),
+        format!("Signature key:   {}", to_hex(leaf_node.signature_key().as_slice())),
+        format!("Hash ref:        {}", hash_ref),
+        format!("Valid from:      {}", format_time(lifetime.not_before())),
+        format!("Expires:         {}", format_time(lifetime.not_after())),
+        String::new(),
+        "Capabilities:".to_string(),
+        format!("  Versions:      {:?}", capabilities.versions()),
+        format!("  Ciphersuites:  {:?}", capabilities.ciphersuites()),
+        format!("  Extensions:    {:?}", capabilities.extensions()),
+        format!("  Proposals:     {:?}", capabilities.proposals()),
+        format!("  Credentials:   {:?}", capabilities.credentials()),
+        String::new(),
+        format!(
+            "Extensions:      {}",
+            if extensions.is_empty() { "none".to_string() } else { extensions.join(", ") }
+        ),
+    ]
+}
+
+/// Render the identity carried by a basic credential, falling back to a placeholder.
+pub fn credential_identity(credential: &Credential) -> String {
+    BasicCredential::try_from(credential.clone())
+        .map(|basic| String::from_utf8_lossy(basic.identity()).into_owned())
+        .unwrap_or_else(|_| "<unknown>".to_string())
+}
+/// Framing, encrypted sender data, the sender's signature and the AEAD tag around an
+/// application message, roughly, for the default Ed25519 ciphersuite.
+const CIPHERTEXT_OVERHEAD: usize = 128;
+
+/// Estimated size of the MLS message carrying `plaintext_len` bytes in `group_id`, before
+/// compression and transport encoding.
+pub fn ciphertext_estimate(group_id: &str, plaintext_len: usize) -> usize {
+    plaintext_len + group_id.len() + CIPHERTEXT_OVERHEAD
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::group::GroupMode;
+
+    const GROUP_ID: &str = "announcements";
+
+    /// Alice's announcement group with Bob added, from each side.
+    async fn announcement_group() -> (MlsClient, MlsClient) {
+        let crypto = CryptoProvider::new();
+        let mut alice = MlsClient::new("alice", &crypto).await.unwrap();
+        let mut bob = MlsClient::new("bob", &crypto).await.unwrap();
+        let state = GroupState::new("alice", GroupMode::Announce);
+        let group = alice.create_group(WirePolicy::Ciphertext, &state).unwrap();
+        alice.add_group(GROUP_ID, group);
+        let (_commit, welcome) = alice.add_member(GROUP_ID, bob.get_key_package()).unwrap();
+        alice.merge_pending_commit(GROUP_ID).unwrap();
+        bob.join_from_welcome(GROUP_ID, &welcome, WirePolicy::Ciphertext).unwrap();
+        (alice, bob)
+    }
+
+    #[tokio::test]
+    async fn rejects_a_state_change_from_a_non_admin() {
+        let (mut alice, mut bob) = announcement_group().await;
+        let takeover = GroupState { mode: GroupMode::Chat, admins: vec!["bob".to_string()] };
+        let commit = bob.stage_group_state(GROUP_ID, &takeover).unwrap();
+        assert!(matches!(alice.process_incoming(GROUP_ID, &commit), Err(MlsError::PolicyViolation { .. })));
+        assert_eq!(alice.group_state(GROUP_ID), Some(GroupState::new("alice", GroupMode::Announce)));
+    }
+
+    #[tokio::test]
+    async fn applies_a_state_change_from_an_admin() {
+        let (mut alice, mut bob) = announcement_group().await;
+        let promoted = GroupState { mode: GroupMode::Announce, admins: vec!["alice".to_string(), "bob".to_string()] };
+        let commit = alice.stage_group_state(GROUP_ID, &promoted).unwrap();
+        assert!(matches!(bob.process_incoming(GROUP_ID, &commit).unwrap(), IncomingMls::Commit { .. }));
+        assert_eq!(bob.group_state(GROUP_ID), Some(promoted));
+    }
+}
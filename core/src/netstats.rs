@@ -0,0 +1,124 @@
+use chrono::{DateTime, Local};
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use crate::trace::Direction;
+
+/// Reconnect attempts listed by `netstat`; older ones only count towards the totals.
+const MAX_RECONNECTS: usize = 20;
+
+/// Frames of one message type exchanged with the delivery service.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct FrameCount {
+    pub sent: u64,
+    pub received: u64,
+}
+
+/// A connection attempt made after the initial one.
+#[derive(Debug, Clone)]
+pub struct Reconnect {
+    pub at: DateTime<Local>,
+    /// The endpoint connected to, or the first one tried when every endpoint failed
+    pub endpoint: String,
+    pub succeeded: bool,
+}
+
+/// Traffic and connection counters for the session, kept across reconnects.
+#[derive(Debug, Clone)]
+pub struct NetworkStats {
+    pub since: DateTime<Local>,
+    /// Bytes on the wire, newline delimiters included
+    pub bytes_sent: u64,
+    pub bytes_received: u64,
+    /// By the frame's `type`; frames without one count as "other"
+    pub frames: BTreeMap<String, FrameCount>,
+    pub connect_attempts: u64,
+    pub reconnect_failures: u64,
+    /// Connections the server closed or that broke while in use
+    pub connection_drops: u64,
+    /// The most recent reconnect attempts, oldest first
+    pub reconnects: Vec<Reconnect>,
+}
+
+impl Default for NetworkStats {
+    fn default() -> Self {
+        Self {
+            since: Local::now(),
+            bytes_sent: 0,
+            bytes_received: 0,
+            frames: BTreeMap::new(),
+            connect_attempts: 0,
+            reconnect_failures: 0,
+            connection_drops: 0,
+            reconnects: Vec::new(),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct FrameType {
+    #[serde(rename = "type")]
+    message_type: String,
+}
+
+impl NetworkStats {
+    /// Count one frame written to or read from the socket; `line` is its JSON without the newline.
+    pub fn record_frame(&mut self, direction: Direction, line: &str) {
+        let bytes = line.len() as u64 + 1;
+        let message_type = serde_json::from_str::<FrameType>(line)
+            .map(|frame| frame.message_type)
+            .unwrap_or_else(|_| "other".to_string());
+        let count = self.frames.entry(message_type).or_default();
+        match direction {
+            Direction::Sent => {
+                self.bytes_sent += bytes;
+                count.sent += 1;
+            }
+            Direction::Received => {
+                self.bytes_received += bytes;
+                count.received += 1;
+            }
+        }
+    }
+
+    /// Count a connection attempt; every attempt after the first is a reconnect.
+    pub fn record_connect(&mut self, endpoint: &str, succeeded: bool) {
+        self.connect_attempts += 1;
+        if self.connect_attempts == 1 {
+            return;
+        }
+        if !succeeded {
+            self.reconnect_failures += 1;
+        }
+        self.reconnects.push(Reconnect { at: Local::now(), endpoint: endpoint.to_string(), succeeded });
+        let excess = self.reconnects.len().saturating_sub(MAX_RECONNECTS);
+        self.reconnects.drain(..excess);
+    }
+
+    pub fn reconnect_attempts(&self) -> u64 {
+        self.connect_attempts.saturating_sub(1)
+    }
+
+    pub fn frames_sent(&self) -> u64 {
+        self.frames.values().map(|count| count.sent).sum()
+    }
+
+    pub fn frames_received(&self) -> u64 {
+        self.frames.values().map(|count| count.received).sum()
+    }
+}
+
+/// `bytes` with a binary unit, e.g. "1.5 MiB".
+pub fn format_bytes(bytes: u64) -> String {
+    const UNITS: &[&str] = &["B", "KiB", "MiB", "GiB"];
+    let mut size = bytes as f64;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{} B", bytes)
+    } else {
+        format!("{:.1} {}", size, UNITS[unit])
+    }
+}
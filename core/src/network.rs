@@ -0,0 +1,993 @@
+use serde::{Deserialize, Serialize};
+use chrono::{DateTime, Local};
+use std::collections::BTreeSet;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::net::TcpStream;
+use tokio::net::tcp::{OwnedReadHalf, OwnedWriteHalf};
+use tokio::time::timeout;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader, Lines};
+use base64::{Engine as _, engine::general_purpose::STANDARD as BASE64};
+use crate::errors::NetworkError;
+use crate::netstats::NetworkStats;
+use crate::send_queue::{Frame, SendPriority, SendQueue};
+use crate::tasks::{ProgressEvent, TaskId};
+use crate::trace::{Direction, ProtocolTrace, TraceEntry};
+use crate::transport::{self, HelloSession, ServerKey};
+use tokio::sync::mpsc;
+
+const SEND_QUEUE_CAPACITY: usize = 256;
+/// Attempts made to queue a frame before giving up, reconnecting in between.
+const MAX_SEND_ATTEMPTS: u32 = 3;
+const INITIAL_BACKOFF: Duration = Duration::from_millis(250);
+/// Inbound frames held until fetched; the oldest are dropped beyond this.
+const MAX_INBOX: usize = 10_000;
+/// Delivery sequences remembered for dropping redelivered frames.
+const MAX_RECEIVED: usize = 50_000;
+/// How long to wait for the delivery service to answer a request.
+const REPLY_TIMEOUT: Duration = Duration::from_secs(2);
+/// How long a redeemed invite token waits for the issuing admin's Welcome.
+const TOKEN_WELCOME_TIMEOUT: Duration = Duration::from_secs(30);
+/// Most bytes a debounced write gathers, so a commit is not stuck behind a long batch.
+const MAX_BATCH_BYTES: usize = 64 * 1024;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NetworkMessage {
+    #[serde(rename = "type")]
+    pub message_type: String,
+    pub sender: String,
+    pub recipient: Option<String>,
+    pub group_id: Option<String>,
+    pub content: Vec<u8>,
+    pub timestamp: u64,
+    /// Server-assigned delivery sequence, authoritative for display ordering
+    #[serde(default)]
+    pub sequence: Option<u64>,
+    /// When the server received the frame (Unix seconds), stamped along with the sequence;
+    /// shown instead of the sender-claimed `timestamp`
+    #[serde(default)]
+    pub received_at: Option<u64>,
+    /// Sender-chosen ID used to drop duplicates after reconnects and re-fetches
+    #[serde(default)]
+    pub message_id: Option<String>,
+}
+
+impl NetworkMessage {
+    /// A frame addressed to a group, stamped with the current time and a fresh ID.
+    pub fn for_group(message_type: &str, sender: &str, group_id: &str, content: Vec<u8>) -> Self {
+        Self {
+            message_type: message_type.to_string(),
+            sender: sender.to_string(),
+            recipient: None,
+            group_id: Some(group_id.to_string()),
+            content,
+            timestamp: chrono::Utc::now().timestamp() as u64,
+            sequence: None,
+            received_at: None,
+            message_id: Some(uuid::Uuid::new_v4().to_string()),
+        }
+    }
+
+    /// When the delivery service says it received the frame, if it stamps frames.
+    pub fn server_received_at(&self) -> Option<DateTime<Local>> {
+        self.received_at
+            .and_then(|at| DateTime::from_timestamp(i64::try_from(at).ok()?, 0))
+            .map(|at| at.with_timezone(&Local))
+    }
+}
+
+/// Reply to the initial request; only the server clock, capabilities, identity key and size
+/// limits are of interest here.
+#[derive(Debug, Deserialize)]
+struct HelloResponse {
+    #[serde(default)]
+    server_time: Option<u64>,
+    #[serde(default)]
+    capabilities: Option<Vec<String>>,
+    /// Ed25519 identity key of the delivery service, base64
+    #[serde(default)]
+    server_key: Option<String>,
+    /// Its signature over the session (see `transport::server_key_payload`), base64
+    #[serde(default)]
+    key_proof: Option<String>,
+    #[serde(flatten)]
+    limits: ServerLimits,
+}
+
+/// How a connection attempt ended. The client prints nothing; callers report it.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ConnectStatus {
+    Connected { endpoint: String },
+    /// Every endpoint failed; `reason` is the last one's
+    Failed { endpoint: String, reason: String },
+}
+
+impl ConnectStatus {
+    pub fn describe(&self) -> String {
+        match self {
+            ConnectStatus::Connected { endpoint } => format!("Connected to MLS service at {}", endpoint),
+            ConnectStatus::Failed { endpoint, reason } => format!("Failed to connect to MLS service at {}: {}", endpoint, reason),
+        }
+    }
+}
+
+/// Size limits advertised by the delivery service, if any.
+#[derive(Debug, Clone, Copy, Default, Deserialize)]
+pub struct ServerLimits {
+    #[serde(default)]
+    pub max_message_bytes: Option<usize>,
+    #[serde(default)]
+    pub max_attachment_bytes: Option<usize>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ListKeyPackagesMessage {
+    #[serde(rename = "type")]
+    pub message_type: String,
+    pub client_id: String,
+    /// Random nonce the delivery service signs with its identity key in the reply
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub challenge: Option<String>,
+    /// The endpoint as dialed, which the delivery service signs too; one that does not answer
+    /// to this name must not sign it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub server_name: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CreateGroupMessage {
+    #[serde(rename = "type")]
+    pub message_type: String,
+    pub group_id: String,
+    pub creator_id: String,
+    pub group_info: String, // base64 encoded
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct JoinGroupMessage {
+    #[serde(rename = "type")]
+    pub message_type: String,
+    pub group_id: String,
+    pub client_id: String,
+    pub key_package: String, // base64 encoded
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PublishKeyPackageMessage {
+    #[serde(rename = "type")]
+    pub message_type: String,
+    pub client_id: String,
+    pub key_package: String, // base64 encoded
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct FetchKeyPackagesMessage {
+    #[serde(rename = "type")]
+    pub message_type: String,
+    pub client_id: String,
+    pub identity: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RequestInviteTokenMessage {
+    #[serde(rename = "type")]
+    pub message_type: String,
+    pub client_id: String,
+    pub group_id: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RedeemInviteTokenMessage {
+    #[serde(rename = "type")]
+    pub message_type: String,
+    pub client_id: String,
+    pub token: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct FindIdentitiesMessage {
+    #[serde(rename = "type")]
+    pub message_type: String,
+    pub client_id: String,
+    pub query: String,
+}
+
+/// One identity in the delivery service's `directory` reply, with the key packages it has
+/// published (base64 encoded).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DirectoryEntry {
+    pub identity: String,
+    #[serde(default)]
+    pub key_packages: Vec<String>,
+}
+
+/// Body of the delivery service's `invite_token` and `token_redeemed` replies. A rejected
+/// redemption (unknown or already used token) carries no group.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct InviteToken {
+    pub token: String,
+    #[serde(default)]
+    pub group_id: Option<String>,
+}
+
+/// Confirms that frames with these delivery sequences were processed, so the delivery
+/// service may delete them instead of redelivering them on the next connect.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AckMessage {
+    #[serde(rename = "type")]
+    pub message_type: String,
+    pub client_id: String,
+    pub sequences: Vec<u64>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ListGroupsMessage {
+    #[serde(rename = "type")]
+    pub message_type: String,
+    pub client_id: String,
+}
+
+pub struct NetworkClient {
+    /// Addresses of the same delivery service, in configured order
+    endpoints: Vec<String>,
+    /// The endpoint in use, or the last one that worked; tried first on every connect
+    active_endpoint: usize,
+    /// Endpoints (from, to) of the last failover, until the UI takes it
+    failover: Option<(String, String)>,
+    client_id: String,
+    connected: bool,
+    send_queue: Option<Arc<SendQueue>>,
+    server_clock_offset: Option<i64>,
+    server_limits: ServerLimits,
+    /// Capabilities the delivery service advertised, `None` if it did not say
+    server_capabilities: Option<Vec<String>>,
+    /// Identity key presented in the last greeting, `None` if the server sent none
+    server_key: Option<ServerKey>,
+    progress_tx: mpsc::UnboundedSender<ProgressEvent>,
+    progress_rx: mpsc::UnboundedReceiver<ProgressEvent>,
+    trace: Arc<Mutex<ProtocolTrace>>,
+    inbox: Arc<Mutex<Inbox>>,
+    stats: Arc<Mutex<NetworkStats>>,
+    /// Milliseconds the writer waits for more frames before writing, 0 to write at once
+    send_debounce: Arc<AtomicU64>,
+    /// Frames a failed write left unsent, oldest first, queued again on the next connection
+    stranded: Arc<Mutex<Vec<(SendPriority, Frame)>>>,
+    /// Outcome of the last connection attempt
+    connect_status: ConnectStatus,
+}
+
+/// Frames read from the delivery service until the application takes them, and the delivery
+/// sequences still to acknowledge. The service redelivers unacknowledged frames on every
+/// connect, so delivery is at-least-once; duplicates are dropped here by sequence, and the
+/// timeline additionally skips message IDs it has already applied.
+#[derive(Default)]
+struct Inbox {
+    /// Oldest first
+    frames: Vec<NetworkMessage>,
+    /// Sequences read this session
+    received: BTreeSet<u64>,
+    /// Sequences of frames handed to the application since the last acknowledgement
+    unacked: Vec<u64>,
+    /// When the last acknowledgement was queued
+    last_ack: Option<Instant>,
+}
+
+impl Inbox {
+    fn push(&mut self, message: NetworkMessage) {
+        if let Some(sequence) = message.sequence {
+            if !self.received.insert(sequence) {
+                // Redelivered: if it was already handed out, our acknowledgement was lost
+                if !self.frames.iter().any(|frame| frame.sequence == Some(sequence)) {
+                    self.unacked.push(sequence);
+                }
+                return;
+            }
+            while self.received.len() > MAX_RECEIVED {
+                self.received.pop_first();
+            }
+        }
+        self.frames.push(message);
+        let excess = self.frames.len().saturating_sub(MAX_INBOX);
+        // Dropped frames are never acknowledged, so accept them again when redelivered
+        for dropped in self.frames.drain(..excess) {
+            if let Some(sequence) = dropped.sequence {
+                self.received.remove(&sequence);
+            }
+        }
+    }
+
+    /// Remove and return the frames `wanted` selects, oldest first, to be acknowledged.
+    fn take(&mut self, wanted: impl Fn(&NetworkMessage) -> bool) -> Vec<NetworkMessage> {
+        let (taken, kept): (Vec<_>, Vec<_>) = std::mem::take(&mut self.frames).into_iter().partition(|message| wanted(message));
+        self.frames = kept;
+        self.unacked.extend(taken.iter().filter_map(|message| message.sequence));
+        taken
+    }
+
+    /// Return frames taken but not used to the front of the inbox.
+    fn put_back(&mut self, messages: Vec<NetworkMessage>) {
+        self.unacked.retain(|sequence| !messages.iter().any(|message| message.sequence == Some(*sequence)));
+        self.frames.splice(0..0, messages);
+    }
+}
+
+/// Handle for requests whose replies are awaited on a spawned task rather than the UI loop.
+#[derive(Clone)]
+pub struct Requester {
+    client_id: String,
+    queue: Arc<SendQueue>,
+    inbox: Arc<Mutex<Inbox>>,
+    trace: Arc<Mutex<ProtocolTrace>>,
+}
+
+impl NetworkClient {
+    /// Connect to the first reachable address in `endpoints`, all serving the same delivery
+    /// service; the client stays disconnected (without an error) if none answers.
+    pub async fn new(endpoints: &[String], client_id: &str) -> Result<Self, NetworkError> {
+        let (progress_tx, progress_rx) = mpsc::unbounded_channel();
+        let mut client = Self {
+            endpoints: endpoints.to_vec(),
+            active_endpoint: 0,
+            failover: None,
+            client_id: client_id.to_string(),
+            connected: false,
+            send_queue: None,
+            server_clock_offset: None,
+            server_limits: ServerLimits::default(),
+            server_capabilities: None,
+            server_key: None,
+            progress_tx,
+            progress_rx,
+            trace: Arc::new(Mutex::new(ProtocolTrace::default())),
+            inbox: Arc::new(Mutex::new(Inbox::default())),
+            stats: Arc::new(Mutex::new(NetworkStats::default())),
+            send_debounce: Arc::new(AtomicU64::new(0)),
+            stranded: Arc::new(Mutex::new(Vec::new())),
+            connect_status: ConnectStatus::Failed { endpoint: String::new(), reason: "not attempted".to_string() },
+        };
+        
+        // Attempt to connect to the delivery service
+        client.connect().await?;
+        
+        Ok(client)
+    }
+
+    /// Connect to the endpoint that worked last, failing over to the others in configured order.
+    pub async fn connect(&mut self) -> Result<ConnectStatus, NetworkError> {
+        let previous = self.active_endpoint;
+        let order: Vec<usize> = std::iter::once(previous)
+            .chain((0..self.endpoints.len()).filter(|index| *index != previous))
+            .filter(|index| *index < self.endpoints.len())
+            .collect();
+        let mut status = ConnectStatus::Failed { endpoint: String::new(), reason: "no delivery service address configured".to_string() };
+        for index in order {
+            status = self.connect_to(index).await?;
+            if self.connected {
+                if index != previous {
+                    self.failover = Some((self.endpoints[previous].clone(), self.endpoints[index].clone()));
+                }
+                self.active_endpoint = index;
+                break;
+            }
+        }
+        self.record_connect(self.connected);
+        self.connect_status = status.clone();
+        Ok(status)
+    }
+
+    /// Outcome of the last connection attempt, such as the one `new` made.
+    pub fn connect_status(&self) -> &ConnectStatus {
+        &self.connect_status
+    }
+
+    fn record_connect(&self, succeeded: bool) {
+        if let Ok(mut stats) = self.stats.lock() {
+            stats.record_connect(self.endpoint(), succeeded);
+        }
+    }
+
+    async fn connect_to(&mut self, index: usize) -> Result<ConnectStatus, NetworkError> {
+        let address = self.endpoints[index].clone();
+        // Attempt to connect with timeout
+        match timeout(Duration::from_secs(5), TcpStream::connect(&address)).await {
+            Ok(Ok(stream)) => {
+                let (reader, mut writer) = stream.into_split();
+                // Send initial message to establish connection
+                let challenge = uuid::Uuid::new_v4().to_string();
+                let list_message = ListKeyPackagesMessage {
+                    message_type: "list_key_packages".to_string(),
+                    client_id: self.client_id.clone(),
+                    challenge: Some(challenge.clone()),
+                    server_name: Some(address.clone()),
+                };
+                
+                let message_json = serde_json::to_string(&list_message)?;
+                
+                // Send initial message
+                writer.write_all(message_json.as_bytes()).await?;
+                writer.write_all(b"\n").await?; // Add newline for line-based protocol
+                writer.flush().await?;
+                record_frame(&self.stats, Direction::Sent, &message_json);
+
+                // The reply carries the server clock and limits; older servers may not answer at all
+                let mut lines = BufReader::new(reader).lines();
+                let mut hello = Self::read_hello(&mut lines, &self.stats).await;
+                self.server_clock_offset = hello.as_ref()
+                    .and_then(|hello| hello.server_time)
+                    .map(|server_time| i64::try_from(server_time).unwrap_or(i64::MAX).saturating_sub(chrono::Utc::now().timestamp()));
+                self.server_key = hello.as_mut().and_then(|hello| {
+                    let public_key = BASE64.decode(hello.server_key.take()?).unwrap_or_default();
+                    // A missing or garbled proof is kept empty, so it fails verification
+                    let proof = hello.key_proof.take().and_then(|proof| BASE64.decode(proof).ok()).unwrap_or_default();
+                    let signed = transport::server_key_payload(&HelloSession {
+                        endpoint: &address,
+                        client_id: &self.client_id,
+                        challenge: &challenge,
+                        capabilities: hello.capabilities.as_deref(),
+                        server_time: hello.server_time,
+                        max_message_bytes: hello.limits.max_message_bytes,
+                        max_attachment_bytes: hello.limits.max_attachment_bytes,
+                    });
+                    Some(ServerKey { public_key, proof, challenge, signed })
+                });
+                self.server_capabilities = hello.as_mut().and_then(|hello| hello.capabilities.take());
+                self.server_limits = hello.map(|hello| hello.limits).unwrap_or_default();
+                
+                let queue = Arc::new(SendQueue::new(SEND_QUEUE_CAPACITY));
+                tokio::spawn(Self::writer_task(
+                    writer,
+                    queue.clone(),
+                    self.progress_tx.clone(),
+                    self.stats.clone(),
+                    self.send_debounce.clone(),
+                    self.stranded.clone(),
+                ));
+                tokio::spawn(Self::reader_task(lines, queue.clone(), self.inbox.clone(), self.stats.clone()));
+                // What the last connection failed to write goes out first; receivers skip any
+                // of it that did arrive by its message ID
+                let mut stranded = self.stranded.lock().map(|mut stranded| std::mem::take(&mut *stranded)).unwrap_or_default().into_iter();
+                while let Some((priority, frame)) = stranded.next() {
+                    if queue.push(priority, frame.clone()).await.is_err() {
+                        // This connection broke too; keep the rest for the next one
+                        if let Ok(mut kept) = self.stranded.lock() {
+                            kept.extend(std::iter::once((priority, frame)).chain(stranded));
+                        }
+                        break;
+                    }
+                }
+                self.send_queue = Some(queue);
+                self.connected = true;
+                Ok(ConnectStatus::Connected { endpoint: address })
+            }
+            Ok(Err(e)) => {
+                self.connected = false;
+                self.send_queue = None;
+                // Don't fail completely, just mark as disconnected
+                Ok(ConnectStatus::Failed { endpoint: address, reason: e.to_string() })
+            }
+            Err(_) => {
+                self.connected = false;
+                self.send_queue = None;
+                Ok(ConnectStatus::Failed { endpoint: address, reason: "connection timed out".to_string() })
+            }
+        }
+    }
+
+    pub fn is_connected(&self) -> bool {
+        self.connected && self.send_queue.as_ref().is_some_and(|queue| !queue.is_closed())
+    }
+
+    /// The address in use, or the one the next connect tries first.
+    pub fn endpoint(&self) -> &str {
+        self.endpoints.get(self.active_endpoint).map_or("", String::as_str)
+    }
+
+    /// Position of `endpoint` in the configured list (0 is the preferred address) and the list length.
+    pub fn endpoint_position(&self) -> (usize, usize) {
+        (self.active_endpoint, self.endpoints.len())
+    }
+
+    /// The endpoints (from, to) of a failover since the last call.
+    pub fn take_failover(&mut self) -> Option<(String, String)> {
+        self.failover.take()
+    }
+
+    /// Frames waiting to be written, per priority in `SendPriority::ALL` order.
+    pub fn queue_depths(&self) -> [usize; 4] {
+        self.send_queue.as_ref().map(|queue| queue.depths()).unwrap_or_default()
+    }
+
+    /// Drain the send queue onto the socket; the queue is closed if the connection breaks. A
+    /// failed write leaves its batch and everything queued behind it in `stranded`.
+    async fn writer_task(
+        mut stream: OwnedWriteHalf,
+        queue: Arc<SendQueue>,
+        progress: mpsc::UnboundedSender<ProgressEvent>,
+        stats: Arc<Mutex<NetworkStats>>,
+        debounce: Arc<AtomicU64>,
+        stranded: Arc<Mutex<Vec<(SendPriority, Frame)>>>,
+    ) {
+        while let Some(next) = queue.pop().await {
+            let mut batch = vec![next];
+            // On slow links, wait for frames sent close behind so they go out in one write
+            let wait = Duration::from_millis(debounce.load(Ordering::Relaxed));
+            if !wait.is_zero() {
+                tokio::time::sleep(wait).await;
+                let mut bytes = batch[0].1.data.len();
+                while bytes < MAX_BATCH_BYTES && !queue.is_empty() {
+                    let Some(next) = queue.pop().await else {
+                        break;
+                    };
+                    bytes += next.1.data.len();
+                    batch.push(next);
+                }
+            }
+            let lines: String = batch.iter().map(|(_, frame)| format!("{}\n", frame.data)).collect();
+            let written = async {
+                stream.write_all(lines.as_bytes()).await?;
+                stream.flush().await
+            }.await;
+            match written {
+                Ok(()) => {
+                    for (_, frame) in &batch {
+                        record_frame(&stats, Direction::Sent, &frame.data);
+                        if let Some(task) = frame.task {
+                            let _ = progress.send(ProgressEvent::FrameWritten(task));
+                        }
+                    }
+                }
+                Err(_) => {
+                    if let Ok(mut stats) = stats.lock() {
+                        stats.connection_drops += 1;
+                    }
+                    // Their operations carry on once the next connection writes them
+                    queue.close();
+                    while let Some(next) = queue.pop().await {
+                        batch.push(next);
+                    }
+                    if let Ok(mut stranded) = stranded.lock() {
+                        stranded.extend(batch);
+                    }
+                    return;
+                }
+            }
+        }
+        queue.close();
+        // Operations with frames still queued can no longer complete
+        while let Some((_, frame)) = queue.pop().await {
+            if let Some(task) = frame.task {
+                let _ = progress.send(ProgressEvent::Failed(task, "connection lost".to_string()));
+            }
+        }
+    }
+
+    /// Collect inbound frames for `fetch_messages`; the connection counts as lost once the server closes it.
+    async fn reader_task(
+        mut lines: Lines<BufReader<OwnedReadHalf>>,
+        queue: Arc<SendQueue>,
+        inbox: Arc<Mutex<Inbox>>,
+        stats: Arc<Mutex<NetworkStats>>,
+    ) {
+        while let Ok(Some(line)) = lines.next_line().await {
+            record_frame(&stats, Direction::Received, &line);
+            // Anything else on the wire (e.g. acknowledgements) is not for us
+            let Ok(message) = serde_json::from_str::<NetworkMessage>(&line) else {
+                continue;
+            };
+            let Ok(mut inbox) = inbox.lock() else {
+                break;
+            };
+            inbox.push(message);
+        }
+        // Still open means the server went away rather than us closing the connection
+        if !queue.is_closed() {
+            if let Ok(mut stats) = stats.lock() {
+                stats.connection_drops += 1;
+            }
+        }
+        queue.close();
+    }
+
+    /// Remove and return the inbound frames `wanted` selects, oldest first.
+    fn take_inbound(&self, wanted: impl Fn(&NetworkMessage) -> bool) -> Vec<NetworkMessage> {
+        take_inbound(&self.inbox, &self.trace, wanted)
+    }
+
+    /// A handle for awaiting replies off the UI loop; `None` while disconnected.
+    pub fn requester(&self) -> Option<Requester> {
+        let queue = self.send_queue.clone().filter(|_| self.is_connected())?;
+        Some(Requester {
+            client_id: self.client_id.clone(),
+            queue,
+            inbox: self.inbox.clone(),
+            trace: self.trace.clone(),
+        })
+    }
+
+    /// Progress reported by the writer task since the last call.
+    pub fn drain_progress(&mut self) -> Vec<ProgressEvent> {
+        let mut events = Vec::new();
+        while let Ok(event) = self.progress_rx.try_recv() {
+            events.push(event);
+        }
+        events
+    }
+
+    async fn enqueue(&self, priority: SendPriority, frame: Frame) -> Result<(), NetworkError> {
+        match &self.send_queue {
+            Some(queue) => queue.push(priority, frame).await,
+            None => Err(NetworkError::NotConnected),
+        }
+    }
+
+    /// Queue a frame, reconnecting with exponential backoff if the connection has dropped.
+    async fn enqueue_with_retry(&mut self, priority: SendPriority, frame: Frame) -> Result<(), NetworkError> {
+        let mut backoff = INITIAL_BACKOFF;
+        let mut attempt = 1;
+        loop {
+            if !self.is_connected() {
+                self.connect().await?;
+            }
+            match self.enqueue(priority, frame.clone()).await {
+                Ok(()) => return Ok(()),
+                Err(e) if attempt >= MAX_SEND_ATTEMPTS => return Err(e),
+                Err(_) => {
+                    tokio::time::sleep(backoff).await;
+                    backoff *= 2;
+                    attempt += 1;
+                }
+            }
+        }
+    }
+
+    /// Traffic and reconnect counters for this session.
+    pub fn stats(&self) -> NetworkStats {
+        self.stats.lock().map(|stats| stats.clone()).unwrap_or_default()
+    }
+
+    /// Seconds the server clock is ahead of ours, if the server reported its time.
+    pub fn server_clock_offset(&self) -> Option<i64> {
+        self.server_clock_offset
+    }
+
+    pub fn server_limits(&self) -> ServerLimits {
+        self.server_limits
+    }
+
+    /// Capabilities advertised in the last greeting, `None` if the server sent none.
+    pub fn server_capabilities(&self) -> Option<&[String]> {
+        self.server_capabilities.as_deref()
+    }
+
+    /// Identity key presented in the last greeting, not yet verified.
+    pub fn server_key(&self) -> Option<&ServerKey> {
+        self.server_key.as_ref()
+    }
+
+    /// The last `count` protocol frames exchanged for `group_id`, oldest first.
+    pub fn trace(&self, group_id: &str, count: usize) -> Vec<TraceEntry> {
+        self.trace.lock().map(|trace| trace.recent(group_id, count)).unwrap_or_default()
+    }
+
+    fn record(&self, direction: Direction, message_type: &str, group_id: Option<&str>, peer: Option<&str>) {
+        record(&self.trace, direction, message_type, group_id, peer);
+    }
+
+    async fn read_hello(lines: &mut Lines<BufReader<OwnedReadHalf>>, stats: &Mutex<NetworkStats>) -> Option<HelloResponse> {
+        let line = match timeout(Duration::from_secs(1), lines.next_line()).await {
+            Ok(Ok(Some(line))) => line,
+            _ => return None,
+        };
+        record_frame(stats, Direction::Received, &line);
+        serde_json::from_str(&line).ok()
+    }
+
+    pub async fn send_message(&mut self, message: &NetworkMessage) -> Result<(), NetworkError> {
+        self.send_message_for_task(message, None).await
+    }
+
+    /// Send a frame whose delivery is reported as progress of `task`.
+    pub async fn send_message_for_task(&mut self, message: &NetworkMessage, task: Option<TaskId>) -> Result<(), NetworkError> {
+        let frame = Frame { data: serde_json::to_string(message)?, task };
+        self.enqueue_with_retry(SendPriority::for_message_type(&message.message_type), frame).await?;
+        self.record(Direction::Sent, &message.message_type, message.group_id.as_deref(), message.recipient.as_deref());
+        
+        Ok(())
+    }
+
+    pub async fn fetch_messages(&self, group_id: &str) -> Result<Vec<NetworkMessage>, NetworkError> {
+        if !self.connected {
+            return Err(NetworkError::NotConnected);
+        }
+        
+        // Welcomes are consumed by whoever awaits the join (`Requester::await_welcome`)
+        Ok(self.take_inbound(|message| message.group_id.as_deref() == Some(group_id) && message.message_type != "welcome"))
+    }
+
+    /// Acknowledge every frame handed out by `fetch_messages` and friends since the last call.
+    /// Call once they are applied and saved: the delivery service then deletes them, and
+    /// redelivers the rest after a reconnect. While disconnected they stay pending.
+    pub async fn acknowledge(&self) -> Result<(), NetworkError> {
+        if !self.is_connected() {
+            return Ok(());
+        }
+        let sequences = match self.inbox.lock() {
+            Ok(mut inbox) => std::mem::take(&mut inbox.unacked),
+            Err(_) => return Ok(()),
+        };
+        if sequences.is_empty() {
+            return Ok(());
+        }
+        let ack = AckMessage {
+            message_type: "ack".to_string(),
+            client_id: self.client_id.clone(),
+            sequences,
+        };
+        let frame = Frame { data: serde_json::to_string(&ack)?, task: None };
+        let queued = self.enqueue(SendPriority::Control, frame).await;
+        if let Ok(mut inbox) = self.inbox.lock() {
+            match &queued {
+                Ok(()) => inbox.last_ack = Some(Instant::now()),
+                Err(_) => inbox.unacked.extend(ack.sequences),
+            }
+        }
+        queued
+    }
+
+    /// Whether `interval` has passed since the last acknowledgement, for callers that batch them.
+    pub fn ack_due(&self, interval: Duration) -> bool {
+        match self.inbox.lock() {
+            Ok(inbox) => match inbox.last_ack {
+                Some(at) => at.elapsed() >= interval,
+                None => true,
+            },
+            Err(_) => true,
+        }
+    }
+
+    /// Hold outgoing frames for `debounce` so ones sent close together share a write.
+    pub fn set_send_debounce(&self, debounce: Duration) {
+        self.send_debounce.store(u64::try_from(debounce.as_millis()).unwrap_or(u64::MAX), Ordering::Relaxed);
+    }
+
+    /// Invitations to groups we hold no state for, which `fetch_messages` never asks about.
+    pub async fn fetch_invitations(&self) -> Result<Vec<NetworkMessage>, NetworkError> {
+        if !self.connected {
+            return Err(NetworkError::NotConnected);
+        }
+
+        Ok(self.take_inbound(|message| message.message_type == "invitation"))
+    }
+
+    /// Indexes of the slices of `parent_id` the delivery service has stored for `group_id`.
+    pub async fn fetch_chunk_status(&self, _group_id: &str, _parent_id: &str) -> Result<Vec<u32>, NetworkError> {
+        if !self.connected {
+            return Err(NetworkError::NotConnected);
+        }
+
+        // In a real implementation, this would ask the delivery service which chunks it received
+        // For now, report none so every unconfirmed chunk is resent
+        Ok(Vec::new())
+    }
+
+    pub async fn publish_key_package(&self, key_package: &[u8]) -> Result<(), NetworkError> {
+        if !self.connected {
+            return Err(NetworkError::NotConnected);
+        }
+        
+        let publish_message = PublishKeyPackageMessage {
+            message_type: "publish_key_package".to_string(),
+            client_id: self.client_id.clone(),
+            key_package: BASE64.encode(key_package),
+        };
+        let frame = Frame { data: serde_json::to_string(&publish_message)?, task: None };
+        self.enqueue(SendPriority::Handshake, frame).await
+    }
+
+    pub async fn fetch_key_packages(&self, identity: &str) -> Result<Vec<Vec<u8>>, NetworkError> {
+        self.requester().ok_or(NetworkError::NotConnected)?.fetch_key_packages(identity).await
+    }
+
+    pub async fn create_group(&mut self, group_id: &str, group_info: &[u8], creator_id: &str, task: Option<TaskId>) -> Result<(), NetworkError> {
+        let create_message = CreateGroupMessage {
+            message_type: "create_group".to_string(),
+            group_id: group_id.to_string(),
+            creator_id: creator_id.to_string(),
+            group_info: BASE64.encode(group_info),
+        };
+        
+        let frame = Frame { data: serde_json::to_string(&create_message)?, task };
+        self.enqueue_with_retry(SendPriority::Handshake, frame).await?;
+        self.record(Direction::Sent, &create_message.message_type, Some(group_id), None);
+        
+        Ok(())
+    }
+
+    /// Ask the group's members to add us; their Welcome arrives later, see `Requester::await_welcome`.
+    pub async fn join_group(&mut self, group_id: &str, key_package: &[u8], client_id: &str, task: Option<TaskId>) -> Result<(), NetworkError> {
+        let join_message = JoinGroupMessage {
+            message_type: "join_group".to_string(),
+            group_id: group_id.to_string(),
+            client_id: client_id.to_string(),
+            key_package: BASE64.encode(key_package),
+        };
+        
+        let frame = Frame { data: serde_json::to_string(&join_message)?, task };
+        self.enqueue_with_retry(SendPriority::Handshake, frame).await?;
+        self.record(Direction::Sent, &join_message.message_type, Some(group_id), None);
+        
+        Ok(())
+    }
+
+    pub async fn list_groups(&self) -> Result<Vec<String>, NetworkError> {
+        if !self.connected {
+            return Err(NetworkError::NotConnected);
+        }
+        
+        let list_message = ListGroupsMessage {
+            message_type: "list_groups".to_string(),
+            client_id: self.client_id.clone(),
+        };
+        
+        let frame = Frame { data: serde_json::to_string(&list_message)?, task: None };
+        self.enqueue(SendPriority::Control, frame).await?;
+        
+        // For now, return empty list
+        // In a real implementation, this would wait for a response from the server
+        Ok(Vec::new())
+    }
+}
+
+impl Requester {
+    /// Wait briefly for the first inbound frame `wanted` selects.
+    async fn await_reply(&self, wanted: impl Fn(&NetworkMessage) -> bool) -> Option<NetworkMessage> {
+        self.await_reply_within(REPLY_TIMEOUT, wanted).await
+    }
+
+    async fn await_reply_within(&self, limit: Duration, wanted: impl Fn(&NetworkMessage) -> bool) -> Option<NetworkMessage> {
+        let deadline = tokio::time::Instant::now() + limit;
+        loop {
+            let mut taken = take_inbound(&self.inbox, &self.trace, &wanted);
+            if !taken.is_empty() {
+                let reply = taken.remove(0);
+                // Only one reply is consumed; put any others back in order
+                if let Ok(mut inbox) = self.inbox.lock() {
+                    inbox.put_back(taken);
+                }
+                return Some(reply);
+            }
+            if tokio::time::Instant::now() >= deadline || self.queue.is_closed() {
+                return None;
+            }
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        }
+    }
+
+    pub async fn fetch_key_packages(&self, identity: &str) -> Result<Vec<Vec<u8>>, NetworkError> {
+        let fetch_message = FetchKeyPackagesMessage {
+            message_type: "fetch_key_packages".to_string(),
+            client_id: self.client_id.clone(),
+            identity: identity.to_string(),
+        };
+        let frame = Frame { data: serde_json::to_string(&fetch_message)?, task: None };
+        self.queue.push(SendPriority::Control, frame).await?;
+
+        // Servers that do not answer are treated as having no packages
+        let Some(reply) = self.await_reply(|message| message.message_type == "key_packages" && message.sender == identity).await else {
+            return Ok(Vec::new());
+        };
+        let packages: Vec<String> = serde_json::from_slice(&reply.content)?;
+        Ok(packages.iter().filter_map(|package| BASE64.decode(package).ok()).collect())
+    }
+
+    /// Look up identities whose name contains `query` in the delivery service's directory; `None`
+    /// if it did not answer (it has no directory).
+    pub async fn find_identities(&self, query: &str) -> Result<Option<Vec<DirectoryEntry>>, NetworkError> {
+        let request = FindIdentitiesMessage {
+            message_type: "find_identities".to_string(),
+            client_id: self.client_id.clone(),
+            query: query.to_string(),
+        };
+        let frame = Frame { data: serde_json::to_string(&request)?, task: None };
+        self.queue.push(SendPriority::Control, frame).await?;
+        record(&self.trace, Direction::Sent, &request.message_type, None, None);
+
+        let Some(reply) = self.await_reply(|message| message.message_type == "directory").await else {
+            return Ok(None);
+        };
+        Ok(Some(serde_json::from_slice(&reply.content)?))
+    }
+
+    /// Ask the delivery service for a single-use token admitting one person to `group_id`;
+    /// `None` if it issued none (we are not a member, or the server does not support tokens).
+    pub async fn request_invite_token(&self, group_id: &str) -> Result<Option<String>, NetworkError> {
+        let request = RequestInviteTokenMessage {
+            message_type: "request_invite_token".to_string(),
+            client_id: self.client_id.clone(),
+            group_id: group_id.to_string(),
+        };
+        let frame = Frame { data: serde_json::to_string(&request)?, task: None };
+        self.queue.push(SendPriority::Control, frame).await?;
+        record(&self.trace, Direction::Sent, &request.message_type, Some(group_id), None);
+
+        let Some(reply) = self.await_reply(|message| message.message_type == "invite_token").await else {
+            return Ok(None);
+        };
+        let issued: InviteToken = serde_json::from_slice(&reply.content)?;
+        Ok(issued.group_id.is_some_and(|issued_for| issued_for == group_id).then_some(issued.token))
+    }
+
+    /// Spend `token`; the delivery service asks the admin who requested it to add us. `None` if the
+    /// token was rejected, otherwise the group it admits us to.
+    pub async fn redeem_invite_token(&self, token: &str) -> Result<Option<String>, NetworkError> {
+        let request = RedeemInviteTokenMessage {
+            message_type: "redeem_invite_token".to_string(),
+            client_id: self.client_id.clone(),
+            token: token.to_string(),
+        };
+        let frame = Frame { data: serde_json::to_string(&request)?, task: None };
+        self.queue.push(SendPriority::Handshake, frame).await?;
+        record(&self.trace, Direction::Sent, &request.message_type, None, None);
+
+        let Some(reply) = self.await_reply(|message| message.message_type == "token_redeemed").await else {
+            return Ok(None);
+        };
+        let redeemed: InviteToken = serde_json::from_slice(&reply.content)?;
+        Ok(redeemed.group_id.filter(|_| redeemed.token == token))
+    }
+
+    /// The Welcome the issuing admin sends once a redeemed token reaches them, who may take a
+    /// while to fetch our key package and commit.
+    pub async fn await_token_welcome(&self, group_id: &str) -> Option<Vec<u8>> {
+        self.await_reply_within(TOKEN_WELCOME_TIMEOUT, |message| {
+            message.message_type == "welcome" && message.group_id.as_deref() == Some(group_id)
+        })
+        .await
+        .map(|message| message.content)
+    }
+
+    /// The Welcome a member sends after `NetworkClient::join_group`; `None` if none came, i.e. the
+    /// group was not found.
+    pub async fn await_welcome(&self, group_id: &str) -> Option<Vec<u8>> {
+        self.await_reply(|message| message.message_type == "welcome" && message.group_id.as_deref() == Some(group_id))
+            .await
+            .map(|message| message.content)
+    }
+}
+
+fn take_inbound(
+    inbox: &Mutex<Inbox>,
+    trace: &Mutex<ProtocolTrace>,
+    wanted: impl Fn(&NetworkMessage) -> bool,
+) -> Vec<NetworkMessage> {
+    let Ok(mut inbox) = inbox.lock() else {
+        return Vec::new();
+    };
+    let taken = inbox.take(wanted);
+    for message in &taken {
+        record(trace, Direction::Received, &message.message_type, message.group_id.as_deref(), Some(&message.sender));
+    }
+    taken
+}
+
+fn record_frame(stats: &Mutex<NetworkStats>, direction: Direction, line: &str) {
+    if let Ok(mut stats) = stats.lock() {
+        stats.record_frame(direction, line);
+    }
+}
+
+fn record(trace: &Mutex<ProtocolTrace>, direction: Direction, message_type: &str, group_id: Option<&str>, peer: Option<&str>) {
+    if let Ok(mut trace) = trace.lock() {
+        trace.record(direction, message_type, group_id, peer);
+    }
+}
+
+impl Drop for NetworkClient {
+    fn drop(&mut self) {
+        // Let the writer task finish so the old connection is released on reconnect
+        if let Some(queue) = &self.send_queue {
+            queue.close();
+        }
+    }
+}
\ No newline at end of file
@@ -0,0 +1,174 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::io::{self, Write};
+use std::process::Stdio;
+use std::sync::{Mutex, PoisonError};
+use tokio::process::Command;
+use crate::config::Config;
+use crate::platform;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NotifyEvent {
+    Message,
+    Mention,
+    /// Invitations and join requests
+    Invite,
+    /// A `remind` reminder came due
+    Reminder,
+}
+
+#[derive(Debug, Clone)]
+pub struct Notification {
+    pub event: NotifyEvent,
+    pub group_id: Option<String>,
+    pub text: String,
+}
+
+/// A channel that alerts the user about a notification (bell, audio, desktop).
+pub trait Notifier {
+    fn notify(&self, notification: &Notification) -> io::Result<()>;
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Sound {
+    #[default]
+    Off,
+    Bell,
+    /// Path to an audio file played with the platform's command-line player
+    File(String),
+}
+
+/// Which sound each event plays; `groups` overrides message and mention sounds per group.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SoundConfig {
+    #[serde(default)]
+    pub message: Sound,
+    #[serde(default)]
+    pub mention: Sound,
+    #[serde(default)]
+    pub invite: Sound,
+    #[serde(default)]
+    pub reminder: Sound,
+    #[serde(default)]
+    pub groups: HashMap<String, Sound>,
+}
+
+impl SoundConfig {
+    pub fn for_notification(&self, notification: &Notification) -> &Sound {
+        let group_override = notification.group_id.as_ref().and_then(|id| self.groups.get(id));
+        match notification.event {
+            NotifyEvent::Message => group_override.unwrap_or(&self.message),
+            NotifyEvent::Mention => group_override.unwrap_or(&self.mention),
+            NotifyEvent::Invite => &self.invite,
+            NotifyEvent::Reminder => &self.reminder,
+        }
+    }
+}
+
+/// The notifiers enabled by the configuration.
+pub fn from_config(config: &Config) -> Vec<Box<dyn Notifier>> {
+    let mut notifiers: Vec<Box<dyn Notifier>> = vec![Box::new(SoundNotifier::new(config.sounds.clone(), config.mention_bell))];
+    if config.desktop_notifications {
+        notifiers.push(Box::new(DesktopNotifier));
+    }
+    if let Some(url) = &config.push_relay {
+        notifiers.push(Box::new(PushRelayNotifier::new(url)));
+    }
+    notifiers
+}
+
+pub struct SoundNotifier {
+    sounds: SoundConfig,
+    /// Ring the bell on mentions whatever sound they play
+    mention_bell: bool,
+}
+
+impl SoundNotifier {
+    pub fn new(sounds: SoundConfig, mention_bell: bool) -> Self {
+        Self { sounds, mention_bell }
+    }
+}
+
+fn ring_bell() -> io::Result<()> {
+    let mut stdout = std::io::stdout();
+    stdout.write_all(b"\x07")?;
+    stdout.flush()?;
+    Ok(())
+}
+
+impl Notifier for SoundNotifier {
+    fn notify(&self, notification: &Notification) -> io::Result<()> {
+        let sound = self.sounds.for_notification(notification);
+        if self.mention_bell && notification.event == NotifyEvent::Mention && *sound != Sound::Bell {
+            ring_bell()?;
+        }
+        match sound {
+            Sound::Off => Ok(()),
+            Sound::Bell => ring_bell(),
+            Sound::File(path) => {
+                let (player, args) = platform::sound_player(path);
+                spawn_quiet(Command::new(player).args(args))
+            }
+        }
+    }
+}
+
+/// Desktop notifications through the platform notification service.
+pub struct DesktopNotifier;
+
+impl Notifier for DesktopNotifier {
+    fn notify(&self, notification: &Notification) -> io::Result<()> {
+        if cfg!(target_os = "macos") {
+            let script = format!(
+                "display notification {:?} with title \"MLS Client\"",
+                notification.text
+            );
+            spawn_quiet(Command::new("osascript").arg("-e").arg(script))
+        } else {
+            spawn_quiet(Command::new("notify-send").arg("MLS Client").arg(&notification.text))
+        }
+    }
+}
+
+/// Forwards a stub per notification to a push relay (an ntfy topic or UnifiedPush endpoint).
+/// The stub holds only the group ID and how many notifications that group has raised this
+/// session; message text never leaves the client.
+pub struct PushRelayNotifier {
+    url: String,
+    counters: Mutex<HashMap<Option<String>, u64>>,
+}
+
+impl PushRelayNotifier {
+    pub fn new(url: &str) -> Self {
+        Self { url: url.to_string(), counters: Mutex::new(HashMap::new()) }
+    }
+}
+
+impl Notifier for PushRelayNotifier {
+    fn notify(&self, notification: &Notification) -> io::Result<()> {
+        let count = {
+            let mut counters = self.counters.lock().unwrap_or_else(PoisonError::into_inner);
+            let count = counters.entry(notification.group_id.clone()).or_default();
+            *count += 1;
+            *count
+        };
+        let stub = serde_json::json!({ "group_id": notification.group_id, "count": count });
+        spawn_quiet(
+            Command::new("curl")
+                .args(["-fsS", "-m", "10", "-H", "Content-Type: application/json", "-d"])
+                .arg(stub.to_string())
+                .arg(&self.url),
+        )
+    }
+}
+
+/// Run a helper without letting its output corrupt the terminal UI.
+fn spawn_quiet(command: &mut Command) -> io::Result<()> {
+    command
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()?;
+    Ok(())
+}
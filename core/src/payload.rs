@@ -0,0 +1,265 @@
+//! The typed payload inside `application` frames: a version and a content type in front of
+//! the body, so a client meeting a type it does not know shows the sender's fallback text
+//! instead of garbage. Payloads without the prefix are plain chat text, as older clients
+//! send it.
+//!
+//! Group control messages (nicknames, redactions, onboarding messages) are content types
+//! too, so the delivery service relays them as ordinary `application` frames it cannot tell
+//! apart from chat.
+//!
+//! A typed payload also carries the message ID. The ID on the frame is the delivery
+//! service's to change; the one sealed inside MLS is what receivers dedupe and refer to.
+//!
+//! Whether a payload is compressed is sealed inside MLS too, by the prefix in front of it,
+//! so the delivery service can neither strip the flag nor set it on plain payloads.
+
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::borrow::Cow;
+
+use crate::compression;
+use crate::group::Onboarding;
+
+/// Feature listed in the leaf capabilities of clients that read typed payloads; only groups
+/// where every leaf lists it are sent them.
+pub const PAYLOAD: &str = "payload-v1";
+
+/// Raised only for changes older clients would misread; new content types and new body
+/// fields keep it.
+pub const VERSION: u32 = 1;
+
+/// Marks a typed payload. Typed text never starts with a NUL byte, so plain chat text from
+/// older clients cannot be taken for one.
+const PREFIX: &[u8] = b"\0mls-payload\0";
+
+/// Marks a typed payload whose JSON is compressed with zstd. Sent only to groups where every
+/// leaf lists the `zstd` feature as well.
+const ZSTD_PREFIX: &[u8] = b"\0mls-payload+zstd\0";
+
+pub const CHAT: &str = "chat";
+pub const REACTION: &str = "reaction";
+pub const PROFILE: &str = "profile";
+pub const REDACTION: &str = "redaction";
+pub const ONBOARDING: &str = "onboarding";
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Payload {
+    pub v: u32,
+    /// Message ID the sender gave the frame
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+    #[serde(rename = "type")]
+    pub content_type: String,
+    #[serde(default)]
+    pub body: Value,
+    /// Plain text for clients that do not know `content_type`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fallback: Option<String>,
+}
+
+/// A received payload: the message ID sealed in it, if any, and what it holds.
+#[derive(Debug, PartialEq)]
+pub struct Decoded {
+    pub id: Option<String>,
+    pub content: Content,
+}
+
+/// What a received payload holds, as far as this client understands it.
+#[derive(Debug, PartialEq)]
+pub enum Content {
+    Chat(String),
+    Reaction { message_id: String, emoji: String },
+    /// The sender's nickname in the group; empty clears it
+    Profile { nick: String },
+    /// An admin's redaction of the message with this ID
+    Redaction { message_id: String },
+    /// The group's onboarding message, or `None` once an admin removed it
+    Onboarding(Option<Onboarding>),
+    /// A newer client's content type or version: shown as its fallback text, if any
+    Unsupported { content_type: String, fallback: Option<String> },
+}
+
+impl Content {
+    /// The content type it came as, e.g. "chat".
+    pub fn kind(&self) -> &str {
+        match self {
+            Content::Chat(_) => CHAT,
+            Content::Reaction { .. } => REACTION,
+            Content::Profile { .. } => PROFILE,
+            Content::Redaction { .. } => REDACTION,
+            Content::Onboarding(_) => ONBOARDING,
+            Content::Unsupported { content_type, .. } => content_type,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct ChatBody {
+    text: String,
+}
+
+#[derive(Deserialize)]
+struct ReactionBody {
+    message_id: String,
+    emoji: String,
+}
+
+#[derive(Deserialize)]
+struct ProfileBody {
+    nick: String,
+}
+
+#[derive(Deserialize)]
+struct RedactionBody {
+    message_id: String,
+}
+
+impl Payload {
+    pub fn chat(text: &str) -> Self {
+        Payload { v: VERSION, id: None, content_type: CHAT.to_string(), body: json!({ "text": text }), fallback: None }
+    }
+
+    pub fn reaction(message_id: &str, emoji: &str) -> Self {
+        Payload {
+            v: VERSION,
+            id: None,
+            content_type: REACTION.to_string(),
+            body: json!({ "message_id": message_id, "emoji": emoji }),
+            fallback: Some(format!("reacted with {}", emoji)),
+        }
+    }
+
+    pub fn profile(nick: &str) -> Self {
+        Payload { v: VERSION, id: None, content_type: PROFILE.to_string(), body: json!({ "nick": nick }), fallback: None }
+    }
+
+    pub fn redaction(message_id: &str) -> Self {
+        Payload { v: VERSION, id: None, content_type: REDACTION.to_string(), body: json!({ "message_id": message_id }), fallback: None }
+    }
+
+    pub fn onboarding(onboarding: &Option<Onboarding>) -> Self {
+        Payload { v: VERSION, id: None, content_type: ONBOARDING.to_string(), body: json!(onboarding), fallback: None }
+    }
+
+    /// Seal the frame's message ID into the payload.
+    pub fn with_id(mut self, id: &str) -> Self {
+        self.id = Some(id.to_string());
+        self
+    }
+
+    pub fn encode(&self) -> serde_json::Result<Vec<u8>> {
+        let mut encoded = PREFIX.to_vec();
+        encoded.extend(serde_json::to_vec(self)?);
+        Ok(encoded)
+    }
+
+    /// `encode`, compressed if that makes it worth it.
+    pub fn encode_compressed(&self) -> serde_json::Result<Vec<u8>> {
+        let json = serde_json::to_vec(self)?;
+        Ok(match compression::maybe_compress(&json) {
+            Some(compressed) => [ZSTD_PREFIX, &compressed].concat(),
+            None => [PREFIX, &json].concat(),
+        })
+    }
+}
+
+/// Whether `payload`, as encoded for sending, is compressed.
+pub fn is_compressed(payload: &[u8]) -> bool {
+    payload.starts_with(ZSTD_PREFIX)
+}
+
+/// Read a decrypted application payload, decompressing it if its prefix says so.
+pub fn decode(payload: &[u8]) -> Decoded {
+    let unreadable = || Decoded { id: None, content: Content::Unsupported { content_type: "unreadable".to_string(), fallback: None } };
+    let typed = if let Some(compressed) = payload.strip_prefix(ZSTD_PREFIX) {
+        let Ok(json) = compression::decompress(compressed) else {
+            return unreadable();
+        };
+        Cow::Owned(json)
+    } else if let Some(typed) = payload.strip_prefix(PREFIX) {
+        Cow::Borrowed(typed)
+    } else {
+        return Decoded { id: None, content: Content::Chat(String::from_utf8_lossy(payload).into_owned()) };
+    };
+    let Ok(payload) = serde_json::from_slice::<Payload>(&typed) else {
+        return unreadable();
+    };
+    let known = match payload.content_type.as_str() {
+        _ if payload.v > VERSION => None,
+        CHAT => serde_json::from_value::<ChatBody>(payload.body.clone()).ok().map(|body| Content::Chat(body.text)),
+        REACTION => serde_json::from_value::<ReactionBody>(payload.body.clone())
+            .ok()
+            .map(|body| Content::Reaction { message_id: body.message_id, emoji: body.emoji }),
+        PROFILE => serde_json::from_value::<ProfileBody>(payload.body.clone()).ok().map(|body| Content::Profile { nick: body.nick }),
+        REDACTION => serde_json::from_value::<RedactionBody>(payload.body.clone())
+            .ok()
+            .map(|body| Content::Redaction { message_id: body.message_id }),
+        ONBOARDING => serde_json::from_value::<Option<Onboarding>>(payload.body.clone()).ok().map(Content::Onboarding),
+        _ => None,
+    };
+    let content = known.unwrap_or(Content::Unsupported { content_type: payload.content_type, fallback: payload.fallback });
+    Decoded { id: payload.id, content }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_untyped_payloads_as_chat() {
+        assert_eq!(decode(b"hello"), Decoded { id: None, content: Content::Chat("hello".to_string()) });
+    }
+
+    #[test]
+    fn round_trips_typed_payloads() {
+        let encoded = Payload::chat("hi").with_id("m1").encode().unwrap();
+        assert_eq!(decode(&encoded), Decoded { id: Some("m1".to_string()), content: Content::Chat("hi".to_string()) });
+        let encoded = Payload::reaction("m1", "👍").encode().unwrap();
+        assert_eq!(decode(&encoded).content, Content::Reaction { message_id: "m1".to_string(), emoji: "👍".to_string() });
+        let encoded = Payload::redaction("m1").encode().unwrap();
+        assert_eq!(decode(&encoded).content, Content::Redaction { message_id: "m1".to_string() });
+    }
+
+    #[test]
+    fn round_trips_compressed_payloads() {
+        let text = "the same sentence again and again. ".repeat(100);
+        let encoded = Payload::chat(&text).with_id("m1").encode_compressed().unwrap();
+        assert!(is_compressed(&encoded));
+        assert_eq!(decode(&encoded), Decoded { id: Some("m1".to_string()), content: Content::Chat(text) });
+        // Too short to be worth it
+        let encoded = Payload::chat("hi").encode_compressed().unwrap();
+        assert!(!is_compressed(&encoded));
+        assert_eq!(decode(&encoded).content, Content::Chat("hi".to_string()));
+    }
+
+    #[test]
+    fn shows_newer_payloads_as_their_fallback() {
+        let poll = Payload {
+            v: VERSION,
+            id: None,
+            content_type: "poll".to_string(),
+            body: json!({ "question": "lunch?" }),
+            fallback: Some("poll: lunch?".to_string()),
+        };
+        let expected = Content::Unsupported { content_type: "poll".to_string(), fallback: Some("poll: lunch?".to_string()) };
+        assert_eq!(decode(&poll.encode().unwrap()).content, expected);
+        let newer = Payload { v: VERSION + 1, ..Payload::chat("hi") };
+        assert!(matches!(decode(&newer.encode().unwrap()).content, Content::Unsupported { .. }));
+    }
+
+    #[test]
+    fn marks_malformed_payloads_unreadable() {
+        let mut garbage = PREFIX.to_vec();
+        garbage.extend(b"{not json");
+        assert_eq!(decode(&garbage).content, Content::Unsupported { content_type: "unreadable".to_string(), fallback: None });
+        let mut not_zstd = ZSTD_PREFIX.to_vec();
+        not_zstd.extend(b"not zstd");
+        assert_eq!(decode(&not_zstd).content, Content::Unsupported { content_type: "unreadable".to_string(), fallback: None });
+        // Compressed bytes without the prefix are not taken for compressed
+        let bare = compression::maybe_compress(&serde_json::to_vec(&Payload::chat(&"x".repeat(1000))).unwrap()).unwrap();
+        assert!(matches!(decode(&bare).content, Content::Chat(_)));
+        // A known type whose body does not fit it
+        let wrong_body = Payload { body: json!({ "nick": 5 }), ..Payload::profile("x") };
+        assert!(matches!(decode(&wrong_body.encode().unwrap()).content, Content::Unsupported { .. }));
+    }
+}
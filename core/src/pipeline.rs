@@ -0,0 +1,464 @@
+//! The path messages and commits take between MLS and the delivery service, shared by every
+//! interface. Outgoing chat is typed, chunked and sealed into frames; incoming frames are
+//! screened, opened and checked against the group's rules before any of them takes effect;
+//! our own commits are delivered, merged and logged.
+
+use chrono::{DateTime, Local};
+use std::collections::{BTreeMap, HashMap};
+use uuid::Uuid;
+use crate::chunking::{self, Chunk, Reassembler};
+use crate::compression;
+use crate::crypto::CryptoProvider;
+use crate::errors::{AuditError, ChunkError, MlsError, NetworkError, PipelineError};
+use crate::group::{Authenticated, Group, GroupMode};
+use crate::history::{HistoryBundle, HistoryShare};
+use crate::mls_client::{CommitSummary, EncryptionDetails, IncomingMls, MlsClient};
+use crate::network::{NetworkClient, NetworkMessage};
+use crate::payload::{self, Content, Payload};
+use crate::rotation::RotationStatus;
+use crate::transfers::ResendRequest;
+
+/// An application frame before encryption: its type, its ID on the delivery service and the
+/// plaintext MLS protects.
+#[derive(Debug, Clone)]
+pub struct Frame {
+    pub message_type: &'static str,
+    pub id: String,
+    pub plaintext: Vec<u8>,
+}
+
+impl Frame {
+    pub fn new(message_type: &'static str, id: &str, plaintext: Vec<u8>) -> Self {
+        Self { message_type, id: id.to_string(), plaintext }
+    }
+
+    /// The `application_chunk` frame carrying one slice of a chunked message.
+    pub fn chunk(chunk: &Chunk) -> Result<Self, serde_json::Error> {
+        let id = format!("{}#{}", chunk.parent_id, chunk.index);
+        Ok(Self::new("application_chunk", &id, serde_json::to_vec(chunk)?))
+    }
+
+    /// Encrypt the frame for the group as `sender`, with what it put on the wire.
+    pub fn seal(&self, mls_client: &mut MlsClient, sender: &str, group_id: &str) -> Result<(NetworkMessage, EncryptionDetails), MlsError> {
+        let (ciphertext, details) = mls_client.encrypt_message_detailed(group_id, &self.plaintext)?;
+        let mut message = NetworkMessage::for_group(self.message_type, sender, group_id, ciphertext);
+        message.message_id = Some(self.id.clone());
+        Ok((message, details))
+    }
+}
+
+/// Chat text as it goes into MLS: typed, and compressed if `compress` asks for it, but only
+/// if every member can read it. The typed payload says whether it is compressed, so
+/// compression needs it too.
+pub fn chat_payload(mls_client: &MlsClient, group_id: &str, message_id: &str, text: &str, compress: bool) -> Result<Vec<u8>, serde_json::Error> {
+    let typed = mls_client.supports_all(group_id, payload::PAYLOAD);
+    let compress = typed && compress && mls_client.supports_all(group_id, compression::ZSTD);
+    match (typed, compress) {
+        (false, _) => Ok(text.as_bytes().to_vec()),
+        (true, false) => Payload::chat(text).with_id(message_id).encode(),
+        (true, true) => Payload::chat(text).with_id(message_id).encode_compressed(),
+    }
+}
+
+/// Split a payload too large for one frame into slices of `threshold` bytes, unless it is
+/// over `limit` altogether.
+pub fn split(crypto: &CryptoProvider, message_id: &str, payload: &[u8], threshold: usize, limit: usize) -> Result<Vec<Chunk>, PipelineError> {
+    if payload.len() > limit {
+        return Err(PipelineError::TooLarge { size: payload.len(), limit });
+    }
+    Ok(chunking::split(crypto, message_id, payload, threshold)?)
+}
+
+/// Encrypt a typed payload, such as a control message, as an `application` frame: to
+/// `recipient` alone if given, else to the group. Refused unless every leaf of the group
+/// lists `payload-v1`, since older clients would show it as chat text.
+pub fn seal_payload(
+    mls_client: &mut MlsClient,
+    sender: &str,
+    group_id: &str,
+    payload: Payload,
+    recipient: Option<&str>,
+) -> Result<NetworkMessage, PipelineError> {
+    if !mls_client.supports_all(group_id, payload::PAYLOAD) {
+        return Err(PipelineError::Untyped);
+    }
+    let frame_id = Uuid::new_v4().to_string();
+    let frame = Frame::new("application", &frame_id, payload.with_id(&frame_id).encode()?);
+    let (mut message, _) = frame.seal(mls_client, sender, group_id)?;
+    message.recipient = recipient.map(str::to_string);
+    Ok(message)
+}
+
+/// Encrypt earlier messages for `recipient`, a member just added, in the epoch they joined.
+pub fn seal_history(
+    mls_client: &mut MlsClient,
+    sender: &str,
+    group_id: &str,
+    bundle: &HistoryBundle,
+    recipient: &str,
+) -> Result<NetworkMessage, PipelineError> {
+    let frame = Frame::new("history_bundle", &Uuid::new_v4().to_string(), serde_json::to_vec(bundle)?);
+    let (mut message, _) = frame.seal(mls_client, sender, group_id)?;
+    message.recipient = Some(recipient.to_string());
+    Ok(message)
+}
+
+/// Whether a frame for `group` still needs processing, noting its sender as active if so.
+/// `me` is our own identity.
+pub fn admit(group: &mut Group, frame: &NetworkMessage, message_id: &str, me: &str) -> bool {
+    // Removed groups are read-only: nothing after the removal can be decrypted
+    if group.removed {
+        return false;
+    }
+    // The delivery service's copy of our own message confirms the local echo and puts it
+    // in server order; we cannot decrypt it anyway
+    if frame.message_type == "application"
+        && frame.sender == me
+        && group.reconcile_echo(message_id, &frame.sender, frame.sequence, frame.server_received_at())
+    {
+        return false;
+    }
+    // Already applied before a reconnect/re-fetch: no timeline entry, no notification. IDs
+    // are recorded only once their frame was processed, so a forged frame cannot claim one first
+    if group.seen_ids.contains(message_id) {
+        return false;
+    }
+    // Any frame counts as activity; a claimed time in the future is capped at now
+    let sent_at = i64::try_from(frame.timestamp).ok()
+        .and_then(|claimed| DateTime::from_timestamp(claimed, 0))
+        .map_or_else(Local::now, |t| t.with_timezone(&Local).min(Local::now()));
+    group.note_activity(&frame.sender, sent_at);
+    true
+}
+
+/// What an admitted frame turned out to hold, for the interface to apply.
+#[derive(Debug)]
+pub enum Inbound {
+    /// Application content under the frame's ID, not yet checked with `read_application`
+    Application { id: String, sender: String, content: Vec<u8>, proof: Authenticated },
+    /// A chunked message the frame's slice completed, under the message's ID. Every slice
+    /// was authenticated.
+    Reassembled { id: String, sender: String, content: Vec<u8>, proof: Authenticated },
+    /// A pointer to a blob in an attachment store
+    Attachment { id: String, sender: String, content: Vec<u8>, proof: Authenticated },
+    /// Messages re-shared with us from before we joined
+    History { sender: String, content: Vec<u8> },
+    /// Slices of one of our uploads a member is missing
+    Resend(ResendRequest),
+    /// A merged commit; `superseded` is our own pending commit it won the epoch over
+    Commit { summary: CommitSummary, superseded: Option<CommitSummary> },
+    Proposal { sender: String },
+    Removed { by: String },
+    /// Addressed to another member; the frame is not taken as seen
+    Elsewhere,
+    /// Nothing to apply, such as a slice of a message still incomplete
+    Ignored,
+}
+
+/// Decrypt an admitted frame and say what it holds. Only MLS failures, which may clear up
+/// once the group moves on, and dropped chunk slices are errors.
+pub fn open(
+    mls_client: &mut MlsClient,
+    crypto: &CryptoProvider,
+    downloads: &mut Reassembler,
+    group: &mut Group,
+    me: &str,
+    message_id: &str,
+    frame: &NetworkMessage,
+) -> Result<Inbound, PipelineError> {
+    let addressed = frame.recipient.as_deref() == Some(me);
+    let inbound = match frame.message_type.as_str() {
+        // Onboarding messages for a member someone just added are for them alone
+        "application" if frame.recipient.is_some() && !addressed => Inbound::Elsewhere,
+        "application" => match mls_client.process_incoming(&group.id, &frame.content)? {
+            IncomingMls::Application { sender, content, proof } => Inbound::Application { id: message_id.to_string(), sender, content, proof },
+            IncomingMls::Commit { summary, superseded } => Inbound::Commit { summary, superseded },
+            IncomingMls::Removed { by } => Inbound::Removed { by },
+            IncomingMls::Proposal { .. } => Inbound::Ignored,
+        },
+        "commit" | "proposal" => match mls_client.process_incoming(&group.id, &frame.content)? {
+            IncomingMls::Commit { summary, superseded } => Inbound::Commit { summary, superseded },
+            IncomingMls::Removed { by } => Inbound::Removed { by },
+            IncomingMls::Proposal { sender } => Inbound::Proposal { sender },
+            IncomingMls::Application { .. } => Inbound::Ignored,
+        },
+        "application_chunk" => {
+            let IncomingMls::Application { sender, content, proof } = mls_client.process_incoming(&group.id, &frame.content)? else {
+                return Ok(Inbound::Ignored);
+            };
+            let assembled = serde_json::from_slice::<Chunk>(&content)
+                .map_err(ChunkError::from)
+                .and_then(|chunk| downloads.add(crypto, &group.id, &sender, chunk));
+            match assembled {
+                Ok(Some((parent_id, content))) if group.seen_ids.insert(parent_id.clone()) => {
+                    Inbound::Reassembled { id: parent_id, sender, content, proof }
+                }
+                Ok(_) => Inbound::Ignored,
+                Err(source) => return Err(PipelineError::Reassemble { sender, source }),
+            }
+        }
+        "attachment" => match mls_client.process_incoming(&group.id, &frame.content)? {
+            IncomingMls::Application { sender, content, proof } => Inbound::Attachment { id: message_id.to_string(), sender, content, proof },
+            _ => Inbound::Ignored,
+        },
+        "history_bundle" | "chunk_resend" if !addressed => Inbound::Elsewhere,
+        "history_bundle" => match mls_client.process_incoming(&group.id, &frame.content)? {
+            IncomingMls::Application { sender, content, .. } => Inbound::History { sender, content },
+            _ => Inbound::Ignored,
+        },
+        "chunk_resend" => serde_json::from_slice(&frame.content).map_or(Inbound::Ignored, Inbound::Resend),
+        _ => Inbound::Ignored,
+    };
+    Ok(inbound)
+}
+
+/// Decode application content from `sender` and check it against the group's rules; nothing
+/// from the message takes effect until they allow it.
+pub fn read_application(group: &Group, message_id: &str, sender: &str, content: &[u8]) -> Result<Content, PipelineError> {
+    let decoded = payload::decode(content);
+    if decoded.id.as_ref().is_some_and(|id| id != message_id) {
+        return Err(PipelineError::IdChanged);
+    }
+    if !group.accepts(sender, &decoded.content) {
+        return Err(PipelineError::NotAllowed { kind: decoded.content.kind().to_string(), sender: sender.to_string() });
+    }
+    Ok(decoded.content)
+}
+
+/// When a frame says it was sent, against when the delivery service received it.
+#[derive(Debug, Clone, Copy)]
+pub struct SentAt {
+    /// The sender's claimed time
+    pub timestamp: DateTime<Local>,
+    pub received_at: Option<DateTime<Local>>,
+    /// Seconds the claimed time is ahead of the server's
+    pub skew: i64,
+}
+
+impl SentAt {
+    pub fn skewed(&self, threshold_secs: u64) -> bool {
+        self.skew.unsigned_abs() > threshold_secs
+    }
+}
+
+/// Compare the sender's claimed time against when the server received the frame, or the
+/// server-corrected clock if the server does not stamp frames.
+pub fn sent_at(frame: &NetworkMessage, server_clock_offset: Option<i64>) -> SentAt {
+    let received_at = frame.server_received_at();
+    let reference = match received_at {
+        Some(at) => at.timestamp(),
+        None => Local::now().timestamp().saturating_add(server_clock_offset.unwrap_or(0)),
+    };
+    // Saturating: the claimed time comes from the peer and may be absurd
+    let claimed = i64::try_from(frame.timestamp).unwrap_or(i64::MAX);
+    let skew = claimed.saturating_sub(reference);
+    let timestamp = DateTime::from_timestamp(claimed, 0)
+        .map(|t| t.with_timezone(&Local))
+        .unwrap_or_else(Local::now);
+    SentAt { timestamp, received_at, skew }
+}
+
+/// What to apply locally once a commit of ours is merged.
+#[derive(Debug, Clone)]
+pub enum CommitEffect {
+    Add { identities: Vec<String>, share: Option<HistoryShare> },
+    Remove { identities: Vec<String> },
+    /// Required capabilities dropped so `identity` can be added
+    Relax { identity: String },
+    /// Our leaf's keys refreshed, moving the group to a new epoch
+    Update,
+    /// The group context switched to `mode`
+    Mode { mode: GroupMode },
+}
+
+impl CommitEffect {
+    pub fn describe(&self) -> String {
+        match self {
+            CommitEffect::Add { identities, .. } => format!("invite {}", identities.join(", ")),
+            CommitEffect::Remove { identities } => format!("remove {}", identities.join(", ")),
+            CommitEffect::Relax { identity } => format!("relax requirements for {}", identity),
+            CommitEffect::Update => "rotate the group's keys".to_string(),
+            CommitEffect::Mode { mode } => format!("switch to {} mode", mode.as_str()),
+        }
+    }
+}
+
+/// A staged commit of ours and the frames that deliver it, held in debug mode until
+/// `commit send` or `commit abort`.
+pub struct HeldCommit {
+    pub messages: Vec<NetworkMessage>,
+    pub effect: CommitEffect,
+}
+
+/// Send the frames of our staged commit and merge it, returning what it changed. If the
+/// group is gone from the delivery service or a frame cannot be sent, the commit is
+/// discarded and the group stays at its epoch.
+pub async fn deliver_commit(
+    mls_client: &mut MlsClient,
+    network_client: &mut NetworkClient,
+    group: &Group,
+    messages: &[NetworkMessage],
+) -> Result<Option<CommitSummary>, PipelineError> {
+    if group.deleted.is_some() {
+        mls_client.clear_pending_commit(&group.id)?;
+        return Err(PipelineError::Deleted(group.name.clone()));
+    }
+    for message in messages {
+        if let Err(e) = network_client.send_message(message).await {
+            mls_client.clear_pending_commit(&group.id)?;
+            return Err(e.into());
+        }
+    }
+    let summary = mls_client.pending_commit(&group.id);
+    mls_client.merge_pending_commit(&group.id)?;
+    Ok(summary)
+}
+
+/// Add a merged commit to the group's membership log, read by `diff`, and take up the
+/// members, mode and admins it may have changed; those are taken up even if the log
+/// refuses the commit.
+pub fn record_commit(mls_client: &MlsClient, group: &mut Group, summary: &CommitSummary, audit_key: &[u8]) -> Result<(), AuditError> {
+    let logged = group.record_commit(summary.membership_change(), audit_key);
+    sync_group_state(mls_client, group);
+    logged
+}
+
+/// Take the group's members from its MLS tree, and its mode and admins from its group
+/// context, the only places they change.
+pub fn sync_group_state(mls_client: &MlsClient, group: &mut Group) {
+    if mls_client.get_group(&group.id).is_none() {
+        return;
+    }
+    group.members = mls_client.member_identities(&group.id);
+    if let Some(state) = mls_client.group_state(&group.id) {
+        group.mode = state.mode;
+        group.admins = state.admins;
+    }
+}
+
+/// Where an identity rotation stands after a `rotate_identity` pass.
+pub struct RotationReport {
+    /// Per group, whether its leaf carries the new key
+    pub progress: BTreeMap<String, RotationStatus>,
+    /// Groups whose rotation commit merged but was not added to the membership log
+    pub unlogged: Vec<(String, CommitSummary, AuditError)>,
+    /// Every group carries the new key, so the old one was retired and a key package for
+    /// the new one published
+    pub retired: bool,
+}
+
+/// Move our leaf in every group to a new signature key, resuming a rotation already under
+/// way. Groups that fail stay on the old key until the next pass; the old key is retired
+/// once none is left.
+pub async fn rotate_identity(
+    mls_client: &mut MlsClient,
+    network_client: &mut NetworkClient,
+    me: &str,
+    groups: &mut HashMap<String, Group>,
+    audit_key: &[u8],
+) -> Result<RotationReport, PipelineError> {
+    if !network_client.is_connected() {
+        return Err(NetworkError::NotConnected.into());
+    }
+    let group_ids: Vec<String> = mls_client.groups.keys().cloned().collect();
+    // The new key is only in use once a group carries it; with none, nothing would move
+    if group_ids.is_empty() {
+        return Err(PipelineError::NoGroups);
+    }
+    mls_client.start_rotation()?;
+    let rotation = mls_client.rotation.as_mut().ok_or(MlsError::NoRotation)?;
+    // Groups left or lost since the rotation started have no leaf to move
+    rotation.progress.retain(|group_id, _| group_ids.contains(group_id));
+    for group_id in &group_ids {
+        rotation.progress.entry(group_id.clone()).or_insert(RotationStatus::Pending);
+    }
+
+    let mut unlogged = Vec::new();
+    for group_id in &group_ids {
+        if mls_client.rotation.as_ref().is_some_and(|rotation| rotation.committed(group_id)) {
+            continue;
+        }
+        // Merging marks the group committed and switches it to the new key
+        let failure = match mls_client.stage_signer_update(group_id) {
+            Ok(commit) => {
+                let message = NetworkMessage::for_group("commit", me, group_id, commit);
+                let summary = mls_client.pending_commit(group_id);
+                match network_client.send_message(&message).await {
+                    Ok(()) => match mls_client.merge_pending_commit(group_id) {
+                        Ok(_) => {
+                            if let (Some(summary), Some(group)) = (summary, groups.get_mut(group_id)) {
+                                if let Err(e) = record_commit(mls_client, group, &summary, audit_key) {
+                                    unlogged.push((group_id.clone(), summary, e));
+                                }
+                            }
+                            None
+                        }
+                        Err(e) => Some(e.to_string()),
+                    },
+                    Err(e) => {
+                        mls_client.clear_pending_commit(group_id)?;
+                        Some(e.to_string())
+                    }
+                }
+            }
+            Err(e) => Some(e.to_string()),
+        };
+        if let (Some(failure), Some(rotation)) = (failure, mls_client.rotation.as_mut()) {
+            rotation.progress.insert(group_id.clone(), RotationStatus::Failed(failure));
+        }
+    }
+
+    let progress = mls_client.rotation.as_ref().map(|rotation| rotation.progress.clone()).unwrap_or_default();
+    let retired = mls_client.rotation.as_ref().is_some_and(|rotation| rotation.remaining() == 0);
+    if retired {
+        // Every group carries the new key: retire the old one and republish
+        mls_client.retire_signer()?;
+        network_client.publish_key_package(&mls_client.key_package_bytes()?).await?;
+    }
+    Ok(RotationReport { progress, unlogged, retired })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn group() -> Group {
+        serde_json::from_value(json!({
+            "id": "g", "name": "general", "members": [], "messages": [], "is_active": true, "admins": [], "mode": "chat"
+        }))
+        .unwrap()
+    }
+
+    fn frame(message_type: &str, sender: &str, timestamp: u64) -> NetworkMessage {
+        let mut frame = NetworkMessage::for_group(message_type, sender, "g", Vec::new());
+        frame.timestamp = timestamp;
+        frame
+    }
+
+    #[test]
+    fn admits_a_frame_once_and_never_for_a_removed_group() {
+        let mut group = group();
+        let frame = frame("application", "bob", 1_700_000_000);
+        assert!(admit(&mut group, &frame, "m1", "alice"));
+        assert!(group.last_active.contains_key("bob"));
+        group.seen_ids.insert("m1".to_string());
+        assert!(!admit(&mut group, &frame, "m1", "alice"));
+        group.removed = true;
+        assert!(!admit(&mut group, &frame, "m2", "alice"));
+    }
+
+    #[test]
+    fn measures_skew_against_the_server_stamp() {
+        let mut stamped = frame("application", "bob", 1_700_000_090);
+        stamped.received_at = Some(1_700_000_000);
+        let sent = sent_at(&stamped, None);
+        assert_eq!(sent.skew, 90);
+        assert!(sent.skewed(60));
+        assert!(!sent.skewed(90));
+        assert_eq!(sent.received_at.map(|at| at.timestamp()), Some(1_700_000_000));
+
+        let absurd = sent_at(&frame("application", "bob", u64::MAX), Some(0));
+        assert!(absurd.skew > 0);
+    }
+}
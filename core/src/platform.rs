@@ -0,0 +1,142 @@
+//! Differences between the platforms the client runs on, kept as plain functions so they can
+//! be checked anywhere rather than only on the platform concerned.
+
+use serde::{Deserialize, Serialize};
+
+/// Whether the interface draws with Unicode symbols or plain ASCII.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum GlyphMode {
+    /// Unicode unless the console cannot show it
+    #[default]
+    Auto,
+    Unicode,
+    Ascii,
+}
+
+impl GlyphMode {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "auto" => Some(GlyphMode::Auto),
+            "unicode" => Some(GlyphMode::Unicode),
+            "ascii" => Some(GlyphMode::Ascii),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            GlyphMode::Auto => "auto",
+            GlyphMode::Unicode => "unicode",
+            GlyphMode::Ascii => "ascii",
+        }
+    }
+
+    /// Resolve `Auto` for this console.
+    pub fn unicode(self) -> bool {
+        match self {
+            GlyphMode::Auto => unicode_console(console_code_page(), std::env::var_os("WT_SESSION").is_some()),
+            GlyphMode::Unicode => true,
+            GlyphMode::Ascii => false,
+        }
+    }
+}
+
+/// The UTF-8 code page on Windows.
+const CP_UTF8: u32 = 65001;
+
+/// Whether a console can draw box drawing and symbols: anything but a Windows console on a
+/// legacy code page, where Windows Terminal still renders them itself.
+pub fn unicode_console(code_page: Option<u32>, windows_terminal: bool) -> bool {
+    match code_page {
+        Some(code_page) => code_page == CP_UTF8 || windows_terminal,
+        None => true,
+    }
+}
+
+/// The Windows console's output code page; `None` elsewhere.
+#[cfg(windows)]
+pub fn console_code_page() -> Option<u32> {
+    #[link(name = "kernel32")]
+    extern "system" {
+        fn GetConsoleOutputCP() -> u32;
+    }
+    // SAFETY: takes no arguments and only reads the console's state; 0 means no console
+    let code_page = unsafe { GetConsoleOutputCP() };
+    (code_page != 0).then_some(code_page)
+}
+
+#[cfg(not(windows))]
+pub fn console_code_page() -> Option<u32> {
+    None
+}
+
+/// A one-cell ASCII stand-in for a symbol the interface draws, so layouts keep their width.
+/// Anything else, including message text, is left alone.
+pub fn ascii_fallback(symbol: &str) -> Option<&'static str> {
+    let mut chars = symbol.chars();
+    let (Some(c), None) = (chars.next(), chars.next()) else {
+        return None;
+    };
+    Some(match c {
+        '─' | '━' | '═' | '┄' | '┅' | '┈' | '┉' | '╌' | '╍' => "-",
+        '│' | '┃' | '║' | '┆' | '┇' | '┊' | '┋' | '╎' | '╏' | '▏' => "|",
+        '\u{2500}'..='\u{257F}' => "+",
+        // The task spinner's frames, so it still turns
+        '⠋' | '⠼' | '⠇' => "|",
+        '⠙' | '⠴' | '⠏' => "/",
+        '⠹' | '⠦' => "-",
+        '⠸' | '⠧' => "\\",
+        '…' => ".",
+        '•' | '●' | '■' | '📌' => "*",
+        '○' => "o",
+        '↑' => "^",
+        '↓' | '▾' => "v",
+        '→' | '▸' | '↪' => ">",
+        '⇄' => "=",
+        '⚠' | '⏰' => "!",
+        '✔' | '👍' => "+",
+        '✘' => "x",
+        '⟳' | '⏳' => "~",
+        '🔒' => "#",
+        '⚙' => "%",
+        '·' => ".",
+        _ => return None,
+    })
+}
+
+/// `text` with every symbol `ascii_fallback` knows replaced, for line-based output.
+pub fn ascii_text(text: &str) -> String {
+    text.chars()
+        .map(|c| {
+            let mut buf = [0u8; 4];
+            ascii_fallback(c.encode_utf8(&mut buf)).map_or_else(|| c.to_string(), str::to_string)
+        })
+        .collect()
+}
+
+/// Device names Windows reserves in every directory, with or without an extension.
+const RESERVED_NAMES: &[&str] = &[
+    "con", "prn", "aux", "nul",
+    "com1", "com2", "com3", "com4", "com5", "com6", "com7", "com8", "com9",
+    "lpt1", "lpt2", "lpt3", "lpt4", "lpt5", "lpt6", "lpt7", "lpt8", "lpt9",
+];
+
+/// Whether `name` cannot be a file or directory name on Windows, so data created under it
+/// would not carry over there.
+pub fn reserved_file_name(name: &str) -> bool {
+    let stem = name.split('.').next().unwrap_or(name).trim_end();
+    RESERVED_NAMES.iter().any(|reserved| stem.eq_ignore_ascii_case(reserved))
+}
+
+/// The command that plays a sound file in the background.
+pub fn sound_player(path: &str) -> (String, Vec<String>) {
+    if cfg!(target_os = "macos") {
+        ("afplay".to_string(), vec![path.to_string()])
+    } else if cfg!(windows) {
+        let script = format!("(New-Object Media.SoundPlayer '{}').PlaySync()", path.replace('\'', "''"));
+        ("powershell".to_string(), vec!["-NoProfile".to_string(), "-Command".to_string(), script])
+    } else {
+        ("paplay".to_string(), vec![path.to_string()])
+    }
+}
@@ -0,0 +1,224 @@
+use chrono::{DateTime, Utc};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::Path;
+use std::process::Stdio;
+use std::sync::OnceLock;
+use std::time::Duration;
+use tokio::io::AsyncReadExt;
+use tokio::process::Command;
+use crate::errors::{PreviewError, StorageError};
+use crate::vault;
+
+/// Link previews fetched so far, so a link is only fetched once.
+pub const PREVIEWS_PATH: &str = "previews.json";
+
+/// Previews kept; the oldest are dropped beyond this.
+const MAX_CACHED: usize = 500;
+/// How much of a page is read looking for its title; the metadata sits in the head.
+const MAX_PAGE_BYTES: u64 = 256 * 1024;
+const FETCH_TIMEOUT: Duration = Duration::from_secs(15);
+const MAX_TITLE_CHARS: usize = 120;
+const MAX_DESCRIPTION_CHARS: usize = 200;
+
+/// Opt-in link previews. Fetching a link tells its server (and the proxy) that someone is
+/// reading the conversation it was posted in, so nothing is fetched without a proxy and
+/// only in the listed groups.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct LinkPreviewConfig {
+    /// Proxy every fetch goes through, as curl takes it (e.g. `socks5h://127.0.0.1:9050`)
+    pub proxy: String,
+    /// Groups whose received links are previewed, by ID
+    #[serde(default)]
+    pub groups: BTreeSet<String>,
+}
+
+impl LinkPreviewConfig {
+    /// Check the proxy is usable, for config validation.
+    pub fn validate(&self) -> Result<(), String> {
+        const SCHEMES: [&str; 5] = ["http://", "https://", "socks4://", "socks5://", "socks5h://"];
+        if !SCHEMES.iter().any(|scheme| self.proxy.starts_with(scheme)) {
+            return Err(format!("proxy `{}` must be an http(s):// or socks4/5(h):// URL", self.proxy));
+        }
+        Ok(())
+    }
+}
+
+/// What a page says about itself, shown as a card beneath the message linking it.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Preview {
+    pub title: String,
+    pub description: Option<String>,
+    pub fetched_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct PreviewCache {
+    entries: BTreeMap<String, Preview>,
+}
+
+impl PreviewCache {
+    pub fn get(&self, url: &str) -> Option<&Preview> {
+        self.entries.get(url)
+    }
+
+    pub fn insert(&mut self, url: String, preview: Preview) {
+        self.entries.insert(url, preview);
+        while self.entries.len() > MAX_CACHED {
+            let oldest = self.entries.iter().min_by_key(|(_, preview)| preview.fetched_at).map(|(url, _)| url.clone());
+            match oldest {
+                Some(url) => self.entries.remove(&url),
+                None => break,
+            };
+        }
+    }
+}
+
+pub async fn load(path: &Path) -> Result<PreviewCache, StorageError> {
+    if !vault::locate(path).exists() {
+        return Ok(PreviewCache::default());
+    }
+    let name = path.display().to_string();
+    let content = vault::read_to_string(path).await.map_err(StorageError::io(name.clone()))?;
+    serde_json::from_str(&content).map_err(StorageError::invalid(name))
+}
+
+pub async fn save(cache: &PreviewCache, path: &Path) -> Result<(), StorageError> {
+    let name = path.display().to_string();
+    let content = serde_json::to_string(cache).map_err(StorageError::invalid(name.clone()))?;
+    vault::write(path, content).await.map_err(StorageError::io(name))?;
+    Ok(())
+}
+
+/// The first http(s) link in `text`, without trailing punctuation.
+pub fn first_url(text: &str) -> Option<&str> {
+    static URL: OnceLock<Regex> = OnceLock::new();
+    let url = URL.get_or_init(|| Regex::new(r#"https?://[^\s<>"'`]+"#).expect("valid URL pattern"));
+    url.find(text).map(|found| found.as_str().trim_end_matches(['.', ',', ';', ':', '!', '?', ')', ']', '}']))
+}
+
+/// Fetch `url` through `proxy` with curl and read its title and description.
+pub async fn fetch(proxy: &str, url: &str) -> Result<Preview, PreviewError> {
+    let mut child = Command::new("curl")
+        .args(["--silent", "--show-error", "--location", "--max-redirs", "3"])
+        .args(["--proto", "=http,https", "--proto-redir", "=http,https"])
+        .args(["--max-time", &FETCH_TIMEOUT.as_secs().to_string()])
+        .args(["--proxy", proxy, "--header", "Accept: text/html"])
+        .arg(url)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true)
+        .spawn()
+        .map_err(PreviewError::Spawn)?;
+    let read = async {
+        let mut page = Vec::new();
+        if let Some(stdout) = child.stdout.take() {
+            stdout.take(MAX_PAGE_BYTES).read_to_end(&mut page).await?;
+        }
+        // A page cut off at the limit still has its head; curl is killed with the child
+        if page.len() as u64 >= MAX_PAGE_BYTES {
+            return Ok(page);
+        }
+        let output = child.wait_with_output().await?;
+        if !output.status.success() {
+            return Err(std::io::Error::other(String::from_utf8_lossy(&output.stderr).trim().to_string()));
+        }
+        Ok(page)
+    };
+    let page = tokio::time::timeout(FETCH_TIMEOUT + Duration::from_secs(1), read)
+        .await
+        .map_err(|_| PreviewError::Timeout(FETCH_TIMEOUT.as_secs()))?
+        .map_err(|e| PreviewError::Failed(e.to_string()))?;
+    parse(&String::from_utf8_lossy(&page)).ok_or(PreviewError::NoMetadata)
+}
+
+/// Title (Open Graph first, then `<title>`) and description from a page's HTML.
+pub fn parse(html: &str) -> Option<Preview> {
+    static META: OnceLock<Regex> = OnceLock::new();
+    static ATTRIBUTE: OnceLock<Regex> = OnceLock::new();
+    static TITLE: OnceLock<Regex> = OnceLock::new();
+    let meta = META.get_or_init(|| Regex::new(r"(?is)<meta\s[^>]*>").expect("valid meta pattern"));
+    let attribute = ATTRIBUTE.get_or_init(|| {
+        Regex::new(r#"(?is)\b(property|name|content)\s*=\s*(?:"([^"]*)"|'([^']*)')"#).expect("valid attribute pattern")
+    });
+    let title_tag = TITLE.get_or_init(|| Regex::new(r"(?is)<title[^>]*>(.*?)</title>").expect("valid title pattern"));
+
+    let mut properties: BTreeMap<String, String> = BTreeMap::new();
+    for tag in meta.find_iter(html) {
+        let mut key = None;
+        let mut content = None;
+        for attr in attribute.captures_iter(tag.as_str()) {
+            let value = attr.get(2).or_else(|| attr.get(3)).map_or("", |value| value.as_str());
+            if attr[1].eq_ignore_ascii_case("content") {
+                content = Some(value.to_string());
+            } else {
+                key = Some(value.to_ascii_lowercase());
+            }
+        }
+        if let (Some(key), Some(content)) = (key, content) {
+            properties.entry(key).or_insert(content);
+        }
+    }
+
+    let title = properties.get("og:title").cloned()
+        .or_else(|| title_tag.captures(html).map(|captures| captures[1].to_string()))
+        .map(|title| clean(&title, MAX_TITLE_CHARS))
+        .filter(|title| !title.is_empty())?;
+    let description = properties.get("og:description").or_else(|| properties.get("description"))
+        .map(|description| clean(description, MAX_DESCRIPTION_CHARS))
+        .filter(|description| !description.is_empty());
+    Some(Preview { title, description, fetched_at: Utc::now() })
+}
+
+/// Decode the common entities, drop control characters (a page could carry terminal escapes),
+/// collapse whitespace and cut to `max` characters.
+fn clean(text: &str, max: usize) -> String {
+    let decoded = decode_entities(text);
+    let words: Vec<&str> = decoded.split_whitespace().collect();
+    let text: String = words.join(" ").chars().filter(|c| !c.is_control()).collect();
+    if text.chars().count() <= max {
+        return text;
+    }
+    let mut cut: String = text.chars().take(max.saturating_sub(1)).collect();
+    cut.push('…');
+    cut
+}
+
+fn decode_entities(text: &str) -> String {
+    let mut decoded = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find('&') {
+        decoded.push_str(&rest[..start]);
+        rest = &rest[start..];
+        let entity = rest.find(';').filter(|end| *end <= 10).map(|end| (&rest[1..end], end));
+        let replacement = entity.and_then(|(name, end)| {
+            let c = match name {
+                "amp" => Some('&'),
+                "lt" => Some('<'),
+                "gt" => Some('>'),
+                "quot" => Some('"'),
+                "apos" | "#39" => Some('\''),
+                "nbsp" => Some(' '),
+                _ => name.strip_prefix("#x").or_else(|| name.strip_prefix("#X"))
+                    .and_then(|hex| u32::from_str_radix(hex, 16).ok())
+                    .or_else(|| name.strip_prefix('#').and_then(|dec| dec.parse().ok()))
+                    .and_then(char::from_u32),
+            };
+            c.map(|c| (c, end))
+        });
+        match replacement {
+            Some((c, end)) => {
+                decoded.push(c);
+                rest = &rest[end + 1..];
+            }
+            None => {
+                decoded.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    decoded.push_str(rest);
+    decoded
+}
@@ -0,0 +1,291 @@
+use crate::crypto::to_hex;
+use crate::errors::MlsError;
+
+/// Hex digits of a key or hash shown per node
+const SHORT_HEX: usize = 12;
+
+/// A non-blank node of an exported ratchet tree (RFC 9420, section 7.8).
+#[derive(Debug, Clone)]
+pub enum TreeNode {
+    Leaf {
+        identity: String,
+        encryption_key: Vec<u8>,
+        /// Present when the leaf was last set by a commit
+        parent_hash: Option<Vec<u8>>,
+        /// Extension types listed in the leaf's capabilities
+        extensions: Vec<u16>,
+    },
+    Parent {
+        encryption_key: Vec<u8>,
+        parent_hash: Vec<u8>,
+        unmerged_leaves: Vec<u32>,
+    },
+}
+
+/// Decode a TLS-serialized ratchet tree into its array representation, `None` for blank nodes.
+pub fn parse(data: &[u8]) -> Result<Vec<Option<TreeNode>>, MlsError> {
+    let mut outer = Reader::new(data);
+    let mut reader = Reader::new(outer.vector()?);
+    let mut nodes = Vec::new();
+    while !reader.is_empty() {
+        let node = match reader.u8()? {
+            0 => None,
+            1 => Some(reader.node()?),
+            _ => return Err(MlsError::MalformedTree("invalid optional node")),
+        };
+        nodes.push(node);
+    }
+    Ok(nodes)
+}
+
+/// Render the tree sideways, root first, marking our own leaf and blank nodes.
+pub fn render(nodes: &[Option<TreeNode>], own_leaf: Option<u32>) -> Vec<String> {
+    if nodes.is_empty() {
+        return vec!["(empty tree)".to_string()];
+    }
+    let mut lines = Vec::new();
+    draw(nodes, root(nodes.len()), own_leaf, "", "", &mut lines);
+    lines
+}
+
+fn draw(nodes: &[Option<TreeNode>], index: usize, own_leaf: Option<u32>, first: &str, rest: &str, lines: &mut Vec<String>) {
+    lines.push(format!("{}{}", first, describe(index, nodes[index].as_ref(), own_leaf)));
+    if level(index) == 0 {
+        return;
+    }
+    let (left, right) = (left(index), right(index, nodes.len()));
+    draw(nodes, left, own_leaf, &format!("{}├── ", rest), &format!("{}│   ", rest), lines);
+    draw(nodes, right, own_leaf, &format!("{}└── ", rest), &format!("{}    ", rest), lines);
+}
+
+fn describe(index: usize, node: Option<&TreeNode>, own_leaf: Option<u32>) -> String {
+    let short = |bytes: &[u8]| to_hex(bytes).chars().take(SHORT_HEX).collect::<String>();
+    match node {
+        None if level(index) == 0 => format!("L{} (blank)", index / 2),
+        None => format!("P{} (blank)", index),
+        Some(TreeNode::Leaf { identity, encryption_key, parent_hash, .. }) => {
+            let you = if own_leaf == Some((index / 2) as u32) { " (you)" } else { "" };
+            let parent_hash = parent_hash.as_deref().map(|hash| format!(" ph={}", short(hash))).unwrap_or_default();
+            format!("L{} {}{} key={}{}", index / 2, identity, you, short(encryption_key), parent_hash)
+        }
+        Some(TreeNode::Parent { encryption_key, parent_hash, unmerged_leaves }) => {
+            let parent_hash = if parent_hash.is_empty() { "root".to_string() } else { short(parent_hash) };
+            let unmerged = if unmerged_leaves.is_empty() {
+                String::new()
+            } else {
+                format!(" unmerged={:?}", unmerged_leaves)
+            };
+            format!("P{} key={} ph={}{}", index, short(encryption_key), parent_hash, unmerged)
+        }
+    }
+}
+
+/// Tree math from RFC 9420, appendix C; `width` is the number of nodes. The interop runner
+/// checks these against the published tree-math vectors.
+pub(crate) fn level(index: usize) -> u32 {
+    index.trailing_ones()
+}
+
+pub(crate) fn root(width: usize) -> usize {
+    let leaves = width.div_ceil(2);
+    leaves.next_power_of_two() - 1
+}
+
+/// The left child of a parent node.
+pub(crate) fn left(index: usize) -> usize {
+    index ^ (1 << (level(index) - 1))
+}
+
+/// The right child of a parent node, descending past nodes a truncated tree does not have.
+pub(crate) fn right(index: usize, width: usize) -> usize {
+    let mut child = index ^ (3 << (level(index) - 1));
+    while child >= width {
+        child = left(child);
+    }
+    child
+}
+
+/// `None` for the root. Like `right`, steps past nodes a truncated tree does not have.
+pub(crate) fn parent(index: usize, width: usize) -> Option<usize> {
+    if index == root(width) {
+        return None;
+    }
+    let mut parent = parent_step(index);
+    while parent >= width {
+        parent = parent_step(parent);
+    }
+    Some(parent)
+}
+
+/// The parent in the full tree containing `index`.
+fn parent_step(index: usize) -> usize {
+    let k = level(index);
+    let b = (index >> (k + 1)) & 1;
+    (index | (1 << k)) ^ (b << (k + 1))
+}
+
+/// `None` for the root.
+pub(crate) fn sibling(index: usize, width: usize) -> Option<usize> {
+    let parent = parent(index, width)?;
+    Some(if index < parent { right(parent, width) } else { left(parent) })
+}
+
+/// Reads TLS-encoded fields front to back.
+pub(crate) struct Reader<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    pub(crate) fn new(data: &'a [u8]) -> Self {
+        Self { data }
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.data.is_empty()
+    }
+
+    pub(crate) fn take(&mut self, len: usize) -> Result<&'a [u8], MlsError> {
+        if self.data.len() < len {
+            return Err(MlsError::MalformedTree("truncated"));
+        }
+        let (taken, rest) = self.data.split_at(len);
+        self.data = rest;
+        Ok(taken)
+    }
+
+    pub(crate) fn u8(&mut self) -> Result<u8, MlsError> {
+        Ok(self.take(1)?[0])
+    }
+
+    pub(crate) fn u16(&mut self) -> Result<u16, MlsError> {
+        let bytes = self.take(2)?;
+        Ok(u16::from_be_bytes([bytes[0], bytes[1]]))
+    }
+
+    pub(crate) fn u32(&mut self) -> Result<u32, MlsError> {
+        let bytes = self.take(4)?;
+        Ok(u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    }
+
+    pub(crate) fn u64(&mut self) -> Result<u64, MlsError> {
+        let mut bytes = [0; 8];
+        bytes.copy_from_slice(self.take(8)?);
+        Ok(u64::from_be_bytes(bytes))
+    }
+
+    /// Whatever has not been read yet, e.g. padding.
+    pub(crate) fn rest(self) -> &'a [u8] {
+        self.data
+    }
+
+    /// Variable-length vector: a 1, 2 or 4 byte length prefix, then the bytes.
+    pub(crate) fn vector(&mut self) -> Result<&'a [u8], MlsError> {
+        let first = self.u8()?;
+        let mut len = usize::from(first & 0x3f);
+        let extra = match first >> 6 {
+            0 => 0,
+            1 => 1,
+            2 => 3,
+            _ => return Err(MlsError::MalformedTree("invalid length prefix")),
+        };
+        for byte in self.take(extra)? {
+            len = (len << 8) | usize::from(*byte);
+        }
+        self.take(len)
+    }
+
+    fn node(&mut self) -> Result<TreeNode, MlsError> {
+        match self.u8()? {
+            1 => self.leaf(),
+            2 => {
+                let encryption_key = self.vector()?.to_vec();
+                let parent_hash = self.vector()?.to_vec();
+                let unmerged_leaves = self.vector()?
+                    .chunks(4)
+                    .map(|chunk| chunk.try_into().map(u32::from_be_bytes))
+                    .collect::<Result<_, _>>()
+                    .map_err(|_| MlsError::MalformedTree("invalid unmerged leaves"))?;
+                Ok(TreeNode::Parent { encryption_key, parent_hash, unmerged_leaves })
+            }
+            _ => Err(MlsError::MalformedTree("invalid node type")),
+        }
+    }
+
+    fn leaf(&mut self) -> Result<TreeNode, MlsError> {
+        let encryption_key = self.vector()?.to_vec();
+        let _signature_key = self.vector()?;
+        let identity = match self.u16()? {
+            // Basic credential: the identity itself
+            1 => String::from_utf8_lossy(self.vector()?).into_owned(),
+            // X.509: the certificate chain
+            2 => {
+                self.vector()?;
+                "<x509 credential>".to_string()
+            }
+            _ => return Err(MlsError::MalformedTree("unknown credential type")),
+        };
+        // Capabilities: versions, ciphersuites, extensions, proposals, credentials
+        self.vector()?;
+        self.vector()?;
+        let extensions = self.vector()?
+            .chunks(2)
+            .map(|chunk| chunk.try_into().map(u16::from_be_bytes))
+            .collect::<Result<_, _>>()
+            .map_err(|_| MlsError::MalformedTree("invalid extension types"))?;
+        self.vector()?;
+        self.vector()?;
+        let parent_hash = match self.u8()? {
+            // Key package: lifetime (not_before, not_after)
+            1 => {
+                self.take(16)?;
+                None
+            }
+            2 => None,
+            3 => Some(self.vector()?.to_vec()),
+            _ => return Err(MlsError::MalformedTree("invalid leaf node source")),
+        };
+        let _extensions = self.vector()?;
+        let _signature = self.vector()?;
+        Ok(TreeNode::Leaf { identity, encryption_key, parent_hash, extensions })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn navigates_a_full_tree() {
+        // Four leaves: 0 2 4 6, parents 1 5, root 3
+        let width = 7;
+        assert_eq!(root(width), 3);
+        assert_eq!([level(0), level(1), level(3)], [0, 1, 2]);
+        assert_eq!((left(3), right(3, width)), (1, 5));
+        assert_eq!((left(5), right(5, width)), (4, 6));
+        assert_eq!([parent(0, width), parent(1, width), parent(6, width), parent(3, width)], [Some(1), Some(3), Some(5), None]);
+        assert_eq!([sibling(0, width), sibling(5, width), sibling(3, width)], [Some(2), Some(1), None]);
+    }
+
+    #[test]
+    fn navigates_a_truncated_tree() {
+        // Three leaves: 0 2 4, parent 1, root 3 whose right subtree is only leaf 4
+        let width = 5;
+        assert_eq!(root(width), 3);
+        assert_eq!(right(3, width), 4);
+        assert_eq!(parent(4, width), Some(3));
+        assert_eq!(sibling(4, width), Some(1));
+        assert_eq!(root(1), 0);
+    }
+
+    #[test]
+    fn reads_length_prefixed_vectors() {
+        let mut short = Reader::new(&[0x02, 0xaa, 0xbb, 0xcc]);
+        assert_eq!(short.vector().unwrap(), [0xaa, 0xbb]);
+        let long: Vec<u8> = [0x41, 0x00].into_iter().chain([7; 256]).collect();
+        let mut reader = Reader::new(&long);
+        assert_eq!(reader.vector().unwrap().len(), 256);
+        assert!(reader.is_empty());
+        assert!(Reader::new(&[0xc0]).vector().is_err());
+        assert!(Reader::new(&[0x05, 0x01]).vector().is_err());
+    }
+}
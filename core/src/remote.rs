@@ -0,0 +1,221 @@
+//! Files kept on an SFTP server, a WebDAV collection or an S3-compatible bucket.
+//!
+//! Transfers run the `sftp` and `curl` tools, so they use the SSH keys, `~/.netrc` entries
+//! and proxies already set up for them. `sftp` runs in batch mode and never prompts for a
+//! password. S3 requests are signed by `curl --aws-sigv4` with the `AWS_ACCESS_KEY_ID` and
+//! `AWS_SECRET_ACCESS_KEY` environment variables, handed to curl on stdin rather than its
+//! command line.
+
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use std::process::{Output, Stdio};
+use tokio::fs;
+use tokio::io::AsyncWriteExt;
+use tokio::process::Command;
+use crate::errors::{RemoteError, StorageError};
+
+/// An S3 bucket, addressed path-style as `<endpoint>/<bucket>/<name>` so any compatible
+/// store works.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct S3Bucket {
+    /// e.g. `https://s3.eu-central-1.amazonaws.com` or a MinIO address
+    pub endpoint: String,
+    pub bucket: String,
+    #[serde(default = "default_region")]
+    pub region: String,
+}
+
+fn default_region() -> String {
+    "us-east-1".to_string()
+}
+
+/// Where files are put.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Remote {
+    /// `host` may carry a `user@` login; `path` is relative to the login directory
+    Sftp { host: String, port: Option<String>, path: String },
+    /// A collection URL
+    WebDav(String),
+    S3(S3Bucket),
+}
+
+impl Remote {
+    /// An `sftp://[user@]host[:port]/path` or `http(s)://` WebDAV target.
+    pub fn parse(target: &str) -> Option<Remote> {
+        let target = target.trim();
+        if let Some(rest) = target.strip_prefix("sftp://") {
+            let (authority, path) = rest.split_once('/').unwrap_or((rest, ""));
+            let (host, port) = match authority.rsplit_once(':') {
+                Some((host, port)) if port.parse::<u16>().is_ok() => (host, Some(port.to_string())),
+                _ => (authority, None),
+            };
+            Some(Remote::Sftp { host: host.to_string(), port, path: path.trim_end_matches('/').to_string() })
+        } else if target.starts_with("https://") || target.starts_with("http://") {
+            Some(Remote::WebDav(target.trim_end_matches('/').to_string()))
+        } else {
+            None
+        }
+    }
+
+    /// The store without any login, the same for every member using it.
+    pub fn locator(&self) -> String {
+        match self {
+            Remote::Sftp { host, port, path } => {
+                let host = host.rsplit_once('@').map_or(host.as_str(), |(_, host)| host);
+                let port = port.as_ref().map(|port| format!(":{}", port)).unwrap_or_default();
+                format!("sftp://{}{}/{}", host, port, path)
+            }
+            Remote::WebDav(url) => without_userinfo(url),
+            Remote::S3(bucket) => format!("{}/{}", without_userinfo(bucket.endpoint.trim_end_matches('/')), bucket.bucket),
+        }
+    }
+
+    /// Upload the local file `source` as `name`.
+    pub async fn put(&self, source: &Path, name: &str) -> Result<(), RemoteError> {
+        match self {
+            Remote::Sftp { host, port, path } => {
+                let script = format!("-mkdir {}\nput {} {}\n", quoted(path), quoted(&source.display().to_string()), quoted(&joined(path, name)));
+                run("sftp", &mut sftp(host, port.as_deref()), Some(script)).await?;
+            }
+            Remote::WebDav(url) => {
+                let mut command = curl();
+                command.arg("-T").arg(source).arg(format!("{}/{}", url, name));
+                run("curl", &mut command, None).await?;
+            }
+            Remote::S3(bucket) => {
+                let mut command = s3(bucket);
+                command.arg("-T").arg(source).arg(object_url(bucket, name));
+                run("curl", &mut command, Some(s3_credentials())).await?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Download `name`, refusing more than `limit` bytes where the tool can tell in advance.
+    pub async fn get(&self, name: &str, limit: usize) -> Result<Vec<u8>, RemoteError> {
+        match self {
+            Remote::Sftp { host, port, path } => {
+                let staged = std::env::temp_dir().join(format!("mls-{}", name));
+                let script = format!("get {} {}\n", quoted(&joined(path, name)), quoted(&staged.display().to_string()));
+                let fetched = run("sftp", &mut sftp(host, port.as_deref()), Some(script)).await;
+                let content = match fetched {
+                    Ok(_) => fs::read(&staged).await.map_err(StorageError::io(staged.display().to_string())).map_err(RemoteError::from),
+                    Err(e) => Err(e),
+                };
+                let _ = fs::remove_file(&staged).await;
+                content
+            }
+            Remote::WebDav(url) => {
+                let mut command = curl();
+                command.arg("--max-filesize").arg(limit.to_string()).arg(format!("{}/{}", url, name));
+                run("curl", &mut command, None).await
+            }
+            Remote::S3(bucket) => {
+                let mut command = s3(bucket);
+                command.arg("--max-filesize").arg(limit.to_string()).arg(object_url(bucket, name));
+                run("curl", &mut command, Some(s3_credentials())).await
+            }
+        }
+    }
+
+    pub async fn delete(&self, name: &str) -> Result<(), RemoteError> {
+        match self {
+            Remote::Sftp { host, port, path } => {
+                run("sftp", &mut sftp(host, port.as_deref()), Some(format!("rm {}\n", quoted(&joined(path, name))))).await?;
+            }
+            Remote::WebDav(url) => {
+                let mut command = curl();
+                command.args(["-X", "DELETE"]).arg(format!("{}/{}", url, name));
+                run("curl", &mut command, None).await?;
+            }
+            Remote::S3(bucket) => {
+                let mut command = s3(bucket);
+                command.args(["-X", "DELETE"]).arg(object_url(bucket, name));
+                run("curl", &mut command, Some(s3_credentials())).await?;
+            }
+        }
+        Ok(())
+    }
+}
+
+fn without_userinfo(url: &str) -> String {
+    let Some((scheme, rest)) = url.split_once("://") else {
+        return url.to_string();
+    };
+    let authority_end = rest.find('/').unwrap_or(rest.len());
+    match rest[..authority_end].rsplit_once('@') {
+        Some((_, host)) => format!("{}://{}{}", scheme, host, &rest[authority_end..]),
+        None => url.to_string(),
+    }
+}
+
+/// `sftp` in batch mode, reading its commands from stdin.
+fn sftp(host: &str, port: Option<&str>) -> Command {
+    let mut command = Command::new("sftp");
+    command.args(["-b", "-"]);
+    if let Some(port) = port {
+        command.args(["-P", port]);
+    }
+    command.arg(host);
+    command
+}
+
+fn curl() -> Command {
+    let mut command = Command::new("curl");
+    command.args(["-sSf", "--netrc-optional"]);
+    command
+}
+
+/// `curl` signing for S3; the credentials follow on stdin as a curl config file.
+fn s3(bucket: &S3Bucket) -> Command {
+    let mut command = Command::new("curl");
+    command
+        .args(["-sSf", "-K", "-", "--aws-sigv4"])
+        .arg(format!("aws:amz:{}:s3", bucket.region))
+        .args(["-H", "x-amz-content-sha256: UNSIGNED-PAYLOAD"]);
+    command
+}
+
+fn s3_credentials() -> String {
+    let key = std::env::var("AWS_ACCESS_KEY_ID").unwrap_or_default();
+    let secret = std::env::var("AWS_SECRET_ACCESS_KEY").unwrap_or_default();
+    format!("user = {}\n", quoted(&format!("{}:{}", key, secret)))
+}
+
+fn object_url(bucket: &S3Bucket, name: &str) -> String {
+    format!("{}/{}/{}", bucket.endpoint.trim_end_matches('/'), bucket.bucket, name)
+}
+
+fn joined(dir: &str, name: &str) -> String {
+    if dir.is_empty() {
+        name.to_string()
+    } else {
+        format!("{}/{}", dir, name)
+    }
+}
+
+/// A path as one word of an sftp batch command (or a curl config value).
+fn quoted(path: &str) -> String {
+    format!("\"{}\"", path.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+/// Run a tool to completion with `stdin` piped in, returning its stdout on success.
+async fn run(tool: &'static str, command: &mut Command, stdin: Option<String>) -> Result<Vec<u8>, RemoteError> {
+    let output = output(command, stdin).await.map_err(|source| RemoteError::Spawn { tool, source })?;
+    if !output.status.success() {
+        return Err(RemoteError::Failed { tool, stderr: String::from_utf8_lossy(&output.stderr).trim().to_string() });
+    }
+    Ok(output.stdout)
+}
+
+/// Spawn `command` with every stream piped, write `stdin` and collect the rest.
+pub(crate) async fn output(command: &mut Command, stdin: Option<String>) -> std::io::Result<Output> {
+    let mut child = command.stdin(Stdio::piped()).stdout(Stdio::piped()).stderr(Stdio::piped()).kill_on_drop(true).spawn()?;
+    if let Some(mut pipe) = child.stdin.take() {
+        if let Some(stdin) = stdin {
+            // A write error means the tool exited early; its status and stderr explain why
+            let _ = pipe.write_all(stdin.as_bytes()).await;
+        }
+    }
+    child.wait_with_output().await
+}
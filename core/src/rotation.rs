@@ -0,0 +1,36 @@
+//! An identity rotation in progress: the new signature key and, per group, whether the Update
+//! moving our leaf to it has been merged. Each group signs with the new key from that merge
+//! on. It lasts only for the session, like the MLS groups whose leaves it moves.
+
+use openmls_basic_credential::SignatureKeyPair;
+use std::collections::BTreeMap;
+
+#[derive(Debug, Clone)]
+pub enum RotationStatus {
+    Pending,
+    /// The group's leaf carries the new key since `epoch`
+    Committed { epoch: u64 },
+    Failed(String),
+}
+
+/// The old key is kept until every group has converged.
+pub struct IdentityRotation {
+    pub new_signer: SignatureKeyPair,
+    pub progress: BTreeMap<String, RotationStatus>,
+}
+
+impl IdentityRotation {
+    pub fn new(new_signer: SignatureKeyPair) -> Self {
+        Self { new_signer, progress: BTreeMap::new() }
+    }
+
+    /// Whether our leaf in the group already carries the new key, so we must sign with it.
+    pub fn committed(&self, group_id: &str) -> bool {
+        matches!(self.progress.get(group_id), Some(RotationStatus::Committed { .. }))
+    }
+
+    /// Groups still on the old key.
+    pub fn remaining(&self) -> usize {
+        self.progress.values().filter(|status| !matches!(status, RotationStatus::Committed { .. })).count()
+    }
+}
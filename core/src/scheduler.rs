@@ -0,0 +1,121 @@
+use chrono::{DateTime, Duration, Local, NaiveDateTime, NaiveTime};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use crate::errors::StorageError;
+use crate::vault;
+
+pub const SCHEDULE_PATH: &str = "schedule.json";
+
+/// What happens when a job comes due.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum JobKind {
+    /// Shown locally only
+    Reminder,
+    /// Shown locally and posted to its group
+    Send,
+}
+
+/// A job waiting for its due time.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Job {
+    pub id: u64,
+    pub due: DateTime<Local>,
+    pub kind: JobKind,
+    pub text: String,
+    /// Group the job was created in; always set for sends
+    #[serde(default)]
+    pub group_id: Option<String>,
+    /// Account that created it; `None` for the primary one
+    #[serde(default)]
+    pub account: Option<String>,
+}
+
+/// Timed jobs (reminders and scheduled sends), persisted across restarts. Jobs that came
+/// due while the client was closed are handed out on the first check after it starts.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct Scheduler {
+    #[serde(default)]
+    next_id: u64,
+    #[serde(default)]
+    jobs: Vec<Job>,
+}
+
+impl Scheduler {
+    /// Schedule a job, returning its ID.
+    pub fn add(&mut self, due: DateTime<Local>, kind: JobKind, text: String, group_id: Option<String>, account: Option<String>) -> u64 {
+        self.next_id += 1;
+        let id = self.next_id;
+        let index = self.jobs.partition_point(|job| job.due <= due);
+        self.jobs.insert(index, Job { id, due, kind, text, group_id, account });
+        id
+    }
+
+    pub fn cancel(&mut self, id: u64) -> Option<Job> {
+        let index = self.jobs.iter().position(|job| job.id == id)?;
+        Some(self.jobs.remove(index))
+    }
+
+    /// Pending jobs, soonest first.
+    pub fn jobs(&self) -> &[Job] {
+        &self.jobs
+    }
+
+    /// Remove and return the jobs due at `now`, soonest first.
+    pub fn take_due(&mut self, now: DateTime<Local>) -> Vec<Job> {
+        let due = self.jobs.partition_point(|job| job.due <= now);
+        self.jobs.drain(..due).collect()
+    }
+}
+
+/// Parse when a job should run: a delay such as `45s`, `10m`, `1h30m` or `2d`, a time of day
+/// (`HH:MM`, the next time it comes round), or a date and time (`YYYY-MM-DDTHH:MM`).
+pub fn parse_when(value: &str, now: DateTime<Local>) -> Option<DateTime<Local>> {
+    if let Ok(time) = NaiveTime::parse_from_str(value, "%H:%M") {
+        let today = now.date_naive().and_time(time).and_local_timezone(Local).earliest()?;
+        return if today > now { Some(today) } else { today.checked_add_signed(Duration::days(1)) };
+    }
+    if let Ok(at) = NaiveDateTime::parse_from_str(value, "%Y-%m-%dT%H:%M") {
+        return at.and_local_timezone(Local).earliest().filter(|at| *at > now);
+    }
+    let delay = parse_delay(value)?;
+    now.checked_add_signed(delay).filter(|at| *at > now)
+}
+
+fn parse_delay(value: &str) -> Option<Duration> {
+    let mut total = Duration::zero();
+    let mut digits = String::new();
+    for c in value.chars() {
+        if c.is_ascii_digit() {
+            digits.push(c);
+            continue;
+        }
+        let amount: i64 = std::mem::take(&mut digits).parse().ok()?;
+        let part = match c {
+            's' => Duration::try_seconds(amount)?,
+            'm' => Duration::try_minutes(amount)?,
+            'h' => Duration::try_hours(amount)?,
+            'd' => Duration::try_days(amount)?,
+            _ => return None,
+        };
+        total = total.checked_add(&part)?;
+    }
+    digits.is_empty().then_some(total)
+}
+
+/// Load the schedule from `path` (normally `SCHEDULE_PATH`), or an empty one on first run.
+pub async fn load(path: &Path) -> Result<Scheduler, StorageError> {
+    if !vault::locate(path).exists() {
+        return Ok(Scheduler::default());
+    }
+    let name = path.display().to_string();
+    let content = vault::read_to_string(path).await.map_err(StorageError::io(name.clone()))?;
+    serde_json::from_str(&content).map_err(StorageError::invalid(name))
+}
+
+pub async fn save(scheduler: &Scheduler, path: &Path) -> Result<(), StorageError> {
+    let name = path.display().to_string();
+    let content = serde_json::to_string(scheduler).map_err(StorageError::invalid(name.clone()))?;
+    vault::write(path, content).await.map_err(StorageError::io(name))?;
+    Ok(())
+}
@@ -0,0 +1,395 @@
+//! Message search over the whole history, including pages moved to disk.
+//!
+//! A query is a list of words and filters, all of which must match: `from:alice`,
+//! `in:<group name or ID>`, `before:2024-01-01` (earlier than that day), `after:2024-01-01`
+//! (that day or later) and `has:attachment|link|reaction|pin`. A leading `-` negates a term,
+//! and `OR` separates alternatives, each a list of terms. Words match the start of words in a message,
+//! case-insensitively. `Query::parse` builds the syntax tree; `SearchIndex` answers it with
+//! one SQL query against `SEARCH_INDEX_PATH`, an SQLite database whose FTS5 table holds the
+//! words of every message (but not their text) and whose indexed columns hold senders,
+//! groups, times and `has:` flags. Messages are only read back for the hits.
+
+use std::collections::HashMap;
+use std::path::Path;
+
+use chrono::{DateTime, Local, NaiveDate, TimeZone};
+use rusqlite::types::Value;
+use rusqlite::{params, params_from_iter, Connection, OptionalExtension};
+
+use crate::errors::SearchError;
+use crate::preview;
+use crate::Message;
+
+pub const SEARCH_INDEX_PATH: &str = "search.db";
+
+const SCHEMA: &str = "
+    PRAGMA secure_delete = ON;
+    CREATE TABLE IF NOT EXISTS messages (
+        id INTEGER PRIMARY KEY,
+        group_id TEXT NOT NULL,
+        message_id TEXT NOT NULL,
+        sender TEXT NOT NULL,
+        timestamp INTEGER NOT NULL,
+        has INTEGER NOT NULL,
+        signature INTEGER NOT NULL,
+        UNIQUE (group_id, message_id)
+    );
+    CREATE INDEX IF NOT EXISTS messages_sender ON messages (sender, timestamp);
+    CREATE INDEX IF NOT EXISTS messages_group ON messages (group_id, timestamp);
+    CREATE INDEX IF NOT EXISTS messages_time ON messages (timestamp);
+    CREATE VIRTUAL TABLE IF NOT EXISTS message_words USING fts5 (
+        content, content = '', contentless_delete = 1, tokenize = 'unicode61 remove_diacritics 2'
+    );
+";
+
+/// What a `has:` filter asks a message to carry.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Has {
+    Attachment,
+    Link,
+    Reaction,
+    Pin,
+}
+
+impl Has {
+    fn bit(self) -> i64 {
+        match self {
+            Has::Attachment => 1,
+            Has::Link => 2,
+            Has::Reaction => 4,
+            Has::Pin => 8,
+        }
+    }
+
+    fn parse(value: &str) -> Option<Self> {
+        match value {
+            "attachment" | "file" => Some(Has::Attachment),
+            "link" | "url" => Some(Has::Link),
+            "reaction" => Some(Has::Reaction),
+            "pin" | "pinned" => Some(Has::Pin),
+            _ => None,
+        }
+    }
+
+    fn of(message: &Message) -> Vec<Has> {
+        let mut has = Vec::new();
+        if message.attachment {
+            has.push(Has::Attachment);
+        }
+        if preview::first_url(&message.content).is_some() {
+            has.push(Has::Link);
+        }
+        if !message.reactions.is_empty() {
+            has.push(Has::Reaction);
+        }
+        if message.pinned {
+            has.push(Has::Pin);
+        }
+        has
+    }
+}
+
+/// One condition on a message.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Term {
+    /// Lowercase; matches any word of the message starting with it
+    Word(String),
+    From(String),
+    In(String),
+    Before(NaiveDate),
+    After(NaiveDate),
+    Has(Has),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Query {
+    Term(Term),
+    Not(Box<Query>),
+    And(Vec<Query>),
+    Or(Vec<Query>),
+}
+
+impl Query {
+    /// Parse the words of a `search` command line.
+    pub fn parse(words: &[&str]) -> Result<Query, SearchError> {
+        let mut alternatives = Vec::new();
+        let mut terms = Vec::new();
+        for word in words {
+            if *word == "OR" {
+                if terms.is_empty() {
+                    return Err(SearchError::DanglingOr);
+                }
+                alternatives.push(and(std::mem::take(&mut terms)));
+                continue;
+            }
+            let (negated, word) = match word.strip_prefix('-') {
+                Some(rest) if !rest.is_empty() => (true, rest),
+                _ => (false, *word),
+            };
+            let Some(query) = parse_term(word)? else {
+                continue;
+            };
+            terms.push(if negated { Query::Not(Box::new(query)) } else { query });
+        }
+        if terms.is_empty() {
+            return Err(if alternatives.is_empty() { SearchError::Empty } else { SearchError::DanglingOr });
+        }
+        alternatives.push(and(terms));
+        Ok(if alternatives.len() == 1 { alternatives.remove(0) } else { Query::Or(alternatives) })
+    }
+}
+
+fn and(mut terms: Vec<Query>) -> Query {
+    if terms.len() == 1 {
+        terms.remove(0)
+    } else {
+        Query::And(terms)
+    }
+}
+
+/// A filter, or the words of free text; `None` if the text holds no words at all.
+fn parse_term(word: &str) -> Result<Option<Query>, SearchError> {
+    if let Some((key, value)) = word.split_once(':') {
+        let filter = match key.to_lowercase().as_str() {
+            "from" => Some(Term::From(value.to_lowercase())),
+            "in" => Some(Term::In(value.to_lowercase())),
+            "before" => Some(Term::Before(parse_date(key, value)?)),
+            "after" => Some(Term::After(parse_date(key, value)?)),
+            "has" => Some(Term::Has(Has::parse(&value.to_lowercase()).ok_or_else(|| SearchError::UnknownHas(value.to_string()))?)),
+            // Anything else, such as a URL, is searched as text
+            _ => None,
+        };
+        if let Some(filter) = filter {
+            if value.is_empty() {
+                return Err(SearchError::MissingValue(key.to_string()));
+            }
+            return Ok(Some(Query::Term(filter)));
+        }
+    }
+    let words: Vec<Query> = words(word).into_iter().map(|word| Query::Term(Term::Word(word))).collect();
+    Ok((!words.is_empty()).then(|| and(words)))
+}
+
+fn parse_date(key: &str, value: &str) -> Result<NaiveDate, SearchError> {
+    if value.is_empty() {
+        return Err(SearchError::MissingValue(key.to_string()));
+    }
+    NaiveDate::parse_from_str(value, "%Y-%m-%d").map_err(|_| SearchError::BadDate { filter: key.to_string(), value: value.to_string() })
+}
+
+/// The lowercase words of `text`: runs of letters and digits.
+fn words(text: &str) -> Vec<String> {
+    text.split(|c: char| !c.is_alphanumeric()).filter(|word| !word.is_empty()).map(str::to_lowercase).collect()
+}
+
+/// Local midnight starting `date`, in Unix seconds.
+fn start_of(date: NaiveDate) -> i64 {
+    let midnight = date.and_hms_opt(0, 0, 0).unwrap_or_default();
+    Local.from_local_datetime(&midnight).earliest().unwrap_or_else(|| Local.from_utc_datetime(&midnight)).timestamp()
+}
+
+/// FNV-1a over what the index records of a message, to skip unchanged ones cheaply.
+fn signature(message: &Message) -> i64 {
+    let redacted = message.redacted_by.is_some();
+    let has: i64 = Has::of(message).into_iter().map(Has::bit).sum();
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    let fields = [message.content.as_bytes(), message.sender.as_bytes(), &message.timestamp.timestamp().to_le_bytes(), &has.to_le_bytes(), &[u8::from(redacted)]];
+    for byte in fields.into_iter().flatten() {
+        hash ^= u64::from(*byte);
+        hash = hash.wrapping_mul(0x0100_0000_01b3);
+    }
+    hash as i64
+}
+
+/// A matching message, found by its group and ID.
+#[derive(Debug, Clone)]
+pub struct Hit {
+    pub group_id: String,
+    pub message_id: String,
+    pub timestamp: DateTime<Local>,
+}
+
+/// The persistent index: one row per message, and its words in the FTS5 table under the same
+/// row ID.
+pub struct SearchIndex {
+    connection: Connection,
+    /// Signatures of the messages checked this session, by group and message ID
+    checked: HashMap<(String, String), i64>,
+}
+
+impl SearchIndex {
+    /// Open the index at `path`, creating it if needed.
+    pub fn open(path: &Path) -> Result<Self, SearchError> {
+        Self::with_connection(Connection::open(path)?)
+    }
+
+    /// A new index that lives only in memory, for profiles sealed under a passphrase, whose
+    /// message words must not reach the disk in the clear.
+    pub fn open_in_memory() -> Result<Self, SearchError> {
+        Self::with_connection(Connection::open_in_memory()?)
+    }
+
+    fn with_connection(connection: Connection) -> Result<Self, SearchError> {
+        connection.execute_batch(SCHEMA)?;
+        Ok(Self { connection, checked: HashMap::new() })
+    }
+
+    /// Messages in the index.
+    pub fn len(&self) -> Result<usize, SearchError> {
+        let count: i64 = self.connection.query_row("SELECT COUNT(*) FROM messages", [], |row| row.get(0))?;
+        Ok(usize::try_from(count).unwrap_or_default())
+    }
+
+    pub fn is_empty(&self) -> Result<bool, SearchError> {
+        Ok(self.len()? == 0)
+    }
+
+    /// Add or refresh `messages` of `group_id` in one transaction; returns how many changed.
+    /// Redacted messages keep only their sender, group and time.
+    pub fn update<'a>(&mut self, group_id: &str, messages: impl IntoIterator<Item = &'a Message>) -> Result<usize, SearchError> {
+        let transaction = self.connection.transaction()?;
+        let mut changed = 0;
+        for message in messages {
+            let key = (group_id.to_string(), message.id.clone());
+            let signature = signature(message);
+            if self.checked.get(&key) == Some(&signature) {
+                continue;
+            }
+            let existing: Option<(i64, i64)> = transaction
+                .prepare_cached("SELECT id, signature FROM messages WHERE group_id = ?1 AND message_id = ?2")?
+                .query_row(params![group_id, message.id], |row| Ok((row.get(0)?, row.get(1)?)))
+                .optional()?;
+            if existing.is_some_and(|(_, indexed)| indexed == signature) {
+                self.checked.insert(key, signature);
+                continue;
+            }
+            let redacted = message.redacted_by.is_some();
+            let has: i64 = if redacted { 0 } else { Has::of(message).into_iter().map(Has::bit).sum() };
+            let sender = message.sender.to_lowercase();
+            let timestamp = message.timestamp.timestamp();
+            let id = match existing {
+                Some((id, _)) => {
+                    transaction
+                        .prepare_cached("UPDATE messages SET sender = ?2, timestamp = ?3, has = ?4, signature = ?5 WHERE id = ?1")?
+                        .execute(params![id, sender, timestamp, has, signature])?;
+                    transaction.prepare_cached("DELETE FROM message_words WHERE rowid = ?1")?.execute(params![id])?;
+                    id
+                }
+                None => {
+                    transaction
+                        .prepare_cached(
+                            "INSERT INTO messages (group_id, message_id, sender, timestamp, has, signature) VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+                        )?
+                        .execute(params![group_id, message.id, sender, timestamp, has, signature])?;
+                    transaction.last_insert_rowid()
+                }
+            };
+            if !redacted {
+                transaction
+                    .prepare_cached("INSERT INTO message_words (rowid, content) VALUES (?1, ?2)")?
+                    .execute(params![id, message.content])?;
+            }
+            self.checked.insert(key, signature);
+            changed += 1;
+        }
+        transaction.commit()?;
+        Ok(changed)
+    }
+
+    /// Drop one message, e.g. a send that was undone after it was indexed.
+    pub fn remove(&mut self, group_id: &str, message_id: &str) -> Result<(), SearchError> {
+        let transaction = self.connection.transaction()?;
+        transaction.execute(
+            "DELETE FROM message_words WHERE rowid IN (SELECT id FROM messages WHERE group_id = ?1 AND message_id = ?2)",
+            params![group_id, message_id],
+        )?;
+        transaction.execute("DELETE FROM messages WHERE group_id = ?1 AND message_id = ?2", params![group_id, message_id])?;
+        transaction.commit()?;
+        self.checked.remove(&(group_id.to_string(), message_id.to_string()));
+        Ok(())
+    }
+
+    /// Drop every message of a forgotten group.
+    pub fn remove_group(&mut self, group_id: &str) -> Result<(), SearchError> {
+        let transaction = self.connection.transaction()?;
+        transaction.execute("DELETE FROM message_words WHERE rowid IN (SELECT id FROM messages WHERE group_id = ?1)", params![group_id])?;
+        transaction.execute("DELETE FROM messages WHERE group_id = ?1", params![group_id])?;
+        transaction.commit()?;
+        self.checked.retain(|(group, _), _| group != group_id);
+        Ok(())
+    }
+
+    /// How many messages match `query`, and the newest `limit` of them. `groups` lists the
+    /// (ID, name) of every group, for `in:`.
+    pub fn search(&self, query: &Query, groups: &[(&str, &str)], limit: usize) -> Result<(usize, Vec<Hit>), SearchError> {
+        let mut values = Vec::new();
+        let condition = condition(query, groups, &mut values);
+        let count: i64 = self.connection.query_row(
+            &format!("SELECT COUNT(*) FROM messages WHERE {}", condition),
+            params_from_iter(values.iter()),
+            |row| row.get(0),
+        )?;
+        values.push(Value::Integer(i64::try_from(limit).unwrap_or(i64::MAX)));
+        let mut statement = self.connection.prepare(&format!(
+            "SELECT group_id, message_id, timestamp FROM messages WHERE {} ORDER BY timestamp DESC LIMIT ?",
+            condition
+        ))?;
+        let hits = statement
+            .query_map(params_from_iter(values.iter()), |row| {
+                let timestamp: i64 = row.get(2)?;
+                Ok(Hit {
+                    group_id: row.get(0)?,
+                    message_id: row.get(1)?,
+                    timestamp: DateTime::from_timestamp(timestamp, 0).map(|at| at.with_timezone(&Local)).unwrap_or_default(),
+                })
+            })?
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok((usize::try_from(count).unwrap_or_default(), hits))
+    }
+}
+
+/// The SQL condition on `messages` for `query`, pushing its parameters onto `values`.
+fn condition(query: &Query, groups: &[(&str, &str)], values: &mut Vec<Value>) -> String {
+    let mut join = |queries: &[Query], operator: &str| {
+        let parts: Vec<String> = queries.iter().map(|query| condition(query, groups, values)).collect();
+        format!("({})", parts.join(operator))
+    };
+    match query {
+        Query::And(queries) => join(queries, " AND "),
+        Query::Or(queries) => join(queries, " OR "),
+        Query::Not(inner) => format!("NOT {}", condition(inner, groups, values)),
+        Query::Term(Term::Word(prefix)) => {
+            // Words are letters and digits only, so they need no escaping inside the quotes
+            values.push(Value::Text(format!("\"{}\"*", prefix)));
+            "id IN (SELECT rowid FROM message_words WHERE message_words MATCH ?)".to_string()
+        }
+        Query::Term(Term::From(sender)) => {
+            values.push(Value::Text(sender.clone()));
+            "sender = ?".to_string()
+        }
+        Query::Term(Term::In(wanted)) => {
+            let ids: Vec<&str> = groups
+                .iter()
+                .filter(|(id, name)| id.to_lowercase() == *wanted || name.to_lowercase() == *wanted)
+                .map(|(id, _)| *id)
+                .collect();
+            if ids.is_empty() {
+                return "0".to_string();
+            }
+            values.extend(ids.iter().map(|id| Value::Text(id.to_string())));
+            format!("group_id IN ({})", vec!["?"; ids.len()].join(", "))
+        }
+        Query::Term(Term::Before(date)) => {
+            values.push(Value::Integer(start_of(*date)));
+            "timestamp < ?".to_string()
+        }
+        Query::Term(Term::After(date)) => {
+            values.push(Value::Integer(start_of(*date)));
+            "timestamp >= ?".to_string()
+        }
+        Query::Term(Term::Has(has)) => {
+            values.push(Value::Integer(has.bit()));
+            "(has & ?) != 0".to_string()
+        }
+    }
+}
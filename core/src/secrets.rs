@@ -0,0 +1,158 @@
+use std::path::{Path, PathBuf};
+use argon2::{Algorithm, Argon2, Params, Version};
+use tokio::fs;
+use tokio::io::AsyncWriteExt;
+use crate::archive::ARCHIVE_DIR;
+use crate::backup::BACKUP_STATE_PATH;
+use crate::config::{ACCOUNTS_DIR, CONFIG_PATH};
+use crate::errors::{CryptoError, StorageError};
+use crate::history::HISTORY_PATH;
+use crate::integrity::{AUDIT_KEY_PATH, DAMAGED_HISTORY_PATH, HISTORY_CHECKSUM_PATH};
+use crate::migrate::VERSION_PATH;
+use crate::preview::PREVIEWS_PATH;
+use crate::scheduler::SCHEDULE_PATH;
+use crate::search::SEARCH_INDEX_PATH;
+use crate::transfers::TRANSFERS_PATH;
+use crate::vault::{KEYSLOTS_PATH, PROFILES_DIR};
+
+pub(crate) const SALT_LEN: usize = 16;
+/// `KdfParams` as stored next to a salt: memory, iterations and lanes, as big-endian `u32`s.
+pub(crate) const KDF_PARAMS_LEN: usize = 12;
+const DERIVED_LEN: usize = 32;
+/// Costs above these are refused when read back, so an edited file cannot make an unlock
+/// attempt allocate gigabytes or run for hours.
+const MAX_MEMORY_KIB: u32 = 1 << 20;
+const MAX_ITERATIONS: u32 = 64;
+const MAX_LANES: u32 = 16;
+
+/// Argon2id costs a passphrase key is derived with. They are stored with the salt, so the
+/// defaults can be raised without locking out passphrases set under the old ones.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct KdfParams {
+    memory_kib: u32,
+    iterations: u32,
+    lanes: u32,
+}
+
+impl Default for KdfParams {
+    /// The argon2 crate's defaults, OWASP's minimum for Argon2id: 19 MiB, 2 passes, 1 lane.
+    fn default() -> Self {
+        Self { memory_kib: Params::DEFAULT_M_COST, iterations: Params::DEFAULT_T_COST, lanes: Params::DEFAULT_P_COST }
+    }
+}
+
+impl KdfParams {
+    pub(crate) fn encode(&self) -> [u8; KDF_PARAMS_LEN] {
+        let mut encoded = [0; KDF_PARAMS_LEN];
+        for (chunk, value) in encoded.chunks_mut(4).zip([self.memory_kib, self.iterations, self.lanes]) {
+            chunk.copy_from_slice(&value.to_be_bytes());
+        }
+        encoded
+    }
+
+    /// The costs in `encoded`, `None` if they are cut short or beyond what is accepted.
+    pub(crate) fn decode(encoded: &[u8]) -> Option<Self> {
+        let mut values = encoded.get(..KDF_PARAMS_LEN)?.chunks(4).map(|chunk| u32::from_be_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]));
+        let params = Self { memory_kib: values.next()?, iterations: values.next()?, lanes: values.next()? };
+        let within = params.memory_kib <= MAX_MEMORY_KIB && params.iterations <= MAX_ITERATIONS && params.lanes <= MAX_LANES;
+        (within && params.argon2().is_ok()).then_some(params)
+    }
+
+    fn argon2(&self) -> Result<Argon2<'static>, argon2::Error> {
+        let params = Params::new(self.memory_kib, self.iterations, self.lanes, Some(DERIVED_LEN))?;
+        Ok(Argon2::new(Algorithm::Argon2id, Version::V0x13, params))
+    }
+}
+
+/// Argon2id of `passphrase` under `salt`: the key a profile key is wrapped under.
+pub(crate) fn derive(salt: &[u8], params: KdfParams, passphrase: &str) -> Result<Vec<u8>, CryptoError> {
+    let mut key = vec![0; DERIVED_LEN];
+    params.argon2()
+        .and_then(|argon2| argon2.hash_password_into(passphrase.as_bytes(), salt, &mut key))
+        .map_err(CryptoError::Derive)?;
+    Ok(key)
+}
+
+/// Files holding local secrets, relative to the data directory: the top-level one while no
+/// passphrase is set, a profile directory after (see `vault`).
+pub fn local_data_paths() -> Vec<&'static str> {
+    vec![CONFIG_PATH, HISTORY_PATH, HISTORY_CHECKSUM_PATH, DAMAGED_HISTORY_PATH, TRANSFERS_PATH, SCHEDULE_PATH, PREVIEWS_PATH, VERSION_PATH, BACKUP_STATE_PATH, SEARCH_INDEX_PATH, AUDIT_KEY_PATH]
+}
+
+/// Overwrite a file with zeros and flush it to disk before unlinking it.
+pub async fn secure_delete(path: &Path) -> Result<(), StorageError> {
+    let overwrite = async {
+        let len = fs::metadata(path).await?.len() as usize;
+        let mut file = fs::OpenOptions::new().write(true).open(path).await?;
+        file.write_all(&vec![0u8; len]).await?;
+        file.sync_all().await?;
+        drop(file);
+        fs::remove_file(path).await
+    };
+    overwrite.await.map_err(StorageError::io(path.display().to_string()))
+}
+
+/// Securely delete a directory holding data files at any depth, e.g. `backups`.
+pub async fn secure_delete_dir(dir: &str) -> Result<(), StorageError> {
+    if !Path::new(dir).exists() {
+        return Ok(());
+    }
+    let mut pending = vec![PathBuf::from(dir)];
+    while let Some(current) = pending.pop() {
+        let name = current.display().to_string();
+        let mut entries = fs::read_dir(&current).await.map_err(StorageError::io(name.clone()))?;
+        while let Some(entry) = entries.next_entry().await.map_err(StorageError::io(name.clone()))? {
+            let path = entry.path();
+            if path.is_dir() {
+                pending.push(path);
+            } else {
+                secure_delete(&path).await?;
+            }
+        }
+    }
+    fs::remove_dir_all(dir).await.map_err(StorageError::io(dir))
+}
+
+/// Destroy every profile, the key slots and any plain data files, for the `wipe` command.
+pub async fn wipe_local_data() -> Result<(), StorageError> {
+    for path in local_data_paths().into_iter().chain([KEYSLOTS_PATH]) {
+        let path = Path::new(path);
+        if path.exists() {
+            secure_delete(path).await?;
+        }
+    }
+    crate::migrate::wipe_backups().await?;
+    secure_delete_dir(ARCHIVE_DIR).await?;
+    secure_delete_dir(ACCOUNTS_DIR).await?;
+    secure_delete_dir(PROFILES_DIR).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn derives_a_key_per_salt_passphrase_and_costs() {
+        let salt = [1; SALT_LEN];
+        let params = KdfParams::default();
+        let key = derive(&salt, params, "passphrase").unwrap();
+        assert_eq!(key.len(), DERIVED_LEN);
+        assert_eq!(key, derive(&salt, params, "passphrase").unwrap());
+        assert_ne!(key, derive(&[2; SALT_LEN], params, "passphrase").unwrap());
+        assert_ne!(key, derive(&salt, params, "passphrasf").unwrap());
+        let cheaper = KdfParams { iterations: 1, ..params };
+        assert_ne!(key, derive(&salt, cheaper, "passphrase").unwrap());
+    }
+
+    #[test]
+    fn reads_back_the_costs_it_stored_and_refuses_excessive_ones() {
+        let params = KdfParams::default();
+        assert_eq!(KdfParams::decode(&params.encode()), Some(params));
+        assert_eq!(KdfParams::decode(&params.encode()[..KDF_PARAMS_LEN - 1]), None);
+        let excessive = KdfParams { memory_kib: MAX_MEMORY_KIB + 1, ..params };
+        assert_eq!(KdfParams::decode(&excessive.encode()), None);
+        // Argon2 needs at least one pass and 8 KiB per lane
+        assert_eq!(KdfParams::decode(&KdfParams { iterations: 0, ..params }.encode()), None);
+        assert_eq!(KdfParams::decode(&KdfParams { memory_kib: 1, ..params }.encode()), None);
+    }
+}
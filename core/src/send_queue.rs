@@ -0,0 +1,179 @@
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use tokio::sync::{Notify, Semaphore};
+use crate::errors::NetworkError;
+use crate::tasks::TaskId;
+
+/// Outbound frame classes, highest priority first.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum SendPriority {
+    Handshake,
+    Control,
+    Chat,
+    Attachment,
+}
+
+impl SendPriority {
+    pub const ALL: [SendPriority; 4] = [
+        SendPriority::Handshake,
+        SendPriority::Control,
+        SendPriority::Chat,
+        SendPriority::Attachment,
+    ];
+
+    /// Classify a DS frame by its `type` field.
+    pub fn for_message_type(message_type: &str) -> Self {
+        match message_type {
+            "commit" | "welcome" | "proposal" | "create_group" | "join_group" | "key_package" => SendPriority::Handshake,
+            "application" | "application_chunk" => SendPriority::Chat,
+            "attachment" => SendPriority::Attachment,
+            _ => SendPriority::Control,
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            SendPriority::Handshake => "handshake",
+            SendPriority::Control => "control",
+            SendPriority::Chat => "chat",
+            SendPriority::Attachment => "attachments",
+        }
+    }
+}
+
+/// A serialized DS frame, optionally belonging to a tracked operation.
+#[derive(Debug, Clone)]
+pub struct Frame {
+    pub data: String,
+    pub task: Option<TaskId>,
+}
+
+/// Bounded multi-lane queue drained by the connection writer task.
+///
+/// Each priority has its own capacity, so a full attachment lane applies
+/// backpressure to uploads without ever blocking a commit.
+pub struct SendQueue {
+    lanes: Mutex<[VecDeque<Frame>; 4]>,
+    capacity: [Semaphore; 4],
+    notify: Notify,
+    closed: AtomicBool,
+}
+
+impl SendQueue {
+    pub fn new(capacity_per_lane: usize) -> Self {
+        Self {
+            lanes: Mutex::new(Default::default()),
+            capacity: std::array::from_fn(|_| Semaphore::new(capacity_per_lane)),
+            notify: Notify::new(),
+            closed: AtomicBool::new(false),
+        }
+    }
+
+    /// Enqueue a frame, waiting while its lane is full.
+    pub async fn push(&self, priority: SendPriority, frame: Frame) -> Result<(), NetworkError> {
+        let lane = priority as usize;
+        self.capacity[lane]
+            .acquire()
+            .await
+            .map_err(|_| NetworkError::QueueClosed)?
+            .forget();
+        self.lanes.lock().unwrap()[lane].push_back(frame);
+        self.notify.notify_one();
+        Ok(())
+    }
+
+    /// Next frame in priority order with its priority, or `None` once the queue is closed and
+    /// drained.
+    pub async fn pop(&self) -> Option<(SendPriority, Frame)> {
+        loop {
+            let next = {
+                let mut lanes = self.lanes.lock().unwrap();
+                lanes.iter_mut().enumerate().find_map(|(lane, queue)| queue.pop_front().map(|frame| (lane, frame)))
+            };
+            if let Some((lane, frame)) = next {
+                self.capacity[lane].add_permits(1);
+                return Some((SendPriority::ALL[lane], frame));
+            }
+            if self.is_closed() {
+                return None;
+            }
+            self.notify.notified().await;
+        }
+    }
+
+    pub fn close(&self) {
+        self.closed.store(true, Ordering::SeqCst);
+        for semaphore in &self.capacity {
+            semaphore.close();
+        }
+        self.notify.notify_one();
+    }
+
+    pub fn is_closed(&self) -> bool {
+        self.closed.load(Ordering::SeqCst)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.depths().iter().all(|depth| *depth == 0)
+    }
+
+    /// Frames waiting per priority, in `SendPriority::ALL` order.
+    pub fn depths(&self) -> [usize; 4] {
+        let lanes = self.lanes.lock().unwrap();
+        std::array::from_fn(|lane| lanes[lane].len())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+    use tokio::time::timeout;
+
+    fn frame(data: &str) -> Frame {
+        Frame { data: data.to_string(), task: None }
+    }
+
+    #[tokio::test]
+    async fn drains_higher_priorities_first() {
+        let queue = SendQueue::new(4);
+        queue.push(SendPriority::Attachment, frame("blob")).await.unwrap();
+        queue.push(SendPriority::Chat, frame("hello")).await.unwrap();
+        queue.push(SendPriority::Handshake, frame("commit")).await.unwrap();
+        queue.push(SendPriority::Chat, frame("again")).await.unwrap();
+        assert_eq!(queue.depths(), [1, 0, 2, 1]);
+        let mut order = Vec::new();
+        while !queue.is_empty() {
+            order.push(queue.pop().await.unwrap().1.data);
+        }
+        assert_eq!(order, ["commit", "hello", "again", "blob"]);
+    }
+
+    #[tokio::test]
+    async fn a_full_lane_does_not_block_the_others() {
+        let queue = SendQueue::new(1);
+        queue.push(SendPriority::Attachment, frame("blob")).await.unwrap();
+        assert!(timeout(Duration::from_millis(50), queue.push(SendPriority::Attachment, frame("more"))).await.is_err());
+        queue.push(SendPriority::Handshake, frame("commit")).await.unwrap();
+        assert_eq!(queue.pop().await.unwrap().0, SendPriority::Handshake);
+    }
+
+    #[tokio::test]
+    async fn closing_refuses_new_frames_but_drains_queued_ones() {
+        let queue = SendQueue::new(4);
+        queue.push(SendPriority::Chat, frame("hello")).await.unwrap();
+        queue.close();
+        assert!(queue.push(SendPriority::Chat, frame("late")).await.is_err());
+        assert_eq!(queue.pop().await.unwrap().1.data, "hello");
+        assert!(queue.pop().await.is_none());
+    }
+
+    #[test]
+    fn classifies_frames_by_type() {
+        assert_eq!(SendPriority::for_message_type("commit"), SendPriority::Handshake);
+        assert_eq!(SendPriority::for_message_type("application_chunk"), SendPriority::Chat);
+        assert_eq!(SendPriority::for_message_type("attachment"), SendPriority::Attachment);
+        assert_eq!(SendPriority::for_message_type("fetch"), SendPriority::Control);
+    }
+}
@@ -0,0 +1,71 @@
+//! Bridged and bot senders: identities that relay messages from outside the group's MLS
+//! members (an IRC or Matrix bridge, a webhook gateway) or post on their own. Anyone able to
+//! write to the bridged side can put words in their messages, so those messages are marked in
+//! the timeline and set off no local automation unless a `trust-sender` rule allows it.
+
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+
+/// A local action an incoming message can set off.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Automation {
+    /// Desktop notifications, sounds and the push relay
+    Notify,
+    /// Reading the message aloud
+    Speak,
+    /// Automatic translation by the external translator
+    Translate,
+    /// Fetching a link preview
+    Preview,
+}
+
+impl Automation {
+    pub const ALL: [Automation; 4] = [Automation::Notify, Automation::Speak, Automation::Translate, Automation::Preview];
+
+    pub fn parse(value: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|automation| automation.as_str() == value)
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Automation::Notify => "notify",
+            Automation::Speak => "speak",
+            Automation::Translate => "translate",
+            Automation::Preview => "preview",
+        }
+    }
+}
+
+/// Whether `identity` matches `pattern`, case-insensitively: exactly, or with each `*` standing
+/// for any run of characters (`*-bridge`, `irc:*`).
+pub fn matches(pattern: &str, identity: &str) -> bool {
+    let (pattern, identity) = (pattern.to_lowercase(), identity.to_lowercase());
+    let mut parts = pattern.split('*');
+    let first = parts.next().unwrap_or_default();
+    let Some(mut rest) = identity.strip_prefix(first) else {
+        return false;
+    };
+    let parts: Vec<&str> = parts.collect();
+    let Some((last, middle)) = parts.split_last() else {
+        return rest.is_empty();
+    };
+    for part in middle {
+        match rest.find(part) {
+            Some(at) => rest = &rest[at + part.len()..],
+            None => return false,
+        }
+    }
+    rest.len() >= last.len() && rest.ends_with(last)
+}
+
+/// Whether `identity` is one of the configured bridge or bot identities.
+pub fn is_bridged(bridged: &[String], identity: &str) -> bool {
+    bridged.iter().any(|pattern| matches(pattern, identity))
+}
+
+/// Whether a message from `identity` may set off `automation`: always for ordinary members,
+/// only with a rule for bridged ones.
+pub fn allows(bridged: &[String], trusted: &BTreeMap<String, BTreeSet<Automation>>, identity: &str, automation: Automation) -> bool {
+    !is_bridged(bridged, identity) || trusted.get(identity).is_some_and(|allowed| allowed.contains(&automation))
+}
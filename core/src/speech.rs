@@ -0,0 +1,113 @@
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::process::Stdio;
+use tokio::io::AsyncWriteExt;
+use tokio::process::Command;
+use tokio::sync::mpsc;
+
+/// Which incoming messages of a group are read aloud.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SpeechMode {
+    All,
+    /// Only messages that mention us
+    Mentions,
+}
+
+impl SpeechMode {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "all" => Some(SpeechMode::All),
+            "mentions" => Some(SpeechMode::Mentions),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SpeechMode::All => "all",
+            SpeechMode::Mentions => "mentions",
+        }
+    }
+}
+
+/// Text-to-speech for incoming messages, through a command that reads the text on stdin.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct SpeechConfig {
+    /// Program and arguments, e.g. `["espeak", "--stdin"]`
+    pub command: Vec<String>,
+    /// Groups read aloud, by ID
+    #[serde(default)]
+    pub groups: BTreeMap<String, SpeechMode>,
+}
+
+impl SpeechConfig {
+    /// Check the command can be invoked as written, for config validation.
+    pub fn validate(&self) -> Result<(), String> {
+        if self.command.first().map(|program| program.trim().is_empty()).unwrap_or(true) {
+            return Err("command must name a program".to_string());
+        }
+        Ok(())
+    }
+
+    /// Whether a message in `group_id` is read aloud.
+    pub fn speaks(&self, group_id: &str, mention: bool) -> bool {
+        match self.groups.get(group_id) {
+            Some(SpeechMode::All) => true,
+            Some(SpeechMode::Mentions) => mention,
+            None => false,
+        }
+    }
+}
+
+/// Reads utterances one after another on a background task, so a burst of messages is
+/// spoken in order rather than over each other.
+pub struct Speaker {
+    queue: mpsc::UnboundedSender<(Vec<String>, String)>,
+    errors: mpsc::UnboundedReceiver<String>,
+}
+
+impl Speaker {
+    /// Start the background task; must be called within the tokio runtime.
+    pub fn spawn() -> Self {
+        let (queue, mut utterances) = mpsc::unbounded_channel::<(Vec<String>, String)>();
+        let (errors_tx, errors) = mpsc::unbounded_channel();
+        tokio::spawn(async move {
+            while let Some((command, text)) = utterances.recv().await {
+                if let Err(e) = speak(&command, &text).await {
+                    let program = command.first().cloned().unwrap_or_default();
+                    let _ = errors_tx.send(format!("Could not run {} (is it installed?): {}", program, e));
+                }
+            }
+        });
+        Self { queue, errors }
+    }
+
+    /// Queue `text` to be read by `command`.
+    pub fn say(&self, command: &[String], text: String) {
+        let _ = self.queue.send((command.to_vec(), text));
+    }
+
+    /// The next failure of the speech command since the last call, if any.
+    pub fn take_error(&mut self) -> Option<String> {
+        self.errors.try_recv().ok()
+    }
+}
+
+async fn speak(command: &[String], text: &str) -> std::io::Result<()> {
+    let Some((program, args)) = command.split_first() else {
+        return Ok(());
+    };
+    let mut child = Command::new(program)
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .kill_on_drop(true)
+        .spawn()?;
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin.write_all(text.as_bytes()).await?;
+    }
+    child.wait().await?;
+    Ok(())
+}
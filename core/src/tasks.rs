@@ -0,0 +1,96 @@
+use std::collections::BTreeMap;
+use std::time::Instant;
+
+pub type TaskId = u64;
+
+const SPINNER: [char; 10] = ['⠋', '⠙', '⠹', '⠸', '⠼', '⠴', '⠦', '⠧', '⠇', '⠏'];
+const BAR_WIDTH: usize = 10;
+
+/// Spinner frame for something that has been going on for `elapsed_ms`.
+pub fn spinner(elapsed_ms: u128) -> char {
+    SPINNER[(elapsed_ms / 100) as usize % SPINNER.len()]
+}
+
+/// Reported by the connection writer task for frames belonging to a tracked operation.
+#[derive(Debug, Clone)]
+pub enum ProgressEvent {
+    FrameWritten(TaskId),
+    Failed(TaskId, String),
+}
+
+#[derive(Debug)]
+struct Task {
+    label: String,
+    done: usize,
+    total: usize,
+    started: Instant,
+}
+
+/// In-flight network operations shown as spinners/progress bars in the status area.
+#[derive(Debug, Default)]
+pub struct TaskTracker {
+    tasks: BTreeMap<TaskId, Task>,
+    next_id: TaskId,
+}
+
+impl TaskTracker {
+    /// Track an operation that completes once `total` frames have been written.
+    pub fn start(&mut self, label: impl Into<String>, total: usize) -> TaskId {
+        let id = self.next_id;
+        self.next_id += 1;
+        self.tasks.insert(id, Task { label: label.into(), done: 0, total: total.max(1), started: Instant::now() });
+        id
+    }
+
+    /// Apply an event; returns a message when the task finished or failed.
+    pub fn apply(&mut self, event: ProgressEvent) -> Option<String> {
+        match event {
+            ProgressEvent::FrameWritten(id) => self.advance(id),
+            ProgressEvent::Failed(id, reason) => {
+                let task = self.tasks.remove(&id)?;
+                Some(format!("{}: failed ({})", task.label, reason))
+            }
+        }
+    }
+
+    /// Count one step of `id` as done, e.g. a reply awaited on a spawned task; returns a message
+    /// once every step is.
+    pub fn advance(&mut self, id: TaskId) -> Option<String> {
+        let task = self.tasks.get_mut(&id)?;
+        task.done += 1;
+        if task.done < task.total {
+            return None;
+        }
+        let task = self.tasks.remove(&id)?;
+        Some(format!("{}: done", task.label))
+    }
+
+    /// Stop tracking a task whose frames never reached the queue.
+    pub fn cancel(&mut self, id: TaskId) {
+        self.tasks.remove(&id);
+    }
+
+    /// One line per in-flight task: a spinner for single-frame work, a bar otherwise.
+    pub fn status_lines(&self) -> Vec<String> {
+        self.tasks
+            .values()
+            .map(|task| {
+                let frame = spinner(task.started.elapsed().as_millis());
+                if task.total > 1 {
+                    let filled = task.done * BAR_WIDTH / task.total;
+                    format!(
+                        "{} {} [{}{}] {}/{}",
+                        frame,
+                        task.label,
+                        "#".repeat(filled),
+                        ".".repeat(BAR_WIDTH - filled),
+                        task.done,
+                        task.total
+                    )
+                } else {
+                    format!("{} {}", frame, task.label)
+                }
+            })
+            .collect()
+    }
+}
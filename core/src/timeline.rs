@@ -0,0 +1,113 @@
+use std::collections::HashMap;
+
+/// Rendered (wrapped) heights of timeline messages at the current width.
+#[derive(Debug, Default)]
+pub struct WrapCache {
+    width: u16,
+    group_id: Option<String>,
+    /// Message ID to (rendered length in chars, wrapped height)
+    heights: HashMap<String, (usize, u16)>,
+    /// Heights in timeline order from the last layout
+    order: Vec<u16>,
+}
+
+impl WrapCache {
+    /// Measure `messages` (ID and rendered text) at `width`. Returns `scroll` adjusted so the
+    /// message at the top of the view stays there when the width changed since the last layout.
+    pub fn layout(&mut self, group_id: &str, width: u16, messages: &[(&str, String)], scroll: u16) -> u16 {
+        let group_changed = self.group_id.as_deref() != Some(group_id);
+        let width_changed = self.width != width;
+        let anchor = (width_changed && !group_changed).then(|| self.anchor(scroll));
+        if group_changed || width_changed {
+            self.heights.clear();
+            self.width = width;
+            self.group_id = Some(group_id.to_string());
+        }
+
+        self.order = messages
+            .iter()
+            .map(|(id, text)| {
+                let len = text.chars().count();
+                match self.heights.get(*id) {
+                    Some((cached_len, height)) if *cached_len == len => *height,
+                    _ => {
+                        let height = wrapped_height(text, width);
+                        self.heights.insert(id.to_string(), (len, height));
+                        height
+                    }
+                }
+            })
+            .collect();
+
+        match anchor {
+            Some((index, within)) => {
+                let height = self.order.get(index).copied().unwrap_or(1);
+                self.offset_of(index).saturating_add(within.min(height.saturating_sub(1)))
+            }
+            None => scroll,
+        }
+    }
+
+    /// Message index at rendered line `scroll`, and the line within that message.
+    fn anchor(&self, scroll: u16) -> (usize, u16) {
+        let mut top = 0u16;
+        for (index, height) in self.order.iter().enumerate() {
+            if scroll < top.saturating_add(*height) {
+                return (index, scroll - top);
+            }
+            top = top.saturating_add(*height);
+        }
+        (self.order.len(), 0)
+    }
+
+    /// Drop measured heights (e.g. on resize); the previous order is kept to re-anchor the scroll.
+    pub fn invalidate(&mut self) {
+        self.heights.clear();
+    }
+
+    /// Rendered line at which message `index` starts.
+    pub fn offset_of(&self, index: usize) -> u16 {
+        self.order.iter().take(index).fold(0u16, |acc, height| acc.saturating_add(*height))
+    }
+
+    pub fn height_of(&self, index: usize) -> u16 {
+        self.order.get(index).copied().unwrap_or(0)
+    }
+
+    pub fn total(&self) -> u16 {
+        self.offset_of(self.order.len())
+    }
+
+    /// Rough bytes held by the measured heights, for the memory budget.
+    pub fn footprint(&self) -> usize {
+        let entry = std::mem::size_of::<(String, (usize, u16))>();
+        self.heights.keys().map(|id| id.len() + entry).sum::<usize>() + std::mem::size_of_val(self.order.as_slice())
+    }
+}
+
+/// Lines `text` occupies when word-wrapped (with trimming) to `width` columns.
+pub fn wrapped_height(text: &str, width: u16) -> u16 {
+    let width = width.max(1) as usize;
+    let mut lines = 0usize;
+    for paragraph in text.split('\n') {
+        lines += 1;
+        let mut column = 0usize;
+        for word in paragraph.split_whitespace() {
+            let len = word.chars().count();
+            if column > 0 && column + 1 + len <= width {
+                column += 1 + len;
+                continue;
+            }
+            if column > 0 {
+                lines += 1;
+            }
+            // Words longer than the line are broken across several lines
+            lines += len.saturating_sub(1) / width;
+            column = match len % width {
+                0 if len > 0 => width,
+                rest => rest,
+            };
+        }
+    }
+    lines.min(u16::MAX as usize) as u16
+}
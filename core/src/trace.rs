@@ -0,0 +1,110 @@
+use chrono::{DateTime, Local};
+use std::collections::VecDeque;
+
+const MAX_TRACE_ENTRIES: usize = 500;
+/// Width of each lane in the sequence diagram
+const LANE_WIDTH: usize = 18;
+const DELIVERY_SERVICE: &str = "DS";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    Sent,
+    Received,
+}
+
+/// One protocol frame exchanged with the delivery service.
+#[derive(Debug, Clone)]
+pub struct TraceEntry {
+    pub timestamp: DateTime<Local>,
+    pub direction: Direction,
+    pub message_type: String,
+    pub group_id: Option<String>,
+    /// Recipient of a sent frame or sender of a received one, when known
+    pub peer: Option<String>,
+}
+
+/// Bounded log of recent protocol frames, oldest first.
+#[derive(Debug, Default)]
+pub struct ProtocolTrace {
+    entries: VecDeque<TraceEntry>,
+}
+
+impl ProtocolTrace {
+    pub fn record(&mut self, direction: Direction, message_type: &str, group_id: Option<&str>, peer: Option<&str>) {
+        self.entries.push_back(TraceEntry {
+            timestamp: Local::now(),
+            direction,
+            message_type: message_type.to_string(),
+            group_id: group_id.map(str::to_string),
+            peer: peer.map(str::to_string),
+        });
+        while self.entries.len() > MAX_TRACE_ENTRIES {
+            self.entries.pop_front();
+        }
+    }
+
+    /// The last `count` entries for `group_id`, oldest first.
+    pub fn recent(&self, group_id: &str, count: usize) -> Vec<TraceEntry> {
+        let matching: Vec<&TraceEntry> = self.entries
+            .iter()
+            .filter(|entry| entry.group_id.as_deref() == Some(group_id))
+            .collect();
+        let skip = matching.len().saturating_sub(count);
+        matching.into_iter().skip(skip).cloned().collect()
+    }
+}
+
+/// Render `entries` as an ASCII sequence diagram with lanes for us (`me`), the delivery
+/// service and every peer that appears as a sender or recipient.
+pub fn sequence_diagram(me: &str, entries: &[TraceEntry]) -> Vec<String> {
+    let mut lanes = vec![me.to_string(), DELIVERY_SERVICE.to_string()];
+    for peer in entries.iter().filter_map(|entry| entry.peer.as_deref()) {
+        if !lanes.iter().any(|lane| lane == peer) {
+            lanes.push(peer.to_string());
+        }
+    }
+    let lane_of = |name: &str| lanes.iter().position(|lane| lane == name).unwrap_or(1);
+    let center = |lane: usize| lane * LANE_WIDTH + LANE_WIDTH / 2;
+    let gutter = " ".repeat(9);
+
+    let mut lines = Vec::new();
+    let header: String = lanes
+        .iter()
+        .map(|lane| {
+            let name: String = lane.chars().take(LANE_WIDTH - 2).collect();
+            format!("{:^width$}", name, width = LANE_WIDTH)
+        })
+        .collect();
+    lines.push(format!("{}{}", gutter, header.trim_end()));
+
+    let empty: Vec<char> = (0..lanes.len() * LANE_WIDTH)
+        .map(|x| if x % LANE_WIDTH == LANE_WIDTH / 2 { '|' } else { ' ' })
+        .collect();
+    for entry in entries {
+        // Frames relayed by the DS are drawn straight between the endpoints
+        let peer = entry.peer.as_deref().filter(|peer| *peer != me).map(lane_of);
+        let (from, to) = match entry.direction {
+            Direction::Sent => (0, peer.unwrap_or(1)),
+            Direction::Received => (peer.unwrap_or(1), 0),
+        };
+        let (left, right) = (center(from.min(to)), center(from.max(to)));
+
+        let mut row = empty.clone();
+        for cell in &mut row[left + 1..right] {
+            *cell = '-';
+        }
+        if to > from {
+            row[right - 1] = '>';
+        } else {
+            row[left + 1] = '<';
+        }
+        let room = right - left - 4;
+        for (offset, c) in entry.message_type.chars().take(room).enumerate() {
+            row[left + 2 + offset] = c;
+        }
+
+        let row: String = row.into_iter().collect();
+        lines.push(format!("{} {}", entry.timestamp.format("%H:%M:%S"), row.trim_end()));
+    }
+    lines
+}
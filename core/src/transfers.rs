@@ -0,0 +1,60 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::Path;
+use crate::errors::StorageError;
+use crate::chunking::{Chunk, Reassembler, REASSEMBLY_TIMEOUT};
+use crate::vault;
+
+pub const TRANSFERS_PATH: &str = "transfers.json";
+
+/// A chunked upload kept until the delivery service confirms every slice.
+#[derive(Serialize, Deserialize)]
+pub struct UploadManifest {
+    pub group_id: String,
+    pub chunks: Vec<Chunk>,
+    pub started: DateTime<Utc>,
+}
+
+/// Interrupted chunked transfers in both directions, persisted across restarts.
+#[derive(Default, Serialize, Deserialize)]
+pub struct TransferStore {
+    /// Parent message ID to the upload's chunks
+    #[serde(default)]
+    pub uploads: HashMap<String, UploadManifest>,
+    #[serde(default)]
+    pub downloads: Reassembler,
+}
+
+impl TransferStore {
+    /// Drop transfers the other side will no longer reassemble.
+    pub fn expire(&mut self) {
+        let now = Utc::now();
+        self.uploads.retain(|_, upload| now - upload.started < REASSEMBLY_TIMEOUT);
+        self.downloads.expire();
+    }
+}
+
+/// Body of a `chunk_resend` request: the slices of a message the requester is missing.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ResendRequest {
+    pub parent_id: String,
+    pub indexes: Vec<u32>,
+}
+
+/// Load transfer state from `path` (normally `TRANSFERS_PATH`), or an empty store on first run.
+pub async fn load(path: &Path) -> Result<TransferStore, StorageError> {
+    if !vault::locate(path).exists() {
+        return Ok(TransferStore::default());
+    }
+    let name = path.display().to_string();
+    let content = vault::read_to_string(path).await.map_err(StorageError::io(name.clone()))?;
+    serde_json::from_str(&content).map_err(StorageError::invalid(name))
+}
+
+pub async fn save(store: &TransferStore, path: &Path) -> Result<(), StorageError> {
+    let name = path.display().to_string();
+    let content = serde_json::to_string(store).map_err(StorageError::invalid(name.clone()))?;
+    vault::write(path, content).await.map_err(StorageError::io(name))?;
+    Ok(())
+}
@@ -0,0 +1,72 @@
+use serde::{Deserialize, Serialize};
+use std::process::Stdio;
+use std::time::Duration;
+use tokio::io::AsyncWriteExt;
+use tokio::process::Command;
+use crate::errors::TranslateError;
+
+/// How long a translator may run; local models can be slow, but a hung one is abandoned.
+const TRANSLATE_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// A translator run as an external command, e.g. `trans -b :{lang}` or a local model runner.
+/// The message is written to its stdin and the translation read from its stdout.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct TranslationConfig {
+    /// Program and arguments; `{lang}` in an argument is replaced by `language`
+    pub command: Vec<String>,
+    /// Language to translate into, in the form the command expects (e.g. `en`)
+    pub language: String,
+    /// Groups whose incoming messages are translated as they arrive, by ID
+    #[serde(default)]
+    pub auto_groups: Vec<String>,
+}
+
+impl TranslationConfig {
+    /// Check the translator can be invoked as written, for config validation.
+    pub fn validate(&self) -> Result<(), String> {
+        if self.command.first().map(|program| program.trim().is_empty()).unwrap_or(true) {
+            return Err("command must name a program".to_string());
+        }
+        if self.language.trim().is_empty() {
+            return Err("language must not be empty".to_string());
+        }
+        Ok(())
+    }
+}
+
+/// Translate `text` with the configured command. The target language is also passed in
+/// `MLS_TRANSLATE_TO` for scripts that prefer it to an argument.
+pub async fn translate(config: &TranslationConfig, text: &str) -> Result<String, TranslateError> {
+    let Some((program, args)) = config.command.split_first() else {
+        return Err(TranslateError::NotConfigured);
+    };
+    let mut command = Command::new(program);
+    command
+        .args(args.iter().map(|arg| arg.replace("{lang}", &config.language)))
+        .env("MLS_TRANSLATE_TO", &config.language)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true);
+    let spawn_error = |source| TranslateError::Spawn { program: program.clone(), source };
+    let mut child = command.spawn().map_err(spawn_error)?;
+    if let Some(mut stdin) = child.stdin.take() {
+        // A write error means the translator exited early; its status and stderr explain why
+        let _ = stdin.write_all(text.as_bytes()).await;
+    }
+    let output = tokio::time::timeout(TRANSLATE_TIMEOUT, child.wait_with_output())
+        .await
+        .map_err(|_| TranslateError::Timeout { program: program.clone(), secs: TRANSLATE_TIMEOUT.as_secs() })?
+        .map_err(spawn_error)?;
+    if !output.status.success() {
+        return Err(TranslateError::Failed {
+            program: program.clone(),
+            stderr: String::from_utf8_lossy(&output.stderr).trim().to_string(),
+        });
+    }
+    let translation = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    if translation.is_empty() {
+        return Err(TranslateError::Empty(program.clone()));
+    }
+    Ok(translation)
+}
@@ -0,0 +1,303 @@
+//! Warnings for delivery-service connections that are not protected in transit.
+//!
+//! The client speaks line-delimited JSON over plain TCP, so everything MLS does not
+//! encrypt (identities, group IDs, message types, sizes and timing) can be read and
+//! altered on the path. A connection to anything but a loopback address has to be
+//! accepted once per endpoint with `accept-insecure`. Like an SSH host key, the acceptance
+//! is remembered with a fingerprint, taken over the endpoint and the capabilities its
+//! delivery service advertised; a server that later advertises fewer has to be accepted again.
+//!
+//! Separately, a delivery service can prove an Ed25519 identity key in its greeting. It signs
+//! the session as the client negotiated it: the client's challenge and identity, the endpoint
+//! the client dialed, and the capabilities, clock and limits the client acts on. A proof relayed
+//! from another connection or endpoint, or a greeting altered on the path, fails the check. The
+//! first key seen for an endpoint is pinned; a different key, a bad proof or a missing key later
+//! blocks posting until `trust reset` clears the pin. Without TLS there is nothing to bind later
+//! frames to, so this authenticates the greeting, not the rest of the connection.
+
+use std::collections::BTreeMap;
+use std::net::IpAddr;
+
+use chrono::{DateTime, Local};
+use serde::{Deserialize, Serialize};
+
+use crate::crypto::{to_hex, CryptoProvider};
+
+/// An endpoint accepted for plaintext connections, as it was when accepted.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct InsecureEndpoint {
+    pub fingerprint: String,
+    /// Capabilities advertised then; `None` if the server sent no greeting
+    pub capabilities: Option<Vec<String>>,
+    pub accepted_at: DateTime<Local>,
+}
+
+/// How the current connection is protected in transit.
+#[derive(Debug, Clone, PartialEq)]
+pub enum TransportStatus {
+    /// Plaintext, but the traffic never leaves this machine
+    Loopback,
+    /// Plaintext to an endpoint accepted with this fingerprint
+    Accepted,
+    /// Plaintext to an endpoint never accepted
+    Unaccepted,
+    /// Accepted before, but the server no longer advertises `lost`
+    Downgraded { lost: Vec<String> },
+    /// Accepted before, under a different fingerprint
+    Changed,
+}
+
+impl TransportStatus {
+    /// Whether `accept-insecure` is needed before posting.
+    pub fn needs_acceptance(&self) -> bool {
+        !matches!(self, TransportStatus::Loopback | TransportStatus::Accepted)
+    }
+}
+
+/// Identity key a delivery service presented in its greeting, with its signature over the
+/// session it negotiated.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ServerKey {
+    pub public_key: Vec<u8>,
+    pub proof: Vec<u8>,
+    pub challenge: String,
+    /// `server_key_payload` of the session as this client saw it: what `proof` must sign
+    pub signed: Vec<u8>,
+}
+
+/// The parameters of one connection's greeting, as this client sent and received them.
+#[derive(Debug, Clone, Copy)]
+pub struct HelloSession<'a> {
+    /// The endpoint as dialed, `host:port`
+    pub endpoint: &'a str,
+    pub client_id: &'a str,
+    pub challenge: &'a str,
+    pub capabilities: Option<&'a [String]>,
+    pub server_time: Option<u64>,
+    pub max_message_bytes: Option<usize>,
+    pub max_attachment_bytes: Option<usize>,
+}
+
+/// Identity key of an endpoint, pinned the first time it was proven.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PinnedKey {
+    pub fingerprint: String,
+    pub pinned_at: DateTime<Local>,
+}
+
+/// The presented identity key compared with the pinned one.
+#[derive(Debug, Clone, PartialEq)]
+pub enum KeyStatus {
+    /// No key presented and none pinned: nothing to check
+    NotOffered,
+    /// Proven, and nothing pinned yet: pin `fingerprint`
+    FirstUse { fingerprint: String },
+    Matches,
+    /// Proven, but not the pinned key
+    Changed { pinned: String, presented: String },
+    /// Presented without a valid signature over the session
+    BadProof { presented: String },
+    /// A key is pinned but the server presented none
+    Withdrawn { pinned: String },
+}
+
+impl KeyStatus {
+    /// Whether posting stays blocked until `trust reset`.
+    pub fn is_alarm(&self) -> bool {
+        matches!(self, KeyStatus::Changed { .. } | KeyStatus::BadProof { .. } | KeyStatus::Withdrawn { .. })
+    }
+}
+
+/// What the delivery service signs: a fixed label and every greeting parameter, one per line,
+/// with capabilities sorted and `-` for anything absent.
+pub fn server_key_payload(session: &HelloSession) -> Vec<u8> {
+    let optional = |value: Option<String>| value.unwrap_or_else(|| "-".to_string());
+    let capabilities = session.capabilities.map(|capabilities| {
+        let mut sorted = capabilities.to_vec();
+        sorted.sort();
+        sorted.join(",")
+    });
+    [
+        "mls-ds-hello-v2".to_string(),
+        session.endpoint.to_string(),
+        session.client_id.to_string(),
+        session.challenge.to_string(),
+        optional(capabilities),
+        optional(session.server_time.map(|time| time.to_string())),
+        optional(session.max_message_bytes.map(|bytes| bytes.to_string())),
+        optional(session.max_attachment_bytes.map(|bytes| bytes.to_string())),
+    ]
+    .join("\n")
+    .into_bytes()
+}
+
+/// Verify the presented key's proof and compare its fingerprint with `pinned`.
+pub fn check_server_key(crypto: &CryptoProvider, pinned: Option<&PinnedKey>, key: Option<&ServerKey>) -> KeyStatus {
+    let Some(key) = key else {
+        return match pinned {
+            Some(pinned) => KeyStatus::Withdrawn { pinned: pinned.fingerprint.clone() },
+            None => KeyStatus::NotOffered,
+        };
+    };
+    let presented = crypto.fingerprint(&key.public_key).unwrap_or_default();
+    if crypto.verify_ed25519(&key.public_key, &key.signed, &key.proof).is_err() {
+        return KeyStatus::BadProof { presented };
+    }
+    match pinned {
+        None => KeyStatus::FirstUse { fingerprint: presented },
+        Some(pinned) if pinned.fingerprint == presented => KeyStatus::Matches,
+        Some(pinned) => KeyStatus::Changed { pinned: pinned.fingerprint.clone(), presented },
+    }
+}
+
+/// Whether `endpoint` (`host:port`) names this machine.
+pub fn is_loopback(endpoint: &str) -> bool {
+    let host = endpoint.rsplit_once(':').map_or(endpoint, |(host, _)| host);
+    let host = host.trim_start_matches('[').trim_end_matches(']');
+    host.eq_ignore_ascii_case("localhost") || host.parse::<IpAddr>().is_ok_and(|ip| ip.is_loopback())
+}
+
+/// Hex SHA-256 over the endpoint and the sorted capabilities it advertised.
+pub fn fingerprint(crypto: &CryptoProvider, endpoint: &str, capabilities: Option<&[String]>) -> String {
+    let advertised = match capabilities {
+        Some(capabilities) => {
+            let mut sorted = capabilities.to_vec();
+            sorted.sort();
+            sorted.join(",")
+        }
+        None => "-".to_string(),
+    };
+    let input = format!("{}\n{}", endpoint, advertised);
+    crypto.sha256(input.as_bytes()).map(|digest| to_hex(&digest)).unwrap_or_default()
+}
+
+/// Check the connection to `endpoint` against the endpoints accepted so far.
+pub fn assess(
+    crypto: &CryptoProvider,
+    accepted: &BTreeMap<String, InsecureEndpoint>,
+    endpoint: &str,
+    capabilities: Option<&[String]>,
+) -> TransportStatus {
+    if is_loopback(endpoint) {
+        return TransportStatus::Loopback;
+    }
+    let Some(known) = accepted.get(endpoint) else {
+        return TransportStatus::Unaccepted;
+    };
+    if known.fingerprint == fingerprint(crypto, endpoint, capabilities) {
+        return TransportStatus::Accepted;
+    }
+    let lost: Vec<String> = known
+        .capabilities
+        .iter()
+        .flatten()
+        .filter(|capability| !capabilities.is_some_and(|current| current.contains(capability)))
+        .cloned()
+        .collect();
+    if lost.is_empty() {
+        TransportStatus::Changed
+    } else {
+        TransportStatus::Downgraded { lost }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use openmls_basic_credential::SignatureKeyPair;
+    use openmls_traits::signatures::Signer;
+    use openmls_traits::types::SignatureScheme;
+
+    fn session<'a>(challenge: &'a str, capabilities: Option<&'a [String]>) -> HelloSession<'a> {
+        HelloSession {
+            endpoint: "ds.example.org:8080",
+            client_id: "alice",
+            challenge,
+            capabilities,
+            server_time: Some(1_700_000_000),
+            max_message_bytes: Some(65_536),
+            max_attachment_bytes: None,
+        }
+    }
+
+    /// The key a server with `signer` presents for `session`, proven over `proven`.
+    fn presented(signer: &SignatureKeyPair, session: &HelloSession, proven: &HelloSession) -> ServerKey {
+        ServerKey {
+            public_key: signer.public().to_vec(),
+            proof: signer.sign(&server_key_payload(proven)).unwrap(),
+            challenge: session.challenge.to_string(),
+            signed: server_key_payload(session),
+        }
+    }
+
+    fn pin(crypto: &CryptoProvider, signer: &SignatureKeyPair) -> PinnedKey {
+        PinnedKey { fingerprint: crypto.fingerprint(signer.public()).unwrap(), pinned_at: Local::now() }
+    }
+
+    #[test]
+    fn signs_every_session_parameter() {
+        let capabilities = ["zstd".to_string(), "chunks".to_string()];
+        let reordered = ["chunks".to_string(), "zstd".to_string()];
+        let payload = server_key_payload(&session("c1", Some(&capabilities)));
+        assert_eq!(payload, server_key_payload(&session("c1", Some(&reordered))));
+        assert_eq!(
+            String::from_utf8(payload.clone()).unwrap(),
+            "mls-ds-hello-v2\nds.example.org:8080\nalice\nc1\nchunks,zstd\n1700000000\n65536\n-"
+        );
+        assert_ne!(payload, server_key_payload(&session("c2", Some(&capabilities))));
+        assert_ne!(payload, server_key_payload(&session("c1", None)));
+        let raised = HelloSession { max_message_bytes: Some(1 << 20), ..session("c1", Some(&capabilities)) };
+        assert_ne!(payload, server_key_payload(&raised));
+    }
+
+    #[test]
+    fn pins_a_proven_key_on_first_use() {
+        let crypto = CryptoProvider::new();
+        let signer = SignatureKeyPair::new(SignatureScheme::ED25519).unwrap();
+        let session = session("c1", None);
+        let key = presented(&signer, &session, &session);
+        let pinned = pin(&crypto, &signer);
+        assert_eq!(check_server_key(&crypto, None, Some(&key)), KeyStatus::FirstUse { fingerprint: pinned.fingerprint.clone() });
+        assert_eq!(check_server_key(&crypto, Some(&pinned), Some(&key)), KeyStatus::Matches);
+        assert_eq!(check_server_key(&crypto, None, None), KeyStatus::NotOffered);
+    }
+
+    #[test]
+    fn alarms_on_a_changed_withdrawn_or_unproven_key() {
+        let crypto = CryptoProvider::new();
+        let signer = SignatureKeyPair::new(SignatureScheme::ED25519).unwrap();
+        let other = SignatureKeyPair::new(SignatureScheme::ED25519).unwrap();
+        let current = session("c2", None);
+        let pinned = pin(&crypto, &signer);
+
+        let changed = check_server_key(&crypto, Some(&pinned), Some(&presented(&other, &current, &current)));
+        assert!(matches!(changed, KeyStatus::Changed { .. }));
+        let withdrawn = check_server_key(&crypto, Some(&pinned), None);
+        assert_eq!(withdrawn, KeyStatus::Withdrawn { pinned: pinned.fingerprint.clone() });
+        // A proof replayed from an earlier session does not cover this one
+        let replayed = check_server_key(&crypto, Some(&pinned), Some(&presented(&signer, &current, &session("c1", None))));
+        assert_eq!(replayed, KeyStatus::BadProof { presented: pinned.fingerprint.clone() });
+        assert!([changed, withdrawn, replayed].iter().all(KeyStatus::is_alarm));
+    }
+
+    #[test]
+    fn notices_lost_capabilities() {
+        let crypto = CryptoProvider::new();
+        let endpoint = "ds.example.org:8080";
+        let capabilities = vec!["zstd".to_string(), "chunks".to_string()];
+        let accepted = BTreeMap::from([(
+            endpoint.to_string(),
+            InsecureEndpoint {
+                fingerprint: fingerprint(&crypto, endpoint, Some(&capabilities)),
+                capabilities: Some(capabilities.clone()),
+                accepted_at: Local::now(),
+            },
+        )]);
+        assert_eq!(assess(&crypto, &accepted, endpoint, Some(&capabilities)), TransportStatus::Accepted);
+        let downgraded = assess(&crypto, &accepted, endpoint, Some(&["chunks".to_string()]));
+        assert_eq!(downgraded, TransportStatus::Downgraded { lost: vec!["zstd".to_string()] });
+        assert_eq!(assess(&crypto, &accepted, "other.example.org:8080", None), TransportStatus::Unaccepted);
+        assert_eq!(assess(&crypto, &accepted, "[::1]:8080", None), TransportStatus::Loopback);
+        assert!(is_loopback("localhost:8080") && is_loopback("127.0.0.1:8080") && !is_loopback("10.0.0.1:8080"));
+    }
+}
@@ -0,0 +1,119 @@
+use openmls::prelude::{Credential, CredentialType};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use crate::crypto::{to_hex, CryptoProvider};
+use crate::mls_client::credential_identity;
+use crate::ratchet_tree::Reader;
+
+/// Rules every credential a Welcome or commit brings into a group must meet before it is
+/// applied. The default accepts everything openmls itself accepts.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct HandshakePolicy {
+    /// Credential types members may present ("basic", "x509"); empty allows any
+    #[serde(default)]
+    pub allowed_credentials: Vec<String>,
+    /// SHA-256 fingerprints (hex) of the root certificates X.509 chains must end in; empty
+    /// accepts any root
+    #[serde(default)]
+    pub trusted_roots: Vec<String>,
+    /// Regex every member identity must match in full, e.g. `[a-z0-9.]+@example\.com`
+    #[serde(default)]
+    pub identity_pattern: Option<String>,
+}
+
+impl HandshakePolicy {
+    /// Check the policy can be enforced as written, for config validation.
+    pub fn validate(&self) -> Result<(), String> {
+        if let Some(unknown) = self.allowed_credentials.iter().find(|kind| !matches!(kind.as_str(), "basic" | "x509")) {
+            return Err(format!("unknown credential type `{}`; use basic or x509", unknown));
+        }
+        let is_sha256 = |root: &String| {
+            let normalized = normalize_fingerprint(root);
+            normalized.len() == 64 && normalized.chars().all(|c| c.is_ascii_hexdigit())
+        };
+        if let Some(bad) = self.trusted_roots.iter().find(|root| !is_sha256(root)) {
+            return Err(format!("`{}` is not a SHA-256 fingerprint", bad));
+        }
+        if let Some(pattern) = &self.identity_pattern {
+            full_match(pattern).map_err(|e| format!("identity_pattern: {}", e))?;
+        }
+        Ok(())
+    }
+}
+
+/// `HandshakePolicy` compiled once and applied by `MlsClient` to Welcomes and commits.
+#[derive(Default)]
+pub struct HandshakeValidator {
+    allowed: Vec<String>,
+    trusted_roots: Vec<String>,
+    /// An invalid pattern (e.g. edited by hand) rejects every identity rather than none
+    identity: Option<Result<Regex, String>>,
+    crypto: CryptoProvider,
+}
+
+impl HandshakeValidator {
+    pub fn new(policy: &HandshakePolicy) -> Self {
+        Self {
+            allowed: policy.allowed_credentials.clone(),
+            trusted_roots: policy.trusted_roots.iter().map(|root| normalize_fingerprint(root)).collect(),
+            identity: policy.identity_pattern.as_deref().map(|pattern| full_match(pattern).map_err(|e| e.to_string())),
+            crypto: CryptoProvider::new(),
+        }
+    }
+
+    /// Why `credential` may not be in a group under the policy, if it may not.
+    pub fn check(&self, credential: &Credential) -> Result<(), String> {
+        let kind = match credential.credential_type() {
+            CredentialType::Basic => "basic".to_string(),
+            CredentialType::X509 => "x509".to_string(),
+            CredentialType::Other(code) => format!("type {}", code),
+        };
+        if !self.allowed.is_empty() && !self.allowed.contains(&kind) {
+            return Err(format!("{} presents a {} credential, which the policy does not allow", credential_identity(credential), kind));
+        }
+        if kind == "x509" && !self.trusted_roots.is_empty() {
+            self.check_root(credential.serialized_content())?;
+        }
+        match &self.identity {
+            None => Ok(()),
+            Some(Err(e)) => Err(format!("identity_pattern is invalid ({}), so no identity is accepted", e)),
+            Some(Ok(_)) if kind != "basic" => Err(format!("the identity in a {} credential cannot be checked against identity_pattern", kind)),
+            Some(Ok(pattern)) => {
+                let identity = credential_identity(credential);
+                if pattern.is_match(&identity) {
+                    Ok(())
+                } else {
+                    Err(format!("identity `{}` does not match identity_pattern", identity))
+                }
+            }
+        }
+    }
+
+    /// The chain's last certificate must be one of the pinned roots. Only the chain's claim is
+    /// checked here; certificate signatures are left to whoever issued the credentials.
+    fn check_root(&self, chain: &[u8]) -> Result<(), String> {
+        let malformed = |_| "malformed X.509 certificate chain".to_string();
+        let mut outer = Reader::new(chain);
+        let mut certificates = Reader::new(outer.vector().map_err(malformed)?);
+        let mut root = None;
+        while !certificates.is_empty() {
+            root = Some(certificates.vector().map_err(malformed)?);
+        }
+        let root = root.ok_or_else(|| "empty X.509 certificate chain".to_string())?;
+        let fingerprint = to_hex(&self.crypto.sha256(root).map_err(|e| e.to_string())?);
+        if self.trusted_roots.contains(&fingerprint) {
+            Ok(())
+        } else {
+            Err(format!("X.509 chain ends in untrusted root {}", fingerprint))
+        }
+    }
+}
+
+fn full_match(pattern: &str) -> Result<Regex, regex::Error> {
+    Regex::new(&format!("^(?:{})$", pattern))
+}
+
+/// Lowercase, without the separators fingerprints are often written with.
+fn normalize_fingerprint(fingerprint: &str) -> String {
+    fingerprint.chars().filter(|c| !matches!(c, ':' | ' ')).collect::<String>().to_lowercase()
+}
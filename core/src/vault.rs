@@ -0,0 +1,335 @@
+//! Profiles sealed under a passphrase.
+//!
+//! Once a passphrase is set, every data file lives in a profile directory under
+//! `PROFILES_DIR`, sealed with ChaCha20-Poly1305 under that profile's random key. The keys
+//! are kept in `KEYSLOTS_PATH`, each wrapped under a key derived from a passphrase with
+//! Argon2id, whose salt and costs are stored in the slot, in exactly `SLOTS` slots of the
+//! same size. A slot without a duress passphrase holds a
+//! profile sealed under a random passphrase nobody knows, so the file and the profile
+//! directories look the same whether or not a duress passphrase is set. Which slot the real
+//! profile is in is picked at random when the passphrase is first set.
+//!
+//! Hiding which profile is in use from someone who can read the data directory is out of
+//! scope. File contents are sealed, but not the names, sizes and times of the files: the real
+//! profile's directory fills with history, archive pages and accounts while an unused or
+//! barely used decoy holds little more than its config, and nothing pads or mirrors them. The
+//! duress passphrase is for being made to unlock the client, not for a disk under inspection.
+//!
+//! The profile the client runs in is process-wide, like the working directory the data
+//! paths are relative to: `enter` selects it, and the store modules go through `locate`,
+//! `read` and `write`, which map paths into the profile directory and seal its files while
+//! one is entered, and use the plain files otherwise.
+
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::{PoisonError, RwLock};
+use tokio::fs;
+use crate::archive::ARCHIVE_DIR;
+use crate::config::{ACCOUNTS_DIR, CONFIG_PATH};
+use crate::crypto::{to_hex, CryptoProvider};
+use crate::errors::{CryptoError, StorageError};
+use crate::migrate::BACKUP_DIR;
+use crate::search::SEARCH_INDEX_PATH;
+use crate::secrets::{self, local_data_paths, secure_delete, secure_delete_dir, KdfParams, KDF_PARAMS_LEN, SALT_LEN};
+
+pub const KEYSLOTS_PATH: &str = "keyslots";
+pub const PROFILES_DIR: &str = "profiles";
+/// Slots in `KEYSLOTS_PATH`: the passphrase's and the duress passphrase's, used or not.
+pub const SLOTS: usize = 2;
+
+const KEY_LEN: usize = 32;
+const NONCE_LEN: usize = 12;
+const TAG_LEN: usize = 16;
+const SLOT_LEN: usize = SALT_LEN + KDF_PARAMS_LEN + NONCE_LEN + KEY_LEN + TAG_LEN;
+/// Data files are copied between paths (upgrade backups, the last good history), so the
+/// additional data names the kind of file rather than its path.
+const STORE_AAD: &[u8] = b"mls-profile-store";
+const SLOT_AAD: &[u8] = b"mls-profile-slot";
+
+static ENTERED: RwLock<Option<Profile>> = RwLock::new(None);
+
+/// A profile opened with its passphrase: the slot holding it and its data key.
+#[derive(Clone)]
+pub struct Profile {
+    pub slot: usize,
+    key: Vec<u8>,
+}
+
+impl Profile {
+    /// The directory its data files live in.
+    pub fn dir(&self) -> PathBuf {
+        Path::new(PROFILES_DIR).join(self.slot.to_string())
+    }
+
+    fn seal(&self, data: &[u8]) -> io::Result<Vec<u8>> {
+        let crypto = CryptoProvider::new();
+        let nonce = crypto.random_bytes(NONCE_LEN).map_err(invalid_data)?;
+        let sealed = crypto.seal(&self.key, &nonce, STORE_AAD, data).map_err(invalid_data)?;
+        Ok([nonce, sealed].concat())
+    }
+
+    fn open(&self, sealed: &[u8]) -> io::Result<Vec<u8>> {
+        if sealed.len() < NONCE_LEN + TAG_LEN {
+            return Err(invalid_data("not sealed under this profile's key"));
+        }
+        let (nonce, sealed) = sealed.split_at(NONCE_LEN);
+        CryptoProvider::new().open(&self.key, nonce, STORE_AAD, sealed).map_err(|_| invalid_data("not sealed under this profile's key"))
+    }
+
+    /// Replace whatever the directory held with a fresh profile holding only `config`, as a
+    /// new decoy or an unused slot starts out.
+    pub async fn reset(&self, config: &[u8]) -> Result<(), StorageError> {
+        let dir = self.dir();
+        let name = dir.display().to_string();
+        secure_delete_dir(&name).await?;
+        fs::create_dir_all(&dir).await.map_err(StorageError::io(name))?;
+        let path = dir.join(CONFIG_PATH);
+        fs::write(&path, self.seal(config).map_err(StorageError::io(CONFIG_PATH))?)
+            .await
+            .map_err(StorageError::io(path.display().to_string()))
+    }
+}
+
+/// The wrapped profile keys, as kept in `KEYSLOTS_PATH`.
+pub struct KeySlots {
+    slots: Vec<Vec<u8>>,
+}
+
+impl KeySlots {
+    /// The key slots, `None` if no passphrase was ever set.
+    pub async fn load() -> Result<Option<Self>, StorageError> {
+        if !Path::new(KEYSLOTS_PATH).exists() {
+            return Ok(None);
+        }
+        let content = fs::read(KEYSLOTS_PATH).await.map_err(StorageError::io(KEYSLOTS_PATH))?;
+        if content.len() != SLOT_LEN * SLOTS {
+            return Err(StorageError::io(KEYSLOTS_PATH)(invalid_data("wrong size for the key slots")));
+        }
+        let slots: Vec<Vec<u8>> = content.chunks(SLOT_LEN).map(<[u8]>::to_vec).collect();
+        if slots.iter().any(|slot| KdfParams::decode(&slot[SALT_LEN..]).is_none()) {
+            return Err(StorageError::io(KEYSLOTS_PATH)(invalid_data("unsupported key derivation costs in the key slots")));
+        }
+        Ok(Some(Self { slots }))
+    }
+
+    pub async fn save(&self) -> Result<(), StorageError> {
+        fs::write(KEYSLOTS_PATH, self.slots.concat()).await.map_err(StorageError::io(KEYSLOTS_PATH))
+    }
+
+    /// New key slots: a new profile under `passphrase` in a random slot, and unused ones,
+    /// returned so their directories can be reset, in the others.
+    pub fn create(crypto: &CryptoProvider, passphrase: &str) -> Result<(Self, Profile, Vec<Profile>), CryptoError> {
+        let slot = usize::from(crypto.random_bytes(1)?[0]) % SLOTS;
+        let mut slots = Self { slots: vec![Vec::new(); SLOTS] };
+        let profile = slots.fill(crypto, slot, passphrase)?;
+        let unused = (0..SLOTS).filter(|other| *other != slot).map(|other| slots.fill_unused(crypto, other)).collect::<Result<_, _>>()?;
+        Ok((slots, profile, unused))
+    }
+
+    /// The profile `passphrase` opens, if any. Every slot's key is derived on every attempt,
+    /// so an attempt takes as long whichever slot it opens, if one.
+    pub fn open(&self, crypto: &CryptoProvider, passphrase: &str) -> Result<Option<Profile>, CryptoError> {
+        let mut opened = None;
+        for (slot, wrapped) in self.slots.iter().enumerate() {
+            let (salt, rest) = wrapped.split_at(SALT_LEN);
+            let (params, rest) = rest.split_at(KDF_PARAMS_LEN);
+            let (nonce, wrapped) = rest.split_at(NONCE_LEN);
+            let Some(params) = KdfParams::decode(params) else {
+                continue;
+            };
+            let unwrap_key = secrets::derive(salt, params, passphrase)?;
+            if let Ok(key) = crypto.open(&unwrap_key, nonce, SLOT_AAD, wrapped) {
+                opened.get_or_insert(Profile { slot, key });
+            }
+        }
+        Ok(opened)
+    }
+
+    /// Seal a new, empty profile under `passphrase` in `slot`, dropping the key it held.
+    pub fn fill(&mut self, crypto: &CryptoProvider, slot: usize, passphrase: &str) -> Result<Profile, CryptoError> {
+        let profile = Profile { slot, key: crypto.random_bytes(KEY_LEN)? };
+        self.wrap(crypto, &profile, passphrase)?;
+        Ok(profile)
+    }
+
+    /// Fill `slot` the way a slot without a duress passphrase looks: a new profile under a
+    /// random passphrase that is forgotten straight away.
+    pub fn fill_unused(&mut self, crypto: &CryptoProvider, slot: usize) -> Result<Profile, CryptoError> {
+        let passphrase = to_hex(&crypto.random_bytes(KEY_LEN)?);
+        self.fill(crypto, slot, &passphrase)
+    }
+
+    /// Wrap `profile`'s key under `passphrase`, replacing the passphrase that opened it, with
+    /// a new salt and the current default costs.
+    pub fn wrap(&mut self, crypto: &CryptoProvider, profile: &Profile, passphrase: &str) -> Result<(), CryptoError> {
+        let salt = crypto.random_bytes(SALT_LEN)?;
+        let params = KdfParams::default();
+        let nonce = crypto.random_bytes(NONCE_LEN)?;
+        let wrap_key = secrets::derive(&salt, params, passphrase)?;
+        let wrapped = crypto.seal(&wrap_key, &nonce, SLOT_AAD, &profile.key)?;
+        self.slots[profile.slot] = [salt, params.encode().to_vec(), nonce, wrapped].concat();
+        Ok(())
+    }
+}
+
+/// Run in `profile` from now on: data files are read from and written to its directory,
+/// sealed under its key.
+pub fn enter(profile: Profile) -> io::Result<()> {
+    std::fs::create_dir_all(profile.dir())?;
+    *ENTERED.write().unwrap_or_else(PoisonError::into_inner) = Some(profile);
+    Ok(())
+}
+
+/// The profile entered, `None` while no passphrase is set and the data files are plain.
+pub fn entered() -> Option<Profile> {
+    ENTERED.read().unwrap_or_else(PoisonError::into_inner).clone()
+}
+
+/// Where the data file at `path`, relative to the data directory, is kept.
+pub fn locate(path: impl AsRef<Path>) -> PathBuf {
+    match entered() {
+        Some(profile) => profile.dir().join(path),
+        None => path.as_ref().to_path_buf(),
+    }
+}
+
+/// Read the data file at `path`, opening it if a profile is entered.
+pub async fn read(path: impl AsRef<Path>) -> io::Result<Vec<u8>> {
+    let content = fs::read(locate(&path)).await?;
+    match entered() {
+        Some(profile) => profile.open(&content),
+        None => Ok(content),
+    }
+}
+
+/// `read` for callers outside the runtime.
+pub fn read_blocking(path: impl AsRef<Path>) -> io::Result<Vec<u8>> {
+    let content = std::fs::read(locate(&path))?;
+    match entered() {
+        Some(profile) => profile.open(&content),
+        None => Ok(content),
+    }
+}
+
+pub async fn read_to_string(path: impl AsRef<Path>) -> io::Result<String> {
+    String::from_utf8(read(path).await?).map_err(invalid_data)
+}
+
+/// Write the data file at `path`, sealed if a profile is entered.
+pub async fn write(path: impl AsRef<Path>, content: impl AsRef<[u8]>) -> io::Result<()> {
+    let content = match entered() {
+        Some(profile) => profile.seal(content.as_ref())?,
+        None => content.as_ref().to_vec(),
+    };
+    fs::write(locate(&path), content).await
+}
+
+/// Move the plain data files into `profile`, sealed, and securely delete the originals. The
+/// search index is deleted instead: it is kept in memory while a profile is entered.
+pub async fn seal_plain_files(profile: &Profile) -> Result<(), StorageError> {
+    let dirs = [ARCHIVE_DIR, ACCOUNTS_DIR, BACKUP_DIR];
+    let mut pending: Vec<PathBuf> = local_data_paths().into_iter().chain(dirs).map(PathBuf::from).collect();
+    while let Some(path) = pending.pop() {
+        let name = path.display().to_string();
+        if path.is_dir() {
+            let mut entries = fs::read_dir(&path).await.map_err(StorageError::io(name.clone()))?;
+            while let Some(entry) = entries.next_entry().await.map_err(StorageError::io(name.clone()))? {
+                pending.push(entry.path());
+            }
+            continue;
+        }
+        if !path.is_file() {
+            continue;
+        }
+        if !path.ends_with(SEARCH_INDEX_PATH) {
+            let content = fs::read(&path).await.map_err(StorageError::io(name.clone()))?;
+            let target = profile.dir().join(&path);
+            if let Some(parent) = target.parent() {
+                fs::create_dir_all(parent).await.map_err(StorageError::io(parent.display().to_string()))?;
+            }
+            let sealed = profile.seal(&content).map_err(StorageError::io(name.clone()))?;
+            fs::write(&target, sealed).await.map_err(StorageError::io(target.display().to_string()))?;
+        }
+        secure_delete(&path).await?;
+    }
+    for dir in dirs {
+        secure_delete_dir(dir).await?;
+    }
+    Ok(())
+}
+
+fn invalid_data(error: impl ToString) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, error.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn opens_the_profile_its_passphrase_wraps() {
+        let crypto = CryptoProvider::new();
+        let (slots, profile, unused) = KeySlots::create(&crypto, "correct horse").unwrap();
+        assert_eq!(unused.len(), SLOTS - 1);
+        assert!(unused.iter().all(|other| other.slot != profile.slot));
+        let opened = slots.open(&crypto, "correct horse").unwrap().unwrap();
+        assert_eq!((opened.slot, &opened.key), (profile.slot, &profile.key));
+        assert!(slots.open(&crypto, "wrong").unwrap().is_none());
+    }
+
+    #[test]
+    fn keeps_the_duress_slot_separate_and_the_same_size() {
+        let crypto = CryptoProvider::new();
+        let (mut slots, profile, unused) = KeySlots::create(&crypto, "real").unwrap();
+        let lengths = |slots: &KeySlots| slots.slots.iter().map(Vec::len).collect::<Vec<_>>();
+        assert_eq!(lengths(&slots), [SLOT_LEN; SLOTS]);
+        let decoy = slots.fill(&crypto, unused[0].slot, "duress").unwrap();
+        assert_eq!(lengths(&slots), [SLOT_LEN; SLOTS]);
+        assert_eq!(slots.open(&crypto, "duress").unwrap().unwrap().slot, decoy.slot);
+        assert_eq!(slots.open(&crypto, "real").unwrap().unwrap().slot, profile.slot);
+        // Removing the duress passphrase leaves a slot nobody can open
+        slots.fill_unused(&crypto, decoy.slot).unwrap();
+        assert!(slots.open(&crypto, "duress").unwrap().is_none());
+    }
+
+    #[test]
+    fn opens_a_slot_with_the_costs_stored_in_it() {
+        let crypto = CryptoProvider::new();
+        let (mut slots, profile, _) = KeySlots::create(&crypto, "real").unwrap();
+        // Wrapped under cheaper costs than the defaults, as before the defaults were raised
+        let params = KdfParams::decode(&[0, 0, 0x20, 0, 0, 0, 0, 1, 0, 0, 0, 1]).unwrap();
+        assert_ne!(params, KdfParams::default());
+        let salt = crypto.random_bytes(SALT_LEN).unwrap();
+        let nonce = crypto.random_bytes(NONCE_LEN).unwrap();
+        let wrap_key = secrets::derive(&salt, params, "real").unwrap();
+        let wrapped = crypto.seal(&wrap_key, &nonce, SLOT_AAD, &profile.key).unwrap();
+        slots.slots[profile.slot] = [salt, params.encode().to_vec(), nonce, wrapped].concat();
+        assert_eq!(slots.open(&crypto, "real").unwrap().unwrap().key, profile.key);
+        // Setting the passphrase again moves the slot to the defaults
+        slots.wrap(&crypto, &profile, "real").unwrap();
+        let stored = &slots.slots[profile.slot][SALT_LEN..SALT_LEN + KDF_PARAMS_LEN];
+        assert_eq!(KdfParams::decode(stored), Some(KdfParams::default()));
+        assert_eq!(slots.open(&crypto, "real").unwrap().unwrap().key, profile.key);
+    }
+
+    #[test]
+    fn rewrapping_changes_the_passphrase_but_not_the_key() {
+        let crypto = CryptoProvider::new();
+        let (mut slots, profile, _) = KeySlots::create(&crypto, "old").unwrap();
+        let sealed = profile.seal(b"history").unwrap();
+        slots.wrap(&crypto, &profile, "new").unwrap();
+        assert!(slots.open(&crypto, "old").unwrap().is_none());
+        let reopened = slots.open(&crypto, "new").unwrap().unwrap();
+        assert_eq!(reopened.open(&sealed).unwrap(), b"history");
+    }
+
+    #[test]
+    fn profiles_cannot_read_each_other() {
+        let crypto = CryptoProvider::new();
+        let (_, profile, unused) = KeySlots::create(&crypto, "real").unwrap();
+        let sealed = profile.seal(b"history").unwrap();
+        assert_ne!(&sealed[NONCE_LEN..], b"history");
+        assert!(unused[0].open(&sealed).is_err());
+        assert!(profile.open(&sealed[..NONCE_LEN]).is_err());
+    }
+}
@@ -0,0 +1,27 @@
+//! Bytes from the delivery service, read the way the connection's reader task reads them:
+//! one JSON frame per line, counted for `netstat` and then parsed.
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use mls_client_core::netstats::NetworkStats;
+use mls_client_core::network::NetworkMessage;
+use mls_client_core::trace::Direction;
+
+fuzz_target!(|data: &[u8]| {
+    // The reader stops at the first line that is not UTF-8
+    let Ok(text) = std::str::from_utf8(data) else {
+        return;
+    };
+    let mut stats = NetworkStats::default();
+    for line in text.lines() {
+        stats.record_frame(Direction::Received, line);
+        let Ok(message) = serde_json::from_str::<NetworkMessage>(line) else {
+            continue;
+        };
+        // A frame the client accepted reads back the same after it re-sends it
+        let sent = serde_json::to_string(&message).expect("a parsed frame serializes");
+        let reread: NetworkMessage = serde_json::from_str(&sent).expect("a serialized frame parses");
+        assert_eq!(serde_json::to_string(&reread).expect("a parsed frame serializes"), sent);
+    }
+});
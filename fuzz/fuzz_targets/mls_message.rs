@@ -0,0 +1,40 @@
+//! Bytes from another member, processed the way an incoming `application` or `commit` frame
+//! is: as an MLS message for a fixed group of two, received by the member who did not create
+//! it.
+
+#![no_main]
+
+use std::sync::{Mutex, OnceLock};
+
+use libfuzzer_sys::fuzz_target;
+use mls_client_core::crypto::CryptoProvider;
+use mls_client_core::extensions::GroupState;
+use mls_client_core::mls_client::MlsClient;
+use mls_client_core::{GroupMode, WirePolicy};
+
+const GROUP_ID: &str = "fuzz";
+
+/// Bob, in a group Alice created and added him to. Input the fuzzer makes up cannot carry
+/// Alice's signature, so processing it never moves the group on and every run sees the same
+/// state.
+fn bob() -> &'static Mutex<MlsClient> {
+    static BOB: OnceLock<Mutex<MlsClient>> = OnceLock::new();
+    BOB.get_or_init(|| {
+        let runtime = tokio::runtime::Runtime::new().expect("runtime");
+        let crypto = CryptoProvider::new();
+        let mut alice = runtime.block_on(MlsClient::new("alice", &crypto)).expect("alice");
+        let mut bob = runtime.block_on(MlsClient::new("bob", &crypto)).expect("bob");
+        let state = GroupState::new("alice", GroupMode::Chat);
+        let group = alice.create_group(WirePolicy::Mixed, &state).expect("create group");
+        alice.add_group(GROUP_ID, group);
+        let (_commit, welcome) = alice.add_member(GROUP_ID, bob.get_key_package()).expect("add bob");
+        alice.merge_pending_commit(GROUP_ID).expect("merge add");
+        bob.join_from_welcome(GROUP_ID, &welcome, WirePolicy::Mixed).expect("bob joins");
+        Mutex::new(bob)
+    })
+}
+
+fuzz_target!(|data: &[u8]| {
+    let mut bob = bob().lock().expect("no earlier run panicked");
+    let _ = bob.process_incoming(GROUP_ID, data);
+});
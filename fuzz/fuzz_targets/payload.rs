@@ -0,0 +1,11 @@
+//! Decrypted application content, decoded the way received messages are: decompressed when
+//! the sealed prefix says zstd, then read as a typed payload or plain chat text.
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use mls_client_core::payload;
+
+fuzz_target!(|data: &[u8]| {
+    payload::decode(data);
+});
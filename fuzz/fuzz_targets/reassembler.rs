@@ -0,0 +1,80 @@
+//! Chunks arriving out of order, repeated, from the wrong sender or group, tampered with or
+//! not chunks at all. Valid chunks come from `split`, so the fuzzer reaches reassembly
+//! instead of stopping at the integrity check.
+
+#![no_main]
+
+use arbitrary::Arbitrary;
+use libfuzzer_sys::fuzz_target;
+use mls_client_core::chunking::{self, Chunk, Reassembler};
+use mls_client_core::crypto::CryptoProvider;
+
+#[derive(Debug, Arbitrary)]
+struct Input {
+    payload: Vec<u8>,
+    chunk_size: u8,
+    steps: Vec<Step>,
+}
+
+#[derive(Debug, Arbitrary)]
+enum Step {
+    /// Deliver one of the split chunks, changed as given
+    Deliver { index: u8, sender: bool, group: bool, tamper: Option<Tamper> },
+    /// Deliver content that may not parse as a chunk at all
+    Raw(Vec<u8>),
+    /// Save and reload the partial downloads, as a restart does
+    Reload,
+}
+
+#[derive(Debug, Arbitrary)]
+enum Tamper {
+    Index(u32),
+    Total(u32),
+    ParentId(String),
+    Data(String),
+    PayloadDigest(String),
+}
+
+fuzz_target!(|input: Input| {
+    let crypto = CryptoProvider::new();
+    let Ok(chunks) = chunking::split(&crypto, "parent", &input.payload, usize::from(input.chunk_size)) else {
+        return;
+    };
+    if chunks.is_empty() {
+        return;
+    }
+    let mut reassembler = Reassembler::default();
+    for step in input.steps {
+        let (sender, group, chunk) = match step {
+            Step::Deliver { index, sender, group, tamper } => {
+                let mut chunk = chunks[usize::from(index) % chunks.len()].clone();
+                match tamper {
+                    Some(Tamper::Index(index)) => chunk.index = index,
+                    Some(Tamper::Total(total)) => chunk.total = total,
+                    Some(Tamper::ParentId(parent_id)) => chunk.parent_id = parent_id,
+                    Some(Tamper::Data(data)) => chunk.data = data,
+                    Some(Tamper::PayloadDigest(digest)) => chunk.payload_digest = digest,
+                    None => {}
+                }
+                let sender = if sender { "alice" } else { "mallory" };
+                let group = if group { "group" } else { "other" };
+                (sender, group, chunk)
+            }
+            Step::Raw(content) => match serde_json::from_slice::<Chunk>(&content) {
+                Ok(chunk) => ("alice", "group", chunk),
+                Err(_) => continue,
+            },
+            Step::Reload => {
+                let saved = serde_json::to_vec(&reassembler).expect("partial downloads serialize");
+                reassembler = serde_json::from_slice(&saved).expect("saved partial downloads load");
+                continue;
+            }
+        };
+        // Only the payload that was split can come out, and only under its own ID
+        if let Ok(Some((parent_id, payload))) = reassembler.add(&crypto, group, sender, chunk) {
+            assert_eq!(parent_id, "parent");
+            assert_eq!(payload, input.payload);
+        }
+        reassembler.missing();
+    }
+});
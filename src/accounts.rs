@@ -0,0 +1,322 @@
+use anyhow::Result;
+use chrono::Local;
+use mls_client_core::attachments::AttachmentStorage;
+use mls_client_core::config::{check_account_name, check_address, AccountConfig};
+use mls_client_core::crypto::CryptoProvider;
+use mls_client_core::errors::ConfigError;
+use mls_client_core::history::{self, HISTORY_PATH};
+use mls_client_core::mls_client::MlsClient;
+use mls_client_core::network::NetworkClient;
+use mls_client_core::pipeline::{CommitEffect, HeldCommit};
+use mls_client_core::search::SearchIndex;
+use mls_client_core::transfers::{self, ResendRequest, TransferStore, TRANSFERS_PATH};
+use mls_client_core::validation::{HandshakePolicy, HandshakeValidator};
+use mls_client_core::vault;
+use mls_client_core::{Group, WirePolicy};
+use openmls::prelude::tls_codec::Serialize;
+use std::collections::{HashMap, HashSet};
+use std::path::PathBuf;
+use tokio::sync::mpsc;
+
+use crate::undo_send::DelayedSend;
+use crate::{App, AppEvent, GroupListRow, HeldMessage, JoinRequest};
+
+/// Name `account switch` accepts for the identity at the top of config.json.
+pub const PRIMARY_ACCOUNT: &str = "primary";
+
+/// Session state of one identity. The active account's state lives in the `App` fields of
+/// the same names; the others are parked here and swapped in to run each tick.
+pub struct Account {
+    /// `None` for the primary identity
+    pub name: Option<String>,
+    pub username: String,
+    pub delivery_service_address: String,
+    pub delivery_service_fallbacks: Vec<String>,
+    pub attachment_storage: AttachmentStorage,
+    pub crypto: CryptoProvider,
+    pub mls_client: MlsClient,
+    pub network_client: NetworkClient,
+    pub groups: HashMap<String, Group>,
+    pub active_group: Option<String>,
+    pub pending_invitations: Vec<String>,
+    pub invited_wire_policies: HashMap<String, WirePolicy>,
+    pub pending_join_requests: Vec<JoinRequest>,
+    pub transfers: TransferStore,
+    pub transfers_resumed: bool,
+    pub pending_resends: Vec<ResendRequest>,
+    pub pending_rebases: Vec<(String, CommitEffect)>,
+    pub quarantine_retry: HashSet<String>,
+    pub held_commits: HashMap<String, HeldCommit>,
+    pub held_messages: HashMap<String, HeldMessage>,
+    pub delayed_sends: Vec<DelayedSend>,
+    pub issued_tokens: HashMap<String, String>,
+    pub search_index: Option<SearchIndex>,
+    pub events_tx: mpsc::UnboundedSender<AppEvent>,
+    pub events_rx: mpsc::UnboundedReceiver<AppEvent>,
+}
+
+impl Account {
+    /// Create an additional account's keystore and connect it, with the timelines kept in
+    /// its data directory from earlier sessions.
+    pub async fn open(config: &AccountConfig, handshake_policy: &HandshakePolicy) -> Result<Self> {
+        tokio::fs::create_dir_all(vault::locate(config.data_dir())).await?;
+        let crypto = CryptoProvider::new();
+        let mut mls_client = MlsClient::new(&config.username, &crypto).await?;
+        mls_client.handshake = HandshakeValidator::new(handshake_policy);
+        let network_client = NetworkClient::new(&config.delivery_service_addresses(), &config.username).await?;
+        if network_client.is_connected() {
+            network_client.publish_key_package(&mls_client.key_package.tls_serialize_detached()?).await?;
+        }
+        let (events_tx, events_rx) = mpsc::unbounded_channel();
+        Ok(Self {
+            name: Some(config.name.clone()),
+            username: config.username.clone(),
+            delivery_service_address: config.delivery_service_address.clone(),
+            delivery_service_fallbacks: config.delivery_service_fallbacks.clone(),
+            attachment_storage: config.attachment_storage.clone(),
+            crypto,
+            mls_client,
+            network_client,
+            groups: history::load(&config.data_path(HISTORY_PATH)).await?,
+            active_group: None,
+            pending_invitations: Vec::new(),
+            invited_wire_policies: HashMap::new(),
+            pending_join_requests: Vec::new(),
+            transfers: transfers::load(&config.data_path(TRANSFERS_PATH)).await?,
+            transfers_resumed: false,
+            pending_resends: Vec::new(),
+            pending_rebases: Vec::new(),
+            quarantine_retry: HashSet::new(),
+            held_commits: HashMap::new(),
+            held_messages: HashMap::new(),
+            delayed_sends: Vec::new(),
+            issued_tokens: HashMap::new(),
+            search_index: None,
+            events_tx,
+            events_rx,
+        })
+    }
+
+    /// Exchange this account's state with the active account's in `app`.
+    pub fn swap(&mut self, app: &mut App) {
+        std::mem::swap(&mut self.name, &mut app.account);
+        std::mem::swap(&mut self.username, &mut app.config.username);
+        std::mem::swap(&mut self.delivery_service_address, &mut app.config.delivery_service_address);
+        std::mem::swap(&mut self.delivery_service_fallbacks, &mut app.config.delivery_service_fallbacks);
+        std::mem::swap(&mut self.attachment_storage, &mut app.config.attachment_storage);
+        std::mem::swap(&mut self.crypto, &mut app.crypto);
+        std::mem::swap(&mut self.mls_client, &mut app.mls_client);
+        std::mem::swap(&mut self.network_client, &mut app.network_client);
+        std::mem::swap(&mut self.groups, &mut app.groups);
+        std::mem::swap(&mut self.active_group, &mut app.active_group);
+        std::mem::swap(&mut self.pending_invitations, &mut app.pending_invitations);
+        std::mem::swap(&mut self.invited_wire_policies, &mut app.invited_wire_policies);
+        std::mem::swap(&mut self.pending_join_requests, &mut app.pending_join_requests);
+        std::mem::swap(&mut self.transfers, &mut app.transfers);
+        std::mem::swap(&mut self.transfers_resumed, &mut app.transfers_resumed);
+        std::mem::swap(&mut self.pending_resends, &mut app.pending_resends);
+        std::mem::swap(&mut self.pending_rebases, &mut app.pending_rebases);
+        std::mem::swap(&mut self.quarantine_retry, &mut app.quarantine_retry);
+        std::mem::swap(&mut self.held_commits, &mut app.held_commits);
+        std::mem::swap(&mut self.held_messages, &mut app.held_messages);
+        std::mem::swap(&mut self.delayed_sends, &mut app.delayed_sends);
+        std::mem::swap(&mut self.issued_tokens, &mut app.issued_tokens);
+        std::mem::swap(&mut self.search_index, &mut app.search_index);
+        std::mem::swap(&mut self.events_tx, &mut app.events_tx);
+        std::mem::swap(&mut self.events_rx, &mut app.events_rx);
+    }
+}
+
+fn label(name: &Option<String>) -> &str {
+    name.as_deref().unwrap_or(PRIMARY_ACCOUNT)
+}
+
+impl App {
+    /// Every account in group-list order: the primary one, then `config.accounts`.
+    pub(crate) fn account_names(&self) -> Vec<Option<String>> {
+        std::iter::once(None)
+            .chain(self.config.accounts.iter().map(|account| Some(account.name.clone())))
+            .collect()
+    }
+
+    /// Where the active account keeps `file`; the primary account uses the top-level copy.
+    pub(crate) fn data_path(&self, file: &str) -> PathBuf {
+        match self.account.as_ref().and_then(|name| self.config.accounts.iter().find(|account| &account.name == name)) {
+            Some(account) => account.data_path(file),
+            None => PathBuf::from(file),
+        }
+    }
+
+    /// Group list header for an account: its identity, connection state and unread count.
+    /// The flag is whether it is connected.
+    pub(crate) fn account_header(&self, name: &Option<String>) -> (String, bool) {
+        let active = *name == self.account;
+        let arrow = if active { "▾" } else { "▸" };
+        let state = if active {
+            Some((&self.config.username, self.network_client.endpoint(), self.network_client.is_connected(), &self.groups))
+        } else {
+            self.accounts
+                .iter()
+                .find(|account| account.name == *name)
+                .map(|account| (&account.username, account.network_client.endpoint(), account.network_client.is_connected(), &account.groups))
+        };
+        let Some((username, address, connected, groups)) = state else {
+            return (format!("{} {} (not open)", arrow, label(name)), false);
+        };
+        let unread: usize = groups.values().map(|group| group.unread).sum();
+        let unread = if unread > 0 { format!(" *{}", unread) } else { String::new() };
+        let link = if connected { "●" } else { "○" };
+        (format!("{} {} {} {}@{}{}", arrow, link, label(name), username, address, unread), connected)
+    }
+
+    /// Save the config with the primary identity at the top, whichever account is active.
+    pub(crate) async fn save_config(&self) -> Result<(), ConfigError> {
+        let Some(primary) = self.accounts.iter().find(|account| account.name.is_none()) else {
+            return self.config.save().await;
+        };
+        let mut config = self.config.clone();
+        config.username = primary.username.clone();
+        config.delivery_service_address = primary.delivery_service_address.clone();
+        config.delivery_service_fallbacks = primary.delivery_service_fallbacks.clone();
+        config.attachment_storage = primary.attachment_storage.clone();
+        config.save().await
+    }
+
+    /// Handle events for the parked accounts and, when `sync` is set, sync them like the
+    /// active one. Their status lines are dropped and their events are prefixed with the
+    /// account name; a popup one of them opens is noted in the events instead of shown.
+    pub async fn run_parked_accounts(&mut self, sync: bool) -> Result<()> {
+        for index in 0..self.accounts.len() {
+            let activity = std::mem::take(&mut self.activity);
+            let popup = self.popup.take();
+            let screen = self.screen.clone();
+            let started = Local::now();
+            self.swap_account(index);
+            let mut ran = self.poll_events().await;
+            if ran.is_ok() && sync {
+                ran = self.sync_messages().await;
+            }
+            let opened = self.popup.take().map(|popup| popup.title);
+            let prefix = format!("[{}] ", label(&self.account));
+            self.swap_account(index);
+
+            for event in self.system_events.iter_mut().rev().take_while(|event| event.timestamp >= started) {
+                event.text.insert_str(0, &prefix);
+            }
+            if let Some(title) = opened {
+                self.record_event(format!("{}{} (switch to the account to see it)", prefix, title));
+            }
+            if let Err(e) = ran {
+                self.record_event(format!("{}sync failed: {}", prefix, e));
+            }
+            self.activity = activity;
+            self.popup = popup;
+            self.screen = screen;
+        }
+        Ok(())
+    }
+
+    /// Exchange the parked account at `index` with the active one. The account that was
+    /// active takes its place in the list, so `save_config` can always find the primary.
+    pub(crate) fn swap_account(&mut self, index: usize) {
+        let mut account = self.accounts.remove(index);
+        account.swap(self);
+        self.accounts.insert(index, account);
+    }
+
+    /// Make the account called `name` (or `primary`) the active one.
+    pub(crate) fn switch_account(&mut self, name: &str) {
+        let name = (name != PRIMARY_ACCOUNT).then(|| name.to_string());
+        if name == self.account {
+            self.set_status(format!("Already using account {}", label(&name)));
+            return;
+        }
+        let Some(index) = self.accounts.iter().position(|account| account.name == name) else {
+            self.set_warning(format!("No open account named {}", label(&name)));
+            return;
+        };
+        self.swap_account(index);
+
+        self.temp_username = self.config.username.clone();
+        self.temp_delivery_service = self.config.delivery_service_address.clone();
+        self.selected_message = None;
+        self.message_scroll = 0;
+        self.restored_above = 0;
+        let header = self.group_list_rows().iter().position(|row| *row == GroupListRow::Account(self.account.clone()));
+        match self.active_group.clone() {
+            Some(group_id) => self.select_group(&group_id),
+            None => self.group_list_state.select(header),
+        }
+        self.set_status(format!(
+            "Switched to account {} ({} at {})",
+            label(&self.account),
+            self.config.username,
+            self.config.delivery_service_address
+        ));
+    }
+
+    /// `account add|remove|switch|list`.
+    pub(crate) async fn account_command(&mut self, parts: &[&str]) -> Result<()> {
+        match parts {
+            ["add", name, username, address] => {
+                if let Err(e) = check_account_name(name).and_then(|_| check_address(address)) {
+                    self.set_error(format!("Account not added: {}", e));
+                    return Ok(());
+                }
+                if *name == PRIMARY_ACCOUNT || self.config.accounts.iter().any(|account| account.name == *name) {
+                    self.set_status(format!("Account {} already exists", name));
+                    return Ok(());
+                }
+                let config = AccountConfig {
+                    name: name.to_string(),
+                    username: username.to_string(),
+                    delivery_service_address: address.to_string(),
+                    delivery_service_fallbacks: Vec::new(),
+                    attachment_storage: AttachmentStorage::default(),
+                };
+                let account = Account::open(&config, &self.config.handshake_policy).await?;
+                let connected = account.network_client.is_connected();
+                self.accounts.push(account);
+                self.config.accounts.push(config);
+                self.save_config().await?;
+                self.set_status(if connected {
+                    format!("Added account {}, connected to {} as {}", name, address, username)
+                } else {
+                    format!("Added account {}; could not connect to {}, it is local only", name, address)
+                });
+                let event = self.status_text().to_string();
+                self.record_event(event);
+            }
+            ["remove", name] => {
+                if self.account.as_deref() == Some(*name) {
+                    self.set_status(format!("Switch away from account {} before removing it", name));
+                    return Ok(());
+                }
+                let Some(index) = self.config.accounts.iter().position(|account| account.name == *name) else {
+                    self.set_warning(format!("No account named {}", name));
+                    return Ok(());
+                };
+                let removed = self.config.accounts.remove(index);
+                self.accounts.retain(|account| account.name.as_deref() != Some(*name));
+                self.save_config().await?;
+                self.set_status(format!(
+                    "Removed account {}; its message history stays in {} until 'wipe'",
+                    name,
+                    removed.data_dir().display()
+                ));
+            }
+            ["switch", name] => self.switch_account(name),
+            [] | ["list"] => {
+                let names = self.account_names();
+                let mut lines: Vec<String> = names.iter().map(|name| self.account_header(name).0).collect();
+                lines.push(String::new());
+                lines.push("Switch with 'account switch <name>' or Enter on an account in the group list".to_string());
+                self.show_popup("Accounts", lines);
+            }
+            _ => {
+                self.set_warning("Usage: account add <name> <username> <host:port>, account remove|switch <name>, account list");
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,138 @@
+use crossterm::event::KeyCode;
+use mls_client_core::activity::Severity;
+use ratatui::{
+    style::{Color, Style},
+    text::{Line, Span},
+    widgets::{Block, Borders, Clear, Paragraph, Wrap},
+    Frame,
+};
+
+use crate::{App, AppScreen};
+
+/// Newest activity lines shown in the status panel.
+const STATUS_LINES: usize = 3;
+
+/// The `activity` viewer's filters and scroll position.
+#[derive(Debug, Default)]
+pub struct ActivityView {
+    /// Lowest severity listed
+    pub severity: Severity,
+    /// Only entries containing this text, typed while the viewer is open
+    pub filter: String,
+    pub scroll: u16,
+}
+
+fn severity_style(severity: Severity) -> Style {
+    match severity {
+        Severity::Info => Style::default().fg(Color::Green),
+        Severity::Warning => Style::default().fg(Color::Yellow),
+        Severity::Error => Style::default().fg(Color::Red),
+    }
+}
+
+impl App {
+    /// Report the outcome of what the user did, or something they should know about.
+    pub(crate) fn set_status(&mut self, text: impl Into<String>) {
+        self.activity.push(Severity::Info, text);
+    }
+
+    /// Report a request that was refused or typed wrong.
+    pub(crate) fn set_warning(&mut self, text: impl Into<String>) {
+        self.activity.push(Severity::Warning, text);
+    }
+
+    /// Report a failed operation.
+    pub(crate) fn set_error(&mut self, text: impl Into<String>) {
+        self.activity.push(Severity::Error, text);
+    }
+
+    /// The newest status line.
+    pub(crate) fn status_text(&self) -> &str {
+        self.activity.latest_text()
+    }
+
+    /// The newest activity, newest first, for the status panel.
+    pub(crate) fn status_lines(&self) -> Vec<Line<'static>> {
+        self.activity
+            .recent(STATUS_LINES)
+            .map(|entry| Line::styled(format!("[{}] {}", entry.timestamp.format("%H:%M:%S"), entry.describe()), severity_style(entry.severity)))
+            .collect()
+    }
+
+    /// `activity [info|warning|error] [text]`: open the activity log, listing entries at the
+    /// severity given or above that contain the text.
+    pub(crate) fn activity_command(&mut self, parts: &[&str]) {
+        let (severity, filter) = match parts.split_first() {
+            Some((first, rest)) => match Severity::parse(first) {
+                Some(severity) => (severity, rest.join(" ")),
+                None => (Severity::Info, parts.join(" ")),
+            },
+            None => (Severity::Info, String::new()),
+        };
+        self.open_activity(severity, filter);
+    }
+
+    pub(crate) fn open_activity(&mut self, severity: Severity, filter: String) {
+        self.activity_view = ActivityView { severity, filter, scroll: 0 };
+        self.screen = AppScreen::Activity;
+    }
+
+    pub(crate) fn handle_activity_input(&mut self, key: KeyCode) {
+        let view = &mut self.activity_view;
+        match key {
+            KeyCode::Esc => self.screen = AppScreen::Main,
+            KeyCode::Tab => {
+                let next = Severity::ALL.iter().position(|severity| *severity == view.severity).map_or(0, |at| (at + 1) % Severity::ALL.len());
+                view.severity = Severity::ALL[next];
+                view.scroll = 0;
+            }
+            KeyCode::Up => view.scroll = view.scroll.saturating_sub(1),
+            KeyCode::Down => view.scroll = view.scroll.saturating_add(1),
+            KeyCode::PageUp => view.scroll = view.scroll.saturating_sub(10),
+            KeyCode::PageDown => view.scroll = view.scroll.saturating_add(10),
+            KeyCode::Backspace => {
+                view.filter.pop();
+                view.scroll = 0;
+            }
+            KeyCode::Char(c) => {
+                view.filter.push(c);
+                view.scroll = 0;
+            }
+            _ => {}
+        }
+    }
+
+    pub(crate) fn render_activity(&mut self, f: &mut Frame) {
+        let area = f.size();
+        let view = &self.activity_view;
+        let lines: Vec<Line> = self
+            .activity
+            .matching(view.severity, &view.filter)
+            .map(|entry| {
+                Line::from(vec![
+                    Span::styled(format!("[{}] ", entry.timestamp.format("%Y-%m-%d %H:%M:%S")), Style::default().fg(Color::Gray)),
+                    Span::styled(format!("{:<8}", entry.severity.as_str()), severity_style(entry.severity)),
+                    Span::raw(entry.describe()),
+                ])
+            })
+            .collect();
+        let shown = if view.severity == Severity::Info { "everything".to_string() } else { format!("{}s and worse", view.severity.as_str()) };
+        let filter = if view.filter.is_empty() { "type to filter".to_string() } else { format!("matching '{}'", view.filter) };
+        let title = format!(
+            "Activity: {} of {}, {}, {} (Tab: severity, ↑/↓ PgUp/PgDn: scroll, Esc: close)",
+            lines.len(),
+            self.activity.len(),
+            shown,
+            filter
+        );
+        // Scrolling stops at the last entry
+        let scroll = view.scroll.min(u16::try_from(lines.len().saturating_sub(1)).unwrap_or(u16::MAX));
+        self.activity_view.scroll = scroll;
+        let log = Paragraph::new(lines)
+            .block(Block::default().borders(Borders::ALL).title(title))
+            .wrap(Wrap { trim: false })
+            .scroll((scroll, 0));
+        f.render_widget(Clear, area);
+        f.render_widget(log, area);
+    }
+}
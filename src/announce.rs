@@ -0,0 +1,47 @@
+use anyhow::Result;
+use mls_client_core::speech::SpeechMode;
+
+use crate::App;
+
+impl App {
+    /// `speak [all|mentions|off]`: show or set which incoming messages of the active group
+    /// are read aloud.
+    pub(crate) async fn speak_command(&mut self, parts: &[&str]) -> Result<()> {
+        let Some(group_id) = self.active_group.clone() else {
+            self.set_warning("No active group selected");
+            return Ok(());
+        };
+        let Some(speech) = self.config.speech.as_mut() else {
+            self.set_status("No speech command configured; set `speech` in config.json");
+            return Ok(());
+        };
+        match parts {
+            [] => {
+                let mode = speech.groups.get(&group_id).map(|mode| mode.as_str()).unwrap_or("off");
+                self.set_status(format!("Speech for this group: {}", mode));
+            }
+            ["off"] => {
+                speech.groups.remove(&group_id);
+                self.save_config().await?;
+                self.set_status("Speech off for this group");
+            }
+            [mode] => match SpeechMode::parse(mode) {
+                Some(mode) => {
+                    speech.groups.insert(group_id, mode);
+                    self.save_config().await?;
+                    self.set_status(format!("Speech for this group: {}", mode.as_str()));
+                }
+                None => self.set_warning("Usage: speak [all|mentions|off]"),
+            },
+            _ => self.set_warning("Usage: speak [all|mentions|off]"),
+        }
+        Ok(())
+    }
+
+    /// Read `text` aloud with the configured speech command.
+    pub(crate) fn announce(&self, text: String) {
+        if let Some(speech) = &self.config.speech {
+            self.speaker.say(&speech.command, text);
+        }
+    }
+}
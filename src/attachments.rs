@@ -0,0 +1,152 @@
+use mls_client_core::attachments::{self, Pointer};
+use mls_client_core::errors::{self, AttachmentError};
+use mls_client_core::netstats::format_bytes;
+use mls_client_core::network::NetworkMessage;
+use mls_client_core::pipeline::Frame;
+use mls_client_core::remote::Remote;
+use mls_client_core::tasks::TaskId;
+use mls_client_core::{Authenticated, Protection};
+
+use crate::{App, AppEvent, RetryOp};
+
+/// An `attachment` message whose blob is being downloaded.
+#[derive(Debug)]
+pub struct PendingAttachment {
+    pub group_id: String,
+    pub message_id: String,
+    pub sender: String,
+    pub pointer: Pointer,
+    pub envelope: NetworkMessage,
+    /// What protected the `attachment` message; the blob is bound to it by the digest
+    pub proof: Authenticated,
+    pub task: TaskId,
+}
+
+impl App {
+    /// Seal a payload too large for one message and upload it to the profile's store in the
+    /// background; the `attachment` message pointing at it goes out once the upload is done.
+    pub(crate) fn store_attachment(
+        &mut self,
+        group_id: &str,
+        message_id: &str,
+        remote: Remote,
+        payload: &[u8],
+    ) -> Result<(), AttachmentError> {
+        // The delivery service's own limit does not apply to blobs it never sees
+        let limit = self.config.max_attachment_bytes;
+        if payload.len() > limit {
+            return Err(AttachmentError::TooLarge { size: payload.len(), limit });
+        }
+        let (pointer, sealed) = attachments::seal(&self.crypto, &remote, payload)?;
+        let task = self.tasks.start(format!("Uploading {} to {}", format_bytes(payload.len() as u64), pointer.store), 1);
+        let events = self.events_tx.clone();
+        let group_id = group_id.to_string();
+        let message_id = message_id.to_string();
+        tokio::spawn(async move {
+            let stored = attachments::upload(&remote, &pointer.name, &sealed).await.map(|()| pointer);
+            let _ = events.send(AppEvent::AttachmentStored { group_id, message_id, task, stored });
+        });
+        Ok(())
+    }
+
+    /// Send the pointer to an uploaded blob, or take the message back out of the timeline so
+    /// `retry` can send it again.
+    pub(crate) async fn attachment_stored(
+        &mut self,
+        group_id: String,
+        message_id: String,
+        task: TaskId,
+        stored: Result<Pointer, AttachmentError>,
+    ) {
+        self.tasks.cancel(task);
+        let sent = match stored.map_err(anyhow::Error::from).and_then(|pointer| Ok(serde_json::to_vec(&pointer)?)) {
+            Ok(content) => {
+                let frames = vec![Frame::new("attachment", &message_id, content)];
+                self.send_frames(&group_id, frames, None).await
+            }
+            Err(e) => Err(e),
+        };
+        let Err(e) = sent else {
+            self.mark_attachment(&group_id, &message_id);
+            return;
+        };
+        self.report_error(&errors::SEND_FAILED, e.to_string());
+        let Some(group) = self.groups.get_mut(&group_id) else {
+            return;
+        };
+        if let Some(index) = group.messages.iter().position(|message| message.id == message_id) {
+            let message = group.messages.remove(index);
+            self.last_failed = Some(RetryOp::Send { group_id, message_id, message: message.content });
+        }
+        self.save_history().await;
+    }
+
+    /// Fetch the blob an `attachment` message points at when it is in this profile's store;
+    /// otherwise show what was not fetched and why.
+    pub(crate) fn receive_attachment(
+        &mut self,
+        group_id: &str,
+        message_id: String,
+        sender: String,
+        content: &[u8],
+        envelope: &NetworkMessage,
+        proof: Authenticated,
+    ) {
+        let pointer = match serde_json::from_slice::<Pointer>(content) {
+            Ok(pointer) if pointer.is_well_formed() => pointer,
+            _ => {
+                self.set_status(format!("Dropped a malformed attachment from {} in group {}", sender, group_id));
+                return;
+            }
+        };
+        let Some(remote) = self.config.attachment_storage.remote().filter(|remote| remote.locator() == pointer.store) else {
+            let note = format!(
+                "[Attachment of {} on {}, which is not this profile's attachment storage]",
+                format_bytes(pointer.size as u64),
+                pointer.store
+            );
+            self.deliver_note(group_id, message_id, sender, note, envelope);
+            return;
+        };
+        let limit = self.config.max_attachment_bytes;
+        let task = self.tasks.start(format!("Downloading {} from {}", format_bytes(pointer.size as u64), sender), 1);
+        let events = self.events_tx.clone();
+        let pending = PendingAttachment { group_id: group_id.to_string(), message_id, sender, pointer, envelope: envelope.clone(), proof, task };
+        tokio::spawn(async move {
+            let fetched = attachments::download(&remote, &pending.pointer, limit).await;
+            let _ = events.send(AppEvent::AttachmentFetched { pending: Box::new(pending), fetched });
+        });
+    }
+
+    pub(crate) fn attachment_fetched(&mut self, pending: PendingAttachment, fetched: Result<Vec<u8>, AttachmentError>) {
+        let PendingAttachment { group_id, message_id, sender, pointer, envelope, proof, task } = pending;
+        self.tasks.cancel(task);
+        match fetched.and_then(|sealed| attachments::open(&self.crypto, &pointer, &sealed)) {
+            Ok(payload) => {
+                if self.deliver_application(&group_id, message_id.clone(), sender, payload, &envelope, Protection::Authenticated(proof)) {
+                    self.mark_attachment(&group_id, &message_id);
+                }
+            }
+            Err(e) => {
+                let note = format!("[Attachment of {} could not be fetched: {}]", format_bytes(pointer.size as u64), e);
+                self.deliver_note(&group_id, message_id, sender, note, &envelope);
+            }
+        }
+    }
+
+    /// Put `note` in the timeline in place of a payload that could not be shown.
+    fn deliver_note(&mut self, group_id: &str, message_id: String, sender: String, note: String, envelope: &NetworkMessage) {
+        self.deliver_application(group_id, message_id.clone(), sender, note.into_bytes(), envelope, Protection::Local);
+        self.mark_attachment(group_id, &message_id);
+    }
+
+    /// Flag the message as carried by an attachment, for `has:attachment` searches.
+    fn mark_attachment(&mut self, group_id: &str, message_id: &str) {
+        let Some(group) = self.groups.get_mut(group_id) else {
+            return;
+        };
+        if let Some(message) = group.messages.iter_mut().find(|message| message.id == message_id) {
+            message.attachment = true;
+        }
+    }
+}
@@ -0,0 +1,139 @@
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use anyhow::Result;
+use mls_client_core::backup::{self, BackupOutcome, BackupState, BACKUP_STATE_PATH};
+use mls_client_core::errors::BackupError;
+use mls_client_core::tasks::TaskId;
+
+use crate::{App, AppEvent};
+
+/// A failed scheduled backup is tried again after this long rather than on every tick.
+const RETRY_AFTER: Duration = Duration::from_secs(10 * 60);
+
+/// `--restore-backup <archive> [--identity <age identity file>]`: unpack a backup into the
+/// data directory before starting.
+pub fn restore_requested() -> Option<(PathBuf, Option<PathBuf>)> {
+    let args: Vec<String> = std::env::args().collect();
+    let value = |flag: &str| args.iter().position(|arg| arg == flag).and_then(|i| args.get(i + 1)).map(PathBuf::from);
+    Some((value("--restore-backup")?, value("--identity")))
+}
+
+pub async fn restore(archive: &Path, identity: Option<&Path>) -> Result<()> {
+    let bundle = backup::decrypt(archive, identity).await?;
+    let restored = backup::restore(&bundle).await?;
+    println!(
+        "Restored {} file(s) from the backup taken {}; start the client to use them",
+        restored,
+        bundle.created.format("%Y-%m-%d %H:%M")
+    );
+    Ok(())
+}
+
+/// Scheduled backup bookkeeping.
+#[derive(Default)]
+pub struct Backups {
+    pub state: BackupState,
+    pub running: bool,
+    pub failed_at: Option<Instant>,
+}
+
+impl App {
+    /// `backup`: where backups go and when the last one ran; `backup now`: run one.
+    pub(crate) async fn backup_command(&mut self, parts: &[&str]) {
+        match parts {
+            [] => self.set_status(self.backup_summary()),
+            ["now"] => self.start_backup().await,
+            _ => self.set_warning("Usage: backup [now]"),
+        }
+    }
+
+    fn backup_summary(&self) -> String {
+        let Some(config) = &self.config.backups else {
+            return BackupError::NotConfigured.to_string();
+        };
+        let last = match self.backups.state.last {
+            Some(last) => format!("last {}", last.format("%Y-%m-%d %H:%M")),
+            None => "none yet".to_string(),
+        };
+        let running = if self.backups.running { " (one is running)" } else { "" };
+        format!(
+            "Backups to {} every {}h: {}, {} of {} kept{}",
+            config.target,
+            config.interval_hours,
+            last,
+            self.backups.state.archives.len(),
+            config.keep,
+            running
+        )
+    }
+
+    /// Start a backup once `interval_hours` have passed since the last one.
+    pub(crate) async fn backup_if_due(&mut self) {
+        let Some(config) = &self.config.backups else {
+            return;
+        };
+        if self.recovery.is_some() || self.backups.running || !self.backups.state.due(config) {
+            return;
+        }
+        if self.backups.failed_at.is_some_and(|failed| failed.elapsed() < RETRY_AFTER) {
+            return;
+        }
+        self.start_backup().await;
+    }
+
+    /// Bundle the data files as saved now and encrypt and store them in the background.
+    async fn start_backup(&mut self) {
+        let Some(config) = self.config.backups.clone() else {
+            self.set_warning(BackupError::NotConfigured.to_string());
+            return;
+        };
+        if self.backups.running {
+            self.set_status("A backup is already running");
+            return;
+        }
+        self.save_history().await;
+        let bundle = match backup::collect().await {
+            Ok(bundle) => bundle,
+            Err(e) => {
+                self.backup_failed(BackupError::from(e));
+                return;
+            }
+        };
+        self.backups.running = true;
+        let task = self.tasks.start(format!("Backing up to {}", config.target), 1);
+        let state = self.backups.state.clone();
+        let events = self.events_tx.clone();
+        tokio::spawn(async move {
+            let outcome = backup::run(&config, &bundle, state).await;
+            let _ = events.send(AppEvent::BackedUp { task, outcome });
+        });
+    }
+
+    pub(crate) async fn backup_finished(&mut self, task: TaskId, outcome: Result<BackupOutcome, BackupError>) {
+        self.tasks.cancel(task);
+        self.backups.running = false;
+        let outcome = match outcome {
+            Ok(outcome) => outcome,
+            Err(e) => return self.backup_failed(e),
+        };
+        self.backups.failed_at = None;
+        self.backups.state = outcome.state;
+        let mut message = format!("Backed up to {}", outcome.name);
+        if let Err(e) = backup::save_state(&self.backups.state, Path::new(BACKUP_STATE_PATH)).await {
+            message = format!("{}, but could not record it: {}", message, e);
+        }
+        self.set_status(message.clone());
+        self.record_event(message);
+        for leftover in outcome.leftovers {
+            self.record_event(format!("Could not delete old backup {}", leftover));
+        }
+    }
+
+    fn backup_failed(&mut self, error: BackupError) {
+        self.backups.failed_at = Some(Instant::now());
+        self.set_error(format!("Backup failed: {}", error));
+        let event = self.status_text().to_string();
+        self.record_event(event);
+    }
+}
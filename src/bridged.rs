@@ -0,0 +1,128 @@
+use std::collections::BTreeSet;
+
+use anyhow::Result;
+use mls_client_core::senders::{self, Automation};
+
+use crate::{App, AppScreen, Popup, PopupAction};
+
+const USAGE: &str =
+    "Usage: trust-sender [<identity> [notify|speak|translate|preview|all]... | revoke <identity> | bridge <pattern> | unbridge <pattern>]";
+
+impl App {
+    /// `trust-sender` lists the bridge patterns and rules, `trust-sender <identity> [automation]...`
+    /// lets a bridged sender set off the automations named (all of them by default), `revoke`
+    /// drops that rule, and `bridge`/`unbridge` manage which identities count as bridged.
+    pub(crate) async fn trust_sender_command(&mut self, parts: &[&str]) -> Result<()> {
+        match parts {
+            [] => self.show_sender_rules(),
+            ["bridge", pattern] => {
+                if self.config.bridged_senders.iter().any(|bridged| bridged.eq_ignore_ascii_case(pattern)) {
+                    self.set_status(format!("{} is already marked as bridged", pattern));
+                    return Ok(());
+                }
+                self.config.bridged_senders.push(pattern.to_string());
+                self.save_config().await?;
+                self.set_status(format!("Messages from {} are marked as bridged and set off nothing without a rule", pattern));
+            }
+            ["unbridge", pattern] => {
+                let before = self.config.bridged_senders.len();
+                self.config.bridged_senders.retain(|bridged| !bridged.eq_ignore_ascii_case(pattern));
+                if self.config.bridged_senders.len() == before {
+                    self.set_status(format!("{} is not in the bridged senders", pattern));
+                    return Ok(());
+                }
+                self.save_config().await?;
+                self.set_status(format!("{} is no longer marked as bridged", pattern));
+            }
+            ["revoke", identity] => {
+                if self.config.trusted_senders.remove(*identity).is_none() {
+                    self.set_status(format!("No trust-sender rule for {}", identity));
+                    return Ok(());
+                }
+                self.prompted_senders.remove(*identity);
+                self.save_config().await?;
+                self.set_status(format!("{} sets off no automation any more", identity));
+            }
+            ["bridge" | "unbridge" | "revoke", ..] => self.set_warning(USAGE.to_string()),
+            [identity, names @ ..] => {
+                let automations = if names.is_empty() || names == ["all"] {
+                    Some(Automation::ALL.to_vec())
+                } else {
+                    names.iter().map(|name| Automation::parse(name)).collect()
+                };
+                let Some(automations) = automations else {
+                    self.set_warning(USAGE.to_string());
+                    return Ok(());
+                };
+                self.allow_sender(identity, automations).await?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Add `automations` to `identity`'s rule.
+    pub(crate) async fn allow_sender(&mut self, identity: &str, automations: Vec<Automation>) -> Result<()> {
+        let allowed = self.config.trusted_senders.entry(identity.to_string()).or_default();
+        allowed.extend(automations);
+        let listed = describe(allowed);
+        self.save_config().await?;
+        self.set_status(if senders::is_bridged(&self.config.bridged_senders, identity) {
+            format!("{} may now set off: {}", identity, listed)
+        } else {
+            format!("Rule saved for {} ({}); it only matters once they match a bridged pattern", identity, listed)
+        });
+        let event = self.status_text().to_string();
+        self.record_event(event);
+        Ok(())
+    }
+
+    fn show_sender_rules(&mut self) {
+        let mut lines = vec!["Bridged senders:".to_string()];
+        if self.config.bridged_senders.is_empty() {
+            lines.push("  none; 'trust-sender bridge <pattern>' adds one, with * as a wildcard".to_string());
+        }
+        lines.extend(self.config.bridged_senders.iter().map(|pattern| format!("  {}", pattern)));
+        lines.push(String::new());
+        lines.push("Allowed automations:".to_string());
+        if self.config.trusted_senders.is_empty() {
+            lines.push("  none".to_string());
+        }
+        lines.extend(self.config.trusted_senders.iter().map(|(identity, allowed)| format!("  {}: {}", identity, describe(allowed))));
+        self.show_popup("Sender rules", lines);
+    }
+
+    /// Called when a bridged sender's message set off nothing it would have for a member: ask
+    /// once per sender and session whether to allow it, or just log it if a popup is up.
+    pub(crate) fn held_back_automations(&mut self, identity: &str, automations: Vec<Automation>) {
+        if !self.prompted_senders.insert(identity.to_string()) {
+            return;
+        }
+        let listed = automations.iter().map(|automation| automation.as_str()).collect::<Vec<_>>().join(", ");
+        self.record_event(format!("Held back {} for a message from bridged sender {}", listed, identity));
+        if self.popup.is_some() {
+            return;
+        }
+        self.popup = Some(Popup {
+            title: "Bridged sender".to_string(),
+            lines: vec![
+                format!("{} relays messages from outside the group, so anyone on the other side can write them.", identity),
+                format!("Its message set off nothing; it would have triggered: {}.", listed),
+                format!("Allow these for {} from now on, or later with 'trust-sender {}'.", identity, identity),
+            ],
+            actions: vec![PopupAction::TrustSender { identity: identity.to_string(), automations }],
+        });
+        self.screen = AppScreen::Popup;
+    }
+
+    /// Whether `identity`'s messages are marked as bridged in the timeline.
+    pub(crate) fn is_bridged_sender(&self, identity: &str) -> bool {
+        senders::is_bridged(&self.config.bridged_senders, identity)
+    }
+}
+
+fn describe(allowed: &BTreeSet<Automation>) -> String {
+    if allowed.is_empty() {
+        return "nothing".to_string();
+    }
+    allowed.iter().map(|automation| automation.as_str()).collect::<Vec<_>>().join(", ")
+}
@@ -0,0 +1,235 @@
+use std::collections::HashMap;
+
+use anyhow::Result;
+use mls_client_core::errors::{self, NetworkError};
+use mls_client_core::history::HistoryShare;
+use mls_client_core::mls_client::{self, Mismatch};
+use mls_client_core::network::NetworkMessage;
+use mls_client_core::pipeline::CommitEffect;
+use mls_client_core::tasks::TaskId;
+use openmls::prelude::KeyPackage;
+
+use crate::{App, AppEvent};
+
+/// What became of one identity listed in an `invite-file` run.
+#[derive(Debug)]
+enum Outcome {
+    /// Added by the commit of this batch
+    Added(usize),
+    /// Not attempted: already a member, or the commit before was held for review
+    Skipped(String),
+    Failed(String),
+}
+
+/// An `invite-file` run: key packages arrive one identity at a time and are committed in
+/// batches of `invite_batch_size` once all are in.
+#[derive(Debug)]
+pub struct BulkInvite {
+    group_name: String,
+    task: TaskId,
+    /// Identities in file order, for the report
+    listed: Vec<String>,
+    waiting: usize,
+    ready: Vec<(String, KeyPackage)>,
+    outcomes: HashMap<String, Outcome>,
+}
+
+/// The identities in a list or CSV file: the first column of each line, skipping blank lines,
+/// `#` comments, an `identity` or `username` header and repeats.
+fn parse_identities(text: &str) -> Vec<String> {
+    let mut identities: Vec<String> = Vec::new();
+    for line in text.lines().map(str::trim) {
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let identity = line.split(',').next().unwrap_or_default().trim().trim_matches('"').trim();
+        let header = identity.eq_ignore_ascii_case("identity") || identity.eq_ignore_ascii_case("username");
+        if identity.is_empty() || header || identities.iter().any(|listed| listed == identity) {
+            continue;
+        }
+        identities.push(identity.to_string());
+    }
+    identities
+}
+
+impl App {
+    /// `invite-file <group> <path>`: fetch key packages for everyone listed in `path` on a
+    /// spawned task; `bulk_key_packages` commits them once the last one is in.
+    pub(crate) async fn invite_file(&mut self, wanted: &str, path: &str) {
+        let Some(group) = self.find_group_id(wanted).and_then(|id| self.groups.get(&id)) else {
+            self.set_warning(format!("No group named {}", wanted));
+            return;
+        };
+        let group_id = group.id.clone();
+        let group_name = group.name.clone();
+        if !group.is_admin(&self.config.username) {
+            self.set_status(format!("Only admins can invite members to {}", group_name));
+            return;
+        }
+        if self.bulk_invites.contains_key(&group_id) {
+            self.set_status(format!("An invite-file run for {} is still fetching key packages", group_name));
+            return;
+        }
+        let text = match tokio::fs::read_to_string(path).await {
+            Ok(text) => text,
+            Err(e) => {
+                self.set_error(format!("Failed to read {}: {}", path, e));
+                return;
+            }
+        };
+        let listed = parse_identities(&text);
+        if listed.is_empty() {
+            self.set_status(format!("No identities listed in {}", path));
+            return;
+        }
+        let mut outcomes = HashMap::new();
+        let mut to_fetch = Vec::new();
+        for identity in &listed {
+            if *identity == self.config.username || self.mls_client.find_member(&group_id, identity).is_some() {
+                outcomes.insert(identity.clone(), Outcome::Skipped("already a member".to_string()));
+            } else {
+                to_fetch.push(identity.clone());
+            }
+        }
+        if to_fetch.is_empty() {
+            self.show_invite_report(&group_name, &listed, &outcomes);
+            return;
+        }
+        let Some(requester) = self.network_client.requester() else {
+            self.report_error(&errors::NOT_CONNECTED, format!("cannot invite the identities in {}", path));
+            return;
+        };
+        let task = self.tasks.start(format!("Inviting {} to {}", to_fetch.len(), group_name), to_fetch.len());
+        let run = BulkInvite { group_name, task, listed, waiting: to_fetch.len(), ready: Vec::new(), outcomes };
+        self.set_status(format!("Fetching key packages for {} identities from {}…", to_fetch.len(), path));
+        let events = self.events_tx.clone();
+        let spawned_group_id = group_id.clone();
+        tokio::spawn(async move {
+            for identity in to_fetch {
+                let packages = requester.fetch_key_packages(&identity).await;
+                let group_id = spawned_group_id.clone();
+                let _ = events.send(AppEvent::BulkKeyPackages { group_id, identity, packages });
+            }
+        });
+        self.bulk_invites.insert(group_id, run);
+    }
+
+    /// Check one identity's key package against the group; after the last, commit the batches
+    /// and show the report.
+    pub(crate) async fn bulk_key_packages(&mut self, group_id: &str, identity: String, packages: Result<Vec<Vec<u8>>, NetworkError>) {
+        let Some(task) = self.bulk_invites.get(group_id).map(|run| run.task) else {
+            return;
+        };
+        if let Some(finished) = self.tasks.advance(task) {
+            self.record_event(finished);
+        }
+        let key_package = packages
+            .map_err(|e| format!("failed to fetch key packages: {}", e))
+            .and_then(|packages| packages.first().cloned().ok_or_else(|| "no key packages published".to_string()))
+            .and_then(|data| mls_client::decode_key_package(&self.mls_client.crypto, &data).map_err(|e| e.to_string()))
+            .and_then(|key_package| {
+                let mismatches = self.mls_client.check_compatibility(group_id, &key_package).map_err(|e| e.to_string())?;
+                if mismatches.is_empty() {
+                    return Ok(key_package);
+                }
+                let reasons: Vec<String> = mismatches.iter().map(Mismatch::describe).collect();
+                Err(format!("key package does not meet the group's requirements ({})", reasons.join("; ")))
+            });
+        let Some(run) = self.bulk_invites.get_mut(group_id) else {
+            return;
+        };
+        match key_package {
+            Ok(key_package) => run.ready.push((identity, key_package)),
+            Err(reason) => {
+                run.outcomes.insert(identity, Outcome::Failed(reason));
+            }
+        }
+        run.waiting -= 1;
+        if run.waiting > 0 {
+            return;
+        }
+        let Some(mut run) = self.bulk_invites.remove(group_id) else {
+            return;
+        };
+        self.commit_batches(group_id, &mut run).await;
+        self.show_invite_report(&run.group_name, &run.listed, &run.outcomes);
+    }
+
+    /// Add the fetched key packages `invite_batch_size` at a time, one commit per batch,
+    /// stopping at a commit held for review.
+    async fn commit_batches(&mut self, group_id: &str, run: &mut BulkInvite) {
+        let share = (self.config.share_history_on_invite > 0).then_some(HistoryShare::Last(self.config.share_history_on_invite));
+        let batch_size = self.config.invite_batch_size.max(1);
+        let ready = std::mem::take(&mut run.ready);
+        let mut held = false;
+        for (index, batch) in ready.chunks(batch_size).enumerate() {
+            let identities: Vec<String> = batch.iter().map(|(identity, _)| identity.clone()).collect();
+            let skipped = |reason: &str| Outcome::Skipped(reason.to_string());
+            if held {
+                for identity in identities {
+                    run.outcomes.insert(identity, skipped("an earlier batch is held for review; run invite-file again after `commit send`"));
+                }
+                continue;
+            }
+            let key_packages: Vec<KeyPackage> = batch.iter().map(|(_, key_package)| key_package.clone()).collect();
+            let added = self.add_batch(group_id, &identities, &key_packages, share).await;
+            held = matches!(added, Ok(false)) && self.held_commits.contains_key(group_id);
+            for identity in identities {
+                let outcome = match &added {
+                    Ok(true) => Outcome::Added(index + 1),
+                    Ok(false) if held => skipped("commit held for review; `commit send` adds them"),
+                    Ok(false) => Outcome::Failed(self.status_text().to_string()),
+                    Err(e) => Outcome::Failed(e.to_string()),
+                };
+                run.outcomes.insert(identity, outcome);
+            }
+        }
+    }
+
+    /// Commit one Add for every key package and deliver the shared Welcome to each new member;
+    /// `true` once merged, as with `add_to_group`.
+    async fn add_batch(
+        &mut self,
+        group_id: &str,
+        identities: &[String],
+        key_packages: &[KeyPackage],
+        share: Option<HistoryShare>,
+    ) -> Result<bool> {
+        let (commit, welcome) = self.mls_client.add_members(group_id, key_packages)?;
+        let policy = self.groups.get(group_id).map(|group| group.wire_policy).unwrap_or_default();
+        let mut messages = vec![NetworkMessage::for_group("commit", &self.config.username, group_id, commit)];
+        for identity in identities {
+            let mut welcome_message = NetworkMessage::for_group("welcome", &self.config.username, group_id, welcome.clone());
+            welcome_message.recipient = Some(identity.clone());
+            let mut invitation = NetworkMessage::for_group("invitation", &self.config.username, group_id, policy.as_str().as_bytes().to_vec());
+            invitation.recipient = Some(identity.clone());
+            messages.extend([welcome_message, invitation]);
+        }
+        let effect = CommitEffect::Add { identities: identities.to_vec(), share };
+        self.publish_commit(group_id, messages, effect).await
+    }
+
+    /// One line per listed identity, in file order, under the totals.
+    fn show_invite_report(&mut self, group_name: &str, listed: &[String], outcomes: &HashMap<String, Outcome>) {
+        let batches = outcomes.values().filter_map(|outcome| match outcome {
+            Outcome::Added(batch) => Some(*batch),
+            _ => None,
+        });
+        let batch_count = batches.max().unwrap_or_default();
+        let count = |wanted: fn(&Outcome) -> bool| outcomes.values().filter(|outcome| wanted(outcome)).count();
+        let added = count(|outcome| matches!(outcome, Outcome::Added(_)));
+        let skipped = count(|outcome| matches!(outcome, Outcome::Skipped(_)));
+        let failed = count(|outcome| matches!(outcome, Outcome::Failed(_)));
+        let summary = format!("Invited {} to {} in {} commit(s); {} skipped, {} failed", added, group_name, batch_count, skipped, failed);
+        let mut lines = vec![summary.clone(), String::new()];
+        lines.extend(listed.iter().map(|identity| match outcomes.get(identity) {
+            Some(Outcome::Added(batch)) => format!("✓ {}: added in batch {}", identity, batch),
+            Some(Outcome::Skipped(reason)) => format!("- {}: skipped, {}", identity, reason),
+            Some(Outcome::Failed(reason)) => format!("✗ {}: {}", identity, reason),
+            None => format!("? {}: no result", identity),
+        }));
+        self.record_event(summary.clone());
+        self.set_status(summary);
+        self.show_popup("Invite from file", lines);
+    }
+}
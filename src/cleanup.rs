@@ -0,0 +1,226 @@
+use anyhow::Result;
+use chrono::{DateTime, Local};
+use crossterm::event::KeyCode;
+use mls_client_core::archive::{self, ARCHIVE_DIR, PAGE_SIZE};
+use mls_client_core::config::GroupFolder;
+use mls_client_core::netstats::format_bytes;
+use ratatui::{
+    layout::Rect,
+    style::{Modifier, Style},
+    widgets::{Block, Borders, Clear, List, ListItem, ListState},
+    Frame,
+};
+
+use crate::{App, AppScreen};
+
+/// Folder archived groups are moved into, created collapsed.
+const ARCHIVE_FOLDER: &str = "Archive";
+
+/// A group quiet for longer than the cutoff, with the storage it takes up.
+pub struct StaleGroup {
+    pub group_id: String,
+    pub name: String,
+    /// `None` if nothing was ever recorded
+    pub last_activity: Option<DateTime<Local>>,
+    /// Estimated bytes of its loaded timeline, which `history.json` holds too
+    pub in_memory: usize,
+    /// Bytes of its pages under `archive/`
+    pub on_disk: u64,
+    pub archived: bool,
+}
+
+impl StaleGroup {
+    pub fn describe(&self) -> String {
+        let quiet = match self.last_activity {
+            Some(at) => format!("quiet for {} days", (Local::now() - at).num_days()),
+            None => "no recorded activity".to_string(),
+        };
+        format!(
+            "{}{}: {}, {} loaded, {} on disk",
+            self.name,
+            if self.archived { " [archived]" } else { "" },
+            quiet,
+            format_bytes(self.in_memory as u64),
+            format_bytes(self.on_disk)
+        )
+    }
+}
+
+/// The `cleanup` view: groups quiet for more than `days`, longest quiet first.
+pub struct Cleanup {
+    pub days: u64,
+    pub groups: Vec<StaleGroup>,
+    pub state: ListState,
+}
+
+impl App {
+    /// `cleanup [days]` opens the view; `cleanup archive <group>` archives one group.
+    pub(crate) async fn cleanup_command(&mut self, parts: &[&str]) -> Result<()> {
+        match parts {
+            [] => self.open_cleanup(self.config.inactive_group_days, 0).await,
+            ["archive", group] => match self.find_group_id(group) {
+                Some(group_id) => self.archive_group(&group_id).await?,
+                None => self.set_warning(format!("No group named {}", group)),
+            },
+            [days] => match days.parse() {
+                Ok(days) => self.open_cleanup(days, 0).await,
+                Err(_) => self.set_warning("Usage: cleanup [days] | cleanup archive <group>"),
+            },
+            _ => self.set_warning("Usage: cleanup [days] | cleanup archive <group>"),
+        }
+        Ok(())
+    }
+
+    /// Groups with no message, member activity or commit in the last `days`, from the history
+    /// metadata, with what each takes up in memory and on disk.
+    async fn stale_groups(&self, days: u64) -> Vec<StaleGroup> {
+        // Capped at a century so the subtraction cannot overflow
+        let cutoff = Local::now() - chrono::Duration::days(days.min(36_500) as i64);
+        let archive_dir = self.data_path(ARCHIVE_DIR);
+        let mut stale = Vec::new();
+        for group in self.groups.values() {
+            let last_activity = group.last_activity();
+            if last_activity.is_some_and(|at| at >= cutoff) {
+                continue;
+            }
+            let on_disk = if group.archived_pages > 0 { archive::group_size(&archive_dir, &group.id).await } else { 0 };
+            stale.push(StaleGroup {
+                group_id: group.id.clone(),
+                name: group.name.clone(),
+                last_activity,
+                in_memory: group.messages.iter().map(archive::footprint).sum(),
+                on_disk,
+                archived: self.config.folders.iter().any(|folder| folder.name == ARCHIVE_FOLDER && folder.groups.contains(&group.id)),
+            });
+        }
+        stale.sort_by_key(|group| group.last_activity);
+        stale
+    }
+
+    /// Show the view with row `selected` highlighted, or say that every group is in use.
+    async fn open_cleanup(&mut self, days: u64, selected: usize) {
+        let groups = self.stale_groups(days).await;
+        if groups.is_empty() {
+            self.cleanup = None;
+            self.screen = AppScreen::Main;
+            self.set_status(format!("Every group had activity in the last {} days", days));
+            return;
+        }
+        let mut state = ListState::default();
+        state.select(Some(selected.min(groups.len() - 1)));
+        let loaded: usize = groups.iter().map(|group| group.in_memory).sum();
+        let on_disk: u64 = groups.iter().map(|group| group.on_disk).sum();
+        self.set_status(format!(
+            "{} group(s) quiet for more than {} days, taking {} loaded and {} on disk",
+            groups.len(),
+            days,
+            format_bytes(loaded as u64),
+            format_bytes(on_disk)
+        ));
+        self.cleanup = Some(Cleanup { days, groups, state });
+        self.screen = AppScreen::Cleanup;
+    }
+
+    /// Up/Down pick a group; `a` archives it, `l` and `f` leave or forget it after the usual
+    /// confirmation.
+    pub(crate) async fn handle_cleanup_input(&mut self, key: KeyCode) -> Result<()> {
+        let Some(cleanup) = &mut self.cleanup else {
+            self.screen = AppScreen::Main;
+            return Ok(());
+        };
+        let count = cleanup.groups.len();
+        let selected = cleanup.state.selected().unwrap_or(0);
+        let days = cleanup.days;
+        let Some(group_id) = cleanup.groups.get(selected).map(|group| group.group_id.clone()) else {
+            self.close_cleanup();
+            return Ok(());
+        };
+        match key {
+            KeyCode::Up => cleanup.state.select(Some(if selected > 0 { selected - 1 } else { count - 1 })),
+            KeyCode::Down => cleanup.state.select(Some((selected + 1) % count)),
+            KeyCode::Char('a') => {
+                self.archive_group(&group_id).await?;
+                self.open_cleanup(days, selected).await;
+            }
+            KeyCode::Char('l') => {
+                self.close_cleanup();
+                self.confirm_leave(&group_id).await?;
+            }
+            KeyCode::Char('f') => {
+                self.close_cleanup();
+                self.confirm_forget(&group_id).await?;
+            }
+            KeyCode::Esc | KeyCode::Char('q') => self.close_cleanup(),
+            _ => {}
+        }
+        Ok(())
+    }
+
+    fn close_cleanup(&mut self) {
+        self.cleanup = None;
+        self.screen = AppScreen::Main;
+    }
+
+    /// Move `group_id` into the Archive folder and its older messages to disk. As under the
+    /// memory budget, the newest page stays loaded, and the active group is not trimmed.
+    pub(crate) async fn archive_group(&mut self, group_id: &str) -> Result<()> {
+        let Some(name) = self.groups.get(group_id).map(|group| group.name.clone()) else {
+            return Ok(());
+        };
+        for folder in &mut self.config.folders {
+            folder.groups.retain(|id| id != group_id);
+        }
+        match self.config.folders.iter_mut().find(|folder| folder.name == ARCHIVE_FOLDER) {
+            Some(folder) => folder.groups.push(group_id.to_string()),
+            None => self.config.folders.push(GroupFolder {
+                name: ARCHIVE_FOLDER.to_string(),
+                collapsed: true,
+                groups: vec![group_id.to_string()],
+            }),
+        }
+        let mut moved = 0;
+        if self.active_group.as_deref() != Some(group_id) {
+            let archive_dir = self.data_path(ARCHIVE_DIR);
+            while self.groups.get(group_id).is_some_and(|group| group.messages.len() >= 2 * PAGE_SIZE) {
+                if let Err(e) = self.page_out(&archive_dir, group_id).await {
+                    self.record_event(format!("Failed to move older messages of {} to disk: {}", name, e));
+                    break;
+                }
+                moved += PAGE_SIZE;
+            }
+        }
+        if moved > 0 {
+            self.save_history().await;
+        }
+        self.set_status(match moved {
+            0 => format!("Archived {} to the {} folder", name, ARCHIVE_FOLDER),
+            moved => format!("Archived {} to the {} folder and moved {} older message(s) to disk", name, ARCHIVE_FOLDER, moved),
+        });
+        let event = self.status_text().to_string();
+        self.record_event(event);
+        if let Some(active) = self.active_group.clone() {
+            self.sync_group_selection(&active);
+        }
+        Ok(self.save_config().await?)
+    }
+
+    pub(crate) fn render_cleanup(&mut self, f: &mut Frame) {
+        let Some(cleanup) = &mut self.cleanup else {
+            return;
+        };
+        let area = f.size();
+        let cleanup_area = Rect {
+            x: area.width / 8,
+            y: area.height / 8,
+            width: area.width * 3 / 4,
+            height: area.height * 3 / 4,
+        };
+        let items: Vec<ListItem> = cleanup.groups.iter().map(|group| ListItem::new(group.describe())).collect();
+        let title = format!("Quiet for {}+ days (a: archive, l: leave, f: forget, Esc: close)", cleanup.days);
+        let list = List::new(items)
+            .block(Block::default().borders(Borders::ALL).title(title))
+            .highlight_style(Style::default().add_modifier(Modifier::REVERSED));
+        f.render_widget(Clear, cleanup_area);
+        f.render_stateful_widget(list, cleanup_area, &mut cleanup.state);
+    }
+}
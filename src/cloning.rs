@@ -0,0 +1,88 @@
+use anyhow::Result;
+use chrono::Local;
+use mls_client_core::errors;
+use mls_client_core::Onboarding;
+
+use crate::{App, KeyPackagePurpose};
+
+impl App {
+    /// `clone <group> <new_name>`: create a group with `group`'s wire policy, mode, key
+    /// rotation policy, onboarding message, tag and text direction, put it in the same folder
+    /// with the same per-group settings, and invite everyone else in `group`.
+    pub(crate) async fn clone_group(&mut self, wanted: &str, new_name: &str) -> Result<()> {
+        let Some(source) = self.find_group_id(wanted).and_then(|id| self.groups.get(&id)) else {
+            self.set_warning(format!("No group named {}", wanted));
+            return Ok(());
+        };
+        let source_id = source.id.clone();
+        let source_name = source.name.clone();
+        let policy = source.wire_policy;
+        let mode = source.mode;
+        let rotation_policy = source.rotation_policy;
+        let onboarding = source.onboarding.as_ref().map(|onboarding| onboarding.text.clone());
+        let tag = source.tag.clone();
+        let text_direction = source.text_direction;
+        let invitees: Vec<String> = source.members.iter().filter(|member| **member != self.config.username).cloned().collect();
+
+        self.create_group(new_name, policy, mode).await?;
+        let Some(group_id) = self.active_group.clone() else {
+            return Ok(());
+        };
+        let username = self.config.username.clone();
+        if let Some(group) = self.groups.get_mut(&group_id) {
+            group.rotation_policy = rotation_policy;
+            // Set by us in the new group, so invitees receive it like any onboarding message
+            group.onboarding = onboarding.map(|text| Onboarding { text, set_by: username, set_at: Local::now() });
+            group.onboarding_pinned = group.onboarding.is_some();
+            group.tag = tag;
+            group.text_direction = text_direction;
+        }
+        self.copy_group_settings(&source_id, &group_id);
+        self.save_history().await;
+        if let Err(e) = self.save_config().await {
+            self.record_event(format!("Cloned {}, but failed to save its settings: {}", source_name, e));
+        }
+
+        let status = if invitees.is_empty() {
+            format!("Cloned {} as {}; it had no other members to invite", source_name, new_name)
+        } else if !self.network_client.is_connected() {
+            self.report_error(&errors::NOT_CONNECTED, format!("cloned {} as {}, but invited no one", source_name, new_name));
+            return Ok(());
+        } else {
+            for identity in &invitees {
+                self.request_key_package(identity, KeyPackagePurpose::Invite { group_id: group_id.clone(), share: None });
+            }
+            format!("Cloned {} as {}; inviting {}", source_name, new_name, invitees.join(", "))
+        };
+        self.set_status(status);
+        let event = self.status_text().to_string();
+        self.record_event(event);
+        Ok(())
+    }
+
+    /// Give `to` the folder and the per-group entries of config.json that `from` has.
+    fn copy_group_settings(&mut self, from: &str, to: &str) {
+        let config = &mut self.config;
+        if let Some(folder) = config.folders.iter_mut().find(|folder| folder.groups.iter().any(|id| id == from)) {
+            folder.groups.push(to.to_string());
+        }
+        if let Some(translation) = &mut config.translation {
+            if translation.auto_groups.iter().any(|id| id == from) {
+                translation.auto_groups.push(to.to_string());
+            }
+        }
+        if let Some(speech) = &mut config.speech {
+            if let Some(mode) = speech.groups.get(from).copied() {
+                speech.groups.insert(to.to_string(), mode);
+            }
+        }
+        if let Some(previews) = &mut config.link_previews {
+            if previews.groups.contains(from) {
+                previews.groups.insert(to.to_string());
+            }
+        }
+        if let Some(sound) = config.sounds.groups.get(from).cloned() {
+            config.sounds.groups.insert(to.to_string(), sound);
+        }
+    }
+}
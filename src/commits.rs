@@ -0,0 +1,341 @@
+use anyhow::Result;
+use mls_client_core::errors::{self, AuditError, NetworkError, PipelineError};
+use mls_client_core::mls_client::CommitSummary;
+use mls_client_core::network::NetworkMessage;
+use mls_client_core::notify::{Notification, NotifyEvent};
+use mls_client_core::pipeline::{self, CommitEffect, HeldCommit};
+use mls_client_core::rotation::RotationStatus;
+
+use crate::{App, AppScreen, KeyPackagePurpose, Popup, PopupAction};
+
+impl App {
+    /// Deliver a staged commit of ours and merge it, or in debug mode hold it for review.
+    /// `true` once merged and `effect` applied.
+    pub(crate) async fn publish_commit(&mut self, group_id: &str, messages: Vec<NetworkMessage>, effect: CommitEffect) -> Result<bool> {
+        let held = HeldCommit { messages, effect };
+        if !self.config.debug_mode {
+            return self.deliver_commit(group_id, held).await;
+        }
+        self.held_commits.insert(group_id.to_string(), held);
+        self.preview_commit(group_id);
+        Ok(false)
+    }
+
+    pub(crate) async fn deliver_commit(&mut self, group_id: &str, held: HeldCommit) -> Result<bool> {
+        let Some(group) = self.groups.get(group_id) else {
+            return Ok(false);
+        };
+        let delivered = pipeline::deliver_commit(&mut self.mls_client, &mut self.network_client, group, &held.messages).await;
+        let summary = match delivered {
+            Ok(summary) => summary,
+            Err(e @ PipelineError::Deleted(_)) => {
+                self.set_error(format!("Cannot {}: {}", held.effect.describe(), e));
+                return Ok(false);
+            }
+            Err(PipelineError::Network(e)) => {
+                self.set_error(format!("Failed to {}: {}", held.effect.describe(), e));
+                return Ok(false);
+            }
+            Err(e) => return Err(e.into()),
+        };
+        if let Some(summary) = summary {
+            self.log_commit(group_id, &summary);
+        }
+        self.quarantine_retry.insert(group_id.to_string());
+        self.apply_commit_effect(group_id, held.effect).await;
+        Ok(true)
+    }
+
+    pub(crate) async fn apply_commit_effect(&mut self, group_id: &str, effect: CommitEffect) {
+        match effect {
+            CommitEffect::Add { identities, share } => {
+                self.save_history().await;
+                self.set_status(format!("Invited {} to the group", identities.join(", ")));
+                self.record_event(format!("Invited {}", identities.join(", ")));
+                let onboarding = self.groups.get(group_id).and_then(|group| group.onboarding.clone());
+                for identity in &identities {
+                    if let Some(share) = share {
+                        if let Err(e) = self.share_history(group_id, identity, share).await {
+                            self.set_warning(format!("Invited {}, but failed to share history: {}", identity, e));
+                        }
+                    }
+                    if onboarding.is_some() {
+                        if let Err(e) = self.send_onboarding(group_id, &onboarding, Some(identity)).await {
+                            self.set_warning(format!("Invited {}, but failed to deliver the onboarding message: {}", identity, e));
+                        }
+                    }
+                }
+            }
+            CommitEffect::Remove { identities } => {
+                if let Some(group) = self.groups.get(group_id) {
+                    let removed = format!("Removed {} from {}", identities.join(", "), group.name);
+                    self.set_status(removed.clone());
+                    self.record_event(removed);
+                }
+                self.save_history().await;
+            }
+            CommitEffect::Relax { identity } => {
+                self.set_status(format!("Relaxed group requirements for {}", identity));
+                self.record_event(format!("Relaxed group requirements for {}", identity));
+            }
+            CommitEffect::Update => {
+                let epoch = self.mls_client.epoch(group_id).unwrap_or_default();
+                if let Some(group) = self.groups.get_mut(group_id) {
+                    group.track_epoch(epoch);
+                    let rotated = format!("Rotated the keys of {} (epoch {})", group.name, epoch);
+                    self.set_status(rotated.clone());
+                    self.record_event(rotated);
+                }
+                self.save_history().await;
+            }
+            CommitEffect::Mode { mode } => {
+                let name = self.groups.get(group_id).map_or(group_id.to_string(), |group| group.name.clone());
+                self.set_status(format!("{} is now in {} mode", name, mode.as_str()));
+                self.save_history().await;
+            }
+        }
+    }
+
+    /// Show what the group's pending commit of ours would change, offering to send or abort it.
+    pub(crate) fn preview_commit(&mut self, group_id: &str) {
+        let Some(summary) = self.mls_client.pending_commit(group_id) else {
+            self.set_status("No pending commit in this group");
+            return;
+        };
+        let name = self.groups.get(group_id).map(|group| group.name.clone()).unwrap_or_else(|| group_id.to_string());
+        let list = |names: &[String]| if names.is_empty() { "none".to_string() } else { names.join(", ") };
+        let mut lines = vec![
+            format!("Committer:   {}", summary.committer),
+            format!("New epoch:   {}", summary.epoch),
+            format!("Adds:        {}", list(&summary.adds)),
+            format!("Removes:     {}", list(&summary.removes)),
+            format!("Updates:     {}", list(&summary.updates)),
+            format!("PSKs:        {}", summary.psks),
+            format!("Extensions:  {}", if summary.context_extensions { "changed" } else { "unchanged" }),
+            format!("Update path: {}", if summary.path { "yes" } else { "no" }),
+        ];
+        let mut actions = Vec::new();
+        match self.held_commits.get(group_id) {
+            Some(held) => {
+                lines.push(String::new());
+                lines.push(format!("Held for review: {}. Nothing has been sent yet.", held.effect.describe()));
+                actions.push(PopupAction::SendCommit { group_id: group_id.to_string() });
+            }
+            None => lines.push("Not held here; it is merged once delivered.".to_string()),
+        }
+        actions.push(PopupAction::AbortCommit { group_id: group_id.to_string() });
+        self.set_status(format!("Pending commit in {}: send or abort it before the next change", name));
+        self.popup = Some(Popup { title: format!("Pending commit: {}", name), lines, actions });
+        self.screen = AppScreen::Popup;
+    }
+
+    pub(crate) async fn send_held_commit(&mut self, group_id: &str) -> Result<()> {
+        let Some(held) = self.held_commits.remove(group_id) else {
+            self.set_status("No commit held for review in this group");
+            return Ok(());
+        };
+        if !self.network_client.is_connected() {
+            self.held_commits.insert(group_id.to_string(), held);
+            self.report_error(&errors::NOT_CONNECTED, "cannot send the held commit".to_string());
+            return Ok(());
+        }
+        self.deliver_commit(group_id, held).await?;
+        Ok(())
+    }
+
+    /// Discard our pending commit so the group stays at its current epoch.
+    pub(crate) fn abort_held_commit(&mut self, group_id: &str) -> Result<()> {
+        let held = self.held_commits.remove(group_id);
+        if held.is_none() && self.mls_client.pending_commit(group_id).is_none() {
+            self.set_status("No pending commit in this group");
+            return Ok(());
+        }
+        self.mls_client.clear_pending_commit(group_id)?;
+        let aborted = match held {
+            Some(held) => format!("Aborted commit to {}", held.effect.describe()),
+            None => "Aborted pending commit".to_string(),
+        };
+        self.set_status(aborted.clone());
+        self.record_event(aborted);
+        Ok(())
+    }
+
+    /// In debug mode, log what a received commit changed. If it won the epoch over a pending
+    /// commit of ours, queue our change to be re-applied on the new epoch.
+    pub(crate) fn note_commit(&mut self, group_id: &str, summary: &CommitSummary, superseded: Option<CommitSummary>) {
+        self.quarantine_retry.insert(group_id.to_string());
+        self.log_commit(group_id, summary);
+        if self.config.debug_mode {
+            let line = format!("Commit {}", summary.describe());
+            self.set_status(line.clone());
+            self.record_event(line);
+        }
+        if superseded.is_none() {
+            return;
+        }
+        let notice = match self.held_commits.remove(group_id) {
+            Some(held) => {
+                let notice = format!(
+                    "{}'s commit took epoch {} first; re-applying your change to {} on it",
+                    summary.committer, summary.epoch, held.effect.describe()
+                );
+                self.pending_rebases.push((group_id.to_string(), held.effect));
+                notice
+            }
+            None => format!("{}'s commit took epoch {} first; your pending commit was discarded", summary.committer, summary.epoch),
+        };
+        self.set_status(notice.clone());
+        self.record_event(notice);
+    }
+
+    /// Record a merged commit with `pipeline::record_commit`, reporting it if the membership
+    /// log refused it and announcing a mode switch it made.
+    pub(crate) fn log_commit(&mut self, group_id: &str, summary: &CommitSummary) {
+        let Some(group) = self.groups.get_mut(group_id) else {
+            return;
+        };
+        let mode = group.mode;
+        if let Err(e) = pipeline::record_commit(&self.mls_client, group, summary, &self.audit_key) {
+            let name = group.name.clone();
+            self.unlogged_commit(&name, summary, e);
+        }
+        let Some(group) = self.groups.get(group_id).filter(|group| group.mode != mode) else {
+            return;
+        };
+        let changed = format!("{} switched {} to {} mode", summary.committer, group.name, group.mode.as_str());
+        self.set_status(changed.clone());
+        self.record_event(changed);
+    }
+
+    pub(crate) fn sync_group_state(&mut self, group_id: &str) {
+        if let Some(group) = self.groups.get_mut(group_id) {
+            pipeline::sync_group_state(&self.mls_client, group);
+        }
+    }
+
+    fn unlogged_commit(&mut self, group_name: &str, summary: &CommitSummary, error: AuditError) {
+        let problem = format!("Did not log {}'s commit to epoch {} in {}: {}", summary.committer, summary.epoch, group_name, error);
+        self.set_warning(problem.clone());
+        self.record_event(problem);
+    }
+
+    /// Stage `effect` again after a competing commit voided ours, unless that commit already did it.
+    pub(crate) async fn rebase_commit(&mut self, group_id: &str, effect: CommitEffect) -> Result<()> {
+        match &effect {
+            CommitEffect::Add { identities, share } => {
+                let remaining: Vec<&String> = identities
+                    .iter()
+                    .filter(|identity| self.mls_client.find_member(group_id, identity).is_none())
+                    .collect();
+                if remaining.is_empty() {
+                    self.set_status(format!("{} already added by the competing commit", identities.join(", ")));
+                    return Ok(());
+                }
+                for identity in remaining {
+                    let purpose = KeyPackagePurpose::Invite { group_id: group_id.to_string(), share: *share };
+                    self.request_key_package(identity, purpose);
+                }
+            }
+            CommitEffect::Remove { identities } => {
+                let remaining: Vec<String> = identities
+                    .iter()
+                    .filter(|identity| self.mls_client.find_member(group_id, identity).is_some())
+                    .cloned()
+                    .collect();
+                if remaining.is_empty() {
+                    self.set_status(format!("{} already removed by the competing commit", identities.join(", ")));
+                    return Ok(());
+                }
+                self.kick_members(group_id, remaining).await?;
+            }
+            CommitEffect::Relax { identity } => self.relax_and_add(group_id, identity).await?,
+            CommitEffect::Update => {
+                self.set_status("The competing commit already moved the group to a new epoch");
+                return Ok(());
+            }
+            CommitEffect::Mode { mode } => {
+                if self.groups.get(group_id).is_some_and(|group| group.mode == *mode) {
+                    self.set_status(format!("The competing commit already switched to {} mode", mode.as_str()));
+                    return Ok(());
+                }
+                self.commit_group_mode(group_id, *mode).await?;
+            }
+        }
+        self.record_event(format!("Rebased your change to {}", effect.describe()));
+        Ok(())
+    }
+
+    /// Turn a group we were removed from read-only and offer to forget or rejoin it.
+    pub(crate) fn mark_removed(&mut self, group_id: &str, by: &str) {
+        let Some(group) = self.groups.get_mut(group_id) else {
+            return;
+        };
+        group.removed = true;
+        let name = group.name.clone();
+        let removed = format!("{} removed you from {}", by, name);
+        self.record_event(removed.clone());
+        self.notify(Notification { event: NotifyEvent::Invite, group_id: Some(group_id.to_string()), text: removed.clone() });
+        self.set_status(removed);
+        self.popup = Some(Popup {
+            title: format!("Removed from {}", name),
+            lines: vec![
+                format!("{} removed you from {}.", by, name),
+                "The timeline stays readable, but you can no longer send or receive messages in it.".to_string(),
+            ],
+            actions: vec![
+                PopupAction::Forget { group_id: group_id.to_string() },
+                PopupAction::Rejoin { group_id: group_id.to_string() },
+            ],
+        });
+        self.screen = AppScreen::Popup;
+    }
+
+    /// Move every group to a new signature key, resuming a partially completed rotation.
+    pub(crate) async fn rotate_identity(&mut self) -> Result<()> {
+        let rotated = pipeline::rotate_identity(
+            &mut self.mls_client,
+            &mut self.network_client,
+            &self.config.username,
+            &mut self.groups,
+            &self.audit_key,
+        )
+        .await;
+        let report = match rotated {
+            Ok(report) => report,
+            Err(PipelineError::Network(NetworkError::NotConnected)) => {
+                self.set_error("Cannot rotate identity: not connected to MLS service");
+                return Ok(());
+            }
+            Err(e @ PipelineError::NoGroups) => {
+                self.set_error(format!("Cannot rotate identity: {}", e));
+                return Ok(());
+            }
+            Err(e) => return Err(e.into()),
+        };
+        for (group_id, summary, e) in report.unlogged {
+            let name = self.groups.get(&group_id).map_or(group_id.clone(), |group| group.name.clone());
+            self.unlogged_commit(&name, &summary, e);
+        }
+
+        let lines: Vec<String> = report.progress
+            .iter()
+            .map(|(group_id, status)| {
+                let name = self.groups.get(group_id).map(|g| g.name.as_str()).unwrap_or(group_id.as_str());
+                match status {
+                    RotationStatus::Pending => format!("…  {}: pending", name),
+                    RotationStatus::Committed { epoch } => format!("✔  {}: updated (epoch {})", name, epoch),
+                    RotationStatus::Failed(e) => format!("✘  {}: {}", name, e),
+                }
+            })
+            .collect();
+        if report.retired {
+            self.set_status("Identity rotated: new signature key active in all groups, old key retired");
+            self.record_event("Rotated signature key");
+        } else {
+            let remaining = report.progress.values().filter(|status| !matches!(status, RotationStatus::Committed { .. })).count();
+            self.set_status(format!("Identity rotation incomplete: {} group(s) pending. Run 'rotate-identity' again to retry.", remaining));
+        }
+        self.show_popup("Identity rotation", lines);
+        Ok(())
+    }
+}
@@ -0,0 +1,167 @@
+use anyhow::Result;
+use crossterm::event::KeyCode;
+use mls_client_core::config::Confirmable;
+use ratatui::{
+    layout::Rect,
+    widgets::{Block, Borders, Clear, Paragraph, Wrap},
+    Frame,
+};
+
+use crate::{App, AppScreen};
+
+/// A destructive action held until the user confirms it.
+#[derive(Debug, Clone)]
+pub enum ConfirmedAction {
+    Kick { group_id: String, identities: Vec<String> },
+    Leave { group_id: String },
+    Forget { group_id: String },
+    /// Only opens the typed WIPE/YES guard, which still applies
+    Wipe,
+    Redact { group_id: String, message_id: String },
+}
+
+impl ConfirmedAction {
+    pub fn kind(&self) -> Confirmable {
+        match self {
+            ConfirmedAction::Kick { .. } => Confirmable::Kick,
+            ConfirmedAction::Leave { .. } => Confirmable::Leave,
+            ConfirmedAction::Forget { .. } => Confirmable::Forget,
+            ConfirmedAction::Wipe => Confirmable::Wipe,
+            ConfirmedAction::Redact { .. } => Confirmable::Redact,
+        }
+    }
+}
+
+pub struct Confirmation {
+    pub action: ConfirmedAction,
+    pub prompt: String,
+    pub details: Vec<String>,
+}
+
+impl App {
+    /// Ask before running `action`, unless the user chose not to be asked for its kind.
+    pub(crate) async fn confirm(&mut self, action: ConfirmedAction, prompt: String, details: Vec<String>) -> Result<()> {
+        if self.config.dont_ask.contains(&action.kind()) {
+            return self.run_confirmed(action).await;
+        }
+        self.set_status(format!("{} (y/n)", prompt));
+        self.confirmation = Some(Confirmation { action, prompt, details });
+        self.screen = AppScreen::Confirm;
+        Ok(())
+    }
+
+    /// `y` runs the action, `a` runs it and stops asking for its kind, `n` or Esc cancels.
+    /// Enter does nothing, so a stray keypress never confirms.
+    pub(crate) async fn handle_confirm_input(&mut self, key: KeyCode) -> Result<()> {
+        let remember = match key {
+            KeyCode::Char('y') | KeyCode::Char('Y') => false,
+            KeyCode::Char('a') | KeyCode::Char('A') => true,
+            KeyCode::Char('n') | KeyCode::Char('N') | KeyCode::Esc => {
+                self.confirmation = None;
+                self.screen = AppScreen::Main;
+                self.set_status("Cancelled");
+                return Ok(());
+            }
+            _ => return Ok(()),
+        };
+        self.screen = AppScreen::Main;
+        let Some(confirmation) = self.confirmation.take() else {
+            return Ok(());
+        };
+        let kind = confirmation.action.kind();
+        self.run_confirmed(confirmation.action).await?;
+        if remember {
+            self.config.dont_ask.insert(kind);
+            self.save_config().await?;
+            self.set_status(format!("No longer asking before {}; 'config set dont_ask []' asks again", kind.as_str()));
+        }
+        Ok(())
+    }
+
+    pub(crate) async fn confirm_kick(&mut self, group_id: &str, identities: Vec<String>) -> Result<()> {
+        let Some(group) = self.groups.get(group_id) else {
+            return Ok(());
+        };
+        let prompt = match identities.as_slice() {
+            [identity] => format!("Remove {} from {}?", identity, group.name),
+            _ => format!("Remove {} members from {}?", identities.len(), group.name),
+        };
+        let details = vec![
+            format!("Removes {} in one commit.", identities.join(", ")),
+            "They keep what they already received; adding them back needs a new invite.".to_string(),
+        ];
+        self.confirm(ConfirmedAction::Kick { group_id: group_id.to_string(), identities }, prompt, details).await
+    }
+
+    pub(crate) async fn confirm_leave(&mut self, group_id: &str) -> Result<()> {
+        let Some(group) = self.groups.get(group_id) else {
+            return Ok(());
+        };
+        let prompt = format!("Leave {}?", group.name);
+        let details = vec![
+            "Asks the other members to remove you; the next commit by any of them does.".to_string(),
+            format!("The group and its {} message(s) are then deleted from this device.", group.messages.len()),
+            "Coming back needs a new invite.".to_string(),
+        ];
+        self.confirm(ConfirmedAction::Leave { group_id: group_id.to_string() }, prompt, details).await
+    }
+
+    pub(crate) async fn confirm_forget(&mut self, group_id: &str) -> Result<()> {
+        let Some(group) = self.groups.get(group_id) else {
+            return Ok(());
+        };
+        let prompt = format!("Forget {}?", group.name);
+        let mut details = vec![
+            format!("Deletes the group, its keys and its {} message(s) from this device.", group.messages.len()),
+        ];
+        if !group.is_read_only() {
+            details.push("The other members are not told; use 'leave' to leave the group itself.".to_string());
+        }
+        self.confirm(ConfirmedAction::Forget { group_id: group_id.to_string() }, prompt, details).await
+    }
+
+    pub(crate) async fn confirm_wipe(&mut self) -> Result<()> {
+        let details = vec![
+            "Destroys the keystore, history and config on this device.".to_string(),
+            "You will still be asked to type WIPE and then YES.".to_string(),
+        ];
+        self.confirm(ConfirmedAction::Wipe, "Wipe this device?".to_string(), details).await
+    }
+
+    async fn run_confirmed(&mut self, action: ConfirmedAction) -> Result<()> {
+        match action {
+            ConfirmedAction::Kick { group_id, identities } => self.kick_members(&group_id, identities).await?,
+            ConfirmedAction::Leave { group_id } => self.leave_group(&group_id).await?,
+            ConfirmedAction::Forget { group_id } => self.forget_group(&group_id).await,
+            ConfirmedAction::Wipe => self.start_wipe(),
+            ConfirmedAction::Redact { group_id, message_id } => self.redact_confirmed(&group_id, &message_id).await?,
+        }
+        Ok(())
+    }
+
+    pub(crate) fn render_confirm(&self, f: &mut Frame) {
+        let Some(confirmation) = &self.confirmation else {
+            return;
+        };
+        let area = f.size();
+        let height = (confirmation.details.len() as u16 + 6).min(area.height);
+        let confirm_area = Rect {
+            x: area.width / 6,
+            y: area.height.saturating_sub(height) / 2,
+            width: area.width * 2 / 3,
+            height,
+        };
+
+        f.render_widget(Clear, confirm_area);
+        let mut lines = confirmation.details.clone();
+        lines.push(String::new());
+        lines.push(format!(
+            "y: yes   n/Esc: no   a: yes, and don't ask again before {}",
+            confirmation.action.kind().as_str()
+        ));
+        let paragraph = Paragraph::new(lines.join("\n"))
+            .block(Block::default().borders(Borders::ALL).title(confirmation.prompt.clone()))
+            .wrap(Wrap { trim: false });
+        f.render_widget(paragraph, confirm_area);
+    }
+}
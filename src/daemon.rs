@@ -0,0 +1,344 @@
+use anyhow::Result;
+use crossterm::{
+    event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers},
+    execute,
+    terminal::{self, disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
+};
+use ratatui::{backend::CrosstermBackend, layout::Rect, Terminal, TerminalOptions, Viewport};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::io::{self, Write};
+use std::os::unix::fs::PermissionsExt;
+use std::os::unix::process::CommandExt;
+use std::process::{Command, Stdio};
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+use tokio::net::{UnixListener, UnixStream};
+use tokio::sync::mpsc;
+
+use crate::App;
+
+/// Control socket the daemon listens on, next to the rest of the client's data.
+pub const CONTROL_SOCKET_PATH: &str = "mls-client.sock";
+
+/// How long `--attach` waits for a daemon it started to open the control socket.
+const DAEMON_STARTUP: Duration = Duration::from_secs(5);
+
+/// Run headless, keeping the delivery service connection and MLS state, for `--attach` to drive.
+pub fn requested() -> bool {
+    std::env::args().any(|arg| arg == "--daemon")
+}
+
+/// Attach this terminal to the daemon, starting one if none is running.
+pub fn attach_requested() -> bool {
+    std::env::args().any(|arg| arg == "--attach")
+}
+
+/// One JSON line from an attached terminal; the daemon answers with terminal output only.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum ControlRequest {
+    Attach { width: u16, height: u16 },
+    Resize { width: u16, height: u16 },
+    Key { key: RemoteKey, modifiers: u8 },
+}
+
+/// The keys the interface reacts to, in a form that crosses the socket.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum RemoteKey {
+    Char(char),
+    Enter,
+    Backspace,
+    Esc,
+    Tab,
+    BackTab,
+    Up,
+    Down,
+    Left,
+    Right,
+    Home,
+    End,
+    PageUp,
+    PageDown,
+    Delete,
+    F(u8),
+}
+
+impl RemoteKey {
+    fn from_code(code: KeyCode) -> Option<Self> {
+        Some(match code {
+            KeyCode::Char(c) => Self::Char(c),
+            KeyCode::Enter => Self::Enter,
+            KeyCode::Backspace => Self::Backspace,
+            KeyCode::Esc => Self::Esc,
+            KeyCode::Tab => Self::Tab,
+            KeyCode::BackTab => Self::BackTab,
+            KeyCode::Up => Self::Up,
+            KeyCode::Down => Self::Down,
+            KeyCode::Left => Self::Left,
+            KeyCode::Right => Self::Right,
+            KeyCode::Home => Self::Home,
+            KeyCode::End => Self::End,
+            KeyCode::PageUp => Self::PageUp,
+            KeyCode::PageDown => Self::PageDown,
+            KeyCode::Delete => Self::Delete,
+            KeyCode::F(n) => Self::F(n),
+            _ => return None,
+        })
+    }
+
+    fn code(self) -> KeyCode {
+        match self {
+            Self::Char(c) => KeyCode::Char(c),
+            Self::Enter => KeyCode::Enter,
+            Self::Backspace => KeyCode::Backspace,
+            Self::Esc => KeyCode::Esc,
+            Self::Tab => KeyCode::Tab,
+            Self::BackTab => KeyCode::BackTab,
+            Self::Up => KeyCode::Up,
+            Self::Down => KeyCode::Down,
+            Self::Left => KeyCode::Left,
+            Self::Right => KeyCode::Right,
+            Self::Home => KeyCode::Home,
+            Self::End => KeyCode::End,
+            Self::PageUp => KeyCode::PageUp,
+            Self::PageDown => KeyCode::PageDown,
+            Self::Delete => KeyCode::Delete,
+            Self::F(n) => KeyCode::F(n),
+        }
+    }
+}
+
+/// Collects what ratatui writes for one attached terminal and ships it on each flush.
+struct FrameWriter {
+    pending: Vec<u8>,
+    output: mpsc::UnboundedSender<Vec<u8>>,
+}
+
+impl Write for FrameWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.pending.extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        if !self.pending.is_empty() {
+            // A closed channel means the terminal detached; its reader reports that
+            let _ = self.output.send(std::mem::take(&mut self.pending));
+        }
+        Ok(())
+    }
+}
+
+/// A connected terminal; it gets a screen once it has said how big it is.
+struct Attached {
+    output: mpsc::UnboundedSender<Vec<u8>>,
+    screen: Option<Terminal<CrosstermBackend<FrameWriter>>>,
+}
+
+impl Attached {
+    /// Start drawing afresh at `width` x `height`, clearing whatever the terminal showed.
+    fn resize(&mut self, width: u16, height: u16) -> io::Result<()> {
+        let writer = FrameWriter { pending: Vec::new(), output: self.output.clone() };
+        let mut screen = Terminal::with_options(CrosstermBackend::new(writer), TerminalOptions {
+            viewport: Viewport::Fixed(Rect::new(0, 0, width, height)),
+        })?;
+        screen.clear()?;
+        self.screen = Some(screen);
+        Ok(())
+    }
+}
+
+/// Serve the interface on the control socket until a `quit`; terminals come and go meanwhile.
+pub async fn run(app: &mut App) -> Result<()> {
+    let (listener, owns_socket) = listen()?;
+    let (requests_tx, mut requests) = mpsc::unbounded_channel::<(u64, Option<ControlRequest>)>();
+    let mut attached: HashMap<u64, Attached> = HashMap::new();
+    let mut next_id = 0u64;
+    let mut ticks = tokio::time::interval(Duration::from_millis(250));
+    let mut last_sync = Instant::now();
+
+    while !app.should_quit {
+        tokio::select! {
+            accepted = listener.accept() => {
+                let (stream, _) = accepted?;
+                next_id += 1;
+                attached.insert(next_id, serve(next_id, stream, requests_tx.clone()));
+            }
+            Some((id, request)) = requests.recv() => match request {
+                Some(ControlRequest::Attach { width, height } | ControlRequest::Resize { width, height }) => {
+                    if let Some(terminal) = attached.get_mut(&id) {
+                        terminal.resize(width, height)?;
+                        app.handle_resize();
+                    }
+                }
+                Some(ControlRequest::Key { key, modifiers }) => {
+                    let modifiers = KeyModifiers::from_bits_truncate(modifiers);
+                    app.handle_key(KeyEvent::new(key.code(), modifiers)).await?;
+                }
+                None => {
+                    attached.remove(&id);
+                }
+            },
+            _ = ticks.tick() => app.tick(&mut last_sync).await?,
+        }
+
+        for terminal in attached.values_mut() {
+            if let Some(screen) = &mut terminal.screen {
+                screen.draw(|f| app.render(f))?;
+            }
+        }
+    }
+
+    app.send_delayed_now().await;
+    if owns_socket {
+        let _ = fs::remove_file(CONTROL_SOCKET_PATH);
+    }
+    Ok(())
+}
+
+/// The listening socket: the one systemd passed in when socket-activated, else our own.
+fn listen() -> Result<(UnixListener, bool)> {
+    let activated = std::env::var("LISTEN_PID").ok().and_then(|pid| pid.parse::<u32>().ok()) == Some(std::process::id())
+        && std::env::var("LISTEN_FDS").ok().and_then(|fds| fds.parse::<u32>().ok()).is_some_and(|fds| fds >= 1);
+    if activated {
+        use std::os::unix::io::FromRawFd;
+        // SAFETY: with LISTEN_PID naming this process, systemd passed its listening
+        // socket as the first inherited descriptor, 3, and nothing else owns it
+        let listener = unsafe { std::os::unix::net::UnixListener::from_raw_fd(3) };
+        listener.set_nonblocking(true)?;
+        return Ok((UnixListener::from_std(listener)?, false));
+    }
+
+    // The instance lock is already held, so a socket left here belongs to a dead daemon
+    let _ = fs::remove_file(CONTROL_SOCKET_PATH);
+    let listener = UnixListener::bind(CONTROL_SOCKET_PATH)?;
+    fs::set_permissions(CONTROL_SOCKET_PATH, fs::Permissions::from_mode(0o600))?;
+    Ok((listener, true))
+}
+
+/// Pump one connection: its requests into `requests`, tagged with `id`, and screen output back.
+fn serve(id: u64, stream: UnixStream, requests: mpsc::UnboundedSender<(u64, Option<ControlRequest>)>) -> Attached {
+    let (reader, mut writer) = stream.into_split();
+    let (output, mut frames) = mpsc::unbounded_channel::<Vec<u8>>();
+
+    tokio::spawn(async move {
+        let mut lines = BufReader::new(reader).lines();
+        while let Ok(Some(line)) = lines.next_line().await {
+            // Anything unparseable is dropped rather than ending the session
+            if let Ok(request) = serde_json::from_str(&line) {
+                if requests.send((id, Some(request))).is_err() {
+                    return;
+                }
+            }
+        }
+        let _ = requests.send((id, None));
+    });
+
+    tokio::spawn(async move {
+        while let Some(frame) = frames.recv().await {
+            if writer.write_all(&frame).await.is_err() {
+                break;
+            }
+        }
+    });
+
+    Attached { output, screen: None }
+}
+
+/// Run this terminal as a view of the daemon until it is detached with Ctrl-\ or the daemon quits.
+pub async fn attach() -> Result<()> {
+    let stream = connect().await?;
+    let (mut reader, mut writer) = stream.into_split();
+
+    enable_raw_mode()?;
+    execute!(io::stdout(), EnterAlternateScreen)?;
+    let result = drive(&mut reader, &mut writer).await;
+    disable_raw_mode()?;
+    execute!(io::stdout(), LeaveAlternateScreen)?;
+
+    match result? {
+        true => println!("Detached; the client keeps running. Run with --attach to return."),
+        false => println!("The client has exited."),
+    }
+    Ok(())
+}
+
+/// Forward keys and resizes while copying the daemon's output to the screen. Returns true when
+/// the user detached, false when the daemon closed the connection.
+async fn drive(reader: &mut tokio::net::unix::OwnedReadHalf, writer: &mut tokio::net::unix::OwnedWriteHalf) -> Result<bool> {
+    let (width, height) = terminal::size()?;
+    send(writer, &ControlRequest::Attach { width, height }).await?;
+
+    let mut buffer = vec![0u8; 16 * 1024];
+    let mut stdout = tokio::io::stdout();
+    loop {
+        tokio::select! {
+            read = reader.read(&mut buffer) => {
+                let read = read?;
+                if read == 0 {
+                    return Ok(false);
+                }
+                stdout.write_all(&buffer[..read]).await?;
+                stdout.flush().await?;
+            }
+            _ = tokio::time::sleep(Duration::from_millis(50)) => {
+                while event::poll(Duration::ZERO)? {
+                    match event::read()? {
+                        Event::Key(key) if key.kind == KeyEventKind::Press => {
+                            if key.code == KeyCode::Char('\\') && key.modifiers.contains(KeyModifiers::CONTROL) {
+                                return Ok(true);
+                            }
+                            if let Some(remote) = RemoteKey::from_code(key.code) {
+                                send(writer, &ControlRequest::Key { key: remote, modifiers: key.modifiers.bits() }).await?;
+                            }
+                        }
+                        // Windows reports the buffer size here; ask for the window's
+                        Event::Resize(..) => {
+                            let (width, height) = terminal::size()?;
+                            send(writer, &ControlRequest::Resize { width, height }).await?;
+                        }
+                        _ => {}
+                    }
+                }
+            }
+        }
+    }
+}
+
+async fn send(writer: &mut tokio::net::unix::OwnedWriteHalf, request: &ControlRequest) -> Result<()> {
+    let mut line = serde_json::to_vec(request)?;
+    line.push(b'\n');
+    writer.write_all(&line).await?;
+    Ok(())
+}
+
+/// Connect to the running daemon, or start one in the background and wait for its socket.
+async fn connect() -> Result<UnixStream> {
+    if let Ok(stream) = UnixStream::connect(CONTROL_SOCKET_PATH).await {
+        return Ok(stream);
+    }
+    // Its own process group, so closing this terminal does not take it down
+    Command::new(std::env::current_exe()?)
+        .arg("--daemon")
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .process_group(0)
+        .spawn()?;
+    let started = Instant::now();
+    loop {
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        if let Ok(stream) = UnixStream::connect(CONTROL_SOCKET_PATH).await {
+            return Ok(stream);
+        }
+        if started.elapsed() >= DAEMON_STARTUP {
+            return Err(anyhow::anyhow!(
+                "No daemon is listening on {} and one could not be started; run with --daemon to see why",
+                CONTROL_SOCKET_PATH
+            ));
+        }
+    }
+}
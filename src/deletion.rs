@@ -0,0 +1,76 @@
+use mls_client_core::notify::{Notification, NotifyEvent};
+use mls_client_core::GroupDeletion;
+
+use crate::{App, AppScreen, InputMode, Popup, PopupAction};
+
+impl App {
+    /// Apply a `group_deleted` notice: the group turns read-only, with its timeline kept, and
+    /// whatever was still queued for it is dropped instead of failing on every retry.
+    pub(crate) fn mark_deleted(&mut self, group_id: &str, content: &[u8]) {
+        let Some(group) = self.groups.get_mut(group_id) else {
+            return;
+        };
+        // Redelivered after a reconnect
+        if group.deleted.is_some() {
+            return;
+        }
+        let deletion = GroupDeletion::parse(content);
+        let described = format!("{} {}", group.name, deletion.describe());
+        group.deleted = Some(deletion);
+        let name = group.name.clone();
+
+        let mut unsent: Vec<String> = self.delayed_sends.iter().filter(|send| send.group_id == group_id).map(|send| send.message_id.clone()).collect();
+        self.delayed_sends.retain(|send| send.group_id != group_id);
+        if let Some(held) = self.held_messages.remove(group_id) {
+            unsent.extend(held.message.message_id);
+        }
+        if let Some(group) = self.groups.get_mut(group_id) {
+            group.messages.retain(|message| !unsent.contains(&message.id));
+        }
+        if self.held_commits.remove(group_id).is_some() {
+            let _ = self.mls_client.clear_pending_commit(group_id);
+        }
+        self.pending_rebases.retain(|(pending, _)| pending != group_id);
+        self.transfers.uploads.retain(|_, upload| upload.group_id != group_id);
+
+        self.record_event(described.clone());
+        self.notify(Notification { event: NotifyEvent::Invite, group_id: Some(group_id.to_string()), text: described.clone() });
+        self.set_status(described.clone());
+        let mut lines = vec![
+            format!("{}.", described),
+            "The timeline stays readable here, but nothing more can be sent to or received from it.".to_string(),
+        ];
+        if !unsent.is_empty() {
+            lines.push(format!("{} message(s) still waiting to be sent were dropped.", unsent.len()));
+        }
+        self.popup = Some(Popup {
+            title: format!("{} deleted", name),
+            lines,
+            actions: vec![
+                PopupAction::Export { group_id: group_id.to_string() },
+                PopupAction::Forget { group_id: group_id.to_string() },
+            ],
+        });
+        self.screen = AppScreen::Popup;
+    }
+
+    /// Shown above the timeline while the active group is one the delivery service dropped.
+    pub(crate) fn deletion_banner(&self) -> Option<String> {
+        let group = self.groups.get(self.active_group.as_ref()?)?;
+        let deletion = group.deleted.as_ref()?;
+        Some(format!(
+            "{} {}. It is read-only: 'export <file>' saves the transcript, 'forget' removes it from this device.",
+            group.name,
+            deletion.describe()
+        ))
+    }
+
+    /// Select the group and start an `export` of it on the command line, to be completed with
+    /// a file name and options.
+    pub(crate) fn start_export(&mut self, group_id: &str) {
+        self.select_group(group_id);
+        self.input_mode = InputMode::Command;
+        self.input = "export ".to_string();
+        self.set_status("Type the file to export to, e.g. export transcript.txt, then Enter");
+    }
+}
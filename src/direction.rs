@@ -0,0 +1,55 @@
+use mls_client_core::activity::Severity;
+use mls_client_core::bidi::{self, TextDirection};
+
+use crate::{App, InputMode};
+
+impl App {
+    /// `direction [auto|ltr|rtl]`: show or set the base text direction of the active group.
+    pub(crate) async fn direction_command(&mut self, wanted: Option<&str>) {
+        let Some(group) = self.active_group.as_ref().and_then(|group_id| self.groups.get_mut(group_id)) else {
+            self.set_warning("No active group selected");
+            return;
+        };
+        let Some(wanted) = wanted else {
+            self.activity.push(Severity::Info, format!("Text direction in {}: {}", group.name, group.text_direction.as_str()));
+            return;
+        };
+        let Some(direction) = TextDirection::parse(wanted) else {
+            self.set_warning("Usage: direction [auto|ltr|rtl]");
+            return;
+        };
+        group.text_direction = direction;
+        self.activity.push(Severity::Info, match direction {
+            TextDirection::Auto => format!("{} now lays out each message by its first letter", group.name),
+            TextDirection::Ltr => format!("{} now lays out messages left to right", group.name),
+            TextDirection::Rtl => format!("{} now lays out messages right to left", group.name),
+        });
+        self.save_history().await;
+    }
+
+    /// `text` in screen order for a group laid out `direction`, unless the terminal does that.
+    pub(crate) fn display_text(&self, direction: TextDirection, text: &str) -> String {
+        if self.config.terminal_bidi {
+            return text.to_string();
+        }
+        bidi::reorder(text, direction.is_rtl(text))
+    }
+
+    /// The composer's `text` as drawn in `width` cells: in screen order, whether it is
+    /// right-aligned, and the cursor column. Only messages follow the group's direction.
+    pub(crate) fn composer_view(&self, text: &str, width: u16) -> (String, bool, u16) {
+        let direction = self.active_group.as_ref()
+            .and_then(|group_id| self.groups.get(group_id))
+            .map_or(TextDirection::Ltr, |group| group.text_direction);
+        let width = width as isize;
+        let last = (width - 1).max(0);
+        if !matches!(self.input_mode, InputMode::Message) || self.config.terminal_bidi {
+            let cursor = (bidi::width(text) as isize).min(last);
+            return (text.to_string(), false, cursor as u16);
+        }
+        let rtl = direction.is_rtl(text);
+        let start = if rtl { (width - bidi::width(text) as isize).max(0) } else { 0 };
+        let cursor = (start + bidi::end_cursor(text, rtl)).clamp(0, last);
+        (bidi::reorder(text, rtl), rtl, cursor as u16)
+    }
+}
@@ -0,0 +1,104 @@
+use base64::Engine as _;
+use mls_client_core::errors::{self, NetworkError};
+use mls_client_core::mls_client;
+use mls_client_core::network::DirectoryEntry;
+
+use crate::{App, AppEvent, AppScreen, Popup, PopupAction};
+
+impl App {
+    /// `find <username>`: look the name up in the delivery service's identity directory.
+    pub(crate) fn find_identities(&mut self, query: Option<&str>) {
+        let Some(query) = query.map(str::trim).filter(|query| !query.is_empty()) else {
+            self.set_warning("Usage: find <username>");
+            return;
+        };
+        let Some(requester) = self.network_client.requester() else {
+            self.report_error(&errors::NOT_CONNECTED, format!("cannot look up {}", query));
+            return;
+        };
+        let events = self.events_tx.clone();
+        let query = query.to_string();
+        self.set_status(format!("Looking up {}…", query));
+        tokio::spawn(async move {
+            let entries = requester.find_identities(&query).await;
+            let _ = events.send(AppEvent::Directory { query, entries });
+        });
+    }
+
+    /// Show what the directory returned, with DM and invite actions once the match is unambiguous.
+    pub(crate) fn directory_results(&mut self, query: &str, entries: Result<Option<Vec<DirectoryEntry>>, NetworkError>) {
+        let entries = match entries {
+            Ok(Some(entries)) => entries,
+            Ok(None) => {
+                self.set_status("The delivery service did not answer: it may not offer a directory");
+                return;
+            }
+            Err(e) => {
+                self.set_error(format!("Failed to look up {}: {}", query, e));
+                return;
+            }
+        };
+        if entries.is_empty() {
+            self.set_status(format!("No identities matching {}", query));
+            return;
+        }
+
+        let mut lines = Vec::new();
+        for entry in &entries {
+            let you = if entry.identity == self.config.username { " (you)" } else { "" };
+            lines.push(format!("{}{}", entry.identity, you));
+            lines.push(format!("  Key packages: {} available", entry.key_packages.len()));
+            let fingerprints = self.directory_fingerprints(entry);
+            if fingerprints.is_empty() {
+                lines.push("  Fingerprint:  unknown (no valid key package)".to_string());
+            }
+            for fingerprint in &fingerprints {
+                let status = self.verification_status(&entry.identity, Some(fingerprint));
+                lines.push(format!("  Fingerprint:  {} ({})", fingerprint, status));
+            }
+            lines.push(String::new());
+        }
+        lines.pop();
+
+        // Actions need one identity to act on: the only result, or the one named exactly
+        let target = match entries.as_slice() {
+            [only] => Some(only),
+            _ => entries.iter().find(|entry| entry.identity == query),
+        };
+        let mut actions = Vec::new();
+        if let Some(entry) = target.filter(|entry| entry.identity != self.config.username && !entry.key_packages.is_empty()) {
+            actions.push(PopupAction::DirectMessage { identity: entry.identity.clone() });
+            let invitable = self.active_group.as_ref().and_then(|group_id| self.groups.get(group_id))
+                .filter(|group| !group.is_read_only() && group.is_admin(&self.config.username) && !group.members.contains(&entry.identity));
+            if let Some(group) = invitable {
+                actions.push(PopupAction::Invite { group_id: group.id.clone(), identity: entry.identity.clone() });
+            }
+        } else if target.is_none() {
+            lines.push(String::new());
+            lines.push("Search for the exact username to DM or invite".to_string());
+        }
+
+        self.popup = Some(Popup { title: format!("Directory: {}", query), lines, actions });
+        self.screen = AppScreen::Popup;
+    }
+
+    /// Distinct signature key fingerprints across an entry's valid key packages.
+    fn directory_fingerprints(&self, entry: &DirectoryEntry) -> Vec<String> {
+        let mut fingerprints = Vec::new();
+        for data in &entry.key_packages {
+            let Ok(data) = base64::engine::general_purpose::STANDARD.decode(data) else {
+                continue;
+            };
+            let Ok(key_package) = mls_client::decode_key_package(&self.mls_client.crypto, &data) else {
+                continue;
+            };
+            let Ok(fingerprint) = self.crypto.fingerprint(key_package.leaf_node().signature_key().as_slice()) else {
+                continue;
+            };
+            if !fingerprints.contains(&fingerprint) {
+                fingerprints.push(fingerprint);
+            }
+        }
+        fingerprints
+    }
+}
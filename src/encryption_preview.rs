@@ -0,0 +1,110 @@
+use anyhow::Result;
+use chrono::Local;
+use mls_client_core::errors;
+use mls_client_core::mls_client::EncryptionDetails;
+use mls_client_core::network::NetworkMessage;
+
+use crate::{App, AppScreen, HeldMessage, Popup, PopupAction};
+
+impl App {
+    /// Whether outgoing messages are held after encryption for `debug preview`.
+    pub(crate) fn previews_encryption(&self) -> bool {
+        self.config.debug_mode && self.config.encryption_preview
+    }
+
+    pub(crate) async fn set_encryption_preview(&mut self, enabled: bool) -> Result<()> {
+        self.config.encryption_preview = enabled;
+        self.save_config().await?;
+        self.set_status(match (enabled, self.config.debug_mode) {
+            (true, true) => "Encryption preview on: each message is held after encryption until you send it".to_string(),
+            (true, false) => "Encryption preview on; it takes effect in debug mode ('debug on')".to_string(),
+            (false, _) => "Encryption preview off".to_string(),
+        });
+        Ok(())
+    }
+
+    /// Keep an encrypted application frame back and show what it would put on the wire.
+    pub(crate) fn hold_message(&mut self, group_id: &str, message: NetworkMessage, details: EncryptionDetails, compressed: bool) {
+        self.held_messages.insert(group_id.to_string(), HeldMessage { message, details, compressed });
+        self.show_held_message(group_id);
+    }
+
+    /// `true`, with the preview reopened, while a message for `group_id` awaits review; the
+    /// next one waits so messages keep their order.
+    pub(crate) fn message_held(&mut self, group_id: &str) -> bool {
+        if !self.held_messages.contains_key(group_id) {
+            return false;
+        }
+        self.show_held_message(group_id);
+        self.set_status("Send or discard the previewed message first");
+        true
+    }
+
+    fn show_held_message(&mut self, group_id: &str) {
+        let Some(held) = self.held_messages.get(group_id) else {
+            return;
+        };
+        let details = &held.details;
+        let name = self.groups.get(group_id).map(|group| group.name.clone()).unwrap_or_else(|| group_id.to_string());
+        let padding = match details.padding_size {
+            0 => "none (padding_size 0)".to_string(),
+            size => format!("content padded to a multiple of {} bytes", size),
+        };
+        let lines = vec![
+            format!("Epoch:       {}", details.epoch),
+            format!("Generation:  {} (our application ratchet in this epoch)", details.generation),
+            format!("Plaintext:   {} bytes{}", details.plaintext_bytes, if held.compressed { " (zstd)" } else { "" }),
+            format!("Ciphertext:  {} bytes (serialized MLS message)", details.ciphertext_bytes),
+            format!("Overhead:    {} bytes", details.ciphertext_bytes.saturating_sub(details.plaintext_bytes)),
+            format!("Padding:     {}", padding),
+            format!("Message ID:  {}", held.message.message_id.as_deref().unwrap_or("none")),
+            String::new(),
+            "Encrypted and held; nothing has been sent yet.".to_string(),
+        ];
+        let actions = vec![
+            PopupAction::SendMessage { group_id: group_id.to_string() },
+            PopupAction::DiscardMessage { group_id: group_id.to_string() },
+        ];
+        self.popup = Some(Popup { title: format!("Outgoing message: {}", name), lines, actions });
+        self.screen = AppScreen::Popup;
+    }
+
+    pub(crate) async fn send_held_message(&mut self, group_id: &str) -> Result<()> {
+        let Some(held) = self.held_messages.remove(group_id) else {
+            self.set_status("No message held for review in this group");
+            return Ok(());
+        };
+        if !self.network_client.is_connected() {
+            self.held_messages.insert(group_id.to_string(), held);
+            self.report_error(&errors::NOT_CONNECTED, "cannot send the held message".to_string());
+            return Ok(());
+        }
+        if let Err(e) = self.network_client.send_message(&held.message).await {
+            self.held_messages.insert(group_id.to_string(), held);
+            self.report_error(&errors::SEND_FAILED, e.to_string());
+            return Ok(());
+        }
+        // The echo timeout runs from now, not from when the message was held
+        let message_id = held.message.message_id.as_deref().unwrap_or_default();
+        if let Some(message) = self.groups.get_mut(group_id)
+            .and_then(|group| group.messages.iter_mut().find(|message| message.id == message_id))
+        {
+            message.timestamp = Local::now();
+        }
+        self.set_status(format!("Sent {} bytes at epoch {}, generation {}", held.details.ciphertext_bytes, held.details.epoch, held.details.generation));
+        Ok(())
+    }
+
+    /// Drop the held message and its local echo; its ratchet generation is simply skipped.
+    pub(crate) async fn discard_held_message(&mut self, group_id: &str) {
+        let Some(held) = self.held_messages.remove(group_id) else {
+            self.set_status("No message held for review in this group");
+            return;
+        };
+        if let Some(group) = self.groups.get_mut(group_id) {
+            group.messages.retain(|message| Some(message.id.as_str()) != held.message.message_id.as_deref());
+        }
+        self.save_history().await;
+        self.set_status("Discarded the held message; nothing was sent");
+    }
+}
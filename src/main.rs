@@ -1,58 +1,374 @@
 use anyhow::Result;
-use chrono::{DateTime, Local};
+use base64::Engine as _;
+use chrono::{DateTime, Local, NaiveDate, NaiveTime};
 use crossterm::{
-    event::{self, DisableMouseCapture, EnableMouseCapture, Event, KeyCode, KeyEventKind},
+    event::{self, DisableMouseCapture, EnableMouseCapture, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers},
     execute,
     terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
 };
 use openmls::prelude::*;
-use openmls::prelude::tls_codec::{Serialize, Deserialize};
+use openmls::prelude::tls_codec::Serialize;
 use ratatui::{
     backend::CrosstermBackend,
-    layout::{Constraint, Direction, Layout, Rect},
+    layout::{Alignment, Constraint, Direction, Layout, Rect},
     style::{Color, Modifier, Style},
     text::{Line, Span},
-    widgets::{Block, Borders, Clear, List, ListItem, ListState, Paragraph, Wrap},
+    widgets::{block::Title, Block, Borders, Clear, List, ListItem, ListState, Paragraph, Wrap},
     Frame, Terminal,
 };
-use std::collections::HashMap;
-use std::io;
+use serde::{Deserialize as SerdeDeserialize, Serialize as SerdeSerialize};
+use std::collections::{HashMap, HashSet};
+use std::io::{self, Write};
+use std::path::Path;
+use std::time::{Duration, Instant};
+use tokio::sync::mpsc;
 use uuid::Uuid;
 
-mod config;
-mod crypto;
-mod mls_client;
-mod network;
+mod accounts;
+mod activity;
+mod announce;
+mod attachments;
+mod backups;
+mod bulk_invite;
+mod cleanup;
+mod cloning;
+mod commits;
+mod confirm;
+#[cfg(unix)]
+mod daemon;
+mod deletion;
+mod direction;
+mod directory;
+mod encryption_preview;
+mod memory;
+mod messaging;
+mod previews;
+mod protection;
+mod recovery;
+mod reminders;
+mod search;
+mod selftest;
+mod simple;
+#[cfg(test)]
+mod snapshot_tests;
+mod stress;
+mod switcher;
+mod title;
+mod translation;
+mod transport;
+mod bridged;
 mod ui;
+mod undo_send;
 
-use config::Config;
-use crypto::CryptoProvider;
-use mls_client::MlsClient;
-use network::NetworkClient;
+use mls_client_core::{
+    archive, backup, bidi, command, config, crypto, errors, export, filters, history, instance, integrity, local_ds, migrate, mls_client, netstats, network, notify, payload, platform, preview, ratchet_tree, scheduler,
+    secrets, send_queue, senders, speech, tasks, timeline, trace, transfers, validation, vault,
+};
+use mls_client_core::pipeline::{CommitEffect, HeldCommit};
+use accounts::Account;
+use activity::ActivityView;
+use mls_client_core::activity::{ActivityLog, Severity};
+use attachments::PendingAttachment;
+use archive::ARCHIVE_DIR;
+use mls_client_core::attachments::Pointer;
+use mls_client_core::search::SearchIndex;
+use mls_client_core::extensions::GroupState;
+use backup::{BackupOutcome, BACKUP_STATE_PATH};
+use backups::Backups;
+use bulk_invite::BulkInvite;
+use cleanup::Cleanup;
+use stress::StressRun;
+use transport::ServerTrust;
+use mls_client_core::{Group, GroupMode, GroupTag, Onboarding, RotationPolicy, WirePolicy};
+use bidi::TextDirection;
+use command::{Command, CommandLine, CommitAction, ConfigAction, TemplateAction};
+use confirm::{Confirmation, ConfirmedAction};
+use config::{Config, ConfigIssue, GroupFolder, QuietHours, CONFIG_PATH};
+use crypto::{to_hex, CryptoProvider};
+use errors::{AttachmentError, BackupError, CommandError, ErrorCode, MlsError, NetworkError, PreviewError, TranslateError};
+use export::{ExportEncryption, ExportRange};
+use filters::{FilterAction, FilterPattern, FilterSet, MessageFilter};
+use history::{HistoryShare, SeenIds, HISTORY_PATH};
+use integrity::Report;
+use mls_client::{EncryptionDetails, Mismatch, MlsClient};
+use netstats::format_bytes;
+use network::{DirectoryEntry, NetworkClient, NetworkMessage};
+use notify::{Notification, Notifier, NotifyEvent};
+use vault::{KeySlots, Profile, KEYSLOTS_PATH, SLOTS};
+use preview::{Preview, PreviewCache, PREVIEWS_PATH};
+use scheduler::{Scheduler, SCHEDULE_PATH};
+use payload::Payload;
+use send_queue::SendPriority;
+use senders::Automation;
+use speech::Speaker;
+use tasks::{TaskId, TaskTracker};
+use switcher::{CommandPrompt, FormPart, SwitchEntry, SwitchTarget, Switcher};
+use undo_send::DelayedSend;
+use timeline::WrapCache;
+use title::WindowTitle;
+use transfers::{ResendRequest, TransferStore, TRANSFERS_PATH};
+use validation::HandshakeValidator;
 
-#[derive(Debug, Clone)]
-pub struct Message {
-    pub id: String,
-    pub sender: String,
-    pub content: String,
-    pub timestamp: DateTime<Local>,
-    pub group_id: String,
+/// Target of a `report` frame to the delivery service.
+#[derive(Debug, SerdeSerialize, SerdeDeserialize)]
+struct MessageRef {
+    message_id: String,
+    #[serde(default)]
+    sender: Option<String>,
 }
 
-#[derive(Debug, Clone)]
-pub struct Group {
-    pub id: String,
-    pub name: String,
-    pub members: Vec<String>,
-    pub messages: Vec<Message>,
-    pub is_active: bool,
+const REACTION: &str = "👍";
+
+/// Foreground style for a group's tag; unknown color names fall back to the default color.
+fn tag_style(tag: &GroupTag) -> Style {
+    Style::default().fg(tag.color.parse().unwrap_or(Color::Reset))
+}
+
+/// One line of the group list: an account header (`None` for the primary account), a folder
+/// header (index into `Config::folders`) or a group ID.
+#[derive(Debug, Clone, PartialEq)]
+pub enum GroupListRow {
+    Account(Option<String>),
+    Folder(usize),
+    Group(String),
 }
 
 #[derive(Debug, Clone)]
 pub enum AppScreen {
+    Dashboard,
     Main,
     Settings,
     Help,
+    Locked,
+    Popup,
+    Confirm,
+    Roster,
+    Switcher,
+    Recovery,
+    Cleanup,
+    Activity,
+}
+
+/// An encrypted message of ours held in debug mode with `debug preview` until it is sent
+/// or discarded.
+pub struct HeldMessage {
+    pub message: NetworkMessage,
+    pub details: EncryptionDetails,
+    /// The plaintext was compressed before encryption
+    pub compressed: bool,
+}
+
+/// Completion of work spawned off the UI loop, applied by `poll_events`.
+pub enum AppEvent {
+    /// The Welcome awaited after a join request; `None` if no member sent one in time
+    Welcome { group_id: String, task: TaskId, welcome: Option<Vec<u8>> },
+    KeyPackages { identity: String, purpose: KeyPackagePurpose, packages: Result<Vec<Vec<u8>>, NetworkError> },
+    /// A token issued for `invite-token`; `None` if the delivery service issued none
+    InviteToken { group_id: String, token: Result<Option<String>, NetworkError> },
+    /// A `join-token` the delivery service did not accept
+    TokenRejected { task: TaskId, reason: String },
+    /// The translator's output for a message, or why it failed
+    Translated { group_id: String, message_id: String, translation: Result<String, TranslateError> },
+    /// A link preview card, or why there is none
+    PreviewFetched { url: String, preview: Result<Preview, PreviewError> },
+    /// Identities matching a `find`; `None` if the delivery service has no directory
+    Directory { query: String, entries: Result<Option<Vec<DirectoryEntry>>, NetworkError> },
+    /// A backup run finished, or why it failed
+    BackedUp { task: TaskId, outcome: Result<BackupOutcome, BackupError> },
+    /// An attachment blob finished uploading, or why it did not
+    AttachmentStored { group_id: String, message_id: String, task: TaskId, stored: Result<Pointer, AttachmentError> },
+    /// The sealed blob an `attachment` message points at, or why it could not be fetched
+    AttachmentFetched { pending: Box<PendingAttachment>, fetched: Result<Vec<u8>, AttachmentError> },
+    /// Key packages fetched for one identity of an `invite-file` run
+    BulkKeyPackages { group_id: String, identity: String, packages: Result<Vec<Vec<u8>>, NetworkError> },
+}
+
+/// What a fetched key package is for.
+#[derive(Debug, Clone)]
+pub enum KeyPackagePurpose {
+    Invite { group_id: String, share: Option<HistoryShare> },
+    DirectMessage,
+    /// Relax the group's requirements as needed, then add
+    Relax { group_id: String },
+}
+
+/// A delivery-service operation that failed, kept so `retry` can repeat it.
+#[derive(Debug, Clone)]
+pub enum RetryOp {
+    Publish { group_id: String, group_name: String },
+    Join { group_id: String },
+    /// Sent again under the same message ID, so members who got the first attempt skip it
+    Send { group_id: String, message_id: String, message: String },
+}
+
+/// Detail view drawn over the main screen; action keys run an action, any other key closes it.
+#[derive(Debug, Clone)]
+pub struct Popup {
+    pub title: String,
+    pub lines: Vec<String>,
+    pub actions: Vec<PopupAction>,
+}
+
+#[derive(Debug, Clone)]
+pub enum PopupAction {
+    DirectMessage { identity: String },
+    Invite { group_id: String, identity: String },
+    Verify { identity: String, fingerprint: String },
+    Kick { group_id: String, identity: String },
+    /// Drop the requirements `identity`'s key package lacks, then add them
+    Relax { group_id: String, identity: String },
+    Forget { group_id: String },
+    Rejoin { group_id: String },
+    /// Start an `export` of the group on the command line
+    Export { group_id: String },
+    SendCommit { group_id: String },
+    AbortCommit { group_id: String },
+    SendMessage { group_id: String },
+    DiscardMessage { group_id: String },
+    RemoveInactive { group_id: String, identities: Vec<String> },
+    RepairConfig,
+    AcceptInsecure { endpoint: String, fingerprint: String },
+    /// Let a bridged sender set off the automations its message was held back from
+    TrustSender { identity: String, automations: Vec<Automation> },
+}
+
+impl PopupAction {
+    pub fn key(&self) -> char {
+        match self {
+            PopupAction::DirectMessage { .. } => 'd',
+            PopupAction::Invite { .. } => 'i',
+            PopupAction::Verify { .. } => 'v',
+            PopupAction::Kick { .. } => 'k',
+            PopupAction::Relax { .. } => 'r',
+            PopupAction::Forget { .. } => 'f',
+            PopupAction::Rejoin { .. } => 'j',
+            PopupAction::Export { .. } => 'e',
+            PopupAction::SendCommit { .. } => 's',
+            PopupAction::AbortCommit { .. } => 'a',
+            PopupAction::SendMessage { .. } => 's',
+            PopupAction::DiscardMessage { .. } => 'a',
+            PopupAction::RemoveInactive { .. } => 'x',
+            PopupAction::RepairConfig => 'w',
+            PopupAction::AcceptInsecure { .. } => 'y',
+            PopupAction::TrustSender { .. } => 't',
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            PopupAction::DirectMessage { .. } => "DM",
+            PopupAction::Invite { .. } => "invite to the active group",
+            PopupAction::Verify { .. } => "verify",
+            PopupAction::Kick { .. } => "kick",
+            PopupAction::Relax { .. } => "relax requirements and add",
+            PopupAction::Forget { .. } => "forget",
+            PopupAction::Rejoin { .. } => "rejoin",
+            PopupAction::Export { .. } => "export the transcript",
+            PopupAction::SendCommit { .. } => "send commit",
+            PopupAction::AbortCommit { .. } => "abort commit",
+            PopupAction::SendMessage { .. } => "send message",
+            PopupAction::DiscardMessage { .. } => "discard message",
+            PopupAction::RemoveInactive { .. } => "remove them in one commit",
+            PopupAction::RepairConfig => "rewrite config.json keeping valid settings",
+            PopupAction::AcceptInsecure { .. } => "accept and remember this endpoint",
+            PopupAction::TrustSender { .. } => "allow these for this sender",
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct JoinRequest {
+    pub group_id: String,
+    pub client_id: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct SystemEvent {
+    pub timestamp: DateTime<Local>,
+    pub text: String,
+}
+
+/// An entry on the startup dashboard that Enter can jump to.
+#[derive(Debug, Clone)]
+pub enum DashboardItem {
+    Group(String),
+    Invitation(String),
+    JoinRequest(usize),
+}
+
+const MAX_SYSTEM_EVENTS: usize = 50;
+/// Passphrase attempts at startup before the client gives up.
+const UNLOCK_ATTEMPTS: usize = 3;
+/// How long an automatic key rotation that did not go through waits before it is tried again.
+const AUTO_ROTATION_RETRY: Duration = Duration::from_secs(600);
+/// Protocol frames drawn by `flow` when no count is given.
+const DEFAULT_FLOW_FRAMES: usize = 20;
+/// Low-bandwidth mode: how often received frames are acknowledged, and how long outgoing
+/// frames wait for others to share a write.
+const LOW_BANDWIDTH_ACK_INTERVAL: Duration = Duration::from_secs(30);
+const LOW_BANDWIDTH_SEND_DEBOUNCE: Duration = Duration::from_millis(500);
+/// How long a sent message spins waiting for the delivery service's copy before it is
+/// flagged as unconfirmed.
+const ECHO_TIMEOUT_SECS: i64 = 30;
+
+/// Tallest the pinned onboarding message may be, borders included; longer ones are cut off.
+const MAX_PINNED_HEIGHT: u16 = 8;
+
+/// Below this size the layout is replaced by a placeholder.
+const MIN_TERMINAL_WIDTH: u16 = 80;
+const MIN_TERMINAL_HEIGHT: u16 = 24;
+
+/// Sorted epochs as compact ranges, e.g. "1–3, 7".
+fn epoch_ranges(epochs: &[u64]) -> String {
+    let mut ranges: Vec<(u64, u64)> = Vec::new();
+    for &epoch in epochs {
+        match ranges.last_mut() {
+            Some((_, end)) if *end + 1 == epoch => *end = epoch,
+            _ => ranges.push((epoch, epoch)),
+        }
+    }
+    ranges
+        .iter()
+        .map(|(start, end)| if start == end { start.to_string() } else { format!("{}–{}", start, end) })
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// What the UI can do about a failed operation, chosen from the typed error underneath.
+enum Recovery {
+    Reconnect,
+    Rejoin(String),
+    None,
+}
+
+fn recovery_for(error: &anyhow::Error) -> Recovery {
+    if error.downcast_ref::<NetworkError>().is_some() {
+        return Recovery::Reconnect;
+    }
+    match error.downcast_ref::<MlsError>() {
+        Some(MlsError::UnknownGroup(group_id)) => Recovery::Rejoin(group_id.clone()),
+        _ => Recovery::None,
+    }
+}
+
+/// Whether escape sequences written straight to the terminal are interpreted; legacy Windows
+/// consoles print them instead.
+#[cfg(windows)]
+fn escapes_supported() -> bool {
+    crossterm::ansi_support::supports_ansi()
+}
+
+#[cfg(not(windows))]
+fn escapes_supported() -> bool {
+    true
+}
+
+/// Fill in template placeholders for the group being composed to.
+fn expand_template(template: &str, group: &str, user: &str) -> String {
+    template
+        .replace("{date}", &Local::now().format("%Y-%m-%d").to_string())
+        .replace("{group}", group)
+        .replace("{user}", user)
 }
 
 #[derive(Debug, Clone)]
@@ -61,10 +377,36 @@ pub enum InputMode {
     Command,
     Message,
     Settings,
+    Wipe,
+    /// Typing a new passphrase, masked; see `PassphrasePrompt`
+    Passphrase,
+    /// Highlighting a timeline message for reply/react/copy/pin/report
+    Select,
+    /// Answering the argument prompts of a command picked in the quick switcher
+    Argument,
+}
+
+/// Steps of the guarded `wipe` flow; each must be passed in order.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WipeStage {
+    Confirm,
+    ConfirmAgain,
+    Passphrase,
+}
+
+/// Which passphrase the masked prompt asks for. Each is typed twice; the first entry is kept
+/// until it is repeated.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PassphrasePrompt {
+    New,
+    RepeatNew(String),
+    Duress,
+    RepeatDuress(String),
 }
 
 pub struct App {
     pub config: Config,
+    pub crypto: CryptoProvider,
     pub mls_client: MlsClient,
     pub network_client: NetworkClient,
     pub groups: HashMap<String, Group>,
@@ -73,153 +415,413 @@ pub struct App {
     pub input_mode: InputMode,
     pub screen: AppScreen,
     pub group_list_state: ListState,
+    /// Timeline scroll offset in rendered (wrapped) lines
     pub message_scroll: u16,
-    pub status_message: String,
+    pub wrap_cache: WrapCache,
+    /// Inner height of the timeline at the last render, used for paging
+    pub message_view_height: u16,
+    /// Messages read back from disk above the view since the last render; the scroll moves
+    /// past them so the view stays put
+    pub restored_above: usize,
+    /// When the timelines were last measured against the memory budget
+    pub memory_checked: Instant,
+    /// Every status line shown this session, the newest in the status panel
+    pub activity: ActivityLog,
+    pub activity_view: ActivityView,
     pub should_quit: bool,
     pub settings_field: usize,
     pub temp_delivery_service: String,
     pub temp_username: String,
+    pub pending_invitations: Vec<String>,
+    /// Wire format policy announced with each pending invitation
+    pub invited_wire_policies: HashMap<String, WirePolicy>,
+    pub pending_join_requests: Vec<JoinRequest>,
+    pub system_events: Vec<SystemEvent>,
+    pub dashboard_state: ListState,
+    pub unlock_input: String,
+    pub unlock_failed: bool,
+    pub screen_before_lock: AppScreen,
+    pub last_activity: Instant,
+    pub wipe_stage: Option<WipeStage>,
+    pub passphrase_prompt: Option<PassphrasePrompt>,
+    pub show_metrics: bool,
+    /// Interrupted chunked uploads and downloads, resumed after reconnecting
+    pub transfers: TransferStore,
+    /// Cleared while disconnected so the next sync resumes transfers
+    pub transfers_resumed: bool,
+    /// Slices other members asked us to send again, handled on the next sync
+    pub pending_resends: Vec<ResendRequest>,
+    /// Own changes whose commit lost its epoch to a competing one, re-applied on the next sync
+    pub pending_rebases: Vec<(String, CommitEffect)>,
+    /// Groups that applied a commit since their quarantine was last retried
+    pub quarantine_retry: HashSet<String>,
+    /// When an automatic key rotation was last attempted, by group ID
+    pub auto_rotation_tried: HashMap<String, Instant>,
+    pub events_tx: mpsc::UnboundedSender<AppEvent>,
+    pub events_rx: mpsc::UnboundedReceiver<AppEvent>,
+    pub popup: Option<Popup>,
+    /// Destructive action awaiting y/n
+    pub confirmation: Option<Confirmation>,
+    /// Damage found by the startup integrity check, until a recovery option is picked
+    pub recovery: Option<Report>,
+    /// Key of the membership log links in this data directory; see `integrity::audit_key`
+    pub audit_key: Vec<u8>,
+    /// Own commits awaiting review, by group ID
+    pub held_commits: HashMap<String, HeldCommit>,
+    /// Own messages awaiting review in the encryption preview, by group ID
+    pub held_messages: HashMap<String, HeldMessage>,
+    /// Sent messages inside their undo window, oldest first
+    pub delayed_sends: Vec<DelayedSend>,
+    /// Invite tokens we requested this session, by token, with the group each admits to
+    pub issued_tokens: HashMap<String, String>,
+    pub notifiers: Vec<Box<dyn Notifier>>,
+    /// Reminders and scheduled sends, persisted in `SCHEDULE_PATH`
+    pub scheduler: Scheduler,
+    /// Reads incoming messages aloud in the groups `speech` selects
+    pub speaker: Speaker,
+    /// Link previews fetched so far, persisted in `PREVIEWS_PATH`
+    pub previews: PreviewCache,
+    /// Links fetched (or being fetched) this session, so a failing one is not retried
+    pub preview_requests: HashSet<String>,
+    pub window_title: WindowTitle,
+    /// `config.filters` compiled for the timeline
+    pub filters: FilterSet,
+    /// Unsent composer text, kept when the composer is closed with Esc
+    pub draft: String,
+    pub roster_state: ListState,
+    /// Ctrl+K quick switcher over groups and commands
+    pub switcher: Switcher,
+    pub command_prompt: Option<CommandPrompt>,
+    /// Index into the active group's timeline while in selection mode
+    pub selected_message: Option<usize>,
+    /// Most recent coded failure and its specifics, for `details`
+    pub last_error: Option<(&'static ErrorCode, String)>,
+    pub last_failed: Option<RetryOp>,
+    pub tasks: TaskTracker,
+    /// Problems found in config.json at startup; the affected settings use their defaults
+    pub config_issues: Vec<ConfigIssue>,
+    /// Name of the active account, `None` for the primary identity
+    pub account: Option<String>,
+    /// The other accounts, kept connected and synced in the background
+    pub accounts: Vec<Account>,
+    pub backups: Backups,
+    /// `invite-file` runs waiting for key packages, by group ID
+    pub bulk_invites: HashMap<String, BulkInvite>,
+    /// The `cleanup` view's quiet groups, while it is open
+    pub cleanup: Option<Cleanup>,
+    /// The delivery service's identity key as last checked against its pin
+    pub server_trust: Option<ServerTrust>,
+    /// `search.db`, opened by the first search or save and kept up to date as the history is saved
+    pub search_index: Option<SearchIndex>,
+    /// The running or last `stress` run
+    pub stress: Option<StressRun>,
+    /// Bridged senders already asked about this session, so each prompts at most once
+    pub prompted_senders: HashSet<String>,
 }
 
 impl App {
     pub async fn new() -> Result<Self> {
-        let config = Config::load_or_default().await?;
-        let crypto_provider = CryptoProvider::new();
-        let mls_client = MlsClient::new(&config.username, crypto_provider).await?;
-        let network_client = NetworkClient::new(&config.delivery_service_address).await?;
+        // Upgrade files left by older clients before anything reads them
+        let upgrade = migrate::run().await?;
+        let (config, config_issues) = Config::load_checked().await?;
+        let crypto = CryptoProvider::new();
+        let mut mls_client = MlsClient::new(&config.username, &crypto).await?;
+        mls_client.handshake = HandshakeValidator::new(&config.handshake_policy);
+        // The first `--local-ds` client hosts the delivery service; later ones connect to it
+        let hosting_ds = std::env::args().any(|arg| arg == "--local-ds")
+            && local_ds::spawn(&config.delivery_service_address).await?;
+        let network_client = NetworkClient::new(&config.delivery_service_addresses(), &config.username).await?;
+        if network_client.is_connected() {
+            network_client.publish_key_package(&mls_client.key_package.tls_serialize_detached()?).await?;
+        }
         
-        let mut group_list_state = ListState::default();
-        group_list_state.select(Some(0));
-
-        let status_message = if network_client.is_connected() {
-            format!("Connected to MLS service at {}. Groups will be synchronized.", config.delivery_service_address)
+        let status = if hosting_ds {
+            format!("Hosting a local delivery service at {}. Start other clients with --local-ds to join it.", config.delivery_service_address)
+        } else if network_client.is_connected() {
+            format!("{}. Groups will be synchronized.", network_client.connect_status().describe())
         } else {
-            format!("Disconnected from MLS service at {}. Groups will be local only.", config.delivery_service_address)
+            format!("{}. Groups will be local only.", network_client.connect_status().describe())
         };
 
-        Ok(Self {
-            config: config.clone(),
+        // Timelines from earlier sessions; MLS state is not persisted yet, so these are read-only views
+        let audit_key = integrity::audit_key().await?;
+        let (groups, integrity) = integrity::load_checked(Path::new(HISTORY_PATH), Path::new(ARCHIVE_DIR), &audit_key).await;
+        let transfers = transfers::load(Path::new(TRANSFERS_PATH)).await?;
+        let scheduler = scheduler::load(Path::new(SCHEDULE_PATH)).await?;
+        let previews = preview::load(Path::new(PREVIEWS_PATH)).await?;
+        let backup_state = backup::load_state(Path::new(BACKUP_STATE_PATH)).await?;
+
+        let mut app = Self::with_clients(config.clone(), mls_client, network_client);
+        app.groups = groups;
+        app.audit_key = audit_key;
+        app.transfers = transfers;
+        app.scheduler = scheduler;
+        app.previews = previews;
+        app.backups.state = backup_state;
+        app.config_issues = config_issues;
+        app.set_status(status.clone());
+        app.record_event(status);
+        if let Some(upgrade) = upgrade {
+            app.set_status(format!(
+                "Upgraded stored data from version {} to {}; the previous files are in {}",
+                upgrade.from, upgrade.to, upgrade.backup
+            ));
+            let upgrade_event = app.status_text().to_string();
+            app.record_event(upgrade_event);
+        }
+        if let Some(banner) = app.config_banner() {
+            app.set_status(banner.clone());
+            app.record_event(banner);
+        }
+        if let Some((banner, _)) = app.transport_banner() {
+            app.set_status(banner.clone());
+            app.record_event(banner);
+        }
+        app.check_server_clock();
+        if integrity.is_clean() {
+            if let Err(e) = integrity::keep_last_good().await {
+                app.record_event(format!("Could not back up the history file: {}", e));
+            }
+        } else {
+            app.start_recovery(integrity);
+        }
+        for account in config.accounts {
+            match Account::open(&account, &app.config.handshake_policy).await {
+                Ok(opened) => app.accounts.push(opened),
+                Err(e) => app.record_event(format!("Could not open account {}: {}", account.name, e)),
+            }
+        }
+        Ok(app)
+    }
+
+    /// An app for `config` on the given clients with nothing read from the data directory:
+    /// no groups, transfers, schedule, previews or backup record.
+    fn with_clients(config: Config, mls_client: MlsClient, network_client: NetworkClient) -> Self {
+        let mut group_list_state = ListState::default();
+        group_list_state.select(Some(0));
+        let (events_tx, events_rx) = mpsc::unbounded_channel();
+        let mut dashboard_state = ListState::default();
+        dashboard_state.select(Some(0));
+        let temp_delivery_service = config.delivery_service_address.clone();
+        let temp_username = config.username.clone();
+        let notifiers = notify::from_config(&config);
+        let filters = FilterSet::new(&config.filters);
+        Self {
+            config,
+            crypto: CryptoProvider::new(),
             mls_client,
             network_client,
             groups: HashMap::new(),
             active_group: None,
             input: String::new(),
             input_mode: InputMode::Normal,
-            screen: AppScreen::Main,
+            screen: AppScreen::Dashboard,
             group_list_state,
             message_scroll: 0,
-            status_message,
+            wrap_cache: WrapCache::default(),
+            message_view_height: 0,
+            restored_above: 0,
+            memory_checked: Instant::now(),
+            activity: ActivityLog::default(),
+            activity_view: ActivityView::default(),
             should_quit: false,
             settings_field: 0,
-            temp_delivery_service: config.delivery_service_address.clone(),
-            temp_username: config.username.clone(),
-        })
+            temp_delivery_service,
+            temp_username,
+            pending_invitations: Vec::new(),
+            invited_wire_policies: HashMap::new(),
+            pending_join_requests: Vec::new(),
+            system_events: Vec::new(),
+            dashboard_state,
+            unlock_input: String::new(),
+            unlock_failed: false,
+            screen_before_lock: AppScreen::Main,
+            last_activity: Instant::now(),
+            wipe_stage: None,
+            passphrase_prompt: None,
+            show_metrics: false,
+            transfers: TransferStore::default(),
+            transfers_resumed: false,
+            pending_resends: Vec::new(),
+            pending_rebases: Vec::new(),
+            quarantine_retry: HashSet::new(),
+            auto_rotation_tried: HashMap::new(),
+            events_tx,
+            events_rx,
+            popup: None,
+            confirmation: None,
+            recovery: None,
+            audit_key: Vec::new(),
+            notifiers,
+            speaker: Speaker::spawn(),
+            scheduler: Scheduler::default(),
+            previews: PreviewCache::default(),
+            preview_requests: HashSet::new(),
+            window_title: WindowTitle::default(),
+            filters,
+            draft: String::new(),
+            roster_state: ListState::default(),
+            switcher: Switcher::default(),
+            command_prompt: None,
+            selected_message: None,
+            last_error: None,
+            last_failed: None,
+            tasks: TaskTracker::default(),
+            held_commits: HashMap::new(),
+            held_messages: HashMap::new(),
+            delayed_sends: Vec::new(),
+            issued_tokens: HashMap::new(),
+            config_issues: Vec::new(),
+            account: None,
+            accounts: Vec::new(),
+            backups: Backups::default(),
+            bulk_invites: HashMap::new(),
+            cleanup: None,
+            server_trust: None,
+            search_index: None,
+            stress: None,
+            prompted_senders: HashSet::new(),
+        }
     }
 
-    pub async fn handle_input(&mut self, key: KeyCode) -> Result<()> {
-        match self.input_mode {
-            InputMode::Normal => self.handle_normal_input(key).await,
-            InputMode::Command => self.handle_command_input(key).await,
-            InputMode::Message => self.handle_message_input(key).await,
-            InputMode::Settings => self.handle_settings_input(key).await,
+    /// " (fallback 2 of 3)" while connected through a fallback address, otherwise empty.
+    fn endpoint_note(&self) -> String {
+        match self.network_client.endpoint_position() {
+            (0, _) => String::new(),
+            (index, count) => format!(" (fallback {} of {})", index + 1, count),
         }
     }
 
-    async fn handle_normal_input(&mut self, key: KeyCode) -> Result<()> {
-        match key {
-            KeyCode::Char('q') => self.should_quit = true,
-            KeyCode::Char('c') => {
-                self.input_mode = InputMode::Command;
-                self.input.clear();
-            }
-            KeyCode::Char('m') => {
-                if self.active_group.is_some() {
-                    self.input_mode = InputMode::Message;
-                    self.input.clear();
-                } else {
-                    self.status_message = "No active group selected".to_string();
-                }
-            }
-            KeyCode::Char('s') => {
-                self.screen = AppScreen::Settings;
-                self.input_mode = InputMode::Settings;
-            }
-            KeyCode::Char('h') => {
-                self.screen = AppScreen::Help;
-            }
-            KeyCode::Up => {
-                let groups: Vec<_> = self.groups.keys().cloned().collect();
-                if !groups.is_empty() {
-                    let selected = self.group_list_state.selected().unwrap_or(0);
-                    let new_selected = if selected > 0 { selected - 1 } else { groups.len() - 1 };
-                    self.group_list_state.select(Some(new_selected));
-                    self.active_group = Some(groups[new_selected].clone());
-                }
-            }
-            KeyCode::Down => {
-                let groups: Vec<_> = self.groups.keys().cloned().collect();
-                if !groups.is_empty() {
-                    let selected = self.group_list_state.selected().unwrap_or(0);
-                    let new_selected = if selected < groups.len() - 1 { selected + 1 } else { 0 };
-                    self.group_list_state.select(Some(new_selected));
-                    self.active_group = Some(groups[new_selected].clone());
-                }
-            }
-            // Add j/k for single-line scroll (Mac-friendly)
-            KeyCode::Char('j') => {
-                self.message_scroll = self.message_scroll.saturating_add(1);
+    /// Warn when the local clock disagrees with the DS clock reported at connect time.
+    fn check_server_clock(&mut self) {
+        if let Some(offset) = self.network_client.server_clock_offset() {
+            if offset.unsigned_abs() > self.config.clock_skew_threshold_secs {
+                let warning = format!("Local clock differs from server time by {}s; message times may be misleading", offset);
+                self.set_status(warning.clone());
+                self.record_event(warning);
             }
-            KeyCode::Char('k') => {
-                self.message_scroll = self.message_scroll.saturating_sub(1);
-            }
-            _ => {}
         }
-        Ok(())
     }
 
-    async fn handle_command_input(&mut self, key: KeyCode) -> Result<()> {
+    /// Blank the UI behind the lock screen; requires a configured passphrase.
+    pub fn lock(&mut self) {
+        if matches!(self.screen, AppScreen::Locked) {
+            return;
+        }
+        if vault::entered().is_none() {
+            self.set_status("Set a passphrase with 'passphrase' before locking");
+            return;
+        }
+        self.screen_before_lock = std::mem::replace(&mut self.screen, AppScreen::Locked);
+        self.unlock_input.clear();
+        self.unlock_failed = false;
+    }
+
+    /// Lock the session if it has been idle longer than the configured timeout.
+    pub fn check_auto_lock(&mut self) {
+        let minutes = self.config.auto_lock_minutes;
+        if minutes > 0 && self.last_activity.elapsed() >= Duration::from_secs(minutes * 60) {
+            self.lock();
+        }
+    }
+
+    pub async fn handle_lock_input(&mut self, key: KeyCode) -> Result<()> {
         match key {
             KeyCode::Enter => {
-                let command = self.input.trim().to_owned();
-                self.execute_command(&command).await?;
-                self.input.clear();
-                self.input_mode = InputMode::Normal;
-            }
-            KeyCode::Esc => {
-                self.input.clear();
-                self.input_mode = InputMode::Normal;
-            }
-            KeyCode::Char(c) => {
-                self.input.push(c);
+                let opened = match KeySlots::load().await? {
+                    Some(slots) => slots.open(&self.crypto, &self.unlock_input)?,
+                    None => None,
+                };
+                self.unlock_input.clear();
+                let Some(profile) = opened else {
+                    self.unlock_failed = true;
+                    self.record_event("Failed unlock attempt");
+                    return Ok(());
+                };
+                if vault::entered().is_some_and(|current| current.slot != profile.slot) {
+                    self.switch_profile(profile).await?;
+                }
+                self.unlock_failed = false;
+                self.screen = std::mem::replace(&mut self.screen_before_lock, AppScreen::Main);
+                self.set_status("Session unlocked");
             }
+            KeyCode::Esc => self.unlock_input.clear(),
+            KeyCode::Char(c) => self.unlock_input.push(c),
             KeyCode::Backspace => {
-                self.input.pop();
+                self.unlock_input.pop();
             }
             _ => {}
         }
         Ok(())
     }
 
-    async fn handle_message_input(&mut self, key: KeyCode) -> Result<()> {
+    /// Replace the session with the profile in another key slot, as the duress passphrase
+    /// opens it: the current profile is dropped from memory, and the other one is loaded and
+    /// run as at startup, saving to its own sealed files.
+    async fn switch_profile(&mut self, profile: Profile) -> Result<()> {
+        let screen = match self.screen_before_lock {
+            AppScreen::Dashboard => AppScreen::Dashboard,
+            _ => AppScreen::Main,
+        };
+        vault::enter(profile)?;
+        *self = App::new().await?;
+        self.screen = AppScreen::Locked;
+        self.screen_before_lock = screen;
+        Ok(())
+    }
+
+    /// `passphrase` and `passphrase duress`: ask for the new passphrase at a masked prompt, so
+    /// it is never shown or left in the command line.
+    fn start_passphrase_prompt(&mut self, prompt: PassphrasePrompt) {
+        if prompt == PassphrasePrompt::Duress && vault::entered().is_none() {
+            self.set_warning("Set a passphrase with 'passphrase' before a duress passphrase");
+            return;
+        }
+        self.set_status(match prompt {
+            PassphrasePrompt::Duress => "Type the duress passphrase, then Enter. Esc cancels.",
+            _ => "Type the new passphrase, then Enter. Esc cancels.",
+        });
+        self.passphrase_prompt = Some(prompt);
+        self.input_mode = InputMode::Passphrase;
+        self.input.clear();
+    }
+
+    fn cancel_passphrase_prompt(&mut self, reason: &str) {
+        self.passphrase_prompt = None;
+        self.input_mode = InputMode::Normal;
+        self.input.clear();
+        self.set_status(format!("Passphrase not changed: {}", reason));
+    }
+
+    async fn handle_passphrase_input(&mut self, key: KeyCode) -> Result<()> {
         match key {
             KeyCode::Enter => {
-                if let Some(group_id) = &self.active_group {
-                    let message = self.input.trim().to_owned();
-                    if !message.is_empty() {
-                        let group_id_owned = group_id.clone();
-                        self.send_message(&group_id_owned, &message).await?;
+                let entered = std::mem::take(&mut self.input);
+                match self.passphrase_prompt.take() {
+                    Some(PassphrasePrompt::New | PassphrasePrompt::Duress) if entered.is_empty() => {
+                        self.cancel_passphrase_prompt("it cannot be empty");
+                    }
+                    Some(PassphrasePrompt::New) => {
+                        self.passphrase_prompt = Some(PassphrasePrompt::RepeatNew(entered));
+                        self.set_status("Type the new passphrase again.");
                     }
+                    Some(PassphrasePrompt::Duress) => {
+                        self.passphrase_prompt = Some(PassphrasePrompt::RepeatDuress(entered));
+                        self.set_status("Type the duress passphrase again.");
+                    }
+                    Some(PassphrasePrompt::RepeatNew(first)) if first == entered => {
+                        self.input_mode = InputMode::Normal;
+                        self.set_passphrase(&entered).await?;
+                    }
+                    Some(PassphrasePrompt::RepeatDuress(first)) if first == entered => {
+                        self.input_mode = InputMode::Normal;
+                        self.set_duress_passphrase(Some(&entered)).await?;
+                    }
+                    _ => self.cancel_passphrase_prompt("the two entries did not match"),
                 }
-                self.input.clear();
-                self.input_mode = InputMode::Normal;
-            }
-            KeyCode::Esc => {
-                self.input.clear();
-                self.input_mode = InputMode::Normal;
-            }
-            KeyCode::Char(c) => {
-                self.input.push(c);
             }
+            KeyCode::Esc => self.cancel_passphrase_prompt("cancelled"),
+            KeyCode::Char(c) => self.input.push(c),
             KeyCode::Backspace => {
                 self.input.pop();
             }
@@ -228,289 +830,3111 @@ impl App {
         Ok(())
     }
 
-    async fn handle_settings_input(&mut self, key: KeyCode) -> Result<()> {
+    /// Seal the data files under a new passphrase, or change the one that opens this profile.
+    async fn set_passphrase(&mut self, passphrase: &str) -> Result<()> {
+        let Some(current) = vault::entered() else {
+            return self.seal_profile(passphrase).await;
+        };
+        let Some(mut slots) = KeySlots::load().await? else {
+            self.set_error(format!("{} is missing, so the passphrase cannot be changed", KEYSLOTS_PATH));
+            return Ok(());
+        };
+        if slots.open(&self.crypto, passphrase)?.is_some_and(|opened| opened.slot != current.slot) {
+            self.set_warning("The passphrase must differ from the duress passphrase");
+            return Ok(());
+        }
+        slots.wrap(&self.crypto, &current, passphrase)?;
+        slots.save().await?;
+        self.set_status("Passphrase updated. Press Ctrl+L to lock the session.");
+        Ok(())
+    }
+
+    /// Move the plain data files into a profile sealed under `passphrase`, with unused key
+    /// slots beside it, and save what is only in memory there.
+    async fn seal_profile(&mut self, passphrase: &str) -> Result<()> {
+        let (slots, profile, unused) = KeySlots::create(&self.crypto, passphrase)?;
+        let decoy = serde_json::to_vec_pretty(&self.config.decoy())?;
+        for unused in &unused {
+            unused.reset(&decoy).await?;
+        }
+        slots.save().await?;
+        vault::seal_plain_files(&profile).await?;
+        vault::enter(profile)?;
+        // The index on disk is gone; the next search rebuilds it in memory
+        self.search_index = None;
+        self.save_config().await?;
+        self.save_history().await;
+        self.set_status("Passphrase set: the data files are sealed under it, and it is asked for at startup. Press Ctrl+L to lock the session.");
+        Ok(())
+    }
+
+    /// Put a new, empty profile opened by `duress` in the other key slot, or an unused one for
+    /// `None` (`passphrase duress off`). Whatever that slot held is destroyed.
+    async fn set_duress_passphrase(&mut self, duress: Option<&str>) -> Result<()> {
+        let (Some(current), Some(mut slots)) = (vault::entered(), KeySlots::load().await?) else {
+            self.set_warning("Set a passphrase with 'passphrase' before a duress passphrase");
+            return Ok(());
+        };
+        let other = (current.slot + 1) % SLOTS;
+        let profile = match duress {
+            None => slots.fill_unused(&self.crypto, other)?,
+            Some(duress) => {
+                if slots.open(&self.crypto, duress)?.is_some_and(|opened| opened.slot == current.slot) {
+                    self.set_warning("The duress passphrase must differ from the passphrase");
+                    return Ok(());
+                }
+                slots.fill(&self.crypto, other, duress)?
+            }
+        };
+        profile.reset(&serde_json::to_vec_pretty(&self.config.decoy())?).await?;
+        slots.save().await?;
+        self.set_status(match duress {
+            None => "Duress passphrase removed",
+            Some(_) => "Duress passphrase set: at startup or the lock screen it opens a separate, empty profile",
+        });
+        Ok(())
+    }
+
+    fn start_wipe(&mut self) {
+        if vault::entered().is_none() {
+            self.set_status("Set a passphrase with 'passphrase' before using wipe");
+            return;
+        }
+        self.wipe_stage = Some(WipeStage::Confirm);
+        self.input_mode = InputMode::Wipe;
+        self.input.clear();
+        self.set_status("WIPE: this destroys the keystore, history and config. Type WIPE to continue, Esc to abort.");
+    }
+
+    fn cancel_wipe(&mut self, reason: &str) {
+        self.wipe_stage = None;
+        self.input_mode = InputMode::Normal;
+        self.input.clear();
+        self.set_status(format!("Wipe aborted: {}", reason));
+    }
+
+    async fn handle_wipe_input(&mut self, key: KeyCode) -> Result<()> {
         match key {
             KeyCode::Enter => {
-                self.save_settings().await?;
-                self.screen = AppScreen::Main;
-                self.input_mode = InputMode::Normal;
-            }
-            KeyCode::Esc => {
-                self.temp_delivery_service = self.config.delivery_service_address.clone();
-                self.temp_username = self.config.username.clone();
-                self.screen = AppScreen::Main;
-                self.input_mode = InputMode::Normal;
-            }
-            KeyCode::Tab => {
-                self.settings_field = (self.settings_field + 1) % 2;
-            }
-            KeyCode::Char(c) => {
-                if self.settings_field == 0 {
-                    self.temp_delivery_service.push(c);
-                } else {
-                    self.temp_username.push(c);
+                let entered = std::mem::take(&mut self.input);
+                match self.wipe_stage {
+                    Some(WipeStage::Confirm) if entered == "WIPE" => {
+                        self.wipe_stage = Some(WipeStage::ConfirmAgain);
+                        self.set_status("WIPE: are you absolutely sure? Type YES to continue.");
+                    }
+                    Some(WipeStage::ConfirmAgain) if entered == "YES" => {
+                        self.wipe_stage = Some(WipeStage::Passphrase);
+                        self.set_status("WIPE: enter your passphrase to destroy all local data.");
+                    }
+                    Some(WipeStage::Passphrase) => {
+                        let verified = match KeySlots::load().await? {
+                            Some(slots) => slots.open(&self.crypto, &entered)?.is_some(),
+                            None => false,
+                        };
+                        if verified {
+                            self.wipe_local_data().await?;
+                        } else {
+                            self.cancel_wipe("incorrect passphrase");
+                        }
+                    }
+                    _ => self.cancel_wipe("confirmation did not match"),
                 }
             }
+            KeyCode::Esc => self.cancel_wipe("cancelled"),
+            KeyCode::Char(c) => self.input.push(c),
             KeyCode::Backspace => {
-                if self.settings_field == 0 {
-                    self.temp_delivery_service.pop();
-                } else {
-                    self.temp_username.pop();
-                }
+                self.input.pop();
             }
             _ => {}
         }
         Ok(())
     }
 
-    async fn execute_command(&mut self, command: &str) -> Result<()> {
-        let parts: Vec<&str> = command.split_whitespace().collect();
-        
-        match parts.get(0) {
-            Some(&"create") => {
-                if let Some(group_name) = parts.get(1) {
-                    self.create_group(group_name).await?;
-                } else {
-                    self.status_message = "Usage: create <group_name>".to_string();
-                }
-            }
-            Some(&"join") => {
-                if let Some(group_id) = parts.get(1) {
-                    self.join_group(group_id).await?;
-                } else {
-                    self.status_message = "Usage: join <group_id>".to_string();
-                }
-            }
-            Some(&"send") => {
-                if let Some(message) = parts.get(1..) {
-                    let message = message.join(" ");
-                    if let Some(group_id) = &self.active_group {
-                        let group_id_owned = group_id.clone();
-                        self.send_message(&group_id_owned, &message).await?;
-                    } else {
-                        self.status_message = "No active group selected".to_string();
-                    }
-                } else {
-                    self.status_message = "Usage: send <message>".to_string();
-                }
-            }
-            Some(&"quit") => {
-                self.should_quit = true;
-            }
-            Some(&"help") => {
-                self.screen = AppScreen::Help;
+    /// Destroy every local secret and exit.
+    async fn wipe_local_data(&mut self) -> Result<()> {
+        // Drop in-memory MLS state before touching the disk
+        self.mls_client.groups.clear();
+        self.groups.clear();
+        self.accounts.clear();
+        self.delayed_sends.clear();
+
+        secrets::wipe_local_data().await?;
+        self.should_quit = true;
+        Ok(())
+    }
+
+    /// Append to the recent-activity log shown on the dashboard.
+    pub fn record_event(&mut self, text: impl Into<String>) {
+        self.system_events.push(SystemEvent {
+            timestamp: Local::now(),
+            text: text.into(),
+        });
+        if self.system_events.len() > MAX_SYSTEM_EVENTS {
+            self.system_events.remove(0);
+        }
+    }
+
+    /// Group list lines: groups outside any folder first, then each folder with its groups
+    /// unless it is collapsed. With several accounts, each gets a header and only the active
+    /// account's groups are listed.
+    fn group_list_rows(&self) -> Vec<GroupListRow> {
+        if self.config.accounts.is_empty() {
+            return self.account_group_rows();
+        }
+        let mut rows = Vec::new();
+        for name in self.account_names() {
+            let active = name == self.account;
+            rows.push(GroupListRow::Account(name));
+            if active {
+                rows.extend(self.account_group_rows());
             }
-            Some(&"settings") => {
-                self.screen = AppScreen::Settings;
-                self.input_mode = InputMode::Settings;
+        }
+        rows
+    }
+
+    fn account_group_rows(&self) -> Vec<GroupListRow> {
+        let foldered: HashSet<&String> = self.config.folders.iter().flat_map(|folder| &folder.groups).collect();
+        let mut rows: Vec<GroupListRow> = self.groups
+            .keys()
+            .filter(|id| !foldered.contains(id))
+            .map(|id| GroupListRow::Group(id.clone()))
+            .collect();
+        for (index, folder) in self.config.folders.iter().enumerate() {
+            rows.push(GroupListRow::Folder(index));
+            if !folder.collapsed {
+                rows.extend(folder.groups
+                    .iter()
+                    .filter(|id| self.groups.contains_key(*id))
+                    .map(|id| GroupListRow::Group(id.clone())));
             }
-            Some(&"groups") => {
-                if self.groups.is_empty() {
-                    self.status_message = "No local groups available. Use 'create <group_name>' to create a group.".to_string();
-                } else {
-                    let groups_info: Vec<String> = self.groups
-                        .iter()
-                        .map(|(id, group)| format!("• {} (ID: {}) - {} members", group.name, id, group.members.len()))
-                        .collect();
-                    self.status_message = format!("Local groups:\n{}", groups_info.join("\n"));
-                }
+        }
+        rows
+    }
+
+    fn folder_of(&self, group_id: &str) -> Option<usize> {
+        self.config.folders.iter().position(|folder| folder.groups.iter().any(|id| id == group_id))
+    }
+
+    /// Point the list cursor at `group_id`, or at its folder header while the folder is collapsed.
+    fn sync_group_selection(&mut self, group_id: &str) {
+        let rows = self.group_list_rows();
+        let row = rows.iter().position(|row| *row == GroupListRow::Group(group_id.to_string())).or_else(|| {
+            let folder = self.folder_of(group_id)?;
+            rows.iter().position(|row| *row == GroupListRow::Folder(folder))
+        });
+        if let Some(row) = row {
+            self.group_list_state.select(Some(row));
+        }
+    }
+
+    /// Move the list cursor by `delta` rows (wrapping), activating the group under it.
+    fn move_group_cursor(&mut self, delta: isize) {
+        let rows = self.group_list_rows();
+        if rows.is_empty() {
+            return;
+        }
+        let selected = self.group_list_state.selected().unwrap_or(0) as isize;
+        let next = (selected + delta).rem_euclid(rows.len() as isize) as usize;
+        match &rows[next] {
+            GroupListRow::Group(id) => self.select_group(id),
+            GroupListRow::Account(_) | GroupListRow::Folder(_) => self.group_list_state.select(Some(next)),
+        }
+    }
+
+    /// Collapse or expand the folder under the cursor, or the one holding the active group.
+    async fn toggle_folder(&mut self) -> Result<()> {
+        let rows = self.group_list_rows();
+        let folder = match self.group_list_state.selected().and_then(|row| rows.get(row)) {
+            Some(GroupListRow::Folder(index)) => Some(*index),
+            Some(GroupListRow::Group(id)) => self.folder_of(id),
+            Some(GroupListRow::Account(_)) | None => None,
+        };
+        let Some(index) = folder else {
+            self.set_status("Not in a folder");
+            return Ok(());
+        };
+        let folder = &mut self.config.folders[index];
+        folder.collapsed = !folder.collapsed;
+        // Keep the cursor on this folder's header rather than on a row that vanished
+        let header = self.group_list_rows().iter().position(|row| *row == GroupListRow::Folder(index));
+        self.group_list_state.select(header);
+        Ok(self.save_config().await?)
+    }
+
+    /// Move the active group to the next (`delta` 1) or previous (-1) folder; before the
+    /// first folder is "no folder".
+    async fn shift_group_folder(&mut self, delta: isize) -> Result<()> {
+        let Some(group_id) = self.active_group.clone() else {
+            self.set_warning("No active group selected");
+            return Ok(());
+        };
+        // Position 0 is outside any folder, folder i is position i + 1
+        let slots = self.config.folders.len() as isize + 1;
+        let current = self.folder_of(&group_id).map_or(0, |index| index as isize + 1);
+        let target = (current + delta).rem_euclid(slots);
+        self.move_to_folder(&group_id, (target > 0).then(|| target as usize - 1)).await
+    }
+
+    async fn move_to_folder(&mut self, group_id: &str, folder: Option<usize>) -> Result<()> {
+        for existing in &mut self.config.folders {
+            existing.groups.retain(|id| id != group_id);
+        }
+        let name = self.groups.get(group_id).map(|group| group.name.clone()).unwrap_or_else(|| group_id.to_string());
+        match folder.and_then(|index| self.config.folders.get_mut(index)) {
+            Some(folder) => {
+                folder.groups.push(group_id.to_string());
+                // Expand so the moved group stays visible
+                folder.collapsed = false;
+                self.activity.push(Severity::Info, format!("Moved {} to folder {}", name, folder.name));
             }
-            Some(&"list") => {
-                // List groups from the server
-                match self.network_client.list_groups().await {
-                    Ok(server_groups) => {
-                        if server_groups.is_empty() {
-                            self.status_message = "No groups found on server. Use 'create <group_name>' to create a group.".to_string();
-                        } else {
-                            let groups_list = server_groups.join("\n• ");
-                            self.status_message = format!("Groups available on server:\n• {}", groups_list);
-                        }
+            None => self.set_status(format!("Moved {} out of its folder", name)),
+        }
+        self.sync_group_selection(group_id);
+        Ok(self.save_config().await?)
+    }
+
+    /// Manage message filters; new ones apply to the active group unless `all` is given.
+    async fn filter_command(&mut self, parts: &[&str]) -> Result<()> {
+        match parts {
+            ["add", rest @ ..] => {
+                let (everywhere, rest) = match rest {
+                    ["all", rest @ ..] => (true, rest),
+                    _ => (false, rest),
+                };
+                let parsed = match rest {
+                    [action, "regex", pattern @ ..] if !pattern.is_empty() => {
+                        FilterAction::parse(action).map(|action| (action, FilterPattern::Regex(pattern.join(" "))))
                     }
-                    Err(e) => {
-                        self.status_message = format!("Failed to list groups from server: {}", e);
+                    [action, "keywords", words @ ..] => {
+                        let keywords: Vec<String> = words
+                            .iter()
+                            .flat_map(|word| word.split(','))
+                            .filter(|keyword| !keyword.is_empty())
+                            .map(str::to_string)
+                            .collect();
+                        FilterAction::parse(action).filter(|_| !keywords.is_empty()).map(|action| (action, FilterPattern::Keywords(keywords)))
                     }
+                    _ => None,
+                };
+                let Some((action, pattern)) = parsed else {
+                    self.set_warning("Usage: filter add [all] <hide|dim|highlight> regex <pattern> | keywords <word,...>");
+                    return Ok(());
+                };
+                let group = if everywhere { None } else { self.active_group.clone() };
+                let filter = MessageFilter { action, pattern, group };
+                if let Err(e) = filter.validate() {
+                    self.set_warning(format!("Invalid pattern: {}", e));
+                    return Ok(());
                 }
+                self.set_status(format!("Added filter {}: {} in {}", self.config.filters.len() + 1, filter.describe(), self.filter_scope(&filter)));
+                self.config.filters.push(filter);
             }
-            Some(&"status") => {
-                if self.network_client.is_connected() {
-                    self.status_message = format!("Connected to MLS service at {}. {} groups available.", 
-                        self.config.delivery_service_address, self.groups.len());
+            ["remove", index] => {
+                let Some(index) = index.parse::<usize>().ok().filter(|index| (1..=self.config.filters.len()).contains(index)) else {
+                    self.set_warning(format!("No filter {}; 'filter list' shows their numbers", index));
+                    return Ok(());
+                };
+                let filter = self.config.filters.remove(index - 1);
+                self.set_status(format!("Removed filter: {}", filter.describe()));
+            }
+            ["list"] => {
+                let lines = if self.config.filters.is_empty() {
+                    vec!["No filters. Add one with 'filter add'.".to_string()]
                 } else {
-                    self.status_message = format!("Disconnected from MLS service at {}. Groups will be local only.", 
-                        self.config.delivery_service_address);
-                }
+                    self.config.filters
+                        .iter()
+                        .enumerate()
+                        .map(|(index, filter)| format!("{}. {} in {}", index + 1, filter.describe(), self.filter_scope(filter)))
+                        .collect()
+                };
+                self.show_popup("Message filters", lines);
+                return Ok(());
             }
             _ => {
-                self.status_message = format!("Unknown command: {}. Available commands: create, join, send, groups, list, status, settings, help, quit", command);
+                self.set_warning("Usage: filter add [all] <hide|dim|highlight> regex <pattern> | keywords <word,...>, filter remove <n>, filter list");
+                return Ok(());
             }
         }
+        self.filters = FilterSet::new(&self.config.filters);
+        self.save_config().await?;
         Ok(())
     }
 
-    async fn create_group(&mut self, group_name: &str) -> Result<()> {
-        let group_id = Uuid::new_v4().to_string();
-        
-        // Create MLS group
-        let group_config = MlsGroupCreateConfig::builder()
-            .wire_format_policy(WireFormatPolicy::default())
-            .build();
-        
-        let mls_group = MlsGroup::new(
-            &self.mls_client.crypto,
-            &self.mls_client.signer,
-            &group_config,
-            CredentialWithKey {
-                credential: self.mls_client.credential.clone().into(),
-                signature_key: self.mls_client.signature_key.clone(),
+    fn filter_scope(&self, filter: &MessageFilter) -> String {
+        match &filter.group {
+            Some(group_id) => self.groups.get(group_id).map_or(group_id.clone(), |group| group.name.clone()),
+            None => "all groups".to_string(),
+        }
+    }
+
+    async fn folder_command(&mut self, parts: &[&str]) -> Result<()> {
+        match parts {
+            ["create", name] => {
+                if self.config.folders.iter().any(|folder| folder.name == *name) {
+                    self.set_status(format!("Folder {} already exists", name));
+                    return Ok(());
+                }
+                self.config.folders.push(GroupFolder { name: name.to_string(), collapsed: false, groups: Vec::new() });
+                self.set_status(format!("Created folder {}", name));
+            }
+            ["delete", name] => {
+                let before = self.config.folders.len();
+                self.config.folders.retain(|folder| folder.name != *name);
+                if self.config.folders.len() == before {
+                    self.set_warning(format!("No folder named {}", name));
+                    return Ok(());
+                }
+                self.set_status(format!("Deleted folder {}; its groups are back at the top level", name));
+            }
+            ["move", group, target] => {
+                let Some(group_id) = self.find_group_id(group) else {
+                    self.set_warning(format!("No group named {}", group));
+                    return Ok(());
+                };
+                if *target == "none" {
+                    return self.move_to_folder(&group_id, None).await;
+                }
+                let Some(index) = self.config.folders.iter().position(|folder| folder.name == *target) else {
+                    self.set_warning(format!("No folder named {}", target));
+                    return Ok(());
+                };
+                return self.move_to_folder(&group_id, Some(index)).await;
+            }
+            _ => {
+                self.set_warning("Usage: folder create|delete <name>, folder move <group> <folder>|none");
+                return Ok(());
+            }
+        }
+        if let Some(group_id) = self.active_group.clone() {
+            self.sync_group_selection(&group_id);
+        }
+        Ok(self.save_config().await?)
+    }
+
+    /// Make `group_id` the active group, syncing the list selection and clearing its unread count.
+    fn select_group(&mut self, group_id: &str) {
+        self.sync_group_selection(group_id);
+        if let Some(group) = self.groups.get_mut(group_id) {
+            group.unread = 0;
+            group.unread_mentions = 0;
+        }
+        self.active_group = Some(group_id.to_string());
+        self.switcher.touch(SwitchTarget::Group(group_id.to_string()));
+        if let Some(reason) = self.groups.get(group_id).and_then(Group::rotation_overdue) {
+            self.set_status(format!("This group's keys are due for rotation ({}): press u to update", reason));
+        }
+    }
+
+    pub fn open_switcher(&mut self) {
+        self.switcher.reset();
+        self.screen = AppScreen::Switcher;
+    }
+
+    /// Every group (direct messages labelled by peer) and every command, unfiltered.
+    fn switcher_entries(&self) -> Vec<SwitchEntry> {
+        let groups = self.groups.values().map(|group| SwitchEntry {
+            target: SwitchTarget::Group(group.id.clone()),
+            label: match group.name.strip_prefix("dm-") {
+                Some(peer) => format!("@ {}", peer),
+                None => format!("# {}", group.name),
             },
-        )?;
+            detail: format!("{} members", group.members.len()),
+        });
+        let commands = switcher::command_forms(command::HELP).into_iter().map(|form| SwitchEntry {
+            target: SwitchTarget::Command(form.syntax.clone()),
+            label: format!("> {}", form.syntax),
+            detail: form.description,
+        });
+        groups.chain(commands).collect()
+    }
 
-        // Store the MLS group
-        self.mls_client.add_group(&group_id, mls_group);
+    /// Run a command picked in the switcher, asking for its arguments one at a time first.
+    async fn start_command_prompt(&mut self, syntax: &str) -> Result<()> {
+        let Some(form) = switcher::command_forms(command::HELP).into_iter().find(|form| form.syntax == syntax) else {
+            return Ok(());
+        };
+        self.switcher.touch(SwitchTarget::Command(syntax.to_string()));
+        let prompt = CommandPrompt::new(form);
+        self.input.clear();
+        if prompt.next().is_none() {
+            self.input_mode = InputMode::Normal;
+            return self.execute_command(&prompt.command_line()).await;
+        }
+        self.command_prompt = Some(prompt);
+        self.input_mode = InputMode::Argument;
+        Ok(())
+    }
 
-        // Store group locally
-        let group = Group {
-            id: group_id.clone(),
-            name: group_name.to_string(),
-            members: vec![self.config.username.clone()],
-            messages: Vec::new(),
-            is_active: true,
+    async fn handle_argument_input(&mut self, key: KeyCode) -> Result<()> {
+        let Some(prompt) = self.command_prompt.as_mut() else {
+            self.input_mode = InputMode::Normal;
+            return Ok(());
         };
-        
-        self.groups.insert(group_id.clone(), group);
-        self.active_group = Some(group_id.clone());
-        
-        // Update group list selection
-        let groups: Vec<_> = self.groups.keys().cloned().collect();
-        if let Some(pos) = groups.iter().position(|g| g == &group_id) {
-            self.group_list_state.select(Some(pos));
+        match key {
+            KeyCode::Enter => {
+                let answer = self.input.trim().to_string();
+                if let Some(FormPart::Required(label)) = prompt.next() {
+                    if answer.is_empty() {
+                        self.activity.push(Severity::Info, format!("{} is required (Esc cancels)", label));
+                        return Ok(());
+                    }
+                }
+                prompt.answers.push(answer);
+                self.input.clear();
+                if prompt.next().is_none() {
+                    let line = prompt.command_line();
+                    self.command_prompt = None;
+                    self.input_mode = InputMode::Normal;
+                    self.execute_command(&line).await?;
+                }
+            }
+            KeyCode::Esc => {
+                self.command_prompt = None;
+                self.input.clear();
+                self.input_mode = InputMode::Normal;
+            }
+            KeyCode::Char(c) => {
+                self.input.push(c);
+            }
+            KeyCode::Backspace => {
+                self.input.pop();
+            }
+            _ => {}
         }
-        
-        // Publish group to MLS service if connected
-        if self.network_client.is_connected() {
-            // Export the group info for sharing
-            let group_info = group_id.as_bytes().to_vec();
-            if let Err(e) = self.network_client.create_group(&group_id, &group_info, &self.config.username).await {
-                self.status_message = format!("Created group: {} (ID: {}), but failed to publish to MLS service: {}", group_name, group_id, e);
-            } else {
-                self.status_message = format!("Created and published group: {} (ID: {})", group_name, group_id);
+        Ok(())
+    }
+
+    pub async fn handle_switcher_input(&mut self, key: KeyCode) -> Result<()> {
+        let matches = self.switcher.filter(self.switcher_entries());
+        let selected = self.switcher.state.selected().unwrap_or(0);
+        match key {
+            KeyCode::Up if !matches.is_empty() => {
+                self.switcher.state.select(Some(if selected > 0 { selected - 1 } else { matches.len() - 1 }));
+            }
+            KeyCode::Down if !matches.is_empty() => {
+                self.switcher.state.select(Some((selected + 1) % matches.len()));
+            }
+            KeyCode::Enter => {
+                self.screen = AppScreen::Main;
+                match matches.into_iter().nth(selected).map(|entry| entry.target) {
+                    Some(SwitchTarget::Group(group_id)) => self.select_group(&group_id),
+                    Some(SwitchTarget::Command(syntax)) => self.start_command_prompt(&syntax).await?,
+                    None => {}
+                }
+            }
+            KeyCode::Esc => self.screen = AppScreen::Main,
+            KeyCode::Char(c) => {
+                self.switcher.query.push(c);
+                self.switcher.state.select(Some(0));
+            }
+            KeyCode::Backspace => {
+                self.switcher.query.pop();
+                self.switcher.state.select(Some(0));
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    fn dashboard_items(&self) -> Vec<DashboardItem> {
+        let mut items: Vec<DashboardItem> = self.groups.keys().cloned().map(DashboardItem::Group).collect();
+        items.extend(self.pending_invitations.iter().cloned().map(DashboardItem::Invitation));
+        items.extend((0..self.pending_join_requests.len()).map(DashboardItem::JoinRequest));
+        items
+    }
+
+    pub async fn handle_dashboard_input(&mut self, key: KeyCode) -> Result<()> {
+        let items = self.dashboard_items();
+        match key {
+            KeyCode::Up if !items.is_empty() => {
+                let selected = self.dashboard_state.selected().unwrap_or(0);
+                let new_selected = if selected > 0 { selected - 1 } else { items.len() - 1 };
+                self.dashboard_state.select(Some(new_selected));
+            }
+            KeyCode::Down if !items.is_empty() => {
+                let selected = self.dashboard_state.selected().unwrap_or(0);
+                let new_selected = if selected + 1 < items.len() { selected + 1 } else { 0 };
+                self.dashboard_state.select(Some(new_selected));
+            }
+            KeyCode::Enter => {
+                self.screen = AppScreen::Main;
+                let selected = self.dashboard_state.selected().unwrap_or(0);
+                match items.get(selected).cloned() {
+                    Some(DashboardItem::Group(group_id)) => self.select_group(&group_id),
+                    Some(DashboardItem::Invitation(group_id)) => {
+                        self.pending_invitations.retain(|g| g != &group_id);
+                        self.join_group(&group_id).await?;
+                    }
+                    Some(DashboardItem::JoinRequest(index)) => {
+                        if let Some(request) = self.pending_join_requests.get(index).cloned() {
+                            self.select_group(&request.group_id);
+                            self.set_status(format!("{} is waiting to join this group", request.client_id));
+                        }
+                    }
+                    None => {}
+                }
+            }
+            KeyCode::Char('q') => self.should_quit = true,
+            _ => {
+                self.screen = AppScreen::Main;
+            }
+        }
+        Ok(())
+    }
+
+    pub async fn handle_input(&mut self, key: KeyCode) -> Result<()> {
+        match self.input_mode {
+            InputMode::Normal => self.handle_normal_input(key).await,
+            InputMode::Command => self.handle_command_input(key).await,
+            InputMode::Message => self.handle_message_input(key).await,
+            InputMode::Settings => self.handle_settings_input(key).await,
+            InputMode::Wipe => self.handle_wipe_input(key).await,
+            InputMode::Passphrase => self.handle_passphrase_input(key).await,
+            InputMode::Select => self.handle_select_input(key).await,
+            InputMode::Argument => self.handle_argument_input(key).await,
+        }
+    }
+
+    async fn handle_normal_input(&mut self, key: KeyCode) -> Result<()> {
+        match key {
+            KeyCode::Char('q') => self.should_quit = true,
+            KeyCode::Char('c') => {
+                self.input_mode = InputMode::Command;
+                self.input.clear();
+            }
+            KeyCode::Char('m') => {
+                if let Some(group_id) = &self.active_group {
+                    if let Some(message) = self.posting_denied(group_id) {
+                        self.set_status(message);
+                    } else {
+                        self.input_mode = InputMode::Message;
+                        self.input = std::mem::take(&mut self.draft);
+                    }
+                } else {
+                    self.set_warning("No active group selected");
+                }
+            }
+            KeyCode::Char('s') => {
+                self.screen = AppScreen::Settings;
+                self.input_mode = InputMode::Settings;
+            }
+            KeyCode::Char('h') => {
+                self.screen = AppScreen::Help;
+            }
+            KeyCode::Char('d') => {
+                self.screen = AppScreen::Dashboard;
+            }
+            KeyCode::Char('a') => self.open_activity(Severity::Info, String::new()),
+            KeyCode::Char('r') => self.open_roster(),
+            KeyCode::Char('u') => {
+                if let Some(group_id) = self.active_group.clone() {
+                    self.update_keys(&group_id).await?;
+                }
+            }
+            KeyCode::Esc => {
+                let count = self.active_group.as_ref()
+                    .and_then(|id| self.groups.get(id))
+                    .map(|group| group.messages.len())
+                    .unwrap_or(0);
+                if count > 0 {
+                    self.selected_message = Some(count - 1);
+                    self.input_mode = InputMode::Select;
+                    self.set_status("Select: ↑/↓ move, r reply, + react, c copy, p pin, ! report, i protection, Esc done");
+                }
+            }
+            KeyCode::Up => self.move_group_cursor(-1),
+            KeyCode::Down => self.move_group_cursor(1),
+            KeyCode::Enter => {
+                let rows = self.group_list_rows();
+                if let Some(GroupListRow::Account(name)) = self.group_list_state.selected().and_then(|row| rows.get(row)) {
+                    let name = name.clone().unwrap_or_else(|| accounts::PRIMARY_ACCOUNT.to_string());
+                    self.switch_account(&name);
+                }
+            }
+            KeyCode::Char('f') => self.toggle_folder().await?,
+            KeyCode::Char('<') => self.shift_group_folder(-1).await?,
+            KeyCode::Char('>') => self.shift_group_folder(1).await?,
+            // Add j/k for single-line scroll (Mac-friendly)
+            KeyCode::Char('j') => {
+                self.message_scroll = self.message_scroll.saturating_add(1);
+            }
+            KeyCode::Char('k') => self.scroll_up(1).await,
+            KeyCode::PageDown => {
+                self.message_scroll = self.message_scroll.saturating_add(self.message_view_height.max(1));
+            }
+            KeyCode::PageUp => self.scroll_up(self.message_view_height.max(1)).await,
+            _ => {}
+        }
+        Ok(())
+    }
+
+    async fn handle_command_input(&mut self, key: KeyCode) -> Result<()> {
+        match key {
+            KeyCode::Enter => {
+                let command = self.input.trim().to_owned();
+                self.input.clear();
+                // Commands may switch to another input mode (settings, wipe)
+                self.input_mode = InputMode::Normal;
+                self.execute_command(&command).await?;
+            }
+            KeyCode::Esc => {
+                self.input.clear();
+                self.input_mode = InputMode::Normal;
+            }
+            KeyCode::Char(c) => {
+                self.input.push(c);
+            }
+            KeyCode::Backspace => {
+                self.input.pop();
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    async fn handle_message_input(&mut self, key: KeyCode) -> Result<()> {
+        match key {
+            KeyCode::Enter => {
+                let message = self.input.trim().to_owned();
+                // Oversized text stays in the composer so it can be shortened
+                if !self.message_fits(&message) {
+                    return Ok(());
+                }
+                // As is text typed while an earlier message awaits review in the encryption preview
+                if let Some(group_id) = self.active_group.clone() {
+                    if self.message_held(&group_id) {
+                        return Ok(());
+                    }
+                }
+                if let Some(group_id) = &self.active_group {
+                    if !message.is_empty() {
+                        let group_id_owned = group_id.clone();
+                        self.send_message(&group_id_owned, &message).await?;
+                    }
+                }
+                self.input.clear();
+                self.input_mode = InputMode::Normal;
+            }
+            KeyCode::Esc => {
+                self.draft = std::mem::take(&mut self.input);
+                self.input_mode = InputMode::Normal;
+            }
+            KeyCode::Char(c) => {
+                self.input.push(c);
+            }
+            KeyCode::Backspace => {
+                self.input.pop();
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    async fn handle_select_input(&mut self, key: KeyCode) -> Result<()> {
+        let Some(group_id) = self.active_group.clone() else {
+            self.exit_selection();
+            return Ok(());
+        };
+        let count = self.groups.get(&group_id).map(|group| group.messages.len()).unwrap_or(0);
+        let Some(selected) = self.selected_message.filter(|index| *index < count) else {
+            self.exit_selection();
+            return Ok(());
+        };
+        match key {
+            KeyCode::Up | KeyCode::Char('k') => {
+                if selected == 0 {
+                    self.load_older_page().await;
+                }
+                self.selected_message = self.selected_message.map(|index| index.saturating_sub(1));
+            }
+            KeyCode::Down | KeyCode::Char('j') => self.selected_message = Some((selected + 1).min(count - 1)),
+            KeyCode::Esc => self.exit_selection(),
+            KeyCode::Char('r') => {
+                self.exit_selection();
+                self.reply_to(&group_id, selected);
+            }
+            KeyCode::Char('+') => self.react_to(&group_id, selected, REACTION).await?,
+            KeyCode::Char('c') => self.copy_message(&group_id, selected)?,
+            KeyCode::Char('p') => self.toggle_pin(&group_id, selected).await,
+            KeyCode::Char('!') => self.report_message(&group_id, selected).await?,
+            KeyCode::Char('i') => self.show_protection(&group_id, selected),
+            _ => {}
+        }
+        Ok(())
+    }
+
+    fn exit_selection(&mut self) {
+        self.selected_message = None;
+        self.input_mode = InputMode::Normal;
+    }
+
+    /// Open the composer quoting the selected message.
+    fn reply_to(&mut self, group_id: &str, index: usize) {
+        if let Some(message) = self.posting_denied(group_id) {
+            self.set_status(message);
+            return;
+        }
+        let Some(group) = self.groups.get(group_id) else {
+            return;
+        };
+        let Some(message) = group.messages.get(index) else {
+            return;
+        };
+        let excerpt: String = message.content.chars().take(40).collect();
+        let ellipsis = if message.content.chars().count() > 40 { "…" } else { "" };
+        self.input = format!("> {}: {}{} | ", group.display_name(&message.sender), excerpt, ellipsis);
+        self.input_mode = InputMode::Message;
+    }
+
+    async fn react_to(&mut self, group_id: &str, index: usize, emoji: &str) -> Result<()> {
+        let Some(message) = self.groups.get_mut(group_id).and_then(|group| group.messages.get_mut(index)) else {
+            return Ok(());
+        };
+        let reactors = message.reactions.entry(emoji.to_string()).or_default();
+        if !reactors.contains(&self.config.username) {
+            reactors.push(self.config.username.clone());
+        }
+        let message_id = message.id.clone();
+
+        // Encrypted like a message; groups with members that cannot read typed payloads only
+        // see it here
+        if self.network_client.is_connected() {
+            if let Err(e) = self.send_payload(group_id, Payload::reaction(&message_id, emoji), None).await {
+                self.set_warning(format!("Reacted locally, but failed to notify members: {}", e));
+                self.save_history().await;
+                return Ok(());
+            }
+        }
+        self.set_status(format!("Reacted with {}", emoji));
+        self.save_history().await;
+        Ok(())
+    }
+
+    /// Put the message text on the system clipboard via the OSC 52 terminal escape.
+    fn copy_message(&mut self, group_id: &str, index: usize) -> Result<()> {
+        let Some(message) = self.groups.get(group_id).and_then(|group| group.messages.get(index)) else {
+            return Ok(());
+        };
+        if !escapes_supported() {
+            self.set_warning("This console cannot reach the clipboard; select the text with the mouse instead");
+            return Ok(());
+        }
+        let encoded = base64::engine::general_purpose::STANDARD.encode(message.content.as_bytes());
+        let mut stdout = io::stdout();
+        write!(stdout, "\x1b]52;c;{}\x07", encoded)?;
+        stdout.flush()?;
+        self.set_status("Copied message to clipboard");
+        Ok(())
+    }
+
+    async fn toggle_pin(&mut self, group_id: &str, index: usize) {
+        let Some(message) = self.groups.get_mut(group_id).and_then(|group| group.messages.get_mut(index)) else {
+            return;
+        };
+        message.pinned = !message.pinned;
+        self.activity.push(Severity::Info, if message.pinned { "Pinned message" } else { "Unpinned message" }.to_string());
+        self.save_history().await;
+    }
+
+    /// Redact the `n`-th most recent message of the active group for every member. The redaction
+    /// travels as an MLS application message, so receivers can check it came from an admin.
+    async fn redact_message(&mut self, n: usize) -> Result<()> {
+        let Some(group_id) = self.active_group.clone() else {
+            self.set_warning("No active group selected");
+            return Ok(());
+        };
+        let Some(group) = self.groups.get(&group_id) else {
+            return Ok(());
+        };
+        if !group.is_admin(&self.config.username) {
+            self.set_status(format!("Only admins can redact messages in {}", group.name));
+            return Ok(());
+        }
+        let Some(target) = group.messages.len().checked_sub(n).and_then(|index| group.messages.get(index)) else {
+            self.set_status(format!("{} has only {} message(s)", group.name, group.messages.len()));
+            return Ok(());
+        };
+        if target.redacted_by.is_some() {
+            self.set_status("That message is already redacted");
+            return Ok(());
+        }
+        let preview: String = target.content.chars().take(80).collect();
+        let prompt = format!("Redact {}'s message in {}?", target.sender, group.name);
+        let details = vec![
+            format!("\"{}{}\"", preview, if preview.len() < target.content.len() { "…" } else { "" }),
+            "Every member's copy is replaced by a redaction notice; this cannot be undone.".to_string(),
+        ];
+        let action = ConfirmedAction::Redact { group_id, message_id: target.id.clone() };
+        self.confirm(action, prompt, details).await
+    }
+
+    /// Send the redaction of `message_id` once confirmed.
+    async fn redact_confirmed(&mut self, group_id: &str, message_id: &str) -> Result<()> {
+        if !self.groups.get(group_id).is_some_and(|group| group.messages.iter().any(|message| message.id == message_id)) {
+            return Ok(());
+        }
+        if !self.network_client.is_connected() {
+            self.report_error(&errors::NOT_CONNECTED, "cannot redact a message");
+            return Ok(());
+        }
+        self.send_payload(group_id, Payload::redaction(message_id), None).await?;
+
+        let username = self.config.username.clone();
+        if let Some(group) = self.groups.get_mut(group_id) {
+            if let Some(sender) = group.redact(message_id, &username, Local::now()) {
+                let redacted = format!("Redacted a message from {} in {}", sender, group.name);
+                self.set_status(redacted.clone());
+                self.record_event(redacted);
+            }
+        }
+        self.save_history().await;
+        Ok(())
+    }
+
+    /// `onboarding [set <text> | clear | dismiss]` for the active group.
+    async fn onboarding_command(&mut self, parts: &[&str]) -> Result<()> {
+        let Some(group_id) = self.active_group.clone() else {
+            self.set_warning("No active group selected");
+            return Ok(());
+        };
+        match parts {
+            [] => {
+                let Some(group) = self.groups.get(&group_id) else {
+                    return Ok(());
+                };
+                let lines = match &group.onboarding {
+                    Some(onboarding) => {
+                        let mut lines: Vec<String> = onboarding.text.lines().map(str::to_string).collect();
+                        lines.push(String::new());
+                        lines.push(format!("Set by {} on {}", onboarding.set_by, onboarding.set_at.format("%Y-%m-%d %H:%M")));
+                        lines
+                    }
+                    None => vec![
+                        "No onboarding message is set for this group.".to_string(),
+                        "Admins set one with 'onboarding set <text>'.".to_string(),
+                    ],
+                };
+                let title = format!("Onboarding: {}", group.name);
+                self.show_popup(title, lines);
+            }
+            ["dismiss"] => {
+                if let Some(group) = self.groups.get_mut(&group_id) {
+                    group.onboarding_pinned = false;
+                }
+                self.save_history().await;
+                self.set_status("Onboarding message unpinned; 'onboarding' shows it again");
+            }
+            ["clear"] => self.set_onboarding(&group_id, None).await?,
+            ["set", text @ ..] if !text.is_empty() => self.set_onboarding(&group_id, Some(text.join(" "))).await?,
+            _ => self.set_warning("Usage: onboarding [set <text> | clear | dismiss]"),
+        }
+        Ok(())
+    }
+
+    /// Set or clear the group's onboarding message and tell the members, so whichever of
+    /// them adds someone next delivers the current one. Admins only.
+    async fn set_onboarding(&mut self, group_id: &str, text: Option<String>) -> Result<()> {
+        let Some(group) = self.groups.get(group_id) else {
+            return Ok(());
+        };
+        if !group.is_admin(&self.config.username) {
+            self.set_status(format!("Only admins can change the onboarding message of {}", group.name));
+            return Ok(());
+        }
+        if !self.network_client.is_connected() {
+            self.report_error(&errors::NOT_CONNECTED, "cannot change the onboarding message");
+            return Ok(());
+        }
+        if text.as_ref().is_some_and(|text| !self.message_fits(text)) {
+            return Ok(());
+        }
+        let onboarding = text.map(|text| Onboarding { text, set_by: self.config.username.clone(), set_at: Local::now() });
+        self.send_onboarding(group_id, &onboarding, None).await?;
+        if let Some(group) = self.groups.get_mut(group_id) {
+            self.activity.push(Severity::Info, match onboarding {
+                Some(_) => format!("Onboarding message set for {}; members added from now on receive it", group.name),
+                None => format!("Onboarding message of {} removed", group.name),
+            });
+            group.onboarding = onboarding;
+        }
+        self.save_history().await;
+        Ok(())
+    }
+
+    /// Flag a message to the delivery service for moderation; only its ID and sender are sent.
+    async fn report_message(&mut self, group_id: &str, index: usize) -> Result<()> {
+        let Some(message) = self.groups.get(group_id).and_then(|group| group.messages.get(index)) else {
+            return Ok(());
+        };
+        if !self.network_client.is_connected() {
+            self.set_error("Cannot report message: not connected to MLS service");
+            return Ok(());
+        }
+        let sender = message.sender.clone();
+        let content = serde_json::to_vec(&MessageRef { message_id: message.id.clone(), sender: Some(sender.clone()) })?;
+        let network_message = NetworkMessage::for_group("report", &self.config.username, group_id, content);
+        self.network_client.send_message(&network_message).await?;
+        let reported = format!("Reported message from {}", sender);
+        self.set_status(reported.clone());
+        self.record_event(reported);
+        Ok(())
+    }
+
+    async fn handle_settings_input(&mut self, key: KeyCode) -> Result<()> {
+        match key {
+            KeyCode::Enter => {
+                self.save_settings().await?;
+                self.screen = AppScreen::Main;
+                self.input_mode = InputMode::Normal;
+            }
+            KeyCode::Esc => {
+                self.temp_delivery_service = self.config.delivery_service_address.clone();
+                self.temp_username = self.config.username.clone();
+                self.screen = AppScreen::Main;
+                self.input_mode = InputMode::Normal;
+            }
+            KeyCode::Tab => {
+                self.settings_field = (self.settings_field + 1) % 2;
+            }
+            KeyCode::Char(c) => {
+                if self.settings_field == 0 {
+                    self.temp_delivery_service.push(c);
+                } else {
+                    self.temp_username.push(c);
+                }
+            }
+            KeyCode::Backspace => {
+                if self.settings_field == 0 {
+                    self.temp_delivery_service.pop();
+                } else {
+                    self.temp_username.pop();
+                }
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    async fn execute_command(&mut self, command: &str) -> Result<()> {
+        let line = match CommandLine::parse(command) {
+            Ok(line) => line,
+            Err(e) => {
+                self.set_error(e.to_string());
+                return Ok(());
+            }
+        };
+        let command = match Command::parse(&line) {
+            Ok(command) => command,
+            Err(e @ (CommandError::Usage(_) | CommandError::Unknown(_))) => {
+                self.set_warning(e.to_string());
+                return Ok(());
+            }
+            Err(e) => {
+                self.set_error(e.to_string());
+                return Ok(());
+            }
+        };
+
+        match command {
+            Command::Create { name, policy } => {
+                let policy = policy.unwrap_or(self.config.default_wire_policy);
+                self.create_group(name, policy, GroupMode::Chat).await?;
+            }
+            Command::Clone { group, new_name } => self.clone_group(group, new_name).await?,
+            Command::InviteFile { group, path } => self.invite_file(group, path).await,
+            Command::Invite { identity, share } => {
+                let default_share = (self.config.share_history_on_invite > 0)
+                    .then_some(HistoryShare::Last(self.config.share_history_on_invite));
+                self.invite_member(identity, share.unwrap_or(default_share)).await?;
+            }
+            Command::Forget(wanted) | Command::Rejoin(wanted) | Command::Leave(wanted) => {
+                let group_id = match wanted {
+                    Some(wanted) => self.find_group_id(wanted),
+                    None => self.active_group.clone(),
+                };
+                match (&command, group_id) {
+                    (Command::Forget(_), Some(group_id)) => self.confirm_forget(&group_id).await?,
+                    (Command::Leave(_), Some(group_id)) => self.confirm_leave(&group_id).await?,
+                    (_, Some(group_id)) => self.join_group(&group_id).await?,
+                    (Command::Forget(_), None) => self.set_warning("Usage: forget [group]"),
+                    (Command::Leave(_), None) => self.set_warning("Usage: leave [group]"),
+                    (_, None) => self.set_warning("Usage: rejoin [group]"),
+                }
+            }
+            Command::InviteToken(wanted) => {
+                let group_id = match wanted {
+                    Some(wanted) => self.find_group_id(wanted),
+                    None => self.active_group.clone(),
+                };
+                match group_id {
+                    Some(group_id) => self.request_invite_token(&group_id),
+                    None => self.set_warning("Usage: invite-token [group]"),
+                }
+            }
+            Command::Find(query) => self.find_identities(query),
+            Command::Search(args) => self.search_command(&args).await,
+            Command::JoinToken(token) => self.join_with_token(token),
+            Command::Onboarding(args) => self.onboarding_command(&args).await?,
+            Command::Kick(identity) => match self.active_group.clone() {
+                Some(group_id) => self.confirm_kick(&group_id, vec![identity.to_string()]).await?,
+                None => self.set_warning("No active group selected"),
+            },
+            Command::Redact(n) => self.redact_message(n).await?,
+            Command::Join(group_id) => self.join_group(group_id).await?,
+            Command::Send(message) => match self.active_group.clone() {
+                Some(group_id) => self.send_message(&group_id, message).await?,
+                None => self.set_warning("No active group selected"),
+            },
+            Command::Mode(mode) => self.set_group_mode(mode).await?,
+            Command::Members => self.open_roster(),
+            Command::Errors(code) => match code {
+                Some(code) => match errors::lookup(code) {
+                    Some(error) => self.show_error_help(error, None),
+                    None => self.set_warning(format!("Unknown error code {}", code)),
+                },
+                None => {
+                    let lines = errors::ALL
+                        .iter()
+                        .map(|error| format!("{}  {}", error.code, error.summary))
+                        .collect();
+                    self.show_popup("Error codes", lines);
+                }
+            },
+            Command::Retry => self.retry_last_failed().await?,
+            Command::Details => match self.last_error.clone() {
+                Some((error, detail)) => self.show_error_help(error, Some(&detail)),
+                None => self.set_status("No recent errors"),
+            },
+            Command::Whoami => self.show_whoami().await?,
+            Command::Flow(count) => self.show_flow(count.unwrap_or(DEFAULT_FLOW_FRAMES)),
+            Command::Tree => self.show_tree()?,
+            Command::Diff(args) => self.show_membership_diff(&args),
+            Command::Quarantine { clear: false } => self.show_quarantine(),
+            Command::Quarantine { clear: true } => {
+                if let Some(group) = self.active_group.as_ref().and_then(|id| self.groups.get_mut(id)) {
+                    let cleared = std::mem::take(&mut group.quarantine).len();
+                    self.set_status(format!("Dropped {} quarantined message(s)", cleared));
+                    self.save_history().await;
+                }
+            }
+            Command::Cleanup(args) => self.cleanup_command(&args).await?,
+            Command::Inactive(days) => self.show_inactive(days.unwrap_or(self.config.inactive_member_days)),
+            Command::Update(wanted) => {
+                let group_id = match wanted {
+                    Some(wanted) => self.find_group_id(wanted),
+                    None => self.active_group.clone(),
+                };
+                match group_id {
+                    Some(group_id) => self.update_keys(&group_id).await?,
+                    None => self.set_warning("Usage: update [group]"),
+                }
+            }
+            Command::KeyPolicy(args) => self.key_policy_command(&args).await?,
+            Command::Export(args) => self.export_command(&args).await,
+            Command::Remind(args) => self.remind_command(&args).await?,
+            Command::Speak(args) => self.speak_command(&args).await?,
+            Command::Preview(args) => self.preview_command(&args).await?,
+            Command::Translate(args) => self.translate_command(&args).await?,
+            Command::Template(TemplateAction::Save(name)) => self.save_template(name).await?,
+            Command::Template(TemplateAction::Use(name)) => self.use_template(name),
+            Command::Template(TemplateAction::Delete(name)) => {
+                if self.config.templates.remove(name).is_some() {
+                    self.save_config().await?;
+                    self.set_status(format!("Deleted template {}", name));
+                } else {
+                    self.set_warning(format!("No template named {}", name));
+                }
+            }
+            Command::Template(TemplateAction::List) => {
+                let lines = self.config.templates
+                    .iter()
+                    .map(|(name, body)| format!("{}: {}", name, body))
+                    .collect();
+                self.show_popup("Templates", lines);
+            }
+            Command::Nick(nick) => self.set_nickname(&nick).await?,
+            Command::Folder(args) => self.folder_command(&args).await?,
+            Command::Filter(args) => self.filter_command(&args).await?,
+            Command::Account(args) => self.account_command(&args).await?,
+            Command::Tag { group, color, icon } => self.tag_group(group, color, icon).await,
+            Command::Direction(direction) => self.direction_command(direction).await,
+            Command::Undo => self.undo_send().await,
+            Command::Backup(args) => self.backup_command(&args).await,
+            Command::Selftest(args) => self.selftest_command(&args).await,
+            Command::Stress(args) => self.stress_command(&args),
+            Command::Activity(args) => self.activity_command(&args),
+            Command::Quit => self.should_quit = true,
+            Command::Help => self.screen = AppScreen::Help,
+            Command::Dashboard => self.screen = AppScreen::Dashboard,
+            Command::DuressPassphrase { remove: true } => self.set_duress_passphrase(None).await?,
+            Command::DuressPassphrase { remove: false } => self.start_passphrase_prompt(PassphrasePrompt::Duress),
+            Command::Passphrase => self.start_passphrase_prompt(PassphrasePrompt::New),
+            Command::Lock => self.lock(),
+            Command::Wipe => self.confirm_wipe().await?,
+            Command::ShowKeyPackage(identity) => self.show_key_package(identity).await?,
+            Command::RotateIdentity => self.rotate_identity().await?,
+            Command::Dnd(on) => self.set_do_not_disturb(on.unwrap_or(!self.config.do_not_disturb)).await?,
+            Command::QuietHours(args) => self.set_quiet_hours(&args).await?,
+            Command::LowBandwidth(on) => self.set_low_bandwidth(on.unwrap_or(!self.config.low_bandwidth)).await?,
+            Command::EncryptionPreview(on) => self.set_encryption_preview(on.unwrap_or(!self.config.encryption_preview)).await?,
+            Command::Debug(on) => self.set_debug_mode(on.unwrap_or(!self.config.debug_mode)).await?,
+            Command::Commit(action) => {
+                let Some(group_id) = self.active_group.clone() else {
+                    self.set_warning("No active group selected");
+                    return Ok(());
+                };
+                match action {
+                    CommitAction::Preview => self.preview_commit(&group_id),
+                    CommitAction::Send => self.send_held_commit(&group_id).await?,
+                    CommitAction::Abort => self.abort_held_commit(&group_id)?,
+                }
+            }
+            Command::Config(ConfigAction::Check) => self.show_config_issues(),
+            Command::Config(ConfigAction::Repair) => self.repair_config().await?,
+            Command::Config(ConfigAction::Show) => self.show_config(),
+            Command::Config(ConfigAction::Set { setting, value }) => self.set_config(setting, &value).await?,
+            Command::Metrics => {
+                self.show_metrics = !self.show_metrics;
+                self.set_status(format!("Metrics HUD {}", if self.show_metrics { "shown" } else { "hidden" }));
+            }
+            Command::Settings => {
+                self.screen = AppScreen::Settings;
+                self.input_mode = InputMode::Settings;
+            }
+            Command::Groups => {
+                if self.groups.is_empty() {
+                    self.set_status("No local groups available. Use 'create <group_name>' to create a group.");
+                } else {
+                    let groups_info: Vec<String> = self.groups
+                        .iter()
+                        .map(|(id, group)| format!("• {} (ID: {}) - {} members", group.name, id, group.members.len()))
+                        .collect();
+                    self.set_status(format!("Local groups:\n{}", groups_info.join("\n")));
+                }
+            }
+            Command::List => {
+                // List groups from the server
+                match self.network_client.list_groups().await {
+                    Ok(server_groups) => {
+                        if server_groups.is_empty() {
+                            self.set_status("No groups found on server. Use 'create <group_name>' to create a group.");
+                        } else {
+                            let groups_list = server_groups.join("\n• ");
+                            self.set_status(format!("Groups available on server:\n• {}", groups_list));
+                        }
+                    }
+                    Err(e) => {
+                        self.set_error(format!("Failed to list groups from server: {}", e));
+                    }
+                }
+            }
+            Command::Netstat => self.show_netstat(),
+            Command::AcceptInsecure(args) => self.accept_insecure_command(&args).await?,
+            Command::Trust(args) => self.trust_command(&args).await?,
+            Command::TrustSender(args) => self.trust_sender_command(&args).await?,
+            Command::Status => {
+                if self.network_client.is_connected() {
+                    self.set_status(format!("Connected to MLS service at {}{}. {} groups available.", 
+                        self.network_client.endpoint(), self.endpoint_note(), self.groups.len()));
+                } else {
+                    self.set_status(format!("Disconnected from MLS service at {}. Groups will be local only.", 
+                        self.config.delivery_service_address));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// `export <file> [from <date>] [to <date>] [member <identity>]... [age|gpg <recipient>]`:
+    /// write the active group's transcript, encrypted if a recipient is given.
+    async fn export_command(&mut self, args: &[&str]) {
+        const USAGE: &str = "Usage: export <file> [from <YYYY-MM-DD>] [to <YYYY-MM-DD>] [member <identity>]... [age <recipient> | gpg <recipient>]";
+        let Some((path, options)) = args.split_first() else {
+            self.set_warning(USAGE.to_string());
+            return;
+        };
+        let Some(group) = self.active_group.as_ref().and_then(|id| self.groups.get(id)) else {
+            self.set_status("Select a group to export first");
+            return;
+        };
+        let date = |value: &str| NaiveDate::parse_from_str(value, "%Y-%m-%d").ok();
+        let mut range = ExportRange::default();
+        let mut encryption = None;
+        for option in options.chunks(2) {
+            match option {
+                ["from", value] if date(value).is_some() => range.from = date(value),
+                ["to", value] if date(value).is_some() => range.to = date(value),
+                ["member", identity] => range.members.push(identity.to_string()),
+                ["age", recipient] if encryption.is_none() => encryption = Some(ExportEncryption::Age(recipient.to_string())),
+                ["gpg", recipient] if encryption.is_none() => encryption = Some(ExportEncryption::Gpg(recipient.to_string())),
+                _ => {
+                    self.set_warning(USAGE.to_string());
+                    return;
+                }
+            }
+        }
+
+        let group = match self.full_timeline(&group.id).await {
+            Ok(Some(group)) => group,
+            Ok(None) => return,
+            Err(e) => {
+                self.set_error(format!("Export failed: {}", e));
+                return;
+            }
+        };
+        let (transcript, count) = export::transcript(&group, &range, &self.config.username);
+        self.set_status(match export::write(path, &transcript, encryption.as_ref()).await {
+            Ok(()) if encryption.is_some() => format!("Exported {} message(s) from {} to {} (encrypted)", count, group.name, path),
+            Ok(()) => format!("Exported {} message(s) from {} to {} as plain text", count, group.name, path),
+            Err(e) => format!("Export failed: {}", e),
+        });
+    }
+
+    async fn create_group(&mut self, group_name: &str, policy: WirePolicy, mode: GroupMode) -> Result<()> {
+        let group_id = Uuid::new_v4().to_string();
+        
+        // Create MLS group, its mode and admins (just us) in the group context
+        let state = GroupState::new(&self.config.username, mode);
+        let mls_group = self.mls_client.create_group(policy, &state)?;
+
+        // Store the MLS group
+        self.mls_client.add_group(&group_id, mls_group);
+
+        // Store group locally
+        let group = Group {
+            id: group_id.clone(),
+            name: group_name.to_string(),
+            members: vec![self.config.username.clone()],
+            messages: Vec::new(),
+            is_active: true,
+            admins: state.admins,
+            mode: state.mode,
+            unread: 0,
+            unread_mentions: 0,
+            rotation_policy: None,
+            epoch_age: None,
+            onboarding: None,
+            onboarding_pinned: false,
+            archived_pages: 0,
+            seen_ids: SeenIds::default(),
+            nicknames: HashMap::new(),
+            tag: None,
+            joined_at: HashMap::from([(self.config.username.clone(), Local::now())]),
+            wire_policy: policy,
+            removed: false,
+            deleted: None,
+            last_active: HashMap::new(),
+            quarantine: Vec::new(),
+            redactions: Vec::new(),
+            membership_log: Vec::new(),
+            audit_anchor: None,
+            text_direction: TextDirection::default(),
+        };
+        
+        self.groups.insert(group_id.clone(), group);
+        self.active_group = Some(group_id.clone());
+        
+        // Update group list selection
+        self.sync_group_selection(&group_id);
+        
+        // Publish group to MLS service if connected
+        if self.network_client.is_connected() {
+            if let Err(e) = self.publish_group(&group_id, group_name).await {
+                self.report_error(&errors::PUBLISH_FAILED, format!("{} (ID: {}): {}", group_name, group_id, e));
+                self.last_failed = Some(RetryOp::Publish { group_id: group_id.clone(), group_name: group_name.to_string() });
+            } else {
+                self.set_status(format!("Created and published group: {} (ID: {})", group_name, group_id));
+            }
+        } else {
+            self.set_status(format!("Created local group: {} (ID: {}) - not connected to MLS service", group_name, group_id));
+        }
+        self.record_event(format!("Created group {}", group_name));
+        self.save_history().await;
+        
+        Ok(())
+    }
+
+    async fn join_group(&mut self, group_id: &str) -> Result<()> {
+        // Check if we're connected to the MLS service
+        if !self.network_client.is_connected() {
+            self.report_error(&errors::NOT_CONNECTED, format!("cannot join group {}", group_id));
+            self.last_failed = Some(RetryOp::Join { group_id: group_id.to_string() });
+            return Ok(());
+        }
+
+        // Check if we're already in this group
+        if self.groups.get(group_id).is_some_and(|group| !group.removed) {
+            self.set_status(format!("Already in group: {}", group_id));
+            return Ok(());
+        }
+
+        // Try to join the group through the MLS service: the request frame, then the Welcome
+        let task = self.tasks.start(format!("Joining group {}", group_id), 2);
+        let key_package = self.mls_client.key_package.tls_serialize_detached()?;
+        match self.network_client.join_group(group_id, &key_package, &self.config.username, Some(task)).await {
+            Ok(()) => {
+                let Some(requester) = self.network_client.requester() else {
+                    self.tasks.cancel(task);
+                    self.report_error(&errors::JOIN_FAILED, format!("group {}: connection lost", group_id));
+                    self.last_failed = Some(RetryOp::Join { group_id: group_id.to_string() });
+                    return Ok(());
+                };
+                self.set_status(format!("Asked to join {}; waiting for a member's Welcome", group_id));
+                let events = self.events_tx.clone();
+                let group_id = group_id.to_string();
+                tokio::spawn(async move {
+                    let welcome = requester.await_welcome(&group_id).await;
+                    let _ = events.send(AppEvent::Welcome { group_id, task, welcome });
+                });
+            }
+            Err(e) => {
+                self.tasks.cancel(task);
+                self.report_error(&errors::JOIN_FAILED, format!("group {}: {}", group_id, e));
+                self.last_failed = Some(RetryOp::Join { group_id: group_id.to_string() });
+            }
+        }
+        Ok(())
+    }
+
+    /// Ask the delivery service for a single-use token admitting one person to `group_id`.
+    fn request_invite_token(&mut self, group_id: &str) {
+        let Some(group) = self.groups.get(group_id) else {
+            self.set_warning(format!("Unknown group: {}", group_id));
+            return;
+        };
+        if !group.is_admin(&self.config.username) {
+            self.set_status(format!("Only admins can invite members to {}", group.name));
+            return;
+        }
+        let Some(requester) = self.network_client.requester() else {
+            self.report_error(&errors::NOT_CONNECTED, format!("cannot request an invite token for {}", group_id));
+            return;
+        };
+        self.set_status(format!("Requesting an invite token for {}…", group.name));
+        let events = self.events_tx.clone();
+        let group_id = group_id.to_string();
+        tokio::spawn(async move {
+            let token = requester.request_invite_token(&group_id).await;
+            let _ = events.send(AppEvent::InviteToken { group_id, token });
+        });
+    }
+
+    fn invite_token_issued(&mut self, group_id: String, token: Result<Option<String>, NetworkError>) {
+        let token = match token {
+            Ok(Some(token)) => token,
+            Ok(None) => {
+                self.set_status("The delivery service did not issue an invite token (it may not support them)");
+                return;
+            }
+            Err(e) => {
+                self.set_error(format!("Failed to request an invite token: {}", e));
+                return;
+            }
+        };
+        let name = self.groups.get(&group_id).map_or(group_id.clone(), |group| group.name.clone());
+        self.record_event(format!("Issued an invite token for {}", name));
+        self.show_popup(format!("Invite token for {}", name), vec![
+            token.clone(),
+            String::new(),
+            "Share it out-of-band; the recipient runs 'join-token <token>'.".to_string(),
+            "It admits one person, once. Keep this client running: it adds whoever redeems it.".to_string(),
+        ]);
+        self.issued_tokens.insert(token, group_id);
+    }
+
+    /// Redeem an invite token, then wait on a spawned task for the issuing admin's Welcome.
+    fn join_with_token(&mut self, token: &str) {
+        let Some(requester) = self.network_client.requester() else {
+            self.report_error(&errors::NOT_CONNECTED, "cannot redeem an invite token");
+            return;
+        };
+        // Finished by the Welcome alone: the redemption frame is not tracked
+        let task = self.tasks.start("Joining with invite token", 1);
+        self.set_status("Redeeming invite token…");
+        let events = self.events_tx.clone();
+        let token = token.to_string();
+        tokio::spawn(async move {
+            let event = match requester.redeem_invite_token(&token).await {
+                Ok(Some(group_id)) => {
+                    let welcome = requester.await_token_welcome(&group_id).await;
+                    AppEvent::Welcome { group_id, task, welcome }
+                }
+                Ok(None) => AppEvent::TokenRejected { task, reason: "unknown or already used".to_string() },
+                Err(e) => AppEvent::TokenRejected { task, reason: e.to_string() },
+            };
+            let _ = events.send(event);
+        });
+    }
+
+    /// Someone redeemed a token: add them if it is one we issued and we still administer the group.
+    fn token_redeemed(&mut self, group_id: &str, identity: &str, token: &[u8]) {
+        let token = String::from_utf8_lossy(token).into_owned();
+        if self.issued_tokens.get(&token).map(String::as_str) != Some(group_id) {
+            return;
+        }
+        self.issued_tokens.remove(&token);
+        let Some(group) = self.groups.get(group_id).filter(|group| !group.is_read_only() && group.is_admin(&self.config.username)) else {
+            return;
+        };
+        let joined = format!("{} redeemed an invite token for {}; adding them", identity, group.name);
+        self.record_event(joined.clone());
+        self.set_status(joined);
+        let share = (self.config.share_history_on_invite > 0).then_some(HistoryShare::Last(self.config.share_history_on_invite));
+        self.request_key_package(identity, KeyPackagePurpose::Invite { group_id: group_id.to_string(), share });
+    }
+
+    /// Join from the Welcome awaited after `join_group`.
+    async fn finish_join(&mut self, group_id: &str, task: TaskId, welcome: Option<Vec<u8>>) -> Result<()> {
+        let Some(welcome_data) = welcome else {
+            self.tasks.cancel(task);
+            self.report_error(&errors::GROUP_NOT_FOUND, group_id);
+            return Ok(());
+        };
+        if let Some(finished) = self.tasks.advance(task) {
+            self.record_event(finished);
+        }
+        // Parse the welcome message and join the MLS group, following the inviter's policy
+        let policy = self.invited_wire_policies.remove(group_id).unwrap_or(self.config.default_wire_policy);
+        match self.mls_client.join_from_welcome(group_id, &welcome_data, policy) {
+            Ok(()) => {
+                // A group we were removed from keeps its timeline
+                if let Some(group) = self.groups.get_mut(group_id).filter(|group| group.removed) {
+                    group.removed = false;
+                    group.wire_policy = policy;
+                    group.members = vec![self.config.username.clone()];
+                } else {
+                    // Create local group representation
+                    let group = Group {
+                        id: group_id.to_string(),
+                        name: format!("Group {}", group_id),
+                        members: vec![self.config.username.clone()], // Will be updated with real members
+                        messages: Vec::new(),
+                        is_active: true,
+                        admins: Vec::new(), // Read from the group context below
+                        mode: GroupMode::Chat,
+                        unread: 0,
+                        unread_mentions: 0,
+                        rotation_policy: None,
+                        epoch_age: None,
+                        onboarding: None,
+                        onboarding_pinned: false,
+                        archived_pages: 0,
+                        seen_ids: SeenIds::default(),
+                        nicknames: HashMap::new(),
+                        tag: None,
+                        joined_at: HashMap::from([(self.config.username.clone(), Local::now())]),
+                        wire_policy: policy,
+                        removed: false,
+                        deleted: None,
+                        last_active: HashMap::new(),
+                        quarantine: Vec::new(),
+                        redactions: Vec::new(),
+                        membership_log: Vec::new(),
+                        audit_anchor: None,
+                        text_direction: TextDirection::default(),
+                    };
+
+                    self.groups.insert(group_id.to_string(), group);
+                }
+                self.sync_group_state(group_id);
+                self.active_group = Some(group_id.to_string());
+                self.pending_invitations.retain(|pending| pending != group_id);
+                
+                // Update group list selection
+                self.sync_group_selection(group_id);
+                
+                self.set_status(format!("Successfully joined group: {} (Welcome message received)", group_id));
+                self.record_event(format!("Joined group {}", group_id));
+                self.save_history().await;
+            }
+            Err(MlsError::PolicyViolation { reason, .. }) => {
+                self.security_alert(format!("refused the Welcome to group {}", group_id), vec![
+                    format!("Reason: {}", reason),
+                    "The group was not joined. Ask the inviter about the member named above, or relax".to_string(),
+                    "handshake_policy and join again.".to_string(),
+                ]);
+            }
+            Err(e) => {
+                self.report_error(&errors::INVALID_WELCOME, format!("group {}: {}", group_id, e));
+            }
+        }
+        Ok(())
+    }
+
+    /// Right-aligned composer title while writing a message: characters, bytes against the
+    /// limit and the estimated encrypted size, yellow from 90% of the limit and red over it.
+    fn composer_counter(&self) -> Title<'static> {
+        if !matches!(self.input_mode, InputMode::Message) || self.input.is_empty() {
+            return Title::default();
+        }
+        let bytes = self.input.len();
+        let limit = self.max_message_bytes();
+        let group_id = self.active_group.as_deref().unwrap_or_default();
+        let color = if bytes > limit {
+            Color::Red
+        } else if bytes.saturating_mul(10) >= limit.saturating_mul(9) {
+            Color::Yellow
+        } else {
+            Color::Gray
+        };
+        let text = format!(
+            " {} chars · {}/{} B · ≈{} B encrypted ",
+            self.input.chars().count(),
+            bytes,
+            limit,
+            mls_client::ciphertext_estimate(group_id, bytes)
+        );
+        Title::from(Span::styled(text, Style::default().fg(color))).alignment(Alignment::Right)
+    }
+
+    /// Repeat the last failed delivery-service operation, reconnecting first if needed.
+    async fn retry_last_failed(&mut self) -> Result<()> {
+        let Some(op) = self.last_failed.take() else {
+            self.set_status("Nothing to retry");
+            return Ok(());
+        };
+        if !self.network_client.is_connected() {
+            let status = self.network_client.connect().await?;
+            if !self.network_client.is_connected() {
+                self.set_error(format!("Nothing retried: {}", status.describe()));
+                self.last_failed = Some(op);
+                return Ok(());
+            }
+        }
+        match op {
+            RetryOp::Publish { group_id, group_name } => {
+                match self.publish_group(&group_id, &group_name).await {
+                    Ok(()) => self.set_status(format!("Published group: {} (ID: {})", group_name, group_id)),
+                    Err(e) => {
+                        self.report_error(&errors::PUBLISH_FAILED, format!("{} (ID: {}): {}", group_name, group_id, e));
+                        self.last_failed = Some(RetryOp::Publish { group_id, group_name });
+                    }
+                }
+            }
+            RetryOp::Join { group_id } => self.join_group(&group_id).await?,
+            RetryOp::Send { group_id, message_id, message } => self.send_message_as(&group_id, message_id, &message).await?,
+        }
+        Ok(())
+    }
+
+    /// Hand the group's signed GroupInfo, ratchet tree included, to the delivery service.
+    async fn publish_group(&mut self, group_id: &str, group_name: &str) -> Result<()> {
+        let group_info = self.mls_client.export_group_info(group_id)?;
+        let task = self.tasks.start(format!("Publishing group {}", group_name), 1);
+        if let Err(e) = self.network_client.create_group(group_id, &group_info, &self.config.username, Some(task)).await {
+            self.tasks.cancel(task);
+            return Err(e.into());
+        }
+        Ok(())
+    }
+
+    /// Fold writer-task progress into the task tracker.
+    pub fn poll_progress(&mut self) {
+        for event in self.network_client.drain_progress() {
+            if let Some(finished) = self.tasks.apply(event) {
+                self.record_event(finished);
+            }
+        }
+    }
+
+    /// One-line status for a coded failure; the full explanation is kept for `details`.
+    fn report_error(&mut self, error: &'static ErrorCode, detail: impl Into<String>) {
+        let detail = detail.into();
+        self.set_error(format!("[{}] {}: {} ('details' for help)", error.code, error.summary, detail));
+        self.last_error = Some((error, detail));
+    }
+
+    fn show_error_help(&mut self, error: &ErrorCode, detail: Option<&str>) {
+        let mut lines = Vec::new();
+        if let Some(detail) = detail {
+            lines.push(format!("Detail: {}", detail));
+            lines.push(String::new());
+        }
+        lines.extend(error.explanation.iter().map(|line| line.to_string()));
+        self.show_popup(format!("{}: {}", error.code, error.summary), lines);
+    }
+
+    pub fn show_popup(&mut self, title: impl Into<String>, lines: Vec<String>) {
+        self.popup = Some(Popup { title: title.into(), lines, actions: Vec::new() });
+        self.screen = AppScreen::Popup;
+    }
+
+    pub async fn handle_popup_input(&mut self, key: KeyCode) -> Result<()> {
+        let popup = self.popup.take();
+        self.screen = AppScreen::Main;
+        let action = popup.and_then(|popup| {
+            popup.actions.into_iter().find(|action| KeyCode::Char(action.key()) == key)
+        });
+        match action {
+            Some(PopupAction::DirectMessage { identity }) => self.direct_message(&identity).await?,
+            Some(PopupAction::Invite { group_id, identity }) => {
+                let share = (self.config.share_history_on_invite > 0).then_some(HistoryShare::Last(self.config.share_history_on_invite));
+                self.request_key_package(&identity, KeyPackagePurpose::Invite { group_id, share });
+            }
+            Some(PopupAction::Verify { identity, fingerprint }) => {
+                self.config.verified_keys.insert(identity.clone(), fingerprint);
+                self.save_config().await?;
+                self.set_status(format!("Marked {}'s signature key as verified", identity));
+                self.record_event(format!("Verified {}", identity));
+            }
+            Some(PopupAction::Kick { group_id, identity }) => self.confirm_kick(&group_id, vec![identity]).await?,
+            Some(PopupAction::RemoveInactive { group_id, identities }) => self.confirm_kick(&group_id, identities).await?,
+            Some(PopupAction::RepairConfig) => self.repair_config().await?,
+            Some(PopupAction::AcceptInsecure { endpoint, fingerprint }) => self.accept_insecure(&endpoint, &fingerprint).await?,
+            Some(PopupAction::TrustSender { identity, automations }) => self.allow_sender(&identity, automations).await?,
+            Some(PopupAction::Relax { group_id, identity }) => self.relax_and_add(&group_id, &identity).await?,
+            Some(PopupAction::Forget { group_id }) => self.confirm_forget(&group_id).await?,
+            Some(PopupAction::Rejoin { group_id }) => self.join_group(&group_id).await?,
+            Some(PopupAction::Export { group_id }) => self.start_export(&group_id),
+            Some(PopupAction::SendCommit { group_id }) => self.send_held_commit(&group_id).await?,
+            Some(PopupAction::AbortCommit { group_id }) => self.abort_held_commit(&group_id)?,
+            Some(PopupAction::SendMessage { group_id }) => self.send_held_message(&group_id).await?,
+            Some(PopupAction::DiscardMessage { group_id }) => self.discard_held_message(&group_id).await,
+            None => {}
+        }
+        Ok(())
+    }
+
+    fn open_roster(&mut self) {
+        if self.active_group.as_ref().and_then(|id| self.groups.get(id)).is_none() {
+            self.set_warning("No active group selected");
+            return;
+        }
+        self.roster_state.select(Some(0));
+        self.screen = AppScreen::Roster;
+    }
+
+    pub async fn handle_roster_input(&mut self, key: KeyCode) -> Result<()> {
+        let Some(group) = self.active_group.as_ref().and_then(|id| self.groups.get(id)) else {
+            self.screen = AppScreen::Main;
+            return Ok(());
+        };
+        let count = group.members.len();
+        let selected = self.roster_state.selected().unwrap_or(0);
+        match key {
+            KeyCode::Up if count > 0 => {
+                self.roster_state.select(Some(if selected > 0 { selected - 1 } else { count - 1 }));
+            }
+            KeyCode::Down if count > 0 => {
+                self.roster_state.select(Some((selected + 1) % count));
+            }
+            KeyCode::Enter => {
+                if let Some(member) = group.members.get(selected).cloned() {
+                    let group_id = group.id.clone();
+                    self.show_member_info(&group_id, &member)?;
+                }
+            }
+            KeyCode::Esc | KeyCode::Char('q') => self.screen = AppScreen::Main,
+            _ => {}
+        }
+        Ok(())
+    }
+
+    /// Whether the member's current key matches the fingerprint we confirmed out-of-band.
+    fn verification_status(&self, identity: &str, fingerprint: Option<&str>) -> &'static str {
+        match (self.config.verified_keys.get(identity), fingerprint) {
+            (None, _) => "unverified",
+            (Some(_), None) => "verified (key not in MLS group state)",
+            (Some(verified), Some(current)) if verified == current => "verified",
+            (Some(_), Some(_)) => "KEY CHANGED since verification",
+        }
+    }
+
+    fn show_member_info(&mut self, group_id: &str, identity: &str) -> Result<()> {
+        let Some(group) = self.groups.get(group_id) else {
+            return Ok(());
+        };
+        let member = self.mls_client.find_member(group_id, identity);
+        let fingerprint = member
+            .as_ref()
+            .map(|member| self.crypto.fingerprint(member.signature_key.as_slice()))
+            .transpose()?;
+
+        let lines = vec![
+            format!("Identity:     {}", identity),
+            format!("Display name: {}", group.display_name(identity)),
+            format!("Role:         {}", if group.is_admin(identity) { "admin" } else { "member" }),
+            format!(
+                "Leaf index:   {}",
+                member.as_ref().map(|m| m.index.u32().to_string()).unwrap_or_else(|| "unknown".to_string())
+            ),
+            format!("Fingerprint:  {}", fingerprint.as_deref().unwrap_or("unknown")),
+            format!("Verification: {}", self.verification_status(identity, fingerprint.as_deref())),
+            format!(
+                "Joined:       {}",
+                group.joined(identity)
+                    .map(|t| t.format("%Y-%m-%d %H:%M:%S").to_string())
+                    .unwrap_or_else(|| "before this device joined".to_string())
+            ),
+        ];
+
+        let mut actions = Vec::new();
+        if identity != self.config.username {
+            actions.push(PopupAction::DirectMessage { identity: identity.to_string() });
+            if let Some(fingerprint) = fingerprint {
+                actions.push(PopupAction::Verify { identity: identity.to_string(), fingerprint });
+            }
+            if group.is_admin(&self.config.username) {
+                actions.push(PopupAction::Kick { group_id: group_id.to_string(), identity: identity.to_string() });
+            }
+        }
+
+        self.popup = Some(Popup { title: format!("Member: {}", identity), lines, actions });
+        self.screen = AppScreen::Popup;
+        Ok(())
+    }
+
+    /// Start a two-person group with `identity` once one of their key packages is fetched.
+    async fn direct_message(&mut self, identity: &str) -> Result<()> {
+        if !self.network_client.is_connected() {
+            self.set_error(format!("Cannot message {}: not connected to MLS service", identity));
+            return Ok(());
+        }
+        self.request_key_package(identity, KeyPackagePurpose::DirectMessage);
+        Ok(())
+    }
+
+    async fn start_direct_message(&mut self, identity: &str, key_package: &KeyPackage) -> Result<()> {
+        self.create_group(&format!("dm-{}", identity), self.config.default_wire_policy, GroupMode::Chat).await?;
+        let Some(group_id) = self.active_group.clone() else {
+            return Ok(());
+        };
+        if self.add_to_group(&group_id, identity, key_package, None).await? {
+            self.set_status(format!("Started a direct message with {}", identity));
+        }
+        Ok(())
+    }
+
+    /// Add `identity` to the active group; they get a Welcome and an invitation to accept, plus
+    /// the earlier messages `share` selects.
+    async fn invite_member(&mut self, identity: &str, share: Option<HistoryShare>) -> Result<()> {
+        let Some(group_id) = self.active_group.clone() else {
+            self.set_warning("No active group selected");
+            return Ok(());
+        };
+        let Some(group) = self.groups.get(&group_id) else {
+            return Ok(());
+        };
+        if !group.is_admin(&self.config.username) {
+            self.set_status(format!("Only admins can invite members to {}", group.name));
+            return Ok(());
+        }
+        if !self.network_client.is_connected() {
+            self.report_error(&errors::NOT_CONNECTED, format!("cannot invite {}", identity));
+            return Ok(());
+        }
+        self.request_key_package(identity, KeyPackagePurpose::Invite { group_id, share });
+        Ok(())
+    }
+
+    /// Fetch `identity`'s key packages on a spawned task; `poll_events` carries on with `purpose`.
+    fn request_key_package(&mut self, identity: &str, purpose: KeyPackagePurpose) {
+        let Some(requester) = self.network_client.requester() else {
+            self.report_error(&errors::NOT_CONNECTED, format!("cannot fetch {}'s key package", identity));
+            return;
+        };
+        let events = self.events_tx.clone();
+        let identity = identity.to_string();
+        self.set_status(format!("Fetching {}'s key package…", identity));
+        tokio::spawn(async move {
+            let packages = requester.fetch_key_packages(&identity).await;
+            let _ = events.send(AppEvent::KeyPackages { identity, purpose, packages });
+        });
+    }
+
+    async fn key_package_fetched(&mut self, identity: &str, purpose: KeyPackagePurpose, packages: Vec<Vec<u8>>) -> Result<()> {
+        let Some(data) = packages.first() else {
+            self.set_status(format!("No key packages published for {}", identity));
+            return Ok(());
+        };
+        let key_package = mls_client::decode_key_package(&self.mls_client.crypto, data)?;
+        match purpose {
+            KeyPackagePurpose::Invite { group_id, share } => {
+                self.add_to_group(&group_id, identity, &key_package, share).await?;
+            }
+            KeyPackagePurpose::DirectMessage => self.start_direct_message(identity, &key_package).await?,
+            KeyPackagePurpose::Relax { group_id } => self.relax_then_add(&group_id, identity, &key_package).await?,
+        }
+        Ok(())
+    }
+
+    /// Apply what spawned tasks finished since the last call.
+    pub async fn poll_events(&mut self) -> Result<()> {
+        if let Some(e) = self.speaker.take_error() {
+            self.set_error(format!("Speech failed: {}", e));
+        }
+        while let Ok(event) = self.events_rx.try_recv() {
+            match event {
+                AppEvent::Welcome { group_id, task, welcome } => self.finish_join(&group_id, task, welcome).await?,
+                AppEvent::KeyPackages { identity, purpose, packages } => match packages {
+                    Ok(packages) => self.key_package_fetched(&identity, purpose, packages).await?,
+                    Err(e) => self.set_error(format!("Failed to fetch {}'s key package: {}", identity, e)),
+                },
+                AppEvent::InviteToken { group_id, token } => self.invite_token_issued(group_id, token),
+                AppEvent::TokenRejected { task, reason } => {
+                    self.tasks.cancel(task);
+                    self.report_error(&errors::INVITE_TOKEN_REJECTED, reason);
+                }
+                AppEvent::Translated { group_id, message_id, translation } => {
+                    self.translation_finished(&group_id, &message_id, translation).await;
+                }
+                AppEvent::PreviewFetched { url, preview } => {
+                    self.preview_fetched(url, preview).await;
+                }
+                AppEvent::Directory { query, entries } => self.directory_results(&query, entries),
+                AppEvent::BackedUp { task, outcome } => self.backup_finished(task, outcome).await,
+                AppEvent::AttachmentStored { group_id, message_id, task, stored } => {
+                    self.attachment_stored(group_id, message_id, task, stored).await;
+                }
+                AppEvent::AttachmentFetched { pending, fetched } => self.attachment_fetched(*pending, fetched),
+                AppEvent::BulkKeyPackages { group_id, identity, packages } => {
+                    self.bulk_key_packages(&group_id, identity, packages).await;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Commit an Add for `identity` and deliver their Welcome, then re-share what `share` selects;
+    /// `false` if their key package does not fit the group (explained in a popup), the commit is
+    /// held for review, or sending failed.
+    async fn add_to_group(
+        &mut self,
+        group_id: &str,
+        identity: &str,
+        key_package: &KeyPackage,
+        share: Option<HistoryShare>,
+    ) -> Result<bool> {
+        let mismatches = self.mls_client.check_compatibility(group_id, key_package)?;
+        if !mismatches.is_empty() {
+            self.explain_mismatches(group_id, identity, &mismatches);
+            return Ok(false);
+        }
+        let group_id = group_id.to_string();
+        let (commit, welcome) = self.mls_client.add_member(&group_id, key_package)?;
+        let commit_message = NetworkMessage::for_group("commit", &self.config.username, &group_id, commit);
+        let mut welcome_message = NetworkMessage::for_group("welcome", &self.config.username, &group_id, welcome);
+        welcome_message.recipient = Some(identity.to_string());
+        // The Welcome does not say which wire formats the group uses, so the invitation does
+        let policy = self.groups.get(&group_id).map(|group| group.wire_policy).unwrap_or_default();
+        let mut invitation = NetworkMessage::for_group("invitation", &self.config.username, &group_id, policy.as_str().as_bytes().to_vec());
+        invitation.recipient = Some(identity.to_string());
+        let effect = CommitEffect::Add { identities: vec![identity.to_string()], share };
+        self.publish_commit(&group_id, vec![commit_message, welcome_message, invitation], effect).await
+    }
+
+    /// Show why `identity` cannot be added, offering to relax the group's requirements when
+    /// every mismatch is a requirement we may drop and we are an admin.
+    fn explain_mismatches(&mut self, group_id: &str, identity: &str, mismatches: &[Mismatch]) {
+        let is_admin = self.groups.get(group_id).is_some_and(|group| group.is_admin(&self.config.username));
+        let relaxable = mismatches.iter().all(Mismatch::relaxable);
+        let mut lines: Vec<String> = mismatches.iter().map(|mismatch| format!("  - {}", mismatch.describe())).collect();
+        lines.insert(0, format!("{}'s key package does not meet this group's requirements:", identity));
+        lines.push(String::new());
+        let mut actions = Vec::new();
+        if !relaxable {
+            lines.push("A group's ciphersuite cannot change; they need a key package for the group's ciphersuite.".to_string());
+        } else if !is_admin {
+            lines.push("Only admins can relax the group's required capabilities.".to_string());
+        } else {
+            lines.push("Relaxing drops these requirements for every member (a group context extensions commit).".to_string());
+            actions.push(PopupAction::Relax { group_id: group_id.to_string(), identity: identity.to_string() });
+        }
+        self.set_error(format!("Cannot add {}: {} incompatibility(ies)", identity, mismatches.len()));
+        self.popup = Some(Popup { title: format!("Cannot add {}", identity), lines, actions });
+        self.screen = AppScreen::Popup;
+    }
+
+    /// Commit relaxed required capabilities for `identity`'s key package, then add them.
+    async fn relax_and_add(&mut self, group_id: &str, identity: &str) -> Result<()> {
+        if !self.network_client.is_connected() {
+            self.report_error(&errors::NOT_CONNECTED, format!("cannot relax requirements for {}", identity));
+            return Ok(());
+        }
+        self.request_key_package(identity, KeyPackagePurpose::Relax { group_id: group_id.to_string() });
+        Ok(())
+    }
+
+    async fn relax_then_add(&mut self, group_id: &str, identity: &str, key_package: &KeyPackage) -> Result<()> {
+        let mismatches = self.mls_client.check_compatibility(group_id, key_package)?;
+        if !mismatches.iter().all(Mismatch::relaxable) {
+            self.explain_mismatches(group_id, identity, &mismatches);
+            return Ok(());
+        }
+        if !mismatches.is_empty() {
+            let commit = self.mls_client.relax_requirements(group_id, &mismatches)?;
+            let network_message = NetworkMessage::for_group("commit", &self.config.username, group_id, commit);
+            let effect = CommitEffect::Relax { identity: identity.to_string() };
+            if !self.publish_commit(group_id, vec![network_message], effect).await? {
+                // Held for review: the Add follows with `invite` once this commit is sent
+                return Ok(());
+            }
+        }
+        if self.add_to_group(group_id, identity, key_package, None).await? {
+            self.set_status(format!("Relaxed requirements and invited {}", identity));
+        }
+        Ok(())
+    }
+
+    /// Move the group to a new epoch with a commit refreshing our leaf's keys.
+    async fn update_keys(&mut self, group_id: &str) -> Result<()> {
+        let Some(group) = self.groups.get(group_id) else {
+            return Ok(());
+        };
+        if group.removed {
+            self.set_status(format!("You were removed from {}", group.name));
+            return Ok(());
+        }
+        if let Some(deletion) = &group.deleted {
+            self.set_status(format!("{} {}: it is read-only", group.name, deletion.describe()));
+            return Ok(());
+        }
+        if self.held_commits.contains_key(group_id) {
+            self.set_status(format!("A commit is held for review in {}; send or abort it first", group.name));
+            return Ok(());
+        }
+        if !self.network_client.is_connected() {
+            let detail = format!("cannot rotate the keys of {}", group.name);
+            self.report_error(&errors::NOT_CONNECTED, detail);
+            return Ok(());
+        }
+        let commit = self.mls_client.stage_self_update(group_id)?;
+        let network_message = NetworkMessage::for_group("commit", &self.config.username, group_id, commit);
+        self.publish_commit(group_id, vec![network_message], CommitEffect::Update).await?;
+        Ok(())
+    }
+
+    /// Restart epoch ages after commits, then rotate the keys of groups whose policy is
+    /// violated and set to commit automatically; other overdue groups only show the badge.
+    async fn enforce_rotation_policies(&mut self) -> Result<()> {
+        let mut changed = false;
+        let mut due = Vec::new();
+        for (group_id, group) in self.groups.iter_mut() {
+            let Some(epoch) = self.mls_client.epoch(group_id) else {
+                continue;
+            };
+            if group.epoch_age.as_ref().map(|age| age.epoch) != Some(epoch) {
+                group.track_epoch(epoch);
+                changed = true;
+            }
+            let auto = group.rotation_policy.is_some_and(|policy| policy.auto_commit)
+                && group.is_admin(&self.config.username)
+                && !group.is_read_only();
+            if auto && group.rotation_overdue().is_some() && !self.held_commits.contains_key(group_id) {
+                due.push(group_id.clone());
+            }
+        }
+        if changed {
+            self.save_history().await;
+        }
+        for group_id in due {
+            if self.auto_rotation_tried.get(&group_id).is_some_and(|at| at.elapsed() < AUTO_ROTATION_RETRY) {
+                continue;
+            }
+            self.auto_rotation_tried.insert(group_id.clone(), Instant::now());
+            self.update_keys(&group_id).await?;
+        }
+        Ok(())
+    }
+
+    /// `key-policy [days <n>] [messages <n>] [auto]` sets the active group's rotation policy,
+    /// `key-policy off` removes it, and no arguments shows it with the epoch's age.
+    async fn key_policy_command(&mut self, args: &[&str]) -> Result<()> {
+        const USAGE: &str = "Usage: key-policy [days <n>] [messages <n>] [auto] | key-policy off";
+        let Some(group_id) = self.active_group.clone() else {
+            self.set_warning("No active group selected");
+            return Ok(());
+        };
+        let policy = match args {
+            [] => {
+                self.show_key_policy(&group_id);
+                return Ok(());
+            }
+            ["off"] => None,
+            _ => {
+                let mut policy = RotationPolicy { max_days: 0, max_messages: 0, auto_commit: false };
+                let mut rest = args;
+                loop {
+                    rest = match rest {
+                        [] => break,
+                        ["days", days, tail @ ..] if days.parse::<u64>().is_ok() => {
+                            policy.max_days = days.parse().unwrap_or_default();
+                            tail
+                        }
+                        ["messages", count, tail @ ..] if count.parse::<u64>().is_ok() => {
+                            policy.max_messages = count.parse().unwrap_or_default();
+                            tail
+                        }
+                        ["auto", tail @ ..] => {
+                            policy.auto_commit = true;
+                            tail
+                        }
+                        _ => {
+                            self.set_warning(USAGE.to_string());
+                            return Ok(());
+                        }
+                    };
+                }
+                if policy.max_days == 0 && policy.max_messages == 0 {
+                    self.set_warning(USAGE.to_string());
+                    return Ok(());
+                }
+                Some(policy)
+            }
+        };
+        let Some(group) = self.groups.get_mut(&group_id) else {
+            return Ok(());
+        };
+        group.rotation_policy = policy;
+        self.activity.push(Severity::Info, match policy {
+            Some(policy) if policy.auto_commit && !group.is_admin(&self.config.username) => format!(
+                "{}: {} (automatic rotation only applies in groups you administer)",
+                group.name,
+                policy.describe()
+            ),
+            Some(policy) => format!("{}: {}", group.name, policy.describe()),
+            None => format!("Removed the key rotation policy of {}", group.name),
+        });
+        self.save_history().await;
+        Ok(())
+    }
+
+    fn show_key_policy(&mut self, group_id: &str) {
+        let Some(group) = self.groups.get(group_id) else {
+            return;
+        };
+        let policy = group.rotation_policy.map(|policy| policy.describe()).unwrap_or_else(|| "no key rotation policy".to_string());
+        let age = group.epoch_age.as_ref().map(|age| {
+            let days = (Local::now() - age.since).num_days();
+            format!("; epoch {} for {} day(s) and {} message(s)", age.epoch, days, age.messages)
+        });
+        let overdue = group.rotation_overdue().map(|reason| format!("; overdue ({}): press u to update", reason));
+        self.set_status(format!("{}: {}{}{}", group.name, policy, age.unwrap_or_default(), overdue.unwrap_or_default()));
+    }
+
+    /// Remove `identities` from the group in a single commit.
+    async fn kick_members(&mut self, group_id: &str, identities: Vec<String>) -> Result<()> {
+        let Some(group) = self.groups.get(group_id) else {
+            return Ok(());
+        };
+        if !group.is_admin(&self.config.username) {
+            self.set_status(format!("Only admins can remove members from {}", group.name));
+            return Ok(());
+        }
+        if !self.network_client.is_connected() {
+            self.set_error(format!("Cannot remove {}: not connected to MLS service", identities.join(", ")));
+            return Ok(());
+        }
+
+        let commit = self.mls_client.remove_members(group_id, &identities)?;
+        let network_message = NetworkMessage::for_group("commit", &self.config.username, group_id, commit);
+        self.publish_commit(group_id, vec![network_message], CommitEffect::Remove { identities }).await?;
+        Ok(())
+    }
+
+    /// Decode my key package, or a peer's fetched from the DS, into a popup.
+    async fn show_key_package(&mut self, identity: Option<&str>) -> Result<()> {
+        let identity = identity.unwrap_or(&self.config.username).to_string();
+        let key_package = if identity == self.config.username {
+            self.mls_client.key_package.clone()
+        } else {
+            if !self.network_client.is_connected() {
+                self.set_error(format!("Cannot fetch key package for {}: not connected to MLS service", identity));
+                return Ok(());
+            }
+            let packages = match self.network_client.fetch_key_packages(&identity).await {
+                Ok(packages) => packages,
+                Err(e) => {
+                    self.set_error(format!("Failed to fetch key packages for {}: {}", identity, e));
+                    return Ok(());
+                }
+            };
+            let Some(data) = packages.first() else {
+                self.set_status(format!("No key packages published for {}", identity));
+                return Ok(());
+            };
+            match mls_client::decode_key_package(&self.mls_client.crypto, data) {
+                Ok(key_package) => key_package,
+                Err(e) => {
+                    self.set_warning(format!("Key package for {} is invalid: {}", identity, e));
+                    return Ok(());
+                }
+            }
+        };
+
+        let lines = mls_client::describe_key_package(&self.mls_client.crypto, &key_package);
+        self.show_popup(format!("Key package: {}", identity), lines);
+        Ok(())
+    }
+
+    /// Identity, fingerprints and local paths for out-of-band verification with peers.
+    /// Draw the recent protocol exchanges of the active group as a sequence diagram.
+    fn show_flow(&mut self, count: usize) {
+        let Some(group_id) = self.active_group.clone() else {
+            self.set_warning("No active group selected");
+            return;
+        };
+        let entries = self.network_client.trace(&group_id, count);
+        if entries.is_empty() {
+            self.set_status("No protocol frames recorded for this group yet");
+            return;
+        }
+        let me = String::from_utf8_lossy(self.mls_client.credential.identity()).to_string();
+        let name = self.groups.get(&group_id).map(|group| group.name.clone()).unwrap_or(group_id);
+        self.show_popup(format!("Protocol flow: {}", name), trace::sequence_diagram(&me, &entries));
+    }
+
+    /// List members of the active group not heard from in `days`; admins may remove them all in
+    /// one commit, which also rotates the group's keys away from their stale leaves.
+    fn show_inactive(&mut self, days: u64) {
+        let Some(group) = self.active_group.as_ref().and_then(|id| self.groups.get(id)) else {
+            self.set_warning("No active group selected");
+            return;
+        };
+        // Capped at a century so the subtraction cannot overflow
+        let cutoff = Local::now() - chrono::Duration::days(days.min(36_500) as i64);
+        let inactive = group.inactive_since(&self.config.username, cutoff);
+        if inactive.is_empty() {
+            self.set_status(format!("Every member of {} was active in the last {} days", group.name, days));
+            return;
+        }
+        let mut lines = vec![format!("Members silent for more than {} days:", days), String::new()];
+        lines.extend(inactive.iter().map(|(member, seen)| {
+            let days_ago = (Local::now() - *seen).num_days();
+            let seen = if group.last_active.contains_key(member) { "last active" } else { "joined, never active," };
+            format!("  {:<20} {} {} days ago", group.display_name(member), seen, days_ago)
+        }));
+        let mut actions = Vec::new();
+        if group.is_admin(&self.config.username) {
+            lines.push(String::new());
+            lines.push("Removing them rotates the group's keys so their stale leaves stop holding group secrets.".to_string());
+            actions.push(PopupAction::RemoveInactive {
+                group_id: group.id.clone(),
+                identities: inactive.into_iter().map(|(member, _)| member).collect(),
+            });
+        }
+        let title = format!("Inactive members: {}", group.name);
+        self.popup = Some(Popup { title, lines, actions });
+        self.screen = AppScreen::Popup;
+    }
+
+    fn show_tree(&mut self) -> Result<()> {
+        let Some(group_id) = self.active_group.clone() else {
+            self.set_warning("No active group selected");
+            return Ok(());
+        };
+        let (epoch, own_leaf, nodes) = self.mls_client.ratchet_tree(&group_id)?;
+        let leaves = nodes.len().div_ceil(2);
+        let members = nodes.iter().step_by(2).filter(|node| node.is_some()).count();
+        let mut lines = vec![
+            format!("Epoch {}, {} members in {} leaves", epoch, members, leaves),
+            String::new(),
+        ];
+        lines.extend(ratchet_tree::render(&nodes, Some(own_leaf)));
+        let name = self.groups.get(&group_id).map(|group| group.name.clone()).unwrap_or(group_id);
+        self.show_popup(format!("Ratchet tree: {}", name), lines);
+        Ok(())
+    }
+
+    /// `diff <group> <epoch_a> <epoch_b>`: who was added, removed and updated between two epochs,
+    /// and by whom, from the group's membership log.
+    fn show_membership_diff(&mut self, parts: &[&str]) {
+        let (Some(wanted), Some(Ok(a)), Some(Ok(b))) = (
+            parts.first(),
+            parts.get(1).map(|epoch| epoch.parse::<u64>()),
+            parts.get(2).map(|epoch| epoch.parse::<u64>()),
+        ) else {
+            self.set_warning("Usage: diff <group> <epoch_a> <epoch_b>");
+            return;
+        };
+        let Some(group) = self.find_group_id(wanted).and_then(|id| self.groups.get(&id)) else {
+            self.set_warning(format!("No group named {}", wanted));
+            return;
+        };
+        let (from, to) = (a.min(b), a.max(b));
+        let diff = group.membership_diff(from, to);
+
+        let mut lines = vec![format!("Epoch {} → epoch {}", from, to), String::new()];
+        for change in &diff.changes {
+            let mut effects = Vec::new();
+            if !change.added.is_empty() {
+                effects.push(format!("added {}", change.added.join(", ")));
+            }
+            if !change.removed.is_empty() {
+                effects.push(format!("removed {}", change.removed.join(", ")));
+            }
+            if !change.updated.is_empty() {
+                effects.push(format!("updated {}", change.updated.join(", ")));
+            }
+            if effects.is_empty() {
+                effects.push("no membership change".to_string());
+            }
+            lines.push(format!(
+                "Epoch {:>4}  {}  by {}: {}",
+                change.epoch,
+                change.at.format("%Y-%m-%d %H:%M"),
+                change.committer,
+                effects.join("; ")
+            ));
+        }
+        if diff.changes.is_empty() {
+            lines.push("No commits logged in this range".to_string());
+        }
+        lines.push(String::new());
+        lines.push(format!("Joined: {}", if diff.joined.is_empty() { "nobody".to_string() } else { diff.joined.join(", ") }));
+        lines.push(format!("Left:   {}", if diff.left.is_empty() { "nobody".to_string() } else { diff.left.join(", ") }));
+        if !diff.missing.is_empty() {
+            lines.push(String::new());
+            lines.push(format!(
+                "Not in the log: epoch(s) {} (before this device joined, or commits it never processed)",
+                epoch_ranges(&diff.missing)
+            ));
+        }
+        let title = format!("Membership diff: {}", group.name);
+        self.show_popup(title, lines);
+    }
+
+    async fn show_whoami(&mut self) -> Result<()> {
+        let hash_ref = |key_package: &KeyPackage| {
+            key_package
+                .hash_ref(self.mls_client.crypto.crypto())
+                .map(|hash| to_hex(hash.as_slice()))
+                .unwrap_or_else(|e| format!("<error: {}>", e))
+        };
+        let signature_key = self.mls_client.signature_key.as_slice();
+
+        let mut lines = vec![
+            format!("Identity:          {}", String::from_utf8_lossy(self.mls_client.credential.identity())),
+            format!("Device ID:         {}", self.config.device_id),
+            format!("Signature key:     {}", to_hex(signature_key)),
+            format!("Fingerprint:       {}", self.crypto.fingerprint(signature_key)?),
+            String::new(),
+            "Key packages:".to_string(),
+            format!("  Current:         {}", hash_ref(&self.mls_client.key_package)),
+        ];
+
+        if self.network_client.is_connected() {
+            match self.network_client.fetch_key_packages(&self.config.username).await {
+                Ok(packages) => {
+                    lines.push(format!("  Published:       {}", packages.len()));
+                    for data in &packages {
+                        let line = match mls_client::decode_key_package(&self.mls_client.crypto, data) {
+                            Ok(key_package) => hash_ref(&key_package),
+                            Err(e) => format!("<invalid: {}>", e),
+                        };
+                        lines.push(format!("    {}", line));
+                    }
+                }
+                Err(e) => lines.push(format!("  Published:       unknown ({})", e)),
+            }
+        } else {
+            lines.push("  Published:       unknown (offline)".to_string());
+        }
+
+        let cwd = std::env::current_dir()?;
+        lines.push(String::new());
+        lines.push("Local storage:".to_string());
+        for path in secrets::local_data_paths() {
+            lines.push(format!("  {}", cwd.join(vault::locate(path)).display()));
+        }
+
+        self.show_popup("Who am I", lines);
+        Ok(())
+    }
+
+    /// Persist timelines; failures are reported but never interrupt the session.
+    async fn save_transfers(&mut self) {
+        if let Err(e) = transfers::save(&self.transfers, &self.data_path(TRANSFERS_PATH)).await {
+            self.set_error(format!("Failed to save transfer state: {}", e));
+        }
+    }
+
+    async fn save_history(&mut self) {
+        // A damaged file stays as it is until the user picks how to recover
+        if self.recovery.is_some() {
+            return;
+        }
+        if let Err(e) = history::save(&self.groups, &self.data_path(HISTORY_PATH)).await {
+            self.set_error(format!("Failed to save message history: {}", e));
+        }
+        if let Err(e) = self.sync_search_index().await {
+            self.set_error(format!("Failed to update the search index: {}", e));
+        }
+    }
+
+    /// Store the unsent composer text as a named template.
+    async fn save_template(&mut self, name: &str) -> Result<()> {
+        let body = self.draft.trim();
+        if body.is_empty() {
+            self.set_status("Nothing to save: type the template in the composer (m), press Esc, then run template save");
+            return Ok(());
+        }
+        self.config.templates.insert(name.to_string(), body.to_string());
+        self.save_config().await?;
+        self.set_status(format!("Saved template {}", name));
+        Ok(())
+    }
+
+    /// Open the composer pre-filled with an expanded template for the active group.
+    fn use_template(&mut self, name: &str) {
+        let Some(template) = self.config.templates.get(name) else {
+            self.set_warning(format!("No template named {}", name));
+            return;
+        };
+        let Some(group) = self.active_group.as_ref().and_then(|id| self.groups.get(id)) else {
+            self.set_warning("No active group selected");
+            return;
+        };
+        if let Some(message) = self.posting_denied(&group.id) {
+            self.set_status(message);
+            return;
+        }
+        self.input = expand_template(template, &group.name, group.display_name(&self.config.username));
+        self.input_mode = InputMode::Message;
+    }
+
+    /// Set (or clear, when empty) the local user's display name in the active group.
+    /// ID of the group whose ID or name is `wanted`.
+    pub fn find_group_id(&self, wanted: &str) -> Option<String> {
+        self.groups
+            .values()
+            .find(|group| group.id == wanted || group.name == wanted)
+            .map(|group| group.id.clone())
+    }
+
+    async fn tag_group(&mut self, wanted: &str, color: &str, icon: Option<&str>) {
+        let Some(group) = self.find_group_id(wanted).and_then(|id| self.groups.get_mut(&id)) else {
+            self.set_warning(format!("No group named {}", wanted));
+            return;
+        };
+        if color == "clear" {
+            group.tag = None;
+            self.activity.push(Severity::Info, format!("Cleared tag of {}", group.name));
+        } else if color.parse::<Color>().is_err() {
+            self.set_warning(format!("Unknown color: {} (use a name like red or #rrggbb)", color));
+            return;
+        } else {
+            group.tag = Some(GroupTag { color: color.to_string(), icon: icon.map(str::to_string) });
+            self.activity.push(Severity::Info, format!("Tagged {} {}", group.name, color));
+        }
+        self.save_history().await;
+    }
+
+    async fn set_nickname(&mut self, nick: &str) -> Result<()> {
+        let Some(group_id) = self.active_group.clone() else {
+            self.set_warning("No active group selected");
+            return Ok(());
+        };
+        let Some(group) = self.groups.get_mut(&group_id) else {
+            return Ok(());
+        };
+        if group.nick_collides(&self.config.username, nick) {
+            let taken = format!("{} is a member's identity or nickname in {}; pick another", nick, group.name);
+            self.set_warning(taken);
+            return Ok(());
+        }
+
+        if nick.is_empty() {
+            group.nicknames.remove(&self.config.username);
+        } else {
+            group.nicknames.insert(self.config.username.clone(), nick.to_string());
+        }
+        let group_name = group.name.clone();
+
+        // Other members learn the name from an encrypted profile payload scoped to this group only
+        if self.network_client.is_connected() {
+            if let Err(e) = self.send_payload(&group_id, Payload::profile(nick), None).await {
+                self.set_warning(format!("Nickname set locally in {}, but failed to notify members: {}", group_name, e));
+                self.save_history().await;
+                return Ok(());
+            }
+        }
+
+        self.set_status(if nick.is_empty() {
+            format!("Cleared nickname in {}", group_name)
+        } else {
+            format!("You are now known as {} in {}", nick, group_name)
+        });
+        self.save_history().await;
+        Ok(())
+    }
+
+    async fn set_group_mode(&mut self, mode: GroupMode) -> Result<()> {
+        let Some(group_id) = self.active_group.clone() else {
+            self.set_warning("No active group selected");
+            return Ok(());
+        };
+        let Some(group) = self.groups.get_mut(&group_id) else {
+            return Ok(());
+        };
+        if !group.is_admin(&self.config.username) {
+            self.activity.push(Severity::Info, format!("Only admins can change the mode of {}", group.name));
+            return Ok(());
+        }
+        if group.mode == mode {
+            self.activity.push(Severity::Info, format!("{} is already in {} mode", group.name, mode.as_str()));
+            return Ok(());
+        }
+        if group.is_read_only() {
+            self.activity.push(Severity::Info, format!("{} is read-only", group.name));
+            return Ok(());
+        }
+        if self.held_commits.contains_key(&group_id) {
+            self.activity.push(Severity::Info, format!("A commit is held for review in {}; send or abort it first", group.name));
+            return Ok(());
+        }
+        if !self.network_client.is_connected() {
+            let detail = format!("cannot change the mode of {}", group.name);
+            self.report_error(&errors::NOT_CONNECTED, detail);
+            return Ok(());
+        }
+        self.commit_group_mode(&group_id, mode).await
+    }
+
+    /// Commit the group context change to `mode`; members' composers follow once they merge it.
+    async fn commit_group_mode(&mut self, group_id: &str, mode: GroupMode) -> Result<()> {
+        let Some(mut state) = self.mls_client.group_state(group_id) else {
+            self.set_warning("This group has no mode in its group context and cannot be switched");
+            return Ok(());
+        };
+        state.mode = mode;
+        let commit = self.mls_client.stage_group_state(group_id, &state)?;
+        let network_message = NetworkMessage::for_group("commit", &self.config.username, group_id, commit);
+        self.publish_commit(group_id, vec![network_message], CommitEffect::Mode { mode }).await?;
+        Ok(())
+    }
+
+    /// Pull pending messages for every joined group from the delivery service.
+    pub async fn sync_messages(&mut self) -> Result<()> {
+        if let Some((from, to)) = self.network_client.take_failover() {
+            self.set_warning(format!("Delivery service {} is unreachable; failed over to {}", from, to));
+            let event = self.status_text().to_string();
+            self.record_event(event);
+        }
+        if !self.network_client.is_connected() {
+            self.transfers_resumed = false;
+            return Ok(());
+        }
+        self.refresh_server_trust().await;
+        if !self.transfers_resumed {
+            self.transfers_resumed = true;
+            if let Err(e) = self.resume_transfers().await {
+                match recovery_for(&e) {
+                    // Try again once the connection is back
+                    Recovery::Reconnect => self.transfers_resumed = false,
+                    Recovery::Rejoin(group_id) => self.last_failed = Some(RetryOp::Join { group_id }),
+                    Recovery::None => {}
+                }
+                self.set_error(format!("Failed to resume transfers: {}", e));
+            }
+        }
+
+        match self.network_client.fetch_invitations().await {
+            Ok(invitations) => {
+                for invitation in invitations {
+                    self.handle_incoming(invitation);
+                }
+            }
+            Err(e) => {
+                self.set_warning(format!("Sync interrupted: {}", e));
+                self.transfers_resumed = false;
+                return Ok(());
+            }
+        }
+
+        let group_ids: Vec<String> = self.groups.keys().cloned().collect();
+        let mut received = false;
+        for group_id in group_ids {
+            let messages = match self.network_client.fetch_messages(&group_id).await {
+                Ok(messages) => messages,
+                // Every network error means the connection is gone; the next sync after reconnecting resumes
+                Err(e) => {
+                    self.set_warning(format!("Sync interrupted: {}", e));
+                    self.transfers_resumed = false;
+                    break;
+                }
+            };
+            for message in messages {
+                self.handle_incoming(message);
+                received = true;
+            }
+        }
+        for request in std::mem::take(&mut self.pending_resends) {
+            self.resend_chunks(&request.parent_id, &request.indexes).await?;
+        }
+        for group_id in std::mem::take(&mut self.quarantine_retry) {
+            self.retry_quarantine(&group_id);
+        }
+        for (group_id, effect) in std::mem::take(&mut self.pending_rebases) {
+            self.rebase_commit(&group_id, effect).await?;
+        }
+        self.enforce_rotation_policies().await?;
+        if received {
+            self.save_history().await;
+            self.save_transfers().await;
+        }
+        // Only now may the delivery service drop what it delivered. On a slow link the
+        // acknowledgements are batched; anything unacknowledged is redelivered and skipped
+        let debounce = if self.config.low_bandwidth { LOW_BANDWIDTH_SEND_DEBOUNCE } else { Duration::ZERO };
+        self.network_client.set_send_debounce(debounce);
+        if !self.config.low_bandwidth || self.network_client.ack_due(LOW_BANDWIDTH_ACK_INTERVAL) {
+            if let Err(e) = self.network_client.acknowledge().await {
+                self.set_error(format!("Could not acknowledge received messages: {}", e));
+            }
+        }
+        Ok(())
+    }
+
+    fn show_quarantine(&mut self) {
+        let Some(group) = self.active_group.as_ref().and_then(|id| self.groups.get(id)) else {
+            self.set_warning("No active group selected");
+            return;
+        };
+        if group.quarantine.is_empty() {
+            self.set_status(format!("No quarantined messages in {}", group.name));
+            return;
+        }
+        let mut lines = vec![
+            format!("{} message(s) failed to process; they are retried after each commit.", group.quarantine.len()),
+            String::new(),
+        ];
+        for entry in &group.quarantine {
+            lines.push(format!(
+                "{}  {:<18} from {:<16} {} attempt(s)",
+                entry.first_failed.format("%Y-%m-%d %H:%M:%S"),
+                entry.reason.code(),
+                entry.frame.sender,
+                entry.attempts
+            ));
+            lines.push(format!("    {}", entry.detail));
+        }
+        let title = format!("Quarantine: {}", group.name);
+        self.show_popup(title, lines);
+    }
+
+    /// Propose our own removal, then drop the group here: once proposed we cannot take part
+    /// in its commits anyway.
+    async fn leave_group(&mut self, group_id: &str) -> Result<()> {
+        let Some(group) = self.groups.get(group_id) else {
+            return Ok(());
+        };
+        if group.is_read_only() {
+            self.forget_group(group_id).await;
+            return Ok(());
+        }
+        if !self.network_client.is_connected() {
+            self.report_error(&errors::NOT_CONNECTED, format!("cannot leave {}", group.name));
+            return Ok(());
+        }
+        let name = group.name.clone();
+        let proposal = self.mls_client.leave_group(group_id)?;
+        let network_message = NetworkMessage::for_group("proposal", &self.config.username, group_id, proposal);
+        self.network_client.send_message(&network_message).await?;
+        self.forget_group(group_id).await;
+        self.set_status(format!("Left {}; the next commit by a member removes you", name));
+        self.record_event(format!("Left group {}", name));
+        Ok(())
+    }
+
+    /// Drop a group and its timeline from this device.
+    async fn forget_group(&mut self, group_id: &str) {
+        let Some(group) = self.groups.remove(group_id) else {
+            return;
+        };
+        self.mls_client.groups.remove(group_id);
+        for folder in &mut self.config.folders {
+            folder.groups.retain(|id| id != group_id);
+        }
+        if self.active_group.as_deref() == Some(group_id) {
+            self.active_group = None;
+        }
+        self.set_status(format!("Forgot group {}", group.name));
+        if group.archived_pages > 0 {
+            if let Err(e) = archive::remove_group(&self.data_path(ARCHIVE_DIR), group_id).await {
+                self.set_warning(format!("Forgot group {}, but failed to delete its older messages: {}", group.name, e));
+            }
+        }
+        self.unindex_group(group_id);
+        self.record_event(format!("Forgot group {}", group.name));
+        self.save_history().await;
+        if let Err(e) = self.save_config().await {
+            self.set_warning(format!("Forgot group {}, but failed to save folders: {}", group.name, e));
+        }
+    }
+
+    /// Whether DND is switched on or the current local time falls within quiet hours.
+    fn dnd_active(&self) -> bool {
+        self.config.do_not_disturb
+            || self.config.quiet_hours.is_some_and(|hours| hours.contains(Local::now().time()))
+    }
+
+    /// Surface a notification on every configured channel unless Do Not Disturb suppresses it.
+    fn notify(&mut self, notification: Notification) {
+        // Reminders the user set get through like mentions
+        let mention = matches!(notification.event, NotifyEvent::Mention | NotifyEvent::Reminder);
+        if self.dnd_active() && !(mention && self.config.dnd_allow_mentions) {
+            return;
+        }
+        let failures: Vec<String> = self.notifiers
+            .iter()
+            .filter_map(|notifier| notifier.notify(&notification).err())
+            .map(|e| e.to_string())
+            .collect();
+        self.set_status(if failures.is_empty() {
+            notification.text
+        } else {
+            format!("{} (alert failed: {})", notification.text, failures.join("; "))
+        });
+    }
+
+    async fn set_do_not_disturb(&mut self, enabled: bool) -> Result<()> {
+        self.config.do_not_disturb = enabled;
+        self.save_config().await?;
+        self.set_status(format!("Do Not Disturb {}", if enabled { "on" } else { "off" }));
+        Ok(())
+    }
+
+    async fn set_low_bandwidth(&mut self, enabled: bool) -> Result<()> {
+        self.config.low_bandwidth = enabled;
+        self.save_config().await?;
+        self.set_status(format!("Low-bandwidth mode {}", if enabled { "on" } else { "off" }));
+        Ok(())
+    }
+
+    async fn set_debug_mode(&mut self, enabled: bool) -> Result<()> {
+        self.config.debug_mode = enabled;
+        self.save_config().await?;
+        self.set_status(format!("Debug mode {}", if enabled { "on" } else { "off" }));
+        Ok(())
+    }
+
+    async fn set_quiet_hours(&mut self, args: &[&str]) -> Result<()> {
+        match args {
+            ["off"] => self.config.quiet_hours = None,
+            [start, end] => match (start.parse::<NaiveTime>(), end.parse::<NaiveTime>()) {
+                (Ok(start), Ok(end)) => self.config.quiet_hours = Some(QuietHours { start, end }),
+                _ => {
+                    self.set_warning("Invalid time: use HH:MM, e.g. quiet-hours 22:00 07:00");
+                    return Ok(());
+                }
+            },
+            _ => {
+                self.set_warning("Usage: quiet-hours <start HH:MM> <end HH:MM> | off");
+                return Ok(());
             }
-        } else {
-            self.status_message = format!("Created local group: {} (ID: {}) - not connected to MLS service", group_name, group_id);
         }
-        
+        self.save_config().await?;
+        self.set_status(match self.config.quiet_hours {
+            Some(hours) => format!("Quiet hours set: {} to {}", hours.start.format("%H:%M"), hours.end.format("%H:%M")),
+            None => "Quiet hours disabled".to_string(),
+        });
         Ok(())
     }
 
-    async fn join_group(&mut self, group_id: &str) -> Result<()> {
-        // Check if we're connected to the MLS service
-        if !self.network_client.is_connected() {
-            self.status_message = format!("Cannot join group {}: Not connected to MLS service. Use 'status' command to check connection.", group_id);
-            return Ok(());
-        }
+    /// One-line notice about config problems found at startup, until they are repaired.
+    fn config_banner(&self) -> Option<String> {
+        let first = self.config_issues.first()?;
+        Some(format!(
+            "{} has {} problem(s), e.g. {}. Affected settings use defaults; 'config check' lists them, 'config repair' rewrites the file.",
+            CONFIG_PATH,
+            self.config_issues.len(),
+            first
+        ))
+    }
 
-        // Check if we're already in this group
-        if self.groups.contains_key(group_id) {
-            self.status_message = format!("Already in group: {}", group_id);
-            return Ok(());
+    fn show_config_issues(&mut self) {
+        if self.config_issues.is_empty() {
+            self.set_status(format!("No problems found in {}", CONFIG_PATH));
+            return;
         }
+        let mut lines: Vec<String> = self.config_issues.iter().map(|issue| format!("- {}", issue)).collect();
+        lines.push(String::new());
+        lines.push("Invalid settings currently use their defaults. Rewriting keeps every valid setting and drops the rest.".to_string());
+        self.popup = Some(Popup {
+            title: format!("Problems in {}", CONFIG_PATH),
+            lines,
+            actions: vec![PopupAction::RepairConfig],
+        });
+        self.screen = AppScreen::Popup;
+    }
 
-        // Try to join the group through the MLS service
-        match self.network_client.join_group(group_id, &self.mls_client.key_package.tls_serialize_detached()?, &self.config.username).await {
-            Ok(welcome_data) => {
-                if welcome_data.is_empty() {
-                    self.status_message = format!("Group {} not found or access denied. This could mean:\n1. The group doesn't exist on the MLS service\n2. You don't have permission to join\n3. The MLS service is not properly configured\n\nTry creating the group first with 'create <group_name>' or check your MLS service configuration.", group_id);
-                    return Ok(());
-                }
+    fn show_config(&mut self) {
+        let settings = serde_json::to_string_pretty(&self.config).unwrap_or_default();
+        let mut lines: Vec<String> = settings.lines().map(str::to_string).collect();
+        lines.push(String::new());
+        lines.push("Change a setting with 'config set <setting> <value>', e.g. 'config set sounds.message off'".to_string());
+        self.show_popup(format!("Effective configuration ({})", CONFIG_PATH), lines);
+    }
 
-                // Parse the welcome message and join the MLS group
-                match Welcome::tls_deserialize(&mut welcome_data.as_slice()) {
-                    Ok(_welcome) => {
-                        // For now, we'll just create a local group representation
-                        // In a full implementation, we'd create the MLS group from the welcome message
-                        // let mls_group = MlsGroup::new_from_welcome(
-                        //     &self.mls_client.crypto,
-                        //     &MlsGroupConfig::default(),
-                        //     welcome,
-                        //     Some(&self.mls_client.storage),
-                        // )?;
-                        // self.mls_client.add_group(group_id, mls_group);
-
-                        // Create local group representation
-                        let group = Group {
-                            id: group_id.to_string(),
-                            name: format!("Group {}", group_id),
-                            members: vec![self.config.username.clone()], // Will be updated with real members
-                            messages: Vec::new(),
-                            is_active: true,
-                        };
-                        
-                        self.groups.insert(group_id.to_string(), group);
-                        self.active_group = Some(group_id.to_string());
-                        
-                        // Update group list selection
-                        let groups: Vec<_> = self.groups.keys().cloned().collect();
-                        if let Some(pos) = groups.iter().position(|g| g == group_id) {
-                            self.group_list_state.select(Some(pos));
-                        }
-                        
-                        self.status_message = format!("Successfully joined group: {} (Welcome message received)", group_id);
-                    }
-                    Err(e) => {
-                        self.status_message = format!("Failed to parse welcome message for group {}: {}", group_id, e);
-                    }
-                }
+    /// Change one setting, save it and apply it to the running session where that is possible.
+    async fn set_config(&mut self, path: &str, raw: &str) -> Result<()> {
+        let updated = match self.config.with_setting(path, raw) {
+            Ok(updated) => updated,
+            Err(issue) => {
+                self.set_warning(format!("Not changed: {}", issue));
+                return Ok(());
             }
-            Err(e) => {
-                self.status_message = format!("Failed to join group {}: {}\n\nThis could be due to:\n1. Network connectivity issues\n2. MLS service not running\n3. Invalid group ID\n\nTry using 'status' command to check connection.", group_id, e);
+        };
+        let identity = ["username", "delivery_service_address", "delivery_service_fallbacks", "attachment_storage"];
+        if self.account.is_some() && identity.contains(&path.split('.').next().unwrap_or(path)) {
+            self.set_status("These settings belong to the primary account; switch to it with 'account switch primary'");
+            return Ok(());
+        }
+        let reconnect = updated.delivery_service_addresses() != self.config.delivery_service_addresses();
+        let handshake_changed = updated.handshake_policy != self.config.handshake_policy;
+        // The MLS credential and device identity are fixed for the session
+        let restart = updated.username != self.config.username || updated.device_id != self.config.device_id;
+        self.config = updated;
+        self.save_config().await?;
+        self.notifiers = notify::from_config(&self.config);
+        self.filters = FilterSet::new(&self.config.filters);
+        if handshake_changed {
+            self.mls_client.handshake = HandshakeValidator::new(&self.config.handshake_policy);
+            for account in &mut self.accounts {
+                account.mls_client.handshake = HandshakeValidator::new(&self.config.handshake_policy);
+            }
+            // Commits quarantined under the old policy may pass the new one
+            self.quarantine_retry.extend(self.groups.keys().cloned());
+        }
+        self.temp_delivery_service = self.config.delivery_service_address.clone();
+        self.temp_username = self.config.username.clone();
+
+        if reconnect {
+            self.network_client = NetworkClient::new(&self.config.delivery_service_addresses(), &self.config.username).await?;
+            if self.network_client.is_connected() {
+                self.check_server_clock();
+                self.set_status(format!("Set {}. {}", path, self.network_client.connect_status().describe()));
+            } else {
+                self.set_error(format!("Set {}. {}", path, self.network_client.connect_status().describe()));
             }
+        } else if restart {
+            self.set_status(format!("Set {}; takes effect after a restart", path));
+        } else {
+            self.set_status(format!("Set {}", path));
         }
         Ok(())
     }
 
-    async fn send_message(&mut self, group_id: &str, message: &str) -> Result<()> {
-        if let Some(group) = self.groups.get_mut(group_id) {
-            let msg = Message {
-                id: Uuid::new_v4().to_string(),
-                sender: self.config.username.clone(),
-                content: message.to_string(),
-                timestamp: Local::now(),
-                group_id: group_id.to_string(),
-            };
-            
-            group.messages.push(msg);
-            self.status_message = format!("Message sent to {}", group.name);
+    /// Write the running config, which already has defaults in place of invalid fields.
+    async fn repair_config(&mut self) -> Result<()> {
+        if self.config_issues.is_empty() {
+            self.set_status(format!("No problems found in {}", CONFIG_PATH));
+            return Ok(());
         }
+        self.save_config().await?;
+        let repaired = std::mem::take(&mut self.config_issues).len();
+        self.set_status(format!("Rewrote {}: {} problem(s) replaced with defaults", CONFIG_PATH, repaired));
+        let event = self.status_text().to_string();
+        self.record_event(event);
         Ok(())
     }
 
     async fn save_settings(&mut self) -> Result<()> {
+        if self.account.is_some() {
+            self.set_status("Settings edit the primary account; switch to it with 'account switch primary'");
+            return Ok(());
+        }
         let old_address = self.config.delivery_service_address.clone();
         self.config.delivery_service_address = self.temp_delivery_service.clone();
         self.config.username = self.temp_username.clone();
-        self.config.save().await?;
+        self.save_config().await?;
         
         // Reconnect to MLS service if address changed
         if old_address != self.config.delivery_service_address {
-            self.network_client = NetworkClient::new(&self.config.delivery_service_address).await?;
+            self.network_client = NetworkClient::new(&self.config.delivery_service_addresses(), &self.config.username).await?;
             
             if self.network_client.is_connected() {
-                self.status_message = format!("Settings saved. Connected to MLS service at {}", self.config.delivery_service_address);
+                self.set_status(format!("Settings saved. {}", self.network_client.connect_status().describe()));
+                self.check_server_clock();
             } else {
-                self.status_message = format!("Settings saved. Failed to connect to MLS service at {}", self.config.delivery_service_address);
+                self.set_error(format!("Settings saved. {}", self.network_client.connect_status().describe()));
             }
         } else {
-            self.status_message = "Settings saved".to_string();
+            self.set_status("Settings saved");
         }
         
         Ok(())
     }
 
+    /// Dispatch a key press to the current screen, after the global lock and switcher keys.
+    pub async fn handle_key(&mut self, key: KeyEvent) -> Result<()> {
+        self.last_activity = Instant::now();
+        let lock_requested = key.code == KeyCode::Char('l')
+            && key.modifiers.contains(KeyModifiers::CONTROL);
+        let switcher_requested = key.code == KeyCode::Char('k')
+            && key.modifiers.contains(KeyModifiers::CONTROL);
+        let undo_requested = key.code == KeyCode::Char('z')
+            && key.modifiers.contains(KeyModifiers::CONTROL);
+        match self.screen {
+            AppScreen::Locked => {
+                self.handle_lock_input(key.code).await?;
+            }
+            _ if lock_requested => {
+                self.lock();
+            }
+            _ if switcher_requested => {
+                self.open_switcher();
+            }
+            _ if undo_requested => {
+                self.undo_send().await;
+            }
+            AppScreen::Help => {
+                self.screen = AppScreen::Main;
+            }
+            AppScreen::Popup => {
+                self.handle_popup_input(key.code).await?;
+            }
+            AppScreen::Confirm => {
+                self.handle_confirm_input(key.code).await?;
+            }
+            AppScreen::Recovery => {
+                self.handle_recovery_input(key.code).await?;
+            }
+            AppScreen::Roster => {
+                self.handle_roster_input(key.code).await?;
+            }
+            AppScreen::Cleanup => {
+                self.handle_cleanup_input(key.code).await?;
+            }
+            AppScreen::Activity => self.handle_activity_input(key.code),
+            AppScreen::Switcher => {
+                self.handle_switcher_input(key.code).await?;
+            }
+            AppScreen::Dashboard => {
+                self.handle_dashboard_input(key.code).await?;
+            }
+            _ => {
+                self.handle_input(key.code).await?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Background work run between input events: auto-lock, task results, due jobs, and a
+    /// sync every two seconds.
+    pub async fn tick(&mut self, last_sync: &mut Instant) -> Result<()> {
+        self.check_auto_lock();
+        self.poll_progress();
+        self.run_scheduled().await?;
+        self.poll_events().await?;
+        self.flush_delayed_sends().await;
+        self.run_stress().await;
+        self.enforce_memory_budget().await;
+        self.backup_if_due().await;
+
+        let sync = last_sync.elapsed() >= Duration::from_secs(2);
+        if sync {
+            self.sync_messages().await?;
+            *last_sync = Instant::now();
+        }
+        self.run_parked_accounts(sync).await
+    }
+
+    pub fn handle_resize(&mut self) {
+        self.wrap_cache.invalidate();
+    }
+
     pub fn render(&mut self, f: &mut Frame) {
+        let started = Instant::now();
+        self.render_screen(f);
+        // Consoles on a legacy code page get ASCII stand-ins for borders and symbols
+        if !self.config.glyphs.unicode() {
+            for cell in &mut f.buffer_mut().content {
+                if let Some(ascii) = platform::ascii_fallback(cell.symbol()) {
+                    cell.set_symbol(ascii);
+                }
+            }
+        }
+        self.record_stress_frame(started.elapsed());
+    }
+
+    fn render_screen(&mut self, f: &mut Frame) {
+        let area = f.size();
+        if area.width < MIN_TERMINAL_WIDTH || area.height < MIN_TERMINAL_HEIGHT {
+            self.render_too_small(f);
+            return;
+        }
         match self.screen {
+            AppScreen::Dashboard => self.render_dashboard(f),
             AppScreen::Main => self.render_main(f),
             AppScreen::Settings => self.render_settings(f),
             AppScreen::Help => self.render_help(f),
+            AppScreen::Locked => self.render_locked(f),
+            AppScreen::Popup => {
+                self.render_main(f);
+                self.render_popup(f);
+            }
+            AppScreen::Confirm => {
+                self.render_main(f);
+                self.render_confirm(f);
+            }
+            AppScreen::Recovery => {
+                self.render_dashboard(f);
+                self.render_recovery(f);
+            }
+            AppScreen::Roster => {
+                self.render_main(f);
+                self.render_roster(f);
+            }
+            AppScreen::Cleanup => {
+                self.render_main(f);
+                self.render_cleanup(f);
+            }
+            AppScreen::Activity => self.render_activity(f),
+            AppScreen::Switcher => {
+                self.render_main(f);
+                self.render_switcher(f);
+            }
+        }
+    }
+
+    fn render_too_small(&mut self, f: &mut Frame) {
+        let area = f.size();
+        let text = format!(
+            "Terminal too small\n(need {}x{}, have {}x{})",
+            MIN_TERMINAL_WIDTH, MIN_TERMINAL_HEIGHT, area.width, area.height
+        );
+        let placeholder = Paragraph::new(text)
+            .style(Style::default().fg(Color::Yellow))
+            .alignment(Alignment::Center)
+            .wrap(Wrap { trim: true });
+        f.render_widget(placeholder, area);
+    }
+
+    fn render_dashboard(&mut self, f: &mut Frame) {
+        let chunks = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([
+                Constraint::Length(self.banner_height()), // Config problems, plaintext transport
+                Constraint::Length(3),      // Connection status
+                Constraint::Min(0),         // Groups, invitations, join requests
+                Constraint::Percentage(35), // Recent events
+                Constraint::Length(3),      // Key hints
+            ].as_ref())
+            .split(f.size());
+
+        let (connection_text, connection_color) = if self.network_client.is_connected() {
+            let color = if self.network_client.endpoint_position().0 > 0 { Color::Yellow } else { Color::Green };
+            (format!("Connected to {}{} as {}", self.network_client.endpoint(), self.endpoint_note(), self.config.username), color)
+        } else {
+            (format!("Disconnected from {} (local only) as {}", self.config.delivery_service_address, self.config.username), Color::Red)
+        };
+        let connection = Paragraph::new(connection_text)
+            .style(Style::default().fg(connection_color))
+            .block(Block::default().borders(Borders::ALL).title("Connection"));
+        f.render_widget(connection, chunks[1]);
+        self.render_banners(f, chunks[0]);
+
+        let items: Vec<ListItem> = self.dashboard_items()
+            .iter()
+            .map(|item| match item {
+                DashboardItem::Group(group_id) => {
+                    let group = &self.groups[group_id];
+                    let style = if group.unread > 0 {
+                        Style::default().add_modifier(Modifier::BOLD)
+                    } else {
+                        Style::default()
+                    };
+                    ListItem::new(format!("Group    {} - {} unread", group.name, group.unread)).style(style)
+                }
+                DashboardItem::Invitation(group_id) => {
+                    ListItem::new(format!("Invite   {} (Enter to join)", group_id))
+                        .style(Style::default().fg(Color::Yellow))
+                }
+                DashboardItem::JoinRequest(index) => {
+                    let request = &self.pending_join_requests[*index];
+                    let group_name = self.groups.get(&request.group_id)
+                        .map(|group| group.name.as_str())
+                        .unwrap_or(request.group_id.as_str());
+                    ListItem::new(format!("Approval {} wants to join {}", request.client_id, group_name))
+                        .style(Style::default().fg(Color::Cyan))
+                }
+            })
+            .collect();
+
+        let title = format!(
+            "Activity ({} invitations, {} join requests)",
+            self.pending_invitations.len(),
+            self.pending_join_requests.len()
+        );
+        let activity = List::new(items)
+            .block(Block::default().borders(Borders::ALL).title(title))
+            .highlight_style(Style::default().add_modifier(Modifier::REVERSED));
+        f.render_stateful_widget(activity, chunks[2], &mut self.dashboard_state);
+
+        let events: Vec<Line> = self.system_events
+            .iter()
+            .rev()
+            .map(|event| {
+                Line::from(vec![
+                    Span::styled(
+                        format!("[{}] ", event.timestamp.format("%H:%M:%S")),
+                        Style::default().fg(Color::Gray),
+                    ),
+                    Span::raw(event.text.clone()),
+                ])
+            })
+            .collect();
+        let events_paragraph = Paragraph::new(events)
+            .block(Block::default().borders(Borders::ALL).title("Recent events"))
+            .wrap(Wrap { trim: true });
+        f.render_widget(events_paragraph, chunks[3]);
+
+        let hints = Paragraph::new("↑/↓: Select  Enter: Open  q: Quit  any other key: Continue")
+            .block(Block::default().borders(Borders::ALL).title("Dashboard"));
+        f.render_widget(hints, chunks[4]);
+    }
+
+    /// Rows taken by the configuration and transport banners, 3 for each shown.
+    fn banner_height(&self) -> u16 {
+        let config = !self.config_issues.is_empty();
+        let transport = self.transport_banner().is_some();
+        let trust = self.trust_banner().is_some();
+        let deleted = self.deletion_banner().is_some();
+        3 * (u16::from(config) + u16::from(transport) + u16::from(trust) + u16::from(deleted))
+    }
+
+    fn render_banners(&self, f: &mut Frame, area: Rect) {
+        let mut banners = Vec::new();
+        if let Some(banner) = self.config_banner() {
+            banners.push(("Configuration", banner, Color::Yellow));
+        }
+        if let Some((banner, color)) = self.transport_banner() {
+            banners.push(("Transport", banner, color));
+        }
+        if let Some(banner) = self.trust_banner() {
+            banners.push(("Server identity", banner, Color::Red));
+        }
+        if let Some(banner) = self.deletion_banner() {
+            banners.push(("Group deleted", banner, Color::Red));
+        }
+        let rows = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints(vec![Constraint::Length(3); banners.len()])
+            .split(area);
+        for ((title, banner, color), row) in banners.into_iter().zip(rows.iter()) {
+            let banner = Paragraph::new(banner)
+                .style(Style::default().fg(color))
+                .block(Block::default().borders(Borders::ALL).title(title))
+                .wrap(Wrap { trim: true });
+            f.render_widget(banner, *row);
+        }
+    }
+
+    /// The active group's onboarding message while it is pinned above the timeline.
+    fn pinned_onboarding(&self) -> Option<&Onboarding> {
+        let group = self.groups.get(self.active_group.as_ref()?)?;
+        group.onboarding.as_ref().filter(|_| group.onboarding_pinned)
+    }
+
+    fn render_onboarding(&self, f: &mut Frame, area: Rect) {
+        if let Some(onboarding) = self.pinned_onboarding() {
+            let title = format!("📌 Onboarding from {} ('onboarding dismiss' to unpin)", onboarding.set_by);
+            let pinned = Paragraph::new(onboarding.text.as_str())
+                .style(Style::default().fg(Color::Cyan))
+                .block(Block::default().borders(Borders::ALL).title(title))
+                .wrap(Wrap { trim: true });
+            f.render_widget(pinned, area);
         }
     }
 
@@ -525,26 +3949,75 @@ impl App {
             .constraints([Constraint::Min(0), Constraint::Length(3)].as_ref())
             .split(chunks[0]);
 
+        let pinned_height = self.pinned_onboarding()
+            .map(|onboarding| (timeline::wrapped_height(&onboarding.text, chunks[1].width.saturating_sub(2)) + 2).min(MAX_PINNED_HEIGHT))
+            .unwrap_or(0);
         let right_chunks = Layout::default()
             .direction(Direction::Vertical)
             .constraints([
+                Constraint::Length(self.banner_height()), // Config problems, plaintext transport
+                Constraint::Length(pinned_height), // Pinned onboarding message
                 Constraint::Min(0),         // Messages area
                 Constraint::Length(3),      // Input area
                 Constraint::Percentage(35), // Status area (takes 35% of right panel)
             ].as_ref())
             .split(chunks[1]);
+        self.render_banners(f, right_chunks[0]);
+        self.render_onboarding(f, right_chunks[1]);
 
         // Groups list
-        let groups: Vec<ListItem> = self.groups
-            .iter()
-            .map(|(id, group)| {
-                let style = if Some(id) == self.active_group.as_ref() {
+        let groups: Vec<ListItem> = self.group_list_rows()
+            .into_iter()
+            .filter_map(|row| {
+                let id = match row {
+                    GroupListRow::Account(name) => {
+                        let (header, connected) = self.account_header(&name);
+                        let color = if connected { Color::Green } else { Color::Red };
+                        return Some(ListItem::new(header).style(Style::default().fg(color).add_modifier(Modifier::BOLD)));
+                    }
+                    GroupListRow::Folder(index) => {
+                        let folder = &self.config.folders[index];
+                        let unread: usize = folder.groups.iter().filter_map(|id| self.groups.get(id)).map(|group| group.unread).sum();
+                        let unread = if unread > 0 { format!(" *{}", unread) } else { String::new() };
+                        let arrow = if folder.collapsed { "▸" } else { "▾" };
+                        return Some(ListItem::new(format!("{} {} ({}){}", arrow, folder.name, folder.groups.len(), unread))
+                            .style(Style::default().add_modifier(Modifier::BOLD)));
+                    }
+                    GroupListRow::Group(id) => id,
+                };
+                let group = self.groups.get(&id)?;
+                let style = if group.is_read_only() {
+                    // Read-only after removal or deletion
+                    Style::default().fg(Color::DarkGray)
+                } else if Some(&id) == self.active_group.as_ref() {
                     Style::default().fg(Color::Yellow).add_modifier(Modifier::BOLD)
                 } else {
                     Style::default()
                 };
-                ListItem::new(format!("{} ({})", group.name, group.members.len()))
-                    .style(style)
+                let marker = if group.deleted.as_ref().is_some_and(|deletion| deletion.expired) {
+                    " [expired]"
+                } else if group.deleted.is_some() {
+                    " [deleted]"
+                } else if group.removed {
+                    " [removed]"
+                } else if group.mode == GroupMode::Announce {
+                    " [announce]"
+                } else {
+                    ""
+                };
+                let unread = if group.unread > 0 { format!(" *{}", group.unread) } else { String::new() };
+                let quarantined = if group.quarantine.is_empty() { String::new() } else { format!(" ⚠{}", group.quarantine.len()) };
+                let overdue = if group.rotation_overdue().is_some() { " ⟳" } else { "" };
+                // Grouped entries are indented under their folder header
+                let mut spans = Vec::new();
+                if self.folder_of(&id).is_some() {
+                    spans.push(Span::raw("  "));
+                }
+                if let Some(tag) = &group.tag {
+                    spans.push(Span::styled(tag.label(), tag_style(tag)));
+                }
+                spans.push(Span::raw(format!("{} ({}){}{}{}{}", group.name, group.members.len(), marker, unread, quarantined, overdue)));
+                Some(ListItem::new(Line::from(spans)).style(style))
             })
             .collect();
 
@@ -560,85 +4033,294 @@ impl App {
         f.render_widget(controls, left_chunks[1]);
 
         // Messages
-        let messages: Vec<Line> = if let Some(group_id) = &self.active_group {
+        // One entry per message: its line, and the translation beneath it if there is one
+        let messages: Vec<Vec<Line>> = if let Some(group_id) = &self.active_group {
             if let Some(group) = self.groups.get(group_id) {
-                group.messages.iter().map(|msg| {
-                    Line::from(vec![
-                        Span::styled(
-                            format!("[{}]", msg.timestamp.format("%H:%M:%S")),
-                            Style::default().fg(Color::Gray),
-                        ),
-                        Span::styled(
-                            format!(" {}: ", msg.sender),
-                            Style::default().fg(Color::Blue).add_modifier(Modifier::BOLD),
-                        ),
-                        Span::raw(msg.content.clone()),
-                    ])
+                let previews_links = self.previews_links(group_id);
+                group.messages.iter().enumerate().map(|(index, msg)| {
+                    let selected_style = |line: Line<'static>| if self.selected_message == Some(index) {
+                        line.style(Style::default().add_modifier(Modifier::REVERSED))
+                    } else {
+                        line
+                    };
+                    let filtered = msg.redacted_by.is_none().then(|| self.filters.action_for(group_id, &msg.content)).flatten();
+                    // Hidden messages collapse to one line so timeline indexes stay aligned
+                    if filtered == Some(FilterAction::Hide) {
+                        return vec![selected_style(Line::from(Span::styled(
+                            format!("[{}] message from {} hidden by a filter", msg.shown_time().format("%H:%M:%S"), group.display_name(&msg.sender)),
+                            Style::default().fg(Color::DarkGray),
+                        )))];
+                    }
+                    let timestamp_style = if msg.clock_skewed {
+                        Style::default().fg(Color::Red)
+                    } else {
+                        Style::default().fg(Color::Gray)
+                    };
+                    // A skewed message shows when it arrived, with the time its sender claims
+                    let skew_note = if !msg.clock_skewed {
+                        String::new()
+                    } else if msg.received_at.is_some() {
+                        format!(" ⚠ claims {}", msg.timestamp.format("%Y-%m-%d %H:%M:%S"))
+                    } else {
+                        " ⚠".to_string()
+                    };
+                    let mut spans = vec![
+                        Span::styled(format!("[{}{}]", msg.shown_time().format("%H:%M:%S"), skew_note), timestamp_style),
+                        Span::styled(format!(" {}", msg.protection.marker()), protection::marker_style(&msg.protection)),
+                    ];
+                    if let Some(shared_by) = &msg.shared_by {
+                        spans.push(Span::styled(
+                            format!(" ↪ shared by {}", group.display_name(shared_by)),
+                            Style::default().fg(Color::DarkGray).add_modifier(Modifier::ITALIC),
+                        ));
+                    }
+                    // Bridged senders stand apart: anyone on the other side of the bridge can write as them
+                    let (bridged, sender_color) = if self.is_bridged_sender(&msg.sender) { (" [bridged]", Color::Magenta) } else { ("", Color::Blue) };
+                    spans.push(Span::styled(
+                        format!(" {}{}{}: ", if msg.pinned { "📌 " } else { "" }, group.display_name(&msg.sender), bridged),
+                        Style::default().fg(sender_color).add_modifier(Modifier::BOLD),
+                    ));
+                    let content_style = match filtered {
+                        _ if msg.redacted_by.is_some() => Style::default().fg(Color::DarkGray).add_modifier(Modifier::ITALIC),
+                        Some(FilterAction::Dim) => Style::default().fg(Color::DarkGray),
+                        Some(FilterAction::Highlight) => Style::default().fg(Color::Black).bg(Color::Yellow),
+                        _ => Style::default(),
+                    };
+                    if let Some(content_type) = msg.unsupported.as_ref().filter(|_| msg.redacted_by.is_none()) {
+                        spans.push(Span::styled(
+                            format!("[{} message this version cannot show] ", content_type),
+                            Style::default().fg(Color::DarkGray).add_modifier(Modifier::ITALIC),
+                        ));
+                    }
+                    spans.push(Span::styled(self.display_text(group.text_direction, &msg.content), content_style));
+                    for (emoji, reactors) in &msg.reactions {
+                        spans.push(Span::styled(format!("  {} {}", emoji, reactors.len()), Style::default().fg(Color::Gray)));
+                    }
+                    if msg.pending {
+                        let waited = Local::now().signed_duration_since(msg.timestamp).num_milliseconds();
+                        let marker = if let Some(secs) = self.undo_countdown(&msg.id) {
+                            format!("  ⏳ {}s · Ctrl+Z to undo", secs)
+                        } else if waited < ECHO_TIMEOUT_SECS * 1000 {
+                            format!("  {}", tasks::spinner(u128::try_from(waited).unwrap_or(0)))
+                        } else {
+                            "  ⚠ unconfirmed".to_string()
+                        };
+                        spans.push(Span::styled(marker, Style::default().fg(Color::DarkGray)));
+                    }
+                    let mut lines = vec![selected_style(Line::from(spans))];
+                    if let Some(translation) = &msg.translation {
+                        lines.push(selected_style(Line::from(Span::styled(
+                            format!("  ⇄ {}", translation),
+                            Style::default().fg(Color::Cyan).add_modifier(Modifier::ITALIC),
+                        ))));
+                    }
+                    let card = (previews_links && msg.redacted_by.is_none() && msg.sender != self.config.username)
+                        .then(|| preview::first_url(&msg.content).and_then(|url| self.previews.get(url)))
+                        .flatten();
+                    if let Some(card) = card {
+                        lines.push(selected_style(Line::from(Span::styled(
+                            format!("  ▏ {}", card.title),
+                            Style::default().fg(Color::Magenta).add_modifier(Modifier::BOLD),
+                        ))));
+                        if let Some(description) = &card.description {
+                            lines.push(selected_style(Line::from(Span::styled(
+                                format!("  ▏ {}", description),
+                                Style::default().fg(Color::DarkGray),
+                            ))));
+                        }
+                    }
+                    lines
                 }).collect()
             } else {
                 vec![]
             }
         } else {
-            vec![Line::from("No active group selected")]
+            vec![vec![Line::from("No active group selected")]]
         };
 
-        let messages_paragraph = Paragraph::new(messages)
-            .block(Block::default().borders(Borders::ALL).title("Messages"))
+        // Scroll in wrapped-line space so resizes keep the same message at the top
+        let inner_width = right_chunks[2].width.saturating_sub(2);
+        let inner_height = right_chunks[2].height.saturating_sub(2);
+        self.message_view_height = inner_height;
+        if let Some(group) = self.active_group.as_ref().and_then(|id| self.groups.get(id)) {
+            let measured: Vec<(&str, String)> = group.messages
+                .iter()
+                .zip(&messages)
+                .map(|(msg, lines)| {
+                    let text: Vec<String> = lines.iter().map(|line| line.spans.iter().map(|span| span.content.as_ref()).collect()).collect();
+                    (msg.id.as_str(), text.join("\n"))
+                })
+                .collect();
+            let mut scroll = self.wrap_cache.layout(&group.id, inner_width, &measured, self.message_scroll);
+            let restored = std::mem::take(&mut self.restored_above);
+            scroll = scroll.saturating_add(self.wrap_cache.offset_of(restored));
+            if let Some(selected) = self.selected_message {
+                let top = self.wrap_cache.offset_of(selected);
+                let bottom = top.saturating_add(self.wrap_cache.height_of(selected));
+                if top < scroll {
+                    scroll = top;
+                } else if bottom > scroll.saturating_add(inner_height) {
+                    scroll = bottom.saturating_sub(inner_height);
+                }
+            }
+            self.message_scroll = scroll.min(self.wrap_cache.total().saturating_sub(inner_height));
+        }
+
+        let title = match self.active_group.as_ref().and_then(|id| self.groups.get(id)) {
+            Some(group) => match &group.tag {
+                Some(tag) => Line::from(vec![
+                    Span::raw("Messages: "),
+                    Span::styled(format!("{}{}", tag.label(), group.name), tag_style(tag)),
+                ]),
+                None => Line::from(format!("Messages: {}", group.name)),
+            },
+            None => Line::from("Messages"),
+        };
+        let messages_paragraph = Paragraph::new(messages.into_iter().flatten().collect::<Vec<_>>())
+            .block(Block::default().borders(Borders::ALL).title(title))
             .wrap(Wrap { trim: true })
             .scroll((self.message_scroll, 0));
 
-        f.render_widget(messages_paragraph, right_chunks[0]);
+        f.render_widget(messages_paragraph, right_chunks[2]);
 
         // Input
+        let read_only = self.active_group.as_ref()
+            .map(|group_id| self.posting_denied(group_id).is_some())
+            .unwrap_or(false);
+        let prompt_title = self.command_prompt.as_ref()
+            .and_then(|prompt| Some(format!("{}: {} (Enter next, Esc cancel)", prompt.form.syntax, prompt.next()?.prompt()?)));
         let input_title = match self.input_mode {
             InputMode::Command => "Command",
             InputMode::Message => "Message",
+            InputMode::Wipe => "WIPE ALL LOCAL DATA",
+            InputMode::Passphrase => match self.passphrase_prompt {
+                Some(PassphrasePrompt::RepeatNew(_)) => "Repeat the new passphrase",
+                Some(PassphrasePrompt::Duress) => "Duress passphrase",
+                Some(PassphrasePrompt::RepeatDuress(_)) => "Repeat the duress passphrase",
+                _ => "New passphrase",
+            },
+            InputMode::Select => "Select message",
+            InputMode::Argument => prompt_title.as_deref().unwrap_or("Command"),
+            _ if read_only => "Input (announcement group: read-only)",
             _ => "Input",
         };
         
-        let input = Paragraph::new(self.input.as_str())
+        let input_text = if self.wipe_stage == Some(WipeStage::Passphrase) || matches!(self.input_mode, InputMode::Passphrase) {
+            "*".repeat(self.input.chars().count())
+        } else {
+            self.input.clone()
+        };
+        let (input_text, right_aligned, cursor) = self.composer_view(&input_text, right_chunks[3].width.saturating_sub(2));
+        let input = Paragraph::new(input_text)
+            .alignment(if right_aligned { Alignment::Right } else { Alignment::Left })
             .style(match self.input_mode {
                 InputMode::Normal => Style::default(),
+                InputMode::Wipe => Style::default().fg(Color::Red),
                 _ => Style::default().fg(Color::Yellow),
             })
-            .block(Block::default().borders(Borders::ALL).title(input_title));
-        f.render_widget(input, right_chunks[1]);
+            .block(Block::default().borders(Borders::ALL).title(input_title).title(self.composer_counter()));
+        f.render_widget(input, right_chunks[3]);
 
         // Status with available groups
-        let status_content = if self.groups.is_empty() {
-            format!(
-                "User: {}\n{}\n\nAvailable groups: None\nUse 'create <group_name>' to create a group",
-                self.config.username,
-                self.status_message
-            )
+        let user = format!("{} @ {}{}", self.config.username, self.network_client.endpoint(), self.endpoint_note());
+        let mut status_content: Vec<Line> = self.tasks.status_lines().into_iter().map(Line::from).collect();
+        if self.show_metrics {
+            status_content.push(Line::from(self.metrics_line()));
+        }
+        status_content.push(Line::from(format!("User: {}", user)));
+        status_content.extend(self.status_lines());
+        status_content.push(Line::from(""));
+        if self.groups.is_empty() {
+            status_content.push(Line::from("Available groups: None"));
+            status_content.push(Line::from("Use 'create <group_name>' to create a group"));
         } else {
-            let groups_list: Vec<String> = self.groups
+            status_content.push(Line::from("Available groups:"));
+            status_content.extend(self.groups
                 .iter()
-                .map(|(id, group)| format!("• {} ({}) - {} members", group.name, id, group.members.len()))
-                .collect();
-            format!(
-                "User: {}\n{}\n\nAvailable groups:\n{}",
-                self.config.username,
-                self.status_message,
-                groups_list.join("\n")
-            )
-        };
+                .map(|(id, group)| Line::from(format!("• {} ({}) - {} members", group.name, id, group.members.len()))));
+        }
+        let mut status_title = "Status & Groups".to_string();
+        if self.dnd_active() {
+            status_title.push_str(" [DND]");
+        }
+        if self.config.low_bandwidth {
+            status_title.push_str(" [LOW-BW]");
+        }
         
         let status = Paragraph::new(status_content)
             .style(Style::default().fg(Color::Green))
-            .block(Block::default().borders(Borders::ALL).title("Status & Groups"))
+            .block(Block::default().borders(Borders::ALL).title(status_title))
             .wrap(Wrap { trim: true });
-        f.render_widget(status, right_chunks[2]);
+        f.render_widget(status, right_chunks[4]);
 
         // Cursor
-        if matches!(self.input_mode, InputMode::Command | InputMode::Message) {
+        if matches!(self.input_mode, InputMode::Command | InputMode::Message | InputMode::Wipe | InputMode::Passphrase | InputMode::Argument) {
             f.set_cursor(
-                right_chunks[1].x + self.input.len() as u16 + 1,
-                right_chunks[1].y + 1,
+                right_chunks[3].x + cursor + 1,
+                right_chunks[3].y + 1,
             );
         }
     }
 
+    /// One-line metrics HUD shown above the status text when enabled.
+    fn metrics_line(&self) -> String {
+        let depths = self.network_client.queue_depths();
+        let lanes: Vec<String> = SendPriority::ALL
+            .iter()
+            .zip(depths)
+            .map(|(priority, depth)| format!("{} {}", priority.label(), depth))
+            .collect();
+        let stats = self.network_client.stats();
+        format!(
+            "[metrics] send queue: {} ({}) | ↑ {} ↓ {} | reconnects: {}",
+            depths.iter().sum::<usize>(),
+            lanes.join(", "),
+            format_bytes(stats.bytes_sent),
+            format_bytes(stats.bytes_received),
+            stats.reconnect_attempts()
+        )
+    }
+
+    /// Traffic and connection statistics for the session, for diagnosing metered or flaky links.
+    fn show_netstat(&mut self) {
+        let stats = self.network_client.stats();
+        let elapsed = (Local::now() - stats.since).num_seconds().max(1);
+        let state = if self.network_client.is_connected() { "connected" } else { "disconnected" };
+        let mut lines = vec![
+            format!("Session since {} ({}m {}s)", stats.since.format("%H:%M:%S"), elapsed / 60, elapsed % 60),
+            format!("Endpoint:  {}{} ({})", self.network_client.endpoint(), self.endpoint_note(), state),
+            format!(
+                "Sent:      {} in {} frames ({}/s)",
+                format_bytes(stats.bytes_sent),
+                stats.frames_sent(),
+                format_bytes(stats.bytes_sent / elapsed as u64)
+            ),
+            format!(
+                "Received:  {} in {} frames ({}/s)",
+                format_bytes(stats.bytes_received),
+                stats.frames_received(),
+                format_bytes(stats.bytes_received / elapsed as u64)
+            ),
+            String::new(),
+            format!("{:<24} {:>8} {:>8}", "Frame type", "sent", "received"),
+        ];
+        for (message_type, count) in &stats.frames {
+            lines.push(format!("{:<24} {:>8} {:>8}", message_type, count.sent, count.received));
+        }
+        lines.push(String::new());
+        lines.push(format!(
+            "Reconnects: {} ({} failed), connection drops: {}",
+            stats.reconnect_attempts(),
+            stats.reconnect_failures,
+            stats.connection_drops
+        ));
+        for reconnect in &stats.reconnects {
+            let outcome = if reconnect.succeeded { "connected" } else { "failed" };
+            lines.push(format!("  {} {} {}", reconnect.at.format("%H:%M:%S"), reconnect.endpoint, outcome));
+        }
+        self.show_popup("Network statistics", lines);
+    }
+
     fn render_settings(&mut self, f: &mut Frame) {
         let area = f.size();
         let popup_area = Rect {
@@ -686,6 +4368,113 @@ impl App {
         f.render_widget(help, chunks[2]);
     }
 
+    fn render_popup(&mut self, f: &mut Frame) {
+        let Some(popup) = &self.popup else {
+            return;
+        };
+        let area = f.size();
+        let popup_area = Rect {
+            x: area.width / 8,
+            y: area.height / 8,
+            width: area.width * 3 / 4,
+            height: area.height * 3 / 4,
+        };
+
+        f.render_widget(Clear, popup_area);
+        let mut lines = popup.lines.clone();
+        if !popup.actions.is_empty() {
+            let hints: Vec<String> = popup.actions.iter().map(|a| format!("{}: {}", a.key(), a.label())).collect();
+            lines.push(String::new());
+            lines.push(hints.join("   "));
+        }
+        let paragraph = Paragraph::new(lines.join("\n"))
+            .block(Block::default().borders(Borders::ALL).title(format!("{} (any key to close)", popup.title)))
+            .wrap(Wrap { trim: false });
+        f.render_widget(paragraph, popup_area);
+    }
+
+    fn render_switcher(&mut self, f: &mut Frame) {
+        let area = f.size();
+        let switcher_area = Rect {
+            x: area.width / 6,
+            y: area.height / 8,
+            width: area.width * 2 / 3,
+            height: area.height * 3 / 4,
+        };
+        let chunks = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([Constraint::Length(3), Constraint::Min(0)].as_ref())
+            .split(switcher_area);
+
+        let items: Vec<ListItem> = self.switcher
+            .filter(self.switcher_entries())
+            .into_iter()
+            .map(|entry| ListItem::new(Line::from(vec![
+                Span::raw(entry.label),
+                Span::styled(format!("  {}", entry.detail), Style::default().fg(Color::DarkGray)),
+            ])))
+            .collect();
+
+        f.render_widget(Clear, switcher_area);
+        let query = Paragraph::new(format!("{}▏", self.switcher.query))
+            .block(Block::default().borders(Borders::ALL).title("Go to a group or run a command (Enter, Esc close)"));
+        f.render_widget(query, chunks[0]);
+        let list = List::new(items)
+            .block(Block::default().borders(Borders::ALL))
+            .highlight_style(Style::default().add_modifier(Modifier::REVERSED));
+        f.render_stateful_widget(list, chunks[1], &mut self.switcher.state);
+    }
+
+    fn render_roster(&mut self, f: &mut Frame) {
+        let Some(group) = self.active_group.as_ref().and_then(|id| self.groups.get(id)) else {
+            return;
+        };
+        let area = f.size();
+        let roster_area = Rect {
+            x: area.width / 4,
+            y: area.height / 8,
+            width: area.width / 2,
+            height: area.height * 3 / 4,
+        };
+
+        let items: Vec<ListItem> = group.members
+            .iter()
+            .map(|member| {
+                let name = group.display_name(member);
+                let label = if name == member { member.clone() } else { format!("{} ({})", name, member) };
+                let admin = if group.is_admin(member) { " [admin]" } else { "" };
+                let verified = if self.config.verified_keys.contains_key(member) { " ✔" } else { "" };
+                ListItem::new(format!("{}{}{}", label, admin, verified))
+            })
+            .collect();
+        let roster = List::new(items)
+            .block(Block::default().borders(Borders::ALL).title(format!("Members of {} (Enter: details, Esc: close)", group.name)))
+            .highlight_style(Style::default().add_modifier(Modifier::REVERSED));
+
+        f.render_widget(Clear, roster_area);
+        f.render_stateful_widget(roster, roster_area, &mut self.roster_state);
+    }
+
+    fn render_locked(&mut self, f: &mut Frame) {
+        let area = f.size();
+        f.render_widget(Clear, area);
+
+        let popup_area = Rect {
+            x: area.width / 4,
+            y: area.height.saturating_sub(7) / 2,
+            width: area.width / 2,
+            height: 7.min(area.height),
+        };
+        let lock_text = format!(
+            "Session locked\n\nPassphrase: {}\n\n{}",
+            "*".repeat(self.unlock_input.chars().count()),
+            if self.unlock_failed { "Incorrect passphrase" } else { "Enter: Unlock  Esc: Clear" }
+        );
+        let lock = Paragraph::new(lock_text)
+            .block(Block::default().borders(Borders::ALL).title("Locked"));
+        f.render_widget(lock, popup_area);
+    }
+
     fn render_help(&mut self, f: &mut Frame) {
         let area = f.size();
         let popup_area = Rect {
@@ -697,7 +4486,7 @@ impl App {
 
         f.render_widget(Clear, popup_area);
 
-        let help_text = vec![
+        let mut help_text = vec![
             "MLS Enhanced Client Help",
             "",
             "Navigation:",
@@ -709,15 +4498,20 @@ impl App {
             "  m: Enter message mode",
             "  s: Settings",
             "  h: Help",
+            "  d: Dashboard",
+            "  a: Activity log of every status line, filterable by severity and text",
+            "  u: Rotate the active group's keys (⟳ marks groups overdue under their key-policy)",
+            "  Enter: Switch to the account under the cursor in the group list",
+            "  Esc: Select messages (r reply, + react, c copy, p pin, ! report, i protection)",
+            "  Ctrl+K: Search groups and commands; a command asks for its arguments, then runs",
+            "  Ctrl+L: Lock session",
+            "  Ctrl+Z: Cancel the last message still in its undo window",
             "  q: Quit",
             "",
             "Command Mode:",
-            "  create <group_name>: Create new group",
-            "  join <group_id>: Join existing group",
-            "  send <message>: Send message",
-            "  list: Show available groups",
-            "  status: Check MLS service connection",
-            "  quit: Exit application",
+        ];
+        help_text.extend_from_slice(command::HELP);
+        help_text.extend_from_slice(&[
             "",
             "MLS Service:",
             "  Groups are shared when connected to MLS service",
@@ -731,7 +4525,7 @@ impl App {
             "  - Try joining 'test-group' for demo",
             "",
             "Press any key to close",
-        ];
+        ]);
 
         let help_paragraph = Paragraph::new(help_text.join("\n"))
             .block(Block::default().borders(Borders::ALL).title("Help"))
@@ -740,8 +4534,106 @@ impl App {
     }
 }
 
+/// `--check-config`: report problems in config.json without starting the client.
+async fn check_config() -> Result<()> {
+    if !vault::locate(CONFIG_PATH).exists() {
+        println!("{} does not exist; defaults will be written on first start", CONFIG_PATH);
+        return Ok(());
+    }
+    let issues = config::check(&vault::read_to_string(CONFIG_PATH).await?);
+    if issues.is_empty() {
+        println!("{} is valid", CONFIG_PATH);
+        return Ok(());
+    }
+    for issue in &issues {
+        println!("{}: {}", CONFIG_PATH, issue);
+    }
+    Err(anyhow::anyhow!(
+        "{} has {} problem(s); start the client and run 'config repair' to rewrite it keeping the valid settings",
+        CONFIG_PATH,
+        issues.len()
+    ))
+}
+
+/// With a passphrase set, ask for it and enter the profile it opens before anything reads the
+/// data files.
+async fn open_profile() -> Result<()> {
+    let Some(slots) = KeySlots::load().await? else {
+        return Ok(());
+    };
+    let crypto = CryptoProvider::new();
+    for _ in 0..UNLOCK_ATTEMPTS {
+        if let Some(profile) = slots.open(&crypto, &read_passphrase()?)? {
+            vault::enter(profile)?;
+            return Ok(());
+        }
+        println!("Incorrect passphrase");
+    }
+    Err(anyhow::anyhow!("Incorrect passphrase"))
+}
+
+/// Read a passphrase from the terminal without echoing it; Esc or Ctrl+C gives up.
+fn read_passphrase() -> Result<String> {
+    print!("Passphrase: ");
+    io::stdout().flush()?;
+    enable_raw_mode()?;
+    let mut passphrase = String::new();
+    let read = loop {
+        match event::read() {
+            Ok(Event::Key(key)) if key.kind == KeyEventKind::Press => match key.code {
+                KeyCode::Enter => break Ok(()),
+                KeyCode::Esc => break Err(anyhow::anyhow!("Cancelled")),
+                KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => break Err(anyhow::anyhow!("Cancelled")),
+                KeyCode::Char(c) => passphrase.push(c),
+                KeyCode::Backspace => {
+                    passphrase.pop();
+                }
+                _ => {}
+            },
+            Ok(_) => {}
+            Err(e) => break Err(e.into()),
+        }
+    };
+    disable_raw_mode()?;
+    println!();
+    read.map(|()| passphrase)
+}
+
 #[tokio::main]
 async fn main() -> Result<()> {
+    if let Some(dir) = selftest::requested() {
+        return selftest::run(&dir).await;
+    }
+
+    // An attached terminal is only a view; the daemon holds the state and the lock
+    #[cfg(unix)]
+    if daemon::attach_requested() {
+        return daemon::attach().await;
+    }
+
+    open_profile().await?;
+    if std::env::args().any(|arg| arg == "--check-config") {
+        return check_config().await;
+    }
+
+    // Refuse to run a second client against the same MLS state
+    let _instance = instance::InstanceLock::acquire()?;
+
+    if let Some((archive, identity)) = backups::restore_requested() {
+        return backups::restore(&archive, identity.as_deref()).await;
+    }
+
+    #[cfg(unix)]
+    if daemon::requested() {
+        let mut app = App::new().await?;
+        return daemon::run(&mut app).await;
+    }
+
+    if simple::requested() {
+        let mut app = App::new().await?;
+        return simple::run(&mut app).await;
+    }
+
     // Setup terminal
     enable_raw_mode()?;
     let mut stdout = io::stdout();
@@ -753,28 +4645,29 @@ async fn main() -> Result<()> {
     let mut app = App::new().await?;
 
     // Main loop
+    let mut last_sync = Instant::now();
     loop {
+        app.update_terminal_title();
         terminal.draw(|f| app.render(f))?;
 
-        if let Event::Key(key) = event::read()? {
-            if key.kind == KeyEventKind::Press {
-                match app.screen {
-                    AppScreen::Help => {
-                        app.screen = AppScreen::Main;
-                    }
-                    _ => {
-                        app.handle_input(key.code).await?;
-                    }
-                }
+        if event::poll(Duration::from_millis(250))? {
+            match event::read()? {
+                Event::Resize(..) => app.handle_resize(),
+                Event::Key(key) if key.kind == KeyEventKind::Press => app.handle_key(key).await?,
+                _ => {}
             }
         }
 
+        app.tick(&mut last_sync).await?;
+
         if app.should_quit {
             break;
         }
     }
+    app.send_delayed_now().await;
 
     // Restore terminal
+    app.restore_terminal_title();
     disable_raw_mode()?;
     execute!(
         terminal.backend_mut(),
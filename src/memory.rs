@@ -0,0 +1,128 @@
+use std::cmp::Reverse;
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+use mls_client_core::archive::{self, ARCHIVE_DIR, PAGE_SIZE};
+use mls_client_core::errors::StorageError;
+use mls_client_core::{Group, Message};
+
+use crate::switcher::SwitchTarget;
+use crate::App;
+
+/// How often the loaded timelines are measured against `memory_budget_mb`.
+const BUDGET_CHECK_INTERVAL: Duration = Duration::from_secs(5);
+
+impl App {
+    /// Estimated bytes held by the loaded timelines and the active group's wrap cache.
+    pub(crate) fn timeline_footprint(&self) -> usize {
+        let messages: usize = self.groups.values().flat_map(|group| &group.messages).map(archive::footprint).sum();
+        messages + self.wrap_cache.footprint()
+    }
+
+    /// Over the memory budget, move the oldest page of the least recently viewed group to disk
+    /// until the timelines fit. The active group is never trimmed, nor any group below two pages.
+    pub(crate) async fn enforce_memory_budget(&mut self) {
+        if self.config.memory_budget_mb == 0 || self.recovery.is_some() || self.memory_checked.elapsed() < BUDGET_CHECK_INTERVAL {
+            return;
+        }
+        self.memory_checked = Instant::now();
+        let budget = usize::try_from(self.config.memory_budget_mb.saturating_mul(1024 * 1024)).unwrap_or(usize::MAX);
+        let mut used = self.timeline_footprint();
+        let archive_dir = self.data_path(ARCHIVE_DIR);
+        let mut evicted = false;
+        while used > budget {
+            let Some(group_id) = self.least_recently_viewed() else {
+                break;
+            };
+            match self.page_out(&archive_dir, &group_id).await {
+                Ok(freed) => used = used.saturating_sub(freed),
+                Err(e) => {
+                    self.set_error(format!("Failed to move old messages out of memory: {}", e));
+                    break;
+                }
+            }
+            evicted = true;
+        }
+        if evicted {
+            self.save_history().await;
+        }
+    }
+
+    /// Move the oldest page of `group_id`'s timeline to disk; returns the bytes freed.
+    pub(crate) async fn page_out(&mut self, archive_dir: &Path, group_id: &str) -> Result<usize, StorageError> {
+        let Some(group) = self.groups.get_mut(group_id) else {
+            return Ok(0);
+        };
+        let page: Vec<Message> = group.messages.drain(..PAGE_SIZE.min(group.messages.len())).collect();
+        if let Err(e) = archive::write_page(archive_dir, group_id, group.archived_pages, &page).await {
+            group.messages.splice(0..0, page);
+            return Err(e);
+        }
+        group.archived_pages += 1;
+        Ok(page.iter().map(archive::footprint).sum())
+    }
+
+    /// The group with a page to spare that was viewed longest ago; among groups not viewed
+    /// lately, the one that has been quiet longest.
+    fn least_recently_viewed(&self) -> Option<String> {
+        self.groups
+            .values()
+            .filter(|group| self.active_group.as_ref() != Some(&group.id) && group.messages.len() >= 2 * PAGE_SIZE)
+            .max_by_key(|group| {
+                let recency = self.switcher.recency(&SwitchTarget::Group(group.id.clone())).unwrap_or(usize::MAX);
+                (recency, Reverse(group.messages.last().map(Message::shown_time)))
+            })
+            .map(|group| group.id.clone())
+    }
+
+    /// Scroll the timeline up `lines`, reading older messages back from disk first when that
+    /// reaches the top.
+    pub(crate) async fn scroll_up(&mut self, lines: u16) {
+        if self.message_scroll < lines {
+            self.load_older_page().await;
+        }
+        self.message_scroll = self.message_scroll.saturating_sub(lines);
+    }
+
+    /// Put the newest page moved to disk back ahead of the active group's timeline, keeping
+    /// the view and the selection on the same messages.
+    pub(crate) async fn load_older_page(&mut self) {
+        let Some(group_id) = self.active_group.clone() else {
+            return;
+        };
+        let Some(page) = self.groups.get(&group_id).and_then(|group| group.archived_pages.checked_sub(1)) else {
+            return;
+        };
+        let loaded = archive::take_page(&self.data_path(ARCHIVE_DIR), &group_id, page).await;
+        let Some(group) = self.groups.get_mut(&group_id) else {
+            return;
+        };
+        // An unreadable page is skipped so the ones before it can still be reached
+        group.archived_pages = page;
+        let added = match loaded {
+            Ok(messages) => group.restore_page(messages),
+            Err(e) => {
+                self.set_warning(format!("Skipped older messages that could not be read: {}", e));
+                0
+            }
+        };
+        self.restored_above += added;
+        if let Some(selected) = &mut self.selected_message {
+            *selected += added;
+        }
+        self.save_history().await;
+    }
+
+    /// `group_id`'s whole timeline, with the pages on disk read in, e.g. for an export.
+    pub(crate) async fn full_timeline(&self, group_id: &str) -> Result<Option<Group>, StorageError> {
+        let Some(mut group) = self.groups.get(group_id).cloned() else {
+            return Ok(None);
+        };
+        let archive_dir = self.data_path(ARCHIVE_DIR);
+        for page in (0..group.archived_pages).rev() {
+            let messages = archive::read_page(&archive_dir, group_id, page).await?;
+            group.restore_page(messages);
+        }
+        Ok(Some(group))
+    }
+}
@@ -0,0 +1,690 @@
+use std::collections::BTreeMap;
+use std::time::{Duration, Instant};
+
+use anyhow::Result;
+use chrono::{Local, Utc};
+use mls_client_core::activity::Severity;
+use mls_client_core::crypto::to_hex;
+use mls_client_core::errors::{self, MlsError, PipelineError};
+use mls_client_core::filters::FilterAction;
+use mls_client_core::history::{HistoryBundle, HistoryShare};
+use mls_client_core::network::NetworkMessage;
+use mls_client_core::notify::{Notification, NotifyEvent};
+use mls_client_core::payload::{self, Content, Payload};
+use mls_client_core::pipeline::{self, Frame, Inbound};
+use mls_client_core::senders::{self, Automation};
+use mls_client_core::tasks::TaskId;
+use mls_client_core::transfers::{ResendRequest, UploadManifest};
+use mls_client_core::{Message, Onboarding, Protection, QuarantineReason, WirePolicy};
+use uuid::Uuid;
+
+use crate::undo_send::DelayedSend;
+use crate::{App, JoinRequest, RetryOp};
+
+impl App {
+    pub(crate) async fn send_message(&mut self, group_id: &str, message: &str) -> Result<()> {
+        self.send_message_as(group_id, Uuid::new_v4().to_string(), message).await
+    }
+
+    /// Send `message` under `message_id`, a new one or that of an attempt being retried.
+    pub(crate) async fn send_message_as(&mut self, group_id: &str, message_id: String, message: &str) -> Result<()> {
+        if let Some(denied) = self.posting_denied(group_id) {
+            self.set_warning(denied);
+            return Ok(());
+        }
+        if !self.message_fits(message) || self.message_held(group_id) {
+            return Ok(());
+        }
+
+        // Encrypt and hand to the delivery service when we hold MLS state for the group, after
+        // the undo window if there is one
+        let delay = Duration::from_secs(self.config.undo_send_secs);
+        let deliverable = self.network_client.is_connected() && self.mls_client.get_group(group_id).is_some();
+        let delayed = deliverable && (!delay.is_zero() || self.sends_waiting(group_id));
+        let mut pending = false;
+        if delayed {
+            self.delayed_sends.push(DelayedSend {
+                group_id: group_id.to_string(),
+                message_id: message_id.clone(),
+                message: message.to_string(),
+                due: Instant::now() + delay,
+            });
+            pending = true;
+        } else if deliverable {
+            match self.transmit_application(group_id, &message_id, message).await {
+                Ok(echoed) => pending = echoed,
+                Err(e) => {
+                    self.report_error(&errors::SEND_FAILED, e.to_string());
+                    self.last_failed = Some(RetryOp::Send { group_id: group_id.to_string(), message_id, message: message.to_string() });
+                    return Ok(());
+                }
+            }
+        }
+
+        if let Some(group) = self.groups.get_mut(group_id) {
+            // Our own message echoed back by the DS must not be appended twice; it reconciles
+            // with this local echo instead
+            group.seen_ids.insert(message_id.clone());
+            let msg = Message {
+                id: message_id,
+                sender: self.config.username.clone(),
+                content: message.to_string(),
+                timestamp: Local::now(),
+                group_id: group_id.to_string(),
+                server_seq: None,
+                received_at: None,
+                clock_skewed: false,
+                pinned: false,
+                reactions: BTreeMap::new(),
+                shared_by: None,
+                redacted_by: None,
+                translation: None,
+                pending,
+                protection: Protection::Sent,
+                attachment: false,
+                unsupported: None,
+            };
+            
+            group.insert_message(msg);
+            if delayed {
+                self.activity.push(Severity::Info, format!("Sending to {} in {}s; Ctrl+Z or 'undo' cancels", group.name, delay.as_secs()));
+            } else if !self.held_messages.contains_key(group_id) {
+                self.activity.push(Severity::Info, format!("Message sent to {}", group.name));
+            }
+        }
+        self.save_history().await;
+        Ok(())
+    }
+
+    /// Check `message` against the configured and server-advertised size limits, reporting
+    /// an error when it is too large.
+    pub(crate) fn message_fits(&mut self, message: &str) -> bool {
+        let limit = self.max_message_bytes();
+        if message.len() <= limit {
+            return true;
+        }
+        self.report_error(&errors::MESSAGE_TOO_LARGE, format!("{} bytes, {} over the {} byte limit", message.len(), message.len() - limit, limit));
+        false
+    }
+
+    /// Our own message size limit, lowered to the delivery service's if it advertised one.
+    pub(crate) fn max_message_bytes(&self) -> usize {
+        let server = self.network_client.server_limits().max_message_bytes;
+        server.map_or(self.config.max_message_bytes, |limit| limit.min(self.config.max_message_bytes))
+    }
+
+    pub(crate) fn max_attachment_bytes(&self) -> usize {
+        let server = self.network_client.server_limits().max_attachment_bytes;
+        server.map_or(self.config.max_attachment_bytes, |limit| limit.min(self.config.max_attachment_bytes))
+    }
+
+    /// Encrypt and send chat text, chunking it when it exceeds the configured threshold.
+    /// Returns whether it went out as a single `application` frame, which the delivery service
+    /// echoes back to us.
+    pub(crate) async fn transmit_application(&mut self, group_id: &str, message_id: &str, text: &str) -> Result<bool> {
+        let payload = pipeline::chat_payload(&self.mls_client, group_id, message_id, text, self.config.compress_payloads)?;
+        let threshold = self.config.chunk_threshold_bytes;
+        let frames = if payload.len() <= threshold {
+            vec![Frame::new("application", message_id, payload)]
+        } else if let Some(remote) = self.config.attachment_storage.remote() {
+            self.store_attachment(group_id, message_id, remote, &payload)?;
+            return Ok(false);
+        } else {
+            let chunks = pipeline::split(&self.crypto, message_id, &payload, threshold, self.max_attachment_bytes())?;
+            let frames = chunks.iter().map(Frame::chunk).collect::<Result<_, _>>()?;
+            // Kept until the delivery service has every slice, so a dropped link resumes the upload
+            self.transfers.uploads.insert(message_id.to_string(), UploadManifest {
+                group_id: group_id.to_string(),
+                chunks,
+                started: Utc::now(),
+            });
+            self.save_transfers().await;
+            frames
+        };
+
+        // Chunked payloads get a progress bar while the writer task drains them
+        let task = (frames.len() > 1).then(|| self.tasks.start(format!("Uploading {} chunks", frames.len()), frames.len()));
+        let echoed = task.is_none();
+        self.send_frames(group_id, frames, task).await?;
+        Ok(echoed)
+    }
+
+    /// Encrypt and queue application frames, cancelling `task` if any of them fails.
+    pub(crate) async fn send_frames(
+        &mut self,
+        group_id: &str,
+        frames: Vec<Frame>,
+        task: Option<TaskId>,
+    ) -> Result<()> {
+        if let Some(group) = self.groups.get(group_id).filter(|group| group.deleted.is_some()) {
+            if let Some(task) = task {
+                self.tasks.cancel(task);
+            }
+            return Err(PipelineError::Deleted(group.name.clone()).into());
+        }
+        for frame in frames {
+            let sent = match frame.seal(&mut self.mls_client, &self.config.username, group_id) {
+                Ok((network_message, details)) => {
+                    // Chunked uploads are not held; their slices go out as usual
+                    if task.is_none() && frame.message_type == "application" && self.previews_encryption() {
+                        self.hold_message(group_id, network_message, details, payload::is_compressed(&frame.plaintext));
+                        continue;
+                    }
+                    self.network_client.send_message_for_task(&network_message, task).await.map_err(anyhow::Error::from)
+                }
+                Err(e) => Err(e.into()),
+            };
+            if let Err(e) = sent {
+                if let Some(task) = task {
+                    self.tasks.cancel(task);
+                }
+                return Err(e);
+            }
+        }
+        Ok(())
+    }
+
+    /// Send the listed slices of an upload again, if it is still in the manifest.
+    pub(crate) async fn resend_chunks(&mut self, parent_id: &str, indexes: &[u32]) -> Result<()> {
+        let Some(upload) = self.transfers.uploads.get(parent_id) else {
+            return Ok(());
+        };
+        let group_id = upload.group_id.clone();
+        let frames: Vec<_> = upload.chunks
+            .iter()
+            .filter(|chunk| indexes.contains(&chunk.index))
+            .map(Frame::chunk)
+            .collect::<Result<_, _>>()?;
+        if frames.is_empty() {
+            return Ok(());
+        }
+        let task = self.tasks.start(format!("Resuming upload ({} chunks)", frames.len()), frames.len());
+        self.send_frames(&group_id, frames, Some(task)).await
+    }
+
+    /// After (re)connecting, resend upload slices the delivery service never stored and ask
+    /// senders for the slices still missing from interrupted downloads.
+    pub(crate) async fn resume_transfers(&mut self) -> Result<()> {
+        self.transfers.expire();
+
+        let uploads: Vec<(String, String, u32)> = self.transfers.uploads
+            .iter()
+            .map(|(parent_id, upload)| (parent_id.clone(), upload.group_id.clone(), upload.chunks.len() as u32))
+            .collect();
+        for (parent_id, group_id, total) in uploads {
+            let received = self.network_client.fetch_chunk_status(&group_id, &parent_id).await?;
+            let missing: Vec<u32> = (0..total).filter(|index| !received.contains(index)).collect();
+            if missing.is_empty() {
+                self.transfers.uploads.remove(&parent_id);
+            } else {
+                self.resend_chunks(&parent_id, &missing).await?;
+            }
+        }
+
+        for (group_id, sender, parent_id, indexes) in self.transfers.downloads.missing() {
+            let content = serde_json::to_vec(&ResendRequest { parent_id, indexes })?;
+            let mut network_message = NetworkMessage::for_group("chunk_resend", &self.config.username, &group_id, content);
+            network_message.recipient = Some(sender);
+            self.network_client.send_message(&network_message).await?;
+        }
+
+        self.save_transfers().await;
+        Ok(())
+    }
+
+    /// Returns an explanation when the local user may not post to the group.
+    pub(crate) fn posting_denied(&self, group_id: &str) -> Option<String> {
+        let group = self.groups.get(group_id)?;
+        if self.server_key_alarm() {
+            Some(format!(
+                "The identity key of {} does not match the pinned one: see 'trust', then 'trust reset' if the change is legitimate",
+                self.network_client.endpoint()
+            ))
+        } else if self.transport_status().is_some_and(|status| status.needs_acceptance()) {
+            Some(format!(
+                "{} is not protected in transit and has not been accepted: review it with 'accept-insecure'",
+                self.network_client.endpoint()
+            ))
+        } else {
+            group.posting_denied(&self.config.username)
+        }
+    }
+
+    /// Send `onboarding` to the whole group, or only to `recipient` when delivering it to a
+    /// member we just added.
+    pub(crate) async fn send_onboarding(&mut self, group_id: &str, onboarding: &Option<Onboarding>, recipient: Option<&str>) -> Result<()> {
+        self.send_payload(group_id, Payload::onboarding(onboarding), recipient).await
+    }
+
+    /// Send a typed payload sealed with `pipeline::seal_payload`, to `recipient` alone if given.
+    pub(crate) async fn send_payload(&mut self, group_id: &str, payload: Payload, recipient: Option<&str>) -> Result<()> {
+        let network_message = pipeline::seal_payload(&mut self.mls_client, &self.config.username, group_id, payload, recipient)?;
+        // Our own copy comes back from the delivery service; it was applied already
+        if let (Some(group), Some(frame_id)) = (self.groups.get_mut(group_id), &network_message.message_id) {
+            group.seen_ids.insert(frame_id.clone());
+        }
+        self.network_client.send_message(&network_message).await?;
+        Ok(())
+    }
+
+    pub(crate) fn handle_incoming(&mut self, message: NetworkMessage) {
+        let Some(group_id) = message.group_id.clone() else {
+            return;
+        };
+
+        // Invitations and join requests may reference groups we don't hold yet
+        match message.message_type.as_str() {
+            "invitation" => {
+                if !self.groups.contains_key(&group_id) && !self.pending_invitations.contains(&group_id) {
+                    self.pending_invitations.push(group_id.clone());
+                    if let Some(policy) = std::str::from_utf8(&message.content).ok().and_then(WirePolicy::parse) {
+                        self.invited_wire_policies.insert(group_id.clone(), policy);
+                    }
+                    let invited = format!("{} invited you to group {}", message.sender, group_id);
+                    self.record_event(invited.clone());
+                    self.notify(Notification { event: NotifyEvent::Invite, group_id: Some(group_id.clone()), text: invited });
+                }
+                return;
+            }
+            "token_join" => {
+                self.token_redeemed(&group_id, &message.sender, &message.content);
+                return;
+            }
+            "group_deleted" => {
+                self.mark_deleted(&group_id, &message.content);
+                return;
+            }
+            "join_request" => {
+                let is_admin = self.groups.get(&group_id)
+                    .map(|group| group.is_admin(&self.config.username))
+                    .unwrap_or(false);
+                if is_admin {
+                    self.pending_join_requests.push(JoinRequest {
+                        group_id: group_id.clone(),
+                        client_id: message.sender.clone(),
+                    });
+                    let requested = format!("{} asked to join group {}", message.sender, group_id);
+                    self.record_event(requested.clone());
+                    self.notify(Notification { event: NotifyEvent::Invite, group_id: Some(group_id.clone()), text: requested });
+                }
+                return;
+            }
+            _ => {}
+        }
+
+        let message_id = message.message_id.clone()
+            .unwrap_or_else(|| self.content_fingerprint(&message.content));
+        let Some(group) = self.groups.get_mut(&group_id) else {
+            return;
+        };
+        if !pipeline::admit(group, &message, &message_id, &self.config.username) {
+            return;
+        }
+        let opened = pipeline::open(
+            &mut self.mls_client,
+            &self.crypto,
+            &mut self.transfers.downloads,
+            group,
+            &self.config.username,
+            &message_id,
+            &message,
+        );
+        match opened {
+            Ok(Inbound::Application { id, sender, content, proof }) => {
+                if !self.deliver_application(&group_id, id, sender, content, &message, Protection::Authenticated(proof)) {
+                    return;
+                }
+            }
+            Ok(Inbound::Reassembled { id, sender, content, proof }) => {
+                self.deliver_application(&group_id, id, sender, content, &message, Protection::Authenticated(proof));
+            }
+            Ok(Inbound::Attachment { id, sender, content, proof }) => {
+                self.receive_attachment(&group_id, id, sender, &content, &message, proof);
+            }
+            Ok(Inbound::History { sender, content }) => self.receive_history(&group_id, sender, &content),
+            Ok(Inbound::Resend(request)) => self.pending_resends.push(request),
+            Ok(Inbound::Commit { summary, superseded }) => self.note_commit(&group_id, &summary, superseded),
+            Ok(Inbound::Proposal { sender }) => {
+                let name = self.groups.get(&group_id).map_or(group_id.clone(), |group| group.name.clone());
+                self.record_event(format!("{} proposed a change in {}; the next commit applies it (e.g. 'update')", sender, name));
+            }
+            Ok(Inbound::Removed { by }) => self.mark_removed(&group_id, &by),
+            Ok(Inbound::Elsewhere) => return,
+            Ok(Inbound::Ignored) => {}
+            Err(PipelineError::Reassemble { sender, source }) => {
+                self.set_warning(format!("Dropped chunk from {} in group {}: {}", sender, group_id, source));
+            }
+            Err(PipelineError::Mls(e)) => return self.report_incoming_failure(&group_id, e, &message),
+            Err(e) => return self.set_warning(format!("Dropped a message in group {}: {}", group_id, e)),
+        }
+        if let Some(group) = self.groups.get_mut(&group_id) {
+            group.seen_ids.insert(message_id);
+        }
+    }
+
+    /// Explain an inbound MLS failure; missing group state can be restored by rejoining. Frames
+    /// that may decrypt once the group moves on are quarantined rather than dropped.
+    pub(crate) fn report_incoming_failure(&mut self, group_id: &str, error: MlsError, message: &NetworkMessage) {
+        let reason = match error {
+            MlsError::UnknownGroup(_) => {
+                self.set_warning(format!("No MLS state for group {}; 'retry' rejoins it to read new messages", group_id));
+                self.last_failed = Some(RetryOp::Join { group_id: group_id.to_string() });
+                return;
+            }
+            MlsError::WireFormatRejected { .. } => {
+                self.report_error(&errors::WIRE_FORMAT_REJECTED, error.to_string());
+                return;
+            }
+            MlsError::WrongEpoch { .. } => QuarantineReason::WrongEpoch,
+            MlsError::UnknownSender { .. } => QuarantineReason::UnknownSender,
+            MlsError::PolicyViolation { .. } => QuarantineReason::PolicyViolation,
+            _ => QuarantineReason::ProcessingFailed,
+        };
+        let message_id = message.message_id.clone().unwrap_or_else(|| self.content_fingerprint(&message.content));
+        let Some(group) = self.groups.get_mut(group_id) else {
+            return;
+        };
+        let first = !group.quarantine.iter().any(|entry| entry.message_id == message_id);
+        group.quarantine(message_id, message.clone(), reason, error.to_string());
+        self.activity.push(Severity::Info, format!(
+            "Quarantined a message from {} in {} ({}); 'quarantine' lists it",
+            message.sender, group.name, reason.code()
+        ));
+        // Retries of a rejected commit are not alerted again
+        if let (MlsError::PolicyViolation { reason, .. }, true) = (error, first) {
+            let summary = format!("rejected a commit from {} in {}", message.sender, group.name);
+            self.security_alert(summary, vec![
+                format!("Reason: {}", reason),
+                "The commit was not applied and is quarantined. The group stays in its current".to_string(),
+                "epoch, so later messages will be quarantined too until the commit is accepted.".to_string(),
+                "If the change is legitimate, relax handshake_policy ('config set handshake_policy ...');".to_string(),
+                "quarantined commits are retried when the policy changes.".to_string(),
+            ]);
+        }
+    }
+
+    /// Interrupt with a popup about a handshake message the handshake policy rejected.
+    pub(crate) fn security_alert(&mut self, summary: String, details: Vec<String>) {
+        self.record_event(format!("Security alert: {}", summary));
+        let mut lines = vec![format!("The handshake policy {}.", summary), String::new()];
+        lines.extend(details);
+        self.show_popup("Security alert", lines);
+        self.set_status(format!("Security alert: {}", summary));
+    }
+
+    /// Process a group's quarantined frames again now that commits have moved it forward.
+    pub(crate) fn retry_quarantine(&mut self, group_id: &str) {
+        let Some(group) = self.groups.get(group_id) else {
+            return;
+        };
+        let entries: Vec<(String, u32, NetworkMessage)> = group.quarantine
+            .iter()
+            .map(|entry| (entry.message_id.clone(), entry.attempts, entry.frame.clone()))
+            .collect();
+        let mut recovered = 0;
+        for (message_id, attempts, frame) in entries {
+            self.handle_incoming(frame);
+            let Some(group) = self.groups.get_mut(group_id) else {
+                return;
+            };
+            // A repeat failure bumps the entry's attempts; anything else means it went through
+            let failed_again = group.quarantine.iter().any(|entry| entry.message_id == message_id && entry.attempts > attempts);
+            if !failed_again {
+                group.quarantine.retain(|entry| entry.message_id != message_id);
+                recovered += 1;
+            }
+        }
+        if recovered > 0 {
+            self.set_status(format!("Recovered {} quarantined message(s)", recovered));
+        }
+    }
+
+    /// Stable ID for messages whose sender did not provide one.
+    pub(crate) fn content_fingerprint(&self, content: &[u8]) -> String {
+        self.crypto
+            .sha256(content)
+            .map(|digest| to_hex(&digest))
+            .unwrap_or_default()
+    }
+
+    /// Append a decrypted application message to the group timeline. Returns false if the ID
+    /// sealed in the payload is not the frame's, so the frame's ID is not taken as seen.
+    pub(crate) fn deliver_application(
+        &mut self,
+        group_id: &str,
+        message_id: String,
+        sender: String,
+        content: Vec<u8>,
+        envelope: &NetworkMessage,
+        protection: Protection,
+    ) -> bool {
+        let Some(group) = self.groups.get(group_id) else {
+            return true;
+        };
+        let read = pipeline::read_application(group, &message_id, &sender, &content);
+        let name = group.name.clone();
+        let content = match read {
+            Ok(content) => content,
+            Err(PipelineError::NotAllowed { kind, sender }) => {
+                let rejected = format!("Rejected {} content from {} in group {}: only admins may send it", kind, sender, name);
+                self.set_status(rejected.clone());
+                self.record_event(rejected);
+                return true;
+            }
+            Err(e) => {
+                self.set_warning(format!("Dropped a message from {} in group {}: {}", sender, group_id, e));
+                return false;
+            }
+        };
+        // A newer client's content type shows as the fallback text it came with
+        let (text, unsupported) = match content {
+            Content::Chat(text) => (text, None),
+            Content::Reaction { message_id, emoji } => {
+                if let Some(group) = self.groups.get_mut(group_id) {
+                    group.apply_reaction(&sender, &message_id, emoji);
+                }
+                return true;
+            }
+            Content::Profile { nick } => {
+                self.apply_profile(group_id, &sender, nick.trim());
+                return true;
+            }
+            Content::Redaction { message_id } => {
+                self.receive_redaction(group_id, sender, &message_id);
+                return true;
+            }
+            Content::Onboarding(onboarding) => {
+                // Deliveries to a member someone just added came addressed to them alone
+                self.receive_onboarding(group_id, sender, onboarding, envelope.recipient.is_some());
+                return true;
+            }
+            Content::Unsupported { content_type, fallback } => (fallback.unwrap_or_default(), Some(content_type)),
+        };
+        let is_active = self.active_group.as_deref() == Some(group_id);
+        // Bridged and bot senders only set off what a `trust-sender` rule allows
+        let blocked: Vec<Automation> = Automation::ALL
+            .into_iter()
+            .filter(|automation| !senders::allows(&self.config.bridged_senders, &self.config.trusted_senders, &sender, *automation))
+            .collect();
+        let auto_translate = self.auto_translates(group_id);
+        let previews_links = self.previews_links(group_id);
+        let dnd = self.dnd_active();
+        let Some(group) = self.groups.get_mut(group_id) else {
+            return true;
+        };
+        let mention = group.mentions(&self.config.username, &text);
+        if !is_active {
+            group.unread += 1;
+            if mention {
+                group.unread_mentions += 1;
+            }
+        }
+        let notification = (!is_active || mention).then(|| {
+            let (event, kind) = if mention {
+                (NotifyEvent::Mention, "mentioned you")
+            } else {
+                (NotifyEvent::Message, "sent a message")
+            };
+            Notification {
+                event,
+                group_id: Some(group_id.to_string()),
+                text: format!("{} {} in {}", group.display_name(&sender), kind, group.name),
+            }
+        });
+
+        let sent = pipeline::sent_at(envelope, self.network_client.server_clock_offset());
+        let clock_skewed = sent.skewed(self.config.clock_skew_threshold_secs);
+        if clock_skewed {
+            self.activity.push(Severity::Info, format!("Clock skew: {}'s message claims a time {}s off from server time", sender, sent.skew));
+        }
+
+        let translate = (auto_translate && sender != self.config.username).then(|| (message_id.clone(), text.clone()));
+        // Read aloud like a notification: not our own messages, not hidden ones, and not
+        // while Do Not Disturb holds notifications back
+        let hidden = self.filters.action_for(group_id, &text) == Some(FilterAction::Hide);
+        // Only links others post: previewing our own would fetch what we already know
+        let preview_text = (previews_links && sender != self.config.username && !hidden).then(|| text.clone());
+        let held = dnd && !(mention && self.config.dnd_allow_mentions);
+        let speak = self.config.speech.iter().any(|speech| speech.speaks(group_id, mention))
+            && sender != self.config.username
+            && !hidden
+            && !held;
+        let utterance = speak.then(|| format!("{} in {}: {}", group.display_name(&sender), group.name, text));
+        let suppressed: Vec<Automation> = [
+            (Automation::Notify, notification.is_some()),
+            (Automation::Speak, utterance.is_some()),
+            (Automation::Translate, translate.is_some()),
+            (Automation::Preview, preview_text.is_some()),
+        ]
+        .into_iter()
+        .filter(|(automation, due)| *due && blocked.contains(automation))
+        .map(|(automation, _)| automation)
+        .collect();
+        let (notification, utterance, translate, preview_text) = (
+            notification.filter(|_| !suppressed.contains(&Automation::Notify)),
+            utterance.filter(|_| !suppressed.contains(&Automation::Speak)),
+            translate.filter(|_| !suppressed.contains(&Automation::Translate)),
+            preview_text.filter(|_| !suppressed.contains(&Automation::Preview)),
+        );
+        let bridged_sender = (!suppressed.is_empty()).then(|| sender.clone());
+        group.insert_message(Message {
+            id: message_id,
+            sender,
+            content: text,
+            timestamp: sent.timestamp,
+            group_id: group_id.to_string(),
+            server_seq: envelope.sequence,
+            received_at: sent.received_at,
+            clock_skewed,
+            pinned: false,
+            reactions: BTreeMap::new(),
+            shared_by: None,
+            redacted_by: None,
+            translation: None,
+            pending: false,
+            protection,
+            attachment: false,
+            unsupported,
+        });
+        if let Some((message_id, text)) = translate {
+            self.request_translation(group_id, message_id, text);
+        }
+        if let Some(text) = preview_text {
+            self.request_preview(&text);
+        }
+        if let Some(utterance) = utterance {
+            self.announce(utterance);
+        }
+        if let Some(notification) = notification {
+            self.notify(notification);
+        }
+        if let Some(sender) = bridged_sender {
+            self.held_back_automations(&sender, suppressed);
+        }
+        true
+    }
+
+    /// Set or clear `sender`'s nickname in the group, unless it would pass them off as another
+    /// member.
+    fn apply_profile(&mut self, group_id: &str, sender: &str, nick: &str) {
+        let Some(group) = self.groups.get_mut(group_id) else {
+            return;
+        };
+        if !group.set_nick(sender, nick) {
+            self.activity.push(Severity::Warning, format!("Ignored nickname {} from {} in {}: it names another member", nick, sender, group.name));
+        }
+    }
+
+    /// Re-encrypt earlier messages in the current epoch and send them to the member just added.
+    pub(crate) async fn share_history(&mut self, group_id: &str, identity: &str, share: HistoryShare) -> Result<()> {
+        let Some(group) = self.groups.get(group_id) else {
+            return Ok(());
+        };
+        let available = group.messages.iter().filter(|message| message.shared_by.is_none() && message.redacted_by.is_none()).count();
+        let bundle = HistoryBundle::select(&group.messages, share, self.max_message_bytes());
+        if bundle.messages.is_empty() {
+            return Ok(());
+        }
+        let shared = bundle.messages.len();
+        let network_message = pipeline::seal_history(&mut self.mls_client, &self.config.username, group_id, &bundle, identity)?;
+        self.network_client.send_message(&network_message).await?;
+        let mut status = format!("Invited {} and shared {} earlier message(s)", identity, shared);
+        if shared < available && matches!(share, HistoryShare::Last(count) if count > shared) {
+            status.push_str(" (older ones left out to stay within the message size limit)");
+        }
+        self.set_status(status);
+        Ok(())
+    }
+
+    /// Merge messages a member re-shared from before we joined, marked with who shared them.
+    pub(crate) fn receive_history(&mut self, group_id: &str, sender: String, content: &[u8]) {
+        let Some(group) = self.groups.get_mut(group_id) else {
+            return;
+        };
+        let bundle: HistoryBundle = match serde_json::from_slice(content) {
+            Ok(bundle) => bundle,
+            Err(e) => {
+                self.set_warning(format!("Ignored unreadable history from {}: {}", sender, e));
+                return;
+            }
+        };
+        let added = group.merge_shared(bundle, &sender);
+        let received = format!("{} shared {} earlier message(s) in {}", sender, added, group.name);
+        self.set_status(received.clone());
+        self.record_event(received);
+    }
+
+    /// Apply a redaction from `sender`, an admin of the group as `Group::accepts` checked.
+    pub(crate) fn receive_redaction(&mut self, group_id: &str, sender: String, message_id: &str) {
+        let Some(group) = self.groups.get_mut(group_id) else {
+            return;
+        };
+        let name = group.name.clone();
+        let redacted = match group.redact(message_id, &sender, Local::now()) {
+            Some(author) => format!("{} redacted a message from {} in {}", sender, author, name),
+            None => format!("{} redacted a message in {} that has not arrived here", sender, name),
+        };
+        self.record_event(redacted);
+    }
+
+    /// Store an onboarding message from an admin, as `Group::accepts` checked. One addressed
+    /// to us came with our own addition, so it is pinned above the timeline.
+    pub(crate) fn receive_onboarding(&mut self, group_id: &str, sender: String, onboarding: Option<Onboarding>, addressed: bool) {
+        let Some(group) = self.groups.get_mut(group_id) else {
+            return;
+        };
+        let event = match &onboarding {
+            Some(_) if addressed => {
+                group.onboarding_pinned = true;
+                format!("{} sent you the onboarding message of {}", sender, group.name)
+            }
+            Some(_) => format!("{} changed the onboarding message of {}", sender, group.name),
+            None => {
+                group.onboarding_pinned = false;
+                format!("{} removed the onboarding message of {}", sender, group.name)
+            }
+        };
+        group.onboarding = onboarding;
+        self.record_event(event);
+    }
+}
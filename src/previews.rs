@@ -0,0 +1,84 @@
+use anyhow::Result;
+use mls_client_core::errors::PreviewError;
+use mls_client_core::preview::{self, Preview, PREVIEWS_PATH};
+use std::path::Path;
+
+use crate::{App, AppEvent};
+
+impl App {
+    /// `preview [on|off]`: show or switch link previews for the active group.
+    pub(crate) async fn preview_command(&mut self, parts: &[&str]) -> Result<()> {
+        let Some(group_id) = self.active_group.clone() else {
+            self.set_warning("No active group selected");
+            return Ok(());
+        };
+        let Some(previews) = self.config.link_previews.as_mut() else {
+            self.set_warning(PreviewError::NotConfigured.to_string());
+            return Ok(());
+        };
+        match parts {
+            [] => {
+                let enabled = previews.groups.contains(&group_id);
+                self.set_status(format!("Link previews are {} for this group", if enabled { "on" } else { "off" }));
+            }
+            [toggle @ ("on" | "off")] => {
+                let enable = *toggle == "on";
+                if enable {
+                    previews.groups.insert(group_id);
+                } else {
+                    previews.groups.remove(&group_id);
+                }
+                self.save_config().await?;
+                self.set_status(if enable {
+                    "Link previews on for this group: links others post are fetched through the proxy".to_string()
+                } else {
+                    "Link previews off for this group".to_string()
+                });
+            }
+            _ => self.set_warning("Usage: preview [on|off]"),
+        }
+        Ok(())
+    }
+
+    /// Whether links received in `group_id` get preview cards.
+    pub(crate) fn previews_links(&self, group_id: &str) -> bool {
+        self.config.link_previews.iter().any(|previews| previews.groups.contains(group_id))
+    }
+
+    /// Fetch a preview for the first link in `text` unless it is cached or was tried this session.
+    pub(crate) fn request_preview(&mut self, text: &str) {
+        let Some(proxy) = self.config.link_previews.as_ref().map(|previews| previews.proxy.clone()) else {
+            return;
+        };
+        let Some(url) = preview::first_url(text) else {
+            return;
+        };
+        if self.previews.get(url).is_some() || !self.preview_requests.insert(url.to_string()) {
+            return;
+        }
+        let events = self.events_tx.clone();
+        let url = url.to_string();
+        tokio::spawn(async move {
+            let preview = preview::fetch(&proxy, &url).await;
+            let _ = events.send(AppEvent::PreviewFetched { url, preview });
+        });
+    }
+
+    pub(crate) async fn preview_fetched(&mut self, url: String, preview: Result<Preview, PreviewError>) {
+        match preview {
+            Ok(preview) => {
+                self.previews.insert(url, preview);
+                self.save_previews().await;
+            }
+            // Pages without a title simply get no card
+            Err(PreviewError::NoMetadata) => {}
+            Err(e) => self.record_event(format!("No preview for {}: {}", url, e)),
+        }
+    }
+
+    async fn save_previews(&mut self) {
+        if let Err(e) = preview::save(&self.previews, Path::new(PREVIEWS_PATH)).await {
+            self.set_error(format!("Failed to save link previews: {}", e));
+        }
+    }
+}
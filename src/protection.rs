@@ -0,0 +1,49 @@
+use mls_client_core::Protection;
+use ratatui::style::{Color, Style};
+
+use crate::App;
+
+/// Colour of a message's protection marker: green for what MLS authenticated as the sender.
+pub fn marker_style(protection: &Protection) -> Style {
+    match protection {
+        Protection::Authenticated(_) | Protection::Sent => Style::default().fg(Color::Green),
+        Protection::Shared => Style::default().fg(Color::Yellow),
+        Protection::Local | Protection::Unrecorded => Style::default().fg(Color::DarkGray),
+    }
+}
+
+impl App {
+    /// Selection mode `i`: what protected the selected message, down to the epoch, sender
+    /// leaf and additional authenticated data MLS checked.
+    pub(crate) fn show_protection(&mut self, group_id: &str, index: usize) {
+        let Some(group) = self.groups.get(group_id) else {
+            return;
+        };
+        let Some(message) = group.messages.get(index) else {
+            return;
+        };
+        let mut lines = vec![
+            format!("{} {}", message.protection.marker(), message.protection.describe()),
+            String::new(),
+            format!("Sender:       {}", group.display_name(&message.sender)),
+        ];
+        match &message.protection {
+            Protection::Authenticated(proof) => {
+                lines.push(format!("Epoch:        {}", proof.epoch));
+                lines.push(format!("Sender leaf:  {}", proof.leaf.map_or("none (not a member)".to_string(), |leaf| leaf.to_string())));
+                lines.push(format!("Wire format:  {}", proof.wire_format));
+                lines.push(format!("AAD:          {}", if proof.aad.is_empty() { "empty" } else { &proof.aad }));
+            }
+            Protection::Shared => {
+                let sharer = message.shared_by.as_deref().map_or("unknown", |member| group.display_name(member));
+                lines.push(format!("Shared by:    {}", sharer));
+            }
+            Protection::Sent | Protection::Local | Protection::Unrecorded => {}
+        }
+        lines.push(format!("Message ID:   {}", message.id));
+        if let Some(sequence) = message.server_seq {
+            lines.push(format!("Delivery seq: {}", sequence));
+        }
+        self.show_popup("Message protection", lines);
+    }
+}
@@ -0,0 +1,137 @@
+use std::path::Path;
+
+use anyhow::Result;
+use crossterm::event::KeyCode;
+use mls_client_core::archive::{self, ARCHIVE_DIR};
+use mls_client_core::integrity::{self, Report, DAMAGED_HISTORY_PATH};
+use ratatui::{
+    layout::Rect,
+    widgets::{Block, Borders, Clear, Paragraph, Wrap},
+    Frame,
+};
+
+use crate::{App, AppScreen};
+
+impl App {
+    /// Show what the startup check found and hold the history as it is until the user picks
+    /// a way to recover; nothing is saved before then.
+    pub(crate) fn start_recovery(&mut self, report: Report) {
+        self.set_error(format!(
+            "Stored history failed its integrity check ({} problem(s)); choose how to recover",
+            report.problems.len()
+        ));
+        let event = self.status_text().to_string();
+        self.record_event(event);
+        for problem in &report.problems {
+            self.record_event(format!("Integrity check: {}", problem));
+        }
+        self.recovery = Some(report);
+        self.screen = AppScreen::Recovery;
+    }
+
+    /// The recovery choices with their keys, for the modal and simple mode.
+    pub(crate) fn recovery_options(&self) -> Vec<(char, String)> {
+        let Some(report) = &self.recovery else {
+            return Vec::new();
+        };
+        let mut options = vec![('b', "Restore the newest backup that passes the check".to_string())];
+        if !report.damaged.is_empty() {
+            options.push((
+                'r',
+                format!("Rejoin the {} damaged group(s): drop their local copy and ask to join again", report.damaged.len()),
+            ));
+        }
+        options.push(('c', "Continue with what could be read".to_string()));
+        options.push(('q', "Quit without changing anything".to_string()));
+        options
+    }
+
+    /// Every choice but quitting first keeps the damaged file as `DAMAGED_HISTORY_PATH`.
+    pub(crate) async fn handle_recovery_input(&mut self, key: KeyCode) -> Result<()> {
+        let choice = match key {
+            KeyCode::Char('q') => {
+                self.should_quit = true;
+                return Ok(());
+            }
+            KeyCode::Char(c) if self.recovery_options().iter().any(|(option, _)| *option == c) => c,
+            _ => return Ok(()),
+        };
+        let backup = match choice {
+            'b' => match integrity::newest_good_backup(Path::new(ARCHIVE_DIR), &self.audit_key).await {
+                Some(backup) => Some(backup),
+                None => {
+                    self.set_error("No backup passes the integrity check; rejoin the damaged groups or continue");
+                    return Ok(());
+                }
+            },
+            _ => None,
+        };
+        if let Err(e) = integrity::set_aside().await {
+            self.set_error(format!("Could not keep a copy of the damaged history, so nothing was changed: {}", e));
+            return Ok(());
+        }
+        let Some(report) = self.recovery.take() else {
+            return Ok(());
+        };
+        self.screen = AppScreen::Dashboard;
+
+        let outcome = match (choice, backup) {
+            ('b', Some((path, groups))) => match integrity::restore_backup(&path).await {
+                Ok(()) => {
+                    self.groups = groups;
+                    self.active_group = None;
+                    format!("Restored the history from {}", path.display())
+                }
+                Err(e) => format!("Failed to restore {}: {}; continuing with what could be read", path.display(), e),
+            },
+            ('r', _) => {
+                let archive_dir = self.data_path(ARCHIVE_DIR);
+                for group_id in &report.damaged {
+                    self.groups.remove(group_id);
+                    if self.active_group.as_ref() == Some(group_id) {
+                        self.active_group = None;
+                    }
+                    if let Err(e) = archive::remove_group(&archive_dir, group_id).await {
+                        self.record_event(format!("Failed to delete older messages of {}: {}", group_id, e));
+                    }
+                }
+                format!("Dropped {} damaged group(s) and asked to join them again", report.damaged.len())
+            }
+            _ => "Continuing with the history that could be read".to_string(),
+        };
+        self.set_status(format!("{} (the damaged file is kept as {})", outcome, DAMAGED_HISTORY_PATH));
+        let event = self.status_text().to_string();
+        self.record_event(event);
+        self.save_history().await;
+
+        if choice == 'r' {
+            for group_id in &report.damaged {
+                self.join_group(group_id).await?;
+            }
+        }
+        Ok(())
+    }
+
+    pub(crate) fn render_recovery(&self, f: &mut Frame) {
+        let Some(report) = &self.recovery else {
+            return;
+        };
+        let mut lines: Vec<String> = report.problems.iter().map(|problem| format!("- {}", problem)).collect();
+        lines.push(String::new());
+        lines.extend(self.recovery_options().into_iter().map(|(key, option)| format!("{}: {}", key, option)));
+
+        let area = f.size();
+        let height = (lines.len() as u16 + 2).min(area.height);
+        let recovery_area = Rect {
+            x: area.width / 8,
+            y: area.height.saturating_sub(height) / 2,
+            width: area.width * 3 / 4,
+            height,
+        };
+        f.render_widget(Clear, recovery_area);
+        let paragraph = Paragraph::new(lines.join("\n"))
+            .block(Block::default().borders(Borders::ALL).title("Stored history is damaged"))
+            .wrap(Wrap { trim: false });
+        f.render_widget(paragraph, recovery_area);
+    }
+}
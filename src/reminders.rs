@@ -0,0 +1,136 @@
+use anyhow::Result;
+use chrono::{Duration, Local};
+use mls_client_core::notify::{Notification, NotifyEvent};
+use mls_client_core::scheduler::{self, Job, JobKind, SCHEDULE_PATH};
+use std::path::Path;
+
+use crate::App;
+
+/// A scheduled send further past its time than this (e.g. the client was closed) is shown
+/// as a reminder but not posted, as the message is probably stale.
+const MAX_SEND_DELAY: Duration = Duration::minutes(15);
+
+impl App {
+    /// `remind [post] <when> <text>`, `remind list` or `remind cancel <id>`.
+    pub(crate) async fn remind_command(&mut self, parts: &[&str]) -> Result<()> {
+        let usage = "Usage: remind [post] <10m|1h30m|HH:MM|YYYY-MM-DDTHH:MM> <text> | remind list | remind cancel <id>";
+        match parts {
+            ["list"] => self.show_reminders(),
+            ["cancel", id] => match id.parse().ok().and_then(|id| self.scheduler.cancel(id)) {
+                Some(job) => {
+                    self.save_schedule().await;
+                    self.set_status(format!("Cancelled reminder {}: {}", job.id, job.text));
+                }
+                None => self.set_warning(format!("No reminder {}; see 'remind list'", id)),
+            },
+            ["post", when, text @ ..] if !text.is_empty() => self.schedule(JobKind::Send, when, text.join(" ")).await,
+            [when, text @ ..] if !text.is_empty() => self.schedule(JobKind::Reminder, when, text.join(" ")).await,
+            _ => self.set_status(usage.to_string()),
+        }
+        Ok(())
+    }
+
+    async fn schedule(&mut self, kind: JobKind, when: &str, text: String) {
+        let Some(due) = scheduler::parse_when(when, Local::now()) else {
+            self.set_error(format!("Cannot schedule for '{}': use a delay (10m, 1h30m, 2d), HH:MM or YYYY-MM-DDTHH:MM in the future", when));
+            return;
+        };
+        let group_id = self.active_group.clone();
+        if kind == JobKind::Send {
+            let Some(group_id) = &group_id else {
+                self.set_status("Select the group to post to before 'remind post'");
+                return;
+            };
+            if let Some(denied) = self.posting_denied(group_id) {
+                self.set_warning(denied);
+                return;
+            }
+        }
+        let id = self.scheduler.add(due, kind, text, group_id, self.account.clone());
+        self.save_schedule().await;
+        let action = if kind == JobKind::Send { "post to this group" } else { "remind you" };
+        self.set_status(format!("Reminder {} will {} at {}", id, action, due.format("%Y-%m-%d %H:%M")));
+    }
+
+    fn show_reminders(&mut self) {
+        if self.scheduler.jobs().is_empty() {
+            self.set_status("No reminders scheduled");
+            return;
+        }
+        let lines = self.scheduler.jobs().iter().map(|job| {
+            let place = match self.job_group_name(job) {
+                Some(name) if job.kind == JobKind::Send => format!(" → posts to {}", name),
+                Some(name) => format!(" in {}", name),
+                None => String::new(),
+            };
+            format!("{:>3}  {}{}: {}", job.id, job.due.format("%Y-%m-%d %H:%M"), place, job.text)
+        }).collect();
+        self.show_popup("Reminders", lines);
+    }
+
+    /// Name of the job's group in the account that created it, if both are still there.
+    fn job_group_name(&self, job: &Job) -> Option<String> {
+        let group_id = job.group_id.as_ref()?;
+        let groups = if job.account == self.account {
+            &self.groups
+        } else {
+            &self.accounts.iter().find(|account| account.name == job.account)?.groups
+        };
+        groups.get(group_id).map(|group| group.name.clone())
+    }
+
+    /// Show the reminders that came due and post the scheduled sends among them, from the
+    /// account that created them.
+    pub(crate) async fn run_scheduled(&mut self) -> Result<()> {
+        let due = self.scheduler.take_due(Local::now());
+        if due.is_empty() {
+            return Ok(());
+        }
+        for job in due {
+            let late = Local::now() - job.due;
+            let outcome = match job.kind {
+                JobKind::Reminder => None,
+                JobKind::Send if late > MAX_SEND_DELAY => {
+                    Some(format!("not posted: {} minutes late", late.num_minutes()))
+                }
+                JobKind::Send => Some(self.post_scheduled(&job).await?),
+            };
+            let place = self.job_group_name(&job).map(|name| format!(" ({})", name)).unwrap_or_default();
+            let mut text = format!("⏰ Reminder{}: {}", place, job.text);
+            if let Some(outcome) = outcome {
+                text.push_str(&format!(" [{}]", outcome));
+            }
+            self.record_event(text.clone());
+            // Shown even when Do Not Disturb holds the alert back
+            self.set_status(text.clone());
+            self.notify(Notification { event: NotifyEvent::Reminder, group_id: job.group_id.clone(), text });
+        }
+        self.save_schedule().await;
+        Ok(())
+    }
+
+    /// Post a scheduled send, switching to its account for the duration if it is parked.
+    /// Returns how sending went, as `send_message` reported it.
+    async fn post_scheduled(&mut self, job: &Job) -> Result<String> {
+        let Some(group_id) = &job.group_id else {
+            return Ok("not posted: no group".to_string());
+        };
+        if job.account == self.account {
+            self.send_message(group_id, &job.text).await?;
+        } else if let Some(index) = self.accounts.iter().position(|account| account.name == job.account) {
+            self.swap_account(index);
+            let sent = self.send_message(group_id, &job.text).await;
+            self.swap_account(index);
+            sent?;
+        } else {
+            return Ok("not posted: its account is not open".to_string());
+        }
+        Ok(self.status_text().to_string())
+    }
+
+    pub(crate) async fn save_schedule(&mut self) {
+        if let Err(e) = scheduler::save(&self.scheduler, Path::new(SCHEDULE_PATH)).await {
+            self.set_error(format!("Failed to save reminders: {}", e));
+        }
+    }
+}
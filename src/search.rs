@@ -0,0 +1,165 @@
+use std::collections::{BTreeSet, HashMap};
+
+use mls_client_core::archive::{self, ARCHIVE_DIR};
+use mls_client_core::errors::SearchError;
+use mls_client_core::search::{Query, SearchIndex, SEARCH_INDEX_PATH};
+use mls_client_core::vault;
+use mls_client_core::Message;
+
+use crate::App;
+
+/// Most matches listed, newest first.
+const MAX_RESULTS: usize = 50;
+
+impl App {
+    /// `search <query>`: list the messages matching the words and filters in `parts`.
+    pub(crate) async fn search_command(&mut self, parts: &[&str]) {
+        let query = match Query::parse(parts) {
+            Ok(query) => query,
+            Err(e) => {
+                self.set_error(e.to_string());
+                return;
+            }
+        };
+        if let Err(e) = self.sync_search_index().await {
+            self.set_error(e.to_string());
+            return;
+        }
+        let Some(index) = &self.search_index else {
+            self.set_status("Search is not available in this session");
+            return;
+        };
+        let groups: Vec<(&str, &str)> = self.groups.values().map(|group| (group.id.as_str(), group.name.as_str())).collect();
+        let (total, hits) = match index.search(&query, &groups, MAX_RESULTS) {
+            Ok(found) => found,
+            Err(e) => {
+                self.set_error(e.to_string());
+                return;
+            }
+        };
+
+        // The index holds no text: take it from the loaded timeline, or else from the archived
+        // pages, reading each page once
+        let archive_dir = self.data_path(ARCHIVE_DIR);
+        let mut pages: HashMap<(String, usize), Vec<Message>> = HashMap::new();
+        let mut lines = Vec::new();
+        let mut gone = Vec::new();
+        for hit in &hits {
+            let Some(group) = self.groups.get(&hit.group_id) else {
+                continue;
+            };
+            let mut message = group.messages.iter().find(|message| message.id == hit.message_id).cloned();
+            for page in (0..group.archived_pages).rev() {
+                if message.is_some() {
+                    break;
+                }
+                let key = (hit.group_id.clone(), page);
+                if !pages.contains_key(&key) {
+                    let messages = archive::read_page(&archive_dir, &hit.group_id, page).await.unwrap_or_default();
+                    pages.insert(key.clone(), messages);
+                }
+                message = pages[&key].iter().find(|message| message.id == hit.message_id).cloned();
+            }
+            let Some(message) = message else {
+                gone.push(hit);
+                continue;
+            };
+            let first_line = message.content.lines().next().unwrap_or_default();
+            let mut excerpt: String = first_line.chars().take(100).collect();
+            if excerpt.len() < message.content.trim_end().len() {
+                excerpt.push('…');
+            }
+            lines.push(format!(
+                "{} {} | {}: {}",
+                message.timestamp.format("%Y-%m-%d %H:%M"),
+                group.name,
+                group.display_name(&message.sender),
+                excerpt
+            ));
+        }
+
+        // Indexed, but removed from the timeline since, like a send that was undone
+        if let Some(index) = &mut self.search_index {
+            for hit in &gone {
+                if let Err(e) = index.remove(&hit.group_id, &hit.message_id) {
+                    self.set_error(e.to_string());
+                    return;
+                }
+            }
+        }
+        let total = total - gone.len();
+        let hits: Vec<_> = hits.iter().filter(|hit| !gone.iter().any(|gone| gone.message_id == hit.message_id)).collect();
+        let groups = hits.iter().map(|hit| hit.group_id.as_str()).collect::<BTreeSet<_>>().len();
+        self.set_status(format!("{} match(es) in {} group(s)", total, groups));
+        if hits.is_empty() {
+            return;
+        }
+        let mut popup = vec![self.status_text().to_string(), String::new()];
+        popup.extend(lines);
+        if total > hits.len() {
+            popup.push(format!("… {} older match(es) not shown; narrow the search with before:, from: or in:", total - hits.len()));
+        }
+        self.show_popup(format!("Search: {}", parts.join(" ")), popup);
+    }
+
+    /// Open `search.db`, indexing every page under `archive/` when it is new, and bring it up
+    /// to date with the loaded timelines. A damaged history is never indexed.
+    pub(crate) async fn sync_search_index(&mut self) -> Result<(), SearchError> {
+        if self.recovery.is_some() {
+            return Ok(());
+        }
+        if self.search_index.is_none() {
+            let mut index = self.open_search_index()?;
+            if index.is_empty()? {
+                self.index_archive(&mut index).await?;
+            }
+            self.search_index = Some(index);
+        }
+        if let Some(index) = &mut self.search_index {
+            for group in self.groups.values() {
+                index.update(&group.id, &group.messages)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Index the messages already moved to disk; unreadable pages are skipped with an event.
+    async fn index_archive(&mut self, index: &mut SearchIndex) -> Result<(), SearchError> {
+        let archive_dir = self.data_path(ARCHIVE_DIR);
+        let mut unreadable = Vec::new();
+        for group in self.groups.values() {
+            for page in 0..group.archived_pages {
+                match archive::read_page(&archive_dir, &group.id, page).await {
+                    Ok(messages) => {
+                        index.update(&group.id, &messages)?;
+                    }
+                    Err(e) => unreadable.push(format!("Search skipped older messages of {}: {}", group.name, e)),
+                }
+            }
+        }
+        for event in unreadable {
+            self.record_event(event);
+        }
+        Ok(())
+    }
+
+    /// `search.db`, or an index in memory while the profile is sealed under a passphrase.
+    fn open_search_index(&self) -> Result<SearchIndex, SearchError> {
+        match vault::entered() {
+            Some(_) => SearchIndex::open_in_memory(),
+            None => SearchIndex::open(&self.data_path(SEARCH_INDEX_PATH)),
+        }
+    }
+
+    /// Drop a forgotten group from `search.db`, so nothing about it stays searchable.
+    pub(crate) fn unindex_group(&mut self, group_id: &str) {
+        // Opened just for this when no search ran yet, so the first one still indexes the archive
+        let removed = match &mut self.search_index {
+            Some(index) => index.remove_group(group_id),
+            None => self.open_search_index().and_then(|mut index| index.remove_group(group_id)),
+        };
+        if let Err(e) = removed {
+            self.record_event(format!("Failed to remove a forgotten group from the search index: {}", e));
+        }
+    }
+}
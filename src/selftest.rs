@@ -0,0 +1,59 @@
+use std::path::{Path, PathBuf};
+
+use anyhow::Result;
+use mls_client_core::crypto::CryptoProvider;
+use mls_client_core::interop::{self, Outcome, VECTOR_FILES};
+
+use crate::App;
+
+/// Where `selftest` looks when no directory is given.
+const DEFAULT_VECTOR_DIR: &str = "test-vectors";
+
+/// `--selftest [dir]`: run the test vectors without starting the client.
+pub fn requested() -> Option<PathBuf> {
+    let args: Vec<String> = std::env::args().collect();
+    let position = args.iter().position(|arg| arg == "--selftest")?;
+    let dir = args.get(position + 1).filter(|arg| !arg.starts_with("--")).map_or(DEFAULT_VECTOR_DIR, String::as_str);
+    Some(PathBuf::from(dir))
+}
+
+/// Print every result and fail if any vector or file did, or if nothing passed, for CI.
+pub async fn run(dir: &Path) -> Result<()> {
+    let results = interop::run(dir, &CryptoProvider::new()).await;
+    for result in &results {
+        println!("{}", result);
+    }
+    let (passed, failed, skipped) = interop::tally(&results);
+    println!("{} passed, {} failed, {} skipped", passed, failed, skipped);
+    if failed > 0 {
+        return Err(anyhow::anyhow!("{} test vector(s) failed", failed));
+    }
+    if passed == 0 {
+        return Err(anyhow::anyhow!("No test vector passed"));
+    }
+    Ok(())
+}
+
+impl App {
+    /// `selftest [dir]`: check tree math and the crypto provider against the vectors in `dir` and list the
+    /// results in a popup. Like `--selftest`, it has failed if nothing passed.
+    pub(crate) async fn selftest_command(&mut self, parts: &[&str]) {
+        let dir = PathBuf::from(parts.first().copied().unwrap_or(DEFAULT_VECTOR_DIR));
+        let mut results = interop::run(&dir, &self.crypto).await;
+        let (passed, failed, skipped) = interop::tally(&results);
+        let verdict = if failed == 0 && passed > 0 { "PASSED" } else { "FAILED" };
+        self.set_status(format!("Self-test {}: {} passed, {} failed, {} skipped", verdict, passed, failed, skipped));
+        let mut lines = vec![
+            format!("Vectors from {} ({})", dir.display(), VECTOR_FILES.join(", ")),
+            String::new(),
+        ];
+        // Failures first, so they are not lost below hundreds of passes
+        results.sort_by_key(|result| match result.outcome {
+            Outcome::Fail(_) => 0,
+            Outcome::Skipped(_) => 1,
+            Outcome::Pass => 2,
+        });
+        lines.extend(results.iter().map(ToString::to_string));
+        self.show_popup("Self-test", lines);
+    }
+}
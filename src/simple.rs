@@ -0,0 +1,300 @@
+use anyhow::Result;
+use crossterm::event::KeyCode;
+use std::collections::HashSet;
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncBufReadExt, BufReader};
+
+use crate::{App, AppScreen, InputMode};
+use mls_client_core::command::{self, CommandLine};
+use mls_client_core::filters::FilterAction;
+use mls_client_core::platform;
+
+/// Messages replayed when a group becomes active; older ones are only in the history.
+const REPLAY_MESSAGES: usize = 20;
+
+/// Use the line-based interface for `--simple` or terminals that cannot drive the TUI.
+pub fn requested() -> bool {
+    std::env::args().any(|arg| arg == "--simple")
+        || std::env::var("TERM").map(|term| term == "dumb").unwrap_or(false)
+}
+
+/// What has already been written to the terminal.
+#[derive(Default)]
+struct Output {
+    printed: HashSet<String>,
+    /// Messages whose translation was printed; it usually arrives after the message
+    translated: HashSet<String>,
+    /// Activity entries already printed, as counted by `ActivityLog::appended`
+    activity_seen: u64,
+    group: Option<String>,
+    locked: bool,
+    composing: bool,
+    confirming: bool,
+    recovering: bool,
+}
+
+/// Plain scrolling interface: `/command` runs a command, any other line is sent to the active group.
+pub async fn run(app: &mut App) -> Result<()> {
+    println!("MLS Enhanced Client (simple mode)");
+    println!("Type /help for commands and /switch <group> to change groups; other lines are sent to the active group.");
+
+    let mut lines = BufReader::new(tokio::io::stdin()).lines();
+    let mut sync = tokio::time::interval(Duration::from_secs(2));
+    let mut output = Output::default();
+
+    while !app.should_quit {
+        app.update_terminal_title();
+        flush(app, &mut output);
+        tokio::select! {
+            line = lines.next_line() => {
+                let Some(line) = line? else {
+                    break;
+                };
+                app.last_activity = Instant::now();
+                handle_line(app, line.trim()).await?;
+            }
+            _ = sync.tick() => {
+                app.check_auto_lock();
+                app.poll_progress();
+                app.run_scheduled().await?;
+                app.poll_events().await?;
+                app.flush_delayed_sends().await;
+                app.enforce_memory_budget().await;
+                app.backup_if_due().await;
+                app.sync_messages().await?;
+                app.run_parked_accounts(true).await?;
+            }
+        }
+    }
+    app.send_delayed_now().await;
+    app.restore_terminal_title();
+    Ok(())
+}
+
+async fn handle_line(app: &mut App, line: &str) -> Result<()> {
+    if matches!(app.screen, AppScreen::Locked) {
+        for c in line.chars() {
+            app.handle_lock_input(KeyCode::Char(c)).await?;
+        }
+        return app.handle_lock_input(KeyCode::Enter).await;
+    }
+
+    // Anything but a clear yes declines a confirmation
+    if matches!(app.screen, AppScreen::Confirm) {
+        let key = match line.trim().to_ascii_lowercase().as_str() {
+            "y" | "yes" => KeyCode::Char('y'),
+            "a" | "always" => KeyCode::Char('a'),
+            _ => KeyCode::Esc,
+        };
+        return app.handle_confirm_input(key).await;
+    }
+
+    if matches!(app.screen, AppScreen::Recovery) {
+        let key = line.trim().chars().next().map_or(KeyCode::Null, |c| KeyCode::Char(c.to_ascii_lowercase()));
+        return app.handle_recovery_input(key).await;
+    }
+
+    // Prompts opened by a command: wipe confirmation, a new passphrase, or a template/reply in
+    // the composer, which an empty line sends unchanged
+    match app.input_mode {
+        InputMode::Wipe | InputMode::Passphrase => {
+            app.input = line.to_string();
+            return app.handle_input(KeyCode::Enter).await;
+        }
+        InputMode::Message => {
+            if !line.is_empty() {
+                app.input = line.to_string();
+            }
+            return app.handle_input(KeyCode::Enter).await;
+        }
+        _ => {}
+    }
+
+    match line.strip_prefix('/') {
+        Some("help") => {
+            println!("Commands (prefix with /):");
+            for line in command::HELP {
+                println!("{}", line);
+            }
+            println!("  switch <group>: Make a group (name or ID) active");
+        }
+        Some(command) if command.starts_with("switch") => {
+            // Quoted or not: `switch My Group` and `switch "My Group"` both work
+            let wanted = match CommandLine::parse(command) {
+                Ok(line) => line.words().get(1..).unwrap_or_default().join(" "),
+                Err(_) => command.trim_start_matches("switch").trim().to_string(),
+            };
+            match app.find_group_id(&wanted) {
+                Some(group_id) => app.select_group(&group_id),
+                None => println!("No group named {}", wanted),
+            }
+        }
+        Some(command) => app.execute_command(command).await?,
+        None if line.is_empty() => {}
+        None => match app.active_group.clone() {
+            Some(group_id) => app.send_message(&group_id, line).await?,
+            None => println!("No active group selected: /create or /join one first"),
+        },
+    }
+    Ok(())
+}
+
+/// Print whatever changed since the last call and fold TUI-only screens back to plain output.
+fn flush(app: &mut App, output: &mut Output) {
+    let locked = matches!(app.screen, AppScreen::Locked);
+    if locked && !output.locked {
+        println!("Session locked. Enter your passphrase (it will be echoed):");
+    }
+    output.locked = locked;
+    if locked {
+        return;
+    }
+    if let (AppScreen::Confirm, Some(confirmation)) = (&app.screen, &app.confirmation) {
+        if !output.confirming {
+            println!("-- {} --", confirmation.prompt);
+            for line in &confirmation.details {
+                println!("{}", line);
+            }
+            println!("Answer y(es), n(o), or a(lways: yes, and don't ask again before {})", confirmation.action.kind().as_str());
+        }
+        output.confirming = true;
+        return;
+    }
+    output.confirming = false;
+    if let (AppScreen::Recovery, Some(report)) = (&app.screen, &app.recovery) {
+        if !output.recovering {
+            println!("-- Stored history is damaged --");
+            for problem in &report.problems {
+                println!("- {}", problem);
+            }
+            for (key, option) in app.recovery_options() {
+                println!("{}: {}", key, option);
+            }
+        }
+        output.recovering = true;
+        // e.g. that no backup passes, while the choices stay open
+        print_activity(app, output);
+        return;
+    }
+    output.recovering = false;
+
+    match app.screen {
+        AppScreen::Popup => {
+            if let Some(popup) = app.popup.take() {
+                println!("-- {} --", popup.title);
+                for line in popup.lines {
+                    println!("{}", line);
+                }
+            }
+        }
+        AppScreen::Roster => {
+            if let Some(group) = app.active_group.as_ref().and_then(|id| app.groups.get(id)) {
+                println!("-- Members of {} --", group.name);
+                for member in &group.members {
+                    println!("{}", group.display_name(member));
+                }
+            }
+        }
+        AppScreen::Cleanup => {
+            if let Some(cleanup) = app.cleanup.take() {
+                println!("-- Groups quiet for more than {} days --", cleanup.days);
+                for group in &cleanup.groups {
+                    println!("{}", group.describe());
+                }
+                println!("Use /cleanup archive <group>, /leave <group> or /forget <group>.");
+            }
+        }
+        AppScreen::Dashboard => {
+            println!("-- Recent events --");
+            for event in &app.system_events {
+                println!("[{}] {}", event.timestamp.format("%H:%M:%S"), event.text);
+            }
+        }
+        AppScreen::Settings => {
+            println!("Settings are not available in simple mode; edit config.json instead.");
+            app.input_mode = InputMode::Normal;
+        }
+        AppScreen::Help => {
+            println!("Type /help for commands.");
+        }
+        AppScreen::Activity => {
+            println!("-- Activity --");
+            let entries: Vec<_> = app.activity.matching(app.activity_view.severity, &app.activity_view.filter).collect();
+            for entry in entries.into_iter().rev() {
+                println!("[{}] {} {}", entry.timestamp.format("%H:%M:%S"), entry.severity.marker(), printable(app, &entry.describe()));
+            }
+        }
+        AppScreen::Main | AppScreen::Locked | AppScreen::Confirm | AppScreen::Switcher | AppScreen::Recovery => {}
+    }
+    app.screen = AppScreen::Main;
+
+    print_activity(app, output);
+    let composing = matches!(app.input_mode, InputMode::Message);
+    if composing && !output.composing {
+        println!("Composer: {}", app.input);
+        println!("(press Enter to send as-is, or type a replacement line)");
+    }
+    output.composing = composing;
+
+    let Some(group) = app.active_group.as_ref().and_then(|id| app.groups.get(id)) else {
+        return;
+    };
+    if output.group.as_deref() != Some(group.id.as_str()) {
+        let icon = group.tag.as_ref().and_then(|tag| tag.icon.as_deref()).map(|icon| format!("{} ", icon)).unwrap_or_default();
+        println!("== {}{} ==", printable(app, &icon), group.name);
+        if let Some(onboarding) = group.onboarding.as_ref().filter(|_| group.onboarding_pinned) {
+            println!("-- Onboarding from {} (/onboarding dismiss to unpin) --", onboarding.set_by);
+            println!("{}", onboarding.text);
+        }
+        let skip = group.messages.len().saturating_sub(REPLAY_MESSAGES);
+        for message in group.messages.iter().take(skip) {
+            output.printed.insert(message.id.clone());
+            output.translated.insert(message.id.clone());
+        }
+        output.group = Some(group.id.clone());
+    }
+    for message in &group.messages {
+        let hidden = app.filters.action_for(&group.id, &message.content) == Some(FilterAction::Hide) && message.redacted_by.is_none();
+        if output.printed.insert(message.id.clone()) && !hidden {
+            let shared = message.shared_by.as_ref()
+                .map(|shared_by| format!(" (shared by {})", group.display_name(shared_by)))
+                .unwrap_or_default();
+            let bridged = if app.is_bridged_sender(&message.sender) { " [bridged]" } else { "" };
+            let unsupported = message.unsupported.as_ref()
+                .map(|content_type| format!("[{} message this version cannot show] ", content_type))
+                .unwrap_or_default();
+            println!(
+                "[{}] {}{} {}{}: {}{}",
+                message.shown_time().format("%H:%M:%S"),
+                printable(app, message.protection.marker()),
+                shared,
+                group.display_name(&message.sender),
+                bridged,
+                unsupported,
+                message.content
+            );
+        }
+        if let Some(translation) = message.translation.as_ref().filter(|_| !hidden) {
+            if output.translated.insert(message.id.clone()) {
+                println!("  {} {}: {}", printable(app, "⇄"), group.display_name(&message.sender), translation);
+            }
+        }
+    }
+}
+
+/// Interface text with its symbols swapped for ASCII when the console cannot show them.
+/// Print the status lines reported since the last call, marked by severity.
+fn print_activity(app: &App, output: &mut Output) {
+    for entry in app.activity.since(output.activity_seen) {
+        println!("{} {}", entry.severity.marker(), printable(app, &entry.text));
+    }
+    output.activity_seen = app.activity.appended();
+}
+
+fn printable(app: &App, text: &str) -> String {
+    if app.config.glyphs.unicode() {
+        text.to_string()
+    } else {
+        platform::ascii_text(text)
+    }
+}
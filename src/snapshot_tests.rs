@@ -0,0 +1,167 @@
+//! Each screen rendered at several terminal sizes and compared with the snapshots in
+//! `src/snapshots/`. After an intended layout change, `cargo insta review` accepts the new
+//! renders.
+
+use std::collections::{BTreeMap, HashMap};
+
+use chrono::{Local, TimeZone};
+use mls_client_core::bidi::TextDirection;
+use mls_client_core::config::Config;
+use mls_client_core::crypto::CryptoProvider;
+use mls_client_core::history::SeenIds;
+use mls_client_core::mls_client::MlsClient;
+use mls_client_core::network::NetworkClient;
+use mls_client_core::{Group, GroupMode, Message, Protection, WirePolicy};
+use ratatui::{backend::TestBackend, Terminal};
+
+use crate::confirm::{Confirmation, ConfirmedAction};
+use crate::{App, AppScreen, MIN_TERMINAL_HEIGHT, MIN_TERMINAL_WIDTH};
+
+const GROUP_ID: &str = "general";
+
+/// The smallest terminal the layout supports, a common default and a large one.
+const SIZES: [(u16, u16); 3] = [(MIN_TERMINAL_WIDTH, MIN_TERMINAL_HEIGHT), (120, 40), (200, 60)];
+
+fn message(id: &str, sender: &str, minute: u32, content: &str) -> Message {
+    let time = Local.with_ymd_and_hms(2026, 3, 14, 9, minute, 0).unwrap();
+    Message {
+        id: id.to_string(),
+        sender: sender.to_string(),
+        content: content.to_string(),
+        timestamp: time,
+        group_id: GROUP_ID.to_string(),
+        server_seq: None,
+        received_at: Some(time),
+        clock_skewed: false,
+        pinned: false,
+        reactions: BTreeMap::new(),
+        shared_by: None,
+        redacted_by: None,
+        translation: None,
+        pending: false,
+        protection: if sender == "user" { Protection::Sent } else { Protection::Unrecorded },
+        attachment: false,
+        unsupported: None,
+    }
+}
+
+/// A disconnected app with one group holding a short conversation, so the renders depend
+/// on nothing but the code.
+async fn app() -> App {
+    let config = Config::default();
+    let mls_client = MlsClient::new(&config.username, &CryptoProvider::new()).await.unwrap();
+    // No addresses, so nothing is dialed
+    let network_client = NetworkClient::new(&[], &config.username).await.unwrap();
+    let mut app = App::with_clients(config, mls_client, network_client);
+    let messages = vec![
+        message("m1", "alice", 0, "Morning! Has everyone seen the new release notes?"),
+        message("m2", "user", 2, "Yes, the reassembly fixes look good. I'll try them on the flaky link this afternoon."),
+        message("m3", "bob", 5, "Same here, and a long message to check that wrapping keeps the layout intact at every width the tests render it at."),
+    ];
+    app.groups.insert(
+        GROUP_ID.to_string(),
+        Group {
+            id: GROUP_ID.to_string(),
+            name: "general".to_string(),
+            members: vec!["user".to_string(), "alice".to_string(), "bob".to_string()],
+            messages,
+            is_active: true,
+            admins: vec!["user".to_string()],
+            mode: GroupMode::Chat,
+            unread: 0,
+            unread_mentions: 0,
+            rotation_policy: None,
+            epoch_age: None,
+            onboarding: None,
+            onboarding_pinned: false,
+            archived_pages: 0,
+            seen_ids: SeenIds::default(),
+            nicknames: HashMap::new(),
+            tag: None,
+            joined_at: HashMap::new(),
+            wire_policy: WirePolicy::default(),
+            removed: false,
+            deleted: None,
+            last_active: HashMap::new(),
+            quarantine: Vec::new(),
+            redactions: Vec::new(),
+            membership_log: Vec::new(),
+            audit_anchor: None,
+            text_direction: TextDirection::default(),
+        },
+    );
+    app.active_group = Some(GROUP_ID.to_string());
+    app.sync_group_selection(GROUP_ID);
+    app
+}
+
+fn render(app: &mut App, (width, height): (u16, u16)) -> String {
+    let mut terminal = Terminal::new(TestBackend::new(width, height)).unwrap();
+    terminal.draw(|f| app.render(f)).unwrap();
+    terminal.backend().to_string()
+}
+
+/// Snapshot `screen` at every size, named `<name>_<width>x<height>`.
+async fn assert_screen(name: &str, screen: AppScreen, prepare: impl Fn(&mut App)) {
+    for size in SIZES {
+        let mut app = app().await;
+        prepare(&mut app);
+        app.screen = screen.clone();
+        insta::assert_snapshot!(format!("{}_{}x{}", name, size.0, size.1), render(&mut app, size));
+    }
+}
+
+#[tokio::test]
+async fn dashboard() {
+    assert_screen("dashboard", AppScreen::Dashboard, |_| {}).await;
+}
+
+#[tokio::test]
+async fn main_screen() {
+    assert_screen("main", AppScreen::Main, |_| {}).await;
+}
+
+#[tokio::test]
+async fn settings() {
+    assert_screen("settings", AppScreen::Settings, |_| {}).await;
+}
+
+#[tokio::test]
+async fn help() {
+    assert_screen("help", AppScreen::Help, |_| {}).await;
+}
+
+#[tokio::test]
+async fn popup() {
+    assert_screen("popup", AppScreen::Popup, |app| {
+        app.show_popup(
+            "Member: alice",
+            vec![
+                "Identity: alice".to_string(),
+                "Joined: 2026-03-14 09:00".to_string(),
+                String::new(),
+                "Not verified; compare fingerprints out of band before trusting this member.".to_string(),
+            ],
+        );
+    })
+    .await;
+}
+
+#[tokio::test]
+async fn confirmation() {
+    assert_screen("confirm", AppScreen::Confirm, |app| {
+        app.confirmation = Some(Confirmation {
+            action: ConfirmedAction::Leave { group_id: GROUP_ID.to_string() },
+            prompt: "Leave general?".to_string(),
+            details: vec!["You will stop receiving its messages.".to_string()],
+        });
+    })
+    .await;
+}
+
+#[tokio::test]
+async fn too_small() {
+    let mut app = app().await;
+    app.screen = AppScreen::Main;
+    insta::assert_snapshot!(render(&mut app, (MIN_TERMINAL_WIDTH - 1, MIN_TERMINAL_HEIGHT - 1)));
+}
@@ -0,0 +1,296 @@
+use std::collections::{BTreeMap, HashSet};
+use std::time::{Duration, Instant};
+
+use chrono::Local;
+use mls_client_core::activity::Severity;
+use mls_client_core::errors;
+use mls_client_core::netstats::format_bytes;
+use mls_client_core::tasks::TaskId;
+use mls_client_core::{Message, Protection};
+use uuid::Uuid;
+
+use crate::App;
+
+/// Messages sent per tick at most, so input stays responsive without a rate.
+const BURST: usize = 50;
+
+/// Words the synthetic messages are drawn from.
+const WORDS: [&str; 32] = [
+    "the", "deploy", "is", "done", "can", "you", "review", "my", "branch", "before", "lunch", "tomorrow", "meeting", "moved", "to",
+    "three", "thanks", "looks", "good", "but", "tests", "fail", "on", "windows", "release", "notes", "ready", "ship", "it", "coffee",
+    "anyone", "later",
+];
+
+/// A `stress` run: synthetic messages go through the same encryption, delivery and history
+/// path as typed ones, with timings of each stage kept for the report.
+pub struct StressRun {
+    group_id: String,
+    group_name: String,
+    task: TaskId,
+    count: usize,
+    /// Messages per second; `None` for as fast as the pipeline allows
+    rate: Option<f64>,
+    started: Instant,
+    /// Set once the last message went out or the run was stopped
+    finished: Option<Instant>,
+    /// Why the run ended early
+    stopped: Option<String>,
+    ids: HashSet<String>,
+    payload_bytes: usize,
+    transmit_time: Duration,
+    saves: usize,
+    save_time: Duration,
+    frames: usize,
+    render_time: Duration,
+    seed: u64,
+}
+
+impl StressRun {
+    /// Messages due by now at the requested rate, at most a burst.
+    fn due(&self) -> usize {
+        let left = self.count - self.ids.len();
+        let due = match self.rate {
+            Some(rate) => ((self.started.elapsed().as_secs_f64() * rate) as usize).min(self.count).saturating_sub(self.ids.len()),
+            None => left,
+        };
+        due.min(left).min(BURST)
+    }
+
+    /// Synthetic text for message `n`: mostly short chat lines, with the occasional link,
+    /// emoji or long multi-line message, tagged so it can be found with `search stress`.
+    fn next_message(&mut self, n: usize) -> String {
+        let mut roll = || {
+            // xorshift64: no need for anything stronger
+            self.seed ^= self.seed << 13;
+            self.seed ^= self.seed >> 7;
+            self.seed ^= self.seed << 17;
+            self.seed
+        };
+        let long = roll() % 10 == 0;
+        let words = if long { 60 + roll() % 140 } else { 3 + roll() % 22 };
+        let mut text = format!("[stress {}/{}]", n, self.count);
+        for i in 0..words {
+            text.push(if long && i > 0 && i % 25 == 0 { '\n' } else { ' ' });
+            text.push_str(WORDS[(roll() % WORDS.len() as u64) as usize]);
+        }
+        if roll() % 8 == 0 {
+            text.push_str(&format!(" https://example.com/stress/{}", n));
+        }
+        if roll() % 12 == 0 {
+            text.push_str(" 🚀");
+        }
+        text
+    }
+
+    fn report(&self, group: Option<&[Message]>) -> Vec<String> {
+        let sent = self.ids.len();
+        let elapsed = self.finished.unwrap_or_else(Instant::now).duration_since(self.started);
+        let per_message = |total: Duration, n: usize| format!("{:.2} ms", total.as_secs_f64() * 1000.0 / n.max(1) as f64);
+        let echoed = group.map_or(0, |messages| messages.iter().filter(|message| self.ids.contains(&message.id) && !message.pending).count());
+        let mut lines = vec![
+            format!("Group: {}", self.group_name),
+            format!("Sent: {} of {} in {:.1}s", sent, self.count, elapsed.as_secs_f64()),
+            format!(
+                "Rate: {:.1} messages/s (asked for {})",
+                sent as f64 / elapsed.as_secs_f64().max(0.001),
+                self.rate.map_or_else(|| "as fast as possible".to_string(), |rate| format!("{}/s", rate))
+            ),
+            format!("Plaintext: {} ({} per message)", format_bytes(self.payload_bytes as u64), format_bytes((self.payload_bytes / sent.max(1)) as u64)),
+            format!("Encrypt and queue: {} per message", per_message(self.transmit_time, sent)),
+            format!("Saving the history: {} per save over {} saves", per_message(self.save_time, self.saves), self.saves),
+            format!("Rendering: {} per frame over {} frames", per_message(self.render_time, self.frames), self.frames),
+            format!("Echoed by the delivery service so far: {} of {}", echoed, sent),
+        ];
+        if let Some(reason) = &self.stopped {
+            lines.push(format!("Stopped early: {}", reason));
+        }
+        lines
+    }
+}
+
+impl App {
+    /// `stress <group> <count> [rate]` starts a run, `stress stop` ends it and `stress` shows
+    /// the current or last run. Only in debug mode: the messages are real and every member
+    /// receives them.
+    pub(crate) fn stress_command(&mut self, parts: &[&str]) {
+        const USAGE: &str = "Usage: stress <group> <count> [messages per second] | stress stop";
+        match parts {
+            [] => self.show_stress_report(),
+            ["stop"] => self.stop_stress("stopped by you"),
+            [group, count, rate @ ..] if rate.len() <= 1 => {
+                let (Ok(count), Ok(rate)) = (count.parse::<usize>(), rate.first().map(|rate| rate.parse::<f64>()).transpose()) else {
+                    self.set_warning(USAGE.to_string());
+                    return;
+                };
+                if count == 0 || rate.is_some_and(|rate| !rate.is_finite() || rate <= 0.0) {
+                    self.set_warning(USAGE.to_string());
+                    return;
+                }
+                self.start_stress(group, count, rate);
+            }
+            _ => self.set_warning(USAGE.to_string()),
+        }
+    }
+
+    fn start_stress(&mut self, wanted: &str, count: usize, rate: Option<f64>) {
+        if !self.config.debug_mode {
+            self.set_status("stress is a developer tool: turn on debug mode with 'debug on' first");
+            return;
+        }
+        if self.previews_encryption() {
+            self.set_status("Turn off the encryption preview with 'debug preview off' first; it would hold every message");
+            return;
+        }
+        if self.stress.as_ref().is_some_and(|run| run.finished.is_none()) {
+            self.set_status("A stress run is in progress; 'stress stop' ends it");
+            return;
+        }
+        let Some(group) = self.find_group_id(wanted).and_then(|id| self.groups.get(&id)) else {
+            self.set_warning(format!("No group named {}", wanted));
+            return;
+        };
+        let (group_id, group_name) = (group.id.clone(), group.name.clone());
+        if !self.network_client.is_connected() || self.mls_client.get_group(&group_id).is_none() {
+            self.report_error(&errors::NOT_CONNECTED, format!("cannot send to {}", group_name));
+            return;
+        }
+        if let Some(denied) = self.posting_denied(&group_id) {
+            self.set_warning(denied);
+            return;
+        }
+        let task = self.tasks.start(format!("Stress test in {}", group_name), count);
+        let seed = Local::now().timestamp_nanos_opt().unwrap_or(1) as u64 | 1;
+        self.set_status(format!("Sending {} synthetic messages to {}; 'stress stop' ends the run", count, group_name));
+        let event = self.status_text().to_string();
+        self.record_event(event);
+        self.stress = Some(StressRun {
+            group_id,
+            group_name,
+            task,
+            count,
+            rate,
+            started: Instant::now(),
+            finished: None,
+            stopped: None,
+            ids: HashSet::new(),
+            payload_bytes: 0,
+            transmit_time: Duration::ZERO,
+            saves: 0,
+            save_time: Duration::ZERO,
+            frames: 0,
+            render_time: Duration::ZERO,
+            seed,
+        });
+    }
+
+    /// Send the messages due this tick, then save the history once for all of them.
+    pub(crate) async fn run_stress(&mut self) {
+        let Some(run) = self.stress.as_ref().filter(|run| run.finished.is_none()) else {
+            return;
+        };
+        let group_id = run.group_id.clone();
+        let due = run.due();
+        if !self.groups.contains_key(&group_id) {
+            self.stop_stress("the group is gone");
+            return;
+        }
+        if let Some(denied) = self.posting_denied(&group_id) {
+            self.stop_stress(&denied);
+            return;
+        }
+        if due == 0 {
+            return;
+        }
+        for _ in 0..due {
+            let Some(run) = &mut self.stress else {
+                return;
+            };
+            let message_id = Uuid::new_v4().to_string();
+            let content = run.next_message(run.ids.len() + 1);
+            let started = Instant::now();
+            let echoed = match self.transmit_application(&group_id, &message_id, &content).await {
+                Ok(echoed) => echoed,
+                Err(e) => {
+                    self.report_error(&errors::SEND_FAILED, e.to_string());
+                    self.stop_stress(&e.to_string());
+                    break;
+                }
+            };
+            let Some(run) = &mut self.stress else {
+                return;
+            };
+            run.transmit_time += started.elapsed();
+            run.payload_bytes += content.len();
+            run.ids.insert(message_id.clone());
+            let task = run.task;
+            if let Some(group) = self.groups.get_mut(&group_id) {
+                group.seen_ids.insert(message_id.clone());
+                group.insert_message(Message {
+                    id: message_id,
+                    sender: self.config.username.clone(),
+                    content,
+                    timestamp: Local::now(),
+                    group_id: group_id.clone(),
+                    server_seq: None,
+                    received_at: None,
+                    clock_skewed: false,
+                    pinned: false,
+                    reactions: BTreeMap::new(),
+                    shared_by: None,
+                    redacted_by: None,
+                    translation: None,
+                    pending: echoed,
+                    protection: Protection::Sent,
+                    attachment: false,
+                    unsupported: None,
+                });
+            }
+            if let Some(done) = self.tasks.advance(task) {
+                self.record_event(done);
+            }
+        }
+
+        let started = Instant::now();
+        self.save_history().await;
+        let Some(run) = &mut self.stress else {
+            return;
+        };
+        run.saves += 1;
+        run.save_time += started.elapsed();
+        if run.finished.is_none() && run.ids.len() == run.count {
+            run.finished = Some(Instant::now());
+            self.activity.push(Severity::Info, format!("Stress test in {} done; 'stress' shows the report", run.group_name));
+            self.show_stress_report();
+        }
+    }
+
+    /// Count a frame drawn while a run is going, for the report.
+    pub(crate) fn record_stress_frame(&mut self, took: Duration) {
+        if let Some(run) = self.stress.as_mut().filter(|run| run.finished.is_none()) {
+            run.frames += 1;
+            run.render_time += took;
+        }
+    }
+
+    fn stop_stress(&mut self, reason: &str) {
+        let Some(run) = self.stress.as_mut().filter(|run| run.finished.is_none()) else {
+            self.set_status("No stress run in progress");
+            return;
+        };
+        run.finished = Some(Instant::now());
+        run.stopped = Some(reason.to_string());
+        let task = run.task;
+        self.tasks.cancel(task);
+        self.show_stress_report();
+    }
+
+    fn show_stress_report(&mut self) {
+        let Some(run) = &self.stress else {
+            self.set_status("No stress run yet: stress <group> <count> [messages per second]");
+            return;
+        };
+        let lines = run.report(self.groups.get(&run.group_id).map(|group| group.messages.as_slice()));
+        let title = if run.finished.is_some() { "Stress test" } else { "Stress test (running)" };
+        self.show_popup(title, lines);
+    }
+}
@@ -0,0 +1,270 @@
+use ratatui::widgets::ListState;
+use std::collections::VecDeque;
+
+/// Targets remembered for recent-first ordering.
+const MAX_RECENT: usize = 50;
+/// Matches found only in an entry's description rank below matches in its label.
+const DETAIL_PENALTY: i64 = 10;
+
+/// Where a quick switcher entry leads.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SwitchTarget {
+    Group(String),
+    /// A command form by its syntax, run once its arguments have been asked for
+    Command(String),
+}
+
+#[derive(Debug, Clone)]
+pub struct SwitchEntry {
+    pub target: SwitchTarget,
+    pub label: String,
+    /// Shown dimmed after the label and searched as well, e.g. a command's description
+    pub detail: String,
+}
+
+/// State of the Ctrl+K palette: the query, the highlighted entry and what was used recently.
+#[derive(Debug, Default)]
+pub struct Switcher {
+    pub query: String,
+    pub state: ListState,
+    recent: VecDeque<SwitchTarget>,
+}
+
+impl Switcher {
+    pub fn reset(&mut self) {
+        self.query.clear();
+        self.state.select(Some(0));
+    }
+
+    /// Record `target` as the most recently used.
+    pub fn touch(&mut self, target: SwitchTarget) {
+        self.recent.retain(|recent| *recent != target);
+        self.recent.push_front(target);
+        self.recent.truncate(MAX_RECENT);
+    }
+
+    /// How many other targets were used since `target`, or `None` if it was not used lately.
+    pub fn recency(&self, target: &SwitchTarget) -> Option<usize> {
+        self.recent.iter().position(|recent| recent == target)
+    }
+
+    /// Entries matching the query, best first; recently used entries win ties.
+    pub fn filter(&self, entries: Vec<SwitchEntry>) -> Vec<SwitchEntry> {
+        let mut scored: Vec<(i64, usize, SwitchEntry)> = entries
+            .into_iter()
+            .filter_map(|entry| {
+                let score = fuzzy_score(&self.query, &entry.label)
+                    .or_else(|| Some(fuzzy_score(&self.query, &entry.detail)? - DETAIL_PENALTY))?;
+                let recency = self.recency(&entry.target).unwrap_or(usize::MAX);
+                Some((score, recency, entry))
+            })
+            .collect();
+        scored.sort_by(|a, b| b.0.cmp(&a.0).then(a.1.cmp(&b.1)).then_with(|| a.2.label.cmp(&b.2.label)));
+        scored.into_iter().map(|(_, _, entry)| entry).collect()
+    }
+}
+
+/// One way of running a command, read from its help line, e.g. `kp show [identity]`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CommandForm {
+    /// The words before any argument, e.g. `kp show`
+    pub name: String,
+    pub syntax: String,
+    pub description: String,
+    pub parts: Vec<FormPart>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FormPart {
+    /// A word typed as is after an argument, e.g. `clear` in `tag <group> clear`
+    Literal(String),
+    /// `<name>` or a choice such as `on|off`; quoted when it needs to be
+    Required(String),
+    /// `[...]`, inserted as typed and skipped when left empty
+    Optional(String),
+}
+
+impl FormPart {
+    pub fn prompt(&self) -> Option<String> {
+        match self {
+            FormPart::Literal(_) => None,
+            FormPart::Required(label) => Some(label.clone()),
+            FormPart::Optional(label) => Some(format!("{} (optional)", label)),
+        }
+    }
+}
+
+/// The arguments asked for so far while running a command from the switcher.
+#[derive(Debug, Clone)]
+pub struct CommandPrompt {
+    pub form: CommandForm,
+    pub answers: Vec<String>,
+}
+
+impl CommandPrompt {
+    pub fn new(form: CommandForm) -> Self {
+        Self { form, answers: Vec::new() }
+    }
+
+    /// The part to ask for next, if any is left.
+    pub fn next(&self) -> Option<&FormPart> {
+        self.form.parts.iter().filter(|part| part.prompt().is_some()).nth(self.answers.len())
+    }
+
+    /// The command line the answers make, quoted for the command tokenizer.
+    pub fn command_line(&self) -> String {
+        // `send` takes the rest of its line as typed, so its text is never quoted
+        let raw = self.form.name == "send";
+        let mut words = vec![self.form.name.clone()];
+        let mut answers = self.answers.iter();
+        for part in &self.form.parts {
+            match part {
+                FormPart::Literal(word) => words.push(word.clone()),
+                FormPart::Required(_) => match answers.next() {
+                    Some(answer) if raw => words.push(answer.clone()),
+                    Some(answer) => words.push(quote(answer)),
+                    None => break,
+                },
+                FormPart::Optional(_) => match answers.next() {
+                    Some(answer) if !answer.is_empty() => words.push(answer.clone()),
+                    Some(_) => {}
+                    None => break,
+                },
+            }
+        }
+        words.join(" ")
+    }
+}
+
+/// Every form of every command in `help`, whose lines read `  <syntax>: <description>`.
+/// Alternatives naming the command again (`tag <group> clear`) or another one after ` / `
+/// become forms of their own.
+pub fn command_forms(help: &[&str]) -> Vec<CommandForm> {
+    let mut forms = Vec::new();
+    for line in help {
+        let Some((syntax, description)) = line.trim().split_once(": ") else {
+            continue;
+        };
+        let command = syntax.split_whitespace().next().unwrap_or_default();
+        for syntax in split_forms(syntax, command) {
+            forms.push(parse_form(syntax.trim(), description));
+        }
+    }
+    forms
+}
+
+fn split_forms<'a>(syntax: &'a str, command: &str) -> Vec<&'a str> {
+    let mut forms = Vec::new();
+    let mut depth = 0usize;
+    let mut start = 0;
+    for (index, c) in syntax.char_indices() {
+        match c {
+            '<' | '[' | '(' => depth += 1,
+            '>' | ']' | ')' => depth = depth.saturating_sub(1),
+            _ => {}
+        }
+        if depth > 0 || index < start {
+            continue;
+        }
+        for separator in [", ", " / ", " | "] {
+            let Some(next) = syntax[index..].strip_prefix(separator) else {
+                continue;
+            };
+            if separator == " / " || next.split_whitespace().next() == Some(command) {
+                forms.push(&syntax[start..index]);
+                start = index + separator.len();
+            }
+        }
+    }
+    forms.push(&syntax[start..]);
+    forms
+}
+
+fn parse_form(syntax: &str, description: &str) -> CommandForm {
+    let mut name = Vec::new();
+    let mut parts = Vec::new();
+    for token in syntax_tokens(syntax) {
+        match token {
+            // Another way of giving the same arguments, left to command mode
+            "|" => break,
+            _ if token.starts_with('(') => {}
+            _ if token.starts_with('[') => {
+                let close = token.rfind(']').unwrap_or(token.len());
+                let after = token.get(close + 1..).unwrap_or_default();
+                parts.push(FormPart::Optional(format!("{}{}", &token[1..close], after)));
+            }
+            _ if token.starts_with('<') || token.contains('|') => {
+                parts.push(FormPart::Required(token.replace(['<', '>'], "")));
+            }
+            _ if parts.is_empty() => name.push(token),
+            _ => parts.push(FormPart::Literal(token.to_string())),
+        }
+    }
+    CommandForm {
+        name: name.join(" "),
+        syntax: syntax.to_string(),
+        description: description.to_string(),
+        parts,
+    }
+}
+
+/// Words of a syntax line, keeping `<...>`, `[...]` and `(...)` groups whole.
+fn syntax_tokens(syntax: &str) -> Vec<&str> {
+    let mut tokens = Vec::new();
+    let mut depth = 0usize;
+    let mut start = None;
+    for (index, c) in syntax.char_indices() {
+        match c {
+            '<' | '[' | '(' => depth += 1,
+            '>' | ']' | ')' => depth = depth.saturating_sub(1),
+            c if c.is_whitespace() && depth == 0 => {
+                if let Some(start) = start.take() {
+                    tokens.push(&syntax[start..index]);
+                }
+                continue;
+            }
+            _ => {}
+        }
+        start.get_or_insert(index);
+    }
+    if let Some(start) = start {
+        tokens.push(&syntax[start..]);
+    }
+    tokens
+}
+
+/// `word` as the command tokenizer reads it back: quoted if it holds whitespace, quotes or
+/// backslashes, is empty, or would be taken for an `--option`.
+fn quote(word: &str) -> String {
+    let plain = !word.is_empty()
+        && !word.starts_with("--")
+        && !word.chars().any(|c| c.is_whitespace() || matches!(c, '"' | '\'' | '\\'));
+    if plain {
+        return word.to_string();
+    }
+    format!("\"{}\"", word.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+/// Case-insensitive subsequence match of `query` in `candidate`. Consecutive characters and
+/// matches at word starts score higher; `None` if some query character is missing.
+pub fn fuzzy_score(query: &str, candidate: &str) -> Option<i64> {
+    let candidate: Vec<char> = candidate.chars().flat_map(char::to_lowercase).collect();
+    let mut score = 0i64;
+    let mut position = 0usize;
+    let mut previous: Option<usize> = None;
+    for wanted in query.chars().flat_map(char::to_lowercase).filter(|c| !c.is_whitespace()) {
+        let found = candidate[position..].iter().position(|c| *c == wanted)? + position;
+        score += 1;
+        if previous.is_some_and(|previous| previous + 1 == found) {
+            score += 5;
+        }
+        if found == 0 || !candidate[found - 1].is_alphanumeric() {
+            score += 3;
+        }
+        // Gaps cost a little so tighter matches rank first
+        score -= (found - position) as i64 / 4;
+        previous = Some(found);
+        position = found + 1;
+    }
+    Some(score)
+}
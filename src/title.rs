@@ -0,0 +1,97 @@
+use std::io::{self, Write};
+
+use crate::{escapes_supported, App, AppScreen};
+
+/// The title last written to the terminal, so it is only rewritten when it changes.
+#[derive(Debug, Default)]
+pub struct WindowTitle {
+    current: Option<String>,
+}
+
+impl WindowTitle {
+    fn set(&mut self, title: String) -> io::Result<()> {
+        if !escapes_supported() {
+            return Ok(());
+        }
+        let mut stdout = io::stdout();
+        if self.current.is_none() {
+            // Save the terminal's own title for `restore`
+            write!(stdout, "\x1b[22;0t")?;
+        }
+        write!(stdout, "\x1b]2;{}\x07", title)?;
+        if inside_multiplexer() {
+            // The window name shown in the tmux/screen status line
+            write!(stdout, "\x1bk{}\x1b\\", title)?;
+        }
+        stdout.flush()?;
+        self.current = Some(title);
+        Ok(())
+    }
+
+    fn restore(&mut self) -> io::Result<()> {
+        if self.current.take().is_none() {
+            return Ok(());
+        }
+        let mut stdout = io::stdout();
+        if inside_multiplexer() {
+            // An empty name hands the window back to tmux's automatic naming
+            write!(stdout, "\x1bk\x1b\\")?;
+        }
+        write!(stdout, "\x1b[23;0t")?;
+        stdout.flush()
+    }
+}
+
+fn inside_multiplexer() -> bool {
+    std::env::var_os("TMUX").is_some()
+        || std::env::var_os("STY").is_some()
+        || std::env::var("TERM").is_ok_and(|term| term.starts_with("screen") || term.starts_with("tmux"))
+}
+
+impl App {
+    /// "mls: <active group> (<unread>, <mentions>@)", counting unread messages in every account.
+    /// The group name is left out while the session is locked.
+    fn window_title(&self) -> String {
+        let groups = self.groups.values().chain(self.accounts.iter().flat_map(|account| account.groups.values()));
+        let (unread, mentions) = groups.fold((0, 0), |(unread, mentions), group| {
+            (unread + group.unread, mentions + group.unread_mentions)
+        });
+        let mut title = "mls".to_string();
+        if matches!(self.screen, AppScreen::Locked) {
+            title.push_str(": locked");
+        } else if let Some(group) = self.active_group.as_ref().and_then(|id| self.groups.get(id)) {
+            // Group names come from other members; keep escape sequences out of the title
+            title.push_str(": ");
+            title.extend(group.name.chars().filter(|c| !c.is_control()));
+        }
+        match (unread, mentions) {
+            (0, _) => {}
+            (unread, 0) => title.push_str(&format!(" ({})", unread)),
+            (unread, mentions) => title.push_str(&format!(" ({}, {}@)", unread, mentions)),
+        }
+        title
+    }
+
+    /// Write the window title if it changed since the last call, or put the terminal's own
+    /// title back once `terminal_title` is switched off.
+    pub fn update_terminal_title(&mut self) {
+        let result = if self.config.terminal_title {
+            let title = self.window_title();
+            if self.window_title.current.as_ref() == Some(&title) {
+                return;
+            }
+            self.window_title.set(title)
+        } else {
+            self.window_title.restore()
+        };
+        if let Err(e) = result {
+            self.set_error(format!("Failed to set terminal title: {}", e));
+        }
+    }
+
+    /// Put back the title the terminal had before the client set it, on exit.
+    pub fn restore_terminal_title(&mut self) {
+        // Best effort: the terminal is being torn down
+        let _ = self.window_title.restore();
+    }
+}
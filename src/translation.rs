@@ -0,0 +1,96 @@
+use anyhow::Result;
+use mls_client_core::errors::TranslateError;
+use mls_client_core::translate;
+
+use crate::{App, AppEvent};
+
+impl App {
+    /// `translate <n>` (1 is the most recent message) or `translate auto [on|off]`.
+    pub(crate) async fn translate_command(&mut self, parts: &[&str]) -> Result<()> {
+        let Some(group_id) = self.active_group.clone() else {
+            self.set_warning("No active group selected");
+            return Ok(());
+        };
+        if self.config.translation.is_none() {
+            self.set_warning(TranslateError::NotConfigured.to_string());
+            return Ok(());
+        }
+        match parts {
+            ["auto"] => {
+                let enabled = self.auto_translates(&group_id);
+                self.set_status(format!("Automatic translation is {} for this group", if enabled { "on" } else { "off" }));
+            }
+            ["auto", toggle @ ("on" | "off")] => {
+                let enable = *toggle == "on";
+                if let Some(translation) = self.config.translation.as_mut() {
+                    translation.auto_groups.retain(|id| *id != group_id);
+                    if enable {
+                        translation.auto_groups.push(group_id);
+                    }
+                }
+                self.save_config().await?;
+                self.set_status(format!("Automatic translation {} for this group", if enable { "on" } else { "off" }));
+            }
+            [n] if n.parse::<usize>().is_ok_and(|n| n > 0) => {
+                let n: usize = n.parse()?;
+                let Some(group) = self.groups.get(&group_id) else {
+                    return Ok(());
+                };
+                let Some(message) = group.messages.len().checked_sub(n).and_then(|index| group.messages.get(index)) else {
+                    self.set_status(format!("{} has only {} message(s)", group.name, group.messages.len()));
+                    return Ok(());
+                };
+                if message.redacted_by.is_some() {
+                    self.set_status("That message was redacted");
+                    return Ok(());
+                }
+                let (message_id, text) = (message.id.clone(), message.content.clone());
+                self.request_translation(&group_id, message_id, text);
+                self.set_status("Translating…");
+            }
+            _ => self.set_warning("Usage: translate <n> | translate auto [on|off]"),
+        }
+        Ok(())
+    }
+
+    /// Whether incoming messages in `group_id` are translated as they arrive.
+    pub(crate) fn auto_translates(&self, group_id: &str) -> bool {
+        self.config.translation.iter().any(|translation| translation.auto_groups.iter().any(|id| id == group_id))
+    }
+
+    /// Run the translator on a spawned task; `poll_events` stores the result on the message.
+    pub(crate) fn request_translation(&mut self, group_id: &str, message_id: String, text: String) {
+        let Some(config) = self.config.translation.clone() else {
+            return;
+        };
+        let events = self.events_tx.clone();
+        let group_id = group_id.to_string();
+        tokio::spawn(async move {
+            let translation = translate::translate(&config, &text).await;
+            let _ = events.send(AppEvent::Translated { group_id, message_id, translation });
+        });
+    }
+
+    pub(crate) async fn translation_finished(&mut self, group_id: &str, message_id: &str, translation: Result<String, TranslateError>) {
+        let translation = match translation {
+            Ok(translation) => translation,
+            Err(e) => {
+                self.set_error(format!("Translation failed: {}", e));
+                return;
+            }
+        };
+        let Some(message) = self.groups.get_mut(group_id).and_then(|group| group.messages.iter_mut().find(|message| message.id == message_id)) else {
+            return;
+        };
+        // Redacted while the translator ran
+        if message.redacted_by.is_some() {
+            return;
+        }
+        // An identical translation is the message already being in the target language
+        if translation == message.content {
+            return;
+        }
+        message.translation = Some(translation);
+        self.save_history().await;
+    }
+}
@@ -0,0 +1,280 @@
+use anyhow::Result;
+use chrono::Local;
+use mls_client_core::errors;
+use mls_client_core::transport::{self, InsecureEndpoint, KeyStatus, PinnedKey, TransportStatus};
+use ratatui::style::Color;
+
+use crate::{App, AppScreen, Popup, PopupAction};
+
+/// Result of checking one connection's identity key, kept until the client reconnects.
+#[derive(Debug)]
+pub struct ServerTrust {
+    endpoint: String,
+    /// Challenge of the greeting checked, unique per connection
+    challenge: Option<String>,
+    status: KeyStatus,
+}
+
+impl App {
+    /// How the delivery-service connection is protected, `None` while disconnected.
+    pub(crate) fn transport_status(&self) -> Option<TransportStatus> {
+        if !self.network_client.is_connected() {
+            return None;
+        }
+        Some(transport::assess(
+            &self.crypto,
+            &self.config.insecure_endpoints,
+            self.network_client.endpoint(),
+            self.network_client.server_capabilities(),
+        ))
+    }
+
+    /// Warning shown above the dashboard and the timeline for a plaintext connection: red
+    /// until the endpoint is accepted, yellow after.
+    pub(crate) fn transport_banner(&self) -> Option<(String, Color)> {
+        let endpoint = self.network_client.endpoint();
+        let banner = match self.transport_status()? {
+            TransportStatus::Loopback => return None,
+            TransportStatus::Accepted => {
+                let accepted_at = self.config.insecure_endpoints.get(endpoint).map(|known| known.accepted_at.format("%Y-%m-%d").to_string());
+                return Some((
+                    format!(
+                        "Plaintext connection to {} (accepted {}): identities, group IDs and message sizes are visible on the network.",
+                        endpoint,
+                        accepted_at.unwrap_or_default()
+                    ),
+                    Color::Yellow,
+                ));
+            }
+            TransportStatus::Unaccepted => format!(
+                "Connected to {} without TLS: identities, group IDs and message sizes are visible on the network. Posting is blocked until you run 'accept-insecure'.",
+                endpoint
+            ),
+            TransportStatus::Downgraded { lost } => format!(
+                "{} no longer advertises {} since you accepted it. Posting is blocked until you review it with 'accept-insecure'.",
+                endpoint,
+                lost.join(", ")
+            ),
+            TransportStatus::Changed => format!(
+                "{} advertises different capabilities than when you accepted it. Posting is blocked until you review it with 'accept-insecure'.",
+                endpoint
+            ),
+        };
+        Some((banner, Color::Red))
+    }
+
+    /// `accept-insecure` shows the connection and its fingerprint for confirmation; `yes`
+    /// accepts it without the popup, `forget <endpoint>` drops an earlier acceptance.
+    pub(crate) async fn accept_insecure_command(&mut self, parts: &[&str]) -> Result<()> {
+        if let ["forget", endpoint] = parts {
+            let status = match self.config.insecure_endpoints.remove(*endpoint) {
+                Some(_) => {
+                    self.save_config().await?;
+                    format!("Forgot the acceptance of {}; connecting to it needs 'accept-insecure' again", endpoint)
+                }
+                None => format!("{} was never accepted", endpoint),
+            };
+            self.set_status(status);
+            return Ok(());
+        }
+        let Some(status) = self.transport_status() else {
+            self.report_error(&errors::NOT_CONNECTED, "there is no connection to accept");
+            return Ok(());
+        };
+        let endpoint = self.network_client.endpoint().to_string();
+        let capabilities = self.network_client.server_capabilities();
+        let fingerprint = transport::fingerprint(&self.crypto, &endpoint, capabilities);
+        match parts {
+            [] => {}
+            ["yes"] => return self.accept_insecure(&endpoint, &fingerprint).await,
+            _ => {
+                self.set_warning("Usage: accept-insecure [yes] | accept-insecure forget <endpoint>");
+                return Ok(());
+            }
+        }
+
+        let mut lines = vec![
+            format!("Endpoint: {}", endpoint),
+            "Transport: plain TCP, no TLS".to_string(),
+            format!(
+                "Capabilities: {}",
+                capabilities.map_or_else(|| "not advertised".to_string(), |capabilities| capabilities.join(", "))
+            ),
+            format!("Fingerprint: {}", fingerprint),
+            String::new(),
+        ];
+        if let Some(known) = self.config.insecure_endpoints.get(&endpoint) {
+            lines.push(format!("Accepted on {} with fingerprint {}", known.accepted_at.format("%Y-%m-%d %H:%M"), known.fingerprint));
+            if let Some(previous) = &known.capabilities {
+                lines.push(format!("Capabilities then: {}", previous.join(", ")));
+            }
+        }
+        let actions = match &status {
+            TransportStatus::Loopback => {
+                lines.push("Loopback address: nothing leaves this machine, so no acceptance is needed.".to_string());
+                Vec::new()
+            }
+            TransportStatus::Accepted => {
+                lines.push("Accepted; 'accept-insecure forget <endpoint>' withdraws it.".to_string());
+                Vec::new()
+            }
+            TransportStatus::Unaccepted | TransportStatus::Downgraded { .. } | TransportStatus::Changed => {
+                if let TransportStatus::Downgraded { lost } = &status {
+                    lines.push(format!("WARNING: the server stopped advertising {}.", lost.join(", ")));
+                }
+                lines.push("MLS keeps message contents confidential, but anyone on the path sees who talks in which group, when, and how much.".to_string());
+                lines.push("Only accept if you trust this network or the fingerprint matches what the server operator published.".to_string());
+                lines.push("'accept-insecure yes' accepts it from the command line.".to_string());
+                vec![PopupAction::AcceptInsecure { endpoint, fingerprint }]
+            }
+        };
+        self.popup = Some(Popup { title: "Plaintext connection".to_string(), lines, actions });
+        self.screen = AppScreen::Popup;
+        Ok(())
+    }
+
+    /// Check the identity key of a new connection against its pin: pin it on first use, and
+    /// interrupt with a warning when it changed.
+    pub(crate) async fn refresh_server_trust(&mut self) {
+        let endpoint = self.network_client.endpoint().to_string();
+        let key = self.network_client.server_key();
+        let challenge = key.map(|key| key.challenge.clone());
+        if self.server_trust.as_ref().is_some_and(|trust| trust.endpoint == endpoint && trust.challenge == challenge) {
+            return;
+        }
+        let mut status = transport::check_server_key(&self.crypto, self.config.pinned_server_keys.get(&endpoint), key);
+        if let KeyStatus::FirstUse { fingerprint } = &status {
+            let pinned = PinnedKey { fingerprint: fingerprint.clone(), pinned_at: Local::now() };
+            self.config.pinned_server_keys.insert(endpoint.clone(), pinned);
+            match self.save_config().await {
+                Ok(()) => self.record_event(format!("Pinned the identity key of {}: {}", endpoint, fingerprint)),
+                Err(e) => self.record_event(format!("Failed to save the pinned identity key of {}: {}", endpoint, e)),
+            }
+            status = KeyStatus::Matches;
+        }
+        let alarm = status.is_alarm();
+        self.server_trust = Some(ServerTrust { endpoint, challenge, status });
+        if alarm {
+            if let Some(banner) = self.trust_banner() {
+                self.set_status(banner.clone());
+                self.record_event(banner);
+            }
+            self.show_trust();
+        }
+    }
+
+    /// Whether the current connection failed its identity key check.
+    pub(crate) fn server_key_alarm(&self) -> bool {
+        self.network_client.is_connected() && self.server_trust.as_ref().is_some_and(|trust| trust.status.is_alarm())
+    }
+
+    /// Warning shown above the dashboard and the timeline while the identity key check fails.
+    pub(crate) fn trust_banner(&self) -> Option<String> {
+        if !self.server_key_alarm() {
+            return None;
+        }
+        let trust = self.server_trust.as_ref()?;
+        let problem = match &trust.status {
+            KeyStatus::Changed { .. } => "presented a different identity key than the one pinned",
+            KeyStatus::BadProof { .. } => "could not prove its identity key",
+            KeyStatus::Withdrawn { .. } => "no longer presents the identity key pinned for it",
+            _ => return None,
+        };
+        Some(format!(
+            "{} {}: someone may be intercepting the connection. Posting is blocked; 'trust' shows the details.",
+            trust.endpoint, problem
+        ))
+    }
+
+    /// `trust` shows the pinned and presented keys; `trust reset <server>` drops the pin so
+    /// the key presented next is pinned instead.
+    pub(crate) async fn trust_command(&mut self, parts: &[&str]) -> Result<()> {
+        match parts {
+            [] => self.show_trust(),
+            ["reset", server] => {
+                let Some(pinned) = self.config.pinned_server_keys.remove(*server) else {
+                    self.set_warning(format!("No identity key is pinned for {}", server));
+                    return Ok(());
+                };
+                self.save_config().await?;
+                if self.server_trust.as_ref().is_some_and(|trust| trust.endpoint == *server) {
+                    self.server_trust = None;
+                    if self.network_client.is_connected() {
+                        self.refresh_server_trust().await;
+                    }
+                }
+                self.set_status(format!("Removed the pinned identity key {} of {}", pinned.fingerprint, server));
+                let event = self.status_text().to_string();
+                self.record_event(event);
+            }
+            _ => self.set_warning("Usage: trust [reset <server>]"),
+        }
+        Ok(())
+    }
+
+    /// The identity key check of the current connection, with what to do about a failure.
+    fn show_trust(&mut self) {
+        let Some(trust) = &self.server_trust else {
+            self.report_error(&errors::NOT_CONNECTED, "no delivery service identity key was checked");
+            return;
+        };
+        let endpoint = trust.endpoint.clone();
+        let mut lines = vec![format!("Server: {}", endpoint)];
+        if let Some(pinned) = self.config.pinned_server_keys.get(&endpoint) {
+            lines.push(format!("Pinned on {}: {}", pinned.pinned_at.format("%Y-%m-%d %H:%M"), pinned.fingerprint));
+        }
+        lines.push(String::new());
+        let title = match &trust.status {
+            KeyStatus::NotOffered => {
+                lines.push("The server presents no identity key, so there is nothing to pin.".to_string());
+                "Server identity"
+            }
+            KeyStatus::FirstUse { .. } | KeyStatus::Matches => {
+                lines.push("The server proved the pinned identity key.".to_string());
+                "Server identity"
+            }
+            KeyStatus::Changed { presented, .. } => {
+                lines.push(format!("WARNING: the server proved a DIFFERENT identity key: {}", presented));
+                "SERVER IDENTITY KEY CHANGED"
+            }
+            KeyStatus::BadProof { presented } => {
+                lines.push(format!("WARNING: the server presented {} but its signature over this session is invalid.", presented));
+                "SERVER IDENTITY NOT PROVEN"
+            }
+            KeyStatus::Withdrawn { .. } => {
+                lines.push("WARNING: the server presented no identity key at all.".to_string());
+                "SERVER IDENTITY KEY MISSING"
+            }
+        };
+        if trust.status.is_alarm() {
+            lines.push(String::new());
+            lines.push("Someone may be intercepting the connection, or the operator rotated the key. Posting is blocked.".to_string());
+            lines.push(format!(
+                "Confirm the new fingerprint with the operator out-of-band, then run 'trust reset {}' to pin it.",
+                endpoint
+            ));
+        }
+        self.show_popup(title, lines);
+    }
+
+    /// Remember `endpoint` as accepted, unless its fingerprint changed since it was shown.
+    pub(crate) async fn accept_insecure(&mut self, endpoint: &str, fingerprint: &str) -> Result<()> {
+        let capabilities = self.network_client.server_capabilities();
+        let current = transport::fingerprint(&self.crypto, self.network_client.endpoint(), capabilities);
+        if self.network_client.endpoint() != endpoint || current != fingerprint {
+            self.set_status(format!("The connection to {} changed since it was shown; run 'accept-insecure' again", endpoint));
+            return Ok(());
+        }
+        let accepted = InsecureEndpoint {
+            fingerprint: fingerprint.to_string(),
+            capabilities: capabilities.map(<[String]>::to_vec),
+            accepted_at: Local::now(),
+        };
+        self.config.insecure_endpoints.insert(endpoint.to_string(), accepted);
+        self.save_config().await?;
+        self.set_status(format!("Accepted the plaintext connection to {} (fingerprint {})", endpoint, &fingerprint[..fingerprint.len().min(16)]));
+        let event = self.status_text().to_string();
+        self.record_event(event);
+        Ok(())
+    }
+}
@@ -0,0 +1,112 @@
+use std::time::Instant;
+
+use chrono::Local;
+use mls_client_core::errors;
+
+use crate::{App, InputMode, RetryOp};
+
+/// A sent message still inside its undo window: nothing has been encrypted yet.
+pub struct DelayedSend {
+    pub group_id: String,
+    pub message_id: String,
+    pub message: String,
+    pub due: Instant,
+}
+
+impl App {
+    /// Whether a message for `group_id` is still waiting, so later ones queue behind it.
+    pub(crate) fn sends_waiting(&self, group_id: &str) -> bool {
+        self.delayed_sends.iter().any(|send| send.group_id == group_id)
+    }
+
+    /// Whole seconds left before `message_id` goes out, while it can still be undone.
+    pub(crate) fn undo_countdown(&self, message_id: &str) -> Option<u64> {
+        let send = self.delayed_sends.iter().find(|send| send.message_id == message_id)?;
+        let left = send.due.saturating_duration_since(Instant::now());
+        Some(left.as_millis().div_ceil(1000) as u64)
+    }
+
+    /// Ctrl+Z: cancel the most recent message still in its undo window, putting its text
+    /// back in an empty composer.
+    pub(crate) async fn undo_send(&mut self) {
+        let Some(send) = self.delayed_sends.pop() else {
+            self.set_status("Nothing to undo: no message is waiting to be sent");
+            return;
+        };
+        let name = match self.groups.get_mut(&send.group_id) {
+            Some(group) => {
+                group.messages.retain(|message| message.id != send.message_id);
+                group.name.clone()
+            }
+            None => send.group_id.clone(),
+        };
+        self.save_history().await;
+        if self.input.is_empty() {
+            self.input = send.message;
+            self.input_mode = InputMode::Message;
+            self.set_status(format!("Unsent your message to {}; it is back in the composer", name));
+        } else {
+            self.set_status(format!("Unsent your message to {}", name));
+        }
+    }
+
+    /// Encrypt and transmit the messages whose undo window has closed, in the order they were
+    /// sent. One held by the encryption preview keeps the rest of its group waiting.
+    pub(crate) async fn flush_delayed_sends(&mut self) {
+        let now = Instant::now();
+        let mut waiting: Vec<DelayedSend> = Vec::new();
+        for send in std::mem::take(&mut self.delayed_sends) {
+            let blocked = send.due > now
+                || self.held_messages.contains_key(&send.group_id)
+                || waiting.iter().any(|earlier| earlier.group_id == send.group_id);
+            if blocked {
+                waiting.push(send);
+            } else {
+                self.transmit_delayed(send).await;
+            }
+        }
+        waiting.append(&mut self.delayed_sends);
+        self.delayed_sends = waiting;
+    }
+
+    /// On exit, send what is still in its undo window rather than lose it.
+    pub(crate) async fn send_delayed_now(&mut self) {
+        let now = Instant::now();
+        for send in &mut self.delayed_sends {
+            send.due = now;
+        }
+        self.flush_delayed_sends().await;
+    }
+
+    async fn transmit_delayed(&mut self, send: DelayedSend) {
+        let sent = if !self.network_client.is_connected() {
+            self.report_error(&errors::NOT_CONNECTED, "the message was not sent");
+            None
+        } else {
+            match self.transmit_application(&send.group_id, &send.message_id, &send.message).await {
+                Ok(echoed) => Some(echoed),
+                Err(e) => {
+                    self.report_error(&errors::SEND_FAILED, e.to_string());
+                    None
+                }
+            }
+        };
+        let Some(group) = self.groups.get_mut(&send.group_id) else {
+            return;
+        };
+        match sent {
+            Some(echoed) => {
+                // The echo timeout runs from now, not from when the message was typed
+                if let Some(message) = group.messages.iter_mut().find(|message| message.id == send.message_id) {
+                    message.pending = echoed;
+                    message.timestamp = Local::now();
+                }
+            }
+            None => {
+                group.messages.retain(|message| message.id != send.message_id);
+                self.last_failed = Some(RetryOp::Send { group_id: send.group_id, message_id: send.message_id, message: send.message });
+            }
+        }
+        self.save_history().await;
+    }
+}